@@ -0,0 +1,67 @@
+#![no_main]
+// Arbitrary decks and action sequences must never panic, break the engine invariants,
+// or leave a hand that check/call play cannot finish.
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use pokers::invariants::check_invariants;
+use pokers::state::action::{Action, ActionEnum};
+use pokers::state::card::Card;
+use pokers::state::State;
+
+#[derive(Debug, Arbitrary)]
+struct Input {
+    n_players: u8,
+    button: u8,
+    sb: u8,
+    bb_mult: u8,
+    stake_mult: u16,
+    deck_order: Vec<u8>,
+    actions: Vec<(u8, u16)>,
+}
+
+fuzz_target!(|input: Input| {
+    let n_players = 2 + (input.n_players % 9) as u64;
+    let button = input.button as u64 % n_players;
+    let sb = 0.5 + input.sb as f64 / 4.0;
+    let bb = sb * (1 + input.bb_mult % 4) as f64;
+    let stake = bb * (1 + input.stake_mult % 1000) as f64;
+
+    // Byte-driven permutation of the full deck, so there are never duplicate cards
+    let mut remaining = Card::collect();
+    let mut deck = Vec::with_capacity(remaining.len());
+    for &b in &input.deck_order {
+        if remaining.is_empty() {
+            break;
+        }
+        deck.push(remaining.remove(b as usize % remaining.len()));
+    }
+    deck.extend(remaining);
+
+    let mut state = State::from_deck(n_players, button, sb, bb, stake, deck, false, 0).unwrap();
+    assert_eq!(check_invariants(&state), Vec::<String>::new());
+
+    for &(kind, amount) in input.actions.iter().take(200) {
+        if state.final_state {
+            break;
+        }
+        let action = match kind % 3 {
+            0 => ActionEnum::Fold,
+            1 => ActionEnum::CheckCall,
+            _ => ActionEnum::BetRaise,
+        };
+        state = state.apply_action(Action::new(action, amount as f64 * bb / 4.0));
+        assert_eq!(check_invariants(&state), Vec::<String>::new(), "{:?}", state);
+    }
+
+    // Termination: once everybody just checks or calls, the hand must end within
+    // one orbit per remaining street
+    let max_actions = 4 * (n_players as usize + 1);
+    for _ in 0..max_actions {
+        if state.final_state {
+            break;
+        }
+        state = state.apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+        assert_eq!(check_invariants(&state), Vec::<String>::new(), "{:?}", state);
+    }
+    assert!(state.final_state, "hand did not terminate: {:?}", state);
+});
@@ -0,0 +1,22 @@
+#![no_main]
+// Any 7 distinct cards must evaluate without panicking, to a valid rank that does not
+// depend on the order the cards are given in.
+use libfuzzer_sys::fuzz_target;
+use pokers::game_logic::rank_hand;
+use pokers::state::card::Card;
+
+fuzz_target!(|data: [u8; 7]| {
+    let mut remaining = Card::collect();
+    let cards: Vec<Card> = data
+        .iter()
+        .map(|&b| remaining.remove(b as usize % remaining.len()))
+        .collect();
+
+    let rank = rank_hand((cards[0], cards[1]), &cards[2..]);
+    assert!((1..=10).contains(&rank.0), "invalid rank {:?}", rank);
+
+    let mut reversed = cards.clone();
+    reversed.reverse();
+    let reversed_rank = rank_hand((reversed[0], reversed[1]), &reversed[2..]);
+    assert_eq!(rank, reversed_rank, "rank depends on card order: {:?}", cards);
+});
@@ -1,14 +1,829 @@
-from typing import Optional
+from typing import Awaitable, Callable, Optional
 from enum import Enum
 
 # visualization.rs ------------------------------------------------------------
-def visualize_state(state: State) -> str: ...
-def visualize_trace(trace: list[State]) -> str: ...
+def visualize_state(state: State, locale: Locale = Locale.En, chips: Optional[ChipFormat] = None) -> str: ...
+def visualize_trace(trace: list[State], locale: Locale = Locale.En, chips: Optional[ChipFormat] = None) -> str: ...
+def render_range_grid(range: HandRange | list[float], html: bool = False) -> str: ...
+
+# chip_format.rs --------------------------------------------------------------
+class ChipFormat:
+    symbol: str
+    symbol_after: bool
+    decimals: Optional[int]
+    chip_value: float  # money a chip stands for
+    thousands_separator: str
+    decimal_separator: str
+    denominations: list[float]  # in chips, smallest first
+    def __init__(
+        self,
+        symbol: str = "",
+        decimals: Optional[int] = None,
+        chip_value: float = 1.0,
+        symbol_after: bool = False,
+        thousands_separator: str = "",
+        decimal_separator: str = ".",
+        denominations: list[float] = [1.0, 5.0, 25.0, 100.0, 500.0, 1000.0, 5000.0, 25000.0],
+    ) -> None: ...
+    def format(self, chips: float) -> str: ...
+    def breakdown(self, chips: float) -> list[tuple[float, int]]: ...  # (denomination, count), largest first
+    def __str__(self) -> str: ...
+
+# messages.rs -----------------------------------------------------------------
+
+class Locale(Enum):
+    En = 0
+    Zh = 1
+
+# range.rs --------------------------------------------------------------------
+
+class HandRange:
+    weights: list[float]  # 169 weights in 13x13 grid order, aces first
+    @staticmethod
+    def from_weights(weights: list[float]) -> HandRange: ...
+    @staticmethod
+    def from_string(string: str) -> HandRange: ...
+    def __str__(self) -> str: ...
+
+def hand_class_index(name: str) -> int: ...
+def hand_class_string(index: int) -> str: ...
+def hand_class_of(hand: tuple[Card, Card]) -> int: ...
+def hand_class_strength(index: int) -> int: ...
+def hand_class_by_strength(rank: int) -> int: ...
+
+# cards.rs --------------------------------------------------------------------
+def all_combos(dead: list[Card] = []) -> list[tuple[Card, Card]]: ...
+def combos_for_range(range: HandRange, dead: list[Card] = []) -> list[tuple[tuple[Card, Card], float]]: ...
+def sample_combos(range: HandRange, n: int, dead: list[Card] = [], seed: int = 0) -> list[tuple[Card, Card]]: ...
+
+# heatmap.rs ------------------------------------------------------------------
+
+class HeatmapTable:
+    position: int
+    stack_bb: float
+    frequencies: list[list[float]]  # 169 rows in grid order, one frequency per action
+
+class StrategyHeatmap:
+    n_players: int
+    action_names: list[str]
+    tables: list[HeatmapTable]
+    def to_json(self) -> str: ...
+    def to_csv(self) -> str: ...
+    def to_html(self) -> str: ...
+    def __str__(self) -> str: ...
+
+def strategy_heatmap(
+    policy: Callable[[State], list[float]],
+    n_players: int,
+    positions: list[int],
+    stacks_bb: list[float],
+    sb: float = 0.5,
+    bb: float = 1.0,
+    action_names: Optional[list[str]] = None,
+) -> StrategyHeatmap: ...
+
+# matchup.rs ------------------------------------------------------------------
+
+class PositionResult:
+    position: str  # "small_blind" or "big_blind"
+    hands: int
+    win_rate: float  # bb/100 of agent A
+
+class StreetAggression:
+    agent: str  # "a" or "b"
+    stage: Stage
+    decisions: int
+    bets_raises: int
+    calls: int
+    checks: int
+    folds: int
+    aggression_frequency: float
+    aggression_factor: Optional[float]
+    def __str__(self) -> str: ...
+
+class MatchupReport:
+    hands: int
+    duplicate: bool
+    seed: int
+    win_rate: float  # bb/100 of agent A
+    ci_low: float
+    ci_high: float
+    deals_won: int
+    deals_lost: int
+    positions: list[PositionResult]
+    aggression: list[StreetAggression]
+    def to_json(self) -> str: ...
+    def __str__(self) -> str: ...
+
+def evaluate_matchup(
+    agent_a: Callable[[State, int], Action],
+    agent_b: Callable[[State, int], Action],
+    n_hands: int,
+    seed: int = 0,
+    duplicate: bool = True,
+    stack_bb: float = 100.0,
+) -> MatchupReport: ...
+
+class LeagueTable:
+    names: list[str]
+    cross_table: list[list[float]]  # bb/100 of the row agent against the column agent
+    ratings: list[float]
+    matchups: list[MatchupReport]
+    def standings(self) -> list[tuple[str, float]]: ...
+    def to_json(self) -> str: ...
+    def __str__(self) -> str: ...
+
+def round_robin(
+    agents: list[Callable[[State, int], Action]],
+    n_hands: int,
+    seed: int = 0,
+    stack_bb: float = 100.0,
+    names: Optional[list[str]] = None,
+    threads: Optional[int] = None,
+) -> Job: ...  # result: LeagueTable
+
+# contributions.rs ------------------------------------------------------------
+
+class StreetAction:
+    player: int
+    action: ActionEnum
+    amount: float  # chips added to the pot by this action
+    pot_before: float
+    pot_after: float
+    def __str__(self) -> str: ...
+
+class StreetContributions:
+    stage: Stage
+    pot_at_start: float  # pot carried over from the previous streets
+    contributions: list[float]  # per player, blinds, straddles and dead blinds included
+    actions: list[StreetAction]
+    def __str__(self) -> str: ...
+
+class StreetSummary:
+    stage: Stage
+    contributions: list[float]  # per player, blinds, antes and uncalled bets included
+    total: float
+    pot: float  # once the street's bets are swept in
+    def __str__(self) -> str: ...
+
+# explain.rs ------------------------------------------------------------------
+class ActionExplanation:
+    legal: bool  # the engine applies the action as given
+    reason: str
+    substitute: Optional[Action]  # applied instead, None when the action is ignored
+    def __str__(self) -> str: ...
+
+# hand_codec.rs ---------------------------------------------------------------
+def compress_hands(states: list[State], n_bet_buckets: int = 10) -> bytes: ...
+def decompress_hands(data: bytes) -> list[State]: ...
+
+# datasets.rs -----------------------------------------------------------------
+def load_hand_histories(path: str) -> HandHistoryDataset: ...
+def observation(state: State, player: int) -> list[float]: ...
+def complete_observation(
+    obs: list[float], seed: int, constraints: Optional[dict[int, HandRange]] = None
+) -> State: ...
+
+class DecisionSample:
+    hand_id: str
+    player: str
+    stage: Stage
+    observation: list[float]
+    action: Action  # amounts in big blinds
+    coercion: Optional[Coercion]
+    reward: float  # net result of the hand in big blinds
+    def __str__(self) -> str: ...
+
+class HandHistoryDataset:
+    samples: list[DecisionSample]
+    hands: int
+    skipped: int
+    def to_parquet(self, path: str) -> None: ...
+    def __len__(self) -> int: ...
+    def __str__(self) -> str: ...
+
+# evaluator.rs ----------------------------------------------------------------
+def compare_hands(
+    board: list[Card], hand_a: tuple[Card, Card], hand_b: tuple[Card, Card], variant: GameVariant = GameVariant.Holdem
+) -> int: ...
+def best_hand(
+    board: list[Card], hands: list[tuple[Card, Card]], variant: GameVariant = GameVariant.Holdem
+) -> list[int]: ...
+
+# game_logic.rs ---------------------------------------------------------------
+def missed_blind_seating(
+    owing: list[bool], button: int, policy: MissedBlinds
+) -> tuple[list[int], list[int]]: ...  # players dealt in, players posting a dead blind
+
+# insurance.rs ----------------------------------------------------------------
+
+class InsuranceQuote:
+    player: int
+    stage: Stage  # street on which the money went in
+    equity: float
+    scoop_probability: float
+    outs: int
+    payout_ratio: float  # paid per chip of premium if the player does not scoop
+    max_payout: float
+    def max_premium(self) -> float: ...
+    def __str__(self) -> str: ...
+
+# state/schema.rs -------------------------------------------------------------
+SCHEMA_VERSION: int
+def migrate_state(json: str) -> str: ...
+
+# outcomes.rs -----------------------------------------------------------------
+
+class OutcomeLabel(Enum):
+    BadBeat = 0
+    Cooler = 1
+    Suckout = 2
+
+class OutcomeCriteria:
+    bad_beat_equity: Optional[float]  # loser's equity on some street before the river
+    bad_beat_min_hand: Optional[HandCategory]  # weakest losing hand of a bad beat
+    cooler_min_hand: HandCategory  # weakest losing hand of a cooler
+    suckout_equity: float  # winner's equity on the turn
+    def __init__(
+        self,
+        bad_beat_equity: Optional[float] = 0.8,
+        bad_beat_min_hand: Optional[HandCategory] = None,
+        cooler_min_hand: HandCategory = HandCategory.Flush,
+        suckout_equity: float = 0.5,
+    ) -> None: ...
+    def __str__(self) -> str: ...
+
+class HandOutcome:
+    labels: list[OutcomeLabel]
+    winner: int
+    loser: int  # player with the best losing hand
+    winning_hand: HandCategory
+    losing_hand: HandCategory
+    equities: list[AllInEquity]  # preflop, flop and turn
+    def has(self, label: OutcomeLabel) -> bool: ...
+    def __str__(self) -> str: ...
+
+def classify_hand_outcome(state: State, criteria: Optional[OutcomeCriteria] = None) -> Optional[HandOutcome]: ...
 
 # parallel.rs -----------------------------------------------------------------
 def parallel_apply_action(
-    states: list[State], actions: list[Action]
+    states: list[State], actions: list[Action], locks: Optional[list[NodeLock]] = None
 ) -> list[State]: ...
+def batch_evaluate(
+    hands: object,  # (n, 2) numpy array of card indices or list of rows
+    boards: object,  # (n, 3 to 5)
+    variant: GameVariant = GameVariant.Holdem,
+) -> list[int]: ...
+
+class Job:
+    def progress(self) -> float: ...
+    def cancel(self) -> None: ...
+    def cancelled(self) -> bool: ...
+    def done(self) -> bool: ...
+    def result(self, timeout: Optional[float] = None) -> object: ...
+
+def equity_matrix(
+    board: list[Card] = [],
+    samples: int = 1000,
+    seed: int = 0,
+    threads: Optional[int] = None,
+    variant: GameVariant = GameVariant.Holdem,
+) -> Job: ...  # result: 169 x 169 list of equities
+
+# search_context.rs -----------------------------------------------------------
+class SearchContext:
+    root: SearchNode
+    deck_size: int
+    def __init__(self, state: State) -> None: ...
+    def contains(self, node: SearchNode) -> bool: ...
+
+class SearchNode:
+    current_player: int
+    stage: Stage
+    pot: float
+    final_state: bool
+    legal_actions: list[ActionEnum]
+    def state(self) -> State: ...
+    def apply_action(self, action: Action) -> SearchNode: ...
+    def rewards(self) -> list[float]: ...
+    def __str__(self) -> str: ...
+
+# profiling.rs ----------------------------------------------------------------
+class PerfCounters:
+    state_clones: int
+    allocations: int  # reallocations included
+    allocated_bytes: int
+    evaluator_calls: int
+    def __str__(self) -> str: ...
+
+# trajectories.rs -------------------------------------------------------------
+class NodeLock:
+    line: str
+    position: Optional[int]  # seats after the button, any player when None
+    strategy: list[tuple[str, float]]  # normalized, sorted by token
+    def __init__(self, line: str, strategy: dict[str, float], position: Optional[int] = None) -> None: ...
+    def matches(self, state: State) -> bool: ...
+    def action(self, state: State) -> Optional[Action]: ...
+    def __str__(self) -> str: ...
+
+class TrajectoryRecorder:  # needs the `parquet` feature
+    rows: int
+    def __init__(self, path: str, row_group_size: int = 65536) -> None: ...
+    def step(
+        self, states: list[State], actions: list[Action], locks: Optional[list[NodeLock]] = None
+    ) -> list[State]: ...
+    def flush(self) -> None: ...
+    def close(self) -> None: ...
+    def __enter__(self) -> TrajectoryRecorder: ...
+    def __exit__(self, exc_type: object, exc_value: object, traceback: object) -> None: ...
+
+# replay_buffer.rs ------------------------------------------------------------
+class ReplayBuffer:  # needs the `replay-buffer` feature
+    capacity: int
+    written: int
+    def __init__(self, path: str, capacity: int) -> None: ...
+    @staticmethod
+    def record_size() -> int: ...
+    def step(
+        self, states: list[State], actions: list[Action], locks: Optional[list[NodeLock]] = None
+    ) -> list[State]: ...
+    def flush(self) -> None: ...
+    def close(self) -> None: ...
+    def __enter__(self) -> ReplayBuffer: ...
+    def __exit__(self, exc_type: object, exc_value: object, traceback: object) -> None: ...
+
+# game_server.rs / serve.rs (need the `websocket` feature) ---------------------
+class BlindLevel:
+    small_blind: float
+    big_blind: float
+    ante: float
+    duration: float  # seconds
+    break_after: float  # seconds of break after the level
+    def __init__(
+        self, small_blind: float, big_blind: float, duration: float, ante: float = 0.0, break_after: float = 0.0
+    ) -> None: ...
+    @staticmethod
+    def parse_schedule(schedule: str) -> list[BlindLevel]: ...
+    def __str__(self) -> str: ...
+
+class AnteStructure(Enum):
+    EveryPlayer = 0
+    BigBlind = 1
+    AnteOnly = 2
+
+class MissedBlinds(Enum):
+    PostDead = 0
+    WaitForBigBlind = 1
+
+class Straddles(Enum):
+    Off = 0
+    Utg = 1
+    Button = 2
+
+class DisconnectProtection(Enum):
+    Off = 0
+    Fold = 1
+    AllIn = 2
+
+class WebhookFormat(Enum):
+    Json = 0
+    Slack = 1
+    Discord = 2
+
+class Webhook:
+    url: str
+    events: list[str]  # handComplete, playerJoined, playerLeft, bigPot; every event when empty
+    format: WebhookFormat
+    def __init__(self, url: str, events: list[str] = [], format: WebhookFormat = WebhookFormat.Json) -> None: ...
+    @staticmethod
+    def parse_list(list: str) -> list[Webhook]: ...
+    def __str__(self) -> str: ...
+
+class Rake:
+    rate: float  # share of the pot, 0.05 for 5%
+    cap: Optional[float]
+    no_flop_no_drop: bool
+    def __init__(self, rate: float, cap: Optional[float] = None, no_flop_no_drop: bool = True) -> None: ...
+    def __str__(self) -> str: ...
+
+class JackpotDrop:
+    name: str
+    amount: float
+    threshold: float  # smallest pot the drop is taken from
+    def __init__(self, name: str, amount: float, threshold: float) -> None: ...
+    @staticmethod
+    def parse_list(list: str) -> list[JackpotDrop]: ...
+    def __str__(self) -> str: ...
+
+class BadBeat:
+    jackpot: str
+    min_losing_hand: HandCategory
+    def __init__(self, jackpot: str, min_losing_hand: HandCategory = HandCategory.FourOfAKind) -> None: ...
+    def __str__(self) -> str: ...
+
+class TableRake:
+    hands: int
+    hands_raked: int
+    rake: float
+    rake_by_street: list[float]  # preflop to river
+    jackpots: dict[str, float]  # chips dropped into each jackpot
+    bad_beats: int
+    def __str__(self) -> str: ...
+
+class AccountDeletion:
+    stats: bool
+    hands: int
+    sessions: int  # recorded sessions
+    def __str__(self) -> str: ...
+
+class GameConfig:
+    max_players: int
+    default_stack_size: float
+    small_blind: float
+    big_blind: float
+    ante: float
+    ante_structure: AnteStructure
+    ante_first: bool
+    stats_file: Optional[str]
+    hand_history_dir: Optional[str]
+    session_recording_dir: Optional[str]
+    pseudonymize_after_days: Optional[float]
+    chip_format: Optional[ChipFormat]
+    admin_token: Optional[str]
+    rules: TableRules
+    insurance: bool
+    locale: Locale
+    action_timeout: Optional[float]
+    session_seed: Optional[int]
+    missed_blinds: MissedBlinds
+    straddles: Straddles
+    max_straddles: int
+    table_id: Optional[str]
+    blind_levels: list[BlindLevel]
+    late_registration_levels: Optional[int]
+    buy_in: float
+    payouts: list[float]  # shares of the prize pool
+    satellite_seats: Optional[int]
+    bounty: float
+    disconnect_protection: DisconnectProtection
+    disconnect_grace: float
+    webhooks: list[Webhook]
+    big_pot_alert: Optional[float]  # big blinds
+    rake: Optional[Rake]
+    jackpot_drops: list[JackpotDrop]
+    bad_beat: Optional[BadBeat]
+    def __init__(
+        self,
+        max_players: int = 6,
+        default_stack_size: float = 1000.0,
+        small_blind: float = 5.0,
+        big_blind: float = 10.0,
+        ante: float = 0.0,
+        ante_structure: AnteStructure = AnteStructure.EveryPlayer,
+        ante_first: bool = False,
+        stats_file: Optional[str] = None,
+        rules: TableRules = TableRules(),
+        insurance: bool = False,
+        locale: Locale = Locale.En,
+        action_timeout: Optional[float] = None,
+        session_seed: Optional[int] = None,
+        missed_blinds: MissedBlinds = MissedBlinds.PostDead,
+        straddles: Straddles = Straddles.Off,
+        max_straddles: int = 1,
+        table_id: Optional[str] = None,
+        blind_levels: list[BlindLevel] = [],
+        late_registration_levels: Optional[int] = None,
+        disconnect_protection: DisconnectProtection = DisconnectProtection.Off,
+        disconnect_grace: float = 30.0,
+        hand_history_dir: Optional[str] = None,
+        webhooks: list[Webhook] = [],
+        big_pot_alert: Optional[float] = None,
+        rake: Optional[Rake] = None,
+        jackpot_drops: list[JackpotDrop] = [],
+        bad_beat: Optional[BadBeat] = None,
+        session_recording_dir: Optional[str] = None,
+        pseudonymize_after_days: Optional[float] = None,
+        chip_format: Optional[ChipFormat] = None,
+        admin_token: Optional[str] = None,
+        buy_in: float = 0.0,
+        payouts: list[float] = [],
+        satellite_seats: Optional[int] = None,
+        bounty: float = 0.0,
+    ) -> None: ...
+    def __str__(self) -> str: ...
+
+class ServerHandle:
+    host: str
+    port: int
+    url: str
+    running: bool
+    def add_bot(
+        self,
+        seat: int,
+        policy: Callable[[State, int], Action],
+        name: Optional[str] = None,
+        think_time: float = 0.0,
+        deadline: Optional[float] = None,
+        fallback: DefaultPolicy = DefaultPolicy.CheckElseFold,
+    ) -> None: ...
+    def add_observer(self, callback: Callable[[StateView], None]) -> None: ...
+    def seat_player(self, seat: int, name: Optional[str] = None) -> None: ...
+    def start_game(self) -> None: ...
+    def act(self, seat: int, action: Action) -> Awaitable[None]: ...
+    def next_event(self) -> Awaitable[dict]: ...
+    def export_hands(self, name: str) -> bytes: ...
+    def export_account(self, name: str) -> bytes: ...
+    def delete_account(self, name: str) -> AccountDeletion: ...
+    def pause_clock(self) -> None: ...
+    def resume_clock(self) -> None: ...
+    def table_rake(self) -> TableRake: ...
+    def stop(self) -> None: ...
+    def __enter__(self) -> ServerHandle: ...
+    def __exit__(self, exc_type: object, exc_value: object, traceback: object) -> None: ...
+
+def serve(config: Optional[GameConfig] = None, host: str = "127.0.0.1", port: int = 0) -> ServerHandle: ...
+
+# seeds.rs --------------------------------------------------------------------
+def hand_seed(session_seed: int, hand_index: int) -> int: ...
+
+# self_play.rs ----------------------------------------------------------------
+class OpponentPool:
+    weights: list[float]
+    def __init__(self) -> None: ...
+    def add_rule(
+        self, policy: DefaultPolicy, weight: float = 1.0, seats: Optional[list[int]] = None
+    ) -> None: ...
+    def add_locks(
+        self,
+        locks: list[NodeLock],
+        weight: float = 1.0,
+        fallback: DefaultPolicy = DefaultPolicy.CheckElseFold,
+        seats: Optional[list[int]] = None,
+    ) -> None: ...
+    def add_hybrid(
+        self, agent: HybridAgent, weight: float = 1.0, seats: Optional[list[int]] = None
+    ) -> None: ...
+    def add_policy(
+        self,
+        policy: Callable[[State, int], Action],
+        weight: float = 1.0,
+        seats: Optional[list[int]] = None,
+    ) -> None: ...
+    def __len__(self) -> int: ...
+    def __str__(self) -> str: ...
+
+class Curriculum:
+    stack_bb: Optional[tuple[float, float]]
+    equal_stacks: bool
+    blinds: list[tuple[float, float]]
+    blind_weights: list[float]
+    random_button: bool
+    def __init__(
+        self,
+        stack_bb: Optional[tuple[float, float]] = None,
+        equal_stacks: bool = False,
+        blinds: list[tuple[float, float]] = [],
+        blind_weights: Optional[list[float]] = None,
+        random_button: bool = False,
+    ) -> None: ...
+    def __str__(self) -> str: ...
+
+class SelfPlayTable:
+    state: State
+    seat: int
+    hand_index: int
+    hero: int
+    seat_agents: list[Optional[int]]
+    hand_over: bool  # the opponents finished the hand before the hero's first decision
+    def __init__(
+        self,
+        n_players: int,
+        sb: float,
+        bb: float,
+        stake: float,
+        seed: int = 0,
+        n_bet_buckets: int = 10,
+        opponents: Optional[OpponentPool] = None,
+        hero: int = 0,
+        curriculum: Optional[Curriculum] = None,
+    ) -> None: ...
+    def observations(self) -> list[list[float]]: ...
+    def legal_action_mask(self) -> list[bool]: ...
+    def step(self, action: int) -> Optional[list[float]]: ...  # rewards in bb when the hand ends
+    def __str__(self) -> str: ...
+
+# spots.rs --------------------------------------------------------------------
+class PotType(Enum):
+    Limped = 0
+    SingleRaised = 1
+    ThreeBet = 2
+
+class FlopTexture(Enum):
+    Rainbow = 0
+    TwoTone = 1
+    Monotone = 2
+    Paired = 3
+
+class SpotSpec:
+    n_players: int
+    hero: int  # seats after the button
+    villain: int
+    pot: PotType
+    stack_bb: float
+    hero_range: Optional[HandRange]
+    villain_range: Optional[HandRange]
+    flop: Optional[FlopTexture]
+    open_bb: float
+    three_bet: float
+    def __init__(
+        self,
+        n_players: int = 6,
+        hero: int = 2,
+        villain: int = 0,
+        pot: PotType = PotType.SingleRaised,
+        stack_bb: float = 100.0,
+        hero_range: Optional[HandRange] = None,
+        villain_range: Optional[HandRange] = None,
+        flop: Optional[FlopTexture] = None,
+        open_bb: float = 2.5,
+        three_bet: float = 3.0,
+    ) -> None: ...
+    def __str__(self) -> str: ...
+
+def generate_spots(spec: SpotSpec, n_spots: int, seed: int = 0) -> list[State]: ...
+
+# solver_strategy.rs ----------------------------------------------------------
+class SolverStrategy:
+    def __init__(self) -> None: ...
+    @staticmethod
+    def load(path: str) -> SolverStrategy: ...  # .csv and .json exports under path
+    def add_csv(self, text: str) -> int: ...
+    def add_json(self, text: str) -> int: ...
+    def add(self, line: str, board: str, hand: str, strategy: dict[str, float]) -> None: ...
+    def strategy_for(
+        self, state: State, hand: Optional[tuple[Card, Card]] = None
+    ) -> Optional[list[tuple[str, float]]]: ...
+    def action_for(
+        self, state: State, hand: Optional[tuple[Card, Card]] = None
+    ) -> Optional[Action]: ...
+    def __call__(self, state: State, player: int) -> Action: ...  # bot policy
+    def __len__(self) -> int: ...  # nodes
+    def __str__(self) -> str: ...
+
+# trainer.rs ------------------------------------------------------------------
+class OptionEv:
+    action: Action
+    ev: float  # in chips, from the hero's stack before acting
+    def __str__(self) -> str: ...
+
+class ActionGrade:
+    action: Action  # as the engine applies it
+    ev: float
+    best: Action
+    best_ev: float
+    ev_loss: float
+    score: float  # 0 for the worst option, 100 for the best
+    equity: float  # against the villain's range
+    options: list[OptionEv]  # best first
+    def __str__(self) -> str: ...
+
+def grade_action(
+    state: State, villain_range: HandRange, action: Action, samples: int = 200, seed: int = 0
+) -> ActionGrade: ...
+
+class Trainer:
+    spec: SpotSpec
+    villain_range: HandRange
+    villain_strategy: Optional[SolverStrategy]
+    samples: int
+    seed: int
+    spots_dealt: int
+    state: Optional[State]  # the spot being played
+    grades: list[ActionGrade]
+    score: Optional[float]  # average, None before the first decision
+    ev_loss: float
+    def __init__(
+        self,
+        spec: SpotSpec,
+        samples: int = 200,
+        seed: int = 0,
+        villain_strategy: Optional[SolverStrategy] = None,
+    ) -> None: ...
+    def next_spot(self) -> State: ...
+    def awaiting_hero(self) -> bool: ...
+    def act(self, action: Action) -> ActionGrade: ...
+    def __str__(self) -> str: ...
+
+# hybrid_agent.rs -------------------------------------------------------------
+class PreflopChart:
+    line: str  # betting line pattern, as for NodeLock
+    position: Optional[int]  # seats after the button, any player when None
+    ranges: list[tuple[str, HandRange]]  # sorted by action token
+    def __init__(
+        self, line: str, ranges: dict[str, HandRange], position: Optional[int] = None
+    ) -> None: ...
+    def __str__(self) -> str: ...
+
+class HybridAgent:
+    charts: list[PreflopChart]
+    solver: Optional[SolverStrategy]
+    fallback: Optional[DefaultPolicy]  # the equity heuristic when None
+    value_equity: float
+    bet_size: float  # fraction of the pot
+    samples: int
+    def __init__(
+        self,
+        charts: list[PreflopChart] = [],
+        solver: Optional[SolverStrategy] = None,
+        fallback: Optional[DefaultPolicy] = None,
+        value_equity: float = 0.65,
+        bet_size: float = 0.66,
+        samples: int = 200,
+    ) -> None: ...
+    def __call__(self, state: State, player: int) -> Action: ...
+    def __str__(self) -> str: ...
+
+# mcts.rs ---------------------------------------------------------------------
+class MctsAgent:
+    simulations: int  # per decision
+    exploration: float  # UCB1 constant, rewards in pots of the decision
+    n_bet_buckets: int  # as for State.legal_action_mask
+    rollout: DefaultPolicy
+    seed: int
+    def __init__(
+        self,
+        simulations: int = 1000,
+        exploration: float = 1.0,
+        n_bet_buckets: int = 4,
+        rollout: DefaultPolicy = DefaultPolicy.CheckCall,
+        seed: int = 0,
+    ) -> None: ...
+    def __call__(self, state: State, player: int) -> Action: ...
+    def search(
+        self, state: State, player: Optional[int] = None
+    ) -> list[tuple[Action, int, float]]: ...  # action, visits, average reward
+    def __str__(self) -> str: ...
+
+# onnx_policy.rs --------------------------------------------------------------
+class OnnxPolicy:  # needs the `onnx` feature
+    path: str
+    n_bet_buckets: int  # the model has 2 + n_bet_buckets logits
+    greedy: bool  # the most likely legal action, or one sampled
+    def __init__(self, path: str, n_bet_buckets: int = 10, greedy: bool = True) -> None: ...
+    def __call__(self, state: State, player: int) -> Action: ...
+    def logits(self, state: State, player: Optional[int] = None) -> list[float]: ...
+    def __str__(self) -> str: ...
+
+# ofc.rs ----------------------------------------------------------------------
+class OfcVariant(Enum):
+    Regular = 0
+    Pineapple = 1
+
+class OfcRow(Enum):
+    Top = 0
+    Middle = 1
+    Bottom = 2
+
+class OfcHand:
+    top: list[Card]
+    middle: list[Card]
+    bottom: list[Card]
+    def is_complete(self) -> bool: ...
+    def fouled(self) -> bool: ...
+    def royalties(self) -> int: ...
+    def __str__(self) -> str: ...
+
+class OfcState:
+    variant: OfcVariant
+    button: int
+    hands: list[OfcHand]
+    fantasyland: list[bool]
+    current_player: int
+    dealt: list[Card]  # the current player's cards to set
+    turns: list[int]
+    final_state: bool
+    scores: list[int]
+    fantasyland_next: list[bool]
+    @staticmethod
+    def from_seed(
+        n_players: int,
+        button: int,
+        seed: int,
+        variant: OfcVariant = OfcVariant.Pineapple,
+        fantasyland: Optional[list[bool]] = None,
+    ) -> OfcState: ...
+    @staticmethod
+    def from_deck(
+        n_players: int,
+        button: int,
+        deck: list[Card],
+        variant: OfcVariant = OfcVariant.Pineapple,
+        fantasyland: Optional[list[bool]] = None,
+    ) -> OfcState: ...
+    def set_cards(self, placements: list[tuple[Card, OfcRow]], discard: Optional[Card] = None) -> OfcState: ...
+    def discards(self, player: int) -> Optional[list[Card]]: ...
+    def cards_remaining(self) -> int: ...
+    def __str__(self) -> str: ...
 
 # state.rs --------------------------------------------------------------------
 
@@ -16,20 +831,42 @@ class State:
     current_player: int
     players_state: list[PlayerState]
     public_cards: list[Card]
+    second_board: list[Card]  # double-board bomb pots only
     stage: Stage
+    street_index: int  # the stage as an integer, 0 to 4
     button: int
     from_action: Optional[ActionRecord]
     legal_actions: list[ActionEnum]
-    deck: list[Card]
+    deck: list[Card]  # raises PermissionError unless full_view
     pot: float
     min_bet: float
     final_state: bool
     status: StateStatus
-    verbose: bool  # New field for verbosity control
+    verbose: bool  # collect the engine decisions in trace
+    engine_diagnostic: Optional[str]  # raises PermissionError unless a full view
+    trace: list[TraceEvent]  # empty unless verbose
+    rules: TableRules
+    clock: Optional[float]
+    all_in_equities: list[AllInEquity]  # with TableRules.report_all_in_equities
+    hand_id: str
+    table_id: Optional[str]
+    session_id: Optional[str]
+    perspective: Optional[int]
+    full_view: bool
+    range_buckets: Optional[int]
+    discard_on: Optional[Stage]
+    owed_blinds: list[BlindObligation]
 
     @staticmethod
     def from_seed(
-        n_players: int, button: int, sb: float, bb: float, stake: float, seed: int, verbose: bool = False
+        n_players: int,
+        button: int,
+        sb: float,
+        bb: float,
+        stake: float,
+        seed: int,
+        verbose: bool = False,
+        ante: float = 0.0,
     ) -> State: ...
     @staticmethod
     def from_deck(
@@ -40,18 +877,193 @@ class State:
         stake: float,
         deck: list[Card],
         verbose: bool = False,
+        seed: int = 0,
+        ante: float = 0.0,
+    ) -> State: ...
+    @staticmethod
+    def bomb_pot(
+        n_players: int,
+        button: int,
+        ante: float,
+        bb: float,
+        stake: float,
+        deck: list[Card],
+        double_board: bool = False,
+        verbose: bool = False,
+        seed: int = 0,
+    ) -> State: ...
+    @staticmethod
+    def ante_only(
+        n_players: int,
+        button: int,
+        ante: float,
+        bb: float,
+        stake: float,
+        deck: list[Card],
+        verbose: bool = False,
+        seed: int = 0,
+    ) -> State: ...
+    @staticmethod
+    def pineapple(
+        n_players: int,
+        button: int,
+        sb: float,
+        bb: float,
+        stake: float,
+        deck: list[Card],
+        crazy: bool = False,
+        verbose: bool = False,
+        seed: int = 0,
     ) -> State: ...
+    def discard(self, player: int, card: Card) -> State: ...
     def apply_action(self, action: Action) -> State: ...
+    def apply_action_at(self, action: Action, timestamp: float) -> State: ...
+    def with_clock(self, timestamp: Optional[float] = None) -> State: ...
+    def apply_default_action(self, policy: DefaultPolicy = DefaultPolicy.CheckElseFold) -> State: ...
+    def apply_protected_all_in(self) -> State: ...
+    def with_range_buckets(self, n_buckets: int) -> State: ...
+    def with_perspective(self, player: Optional[int]) -> State: ...
+    def unsafe_full_view(self) -> State: ...
+    def with_ids(
+        self,
+        hand_id: Optional[str] = None,
+        table_id: Optional[str] = None,
+        session_id: Optional[str] = None,
+    ) -> State: ...
+    def with_rules(self, rules: TableRules) -> State: ...
+    def current_leader(self) -> list[list[int]]: ...  # full view only
+    def cards_remaining(self) -> int: ...
+    def known_dead_cards(self, player: int) -> Optional[list[Card]]: ...
+    def perf_counters(self) -> Optional[PerfCounters]: ...  # needs the `profiling` feature
+    def legal_bet_range(self) -> Optional[tuple[float, float]]: ...
+    def legal_action_mask(self, n_bet_buckets: int = 10) -> list[bool]: ...
+    def mask_action(self, index: int, n_bet_buckets: int = 10) -> Optional[Action]: ...
+    def explain_action(self, action: Action) -> ActionExplanation: ...
+    def post_dead_blind(self, player: int, amount: float) -> State: ...
+    def owe_blinds(self, players: list[int]) -> State: ...
+    def post_owed_blind(self, player: int) -> State: ...
+    def pending_blinds(self) -> list[int]: ...
+    def post_straddle(self, player: int, amount: float) -> State: ...
+    def post_antes(self, ante: float, big_blind_ante: bool = False, ante_first: bool = False) -> State: ...
+    def with_stacks(self, stacks: list[float]) -> State: ...
+    def redeal_unknown(
+        self, seed: int, constraints: Optional[dict[int, HandRange]] = None, hero: Optional[int] = None
+    ) -> State: ...
+    def hand_strength(self, player: int, n_samples: int = 1000) -> Optional[HandStrength]: ...
+    def betting_line(self) -> str: ...
+    def street_contributions(self) -> StreetContributions: ...
+    def closed_street(self) -> Optional[StreetSummary]: ...
+    def insurance_quote(self, player: int) -> Optional[InsuranceQuote]: ...
+    def all_in_ev(self) -> Optional[list[float]]: ...
+    def check_invariants(self) -> list[str]: ...
+    def recompute_pot(self) -> float: ...
+    def view(self) -> StateView: ...
+    def to_json(self) -> str: ...
+    @staticmethod
+    def from_json(json: str) -> State: ...
+    def __str__(self) -> str: ...
+
+class TableRules:
+    min_raise_ratio: float
+    max_raises_per_street: Optional[int]
+    kill_pot_multiplier: Optional[float]
+    seven_deuce_bounty: float
+    bomb_pot_every: Optional[int]
+    bomb_pot_ante: float
+    bomb_pot_double_board: bool
+    variant: GameVariant
+    report_all_in_equities: bool
+    hand_cap: Optional[float]
+    def __init__(
+        self,
+        *,
+        min_raise_ratio: float = 1.0,
+        max_raises_per_street: Optional[int] = None,
+        kill_pot_multiplier: Optional[float] = None,
+        seven_deuce_bounty: float = 0.0,
+        bomb_pot_every: Optional[int] = None,
+        bomb_pot_ante: float = 0.0,
+        bomb_pot_double_board: bool = False,
+        variant: GameVariant = GameVariant.Holdem,
+        report_all_in_equities: bool = False,
+        hand_cap: Optional[float] = None,
+    ) -> None: ...
+    def __str__(self) -> str: ...
+
+class Deck:
+    def __init__(self, seed: Optional[int] = None) -> None: ...
+    def shuffle(self, seed: int) -> None: ...
+    def deal(self, n: int) -> list[Card]: ...
+    def burn(self) -> Card: ...
+    def remove(self, cards: list[Card]) -> None: ...
+    def remaining(self) -> list[Card]: ...
+    def dealt(self) -> list[Card]: ...
+    def burned(self) -> list[Card]: ...
+    def __len__(self) -> int: ...
     def __str__(self) -> str: ...
 
 class PlayerState:
     player: int
-    hand: tuple[Card, Card]
+    hand: Optional[tuple[Card, Card]]  # None when hidden from the state's perspective
     bet_chips: float
     pot_chips: float
     stake: float
     reward: float
     active: bool
+    range_idx: int  # hand_class_strength(hand_class_of(hand)) - 1 preflop, combo index or strength bucket after
+    folded_on: Optional[Stage]
+    invested: list[float]  # preflop, flop, turn, river
+    ante_chips: float
+    held_back: float  # stack kept out of the hand by apply_protected_all_in or the hand cap
+    third_card: Optional[Card]  # Pineapple hands, until discarded; None when hidden
+    discarded: Optional[Card]  # Pineapple hands; None when hidden
+    def __str__(self) -> str: ...
+
+class PlayerView:
+    player: int
+    stake: float
+    bet_chips: float
+    pot_chips: float
+    active: bool
+    reward: float
+    last_stage_action: Optional[ActionEnum]
+    shown_hand: Optional[tuple[Card, Card]]  # once shown down
+    def __str__(self) -> str: ...
+
+class StateView:
+    current_player: int
+    players: list[PlayerView]
+    public_cards: list[Card]
+    second_board: list[Card]
+    stage: Stage
+    button: int
+    from_action: Optional[ActionRecord]
+    legal_actions: list[ActionEnum]
+    pot: float
+    min_bet: float
+    sb: float
+    bb: float
+    final_state: bool
+    hand_id: str
+    table_id: Optional[str]
+    session_id: Optional[str]
+    def __str__(self) -> str: ...
+
+class AllInEquity:
+    stage: Stage
+    players: list[int]
+    equities: list[float]  # in the order of players
+    def __str__(self) -> str: ...
+
+class BlindSettlement(Enum):
+    Pending = 0
+    BigBlind = 1
+    PostedDead = 2
+
+class BlindObligation:
+    player: int
+    amount: float  # the big blind of the hand
+    settlement: BlindSettlement
     def __str__(self) -> str: ...
 
 class StateStatus(Enum):
@@ -59,6 +1071,7 @@ class StateStatus(Enum):
     IllegalAction = 1
     LowBet = 2
     HighBet = 3
+    EngineError = 4
 
     def __int__(self): ...
 
@@ -69,6 +1082,55 @@ class ActionRecord:
     stage: Stage
     action: Action
     legal_actions: list[ActionEnum]
+    timestamp: Optional[float]
+    think_time: Optional[float]
+    forced: bool
+    requested: Action
+    coercion: Optional[Coercion]
+    pot_before: float
+    to_call: float
+    chips: float
+
+class TraceKind(Enum):
+    Action = 0
+    SkippedFolded = 1
+    SkippedAllIn = 2
+    RoundOverBetsMatched = 3
+    RoundOverOnePlayerLeft = 4
+    RoundOverNobodyToAct = 5
+    StreetDealt = 6
+    ShowdownForcedOnePlayerLeft = 7
+    ShowdownForcedAllIn = 8
+    Showdown = 9
+    Winners = 10
+    PotDrift = 11
+    EngineError = 12
+
+class TraceEvent:
+    kind: TraceKind
+    stage: Stage
+    players: list[int]  # players the decision is about
+    action: Optional[ActionEnum]
+    amount: Optional[float]  # action amount, cards dealt or chips put in of a pot drift
+    detail: Optional[str]
+    def __str__(self) -> str: ...
+
+class Coercion(Enum):
+    Timeout = 1
+    IllegalAction = 2
+    MinRaise = 3
+    AllIn = 4
+    Disconnected = 5
+
+class GameVariant(Enum):
+    Holdem = 0
+    ShortDeck = 1
+    AceToFive = 2
+
+class DefaultPolicy(Enum):
+    CheckElseFold = 0
+    Fold = 1
+    CheckCall = 2
 
 class ActionEnum(Enum):
     Fold = 0
@@ -91,6 +1153,15 @@ class Card:
     @staticmethod
     def from_string(string: str) -> Card | None: ...
     def collect(self) -> list[Card]: ...
+    def index(self) -> int: ...
+    @staticmethod
+    def from_index(index: int) -> Card | None: ...
+    def one_hot(self) -> list[float]: ...
+    def __hash__(self) -> int: ...
+    def __eq__(self, other: object) -> bool: ...
+    def __lt__(self, other: Card) -> bool: ...
+
+def cards_one_hot(cards: list[Card]) -> list[float]: ...
 
 class CardSuit(Enum):
     Clubs = 0
@@ -117,6 +1188,31 @@ class CardRank(Enum):
 
     def __int__(self): ...
 
+# hand_strength.rs ------------------------------------------------------------
+
+class HandCategory(Enum):
+    HighCard = 0
+    Pair = 1
+    TwoPair = 2
+    ThreeOfAKind = 3
+    Straight = 4
+    Flush = 5
+    FullHouse = 6
+    FourOfAKind = 7
+    StraightFlush = 8
+    RoyalFlush = 9
+
+    def __int__(self): ...
+    def description(self, locale: Locale = Locale.En) -> str: ...
+
+class HandStrength:
+    player: int
+    stage: Stage
+    category: HandCategory
+    outs: int
+    equity: float
+    def __str__(self) -> str: ...
+
 # stage.rs --------------------------------------------------------------------
 
 class Stage(Enum):
@@ -126,4 +1222,13 @@ class Stage(Enum):
     River = 3
     Showdown = 4
 
-    def __int__(self): ...
\ No newline at end of file
+    def __int__(self): ...
+    def next(self) -> Optional[Stage]: ...
+    def board_card_count(self) -> int: ...
+    @staticmethod
+    def from_str(name: str) -> Stage: ...
+    def __str__(self) -> str: ...
+    def __lt__(self, other: Stage) -> bool: ...
+    def __le__(self, other: Stage) -> bool: ...
+    def __gt__(self, other: Stage) -> bool: ...
+    def __ge__(self, other: Stage) -> bool: ...
\ No newline at end of file
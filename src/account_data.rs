@@ -0,0 +1,169 @@
+// account_data.rs - Everything the server stores about an account, exported or deleted at the
+// request of its owner
+//
+// An account's data is its lifetime stats, its hand histories and the recordings of its
+// connections. Its name also appears in the hands of the players it sat with, where deleting
+// the account replaces it with a pseudonym, see `HandArchive::delete_account`.
+use crate::hand_archive::HandArchive;
+use crate::player_stats::PlayerStatsStore;
+use crate::session_recording::account_recordings;
+use pyo3::prelude::*;
+use std::io::Write;
+use std::path::Path;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+/// What `delete_account` removed
+#[pyclass]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AccountDeletion {
+    /// Whether the account had lifetime stats
+    #[pyo3(get)]
+    pub stats: bool,
+    /// Hand histories deleted
+    #[pyo3(get)]
+    pub hands: usize,
+    /// Recorded sessions deleted, with the connections of every client of the table
+    #[pyo3(get)]
+    pub sessions: usize,
+}
+
+#[pymethods]
+impl AccountDeletion {
+    pub fn __str__(&self) -> PyResult<String> {
+        Ok(format!("{:#?}", self))
+    }
+}
+
+/// ZIP file of everything stored about `account`: `stats.json`, `hands/<hand id>.txt` and
+/// `recordings/<session id>/<client id>.jsonl` for the connections it registered on, when
+/// `recordings` holds the session recordings
+pub fn export_account(
+    stats: &PlayerStatsStore,
+    archive: &HandArchive,
+    recordings: Option<&Path>,
+    account: &str,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut zip = ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+    if let Some(stats) = stats.find(account) {
+        zip.start_file("stats.json", options)?;
+        zip.write_all(serde_json::to_string_pretty(stats)?.as_bytes())?;
+    }
+    archive.write_zip(&mut zip, account, "hands/")?;
+    if let Some(dir) = recordings {
+        for path in account_recordings(dir, account)? {
+            let name = path.strip_prefix(dir)?.to_string_lossy().replace('\\', "/");
+            zip.start_file(format!("recordings/{}", name), options)?;
+            zip.write_all(&std::fs::read(&path)?)?;
+        }
+    }
+    Ok(zip.finish()?.into_inner())
+}
+
+/// Delete everything stored about `account`. Its name is replaced with a pseudonym in the
+/// hands of the other accounts, and the sessions it was recorded in are deleted whole since
+/// every client of the table was sent its name.
+pub fn delete_account(
+    stats: &mut PlayerStatsStore,
+    archive: &mut HandArchive,
+    recordings: Option<&Path>,
+    account: &str,
+) -> Result<AccountDeletion, Box<dyn std::error::Error>> {
+    let mut deletion = AccountDeletion {
+        stats: stats.remove(account)?.is_some(),
+        hands: archive.delete_account(account)?,
+        sessions: 0,
+    };
+    if let Some(dir) = recordings {
+        let mut sessions: Vec<_> = account_recordings(dir, account)?
+            .iter()
+            .filter_map(|path| path.parent().map(Path::to_path_buf))
+            .collect();
+        sessions.dedup();
+        for session in sessions {
+            std::fs::remove_dir_all(session)?;
+            deletion.sessions += 1;
+        }
+    }
+    Ok(deletion)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session_recording::SessionRecorder;
+    use std::io::Read;
+
+    fn hand(id: &str, date: &str, players: [&str; 2]) -> String {
+        format!(
+            "PokerStars Hand #{}: Hold'em No Limit (1/2) - {} UTC\n\
+             Table 'main' 2-max Seat #1 is the button\n\
+             Seat 1: {} (200 in chips)\n\
+             Seat 2: {} (200 in chips)\n\
+             {}: folds",
+            id, date, players[0], players[1], players[1]
+        )
+    }
+
+    #[test]
+    fn accounts_export_and_delete() {
+        let dir = std::env::temp_dir().join(format!("pokers_account_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let recordings = dir.join("recordings");
+
+        let mut stats = PlayerStatsStore::load(dir.join("stats.json")).unwrap();
+        stats.record_hand("alice", 2.0, 4.0, "1");
+        let mut archive = HandArchive::open(dir.join("hands")).unwrap();
+        for account in ["alice", "bob"] {
+            archive
+                .record_hand(account, &hand("1", "2026/10/17 8:00:00", ["alice", "bob"]))
+                .unwrap();
+        }
+        let recorder = SessionRecorder::open(&recordings, "s1", "c1", "main", 7).unwrap();
+        recorder.received(r#"{"messageType":"registerPlayer","data":{"name":"alice"}}"#);
+        SessionRecorder::open(&recordings, "s2", "c2", "main", 7).unwrap();
+
+        let zip = export_account(&stats, &archive, Some(&recordings), "alice").unwrap();
+        let mut zip = zip::ZipArchive::new(std::io::Cursor::new(zip)).unwrap();
+        let mut names: Vec<_> = zip.file_names().map(str::to_string).collect();
+        names.sort();
+        assert_eq!(
+            names,
+            ["hands/1.txt", "recordings/s1/c1.jsonl", "stats.json"]
+        );
+        let mut text = String::new();
+        zip.by_name("stats.json")
+            .unwrap()
+            .read_to_string(&mut text)
+            .unwrap();
+        assert!(text.contains("\"handsPlayed\": 1"));
+
+        let deletion = delete_account(&mut stats, &mut archive, Some(&recordings), "alice");
+        assert_eq!(
+            deletion.unwrap(),
+            AccountDeletion {
+                stats: true,
+                hands: 1,
+                sessions: 1,
+            }
+        );
+        assert!(archive.hands("alice").unwrap().is_empty());
+        assert!(!recordings.join("s1").exists() && recordings.join("s2").exists());
+        assert!(PlayerStatsStore::load(dir.join("stats.json"))
+            .unwrap()
+            .find("alice")
+            .is_none());
+        // Bob keeps the hand, without alice's name
+        let bob = archive.hands("bob").unwrap();
+        assert!(!bob[0].contains("alice"));
+        assert!(bob[0].contains("Seat 1: Player-"));
+        // Nothing maps the pseudonym back to her
+        let pseudonyms = std::fs::read_to_string(dir.join("hands/pseudonyms.json")).unwrap();
+        assert!(!pseudonyms.contains("alice"));
+
+        let empty = delete_account(&mut stats, &mut archive, Some(&recordings), "alice");
+        assert_eq!(empty.unwrap(), AccountDeletion::default());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,80 @@
+// betting_line.rs - Compact notation of the action of a hand, street by street
+use crate::state::action::{ActionEnum, ActionRecord};
+use crate::state::stage::Stage;
+use crate::state::State;
+use itertools::Itertools;
+
+/// Token of one action: `f` fold, `x` check, `c` call, `b<size>` bet, `r<size>` raise.
+/// Sizes are percentages of the pot: a bet of the pot before it, a raise (the chips on top
+/// of the call) of the pot once the player has called.
+fn action_token(record: &ActionRecord) -> String {
+    match record.action.action {
+        ActionEnum::Fold => "f".to_string(),
+        ActionEnum::CheckCall if record.to_call > 0.0 => "c".to_string(),
+        ActionEnum::CheckCall => "x".to_string(),
+        // An all-in for less than the call is a call
+        ActionEnum::BetRaise if record.chips <= record.to_call => "c".to_string(),
+        ActionEnum::BetRaise => {
+            let size = (record.chips - record.to_call) / (record.pot_before + record.to_call);
+            let prefix = if record.to_call > 0.0 { 'r' } else { 'b' };
+            format!("{}{}", prefix, (size * 100.0).round() as i64)
+        }
+    }
+}
+
+/// Actions of every street joined with `-`, streets joined with `, `.
+/// Streets without actions (a bomb pot's preflop, the runout after an all-in) are left out.
+pub fn betting_line(state: &State) -> String {
+    line_of(state.action_list.iter())
+}
+
+/// The betting line of the streets from the flop on, which solvers key their nodes by
+#[allow(dead_code)]
+pub fn postflop_line(state: &State) -> String {
+    line_of(
+        state
+            .action_list
+            .iter()
+            .filter(|record| record.stage != Stage::Preflop),
+    )
+}
+
+fn line_of<'a>(records: impl Iterator<Item = &'a ActionRecord>) -> String {
+    records
+        .group_by(|record| record.stage)
+        .into_iter()
+        .map(|(_, records)| records.map(action_token).join("-"))
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::action::Action;
+
+    #[test]
+    fn line_of_a_hand() {
+        // Button 0 heads-up: player 1 posts the small blind and acts first preflop
+        let mut state = State::from_seed(2, 0, 1.0, 2.0, 200.0, 3, false, 0.0).unwrap();
+        assert_eq!(betting_line(&state), "");
+        for action in [
+            // Raise to 6: 4 on top of the call into a pot of 4 once called
+            Action::new(ActionEnum::BetRaise, 6.0),
+            Action::new(ActionEnum::CheckCall, 0.0),
+            Action::new(ActionEnum::CheckCall, 0.0),
+            // A third of the pot of 12
+            Action::new(ActionEnum::BetRaise, 4.0),
+            Action::new(ActionEnum::CheckCall, 0.0),
+            Action::new(ActionEnum::CheckCall, 0.0),
+            Action::new(ActionEnum::BetRaise, 10.0),
+            // Raise to 25: 15 on top of the call into a pot of 40 once called
+            Action::new(ActionEnum::BetRaise, 25.0),
+            Action::new(ActionEnum::Fold, 0.0),
+        ] {
+            state = state.apply_action(action);
+        }
+        assert!(state.final_state);
+        assert_eq!(betting_line(&state), "r100-c, x-b33-c, x-b50-r38-f");
+        assert_eq!(postflop_line(&state), "x-b33-c, x-b50-r38-f");
+    }
+}
@@ -0,0 +1,122 @@
+// bots.rs - Agents the table server plays for at their seats
+use crate::runtime;
+use crate::state::action::{Action, DefaultPolicy};
+use crate::state::State;
+use futures_util::future::BoxFuture;
+use pyo3::prelude::*;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::error;
+
+/// An agent seated at the table, asked for its action whenever it is its turn
+pub trait BotPlayer: Send + Sync {
+    /// Action of `player` (index in `state.players_state`) on their turn in `state`. The server
+    /// does not wait on it while it runs, so it may call out to slow services. `None` plays the
+    /// bot's fallback action.
+    fn act(&self, state: State, player: u64) -> BoxFuture<'_, Option<Action>>;
+}
+
+/// How the server plays a bot, see `GameServer::seat_bot`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BotSettings {
+    /// Waited before asking the bot for its action
+    pub think_time: Duration,
+
+    /// Time the bot has to answer, after which the fallback is played instead
+    pub deadline: Option<Duration>,
+
+    /// Action forced when the bot misses its deadline or fails
+    pub fallback: DefaultPolicy,
+}
+
+impl Default for BotSettings {
+    fn default() -> Self {
+        BotSettings {
+            think_time: Duration::ZERO,
+            deadline: None,
+            fallback: DefaultPolicy::CheckElseFold,
+        }
+    }
+}
+
+impl BotSettings {
+    /// Settings with the think time and deadline in seconds, which must be finite and not
+    /// negative
+    pub fn checked(
+        think_time: f64,
+        deadline: Option<f64>,
+        fallback: DefaultPolicy,
+    ) -> Result<Self, String> {
+        let think_time = Duration::try_from_secs_f64(think_time)
+            .map_err(|_| format!("Invalid think time of {} seconds", think_time))?;
+        let deadline = deadline
+            .map(|seconds| {
+                Duration::try_from_secs_f64(seconds)
+                    .map_err(|_| format!("Invalid deadline of {} seconds", seconds))
+            })
+            .transpose()?;
+        Ok(BotSettings {
+            think_time,
+            deadline,
+            fallback,
+        })
+    }
+}
+
+/// Bot backed by a Python callable `policy(state, player) -> Action`
+pub struct PyBot {
+    policy: Arc<PyObject>,
+}
+
+impl PyBot {
+    pub fn new(policy: PyObject) -> Self {
+        PyBot {
+            policy: Arc::new(policy),
+        }
+    }
+}
+
+impl BotPlayer for PyBot {
+    fn act(&self, state: State, player: u64) -> BoxFuture<'_, Option<Action>> {
+        let policy = self.policy.clone();
+        Box::pin(async move {
+            // The policy holds the GIL for as long as it runs, away from the runtime's workers
+            let action = runtime::spawn_blocking(move || {
+                Python::with_gil(|py| {
+                    policy
+                        .call1(py, (state, player))
+                        .and_then(|action| action.extract::<Action>(py))
+                })
+            })
+            .await;
+            match action {
+                Ok(Ok(action)) => Some(action),
+                Ok(Err(e)) => {
+                    error!("Bot policy failed: {}", e);
+                    None
+                }
+                Err(_) => {
+                    error!("Bot policy panicked");
+                    None
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_take_times_in_seconds() {
+        let settings = BotSettings::checked(0.5, Some(2.0), DefaultPolicy::CheckElseFold).unwrap();
+        assert_eq!(settings.think_time, Duration::from_millis(500));
+        assert_eq!(settings.deadline, Some(Duration::from_secs(2)));
+        for seconds in [-1.0, f64::NAN, f64::INFINITY, 1e300] {
+            let fallback = DefaultPolicy::CheckElseFold;
+            assert!(BotSettings::checked(seconds, None, fallback).is_err());
+            assert!(BotSettings::checked(0.0, Some(seconds), fallback).is_err());
+        }
+    }
+}
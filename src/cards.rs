@@ -0,0 +1,99 @@
+// cards.rs - Two-card combos with the dead cards removed, for equity and redeal
+use crate::range::{hand_class, HandRange};
+use crate::state::card::{Card, N_CARDS};
+use itertools::Itertools;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+/// The deck without the `dead` cards, in `Card::collect` order
+pub fn live_cards(dead: &[Card]) -> Vec<Card> {
+    Card::collect()
+        .into_iter()
+        .filter(|c| !dead.contains(c))
+        .collect()
+}
+
+/// 52-length encoding of a set of cards (a board, dead cards), with a one at each card's index
+#[pyfunction]
+pub fn cards_one_hot(cards: Vec<Card>) -> Vec<f32> {
+    let mut encoding = vec![0.0; N_CARDS];
+    for card in cards {
+        encoding[card.index() as usize] = 1.0;
+    }
+    encoding
+}
+
+/// Every two-card combo that can still be dealt when the `dead` cards are out
+#[pyfunction]
+#[pyo3(signature = (dead=Vec::new()))]
+pub fn all_combos(dead: Vec<Card>) -> Vec<(Card, Card)> {
+    live_cards(&dead).into_iter().tuple_combinations().collect()
+}
+
+/// The combos of `range` that can still be dealt, each with the weight of its hand class.
+/// Classes with no weight are left out.
+#[pyfunction]
+#[pyo3(signature = (range, dead=Vec::new()))]
+pub fn combos_for_range(range: &HandRange, dead: Vec<Card>) -> Vec<((Card, Card), f64)> {
+    all_combos(dead)
+        .into_iter()
+        .map(|combo| (combo, range.weights[hand_class(combo)]))
+        .filter(|(_, weight)| *weight > 0.0)
+        .collect()
+}
+
+/// Draw a combo, each with probability proportional to its weight
+pub fn sample_combo<R: Rng>(combos: &[((Card, Card), f64)], rng: &mut R) -> Option<(Card, Card)> {
+    combos
+        .choose_weighted(rng, |(_, weight)| *weight)
+        .ok()
+        .map(|(combo, _)| *combo)
+}
+
+/// `n` combos drawn independently from `range` with the `dead` cards out. The draws only
+/// depend on `seed`.
+#[pyfunction]
+#[pyo3(signature = (range, n, dead=Vec::new(), seed=0))]
+pub fn sample_combos(
+    range: &HandRange,
+    n: usize,
+    dead: Vec<Card>,
+    seed: u64,
+) -> PyResult<Vec<(Card, Card)>> {
+    let combos = combos_for_range(range, dead);
+    let mut rng = StdRng::seed_from_u64(seed);
+    (0..n)
+        .map(|_| {
+            sample_combo(&combos, &mut rng)
+                .ok_or_else(|| PyValueError::new_err("No combo of the range is left"))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dead_cards_are_never_dealt() {
+        assert_eq!(all_combos(Vec::new()).len(), 1326);
+
+        let ace = Card::from_string("SA".to_owned()).unwrap();
+        let combos = all_combos(vec![ace]);
+        assert_eq!(combos.len(), 51 * 50 / 2);
+        assert!(combos.iter().all(|&(a, b)| a != ace && b != ace));
+
+        // One ace out leaves 3 of the 6 combos of pocket aces
+        let range = HandRange::from_string("AA, AKs:0.5").unwrap();
+        let weighted = combos_for_range(&range, vec![ace]);
+        assert_eq!(weighted.iter().filter(|(_, w)| *w == 1.0).count(), 3);
+        assert_eq!(weighted.iter().filter(|(_, w)| *w == 0.5).count(), 3);
+
+        let sampled = sample_combos(&range, 100, vec![ace], 7).unwrap();
+        assert!(sampled.iter().all(|&(a, b)| a != ace && b != ace));
+        assert_eq!(sampled, sample_combos(&range, 100, vec![ace], 7).unwrap());
+    }
+}
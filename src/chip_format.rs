@@ -0,0 +1,264 @@
+// chip_format.rs - Chip amounts as display strings and as stacks of chips
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Chip values of the default set, from the white 1 to the 25,000 plaque
+const DEFAULT_DENOMINATIONS: [f64; 8] = [1.0, 5.0, 25.0, 100.0, 500.0, 1_000.0, 5_000.0, 25_000.0];
+
+/// How chip amounts are shown: the money a chip stands for, with its currency symbol and
+/// separators, and the chips of each denomination an amount is stacked with, for animations.
+/// The default shows the chips themselves, with up to two decimals and no separators.
+#[pyclass]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChipFormat {
+    /// Currency symbol, e.g. "$" or " €" with `symbol_after`
+    #[pyo3(get, set)]
+    pub symbol: String,
+    /// Write the symbol after the amount rather than before
+    #[pyo3(get, set)]
+    pub symbol_after: bool,
+    /// Decimal places always written, or up to two without trailing zeros when None
+    #[pyo3(get, set)]
+    pub decimals: Option<u32>,
+    /// Money a chip stands for, e.g. 0.01 for chips counted in cents
+    #[pyo3(get)]
+    pub chip_value: f64,
+    /// Between the groups of three digits, none when empty
+    #[pyo3(get, set)]
+    pub thousands_separator: String,
+    #[pyo3(get, set)]
+    pub decimal_separator: String,
+    /// Chip values of the set `breakdown` stacks amounts with, in chips
+    #[pyo3(get)]
+    pub denominations: Vec<f64>,
+}
+
+/// `count` chips of value `denomination`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ChipStack {
+    pub denomination: f64,
+    pub count: u64,
+}
+
+impl Default for ChipFormat {
+    fn default() -> Self {
+        Self {
+            symbol: String::new(),
+            symbol_after: false,
+            decimals: None,
+            chip_value: 1.0,
+            thousands_separator: String::new(),
+            decimal_separator: ".".to_string(),
+            denominations: DEFAULT_DENOMINATIONS.to_vec(),
+        }
+    }
+}
+
+impl ChipFormat {
+    /// A format with a positive `chip_value` and denominations
+    pub fn checked(
+        chip_value: f64,
+        mut denominations: Vec<f64>,
+        decimals: Option<u32>,
+    ) -> Result<Self, String> {
+        if !(chip_value.is_finite() && chip_value > 0.0) {
+            return Err(format!(
+                "The chip value must be positive, got {}",
+                chip_value
+            ));
+        }
+        if let Some(denomination) = denominations
+            .iter()
+            .find(|denomination| !(denomination.is_finite() && **denomination > 0.0))
+        {
+            return Err(format!(
+                "Chip denominations must be positive, got {}",
+                denomination
+            ));
+        }
+        if decimals.is_some_and(|decimals| decimals > 8) {
+            return Err("At most 8 decimals are written".to_string());
+        }
+        denominations.sort_by(f64::total_cmp);
+        denominations.dedup();
+        Ok(Self {
+            chip_value,
+            denominations,
+            decimals,
+            ..Self::default()
+        })
+    }
+
+    /// `chips` in money, e.g. "$1,234.50"
+    pub fn format(&self, chips: f64) -> String {
+        let value = chips * self.chip_value;
+        let digits = match self.decimals {
+            Some(decimals) => format!("{:.*}", decimals as usize, value.abs()),
+            None => format!("{}", (value.abs() * 100.0).round() / 100.0),
+        };
+        let (integer, fraction) = match digits.split_once('.') {
+            Some((integer, fraction)) => (integer, Some(fraction)),
+            None => (digits.as_str(), None),
+        };
+
+        let mut text = String::new();
+        // No "-0" for a loss that rounds away
+        if value < 0.0 && digits.bytes().any(|digit| (b'1'..=b'9').contains(&digit)) {
+            text.push('-');
+        }
+        if !self.symbol_after {
+            text.push_str(&self.symbol);
+        }
+        for (index, digit) in integer.chars().enumerate() {
+            if index > 0 && (integer.len() - index) % 3 == 0 {
+                text.push_str(&self.thousands_separator);
+            }
+            text.push(digit);
+        }
+        if let Some(fraction) = fraction {
+            text.push_str(&self.decimal_separator);
+            text.push_str(fraction);
+        }
+        if self.symbol_after {
+            text.push_str(&self.symbol);
+        }
+        text
+    }
+
+    /// The fewest chips of `denominations` stacking up to `chips`, largest first. What the
+    /// smallest denomination cannot make up is left out.
+    pub fn breakdown(&self, chips: f64) -> Vec<ChipStack> {
+        // In hundredths of a chip, so 0.1 + 0.2 stacks like 0.3
+        let mut left = (chips.max(0.0) * 100.0).round() as u64;
+        let mut stacks = Vec::new();
+        for &denomination in self.denominations.iter().rev() {
+            let unit = (denomination * 100.0).round() as u64;
+            if unit == 0 || left < unit {
+                continue;
+            }
+            stacks.push(ChipStack {
+                denomination,
+                count: left / unit,
+            });
+            left %= unit;
+        }
+        stacks
+    }
+}
+
+#[pymethods]
+impl ChipFormat {
+    #[new]
+    #[pyo3(signature = (symbol=String::new(), decimals=None, chip_value=1.0, symbol_after=false, thousands_separator=String::new(), decimal_separator=".".to_string(), denominations=DEFAULT_DENOMINATIONS.to_vec()))]
+    pub fn new(
+        symbol: String,
+        decimals: Option<u32>,
+        chip_value: f64,
+        symbol_after: bool,
+        thousands_separator: String,
+        decimal_separator: String,
+        denominations: Vec<f64>,
+    ) -> PyResult<ChipFormat> {
+        let format = ChipFormat::checked(chip_value, denominations, decimals)
+            .map_err(PyValueError::new_err)?;
+        Ok(ChipFormat {
+            symbol,
+            symbol_after,
+            thousands_separator,
+            decimal_separator,
+            ..format
+        })
+    }
+
+    /// `chips` in money, e.g. "$1,234.50"
+    #[pyo3(name = "format")]
+    pub fn py_format(&self, chips: f64) -> String {
+        self.format(chips)
+    }
+
+    /// (denomination, count) of the fewest chips stacking up to `chips`, largest first
+    #[pyo3(name = "breakdown")]
+    pub fn py_breakdown(&self, chips: f64) -> Vec<(f64, u64)> {
+        self.breakdown(chips)
+            .into_iter()
+            .map(|stack| (stack.denomination, stack.count))
+            .collect()
+    }
+
+    pub fn __str__(&self) -> PyResult<String> {
+        Ok(format!("{:#?}", self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn amounts_are_written_in_money() {
+        let chips = ChipFormat::default();
+        assert_eq!(chips.format(1500.0), "1500");
+        assert_eq!(chips.format(2.5), "2.5");
+        assert_eq!(chips.format(0.1 + 0.2), "0.3");
+        assert_eq!(chips.format(-20.0), "-20");
+
+        let dollars = ChipFormat {
+            symbol: "$".to_string(),
+            thousands_separator: ",".to_string(),
+            ..ChipFormat::checked(0.01, vec![1.0], Some(2)).unwrap()
+        };
+        assert_eq!(dollars.format(123_450.0), "$1,234.50");
+        assert_eq!(dollars.format(-5.0), "-$0.05");
+        assert_eq!(dollars.format(-0.1), "$0.00");
+
+        let euros = ChipFormat {
+            symbol: " €".to_string(),
+            symbol_after: true,
+            thousands_separator: ".".to_string(),
+            decimal_separator: ",".to_string(),
+            ..ChipFormat::checked(1.0, vec![1.0], Some(2)).unwrap()
+        };
+        assert_eq!(euros.format(1_234_567.891), "1.234.567,89 €");
+
+        assert!(ChipFormat::checked(0.0, vec![1.0], None).is_err());
+        assert!(ChipFormat::checked(1.0, vec![-1.0], None).is_err());
+    }
+
+    #[test]
+    fn amounts_stack_with_the_fewest_chips() {
+        let chips = ChipFormat::default();
+        let stacks: Vec<(f64, u64)> = chips
+            .breakdown(1_637.5)
+            .iter()
+            .map(|stack| (stack.denomination, stack.count))
+            .collect();
+        assert_eq!(
+            stacks,
+            [
+                (1_000.0, 1),
+                (500.0, 1),
+                (100.0, 1),
+                (25.0, 1),
+                (5.0, 2),
+                (1.0, 2)
+            ]
+        );
+        assert!(chips.breakdown(0.0).is_empty());
+
+        let cents = ChipFormat::checked(1.0, vec![0.25, 0.05], None).unwrap();
+        assert_eq!(
+            cents.breakdown(0.1 + 0.2),
+            [
+                ChipStack {
+                    denomination: 0.25,
+                    count: 1
+                },
+                ChipStack {
+                    denomination: 0.05,
+                    count: 1
+                }
+            ]
+        );
+    }
+}
@@ -0,0 +1,307 @@
+// contributions.rs - Chips put in by each player on the current street, action by action
+use crate::game_logic::{pot_slices, Pot};
+use crate::state::action::ActionEnum;
+use crate::state::stage::Stage;
+use crate::state::{State, StateStatus};
+use pyo3::prelude::*;
+
+/// One action of the street with the chips it added and the pot around it
+#[pyclass]
+#[derive(Debug, Clone)]
+pub struct StreetAction {
+    #[pyo3(get)]
+    pub player: u64,
+
+    #[pyo3(get)]
+    pub action: ActionEnum,
+
+    /// Chips added to the pot by this action
+    #[pyo3(get)]
+    pub amount: f64,
+
+    #[pyo3(get)]
+    pub pot_before: f64,
+
+    #[pyo3(get)]
+    pub pot_after: f64,
+}
+
+#[pymethods]
+impl StreetAction {
+    pub fn __str__(&self) -> PyResult<String> {
+        Ok(format!("{:#?}", self))
+    }
+}
+
+#[pyclass]
+#[derive(Debug, Clone)]
+pub struct StreetContributions {
+    #[pyo3(get)]
+    pub stage: Stage,
+
+    /// Pot carried over from the previous streets
+    #[pyo3(get)]
+    pub pot_at_start: f64,
+
+    /// Chips put in on this street by each player, blinds, straddles and dead blinds included
+    #[pyo3(get)]
+    pub contributions: Vec<f64>,
+
+    #[pyo3(get)]
+    pub actions: Vec<StreetAction>,
+}
+
+#[pymethods]
+impl StreetContributions {
+    pub fn __str__(&self) -> PyResult<String> {
+        Ok(format!("{:#?}", self))
+    }
+}
+
+/// Totals of a betting round once it has closed, see `closed_street`
+#[pyclass]
+#[derive(Debug, Clone)]
+pub struct StreetSummary {
+    #[pyo3(get)]
+    pub stage: Stage,
+
+    /// Chips put in on the street by each player, blinds, antes and uncalled bets included
+    #[pyo3(get)]
+    pub contributions: Vec<f64>,
+
+    /// Chips put in on the street by all players
+    #[pyo3(get)]
+    pub total: f64,
+
+    /// Pot once the street's bets are swept in
+    #[pyo3(get)]
+    pub pot: f64,
+}
+
+#[pymethods]
+impl StreetSummary {
+    pub fn __str__(&self) -> PyResult<String> {
+        Ok(format!("{:#?}", self))
+    }
+}
+
+/// The betting round the last action closed, `None` when the street goes on. An all-in
+/// runout or the showdown only closes the street of the last action: the streets dealt
+/// after it had no betting.
+pub fn closed_street(state: &State) -> Option<StreetSummary> {
+    let record = state.from_action.as_ref()?;
+    let closed = state.stage != record.stage || state.final_state;
+    if !closed || !matches!(state.status, StateStatus::Ok) {
+        return None;
+    }
+
+    let street = (record.stage as usize).min(Stage::River as usize);
+    let contributions: Vec<f64> = state
+        .players_state
+        .iter()
+        .map(|ps| ps.invested[street])
+        .collect();
+    let pot = state
+        .players_state
+        .iter()
+        .map(|ps| ps.invested[..=street].iter().sum::<f64>())
+        .sum();
+    Some(StreetSummary {
+        stage: record.stage,
+        total: contributions.iter().sum(),
+        contributions,
+        pot,
+    })
+}
+
+/// Replay the current street (the street of the last action once the hand is over) from the
+/// chips every action put in
+pub fn street_contributions(state: &State) -> StreetContributions {
+    let n_players = state.players_state.len();
+    let stage = if state.final_state {
+        state
+            .action_list
+            .last()
+            .map(|a| a.stage)
+            .unwrap_or(state.stage)
+    } else {
+        state.stage
+    };
+
+    // Preflop, the street starts with what was posted before the first action: blinds,
+    // straddles and dead blinds as the table dealt them. The rest of the chips in front of a
+    // player, antes apart, came with their actions.
+    let mut contributions = vec![0.0; n_players];
+    if stage == Stage::Preflop {
+        for ps in state.players_state.iter() {
+            let acted: f64 = state
+                .action_list
+                .iter()
+                .filter(|a| a.player == ps.player)
+                .map(|a| a.chips)
+                .sum();
+            contributions[ps.player as usize] = ps.pot_chips + ps.bet_chips - ps.ante_chips - acted;
+        }
+    }
+
+    let mut actions = Vec::new();
+    for record in state.action_list.iter().filter(|a| a.stage == stage) {
+        contributions[record.player as usize] += record.chips;
+        actions.push(StreetAction {
+            player: record.player,
+            action: record.action.action,
+            amount: record.chips,
+            pot_before: record.pot_before,
+            pot_after: record.pot_before + record.chips,
+        });
+    }
+
+    StreetContributions {
+        stage,
+        pot_at_start: state.chips_in_pot() - contributions.iter().sum::<f64>(),
+        contributions,
+        actions,
+    }
+}
+
+/// The chips swept into the pot split into the main pot and the side pots, in that order.
+/// The pot is sliced at the same levels the hand is settled on, antes apart from the bets, and
+/// neighbouring slices the same players can win are shown as one pot. Bets still in front of the
+/// players are left out; once the hand is over every bet is in.
+pub fn pots(state: &State) -> Vec<Pot> {
+    let mut pots: Vec<Pot> = Vec::new();
+    for slice in pot_slices(state, |ps| ps.folded_on.is_none()) {
+        let pot = Pot {
+            amount: slice.amount,
+            eligible_players: slice.eligible.into_iter().collect(),
+        };
+        match pots.last_mut() {
+            Some(last) if last.eligible_players == pot.eligible_players => {
+                last.amount += pot.amount
+            }
+            _ => pots.push(pot),
+        }
+    }
+    pots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::action::Action;
+
+    #[test]
+    fn preflop_raise_and_call() {
+        // Button 0: player 1 posts the small blind, player 2 the big blind, player 0 acts first
+        let state = State::from_seed(3, 0, 0.5, 1.0, 100.0, 7, false, 0.0).unwrap();
+        let state = state.apply_action(Action::new(ActionEnum::BetRaise, 3.0));
+        let state = state.apply_action(Action::new(ActionEnum::Fold, 0.0));
+
+        let street = street_contributions(&state);
+        assert_eq!(street.stage, Stage::Preflop);
+        assert_eq!(street.pot_at_start, 0.0);
+        assert_eq!(street.contributions, vec![3.0, 0.5, 1.0]);
+        let amounts: Vec<(f64, f64)> = street
+            .actions
+            .iter()
+            .map(|a| (a.amount, a.pot_before))
+            .collect();
+        assert_eq!(amounts, vec![(3.0, 1.5), (0.0, 4.5)]);
+
+        // The big blind calls and the flop starts with everything in the pot
+        let state = state.apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+        let street = street_contributions(&state);
+        assert_eq!(street.stage, Stage::Flop);
+        assert_eq!(street.pot_at_start, 6.5);
+        assert_eq!(street.contributions, vec![0.0, 0.0, 0.0]);
+        assert!(street.actions.is_empty());
+    }
+
+    #[test]
+    fn preflop_starts_with_what_was_posted() {
+        // Player 3 straddles to 2 and the button posts a dead blind of 1
+        let state = State::from_seed(4, 0, 0.5, 1.0, 100.0, 7, false, 0.0)
+            .unwrap()
+            .post_straddle(3, 2.0)
+            .unwrap()
+            .post_dead_blind(0, 1.0)
+            .unwrap();
+        let street = street_contributions(&state);
+        assert_eq!(street.contributions, vec![1.0, 0.5, 1.0, 2.0]);
+        assert_eq!(street.pot_at_start, 0.0);
+
+        // The button calls the straddle, the small blind folds
+        let state = state.apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+        let state = state.apply_action(Action::new(ActionEnum::Fold, 0.0));
+        let street = street_contributions(&state);
+        assert_eq!(street.contributions, vec![3.0, 0.5, 1.0, 2.0]);
+        let amounts: Vec<(u64, f64, f64)> = street
+            .actions
+            .iter()
+            .map(|a| (a.player, a.amount, a.pot_before))
+            .collect();
+        assert_eq!(amounts, vec![(0, 2.0, 4.5), (1, 0.0, 6.5)]);
+    }
+
+    #[test]
+    fn closed_street_once_per_round() {
+        let state = State::from_seed(3, 0, 0.5, 1.0, 100.0, 7, false, 0.0).unwrap();
+        let state = state.apply_action(Action::new(ActionEnum::BetRaise, 3.0));
+        assert!(closed_street(&state).is_none());
+        let state = state.apply_action(Action::new(ActionEnum::Fold, 0.0));
+        assert!(closed_street(&state).is_none());
+
+        let state = state.apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+        let summary = closed_street(&state).unwrap();
+        assert_eq!(summary.stage, Stage::Preflop);
+        assert_eq!(summary.contributions, vec![3.0, 0.5, 3.0]);
+        assert_eq!(summary.total, 6.5);
+        assert_eq!(summary.pot, 6.5);
+
+        // A bet nobody calls ends the hand and closes the flop
+        let state = state.apply_action(Action::new(ActionEnum::BetRaise, 4.0));
+        assert!(closed_street(&state).is_none());
+        let state = state.apply_action(Action::new(ActionEnum::Fold, 0.0));
+        assert!(state.final_state);
+        let summary = closed_street(&state).unwrap();
+        assert_eq!(summary.stage, Stage::Flop);
+        assert_eq!(summary.total, 4.0);
+        assert_eq!(summary.pot, 10.5);
+    }
+
+    #[test]
+    fn pots_split_at_the_all_ins() {
+        // Player 0 is all-in for 20, the blinds go on with 60 each
+        let mut state = State::from_seed(3, 0, 0.5, 1.0, 100.0, 7, false, 0.0).unwrap();
+        state.players_state[0].stake = 20.0;
+        let state = state.apply_action(Action::new(ActionEnum::BetRaise, 20.0));
+        let state = state.apply_action(Action::new(ActionEnum::BetRaise, 60.0));
+        assert!(pots(&state).is_empty());
+        let state = state.apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+
+        let swept = pots(&state);
+        let split: Vec<(f64, Vec<u64>)> = swept
+            .iter()
+            .map(|pot| {
+                let mut eligible: Vec<u64> = pot.eligible_players.iter().copied().collect();
+                eligible.sort();
+                (pot.amount, eligible)
+            })
+            .collect();
+        assert_eq!(split, vec![(60.0, vec![0, 1, 2]), (80.0, vec![1, 2])]);
+        assert_eq!(swept.iter().map(|pot| pot.amount).sum::<f64>(), state.pot);
+
+        // The big blind folds to a flop bet: the side pot is the small blind's alone
+        let state = state.apply_action(Action::new(ActionEnum::BetRaise, 10.0));
+        let state = state.apply_action(Action::new(ActionEnum::Fold, 0.0));
+        assert!(state.final_state);
+        let settled = pots(&state);
+        assert_eq!(settled.len(), 2);
+        assert_eq!(settled[0].amount, 60.0);
+        assert_eq!(settled[1].amount, 90.0);
+        assert_eq!(
+            settled[1].eligible_players,
+            std::collections::HashSet::from([1])
+        );
+    }
+}
@@ -0,0 +1,1011 @@
+// datasets.rs - Training samples replayed from PokerStars/GGPoker hand histories
+use crate::game_logic::available_actions;
+use crate::hand_codec;
+use crate::range::HandRange;
+use crate::redeal::redeal_unknown;
+use crate::state::action::{Action, ActionEnum, Coercion};
+use crate::state::card::{Card, CardRank, CardSuit};
+use crate::state::stage::Stage;
+use crate::state::{State, StateStatus};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use strum::IntoEnumIterator;
+
+/// Seats encoded in an observation; larger tables are skipped
+pub const MAX_SEATS: usize = 10;
+
+/// 13 rank and 4 suit one-hot entries per card
+const CARD_FEATURES: usize = 17;
+
+/// Stake, bet, chips already in the pot, still in the hand, acting player
+const SEAT_FEATURES: usize = 5;
+
+/// Where the stage one-hot starts, after the hole cards and the board
+pub const STAGE_OFFSET: usize = 7 * CARD_FEATURES;
+
+/// Preflop to showdown
+pub const STAGE_FEATURES: usize = 5;
+
+/// Hole cards, board, stage, seats from the small blind on, pot and amount to call
+pub const OBSERVATION_SIZE: usize = STAGE_OFFSET + STAGE_FEATURES + MAX_SEATS * SEAT_FEATURES + 2;
+
+/// One decision of a player whose hole cards are known (the hero, or a player who showed)
+#[pyclass]
+#[derive(Debug, Clone)]
+pub struct DecisionSample {
+    #[pyo3(get)]
+    pub hand_id: String,
+
+    #[pyo3(get)]
+    pub player: String,
+
+    #[pyo3(get)]
+    pub stage: Stage,
+
+    /// `observation(state, player)` right before the decision
+    #[pyo3(get)]
+    pub observation: Vec<f32>,
+
+    /// Amounts in big blinds: total bet for `BetRaise`, chips added for `CheckCall`
+    #[pyo3(get)]
+    pub action: Action,
+
+    /// `ActionRecord.coercion` of the decision; replays stop where the engine changes an action
+    #[pyo3(get)]
+    pub coercion: Option<Coercion>,
+
+    /// Net result of the hand for the player, in big blinds
+    #[pyo3(get)]
+    pub reward: f64,
+}
+
+#[pymethods]
+impl DecisionSample {
+    pub fn __str__(&self) -> PyResult<String> {
+        Ok(format!("{:#?}", self))
+    }
+}
+
+#[pyclass]
+#[derive(Debug, Clone, Default)]
+pub struct HandHistoryDataset {
+    #[pyo3(get)]
+    pub samples: Vec<DecisionSample>,
+
+    /// Hands replayed through the engine
+    #[pyo3(get)]
+    pub hands: usize,
+
+    /// Hands that could not be replayed (antes, straddles, run it twice, engine disagreement)
+    #[pyo3(get)]
+    pub skipped: usize,
+}
+
+#[pymethods]
+impl HandHistoryDataset {
+    /// Write the samples as a Parquet file, one row per decision
+    pub fn to_parquet(&self, path: &str) -> PyResult<()> {
+        write_parquet(&self.samples, Path::new(path)).map_err(PyValueError::new_err)
+    }
+
+    pub fn __len__(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn __str__(&self) -> PyResult<String> {
+        Ok(format!(
+            "HandHistoryDataset(samples={}, hands={}, skipped={})",
+            self.samples.len(),
+            self.hands,
+            self.skipped
+        ))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum HistoryAction {
+    Fold,
+    Check,
+    Call(f64),
+    Bet(f64),
+    /// Total bet after the raise
+    RaiseTo(f64),
+}
+
+/// A hand as written in the history, amounts in chips (cents for cash games)
+#[derive(Debug, Clone, Default)]
+struct HandHistory {
+    id: String,
+    /// Players dealt in, in seat order, with their starting stacks
+    players: Vec<(String, f64)>,
+    small_blind: Option<(String, f64)>,
+    big_blind: Option<(String, f64)>,
+    hands: HashMap<String, (Card, Card)>,
+    board: Vec<Card>,
+    actions: Vec<(String, Stage, HistoryAction)>,
+    /// Chips put in minus uncalled bets returned
+    committed: HashMap<String, f64>,
+    collected: HashMap<String, f64>,
+    unsupported: Option<String>,
+}
+
+/// Read every `.txt` hand history and `.bin` file of `compress_hands` under `path` (a file or
+/// a directory, searched recursively) and replay each hand through the engine to collect
+/// (observation, action, reward) samples
+#[pyfunction]
+pub fn load_hand_histories(path: &str) -> PyResult<HandHistoryDataset> {
+    let mut files = Vec::new();
+    collect_files(Path::new(path), &mut files)
+        .map_err(|e| PyValueError::new_err(format!("Cannot read {}: {}", path, e)))?;
+
+    let mut dataset = HandHistoryDataset::default();
+    for file in files {
+        let cannot_read = |e: String| format!("Cannot read {}: {}", file.display(), e);
+        let bytes =
+            std::fs::read(&file).map_err(|e| PyValueError::new_err(cannot_read(e.to_string())))?;
+        if bytes.starts_with(hand_codec::MAGIC) {
+            let deals = hand_codec::decode_deals(&bytes)
+                .map_err(|e| PyValueError::new_err(cannot_read(e)))?;
+            for (initial, last) in deals {
+                if last.players_state.len() > MAX_SEATS {
+                    dataset.skipped += 1;
+                    continue;
+                }
+                dataset.hands += 1;
+                dataset.samples.extend(replay_decoded(initial, &last));
+            }
+            continue;
+        }
+        let text = String::from_utf8(bytes)
+            .map_err(|e| PyValueError::new_err(cannot_read(e.to_string())))?;
+        for hand in split_hands(&text) {
+            match parse_hand(hand).and_then(|history| replay(&history)) {
+                Ok(samples) => {
+                    dataset.hands += 1;
+                    dataset.samples.extend(samples);
+                }
+                Err(_) => dataset.skipped += 1,
+            }
+        }
+    }
+    Ok(dataset)
+}
+
+fn collect_files(path: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    if path.is_file() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+
+    let mut entries: Vec<PathBuf> = std::fs::read_dir(path)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<std::io::Result<_>>()?;
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() {
+            collect_files(&entry, files)?;
+        } else if entry
+            .extension()
+            .is_some_and(|ext| ext == "txt" || ext == "bin")
+        {
+            files.push(entry);
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn split_hands(text: &str) -> Vec<&str> {
+    let text = text.trim_start_matches('\u{feff}');
+    let starts: Vec<usize> = text
+        .match_indices("Hand #")
+        .map(|(i, _)| text[..i].rfind('\n').map(|n| n + 1).unwrap_or(0))
+        .filter(|&start| {
+            let line = &text[start..];
+            line.starts_with("PokerStars Hand #") || line.starts_with("Poker Hand #")
+        })
+        .collect();
+
+    starts
+        .iter()
+        .enumerate()
+        .map(|(i, &start)| &text[start..starts.get(i + 1).copied().unwrap_or(text.len())])
+        .collect()
+}
+
+/// "Ah" -> ace of hearts
+pub(crate) fn parse_card(card: &str) -> Option<Card> {
+    let mut chars = card.chars();
+    let rank = chars.next()?;
+    let suit = chars.next()?;
+    if chars.next().is_some() {
+        return None;
+    }
+    Card::from_string(format!("{}{}", suit, rank).to_uppercase())
+}
+
+/// Cards of every bracketed group in `text`, e.g. "[2c 7d 9h] [Js]"
+fn parse_cards(text: &str) -> Option<Vec<Card>> {
+    let mut cards = Vec::new();
+    for group in text.split('[').skip(1) {
+        let group = group.split(']').next()?;
+        for card in group.split_whitespace() {
+            cards.push(parse_card(card)?);
+        }
+    }
+    Some(cards)
+}
+
+fn parse_amount(text: &str, cents: bool) -> Option<f64> {
+    let number: String = text
+        .trim()
+        .trim_start_matches(['$', '€', '£'])
+        .chars()
+        .take_while(|c| c.is_ascii_digit() || *c == '.' || *c == ',')
+        .filter(|c| *c != ',')
+        .collect();
+    let amount: f64 = number.parse().ok()?;
+    Some(if cents {
+        (amount * 100.0).round()
+    } else {
+        amount
+    })
+}
+
+fn parse_hand(text: &str) -> Result<HandHistory, String> {
+    let mut lines = text.lines();
+    let header = lines.next().ok_or("Empty hand")?;
+    let mut history = HandHistory {
+        id: header
+            .split('#')
+            .nth(1)
+            .and_then(|rest| rest.split(':').next())
+            .ok_or("Missing hand id")?
+            .trim()
+            .to_string(),
+        ..Default::default()
+    };
+    let cents = header.contains(['$', '€', '£']);
+
+    let mut stage = Stage::Preflop;
+    let mut street_bets: HashMap<String, f64> = HashMap::new();
+    let mut summary = false;
+
+    for line in lines.map(str::trim) {
+        if line.starts_with("*** ") {
+            let street = line.split("***").nth(1).unwrap_or("").trim();
+            let next_stage = match street {
+                "FLOP" => Some(Stage::Flop),
+                "TURN" => Some(Stage::Turn),
+                "RIVER" => Some(Stage::River),
+                "SHOW DOWN" | "SHOWDOWN" => Some(Stage::Showdown),
+                "SUMMARY" => {
+                    summary = true;
+                    None
+                }
+                "HOLE CARDS" | "PRE-FLOP" => None,
+                other => {
+                    history.unsupported = Some(format!("Street {}", other));
+                    None
+                }
+            };
+            if let Some(next_stage) = next_stage {
+                stage = next_stage;
+                street_bets.clear();
+                if stage != Stage::Showdown {
+                    history.board = parse_cards(line).ok_or("Bad board")?;
+                }
+            }
+            continue;
+        }
+
+        // Seats are listed before the blinds; the summary repeats them without stacks
+        if line.starts_with("Seat ") && line.contains(" in chips") && !summary {
+            if line.contains("sitting out") {
+                continue;
+            }
+            let rest = line.split_once(": ").ok_or("Bad seat line")?.1;
+            let (name, stack) = rest.rsplit_once(" (").ok_or("Bad seat line")?;
+            let stack = parse_amount(stack, cents).ok_or("Bad stack")?;
+            history.players.push((name.to_string(), stack));
+            continue;
+        }
+
+        if line.contains("showed [") || line.contains("shows [") {
+            // "Hero: shows [..]" during the hand, "Seat 1: Hero showed [..]" in the summary
+            let shown_by = match line.strip_prefix("Seat ") {
+                Some(rest) => rest.split_once(": ").map(|(_, rest)| rest).unwrap_or(rest),
+                None => line,
+            };
+            if let Some(name) = player_of(&history, shown_by) {
+                let cards = parse_cards(line).ok_or("Bad shown cards")?;
+                if let [a, b] = cards[..] {
+                    history.hands.insert(name, (a, b));
+                }
+            }
+            continue;
+        }
+
+        if summary {
+            continue;
+        }
+
+        if let Some(rest) = line.strip_prefix("Dealt to ") {
+            let cards = parse_cards(rest).ok_or("Bad hole cards")?;
+            if let (Some(name), [a, b]) = (player_of(&history, rest), &cards[..]) {
+                history.hands.insert(name, (*a, *b));
+            }
+            continue;
+        }
+
+        if let Some(rest) = line.strip_prefix("Uncalled bet (") {
+            let amount = parse_amount(rest, cents).ok_or("Bad uncalled bet")?;
+            let name = rest
+                .split(" returned to ")
+                .nth(1)
+                .ok_or("Bad uncalled bet")?;
+            *history.committed.entry(name.to_string()).or_default() -= amount;
+            continue;
+        }
+
+        let name = match player_of(&history, line) {
+            Some(name) => name,
+            None => continue,
+        };
+        let rest = line[name.len()..].trim_start_matches(':').trim();
+
+        if let Some(amount) = rest.strip_prefix("collected ") {
+            let amount = parse_amount(amount, cents).ok_or("Bad collected amount")?;
+            *history.collected.entry(name).or_default() += amount;
+            continue;
+        }
+
+        let (verb, args) = rest.split_once(' ').unwrap_or((rest, ""));
+        let amount = || parse_amount(args, cents).ok_or("Bad amount".to_string());
+        let bet_before = street_bets.get(&name).copied().unwrap_or(0.0);
+        let (action, added) = match verb {
+            "posts" if args.starts_with("small blind ") && history.small_blind.is_none() => {
+                let amount =
+                    parse_amount(&args["small blind ".len()..], cents).ok_or("Bad blind")?;
+                history.small_blind = Some((name.clone(), amount));
+                (None, amount)
+            }
+            "posts" if args.starts_with("big blind ") && history.big_blind.is_none() => {
+                let amount = parse_amount(&args["big blind ".len()..], cents).ok_or("Bad blind")?;
+                history.big_blind = Some((name.clone(), amount));
+                (None, amount)
+            }
+            "posts" | "straddle" => {
+                history.unsupported = Some(format!("Forced bet: {}", rest));
+                (None, 0.0)
+            }
+            "folds" => (Some(HistoryAction::Fold), 0.0),
+            "checks" => (Some(HistoryAction::Check), 0.0),
+            "calls" => (Some(HistoryAction::Call(amount()?)), amount()?),
+            "bets" => (Some(HistoryAction::Bet(amount()?)), amount()?),
+            "raises" => {
+                let to = args.split(" to ").nth(1).ok_or("Bad raise")?;
+                let to = parse_amount(to, cents).ok_or("Bad raise")?;
+                (Some(HistoryAction::RaiseTo(to)), to - bet_before)
+            }
+            _ => (None, 0.0),
+        };
+
+        if added > 0.0 {
+            *street_bets.entry(name.clone()).or_default() += added;
+            *history.committed.entry(name.clone()).or_default() += added;
+        }
+        if let Some(action) = action {
+            history.actions.push((name, stage, action));
+        }
+    }
+
+    match history.unsupported {
+        Some(ref reason) => Err(reason.clone()),
+        None => Ok(history),
+    }
+}
+
+/// The seated player `line` starts with, longest name first so prefixes cannot match
+fn player_of(history: &HandHistory, line: &str) -> Option<String> {
+    history
+        .players
+        .iter()
+        .map(|(name, _)| name)
+        .filter(|name| {
+            line.strip_prefix(name.as_str())
+                .is_some_and(|rest| rest.starts_with([':', ' ']) || rest.is_empty())
+        })
+        .max_by_key(|name| name.len())
+        .cloned()
+}
+
+/// Replay a parsed hand through the engine, stopping at the first disagreement
+fn replay(history: &HandHistory) -> Result<Vec<DecisionSample>, String> {
+    let n = history.players.len();
+    if !(2..=MAX_SEATS).contains(&n) {
+        return Err(format!("{} players", n));
+    }
+    let index = |name: &str| history.players.iter().position(|(p, _)| p == name);
+    let (sb_name, sb) = history.small_blind.clone().ok_or("No small blind")?;
+    let (bb_name, bb) = history.big_blind.clone().ok_or("No big blind")?;
+    let sb_idx = index(&sb_name).ok_or("Unknown small blind")?;
+    if index(&bb_name) != Some((sb_idx + 1) % n) {
+        return Err("The big blind does not follow the small blind".to_owned());
+    }
+    // The engine posts the small blind left of the button
+    let button = (sb_idx + n - 1) % n;
+
+    // Known hole cards go to their owners, everything unknown is filled from the rest of the deck
+    let used: Vec<Card> = history
+        .hands
+        .values()
+        .flat_map(|h| [h.0, h.1])
+        .chain(history.board.iter().copied())
+        .collect();
+    let mut filler = Card::collect().into_iter().filter(|c| !used.contains(c));
+    let mut deck = Vec::with_capacity(52);
+    for i in 0..n {
+        let player = (button + i + 1) % n;
+        match history.hands.get(&history.players[player].0) {
+            Some(hand) => deck.extend([hand.0, hand.1]),
+            None => deck.extend(filler.by_ref().take(2)),
+        }
+    }
+    deck.extend(history.board.iter().copied());
+    deck.extend(filler);
+
+    let max_stack = history.players.iter().map(|(_, s)| *s).fold(bb, f64::max);
+    let mut state = State::from_deck(
+        n as u64,
+        button as u64,
+        sb,
+        bb,
+        max_stack,
+        deck,
+        false,
+        0,
+        0.0,
+    )
+    .map_err(|_| "Invalid blinds or stacks".to_owned())?
+    .with_ids(Some(history.id.clone()), None, None);
+    for ps in &mut state.players_state {
+        ps.stake = history.players[ps.player as usize].1 - ps.bet_chips;
+    }
+
+    let reward = |name: &str| {
+        let won = history.collected.get(name).copied().unwrap_or(0.0);
+        let committed = history.committed.get(name).copied().unwrap_or(0.0);
+        (won - committed) / bb
+    };
+
+    let mut samples = Vec::new();
+    for (name, stage, action) in &history.actions {
+        let player = index(name).ok_or("Unknown player")? as u64;
+        if state.final_state || state.current_player != player || state.stage != *stage {
+            return Err(format!("Engine disagrees at {} {:?}", name, action));
+        }
+
+        let engine_action = match *action {
+            HistoryAction::Fold => Action::new(ActionEnum::Fold, 0.0),
+            HistoryAction::Check | HistoryAction::Call(_) => {
+                Action::new(ActionEnum::CheckCall, 0.0)
+            }
+            HistoryAction::Bet(amount) | HistoryAction::RaiseTo(amount) => {
+                Action::new(ActionEnum::BetRaise, amount)
+            }
+        };
+        let next = state.apply_action(engine_action);
+        let record = next.action_list.last().ok_or("No action recorded")?;
+        let expected = match *action {
+            HistoryAction::Call(amount)
+            | HistoryAction::Bet(amount)
+            | HistoryAction::RaiseTo(amount) => amount,
+            _ => record.action.amount,
+        };
+        if !matches!(next.status, StateStatus::Ok)
+            || record.action.action != engine_action.action
+            || (record.action.amount - expected).abs() > 1e-6
+        {
+            return Err(format!("Engine disagrees at {} {:?}", name, action));
+        }
+
+        if history.hands.contains_key(name) {
+            samples.push(DecisionSample {
+                hand_id: state.hand_id.clone(),
+                player: name.clone(),
+                stage: *stage,
+                observation: observation(&state, player),
+                action: Action::new(record.action.action, record.action.amount / bb),
+                coercion: record.coercion,
+                reward: reward(name),
+            });
+        }
+        state = next;
+    }
+    Ok(samples)
+}
+
+/// Samples of every decision of a hand of `compress_hands`, played again from the state it was
+/// dealt in to `last`. Every hand is known, and players go by their seat number.
+fn replay_decoded(mut state: State, last: &State) -> Vec<DecisionSample> {
+    let bb = last.bb;
+    let mut samples = Vec::with_capacity(last.action_list.len());
+    for record in &last.action_list {
+        let player = record.player;
+        samples.push(DecisionSample {
+            hand_id: last.hand_id.clone(),
+            player: player.to_string(),
+            stage: record.stage,
+            observation: observation(&state, player),
+            action: Action::new(record.action.action, record.action.amount / bb),
+            coercion: record.coercion,
+            reward: last.players_state[player as usize].reward / bb,
+        });
+        state = state.apply_action(record.action);
+    }
+    samples
+}
+
+fn encode_card(features: &mut [f32], card: Card) {
+    features[card.rank as usize] = 1.0;
+    features[13 + card.suit as usize] = 1.0;
+}
+
+/// Fixed-size encoding of `state` from `player`'s point of view, chip amounts in big blinds:
+/// hole cards and the 5 board slots (13 rank + 4 suit one-hot each), stage one-hot, then for
+/// each seat from the small blind on: stake, bet, chips in the pot, still in the hand and
+/// whether it is `player`; last the pot and the amount `player` has to call.
+pub fn observation(state: &State, player: u64) -> Vec<f32> {
+    let mut obs = vec![0.0f32; OBSERVATION_SIZE];
+    let bb = state.bb as f32;
+    let n = state.players_state.len();
+
+    let hand = state.players_state[player as usize].hand;
+    encode_card(&mut obs[0..CARD_FEATURES], hand.0);
+    encode_card(&mut obs[CARD_FEATURES..2 * CARD_FEATURES], hand.1);
+    for (i, card) in state.public_cards.iter().take(5).enumerate() {
+        let start = (2 + i) * CARD_FEATURES;
+        encode_card(&mut obs[start..start + CARD_FEATURES], *card);
+    }
+
+    let mut offset = STAGE_OFFSET;
+    obs[offset + state.stage as usize] = 1.0;
+    offset += STAGE_FEATURES;
+
+    for i in 0..n.min(MAX_SEATS) {
+        let ps = &state.players_state[(state.button as usize + 1 + i) % n];
+        let seat = &mut obs[offset + i * SEAT_FEATURES..offset + (i + 1) * SEAT_FEATURES];
+        seat[0] = ps.stake as f32 / bb;
+        seat[1] = ps.bet_chips as f32 / bb;
+        seat[2] = ps.pot_chips as f32 / bb;
+        seat[3] = if ps.active { 1.0 } else { 0.0 };
+        seat[4] = if ps.player == player { 1.0 } else { 0.0 };
+    }
+    offset += MAX_SEATS * SEAT_FEATURES;
+
+    let max_bet = state
+        .players_state
+        .iter()
+        .map(|ps| ps.bet_chips)
+        .fold(0.0, f64::max);
+    obs[offset] = state.pot as f32 / bb;
+    obs[offset + 1] = (max_bet - state.players_state[player as usize].bet_chips) as f32 / bb;
+    obs
+}
+
+/// See `observation`
+#[pyfunction]
+#[pyo3(name = "observation")]
+pub fn py_observation(state: &State, player: u64) -> PyResult<Vec<f32>> {
+    if player as usize >= state.players_state.len() {
+        return Err(PyValueError::new_err(format!(
+            "Player {} is not at the table",
+            player
+        )));
+    }
+    Ok(observation(state, player))
+}
+
+fn decode_card(features: &[f32]) -> Option<Card> {
+    let rank = CardRank::iter().nth(features[..13].iter().position(|&x| x > 0.5)?)?;
+    let suit = CardSuit::iter().nth(features[13..].iter().position(|&x| x > 0.5)?)?;
+    Some(Card::new(suit, rank))
+}
+
+/// A complete `State` consistent with `observation(state, player)` taken at `player`'s
+/// decision, the cards the player cannot see dealt with `State::redeal_unknown`. The
+/// observation has no seat numbers, blinds or history: player `i` sits at seat `i` from the
+/// small blind (the button is the last seat), the big blind is 1 and a player has acted on
+/// the street when they come before the hero in the betting order or put in more than their
+/// blind.
+#[pyfunction]
+#[pyo3(signature = (obs, seed, constraints=None))]
+pub fn complete_observation(
+    obs: Vec<f32>,
+    seed: u64,
+    constraints: Option<HashMap<u64, HandRange>>,
+) -> PyResult<State> {
+    if obs.len() != OBSERVATION_SIZE {
+        return Err(PyValueError::new_err(format!(
+            "An observation has {} values, got {}",
+            OBSERVATION_SIZE,
+            obs.len()
+        )));
+    }
+
+    let card = |slot: usize| decode_card(&obs[slot * CARD_FEATURES..(slot + 1) * CARD_FEATURES]);
+    let hand = match (card(0), card(1)) {
+        (Some(a), Some(b)) => (a, b),
+        _ => return Err(PyValueError::new_err("The observation has no hole cards")),
+    };
+    let board: Vec<Card> = (2..7).map_while(card).collect();
+
+    let mut offset = STAGE_OFFSET;
+    let stage = match obs[offset..offset + STAGE_FEATURES]
+        .iter()
+        .position(|&x| x > 0.5)
+    {
+        Some(0) => Stage::Preflop,
+        Some(1) => Stage::Flop,
+        Some(2) => Stage::Turn,
+        Some(3) => Stage::River,
+        _ => {
+            return Err(PyValueError::new_err(
+                "The observation is not at a decision",
+            ))
+        }
+    };
+    let board_size = stage.board_card_count();
+    if board.len() != board_size {
+        return Err(PyValueError::new_err(format!(
+            "{:?} observation with {} board cards",
+            stage,
+            board.len()
+        )));
+    }
+    offset += STAGE_FEATURES;
+
+    let seats: Vec<&[f32]> = obs[offset..offset + MAX_SEATS * SEAT_FEATURES]
+        .chunks(SEAT_FEATURES)
+        .collect();
+    let n = seats
+        .iter()
+        .rposition(|seat| seat.iter().any(|&x| x != 0.0))
+        .map_or(0, |i| i + 1);
+    if n < 2 {
+        return Err(PyValueError::new_err(
+            "The observation has fewer than 2 players",
+        ));
+    }
+    let hero = seats[..n]
+        .iter()
+        .position(|seat| seat[4] > 0.5)
+        .ok_or_else(|| PyValueError::new_err("The observation has no acting player"))?;
+    offset += MAX_SEATS * SEAT_FEATURES;
+
+    let mut state = State::from_seed(n as u64, n as u64 - 1, 0.5, 1.0, 100.0, seed, false, 0.0)?;
+    // Seats in betting order start from the first player to act on the street
+    let first = if stage == Stage::Preflop && n > 2 {
+        2
+    } else {
+        0
+    };
+    let order = |i: usize| (i + n - first) % n;
+    for (i, seat) in seats[..n].iter().enumerate() {
+        let blind = match (stage, i) {
+            (Stage::Preflop, 0) => 0.5,
+            (Stage::Preflop, 1) => 1.0,
+            _ => 0.0,
+        };
+        let ps = &mut state.players_state[i];
+        ps.stake = seat[0] as f64;
+        ps.bet_chips = seat[1] as f64;
+        ps.pot_chips = seat[2] as f64;
+        ps.active = seat[3] > 0.5;
+        let acted = order(i) < order(hero) || ps.bet_chips > blind;
+        ps.last_stage_action = acted.then_some(ActionEnum::CheckCall);
+    }
+    state.players_state[hero].hand = hand;
+
+    let max_bet = state
+        .players_state
+        .iter()
+        .map(|ps| ps.bet_chips)
+        .fold(0.0, f64::max);
+    state.min_bet = if stage == Stage::Preflop {
+        max_bet.max(1.0)
+    } else {
+        max_bet
+    };
+    state.pot = obs[offset] as f64;
+    state.stage = stage;
+    state.public_cards = board.into();
+    state.current_player = hero as u64;
+    state.deck = Card::collect()
+        .into_iter()
+        .filter(|c| *c != hand.0 && *c != hand.1 && !state.public_cards.contains(c))
+        .collect();
+    state.legal_actions = available_actions(&state);
+
+    redeal_unknown(&state, seed, hero as u64, &constraints.unwrap_or_default())
+        .map_err(PyValueError::new_err)
+}
+
+/// Observations of consecutive rows as a fixed-size list column
+#[cfg(feature = "parquet")]
+pub(crate) fn observation_array(
+    values: impl IntoIterator<Item = f32>,
+) -> Result<arrow::array::FixedSizeListArray, String> {
+    use arrow::array::{FixedSizeListArray, Float32Array};
+    use arrow::datatypes::{DataType, Field};
+    use std::sync::Arc;
+
+    FixedSizeListArray::try_new(
+        Arc::new(Field::new("item", DataType::Float32, false)),
+        OBSERVATION_SIZE as i32,
+        Arc::new(Float32Array::from_iter_values(values)),
+        None,
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Record batch of non-nullable named columns
+#[cfg(feature = "parquet")]
+pub(crate) fn record_batch(
+    columns: Vec<(&str, arrow::array::ArrayRef)>,
+) -> Result<arrow::record_batch::RecordBatch, String> {
+    use arrow::datatypes::{Field, Schema};
+    use arrow::record_batch::RecordBatch;
+    use std::sync::Arc;
+
+    let schema = Arc::new(Schema::new(
+        columns
+            .iter()
+            .map(|(name, array)| Field::new(*name, array.data_type().clone(), false))
+            .collect::<Vec<_>>(),
+    ));
+    RecordBatch::try_new(schema, columns.into_iter().map(|(_, a)| a).collect())
+        .map_err(|e| e.to_string())
+}
+
+/// Writer properties tagging the file with the `pokers.schema_version` it was written with
+#[cfg(feature = "parquet")]
+pub(crate) fn writer_properties() -> parquet::file::properties::WriterProperties {
+    use crate::state::schema::SCHEMA_VERSION;
+    use parquet::file::metadata::KeyValue;
+    use parquet::file::properties::WriterProperties;
+
+    WriterProperties::builder()
+        .set_key_value_metadata(Some(vec![KeyValue::new(
+            "pokers.schema_version".to_owned(),
+            SCHEMA_VERSION.to_string(),
+        )]))
+        .build()
+}
+
+#[cfg(feature = "parquet")]
+fn write_parquet(samples: &[DecisionSample], path: &Path) -> Result<(), String> {
+    use arrow::array::{ArrayRef, Float64Array, StringArray, UInt8Array};
+    use parquet::arrow::ArrowWriter;
+    use std::sync::Arc;
+
+    let observations =
+        observation_array(samples.iter().flat_map(|s| s.observation.iter().copied()))?;
+
+    let columns: Vec<(&str, ArrayRef)> = vec![
+        (
+            "hand_id",
+            Arc::new(StringArray::from_iter_values(
+                samples.iter().map(|s| &s.hand_id),
+            )),
+        ),
+        (
+            "player",
+            Arc::new(StringArray::from_iter_values(
+                samples.iter().map(|s| &s.player),
+            )),
+        ),
+        (
+            "stage",
+            Arc::new(UInt8Array::from_iter_values(
+                samples.iter().map(|s| s.stage as u8),
+            )),
+        ),
+        ("observation", Arc::new(observations)),
+        (
+            "action",
+            Arc::new(UInt8Array::from_iter_values(
+                samples.iter().map(|s| s.action.action as u8),
+            )),
+        ),
+        (
+            "amount",
+            Arc::new(Float64Array::from_iter_values(
+                samples.iter().map(|s| s.action.amount),
+            )),
+        ),
+        (
+            "coercion",
+            Arc::new(UInt8Array::from_iter_values(
+                samples.iter().map(|s| s.coercion.map_or(0, |c| c as u8)),
+            )),
+        ),
+        (
+            "reward",
+            Arc::new(Float64Array::from_iter_values(
+                samples.iter().map(|s| s.reward),
+            )),
+        ),
+    ];
+    let batch = record_batch(columns)?;
+
+    let file = std::fs::File::create(path).map_err(|e| e.to_string())?;
+    let mut writer = ArrowWriter::try_new(file, batch.schema(), Some(writer_properties()))
+        .map_err(|e| e.to_string())?;
+    writer.write(&batch).map_err(|e| e.to_string())?;
+    writer.close().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[cfg(not(feature = "parquet"))]
+fn write_parquet(_samples: &[DecisionSample], _path: &Path) -> Result<(), String> {
+    Err("pokers was built without the `parquet` feature".to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HAND: &str = "\
+PokerStars Hand #230000000001:  Hold'em No Limit ($0.05/$0.10 USD) - 2024/01/01 12:00:00 ET
+Table 'Alpha' 6-max Seat #1 is the button
+Seat 1: Hero ($10 in chips)
+Seat 2: Villain ($12.50 in chips)
+Seat 3: Other ($8 in chips)
+Villain: posts small blind $0.05
+Other: posts big blind $0.10
+*** HOLE CARDS ***
+Dealt to Hero [Ah Kd]
+Hero: raises $0.20 to $0.30
+Villain: folds
+Other: calls $0.20
+*** FLOP *** [Ac 7d 2h]
+Other: checks
+Hero: bets $0.40
+Other: calls $0.40
+*** TURN *** [Ac 7d 2h] [9s]
+Other: checks
+Hero: bets $1
+Other: folds
+Uncalled bet ($1) returned to Hero
+Hero collected $1.43 from pot
+*** SUMMARY ***
+Total pot $1.45 | Rake $0.02
+Seat 1: Hero (button) collected ($1.43)
+";
+
+    #[test]
+    fn replays_the_hero_decisions() {
+        let history = parse_hand(HAND).unwrap();
+        assert_eq!(history.id, "230000000001");
+        assert_eq!(history.players.len(), 3);
+        assert_eq!(history.committed["Hero"], 70.0);
+
+        let samples = replay(&history).unwrap();
+        let actions: Vec<(Stage, ActionEnum, f64)> = samples
+            .iter()
+            .map(|s| (s.stage, s.action.action, s.action.amount))
+            .collect();
+        assert_eq!(
+            actions,
+            vec![
+                (Stage::Preflop, ActionEnum::BetRaise, 3.0),
+                (Stage::Flop, ActionEnum::BetRaise, 4.0),
+                (Stage::Turn, ActionEnum::BetRaise, 10.0),
+            ]
+        );
+        assert!(samples.iter().all(|s| s.coercion.is_none()));
+        // Won 1.43 after putting in 0.70
+        assert!((samples[0].reward - 7.3).abs() < 1e-9);
+
+        let obs = &samples[0].observation;
+        assert_eq!(obs.len(), OBSERVATION_SIZE);
+        assert_eq!(obs[12], 1.0); // ace
+        assert_eq!(obs[13 + 2], 1.0); // of hearts
+
+        // Facing the big blind with nothing in: 1 big blind to call
+        assert_eq!(obs[OBSERVATION_SIZE - 1], 1.0);
+
+        assert_eq!(split_hands(&format!("{HAND}\n\n{HAND}")).len(), 2);
+    }
+
+    #[test]
+    fn completed_observation_plays_on() {
+        let state = State::from_seed(4, 0, 0.5, 1.0, 100.0, 3, false, 0.0).unwrap();
+        let state = state.apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+        let obs = observation(&state, state.current_player);
+
+        let completed = complete_observation(obs.clone(), 11, None).unwrap();
+        assert_eq!(observation(&completed, completed.current_player), obs);
+        assert!(completed.check_invariants().is_empty());
+
+        // The button limps behind, the small blind completes and the big blind checks
+        let mut state = state;
+        let mut completed = completed;
+        for _ in 0..3 {
+            let action = Action::new(ActionEnum::CheckCall, 0.0);
+            state = state.apply_action(action);
+            completed = completed.apply_action(action);
+        }
+        assert_eq!(state.stage, Stage::Flop);
+        assert_eq!(completed.stage, Stage::Flop);
+        assert_eq!(completed.pot, state.pot);
+
+        assert!(complete_observation(vec![0.0; OBSERVATION_SIZE], 11, None).is_err());
+        assert!(py_observation(&state, 4).is_err());
+    }
+
+    #[test]
+    fn compressed_hands_are_loaded_with_every_decision() {
+        let mut state = State::from_seed(3, 0, 0.5, 1.0, 100.0, 7, false, 0.0).unwrap();
+        let mut taken = Vec::new();
+        while !state.final_state {
+            let action = match state.stage {
+                Stage::Preflop if taken.is_empty() => Action::new(ActionEnum::BetRaise, 3.0),
+                _ => Action::new(ActionEnum::CheckCall, 0.0),
+            };
+            taken.push((state.stage, action.action));
+            state = state.apply_action(action);
+        }
+        let dir = std::env::temp_dir().join(format!("pokers_compressed_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let bytes = hand_codec::encode_hands(std::slice::from_ref(&state), 10).unwrap();
+        std::fs::write(dir.join("hands.bin"), bytes).unwrap();
+        std::fs::write(dir.join("hands.txt"), HAND).unwrap();
+
+        let dataset = load_hand_histories(dir.to_str().unwrap()).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!((dataset.hands, dataset.skipped), (2, 0));
+        let decoded: Vec<&DecisionSample> = dataset
+            .samples
+            .iter()
+            .filter(|s| s.hand_id == state.hand_id)
+            .collect();
+        let actions: Vec<(Stage, ActionEnum)> =
+            decoded.iter().map(|s| (s.stage, s.action.action)).collect();
+        assert_eq!(actions, taken);
+        assert_eq!(decoded[0].action.amount, 3.0);
+        let first = &state.action_list[0];
+        assert_eq!(decoded[0].player, first.player.to_string());
+        assert_eq!(
+            decoded[0].reward,
+            state.players_state[first.player as usize].reward
+        );
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn parquet_has_one_row_per_decision() {
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+
+        let samples = replay(&parse_hand(HAND).unwrap()).unwrap();
+        let path =
+            std::env::temp_dir().join(format!("pokers_dataset_{}.parquet", std::process::id()));
+        write_parquet(&samples, &path).unwrap();
+
+        let reader = SerializedFileReader::new(std::fs::File::open(&path).unwrap()).unwrap();
+        let metadata = reader.metadata().file_metadata();
+        assert_eq!(metadata.num_rows(), 3);
+        let version = metadata
+            .key_value_metadata()
+            .and_then(|kv| kv.iter().find(|kv| kv.key == "pokers.schema_version"))
+            .and_then(|kv| kv.value.clone());
+        assert_eq!(
+            version,
+            Some(crate::state::schema::SCHEMA_VERSION.to_string())
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+}
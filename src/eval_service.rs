@@ -0,0 +1,547 @@
+// eval_service.rs - Hand evaluation and equities over a minimal local HTTP API
+//
+// A service for backends in other languages that only need to rank hands and compute equities,
+// without hosting tables. Requests are handled on the connection's own thread without any
+// runtime, so a kept-alive connection answers in microseconds.
+use crate::cards::live_cards;
+use crate::evaluator::{pack_rank, HandRank};
+use crate::state::card::Card;
+use crate::state::rules::GameVariant;
+use itertools::Itertools;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+
+/// Runouts enumerated exactly, above which they are sampled
+const EXACT_RUNOUTS: usize = 50_000;
+
+/// Largest request body read
+const MAX_BODY: usize = 1 << 16;
+
+/// Longest request or header line read
+const MAX_LINE: usize = 8 << 10;
+
+/// Most headers read for a request
+const MAX_HEADERS: usize = 100;
+
+/// Most runouts sampled for one request
+const MAX_SAMPLES: usize = 1_000_000;
+
+/// Body of `POST /rank`
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RankRequest {
+    hands: Vec<[String; 2]>,
+    board: Vec<String>,
+    #[serde(default)]
+    variant: GameVariant,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+struct RankResponse {
+    /// Rank of each hand packed into one integer as by `batch_evaluate`, lower is stronger
+    ranks: Vec<u64>,
+    /// Indices of the best hands
+    winners: Vec<usize>,
+}
+
+/// Body of `POST /equity`
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EquityRequest {
+    hands: Vec<[String; 2]>,
+    #[serde(default)]
+    board: Vec<String>,
+    #[serde(default = "default_samples")]
+    samples: usize,
+    #[serde(default)]
+    seed: u64,
+    #[serde(default)]
+    variant: GameVariant,
+}
+
+fn default_samples() -> usize {
+    10_000
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+struct EquityResponse {
+    /// Share of the pot each hand wins on average, ties split
+    equity: Vec<f64>,
+    /// Whether every runout was enumerated, or `runouts` of them sampled
+    exact: bool,
+    runouts: usize,
+}
+
+fn parse_card(name: &str) -> Result<Card, String> {
+    Card::from_string(name.to_owned()).ok_or_else(|| format!("Invalid card '{}'", name))
+}
+
+type Hands = Vec<(Card, Card)>;
+
+/// The hands and board of a request, with no card used twice
+fn parse_cards(hands: &[[String; 2]], board: &[String]) -> Result<(Hands, Vec<Card>), String> {
+    let hands = hands
+        .iter()
+        .map(|[a, b]| Ok((parse_card(a)?, parse_card(b)?)))
+        .collect::<Result<Vec<_>, String>>()?;
+    let board = board
+        .iter()
+        .map(|name| parse_card(name))
+        .collect::<Result<Vec<_>, String>>()?;
+    let duplicate = board
+        .iter()
+        .chain(hands.iter().flat_map(|(a, b)| [a, b]))
+        .duplicates()
+        .next()
+        .copied();
+    if let Some(card) = duplicate {
+        return Err(format!("Card {} is used more than once", card));
+    }
+    Ok((hands, board))
+}
+
+/// Indices of the lowest ranks
+fn winners(ranks: &[HandRank]) -> Vec<usize> {
+    let best = ranks.iter().min();
+    ranks.iter().positions(|rank| Some(rank) == best).collect()
+}
+
+fn rank(request: RankRequest) -> Result<RankResponse, String> {
+    let (hands, board) = parse_cards(&request.hands, &request.board)?;
+    if !(3..=5).contains(&board.len()) {
+        return Err(format!(
+            "The board must have 3 to 5 cards, got {}",
+            board.len()
+        ));
+    }
+    let evaluator = request.variant.evaluator();
+    let ranks: Vec<HandRank> = hands
+        .iter()
+        .map(|&hand| evaluator.rank_hand(hand, &board))
+        .collect();
+    Ok(RankResponse {
+        ranks: ranks.iter().map(|&rank| pack_rank(rank)).collect(),
+        winners: winners(&ranks),
+    })
+}
+
+/// Number of ways to draw `k` of `n` cards, saturating
+fn combinations(n: usize, k: usize) -> usize {
+    (0..k).fold(1usize, |c, i| {
+        c.saturating_mul(n.saturating_sub(i)) / (i + 1)
+    })
+}
+
+fn equity(request: EquityRequest) -> Result<EquityResponse, String> {
+    let (hands, board) = parse_cards(&request.hands, &request.board)?;
+    if hands.len() < 2 {
+        return Err("Equities take at least two hands".to_owned());
+    }
+    if ![0, 3, 4, 5].contains(&board.len()) {
+        return Err(format!(
+            "The board must have 0 or 3 to 5 cards, got {}",
+            board.len()
+        ));
+    }
+    let missing = 5 - board.len();
+    if 2 * hands.len() + board.len() + missing > 52 {
+        return Err(format!(
+            "{} hands and a board of 5 take more than the 52 cards of the deck",
+            hands.len()
+        ));
+    }
+    if request.samples > MAX_SAMPLES {
+        return Err(format!(
+            "At most {} runouts are sampled, got {}",
+            MAX_SAMPLES, request.samples
+        ));
+    }
+    let evaluator = request.variant.evaluator();
+    let mut dead = board.clone();
+    dead.extend(hands.iter().flat_map(|&(a, b)| [a, b]));
+    let mut unseen = live_cards(&dead);
+
+    let mut equity = vec![0.0; hands.len()];
+    let mut runout = board.clone();
+    let mut add = |runout: &[Card]| {
+        let ranks: Vec<HandRank> = hands
+            .iter()
+            .map(|&hand| evaluator.rank_hand(hand, runout))
+            .collect();
+        let winners = winners(&ranks);
+        for &winner in &winners {
+            equity[winner] += 1.0 / winners.len() as f64;
+        }
+    };
+    let exact = combinations(unseen.len(), missing) <= EXACT_RUNOUTS;
+    let runouts = if exact {
+        let mut n = 0;
+        for cards in unseen.iter().combinations(missing) {
+            runout.truncate(board.len());
+            runout.extend(cards.into_iter().copied());
+            add(&runout);
+            n += 1;
+        }
+        n
+    } else {
+        let mut rng = StdRng::seed_from_u64(request.seed);
+        for _ in 0..request.samples.max(1) {
+            let (cards, _) = unseen.partial_shuffle(&mut rng, missing);
+            runout.truncate(board.len());
+            runout.extend_from_slice(cards);
+            add(&runout);
+        }
+        request.samples.max(1)
+    };
+    Ok(EquityResponse {
+        equity: equity.iter().map(|e| e / runouts as f64).collect(),
+        exact,
+        runouts,
+    })
+}
+
+/// Status and JSON body answering `method path` with `body`
+pub fn handle(method: &str, path: &str, body: &[u8]) -> (u16, String) {
+    fn answer<T, R: Serialize>(body: &[u8], f: impl Fn(T) -> Result<R, String>) -> (u16, String)
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        let result = serde_json::from_slice::<T>(body)
+            .map_err(|e| e.to_string())
+            .and_then(f);
+        match result {
+            Ok(response) => (200, serde_json::to_string(&response).unwrap_or_default()),
+            Err(msg) => (400, json!({ "error": msg }).to_string()),
+        }
+    }
+    match (method, path) {
+        ("POST", "/rank") => answer(body, rank),
+        ("POST", "/equity") => answer(body, equity),
+        ("GET", "/health") => (200, json!({ "ok": true }).to_string()),
+        _ => (
+            404,
+            json!({ "error": format!("No route {} {}", method, path) }).to_string(),
+        ),
+    }
+}
+
+/// Read a line into `line` like `BufRead::read_line`, failing on lines longer than `MAX_LINE`
+fn read_line(reader: &mut impl BufRead, line: &mut String) -> std::io::Result<usize> {
+    let read = reader.take(MAX_LINE as u64).read_line(line)?;
+    if read == MAX_LINE && !line.ends_with('\n') {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "The request line or a header is too long",
+        ));
+    }
+    Ok(read)
+}
+
+/// Answer the requests of one kept-alive connection until it closes. Fails on request lines
+/// or headers that are too long, or too many headers.
+pub fn serve_connection<S: Read + Write>(stream: S) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if read_line(&mut reader, &mut line)? == 0 {
+            return Ok(());
+        }
+        let mut parts = line.split_whitespace();
+        let method = parts.next().unwrap_or_default().to_owned();
+        let path = parts.next().unwrap_or_default().to_owned();
+
+        let mut length = Some(0);
+        let mut close = false;
+        for headers in 0.. {
+            if headers == MAX_HEADERS {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "The request has too many headers",
+                ));
+            }
+            let mut header = String::new();
+            if read_line(&mut reader, &mut header)? == 0 {
+                return Ok(());
+            }
+            let header = header.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                let value = value.trim();
+                if name.eq_ignore_ascii_case("content-length") {
+                    length = value.parse().ok();
+                } else if name.eq_ignore_ascii_case("connection") {
+                    close = value.eq_ignore_ascii_case("close");
+                }
+            }
+        }
+        // Without a valid length, the end of the body and the next request are unknown
+        let (status, response) = match length {
+            None => {
+                close = true;
+                (
+                    400,
+                    json!({ "error": "Invalid Content-Length" }).to_string(),
+                )
+            }
+            Some(length) if length > MAX_BODY => {
+                close = true;
+                (
+                    413,
+                    json!({ "error": "The request is too large" }).to_string(),
+                )
+            }
+            Some(length) => {
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body)?;
+                handle(&method, &path, &body)
+            }
+        };
+
+        let reason = match status {
+            200 => "OK",
+            400 => "Bad Request",
+            404 => "Not Found",
+            _ => "Payload Too Large",
+        };
+        let stream = reader.get_mut();
+        write!(
+            stream,
+            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n{}\r\n{}",
+            status,
+            reason,
+            response.len(),
+            if close { "Connection: close\r\n" } else { "" },
+            response
+        )?;
+        stream.flush()?;
+        if close {
+            return Ok(());
+        }
+    }
+}
+
+/// Serve on `address`, `host:port` over TCP or `unix:<path>` over a Unix socket, one thread
+/// per connection. A socket left at `path` by a previous server is replaced, anything else
+/// there is an error.
+pub fn serve(address: &str) -> std::io::Result<()> {
+    #[cfg(unix)]
+    if let Some(path) = address.strip_prefix("unix:") {
+        use std::os::unix::fs::FileTypeExt;
+        match std::fs::symlink_metadata(path) {
+            Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(path)?,
+            Ok(_) => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::AlreadyExists,
+                    format!("{} exists and is not a socket", path),
+                ))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        let listener = std::os::unix::net::UnixListener::bind(path)?;
+        for stream in listener.incoming() {
+            let stream = stream?;
+            std::thread::spawn(move || serve_connection(stream));
+        }
+        return Ok(());
+    }
+    let listener = TcpListener::bind(address)?;
+    for stream in listener.incoming() {
+        let stream = stream?;
+        stream.set_nodelay(true)?;
+        std::thread::spawn(move || serve_connection(stream));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpStream;
+
+    #[test]
+    fn ranks_and_equities() {
+        let (status, body) = handle(
+            "POST",
+            "/rank",
+            br#"{"hands": [["SA", "HA"], ["D7", "C2"]], "board": ["DA", "CA", "S9"]}"#,
+        );
+        assert_eq!(status, 200);
+        let response: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(response["winners"], json!([0]));
+        // Four of a kind
+        assert_eq!(response["ranks"][0].as_u64().unwrap() >> 40, 3);
+
+        // Aces against kings on a flop of blanks: every runout enumerated
+        let (_, body) = handle(
+            "POST",
+            "/equity",
+            br#"{"hands": [["SA", "HA"], ["SK", "HK"]], "board": ["D2", "C7", "D9"]}"#,
+        );
+        let response: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(response["exact"], json!(true));
+        assert_eq!(response["runouts"], json!(990));
+        let aces = response["equity"][0].as_f64().unwrap();
+        let kings = response["equity"][1].as_f64().unwrap();
+        assert!(aces > 0.9 && (aces + kings - 1.0).abs() < 1e-9);
+
+        // Preflop the runouts are sampled
+        let (_, body) = handle(
+            "POST",
+            "/equity",
+            br#"{"hands": [["SA", "HA"], ["SK", "HK"]], "samples": 2000, "seed": 1}"#,
+        );
+        let response: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(response["exact"], json!(false));
+        assert_eq!(response["runouts"], json!(2000));
+
+        let bad = [
+            br#"{"hands": [["SA", "SA"]], "board": ["DA", "CA", "S9"]}"#.as_slice(),
+            br#"{"hands": [["SA", "XX"]], "board": ["DA", "CA", "S9"]}"#,
+            br#"{"hands": [["SA", "HA"]], "board": ["DA"]}"#,
+            b"not json",
+        ];
+        for body in bad {
+            assert_eq!(handle("POST", "/rank", body).0, 400);
+        }
+        // 24 hands leave 4 cards for the board
+        let cards: Vec<String> = "CDHS"
+            .chars()
+            .cartesian_product("23456789TJQKA".chars())
+            .map(|(suit, rank)| format!("{}{}", suit, rank))
+            .collect();
+        let hands: Vec<_> = cards.chunks(2).take(24).collect();
+        let bad = [
+            json!({ "hands": hands }),
+            json!({ "hands": [["SA", "HA"], ["SK", "HK"]], "samples": MAX_SAMPLES + 1 }),
+        ];
+        for body in bad {
+            let (status, body) = handle("POST", "/equity", body.to_string().as_bytes());
+            assert_eq!(status, 400);
+            assert!(!body.contains("more than once"), "{}", body);
+        }
+        assert_eq!(combinations(3, 5), 0);
+        assert_eq!(handle("GET", "/rank", b"").0, 404);
+    }
+
+    #[test]
+    fn answers_kept_alive_connections() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            serve_connection(stream).unwrap();
+        });
+
+        let mut stream = TcpStream::connect(address).unwrap();
+        let body = r#"{"hands": [["SA", "HA"], ["D7", "C2"]], "board": ["DA", "CA", "S9"]}"#;
+        let request = format!(
+            "POST /rank HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        for _ in 0..2 {
+            stream.write_all(request.as_bytes()).unwrap();
+            let mut status = String::new();
+            reader.read_line(&mut status).unwrap();
+            assert_eq!(status, "HTTP/1.1 200 OK\r\n");
+            let mut length = 0;
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                if header == "\r\n" {
+                    break;
+                }
+                if let Some(value) = header.strip_prefix("Content-Length: ") {
+                    length = value.trim().parse().unwrap();
+                }
+            }
+            let mut response = vec![0; length];
+            reader.read_exact(&mut response).unwrap();
+            let response: serde_json::Value = serde_json::from_slice(&response).unwrap();
+            assert_eq!(response["winners"], json!([0]));
+        }
+    }
+
+    #[test]
+    fn rejects_long_header_lines() {
+        let request = format!(
+            "GET /health HTTP/1.1\r\nHost: {}\r\n\r\n",
+            "a".repeat(MAX_LINE)
+        );
+        assert!(serve_connection(std::io::Cursor::new(request.into_bytes())).is_err());
+        let request = format!(
+            "GET /health HTTP/1.1\r\n{}\r\n",
+            "Host: a\r\n".repeat(MAX_HEADERS)
+        );
+        assert!(serve_connection(std::io::Cursor::new(request.into_bytes())).is_err());
+    }
+
+    #[test]
+    fn closes_on_an_invalid_content_length() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            serve_connection(stream).unwrap();
+        });
+
+        // The pipelined request after it is not read as its body
+        let mut stream = TcpStream::connect(address).unwrap();
+        let request = "POST /rank HTTP/1.1\r\nContent-Length: 12abc\r\n\r\n\
+                       GET /health HTTP/1.1\r\n\r\n";
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(
+            response.starts_with("HTTP/1.1 400 Bad Request\r\n"),
+            "{}",
+            response
+        );
+        assert!(response.contains("Connection: close\r\n"));
+        assert_eq!(response.matches("HTTP/1.1").count(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn replaces_only_stale_sockets() {
+        let path = std::env::temp_dir().join(format!("pokers_eval_{}", std::process::id()));
+        let address = format!("unix:{}", path.display());
+
+        // A regular file is left alone
+        std::fs::write(&path, "keep me").unwrap();
+        let error = serve(&address).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::AlreadyExists);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "keep me");
+        std::fs::remove_file(&path).unwrap();
+
+        // The socket of a server that is gone is bound again
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+        std::thread::spawn(move || serve(&address));
+        let mut stream = None;
+        for _ in 0..1000 {
+            if let Ok(connected) = std::os::unix::net::UnixStream::connect(&path) {
+                stream = Some(connected);
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        let mut stream = stream.unwrap();
+        stream
+            .write_all(b"GET /health HTTP/1.1\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+        let _ = std::fs::remove_file(&path);
+    }
+}
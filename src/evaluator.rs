@@ -0,0 +1,530 @@
+// evaluator.rs - Hand evaluators of the game variants and stateless comparison helpers
+use crate::game_logic::{high_card_value, rank_card_combination};
+use crate::state::card::{Card, CardRank};
+use crate::state::rules::GameVariant;
+use itertools::Itertools;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::cmp::Ordering;
+
+/// Strength of a hand, lower tuples are stronger: `(category, value, kicker)`
+pub type HandRank = (u64, u64, u64);
+
+/// Rank of fewer than five cards
+const NO_HAND: HandRank = (10, 0, 0);
+
+/// One integer per hand rank with the same order: lower is still stronger
+pub fn pack_rank((category, value, kicker): HandRank) -> u64 {
+    // Values and kickers are below 13^5 < 2^20
+    category << 40 | value << 20 | kicker
+}
+
+/// Ranks the hands of one game variant. The engine only compares `HandRank`s, so a new variant
+/// needs an implementation and a `GameVariant`, not changes to the showdown code.
+pub trait Evaluator: Sync {
+    /// Best five-card hand out of `cards`
+    fn eval(&self, cards: &[Card]) -> HandRank;
+
+    fn eval7(&self, cards: [Card; 7]) -> HandRank {
+        self.eval(&cards)
+    }
+
+    /// Best hand out of the hole cards and the board
+    fn rank_hand(&self, hand: (Card, Card), board: &[Card]) -> HandRank {
+        let mut cards = board.to_vec();
+        cards.extend([hand.0, hand.1]);
+        self.eval(&cards)
+    }
+
+    /// Indices of the five cards making the best hand, into the hole cards followed by the
+    /// board. Empty with fewer than five cards.
+    fn best_five(&self, hand: (Card, Card), board: &[Card]) -> Vec<u8> {
+        let mut cards = vec![hand.0, hand.1];
+        cards.extend(board);
+        (0..cards.len() as u8)
+            .combinations(5)
+            .min_by_key(|five| {
+                let five: Vec<Card> = five.iter().map(|&i| cards[i as usize]).collect();
+                self.eval(&five)
+            })
+            .unwrap_or_default()
+    }
+
+    /// Cards of a five-card hand that only break ties between hands of the same pairs, trips
+    /// or quads
+    fn kickers(&self, five: &[Card]) -> Vec<Card> {
+        unpaired(five)
+    }
+}
+
+/// Cards of `five` whose rank appears once
+fn unpaired(five: &[Card]) -> Vec<Card> {
+    let counts = five.iter().counts_by(|c| c.rank);
+    five.iter()
+        .copied()
+        .filter(|c| counts[&c.rank] == 1)
+        .collect()
+}
+
+/// How the players with the best hand split a pot
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Tie {
+    /// Nobody beats the five community cards
+    Board,
+    /// The same hand, with hole cards of these ranks as kickers, from the highest
+    Kickers(Vec<CardRank>),
+    /// The same made hand, the hole cards play no kicker
+    SameHand,
+}
+
+/// How `hand`, one of the tied best hands on `board`, splits the pot
+pub fn tie(evaluator: &dyn Evaluator, hand: (Card, Card), board: &[Card]) -> Tie {
+    if board.len() == 5 && evaluator.eval(board) == evaluator.rank_hand(hand, board) {
+        return Tie::Board;
+    }
+    let mut cards = vec![hand.0, hand.1];
+    cards.extend(board);
+    let best_five = evaluator.best_five(hand, board);
+    let five: Vec<Card> = best_five.iter().map(|&i| cards[i as usize]).collect();
+    let kickers = evaluator.kickers(&five);
+    let mut ranks: Vec<CardRank> = [hand.0, hand.1]
+        .into_iter()
+        .filter(|c| kickers.contains(c))
+        .map(|c| c.rank)
+        .collect();
+    ranks.sort_by(|a, b| b.cmp(a));
+    if ranks.is_empty() {
+        Tie::SameHand
+    } else {
+        Tie::Kickers(ranks)
+    }
+}
+
+fn best_of_fives(cards: &[Card], rank: impl Fn(&[Card]) -> HandRank) -> HandRank {
+    cards
+        .iter()
+        .copied()
+        .combinations(5)
+        .map(|five| rank(&five))
+        .min()
+        .unwrap_or(NO_HAND)
+}
+
+/// Hold'em rank of exactly five cards, the same as `rank_card_combination` without allocating
+pub(crate) fn rank_five(cards: &[Card; 5]) -> HandRank {
+    let mut ranks = cards.map(|c| c.rank as u64);
+    ranks.sort_unstable();
+    // `high_card_value` of ranks sorted from the lowest
+    let value = |ranks: &[u64]| -> u64 {
+        ranks
+            .iter()
+            .enumerate()
+            .map(|(i, &r)| 13_u64.pow(i as u32) * (12 - r))
+            .sum()
+    };
+
+    let flush = cards.iter().all(|c| c.suit == cards[0].suit);
+    let straight = ranks.windows(2).all(|w| w[1] == w[0] + 1) || ranks == [0, 1, 2, 3, 12];
+    if flush && ranks == [8, 9, 10, 11, 12] {
+        return (1, 0, 0);
+    }
+    if flush && straight {
+        return (2, value(&ranks), 0);
+    }
+
+    let (mut quads, mut trips, mut pairs, mut n_pairs) = (None, None, [0; 2], 0);
+    let mut i = 0;
+    while i < 5 {
+        let n = ranks[i..].iter().take_while(|&&r| r == ranks[i]).count();
+        match n {
+            4 => quads = Some(ranks[i]),
+            3 => trips = Some(ranks[i]),
+            2 => {
+                pairs[n_pairs] = ranks[i];
+                n_pairs += 1;
+            }
+            _ => {}
+        }
+        i += n;
+    }
+
+    match (quads, trips, n_pairs) {
+        (Some(quads), _, _) => (3, value(&[quads]), value(&ranks)),
+        (_, Some(trips), 1) => (4, value(&[trips]), value(&ranks)),
+        _ if flush => (5, value(&ranks), 0),
+        _ if straight => (6, value(&ranks), 0),
+        (_, Some(trips), _) => (7, value(&[trips]), value(&ranks)),
+        (_, _, 2) => (8, value(&pairs), value(&ranks)),
+        (_, _, 1) => (9, value(&pairs[..1]), value(&ranks)),
+        _ => (10, value(&ranks), 0),
+    }
+}
+
+/// Hold'em hand ranking, trying every five-card combination
+pub struct Holdem;
+
+impl Evaluator for Holdem {
+    fn eval(&self, cards: &[Card]) -> HandRank {
+        crate::profiling::count_evaluation();
+        if cards.len() < 5 || cards.len() > 7 {
+            return best_of_fives(cards, |five| rank_five(five.try_into().unwrap()));
+        }
+        // Every subset of five cards as a bit mask, without allocating the combinations
+        let mut best = (u64::MAX, 0, 0);
+        for mask in 0_u32..1 << cards.len() {
+            if mask.count_ones() != 5 {
+                continue;
+            }
+            let mut five = [cards[0]; 5];
+            let picked = cards.iter().enumerate().filter(|(i, _)| mask >> i & 1 == 1);
+            for (slot, (_, &card)) in five.iter_mut().zip(picked) {
+                *slot = card;
+            }
+            best = best.min(rank_five(&five));
+        }
+        best
+    }
+
+    /// Straights and flushes have no kicker
+    fn kickers(&self, five: &[Card]) -> Vec<Card> {
+        match self.eval(five).0 {
+            1 | 2 | 5 | 6 => vec![],
+            _ => unpaired(five),
+        }
+    }
+}
+
+/// Short deck (6+) hold'em: a flush beats a full house and A-6-7-8-9 is the lowest straight
+pub struct ShortDeck;
+
+impl Evaluator for ShortDeck {
+    fn eval(&self, cards: &[Card]) -> HandRank {
+        crate::profiling::count_evaluation();
+        best_of_fives(cards, |five| {
+            let ranks: Vec<CardRank> = five.iter().map(|c| c.rank).sorted().collect();
+            let (category, value, kicker) = if ranks
+                == [
+                    CardRank::R6,
+                    CardRank::R7,
+                    CardRank::R8,
+                    CardRank::R9,
+                    CardRank::RA,
+                ] {
+                // The ace plays as a five
+                let flush = five.iter().all(|c| c.suit == five[0].suit);
+                let value = high_card_value(&[
+                    CardRank::R5,
+                    CardRank::R6,
+                    CardRank::R7,
+                    CardRank::R8,
+                    CardRank::R9,
+                ]);
+                (if flush { 2 } else { 6 }, value, 0)
+            } else {
+                rank_card_combination(five.to_vec())
+            };
+            let category = match category {
+                4 => 5,
+                5 => 4,
+                category => category,
+            };
+            (category, value, kicker)
+        })
+    }
+
+    /// Straights and flushes have no kicker
+    fn kickers(&self, five: &[Card]) -> Vec<Card> {
+        match self.eval(five).0 {
+            1 | 2 | 4 | 6 => vec![],
+            _ => unpaired(five),
+        }
+    }
+}
+
+/// Ace-to-five lowball: the lowest hand wins, aces are low and straights and flushes do not
+/// count. Categories go from 1 (no pair) to 6 (four of a kind).
+pub struct AceToFive;
+
+impl Evaluator for AceToFive {
+    fn eval(&self, cards: &[Card]) -> HandRank {
+        crate::profiling::count_evaluation();
+        best_of_fives(cards, |five| {
+            let low = |rank: CardRank| match rank {
+                CardRank::RA => 0,
+                rank => rank as u64 + 1,
+            };
+            // Largest groups first, then the highest rank, as the hands are compared
+            let groups: Vec<(usize, u64)> = five
+                .iter()
+                .map(|c| low(c.rank))
+                .counts()
+                .into_iter()
+                .map(|(rank, n)| (n, rank))
+                .sorted()
+                .rev()
+                .collect();
+            let category = match groups.iter().map(|g| g.0).collect::<Vec<_>>()[..] {
+                [1, 1, 1, 1, 1] => 1,
+                [2, 1, 1, 1] => 2,
+                [2, 2, 1] => 3,
+                [3, 1, 1] => 4,
+                [3, 2] => 5,
+                _ => 6,
+            };
+            let value = groups
+                .iter()
+                .flat_map(|&(n, rank)| std::iter::repeat_n(rank, n))
+                .fold(0, |value, rank| value * 13 + rank);
+            (category, value, 0)
+        })
+    }
+}
+
+/// Boards need 3 to 5 cards and no card can be used twice
+fn validate(board: &[Card], hands: &[(Card, Card)]) -> PyResult<()> {
+    if board.len() < 3 || board.len() > 5 {
+        return Err(PyValueError::new_err(format!(
+            "The board must have 3 to 5 cards, got {}",
+            board.len()
+        )));
+    }
+
+    let mut seen: Vec<Card> = Vec::with_capacity(board.len() + 2 * hands.len());
+    let cards = board
+        .iter()
+        .copied()
+        .chain(hands.iter().flat_map(|h| [h.0, h.1]));
+    for card in cards {
+        if seen.contains(&card) {
+            return Err(PyValueError::new_err(format!(
+                "Card {} is used more than once",
+                card
+            )));
+        }
+        seen.push(card);
+    }
+    Ok(())
+}
+
+/// Compare two hands on the same board: 1 if `hand_a` wins, -1 if `hand_b` wins, 0 on a tie
+#[pyfunction]
+#[pyo3(signature = (board, hand_a, hand_b, variant=GameVariant::Holdem))]
+pub fn compare_hands(
+    board: Vec<Card>,
+    hand_a: (Card, Card),
+    hand_b: (Card, Card),
+    variant: GameVariant,
+) -> PyResult<i32> {
+    validate(&board, &[hand_a, hand_b])?;
+
+    // Lower ranks are stronger hands
+    let evaluator = variant.evaluator();
+    let ordering = evaluator
+        .rank_hand(hand_a, &board)
+        .cmp(&evaluator.rank_hand(hand_b, &board));
+    Ok(match ordering {
+        Ordering::Less => 1,
+        Ordering::Greater => -1,
+        Ordering::Equal => 0,
+    })
+}
+
+/// Indices of the winning hands on `board`, more than one when the pot is split
+#[pyfunction]
+#[pyo3(signature = (board, hands, variant=GameVariant::Holdem))]
+pub fn best_hand(
+    board: Vec<Card>,
+    hands: Vec<(Card, Card)>,
+    variant: GameVariant,
+) -> PyResult<Vec<usize>> {
+    validate(&board, &hands)?;
+
+    let evaluator = variant.evaluator();
+    let ranks: Vec<HandRank> = hands
+        .iter()
+        .map(|&h| evaluator.rank_hand(h, &board))
+        .collect();
+    let best = match ranks.iter().min() {
+        Some(best) => *best,
+        None => return Ok(vec![]),
+    };
+    Ok(ranks
+        .iter()
+        .enumerate()
+        .filter(|(_, rank)| **rank == best)
+        .map(|(i, _)| i)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hand(a: &str, b: &str) -> (Card, Card) {
+        (
+            Card::from_string(a.to_string()).unwrap(),
+            Card::from_string(b.to_string()).unwrap(),
+        )
+    }
+
+    fn cards(cards: &[&str]) -> Vec<Card> {
+        cards
+            .iter()
+            .map(|c| Card::from_string(c.to_string()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn compares_without_a_state() {
+        let board: Vec<Card> = ["C2", "D7", "H9", "SK", "C4"]
+            .iter()
+            .map(|c| Card::from_string(c.to_string()).unwrap())
+            .collect();
+        let aces = hand("SA", "HA");
+        let kings = hand("DK", "HK");
+        let queens = hand("SQ", "HQ");
+        let other_queens = hand("DQ", "CQ");
+
+        assert_eq!(
+            compare_hands(board.clone(), kings, aces, GameVariant::Holdem).unwrap(),
+            1
+        );
+        assert_eq!(
+            compare_hands(board.clone(), queens, aces, GameVariant::Holdem).unwrap(),
+            -1
+        );
+        assert_eq!(
+            compare_hands(board.clone(), queens, other_queens, GameVariant::Holdem).unwrap(),
+            0
+        );
+        assert_eq!(
+            best_hand(
+                board.clone(),
+                vec![queens, other_queens, aces],
+                GameVariant::Holdem
+            )
+            .unwrap(),
+            vec![2]
+        );
+        assert_eq!(
+            best_hand(
+                board.clone(),
+                vec![queens, other_queens],
+                GameVariant::Holdem
+            )
+            .unwrap(),
+            vec![0, 1]
+        );
+        assert!(compare_hands(board, aces, aces, GameVariant::Holdem).is_err());
+    }
+
+    #[test]
+    fn fast_ranking_matches_the_reference() {
+        use rand::seq::SliceRandom;
+        use rand::SeedableRng;
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+        let mut deck = Card::collect();
+        for _ in 0..20_000 {
+            deck.shuffle(&mut rng);
+            let five: [Card; 5] = deck[..5].try_into().unwrap();
+            assert_eq!(rank_five(&five), rank_card_combination(five.to_vec()));
+            assert_eq!(
+                Holdem.eval(&deck[..7]),
+                best_of_fives(&deck[..7], |five| rank_card_combination(five.to_vec()))
+            );
+        }
+    }
+
+    #[test]
+    fn variants_rank_hands_their_own_way() {
+        // Short deck: the flush beats the full house, which hold'em ranks the other way
+        let board = cards(&["H9", "H8", "S8", "H6", "CT"]);
+        let flush = hand("HA", "H7");
+        let full_house = hand("D8", "C9");
+        assert_eq!(
+            compare_hands(board.clone(), flush, full_house, GameVariant::Holdem).unwrap(),
+            -1
+        );
+        assert_eq!(
+            compare_hands(board.clone(), flush, full_house, GameVariant::ShortDeck).unwrap(),
+            1
+        );
+
+        // A-6-7-8-9 is a straight in short deck, below 6-7-8-9-T
+        let wheel = ShortDeck.eval(&cards(&["SA", "D6", "C7", "H8", "S9"]));
+        let six_to_ten = ShortDeck.eval(&cards(&["D6", "C7", "H8", "S9", "ST"]));
+        assert_eq!(wheel.0, 6);
+        assert!(six_to_ten < wheel);
+
+        // Lowball: the wheel is the nuts and pairs are worse than any high card
+        let wheel = AceToFive.eval7(
+            cards(&["SA", "D2", "C3", "H4", "S5", "HK", "DK"])
+                .try_into()
+                .unwrap(),
+        );
+        let six_four = AceToFive.eval(&cards(&["S6", "D4", "C3", "H2", "SA"]));
+        let pair = AceToFive.eval(&cards(&["SA", "DA", "C2", "H3", "S4"]));
+        assert_eq!(
+            wheel,
+            (
+                1,
+                4 * 13_u64.pow(4) + 3 * 13_u64.pow(3) + 2 * 13 * 13 + 13,
+                0
+            )
+        );
+        assert!(wheel < six_four);
+        assert!(six_four < pair);
+        assert_eq!(
+            Holdem.eval(&cards(&["SA", "DA", "C2", "H3", "S4"])),
+            crate::game_logic::rank_hand(hand("SA", "DA"), &cards(&["C2", "H3", "S4"]))
+        );
+    }
+
+    #[test]
+    fn best_five_points_at_the_cards_played() {
+        // A heart flush with both hole cards and three cards of the board
+        let board = cards(&["H2", "H9", "C8", "SK", "H5"]);
+        assert_eq!(
+            Holdem.best_five(hand("HA", "HQ"), &board),
+            vec![0, 1, 2, 3, 6]
+        );
+
+        // A royal flush with one hole card, and the board playing
+        let board = cards(&["SK", "SQ", "SJ", "ST", "D3"]);
+        assert_eq!(
+            Holdem.best_five(hand("SA", "C2"), &board),
+            vec![0, 2, 3, 4, 5]
+        );
+        let board = cards(&["SA", "SK", "SQ", "SJ", "ST"]);
+        assert_eq!(
+            Holdem.best_five(hand("C3", "D4"), &board),
+            vec![2, 3, 4, 5, 6]
+        );
+
+        assert!(Holdem.best_five(hand("C3", "D4"), &board[..2]).is_empty());
+    }
+
+    #[test]
+    fn ties_tell_the_board_from_the_kickers() {
+        // Nobody beats the broadway straight on the board
+        let board = cards(&["SA", "DK", "CQ", "HJ", "ST"]);
+        assert_eq!(tie(&Holdem, hand("C2", "D3"), &board), Tie::Board);
+
+        // Queens with the same ace-king kickers, or the same king kicker with an ace on board
+        let board = cards(&["SQ", "DQ", "C8", "H5", "S3"]);
+        assert_eq!(
+            tie(&Holdem, hand("CK", "DA"), &board),
+            Tie::Kickers(vec![CardRank::RA, CardRank::RK])
+        );
+        let board = cards(&["SQ", "DQ", "CA", "H5", "S3"]);
+        assert_eq!(
+            tie(&Holdem, hand("CK", "D2"), &board),
+            Tie::Kickers(vec![CardRank::RK])
+        );
+
+        // The same straight made with a nine has no kicker
+        let board = cards(&["S8", "D7", "C6", "H5", "SK"]);
+        assert_eq!(tie(&Holdem, hand("C9", "D2"), &board), Tie::SameHand);
+    }
+}
@@ -0,0 +1,195 @@
+// events.rs - What happens at a table, published by the game server for every sink to follow
+use std::sync::Arc;
+
+use crate::protocol::CardInfo;
+use crate::rake::HandRake;
+use crate::state::view::StateView;
+use crate::state::State;
+use crate::websocket_server::{
+    ActionClosedMessage, ErrorMessage, GameStateMessage, HandStrengthMessage, HandWinningsMessage,
+    InsuranceOfferMessage, InsuranceResultMessage, KnockoutMessage, OnMoveMessage,
+    PlayerStatsMessage, PotUpdateMessage, RegisteredMessage, TournamentClockMessage,
+    WebSocketMessage, WinningInfo,
+};
+
+/// Player dealt into a finished hand
+#[derive(Debug, Clone)]
+pub struct HandPlayer {
+    pub seat: u8,
+    /// Empty when the player is gone
+    pub name: String,
+    /// Stack after the hand
+    pub chips: f64,
+}
+
+/// A settled hand, see `TableEvent::HandComplete`
+#[derive(Debug, Clone)]
+pub struct HandSummary {
+    pub state: State,
+    pub table_id: String,
+    /// Every player dealt in, in `State.players_state` order
+    pub players: Vec<HandPlayer>,
+    pub community_cards: Vec<CardInfo>,
+    pub winnings: Vec<WinningInfo>,
+    pub rake: HandRake,
+}
+
+impl HandSummary {
+    /// What the player at `index` won or lost, after the rake and jackpot drops
+    pub fn net(&self, index: usize) -> f64 {
+        let reward = self
+            .state
+            .players_state
+            .get(index)
+            .map_or(0.0, |ps| ps.reward);
+        reward - self.rake.taken_from(index)
+    }
+}
+
+/// Something that happened at the table. The messages for the clients are ready to send;
+/// the other events are for the sinks that keep or report what happened.
+#[derive(Debug, Clone)]
+pub enum TableEvent {
+    /// The table changed. `view` is the hand as observers see it, `None` between hands and
+    /// while an insurance offer hides the runout.
+    GameState {
+        message: GameStateMessage,
+        view: Option<StateView>,
+    },
+    OnMove(OnMoveMessage),
+    /// A betting round closed, before the game state showing the bets swept in
+    ActionClosed(ActionClosedMessage),
+    /// The pot after an action, split into the main pot and the side pots
+    PotUpdate(PotUpdateMessage),
+    TournamentClock(TournamentClockMessage),
+    /// A busted player's bounty was paid to the winners of the pot
+    Knockout(KnockoutMessage),
+    HandWinnings(HandWinningsMessage),
+    HandStrength {
+        player_id: String,
+        message: HandStrengthMessage,
+    },
+    InsuranceOffer {
+        player_id: String,
+        message: InsuranceOfferMessage,
+    },
+    InsuranceResult {
+        player_id: String,
+        message: InsuranceResultMessage,
+    },
+    PlayerStats {
+        player_id: String,
+        message: PlayerStatsMessage,
+    },
+    /// A player registered, or came back on a new connection
+    Registered {
+        player_id: String,
+        message: RegisteredMessage,
+    },
+    /// A player asked for their hand histories, `zip` holds `hands` hands
+    HandsExport {
+        player_id: String,
+        zip: Arc<Vec<u8>>,
+        hands: usize,
+    },
+    /// A request of the player was rejected
+    Error {
+        player_id: String,
+        message: ErrorMessage,
+    },
+    /// A player took their first seat at the table
+    PlayerJoined {
+        seat: u8,
+        name: String,
+        chips: f64,
+    },
+    /// The connection of a seated player closed. `session_net` is what they won or lost
+    /// since they joined.
+    PlayerLeft {
+        seat: u8,
+        name: String,
+        chips: f64,
+        session_net: f64,
+    },
+    /// A hand was settled and the stacks updated, after its `HandWinnings`
+    HandComplete(Arc<HandSummary>),
+}
+
+impl TableEvent {
+    /// The message every client at the table is sent for the event, `None` for the events
+    /// sent to a single player or to no one
+    pub fn broadcast(&self) -> Option<WebSocketMessage> {
+        let (message_type, data) = match self {
+            TableEvent::GameState { message, .. } => ("gameState", serde_json::to_value(message)),
+            TableEvent::OnMove(message) => ("onmove", serde_json::to_value(message)),
+            TableEvent::ActionClosed(message) => ("actionClosed", serde_json::to_value(message)),
+            TableEvent::PotUpdate(message) => ("potUpdate", serde_json::to_value(message)),
+            TableEvent::TournamentClock(message) => {
+                ("tournamentClock", serde_json::to_value(message))
+            }
+            TableEvent::HandWinnings(message) => ("handWinnings", serde_json::to_value(message)),
+            TableEvent::Knockout(message) => ("knockout", serde_json::to_value(message)),
+            _ => return None,
+        };
+        Some(WebSocketMessage {
+            message_type: message_type.to_string(),
+            data: data.unwrap_or_default(),
+        })
+    }
+}
+
+/// Called with every event of the table, in order. Subscribers run while the table is locked
+/// and must return quickly: sinks with slow work to do queue it for a task or thread of their
+/// own.
+pub type Subscriber = Arc<dyn Fn(&TableEvent) + Send + Sync>;
+
+/// Called with the id of the table and each event it publishes, for the tables of a
+/// fast-fold pool or a tournament
+pub type PoolSubscriber = Arc<dyn Fn(&str, &TableEvent) + Send + Sync>;
+
+/// Fans the events of a table out to its subscribers
+#[derive(Clone, Default)]
+pub struct EventBus {
+    subscribers: Vec<Subscriber>,
+}
+
+impl EventBus {
+    pub fn subscribe(&mut self, subscriber: Subscriber) {
+        self.subscribers.push(subscriber);
+    }
+
+    pub fn publish(&self, event: TableEvent) {
+        for subscriber in &self.subscribers {
+            subscriber(&event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn subscribers_see_every_event_in_order() {
+        let mut bus = EventBus::default();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        for sink in 0..2 {
+            let seen = seen.clone();
+            bus.subscribe(Arc::new(move |event| {
+                if let TableEvent::PlayerJoined { seat, .. } = event {
+                    seen.lock().unwrap().push((sink, *seat));
+                }
+            }));
+        }
+
+        for seat in [3, 1] {
+            bus.publish(TableEvent::PlayerJoined {
+                seat,
+                name: format!("Player{}", seat),
+                chips: 1000.0,
+            });
+        }
+        assert_eq!(*seen.lock().unwrap(), vec![(0, 3), (1, 3), (0, 1), (1, 1)]);
+    }
+}
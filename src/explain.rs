@@ -0,0 +1,218 @@
+// explain.rs - Why the engine accepts, adjusts or ignores an action
+use crate::game_logic::raise_cap_reached;
+use crate::state::action::{Action, ActionEnum, Coercion};
+use crate::state::stage::Stage;
+use crate::state::{State, StateStatus};
+use pyo3::prelude::*;
+
+/// Verdict of `State::explain_action`
+#[pyclass]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActionExplanation {
+    /// The engine applies the action as given
+    #[pyo3(get)]
+    pub legal: bool,
+
+    #[pyo3(get)]
+    pub reason: String,
+
+    /// What the engine applies instead of an illegal action, `None` when it ignores it
+    #[pyo3(get)]
+    pub substitute: Option<Action>,
+}
+
+#[pymethods]
+impl ActionExplanation {
+    pub fn __str__(&self) -> PyResult<String> {
+        Ok(format!("{:#?}", self))
+    }
+}
+
+fn legal(reason: String) -> ActionExplanation {
+    ActionExplanation {
+        legal: true,
+        reason,
+        substitute: None,
+    }
+}
+
+fn illegal(reason: String, substitute: Option<Action>) -> ActionExplanation {
+    ActionExplanation {
+        legal: false,
+        reason,
+        substitute,
+    }
+}
+
+/// Applies the action to a copy of the state and explains the `ActionRecord.coercion` the
+/// engine recorded for it, so the substitute is what the engine records for the action
+pub fn explain_action(state: &State, action: Action) -> ActionExplanation {
+    if !matches!(state.status, StateStatus::Ok) {
+        return illegal("The hand was aborted by an engine error".to_owned(), None);
+    }
+    if state.final_state || state.stage == Stage::Showdown {
+        return illegal("The hand is over".to_owned(), None);
+    }
+    if state.legal_actions.is_empty() {
+        return illegal(
+            format!("Player {} is all-in and cannot act", state.current_player),
+            None,
+        );
+    }
+
+    let next = state.apply_action(action);
+    let record = match next.from_action {
+        Some(ref record) if matches!(next.status, StateStatus::Ok) => record,
+        _ => return illegal("The engine could not apply the action".to_owned(), None),
+    };
+
+    let ps = &state.players_state[state.current_player as usize];
+    let max_bet = state
+        .players_state
+        .iter()
+        .filter(|ps| ps.active)
+        .map(|ps| ps.bet_chips)
+        .fold(0.0, f64::max);
+    let all_in = ps.bet_chips + ps.stake;
+    let applied = record.action;
+    let verdict = match applied.action {
+        ActionEnum::Fold => "a fold".to_owned(),
+        ActionEnum::CheckCall if record.chips == 0.0 => "a check".to_owned(),
+        ActionEnum::CheckCall if record.chips == ps.stake => {
+            format!("all-in for {}", record.chips)
+        }
+        ActionEnum::CheckCall => format!("a call of {}", record.chips),
+        ActionEnum::BetRaise if applied.amount <= max_bet => {
+            format!("a call of {}", record.chips)
+        }
+        ActionEnum::BetRaise if applied.amount == all_in => format!("all-in for {}", all_in),
+        ActionEnum::BetRaise => format!("a raise to {}", applied.amount),
+    };
+
+    let Some(coercion) = record.coercion else {
+        return legal(match applied.action {
+            ActionEnum::Fold if record.to_call == 0.0 => {
+                "Fold, although checking costs nothing".to_owned()
+            }
+            ActionEnum::Fold => "Fold".to_owned(),
+            ActionEnum::CheckCall if record.chips == 0.0 => "Check".to_owned(),
+            ActionEnum::CheckCall if record.chips == ps.stake => {
+                format!("Call all-in for {}", record.chips)
+            }
+            ActionEnum::CheckCall => format!("Call {}", record.chips),
+            ActionEnum::BetRaise if max_bet == 0.0 => format!("Bet {}", applied.amount),
+            ActionEnum::BetRaise => format!("Raise to {}", applied.amount),
+        });
+    };
+
+    let reason = match coercion {
+        Coercion::IllegalAction if raise_cap_reached(state) => format!(
+            "The street is capped at {} bets and raises, converted to {}",
+            state.rules.max_raises_per_street.unwrap_or(0),
+            verdict
+        ),
+        Coercion::MinRaise => format!(
+            "Raise to {} is below the minimum raise to {}, converted to {}",
+            action.amount,
+            state.min_bet * state.rules.min_raise_ratio.max(1.0),
+            verdict
+        ),
+        Coercion::AllIn if action.action == ActionEnum::CheckCall => format!(
+            "Calling {} would leave less than one chip, converted to {}",
+            record.to_call, verdict
+        ),
+        Coercion::AllIn if action.amount > all_in => format!(
+            "Raise to {} is more than the {} behind, converted to {}",
+            action.amount, ps.stake, verdict
+        ),
+        Coercion::AllIn => format!(
+            "Only {} behind, less than the current bet of {}: converted to {}",
+            ps.stake, state.min_bet, verdict
+        ),
+        _ => format!(
+            "{:?} is not legal here ({:?}), converted to {}",
+            action.action, coercion, verdict
+        ),
+    };
+    // The substitute is the action as a player would ask for it, calls without an amount
+    let substitute = match applied.action {
+        ActionEnum::BetRaise => applied,
+        other => Action::new(other, 0.0),
+    };
+    illegal(reason, Some(substitute))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::rules::TableRules;
+
+    /// The substitute of a bet or raise is exactly what the engine records
+    fn assert_recorded(state: &State, action: Action) -> ActionExplanation {
+        let explanation = explain_action(state, action);
+        let next = state.apply_action(action);
+        let recorded = next.from_action.unwrap().action;
+        assert_eq!(recorded, explanation.substitute.unwrap_or(action));
+        explanation
+    }
+
+    #[test]
+    fn explains_the_recorded_coercion() {
+        let state = State::from_seed(2, 0, 5.0, 10.0, 100.0, 1234, false, 0.0).unwrap();
+        let state = state.apply_action(Action::new(ActionEnum::BetRaise, 99.5));
+
+        // Calling would leave half a chip behind
+        let call = Action::new(ActionEnum::CheckCall, 0.0);
+        let explanation = explain_action(&state, call);
+        let record = state.apply_action(call).from_action.unwrap();
+        assert_eq!(record.coercion, Some(Coercion::AllIn));
+        assert!(!explanation.legal);
+        assert_eq!(
+            explanation.reason,
+            "Calling 89.5 would leave less than one chip, converted to all-in for 90"
+        );
+        assert_eq!(explanation.substitute, Some(call));
+    }
+
+    #[test]
+    fn explains_coerced_raises() {
+        let rules = TableRules {
+            min_raise_ratio: 2.0,
+            max_raises_per_street: Some(2),
+            ..TableRules::default()
+        };
+        let state = State::from_seed(2, 0, 5.0, 10.0, 100.0, 1234, false, 0.0)
+            .unwrap()
+            .with_rules(rules)
+            .unwrap();
+
+        let explanation = assert_recorded(&state, Action::new(ActionEnum::BetRaise, 15.0));
+        assert!(!explanation.legal);
+        assert_eq!(
+            explanation.reason,
+            "Raise to 15 is below the minimum raise to 20, converted to a raise to 20"
+        );
+        assert!(assert_recorded(&state, Action::new(ActionEnum::BetRaise, 30.0)).legal);
+
+        let explanation = assert_recorded(&state, Action::new(ActionEnum::BetRaise, 500.0));
+        assert_eq!(
+            explanation.substitute,
+            Some(Action::new(ActionEnum::BetRaise, 100.0))
+        );
+
+        // Two raises cap the street
+        let state = state.apply_action(Action::new(ActionEnum::BetRaise, 20.0));
+        let state = state.apply_action(Action::new(ActionEnum::BetRaise, 40.0));
+        let explanation = explain_action(&state, Action::new(ActionEnum::BetRaise, 80.0));
+        assert_eq!(
+            explanation.reason,
+            "The street is capped at 2 bets and raises, converted to a call of 20"
+        );
+
+        let state = state.apply_action(Action::new(ActionEnum::Fold, 0.0));
+        assert_eq!(
+            explain_action(&state, Action::new(ActionEnum::Fold, 0.0)).reason,
+            "The hand is over"
+        );
+    }
+}
@@ -0,0 +1,414 @@
+// fast_fold.rs - Fast-fold pools, where folding moves a player straight on to a new hand
+use crate::events::{PoolSubscriber, Subscriber};
+use crate::game_server::{GameConfig, GamePlayer, GameServer, PlayerAction};
+use crate::hand_archive::HandArchive;
+use crate::messages::ClientError;
+use crate::player_stats::PlayerStatsStore;
+use crate::seeds::hand_seed;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use tracing::{error, info};
+
+/// A pool of players spread over as many tables as it takes. A table deals one hand to a
+/// full table of players from the queue. Players who fold, and everybody once the hand is
+/// over, go back to the queue with their chips and are dealt into the next table that
+/// fills up.
+pub struct FastFoldPool {
+    pool_id: String,
+    /// Settings of every table of the pool
+    config: GameConfig,
+    /// Seeds of the tables, which deal from `hand_seed(pool_seed, table number)`
+    pool_seed: u64,
+    /// Tables by id, opened as the pool grows and reused once their hand is over
+    tables: BTreeMap<String, GameServer>,
+    /// Player id -> id of the table the player sits at
+    seated: HashMap<String, String>,
+    /// Players waiting for a table, first come first dealt
+    queue: VecDeque<GamePlayer>,
+    /// Players who left the pool during a hand, dropped once they are out of it
+    leaving: HashSet<String>,
+    /// Followers of every table, see `FastFoldPool::subscribe`
+    subscribers: Vec<PoolSubscriber>,
+    player_stats: Arc<Mutex<PlayerStatsStore>>,
+    hand_archive: Arc<Mutex<HandArchive>>,
+}
+
+impl FastFoldPool {
+    /// Tables are dealt once `config.max_players` players wait, and are named after
+    /// `pool_id`, e.g. `zoom-1`, `zoom-2`
+    pub fn new(pool_id: &str, config: GameConfig) -> Self {
+        let player_stats = match config.stats_file {
+            Some(ref path) => PlayerStatsStore::load(path.into()).unwrap_or_else(|e| {
+                error!("Failed to load player stats from {}: {}", path, e);
+                PlayerStatsStore::default()
+            }),
+            None => PlayerStatsStore::default(),
+        };
+        let hand_archive = match config.hand_history_dir {
+            Some(ref dir) => HandArchive::open(dir.into()).unwrap_or_else(|e| {
+                error!("Failed to open the hand histories in {}: {}", dir, e);
+                HandArchive::default()
+            }),
+            None => HandArchive::default(),
+        };
+        let pool_seed = config.session_seed.unwrap_or_else(rand::random);
+        info!("Fast-fold pool {}, seed {}", pool_id, pool_seed);
+
+        Self {
+            pool_id: pool_id.to_owned(),
+            config,
+            pool_seed,
+            tables: BTreeMap::new(),
+            seated: HashMap::new(),
+            queue: VecDeque::new(),
+            leaving: HashSet::new(),
+            subscribers: Vec::new(),
+            player_stats: Arc::new(Mutex::new(player_stats)),
+            hand_archive: Arc::new(Mutex::new(hand_archive)),
+        }
+    }
+
+    /// Follow everything that happens at the tables of the pool, from now on
+    pub fn subscribe(&mut self, subscriber: PoolSubscriber) {
+        for (table_id, table) in self.tables.iter_mut() {
+            table.subscribe(for_table(table_id, &subscriber));
+        }
+        self.subscribers.push(subscriber);
+    }
+
+    /// Add a player to the queue with the default stack. A player who left during a hand
+    /// and comes back before it is over stays in the pool.
+    pub async fn join(
+        &mut self,
+        player_id: &str,
+        name: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(table_id) = self.seated.get(player_id) {
+            self.leaving.remove(player_id);
+            if let Some(table) = self.tables.get_mut(table_id) {
+                table.register_player(name, player_id, None).await?;
+            }
+            return Ok(());
+        }
+        if self.players().any(|p| p.name == name || p.id == player_id) {
+            return Err(ClientError::NameTaken(name.to_string()).into());
+        }
+
+        let player = GamePlayer::new(
+            player_id.to_string(),
+            name.to_string(),
+            self.config.default_stack_size,
+        );
+        info!("Player {} joined pool {}", name, self.pool_id);
+        self.queue.push_back(player);
+        self.reseat().await;
+        Ok(())
+    }
+
+    /// Leave the pool. A player in a hand stays in it, disconnected, until they fold or it
+    /// is over.
+    pub async fn leave(&mut self, player_id: &str) {
+        if let Some(index) = self.queue.iter().position(|p| p.id == player_id) {
+            if let Some(player) = self.queue.remove(index) {
+                info!("Player {} left pool {}", player.name, self.pool_id);
+            }
+            return;
+        }
+        if let Some(table) = self
+            .seated
+            .get(player_id)
+            .and_then(|table_id| self.tables.get_mut(table_id))
+        {
+            self.leaving.insert(player_id.to_string());
+            table.player_disconnected(player_id).await;
+        }
+        self.reseat().await;
+    }
+
+    /// Play an action at the player's table. A fold sends them back to the queue, and on
+    /// to the next table dealt.
+    pub async fn handle_action(
+        &mut self,
+        player_id: &str,
+        action: PlayerAction,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let table = self
+            .seated
+            .get(player_id)
+            .and_then(|table_id| self.tables.get_mut(table_id))
+            .ok_or(ClientError::NotSeated)?;
+        table.handle_action(player_id, action).await?;
+        self.reseat().await;
+        Ok(())
+    }
+
+    /// Run the timers of every table and move on the players they folded. Called
+    /// periodically, like `GameServer::tick`.
+    pub async fn tick(&mut self) {
+        for table in self.tables.values_mut() {
+            table.tick().await;
+        }
+        self.reseat().await;
+    }
+
+    /// Id of the table `player_id` sits at, `None` while they wait for one
+    pub fn table_of(&self, player_id: &str) -> Option<&str> {
+        self.seated.get(player_id).map(|table_id| table_id.as_str())
+    }
+
+    pub fn table(&self, table_id: &str) -> Option<&GameServer> {
+        self.tables.get(table_id)
+    }
+
+    /// Ids of the tables opened so far
+    pub fn table_ids(&self) -> Vec<String> {
+        self.tables.keys().cloned().collect()
+    }
+
+    /// Number of players waiting for a table
+    pub fn waiting(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// A player of the pool and their chips, wherever they are
+    pub fn player(&self, player_id: &str) -> Option<&GamePlayer> {
+        self.players().find(|p| p.id == player_id)
+    }
+
+    fn players(&self) -> impl Iterator<Item = &GamePlayer> {
+        let seated = self.seated.iter().filter_map(|(player_id, table_id)| {
+            self.tables
+                .get(table_id)
+                .and_then(|table| table.player(player_id))
+        });
+        self.queue.iter().chain(seated)
+    }
+
+    /// Players dealt into a hand
+    fn table_size(&self) -> usize {
+        self.config.max_players.max(2) as usize
+    }
+
+    /// Send the players who folded, and everybody at tables whose hand is over, back to the
+    /// queue, then deal the tables the queue fills
+    async fn reseat(&mut self) {
+        loop {
+            for table in self.tables.values_mut() {
+                let done = if table.game_running() {
+                    table.folded_players()
+                } else {
+                    table.seated_players()
+                };
+                for player_id in done {
+                    let player = match table.transfer_out(&player_id) {
+                        Ok(player) => player,
+                        Err(e) => {
+                            error!("Failed to move player {} on: {}", player_id, e);
+                            continue;
+                        }
+                    };
+                    self.seated.remove(&player_id);
+                    if self.leaving.remove(&player_id) {
+                        info!("Player {} left pool {}", player.name, self.pool_id);
+                    } else {
+                        self.queue.push_back(player);
+                    }
+                }
+            }
+
+            if self.queue.len() < self.table_size() {
+                return;
+            }
+            let players: Vec<GamePlayer> = self.queue.drain(..self.table_size()).collect();
+            let table_id = self.idle_table();
+            let table = self.tables.get_mut(&table_id).expect("idle table");
+            for (seat, player) in (1..).zip(players) {
+                let player_id = player.id.clone();
+                match table.transfer_in(player, seat) {
+                    Ok(()) => {
+                        self.seated.insert(player_id, table_id.clone());
+                    }
+                    Err(e) => error!("Failed to seat player {}: {}", player_id, e),
+                }
+            }
+
+            // A table that cannot deal, e.g. on a tournament break, hands its players back
+            // until the next call
+            if let Err(e) = table.start_game().await {
+                error!("Table {} could not deal: {}", table_id, e);
+                for player_id in table.seated_players() {
+                    if let Ok(player) = table.transfer_out(&player_id) {
+                        self.seated.remove(&player_id);
+                        self.queue.push_front(player);
+                    }
+                }
+                return;
+            }
+        }
+    }
+
+    /// Id of an empty table between hands, opening one when every table is busy
+    fn idle_table(&mut self) -> String {
+        let idle = self
+            .tables
+            .iter()
+            .find(|(_, table)| !table.game_running() && table.seated_players().is_empty())
+            .map(|(table_id, _)| table_id.clone());
+        if let Some(table_id) = idle {
+            return table_id;
+        }
+
+        let number = self.tables.len() as u64 + 1;
+        let table_id = format!("{}-{}", self.pool_id, number);
+        let config = GameConfig {
+            table_id: Some(table_id.clone()),
+            session_seed: Some(hand_seed(self.pool_seed, number)),
+            ..self.config.clone()
+        };
+        let mut table =
+            GameServer::with_stores(config, self.player_stats.clone(), self.hand_archive.clone());
+        for subscriber in &self.subscribers {
+            table.subscribe(for_table(&table_id, subscriber));
+        }
+        info!("Pool {} opened table {}", self.pool_id, table_id);
+        self.tables.insert(table_id.clone(), table);
+        table_id
+    }
+}
+
+/// Follow a single table of the pool
+fn for_table(table_id: &str, subscriber: &PoolSubscriber) -> Subscriber {
+    let table_id = table_id.to_owned();
+    let subscriber = subscriber.clone();
+    Arc::new(move |event| subscriber(&table_id, event))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::TableEvent;
+
+    /// Pool of tables of `max_players`, and the player to act at each table as last told
+    fn pool(max_players: u8) -> (FastFoldPool, Arc<Mutex<HashMap<String, String>>>) {
+        let mut pool = FastFoldPool::new(
+            "zoom",
+            GameConfig {
+                max_players,
+                session_seed: Some(7),
+                stats_file: None,
+                hand_history_dir: None,
+                ..Default::default()
+            },
+        );
+        let on_move = Arc::new(Mutex::new(HashMap::new()));
+        let follower = on_move.clone();
+        pool.subscribe(Arc::new(move |table_id: &str, event: &TableEvent| {
+            if let TableEvent::OnMove(message) = event {
+                follower
+                    .lock()
+                    .unwrap()
+                    .insert(table_id.to_owned(), message.address.clone());
+            }
+        }));
+        (pool, on_move)
+    }
+
+    #[tokio::test]
+    async fn folding_moves_to_a_new_table() {
+        let (mut pool, on_move) = pool(3);
+        for i in 1..=5 {
+            pool.join(&format!("p{}", i), &format!("Player {}", i))
+                .await
+                .unwrap();
+        }
+        assert_eq!(pool.table_ids(), vec!["zoom-1"]);
+        assert_eq!(pool.waiting(), 2);
+        assert!(pool.table_of("p4").is_none());
+
+        let folder = on_move.lock().unwrap()["zoom-1"].clone();
+        pool.handle_action(&folder, PlayerAction::Fold)
+            .await
+            .unwrap();
+        // The folder and the two waiting players fill a second table right away
+        assert_eq!(pool.table_ids(), vec!["zoom-1", "zoom-2"]);
+        assert_eq!(pool.table_of(&folder), Some("zoom-2"));
+        assert_eq!(pool.table_of("p4"), Some("zoom-2"));
+        assert_eq!(pool.waiting(), 0);
+        assert!(pool.table("zoom-1").unwrap().game_running());
+
+        // The next fold ends the hand at the first table and everybody there moves on
+        let folder = on_move.lock().unwrap()["zoom-1"].clone();
+        pool.handle_action(&folder, PlayerAction::Fold)
+            .await
+            .unwrap();
+        assert!(pool.table_of(&folder).is_none());
+        assert_eq!(pool.waiting(), 2);
+        assert!(pool.table("zoom-1").unwrap().seated_players().is_empty());
+
+        // Players still in a hand cannot be taken away
+        let playing = on_move.lock().unwrap()["zoom-2"].clone();
+        let table = pool.tables.get_mut("zoom-2").unwrap();
+        assert!(matches!(
+            table.transfer_out(&playing),
+            Err(ClientError::StillInHand)
+        ));
+    }
+
+    #[tokio::test]
+    async fn chips_follow_the_player() {
+        let (mut pool, on_move) = pool(2);
+        pool.join("a", "Alice").await.unwrap();
+        pool.join("b", "Bob").await.unwrap();
+
+        // Heads-up, the small blind folds and both players are dealt again at once
+        let folder = on_move.lock().unwrap()["zoom-1"].clone();
+        pool.handle_action(&folder, PlayerAction::Fold)
+            .await
+            .unwrap();
+        let chips = |id: &str| pool.player(id).unwrap().chips;
+        assert!((chips("a") + chips("b") - 2000.0).abs() < 1e-9);
+        assert!((chips(&folder) - 995.0).abs() < 1e-9);
+        assert_eq!(pool.table_ids(), vec!["zoom-1"]);
+        assert!(pool.table("zoom-1").unwrap().game_running());
+
+        // Leaving during a hand keeps the name taken until the hand is over
+        pool.leave("a").await;
+        assert!(pool.player("a").is_some());
+        assert!(pool.join("c", "Alice").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn bets_of_folded_players_stay_in_the_pot() {
+        let (mut pool, on_move) = pool(3);
+        for id in ["a", "b", "c"] {
+            pool.join(id, id).await.unwrap();
+        }
+        let total = |pool: &FastFoldPool| -> f64 {
+            ["a", "b", "c"]
+                .iter()
+                .map(|id| pool.player(id).unwrap().chips)
+                .sum()
+        };
+
+        // The button raises, the small blind folds its 5 chips and moves on while the hand
+        // goes on, then the big blind folds too
+        let raiser = on_move.lock().unwrap()["zoom-1"].clone();
+        pool.handle_action(&raiser, PlayerAction::Raise(30.0))
+            .await
+            .unwrap();
+        let small_blind = on_move.lock().unwrap()["zoom-1"].clone();
+        pool.handle_action(&small_blind, PlayerAction::Fold)
+            .await
+            .unwrap();
+        assert!(pool.table_of(&small_blind).is_none());
+        assert!((pool.player(&small_blind).unwrap().chips - 995.0).abs() < 1e-9);
+
+        let big_blind = on_move.lock().unwrap()["zoom-1"].clone();
+        pool.handle_action(&big_blind, PlayerAction::Fold)
+            .await
+            .unwrap();
+        assert!((pool.player(&big_blind).unwrap().chips - 990.0).abs() < 1e-9);
+        assert!((pool.player(&raiser).unwrap().chips - 1015.0).abs() < 1e-9);
+        assert!((total(&pool) - 3000.0).abs() < 1e-9);
+    }
+}
@@ -1,31 +1,129 @@
 // game_logic.rs - Rewritten using State-Machine-Based Architecture
 use itertools::Itertools;
-use pyo3::exceptions::PyOSError;
+use pyo3::exceptions::{PyOSError, PyPermissionError, PyValueError};
 use pyo3::prelude::*;
-use rand::{seq::SliceRandom, SeedableRng};
-use std::collections::HashSet;
-
-use crate::state::action::{Action, ActionEnum, ActionRecord};
+use smallvec::smallvec;
+use std::collections::{HashMap, HashSet};
+
+use crate::betting_line;
+use crate::contributions::{self, StreetContributions, StreetSummary};
+use crate::evaluator::{Evaluator, HandRank, Holdem};
+use crate::explain::{self, ActionExplanation};
+use crate::hand_strength::{self, HandStrength};
+use crate::insurance::{self, InsuranceQuote};
+use crate::invariants;
+use crate::profiling::{self, PerfCounters, PerfProbe};
+use crate::range::HandRange;
+use crate::redeal;
+use crate::seeds;
+use crate::state::action::{Action, ActionEnum, ActionRecord, Coercion, DefaultPolicy};
 use crate::state::card::{Card, CardRank, CardSuit};
+use crate::state::rules::{MissedBlinds, TableRules};
+use crate::state::schema;
 use crate::state::stage::Stage;
-use crate::state::{PlayerState, State, StateStatus};
-
-// Define a macro for verbose printing controlled by environment variable
-macro_rules! verbose_println {
-    ($state:expr, $($arg:tt)*) => {
+use crate::state::trace::{TraceEvent, TraceKind};
+use crate::state::view::StateView;
+use crate::state::{
+    BlindObligation, BlindSettlement, Board, LegalActions, PlayerState, Players, State, StateStatus,
+};
+
+/// Record a decision in `State.trace` when the state is verbose, without building the event
+/// otherwise
+macro_rules! record_decision {
+    ($state:expr, $event:expr) => {
         if $state.verbose {
-            println!($($arg)*);
-            use std::io::Write;
-            let _ = std::io::stdout().flush();
+            $state.trace.push($event);
         }
     };
 }
 
+/// Hard limit on the number of actions in a single hand. No legal hand gets close to it,
+/// so reaching it means the engine is looping and the hand is aborted with a diagnostic.
+pub const MAX_ACTIONS_PER_HAND: usize = 1000;
+
+#[cfg(feature = "server")]
+thread_local! {
+    /// Time returned by `now` on this thread instead of the wall clock, see `set_simulated_time`
+    static SIMULATED_TIME: std::cell::Cell<Option<f64>> = const { std::cell::Cell::new(None) };
+}
+
+/// Stop the clock of the current thread at `time`, or give it back to the wall clock with
+/// `None`, for tests of timeouts and replays of recorded sessions
+#[cfg(feature = "server")]
+pub(crate) fn set_simulated_time(time: Option<f64>) {
+    SIMULATED_TIME.with(|simulated| simulated.set(time));
+}
+
+/// Wall-clock time in seconds since the Unix epoch, used by the game clock
+pub(crate) fn now() -> f64 {
+    #[cfg(feature = "server")]
+    if let Some(time) = SIMULATED_TIME.with(std::cell::Cell::get) {
+        return time;
+    }
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs_f64())
+        .unwrap_or(0.0)
+}
+
+/// JSON report of an engine error: the `reason`, the versioned document of the `state` and
+/// the `actions` that hit the error when applied to it, in order. `schema::from_document`
+/// reads the state back to replay them.
+pub(crate) fn diagnostic_bundle(reason: &str, state: &State, actions: &[Action]) -> String {
+    let document = schema::to_document(state).unwrap_or_else(|e| serde_json::json!({ "error": e }));
+    serde_json::json!({
+        "reason": reason,
+        "state": document,
+        "actions": actions,
+    })
+    .to_string()
+}
+
+/// The `reason` of a `diagnostic_bundle`
+pub(crate) fn engine_error_reason(diagnostic: &str) -> Option<String> {
+    let bundle: serde_json::Value = serde_json::from_str(diagnostic).ok()?;
+    bundle["reason"].as_str().map(str::to_owned)
+}
+
+/// A random version 4 UUID, the default `State::hand_id`
+pub(crate) fn random_uuid() -> String {
+    uuid_from_bits(rand::random::<u128>())
+}
+
+/// The version 4 UUID `State::from_seed` gives the hands dealt from `seed`, drawn from
+/// SplitMix64 outputs that the deck shuffle does not use
+fn seeded_uuid(seed: u64) -> String {
+    let high = seeds::hand_seed(seed, u64::MAX - 1) as u128;
+    let low = seeds::hand_seed(seed, u64::MAX) as u128;
+    uuid_from_bits((high << 64) | low)
+}
+
+fn uuid_from_bits(mut bits: u128) -> String {
+    // Version 4, variant 1
+    bits = (bits & !(0xF << 76)) | (0x4 << 76);
+    bits = (bits & !(0x3 << 62)) | (0x2 << 62);
+    let hex = format!("{:032x}", bits);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
 #[derive(Debug)]
 pub struct InitStateError {
     msg: String,
 }
 
+impl std::fmt::Display for InitStateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.msg)
+    }
+}
+
 impl std::convert::From<InitStateError> for PyErr {
     fn from(err: InitStateError) -> PyErr {
         PyOSError::new_err(err.msg)
@@ -78,7 +176,7 @@ pub trait GameStateInterface: GameStateInterfaceClone {
         action: Action,
     ) -> Result<Box<dyn GameStateInterface>, StateStatus>;
 
-    fn get_legal_actions(&self, state: &State) -> Vec<ActionEnum>;
+    fn get_legal_actions(&self, state: &State) -> LegalActions;
     fn state_name(&self) -> String;
     fn is_final(&self) -> bool;
 }
@@ -142,11 +240,13 @@ impl AwaitingAction {
             .iter()
             .all(|ps| ps.bet_chips == max_bet || ps.stake == 0.0);
 
-        // Special case for preflop big blind option
+        // Special case for preflop big blind option, which a big blind put all-in by the blind
+        // or the ante does not have
         let preflop_complete = if state.stage == Stage::Preflop {
-            let bb_position = (state.button + 2) % state.players_state.len() as u64;
+            let (_, bb_position) =
+                State::blind_players(state.players_state.len() as u64, state.button);
             let bb_player = &state.players_state[bb_position as usize];
-            !bb_player.active || bb_player.last_stage_action.is_some()
+            !bb_player.active || bb_player.last_stage_action.is_some() || bb_player.stake == 0.0
         } else {
             true
         };
@@ -155,7 +255,7 @@ impl AwaitingAction {
     }
 
     /// Find the next active player who can act
-    fn find_next_active_player(&self, state: &State, current_idx: u64) -> Option<u64> {
+    fn find_next_active_player(&self, state: &mut State, current_idx: u64) -> Option<u64> {
         let mut next_player = (current_idx + 1) % state.players_state.len() as u64;
         let mut attempts = 0;
         let max_attempts = state.players_state.len();
@@ -167,6 +267,15 @@ impl AwaitingAction {
             if player_state.active && player_state.stake > 0.0 {
                 return Some(next_player);
             }
+            let kind = if player_state.active {
+                TraceKind::SkippedAllIn
+            } else {
+                TraceKind::SkippedFolded
+            };
+            record_decision!(
+                state,
+                TraceEvent::new(kind, state.stage).players(vec![next_player])
+            );
 
             next_player = (next_player + 1) % state.players_state.len() as u64;
             attempts += 1;
@@ -226,20 +335,23 @@ impl GameStateInterface for AwaitingAction {
         // Make sure action is legal
         let actual_action = self.make_action_legal(state, action);
         let player_idx = self.player_to_act_idx as usize;
+        let pot_before = state.pot;
+        let stake_before = state.players_state[player_idx].stake;
+        let to_call = state
+            .players_state
+            .iter()
+            .filter(|ps| ps.active)
+            .map(|ps| ps.bet_chips - state.players_state[player_idx].bet_chips)
+            .fold(0.0f64, f64::max);
         let mut final_action_for_record = actual_action;
-
-        verbose_println!(
-            state,
-            "DEBUG: Player {} taking action {:?} with amount {}",
-            player_idx,
-            actual_action.action,
-            actual_action.amount
-        );
+        let mut coercion =
+            (actual_action.action != action.action).then_some(Coercion::IllegalAction);
 
         // Apply the action's effects
         match actual_action.action {
             ActionEnum::Fold => {
                 state.players_state[player_idx].active = false;
+                state.players_state[player_idx].folded_on = Some(state.stage);
                 state.players_state[player_idx].pot_chips +=
                     state.players_state[player_idx].bet_chips;
                 state.players_state[player_idx].bet_chips = 0.0;
@@ -270,6 +382,9 @@ impl GameStateInterface for AwaitingAction {
                     } else if player_stake - required_chips < 1.0 {
                         // Go all-in if would leave less than 1 chip
                         state.players_state[player_idx].stake = 0.0;
+                        if player_stake > required_chips {
+                            coercion = coercion.or(Some(Coercion::AllIn));
+                        }
                         player_stake
                     } else {
                         state.players_state[player_idx].stake -= required_chips;
@@ -289,17 +404,24 @@ impl GameStateInterface for AwaitingAction {
                 let player_stake = state.players_state[player_idx].stake;
 
                 // Calculate actual bet amount
+                let min_raise_to = state.min_bet * state.rules.min_raise_ratio.max(1.0);
                 let actual_total_bet = if player_stake < state.min_bet || player_stake < 1.0 {
                     // Go all-in if insufficient chips
                     current_player_bet + player_stake
-                } else if desired_total_bet < state.min_bet {
-                    state.min_bet.max(current_player_bet)
+                } else if desired_total_bet < min_raise_to {
+                    coercion = coercion.or(Some(Coercion::MinRaise));
+                    min_raise_to.max(current_player_bet)
                 } else {
                     desired_total_bet
                 };
 
                 let additional_chips = (actual_total_bet - current_player_bet).max(0.0);
                 let final_additional_chips = additional_chips.min(player_stake);
+                if current_player_bet + final_additional_chips != desired_total_bet
+                    && final_additional_chips == player_stake
+                {
+                    coercion = coercion.or(Some(Coercion::AllIn));
+                }
 
                 state.players_state[player_idx].bet_chips += final_additional_chips;
                 state.players_state[player_idx].stake -= final_additional_chips;
@@ -327,14 +449,33 @@ impl GameStateInterface for AwaitingAction {
             player: self.player_to_act_idx,
             action: final_action_for_record,
             stage: state.stage,
-            legal_actions: self.get_legal_actions(state),
+            legal_actions: self.get_legal_actions(state).into_vec(),
+            timestamp: None,
+            think_time: None,
+            forced: false,
+            requested: action,
+            coercion,
+            pot_before,
+            to_call,
+            chips: stake_before - state.players_state[player_idx].stake,
         };
+        record_decision!(
+            state,
+            TraceEvent::new(TraceKind::Action, state.stage)
+                .players(vec![action_record.player])
+                .action(action_record.action.action, action_record.action.amount)
+        );
         state.from_action = Some(action_record.clone());
         state.action_list.push(action_record);
 
         // Check if round is over
         if self.is_round_over(state) {
-            verbose_println!(state, "DEBUG: Round is over, transitioning to next stage");
+            let kind = if state.players_state.iter().filter(|ps| ps.active).count() <= 1 {
+                TraceKind::RoundOverOnePlayerLeft
+            } else {
+                TraceKind::RoundOverBetsMatched
+            };
+            record_decision!(state, TraceEvent::new(kind, state.stage));
             return Ok(Box::new(RoundOver::new()));
         }
 
@@ -345,14 +486,17 @@ impl GameStateInterface for AwaitingAction {
             Ok(self)
         } else {
             // No more players can act - round is over
-            verbose_println!(state, "DEBUG: No more players can act, round over");
+            record_decision!(
+                state,
+                TraceEvent::new(TraceKind::RoundOverNobodyToAct, state.stage)
+            );
             Ok(Box::new(RoundOver::new()))
         }
     }
 
-    fn get_legal_actions(&self, state: &State) -> Vec<ActionEnum> {
+    fn get_legal_actions(&self, state: &State) -> LegalActions {
         if state.final_state || state.stage == Stage::Showdown {
-            return vec![];
+            return LegalActions::new();
         }
 
         // Always use the state's current_player, not our internal player_to_act_idx
@@ -360,16 +504,16 @@ impl GameStateInterface for AwaitingAction {
 
         // If player is all-in, they cannot act
         if player_state.stake == 0.0 {
-            return vec![];
+            return LegalActions::new();
         }
 
-        let mut legal_actions = vec![ActionEnum::Fold];
+        let mut legal_actions: LegalActions = smallvec![ActionEnum::Fold];
 
         // Always allow CheckCall
         legal_actions.push(ActionEnum::CheckCall);
 
-        // Allow BetRaise if player has chips to bet
-        if player_state.stake > 0.0 {
+        // Allow BetRaise if player has chips to bet and the street is not capped
+        if player_state.stake > 0.0 && !raise_cap_reached(state) {
             legal_actions.push(ActionEnum::BetRaise);
         }
 
@@ -405,8 +549,8 @@ impl GameStateInterface for RoundOver {
         Err(StateStatus::IllegalAction)
     }
 
-    fn get_legal_actions(&self, _state: &State) -> Vec<ActionEnum> {
-        vec![] // No legal actions in this state
+    fn get_legal_actions(&self, _state: &State) -> LegalActions {
+        LegalActions::new() // No legal actions in this state
     }
 
     fn state_name(&self) -> String {
@@ -431,8 +575,8 @@ impl GameStateInterface for GameOver {
         Err(StateStatus::IllegalAction)
     }
 
-    fn get_legal_actions(&self, _state: &State) -> Vec<ActionEnum> {
-        vec![]
+    fn get_legal_actions(&self, _state: &State) -> LegalActions {
+        LegalActions::new()
     }
 
     fn state_name(&self) -> String {
@@ -485,7 +629,7 @@ impl StateMachine {
         }
     }
 
-    pub fn get_legal_actions(&self, state: &State) -> Vec<ActionEnum> {
+    pub fn get_legal_actions(&self, state: &State) -> LegalActions {
         self.current_state.get_legal_actions(state)
     }
 
@@ -497,7 +641,8 @@ impl StateMachine {
 #[pymethods]
 impl State {
     #[staticmethod]
-    #[pyo3(signature = (n_players, button, sb, bb, stake, seed, verbose=false))]
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (n_players, button, sb, bb, stake, seed, verbose=false, ante=0.0))]
     pub fn from_seed(
         n_players: u64,
         button: u64,
@@ -506,16 +651,19 @@ impl State {
         stake: f64,
         seed: u64,
         verbose: bool,
+        ante: f64,
     ) -> Result<State, InitStateError> {
-        let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
-        let mut deck: Vec<Card> = Card::collect();
-        deck.shuffle(&mut rng);
-
-        State::from_deck(n_players, button, sb, bb, stake, deck, verbose, seed)
+        let deck = seeds::shuffled_deck(seed);
+        let mut state =
+            State::from_deck(n_players, button, sb, bb, stake, deck, verbose, seed, ante)?;
+        state.hand_id = seeded_uuid(seed);
+        Ok(state)
     }
 
+    /// With an `ante`, every player antes it along with the blinds, as with `post_antes`
     #[staticmethod]
-    #[pyo3(signature = (n_players, button, sb, bb, stake, deck, verbose=false, seed=0))]
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (n_players, button, sb, bb, stake, deck, verbose=false, seed=0, ante=0.0))]
     pub fn from_deck(
         n_players: u64,
         button: u64,
@@ -525,6 +673,7 @@ impl State {
         mut deck: Vec<Card>,
         verbose: bool,
         seed: u64,
+        ante: f64,
     ) -> Result<State, InitStateError> {
         // Validation
         if n_players < 2 {
@@ -563,13 +712,26 @@ impl State {
             });
         }
 
+        if !ante.is_finite() {
+            return Err(InitStateError {
+                msg: "The ante must be a finite number".to_owned(),
+            });
+        }
+
+        if ante < 0.0 {
+            return Err(InitStateError {
+                msg: "The ante cannot be negative".to_owned(),
+            });
+        }
+
         // Create players
-        let mut players_state: Vec<PlayerState> = Vec::new();
+        let (sb_player, bb_player) = State::blind_players(n_players, button);
+        let mut players_state = Players::new();
         for i in 0..n_players {
             let player = (button + i + 1) % n_players;
             let chips = match i {
-                _ if player == (button + 1) % n_players => sb,
-                _ if player == (button + 2) % n_players => bb,
+                _ if player == sb_player => sb,
+                _ if player == bb_player => bb,
                 _ => 0.0,
             };
 
@@ -583,6 +745,13 @@ impl State {
                 active: true,
                 range_idx: -1,
                 last_stage_action: None,
+                hand_hidden: false,
+                folded_on: None,
+                invested: [0.0; 4],
+                ante_chips: 0.0,
+                held_back: 0.0,
+                third_card: None,
+                discarded: None,
             };
             players_state.push(p_state);
         }
@@ -608,12 +777,13 @@ impl State {
         let mut state = State {
             current_player: first_player,
             players_state: players_state,
-            public_cards: Vec::new(),
+            public_cards: Board::new(),
+            second_board: Vec::new(),
             stage: Stage::Preflop,
             button: button,
             from_action: None,
             action_list: Vec::new(),
-            legal_actions: Vec::new(),
+            legal_actions: LegalActions::new(),
             deck: deck,
             final_state: false,
             pot: sb + bb,
@@ -623,6 +793,20 @@ impl State {
             status: StateStatus::Ok,
             verbose: verbose,
             seed: seed,
+            rules: TableRules::default(),
+            clock: None,
+            all_in_equities: Vec::new(),
+            engine_diagnostic: None,
+            trace: Vec::new(),
+            hand_id: random_uuid(),
+            table_id: None,
+            session_id: None,
+            perspective: None,
+            full_view: false,
+            range_buckets: None,
+            discard_on: None,
+            owed_blinds: Vec::new(),
+            perf: PerfProbe::default(),
             fsm_state: "AwaitingAction".to_string(),
         };
 
@@ -633,194 +817,1191 @@ impl State {
         let fsm = StateMachine::new(initial_fsm_state);
         state.legal_actions = fsm.get_legal_actions(&state);
 
+        if ante > 0.0 {
+            return state.post_antes(ante, false, false);
+        }
         Ok(state)
     }
 
+    /// Apply `action`. With the clock on, the action is stamped with the current time.
     pub fn apply_action(&self, action: Action) -> State {
-        match self.status {
-            StateStatus::Ok => (),
-            _ => return self.clone(),
+        match self.clock {
+            Some(_) => self.apply_action_at(action, now()),
+            None => self.step(action),
+        }
+    }
+
+    /// Apply `action` as taken at `timestamp` (seconds since the Unix epoch), for replays and
+    /// simulated clocks. The think time is measured from the previous timestamp of the hand.
+    pub fn apply_action_at(&self, action: Action, timestamp: f64) -> State {
+        let mut state = self.step(action);
+        if state.action_list.len() > self.action_list.len() {
+            let think_time = self.clock.map(|started| (timestamp - started).max(0.0));
+            if let Some(record) = state.action_list.last_mut() {
+                record.timestamp = Some(timestamp);
+                record.think_time = think_time;
+                state.from_action = Some(record.clone());
+            }
+            state.clock = Some(timestamp);
+        }
+        state
+    }
+
+    /// Apply the action `policy` forces on the current player, e.g. when their time is up.
+    /// The action is recorded with `forced` set and a `Coercion.Timeout`.
+    #[pyo3(signature = (policy=DefaultPolicy::CheckElseFold))]
+    pub fn apply_default_action(&self, policy: DefaultPolicy) -> State {
+        let mut state = self.apply_action(self.default_action(policy));
+        if let Some(record) = state.action_list.get_mut(self.action_list.len()) {
+            record.forced = true;
+            record.coercion = Some(Coercion::Timeout);
+            state.from_action = Some(record.clone());
         }
+        state
+    }
 
+    /// Disconnect protection: the current player stays in the hand for the chips they have put
+    /// in so far, as if all-in, and the rest of their stack goes to `held_back`. Recorded as a
+    /// forced call of nothing with a `Coercion.Disconnected`.
+    pub fn apply_protected_all_in(&self) -> State {
         if self.final_state {
             return self.clone();
         }
+        let mut protected = self.clone();
+        if let Some(ps) = protected
+            .players_state
+            .get_mut(self.current_player as usize)
+        {
+            ps.held_back += ps.stake;
+            ps.stake = 0.0;
+        }
+        let mut state = protected.apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+        if let Some(record) = state.action_list.get_mut(self.action_list.len()) {
+            record.forced = true;
+            record.coercion = Some(Coercion::Disconnected);
+            state.from_action = Some(record.clone());
+        }
+        state
+    }
 
-        // If we're at showdown, no actions are allowed - handle showdown and finish
-        if self.stage == Stage::Showdown {
-            let mut new_state = self.clone();
-            new_state.handle_showdown();
-            return new_state;
+    /// The same state with the clock started, so every action records when it was taken and
+    /// how long the player thought. The first turn starts at `timestamp`, now by default.
+    #[pyo3(signature = (timestamp=None))]
+    pub fn with_clock(&self, timestamp: Option<f64>) -> State {
+        let mut state = self.clone();
+        state.clock = Some(timestamp.unwrap_or_else(now));
+        state
+    }
+
+    /// The same state with `range_idx` after the flop set to the bucket of the hand's
+    /// strength on the board, out of `n_buckets` with 0 for the strongest hands, instead of
+    /// the preflop index the hand was dealt with. The strength is the share of opponent hands beaten at showdown, so it
+    /// costs about a thousand hand evaluations per street.
+    pub fn with_range_buckets(&self, n_buckets: u32) -> Result<State, InitStateError> {
+        if n_buckets == 0 {
+            return Err(InitStateError {
+                msg: "Range buckets must be at least 1".to_owned(),
+            });
         }
+        let mut state = self.clone();
+        state.range_buckets = Some(n_buckets);
+        state.update_range_indices();
+        Ok(state)
+    }
 
-        let mut new_state = self.clone();
+    /// The same state with the Python getters showing the hand of `player` only, no hand when
+    /// `None`. Hands shown down stay visible.
+    #[pyo3(signature = (player))]
+    pub fn with_perspective(&self, player: Option<u64>) -> State {
+        let mut state = self.clone();
+        state.perspective = player;
+        state
+    }
 
-        // Create FSM based on current state
-        let fsm_state: Box<dyn GameStateInterface> =
-            if new_state.stage == Stage::Showdown || new_state.final_state {
-                Box::new(GameOver)
-            } else {
-                // Determine current betting round context
-                let active_players = new_state
-                    .players_state
-                    .iter()
-                    .filter(|ps| ps.active)
-                    .count();
+    /// The same state with the Python getters showing the deck and every hand, for debugging
+    /// and analysis tools. Never hand it to an agent.
+    pub fn unsafe_full_view(&self) -> State {
+        let mut state = self.clone();
+        state.full_view = true;
+        state
+    }
 
-                // Calculate the current maximum bet to determine amount_to_call
-                let max_bet = new_state
-                    .players_state
-                    .iter()
-                    .filter(|ps| ps.active)
-                    .map(|ps| ps.bet_chips)
-                    .fold(0.0f64, f64::max);
+    #[getter(deck)]
+    fn py_deck(&self) -> PyResult<Vec<Card>> {
+        if !self.full_view {
+            return Err(PyPermissionError::new_err(
+                "The deck is hidden, use cards_remaining() or unsafe_full_view().deck",
+            ));
+        }
+        Ok(self.deck.clone())
+    }
 
-                let context =
-                    BettingRoundContext::new(max_bet, active_players, new_state.current_player);
-                Box::new(AwaitingAction::new(new_state.current_player, context))
-            };
+    #[getter(engine_diagnostic)]
+    fn py_engine_diagnostic(&self) -> PyResult<Option<String>> {
+        if !self.full_view {
+            return Err(PyPermissionError::new_err(
+                "The diagnostic holds every hand, use unsafe_full_view().engine_diagnostic",
+            ));
+        }
+        Ok(self.engine_diagnostic.clone())
+    }
 
-        let mut fsm = StateMachine::new(fsm_state);
+    #[getter(players_state)]
+    fn py_players_state(&self) -> Vec<PlayerState> {
+        self.visible_players().into_vec()
+    }
 
-        match fsm.apply_action(&mut new_state, action) {
-            Ok(()) => {
-                // Check if we need to transition to next stage
-                if fsm.is_final() && !new_state.final_state {
-                    new_state.advance_to_next_stage_or_showdown();
+    #[setter(players_state)]
+    fn set_py_players_state(&mut self, players_state: Vec<PlayerState>) {
+        self.players_state = players_state
+            .into_iter()
+            .map(|ps| PlayerState {
+                hand_hidden: false,
+                ..ps
+            })
+            .collect();
+    }
 
-                    // After advancing to next stage, create a new FSM for the new stage
-                    if !new_state.final_state && new_state.stage != Stage::Showdown {
-                        let active_players = new_state
-                            .players_state
-                            .iter()
-                            .filter(|ps| ps.active)
-                            .count();
+    #[getter(public_cards)]
+    fn py_public_cards(&self) -> Vec<Card> {
+        self.public_cards.to_vec()
+    }
 
-                        let max_bet = new_state
-                            .players_state
-                            .iter()
-                            .filter(|ps| ps.active)
-                            .map(|ps| ps.bet_chips)
-                            .fold(0.0f64, f64::max);
+    #[setter(public_cards)]
+    fn set_py_public_cards(&mut self, public_cards: Vec<Card>) {
+        self.public_cards = public_cards.into();
+    }
 
-                        let context = BettingRoundContext::new(
-                            max_bet,
-                            active_players,
-                            new_state.current_player,
-                        );
-                        let new_fsm_state =
-                            Box::new(AwaitingAction::new(new_state.current_player, context));
-                        let new_fsm = StateMachine::new(new_fsm_state);
-                        new_state.legal_actions = new_fsm.get_legal_actions(&new_state);
-                    } else {
-                        new_state.legal_actions = vec![];
-                    }
-                } else {
-                    // Update legal actions with current FSM
-                    new_state.legal_actions = fsm.get_legal_actions(&new_state);
-                }
-                new_state
-            }
-            Err(status) => {
-                new_state.status = status;
-                new_state
-            }
-        }
+    #[getter(legal_actions)]
+    fn py_legal_actions(&self) -> Vec<ActionEnum> {
+        self.legal_actions.to_vec()
     }
 
-    pub fn __str__(&self) -> PyResult<String> {
-        Ok(format!("{:#?}", self))
+    #[setter(legal_actions)]
+    fn set_py_legal_actions(&mut self, legal_actions: Vec<ActionEnum>) {
+        self.legal_actions = legal_actions.into();
     }
-}
 
-impl State {
-    /// Advance to the next stage or handle showdown
-    fn advance_to_next_stage_or_showdown(&mut self) {
-        verbose_println!(self, "DEBUG: Advancing from stage {:?}", self.stage);
+    /// The same state with the given ids, the others are kept. Records of the hand (actions,
+    /// views, datasets, server messages) carry them, so they can be joined with each other.
+    #[pyo3(signature = (hand_id=None, table_id=None, session_id=None))]
+    pub fn with_ids(
+        &self,
+        hand_id: Option<String>,
+        table_id: Option<String>,
+        session_id: Option<String>,
+    ) -> State {
+        let mut state = self.clone();
+        if let Some(hand_id) = hand_id {
+            state.hand_id = hand_id;
+        }
+        state.table_id = table_id.or(state.table_id);
+        state.session_id = session_id.or(state.session_id);
+        state
+    }
 
-        // Move all bet_chips to pot_chips
-        for player_state in &mut self.players_state {
-            player_state.pot_chips += player_state.bet_chips;
-            player_state.bet_chips = 0.0;
-            player_state.last_stage_action = None; // Reset for new stage
+    /// The same state played under `rules`. Rules apply to the whole hand, so they can only
+    /// be changed before the first action. With a `hand_cap`, each player's chips over the
+    /// cap are held back, so reaching the cap puts them all-in.
+    pub fn with_rules(&self, rules: TableRules) -> Result<State, InitStateError> {
+        if !self.action_list.is_empty() {
+            return Err(InitStateError {
+                msg: "Table rules can only be changed before the first action".to_owned(),
+            });
+        }
+        let rules = rules.checked().map_err(|msg| InitStateError { msg })?;
+        if rules.hand_cap.is_some_and(|cap| cap < self.bb) {
+            return Err(InitStateError {
+                msg: "The hand cap must be at least the big blind".to_owned(),
+            });
         }
 
-        // Advance stage
-        self.stage = match self.stage {
-            Stage::Preflop => Stage::Flop,
-            Stage::Flop => Stage::Turn,
-            Stage::Turn => Stage::River,
-            Stage::River => {
-                // When we reach showdown, handle it immediately
-                self.stage = Stage::Showdown;
-                self.handle_showdown();
-                return;
+        let mut state = self.clone();
+        state.rules = rules;
+        if let Some(cap) = rules.hand_cap {
+            for ps in state.players_state.iter_mut() {
+                let over = ps.stake + ps.bet_chips + ps.pot_chips - cap;
+                let kept_out = over.clamp(0.0, ps.stake);
+                ps.stake -= kept_out;
+                ps.held_back += kept_out;
             }
-            Stage::Showdown => {
-                self.handle_showdown();
-                return;
+        }
+        state.legal_actions = available_actions(&state);
+        Ok(state)
+    }
+
+    /// `player` posts a dead blind of `amount` before the first action: the chips go straight
+    /// to the pot and do not count toward the player's bet on the street
+    pub fn post_dead_blind(&self, player: u64, amount: f64) -> Result<State, InitStateError> {
+        if !self.action_list.is_empty() {
+            return Err(InitStateError {
+                msg: "Dead blinds can only be posted before the first action".to_owned(),
+            });
+        }
+        let stake = match self.players_state.get(player as usize) {
+            Some(ps) => ps.stake,
+            None => {
+                return Err(InitStateError {
+                    msg: format!("Player {} is not at the table", player),
+                })
             }
         };
+        if amount <= 0.0 || amount >= stake {
+            return Err(InitStateError {
+                msg: format!(
+                    "Player {} cannot post a dead blind of {} with {} behind",
+                    player, amount, stake
+                ),
+            });
+        }
 
-        // Deal community cards
-        let cards_to_deal = match self.stage {
-            Stage::Flop => 3,
-            Stage::Turn | Stage::River => 1,
-            _ => 0,
-        };
+        let mut state = self.clone();
+        let ps = &mut state.players_state[player as usize];
+        ps.stake -= amount;
+        ps.pot_chips += amount;
+        state.pot += amount;
+        Ok(state)
+    }
 
-        for _ in 0..cards_to_deal {
-            if !self.deck.is_empty() {
-                self.public_cards.push(self.deck.remove(0));
+    /// `players`, back from sitting out or new at the table, owe the big blind they missed.
+    /// A player in the big blind pays it as usual; the others post it dead with
+    /// `post_owed_blind`, or play the hand and still owe it. Players waiting for the big blind
+    /// are dealt out of the hand, see `missed_blind_seating`.
+    pub fn owe_blinds(&self, players: Vec<u64>) -> Result<State, InitStateError> {
+        if self.stage != Stage::Preflop || !self.action_list.is_empty() {
+            return Err(InitStateError {
+                msg: "Missed blinds can only be owed preflop before the first action".to_owned(),
+            });
+        }
+        let n_players = self.players_state.len() as u64;
+        let (_, big_blind) = State::blind_players(n_players, self.button);
+        let mut state = self.clone();
+        for player in players {
+            if player >= n_players {
+                return Err(InitStateError {
+                    msg: format!("Player {} is not at the table", player),
+                });
+            }
+            if state.owed_blinds.iter().any(|owed| owed.player == player) {
+                return Err(InitStateError {
+                    msg: format!("Player {} already owes a blind", player),
+                });
             }
+            state.owed_blinds.push(BlindObligation {
+                player,
+                amount: state.bb,
+                settlement: if player == big_blind {
+                    BlindSettlement::BigBlind
+                } else {
+                    BlindSettlement::Pending
+                },
+            });
         }
+        Ok(state)
+    }
 
-        verbose_println!(
-            self,
-            "DEBUG: Advanced to {:?}, dealt {} cards",
-            self.stage,
-            cards_to_deal
-        );
+    /// `player` posts the blind they owe as a dead blind, see `post_dead_blind`
+    pub fn post_owed_blind(&self, player: u64) -> Result<State, InitStateError> {
+        let index = self.pending_obligation(player)?;
+        let mut state = self.post_dead_blind(player, self.owed_blinds[index].amount)?;
+        state.owed_blinds[index].settlement = BlindSettlement::PostedDead;
+        Ok(state)
+    }
 
-        // Reset min_bet for new round
-        self.min_bet = 0.0;
+    /// Players of the hand who still owe a missed blind once it is over
+    pub fn pending_blinds(&self) -> Vec<u64> {
+        self.owed_blinds
+            .iter()
+            .filter(|owed| owed.settlement == BlindSettlement::Pending)
+            .map(|owed| owed.player)
+            .collect()
+    }
 
-        // Check if we should go straight to showdown
-        let active_players: Vec<&PlayerState> =
-            self.players_state.iter().filter(|ps| ps.active).collect();
+    /// `player` posts a live straddle raising the preflop bet to `amount` before the first
+    /// action. Preflop action then starts with the player after the straddler, who acts last:
+    /// after the blinds for a button (Mississippi) straddle. The next player can re-straddle.
+    pub fn post_straddle(&self, player: u64, amount: f64) -> Result<State, InitStateError> {
+        if self.stage != Stage::Preflop || !self.action_list.is_empty() {
+            return Err(InitStateError {
+                msg: "Straddles can only be posted preflop before the first action".to_owned(),
+            });
+        }
+        let ps = match self.players_state.get(player as usize) {
+            Some(ps) => ps,
+            None => {
+                return Err(InitStateError {
+                    msg: format!("Player {} is not at the table", player),
+                })
+            }
+        };
+        if ps.bet_chips > 0.0 {
+            return Err(InitStateError {
+                msg: format!("Player {} has already posted a blind or a straddle", player),
+            });
+        }
+        if amount <= self.min_bet || amount >= ps.stake {
+            return Err(InitStateError {
+                msg: format!(
+                    "Player {} cannot straddle to {} over a bet of {} with {} behind",
+                    player, amount, self.min_bet, ps.stake
+                ),
+            });
+        }
 
-        let players_with_chips = active_players.iter().filter(|ps| ps.stake > 0.0).count();
+        let mut state = self.clone();
+        let ps = &mut state.players_state[player as usize];
+        ps.stake -= amount;
+        ps.bet_chips = amount;
+        state.pot += amount;
+        state.min_bet = amount;
+        state.current_player = (player + 1) % state.players_state.len() as u64;
+        state.legal_actions = available_actions(&state);
+        Ok(state)
+    }
 
-        if active_players.len() <= 1 || players_with_chips <= 1 {
-            verbose_println!(
-                self,
-                "DEBUG: Forcing showdown - insufficient active players with chips"
-            );
-            self.complete_to_showdown();
-            return;
+    /// Every player posts an ante of `ante` before the first action, or with `big_blind_ante`
+    /// the big blind posts it alone for the table. Antes are dead money. A player who cannot
+    /// cover both their blind and the ante pays the blind first, or the ante with `ante_first`.
+    #[pyo3(signature = (ante, big_blind_ante=false, ante_first=false))]
+    pub fn post_antes(
+        &self,
+        ante: f64,
+        big_blind_ante: bool,
+        ante_first: bool,
+    ) -> Result<State, InitStateError> {
+        if self.stage != Stage::Preflop || !self.action_list.is_empty() {
+            return Err(InitStateError {
+                msg: "Antes can only be posted preflop before the first action".to_owned(),
+            });
+        }
+        if ante <= 0.0 {
+            return Err(InitStateError {
+                msg: "The ante must be greater than 0".to_owned(),
+            });
         }
 
-        // Find first player to act (left of button)
-        let first_player = (self.button + 1) % self.players_state.len() as u64;
-        self.current_player = first_player;
-        let mut attempts = 0;
+        let mut state = self.clone();
+        let n_players = state.players_state.len() as u64;
+        let (_, big_blind) = State::blind_players(n_players, state.button);
+        for ps in &mut state.players_state {
+            if big_blind_ante && ps.player != big_blind {
+                continue;
+            }
+            let total = ps.bet_chips + ps.stake;
+            let (blind, paid) = if ante_first {
+                let paid = ante.min(total);
+                (ps.bet_chips.min(total - paid), paid)
+            } else {
+                (ps.bet_chips, ante.min(ps.stake))
+            };
+            ps.bet_chips = blind;
+            ps.pot_chips += paid;
+            ps.ante_chips += paid;
+            ps.stake = total - blind - paid;
+        }
+        state.pot = state
+            .players_state
+            .iter()
+            .map(|ps| ps.bet_chips + ps.pot_chips)
+            .sum();
 
-        while attempts < self.players_state.len() {
-            let player_state = &self.players_state[self.current_player as usize];
-            if player_state.active && player_state.stake > 0.0 {
+        // A player put all-in by the ante cannot open the action
+        for _ in 0..n_players {
+            if state.players_state[state.current_player as usize].stake > 0.0 {
                 break;
             }
-
-            self.current_player = (self.current_player + 1) % self.players_state.len() as u64;
-            attempts += 1;
+            state.current_player = (state.current_player + 1) % n_players;
         }
+        state.legal_actions = available_actions(&state);
+        Ok(state)
+    }
 
-        if attempts >= self.players_state.len() {
-            verbose_println!(self, "DEBUG: No players can act, going to showdown");
+    /// The same hand with each player bringing their own stack, in player order, as in a
+    /// tournament. Call it before the antes and the first action. A player short of their
+    /// blind posts what they have, all-in.
+    pub fn with_stacks(&self, stacks: Vec<f64>) -> Result<State, InitStateError> {
+        if self.stage != Stage::Preflop
+            || !self.action_list.is_empty()
+            || self.players_state.iter().any(|ps| ps.pot_chips > 0.0)
+        {
+            return Err(InitStateError {
+                msg: "Stacks can only be set preflop before the antes and the first action"
+                    .to_owned(),
+            });
+        }
+        if stacks.len() != self.players_state.len() {
+            return Err(InitStateError {
+                msg: format!(
+                    "{} stacks for {} players",
+                    stacks.len(),
+                    self.players_state.len()
+                ),
+            });
+        }
+        if stacks
+            .iter()
+            .any(|stack| !stack.is_finite() || *stack <= 0.0)
+        {
+            return Err(InitStateError {
+                msg: "Every stack must be greater than 0".to_owned(),
+            });
+        }
+
+        let mut state = self.clone();
+        for (ps, stack) in state.players_state.iter_mut().zip(stacks) {
+            ps.bet_chips = ps.bet_chips.min(stack);
+            ps.stake = stack - ps.bet_chips;
+        }
+        state.pot = state.players_state.iter().map(|ps| ps.bet_chips).sum();
+
+        // A player put all-in by their blind cannot open the action
+        let n_players = state.players_state.len() as u64;
+        for _ in 0..n_players {
+            if state.players_state[state.current_player as usize].stake > 0.0 {
+                break;
+            }
+            state.current_player = (state.current_player + 1) % n_players;
+        }
+        state.legal_actions = available_actions(&state);
+        Ok(state)
+    }
+
+    /// Bomb pot: every player antes `ante`, preflop betting is skipped and play begins on
+    /// the flop. With `double_board` a second board is dealt and each board wins half the pot.
+    #[staticmethod]
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (n_players, button, ante, bb, stake, deck, double_board=false, verbose=false, seed=0))]
+    pub fn bomb_pot(
+        n_players: u64,
+        button: u64,
+        ante: f64,
+        bb: f64,
+        stake: f64,
+        deck: Vec<Card>,
+        double_board: bool,
+        verbose: bool,
+        seed: u64,
+    ) -> Result<State, InitStateError> {
+        if ante <= 0.0 {
+            return Err(InitStateError {
+                msg: "The ante must be greater than 0".to_owned(),
+            });
+        }
+
+        let mut state = State::from_deck(
+            n_players,
+            button,
+            bb / 2.0,
+            bb,
+            stake,
+            deck,
+            verbose,
+            seed,
+            0.0,
+        )?;
+        state.open_bomb_pot(ante, double_board);
+        Ok(state)
+    }
+
+    /// Ante-only hand: every player antes `ante` and there are no blinds, so preflop betting
+    /// opens to the left of the button with no bet to call, as on the later streets
+    #[staticmethod]
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (n_players, button, ante, bb, stake, deck, verbose=false, seed=0))]
+    pub fn ante_only(
+        n_players: u64,
+        button: u64,
+        ante: f64,
+        bb: f64,
+        stake: f64,
+        deck: Vec<Card>,
+        verbose: bool,
+        seed: u64,
+    ) -> Result<State, InitStateError> {
+        if ante <= 0.0 {
+            return Err(InitStateError {
+                msg: "The ante must be greater than 0".to_owned(),
+            });
+        }
+
+        let mut state = State::from_deck(
+            n_players,
+            button,
+            bb / 2.0,
+            bb,
+            stake,
+            deck,
+            verbose,
+            seed,
+            0.0,
+        )?;
+        state.open_ante_only(ante);
+        Ok(state)
+    }
+
+    /// Pineapple hand: every player is dealt a third hole card and throws one of the three
+    /// away with `discard`, before the preflop betting, or once the flop is dealt for Crazy
+    /// Pineapple. The rest is played as hold'em. The third cards are dealt after the first
+    /// two, in the same order, and the board after them.
+    #[staticmethod]
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (n_players, button, sb, bb, stake, deck, crazy=false, verbose=false, seed=0))]
+    pub fn pineapple(
+        n_players: u64,
+        button: u64,
+        sb: f64,
+        bb: f64,
+        stake: f64,
+        deck: Vec<Card>,
+        crazy: bool,
+        verbose: bool,
+        seed: u64,
+    ) -> Result<State, InitStateError> {
+        if deck.len() < 3 * n_players as usize {
+            return Err(InitStateError {
+                msg: "The number of cards in the deck must be at least 3*n_players".to_owned(),
+            });
+        }
+
+        let mut state =
+            State::from_deck(n_players, button, sb, bb, stake, deck, verbose, seed, 0.0)?;
+        for i in 0..n_players {
+            let player = (button + i + 1) % n_players;
+            state.players_state[player as usize].third_card = Some(state.deck.remove(0));
+        }
+        state.discard_on = Some(if crazy { Stage::Flop } else { Stage::Preflop });
+        Ok(state)
+    }
+
+    /// `player` of a Pineapple hand throws `card`, one of their three hole cards, away and
+    /// plays the other two. A player who acts without discarding, or reaches the showdown
+    /// all-in, throws away the third card dealt.
+    pub fn discard(&self, player: u64, card: Card) -> Result<State, InitStateError> {
+        let discard_on = match self.discard_on {
+            Some(stage) => stage,
+            None => {
+                return Err(InitStateError {
+                    msg: "Only the players of a Pineapple hand discard".to_owned(),
+                })
+            }
+        };
+        if (self.stage as usize) < (discard_on as usize) {
+            return Err(InitStateError {
+                msg: format!("Players discard once the {:?} is dealt", discard_on),
+            });
+        }
+        if self.final_state {
+            return Err(InitStateError {
+                msg: "The hand is over".to_owned(),
+            });
+        }
+        let third_card = match self.players_state.get(player as usize) {
+            Some(ps) if ps.active => ps.third_card,
+            _ => None,
+        };
+        let third_card = match third_card {
+            Some(third_card) => third_card,
+            None => {
+                return Err(InitStateError {
+                    msg: format!("Player {} has no card to discard", player),
+                })
+            }
+        };
+
+        let mut state = self.clone();
+        let ps = &mut state.players_state[player as usize];
+        if card == ps.hand.0 {
+            ps.hand.0 = third_card;
+        } else if card == ps.hand.1 {
+            ps.hand.1 = third_card;
+        } else if card != third_card {
+            return Err(InitStateError {
+                msg: format!("Player {} does not hold {}", player, card),
+            });
+        }
+        ps.third_card = None;
+        ps.discarded = Some(card);
+        state.update_range_indices();
+        Ok(state)
+    }
+
+    /// Hand strength indicator for a single player, computed only from that player's view.
+    /// `None` unless `player` is the perspective or the state is a full view.
+    #[pyo3(signature = (player, n_samples=1000))]
+    pub fn hand_strength(&self, player: u64, n_samples: usize) -> Option<HandStrength> {
+        if !self.sees_as(player) {
+            return None;
+        }
+        hand_strength::hand_strength(self, player, n_samples)
+    }
+
+    /// Number of cards left in the deck, without revealing them
+    pub fn cards_remaining(&self) -> usize {
+        self.deck.len()
+    }
+
+    /// Clones, heap allocations and evaluator calls of the action that led to this state,
+    /// `None` unless the crate was built with the `profiling` feature
+    pub fn perf_counters(&self) -> Option<PerfCounters> {
+        cfg!(feature = "profiling").then_some(self.perf.0)
+    }
+
+    /// Cards `player` knows cannot come: the board (both boards of a double board), their
+    /// own hand and, in a Pineapple hand, their third or discarded card. `None` for an
+    /// unknown player, and unless `player` is the perspective or the state is a full view.
+    pub fn known_dead_cards(&self, player: u64) -> Option<Vec<Card>> {
+        if !self.sees_as(player) {
+            return None;
+        }
+        let ps = self.players_state.get(player as usize)?;
+        Some(
+            self.public_cards
+                .iter()
+                .chain(&self.second_board)
+                .copied()
+                .chain([ps.hand.0, ps.hand.1])
+                .chain(ps.third_card)
+                .chain(ps.discarded)
+                .collect(),
+        )
+    }
+
+    /// Players still in the hand grouped by the strength of their hand on the current board,
+    /// best first, tied players sharing a group. Before the flop only the hole cards count.
+    /// It compares every hand, so only a full view can rank them.
+    pub fn current_leader(&self) -> PyResult<Vec<Vec<u64>>> {
+        if !self.full_view {
+            return Err(PyPermissionError::new_err(
+                "The hands are hidden, use unsafe_full_view().current_leader()",
+            ));
+        }
+        Ok(hand_ranking(self))
+    }
+
+    /// Smallest and largest total bet the current player can raise to, `None` when they
+    /// cannot raise. Raises are at least a big blind above the current bet, and never above
+    /// what the deepest opponent still in can call.
+    pub fn legal_bet_range(&self) -> Option<(f64, f64)> {
+        bet_range(self)
+    }
+
+    /// Fold, CheckCall, then `n_bet_buckets` bet sizes, true where the action is legal for
+    /// the current player. The bet buckets raise by 2k/n of the pot after calling
+    /// (k = 1..n-1), the last one is all-in.
+    #[pyo3(signature = (n_bet_buckets=10))]
+    pub fn legal_action_mask(&self, n_bet_buckets: usize) -> Vec<bool> {
+        let mut mask = vec![
+            self.legal_actions.contains(&ActionEnum::Fold),
+            self.legal_actions.contains(&ActionEnum::CheckCall),
+        ];
+        mask.extend(bet_buckets(self, n_bet_buckets).iter().map(Option::is_some));
+        mask
+    }
+
+    /// Action of entry `index` of `legal_action_mask(n_bet_buckets)`, `None` when illegal
+    #[pyo3(signature = (index, n_bet_buckets=10))]
+    pub fn mask_action(&self, index: usize, n_bet_buckets: usize) -> Option<Action> {
+        match index {
+            0 | 1 => {
+                let action = [ActionEnum::Fold, ActionEnum::CheckCall][index];
+                self.legal_actions
+                    .contains(&action)
+                    .then(|| Action::new(action, 0.0))
+            }
+            _ => bet_buckets(self, n_bet_buckets)
+                .get(index - 2)
+                .copied()
+                .flatten()
+                .map(|total| Action::new(ActionEnum::BetRaise, total)),
+        }
+    }
+
+    /// The same state with every card `hero` (by default the current player) cannot see dealt
+    /// again: the opponents' hole cards and the rest of the deck. `constraints` restricts the
+    /// hands of some opponents to a range. Pot, bets and history are kept, so this is the
+    /// determinization step of determinized MCTS.
+    #[pyo3(signature = (seed, constraints=None, hero=None))]
+    pub fn redeal_unknown(
+        &self,
+        seed: u64,
+        constraints: Option<HashMap<u64, HandRange>>,
+        hero: Option<u64>,
+    ) -> Result<State, InitStateError> {
+        redeal::redeal_unknown(
+            self,
+            seed,
+            hero.unwrap_or(self.current_player),
+            &constraints.unwrap_or_default(),
+        )
+        .map_err(|msg| InitStateError { msg })
+    }
+
+    /// Whether the current player can take `action` as given and, when the engine would
+    /// adjust or ignore it, what it applies instead and why
+    pub fn explain_action(&self, action: Action) -> ActionExplanation {
+        explain::explain_action(self, action)
+    }
+
+    /// The action so far in compact notation, e.g. `r150-c, x-b33-c`, with sizes in percent of the pot
+    pub fn betting_line(&self) -> String {
+        betting_line::betting_line(self)
+    }
+
+    /// Chips put in by each player on the current street, and the pot around every action
+    pub fn street_contributions(&self) -> StreetContributions {
+        contributions::street_contributions(self)
+    }
+
+    /// Totals of the betting round the last action closed, `None` while the street goes on
+    pub fn closed_street(&self) -> Option<StreetSummary> {
+        contributions::closed_street(self)
+    }
+
+    /// Insurance price for the favourite of an all-in on the flop or the turn, once the hand is over
+    pub fn insurance_quote(&self, player: u64) -> Option<InsuranceQuote> {
+        insurance::insurance_quote(self, player)
+    }
+
+    /// Reward each player could expect over every runout of a hand decided by an all-in
+    /// before the river, by player; `None` for hands decided any other way
+    pub fn all_in_ev(&self) -> Option<Vec<f64>> {
+        insurance::all_in_ev(self)
+    }
+
+    /// Engine invariants violated by this state (chip conservation, unique cards, ...)
+    pub fn check_invariants(&self) -> Vec<String> {
+        invariants::check_invariants(self)
+    }
+
+    /// The public information of the state, cheap to clone and share with observers
+    pub fn view(&self) -> StateView {
+        StateView::new(self)
+    }
+
+    /// Set `pot` back to the chips the players have put in (`bet_chips + pot_chips`) and
+    /// return it. The engine keeps the pot in step after every action; this is for states
+    /// built or edited by hand.
+    pub fn recompute_pot(&mut self) -> f64 {
+        self.pot = self.chips_in_pot();
+        self.pot
+    }
+
+    /// Index of the current street, 0 for the preflop to 4 for the showdown
+    #[getter]
+    pub fn street_index(&self) -> u32 {
+        self.stage as u32
+    }
+
+    /// Versioned JSON document of the state, every hand and the deck included. It is for
+    /// saving and restoring states, not for showing them to agents.
+    pub fn to_json(&self) -> PyResult<String> {
+        schema::to_document(self)
+            .map(|document| document.to_string())
+            .map_err(PyValueError::new_err)
+    }
+
+    /// Read a state back from `to_json`, migrating documents of older schema versions
+    #[staticmethod]
+    pub fn from_json(json: &str) -> PyResult<State> {
+        let document = serde_json::from_str(json)
+            .map_err(|e| PyValueError::new_err(format!("Invalid state document: {}", e)))?;
+        schema::from_document(document).map_err(PyValueError::new_err)
+    }
+
+    /// Pickle states as their JSON document
+    pub fn __reduce__(&self, py: Python) -> PyResult<(PyObject, (String,))> {
+        let from_json = py.get_type::<State>().getattr("from_json")?;
+        Ok((from_json.into(), (self.to_json()?,)))
+    }
+
+    pub fn __str__(&self) -> PyResult<String> {
+        let mut state = self.clone();
+        state.players_state = self.visible_players();
+        if !self.full_view {
+            state.deck.clear();
+        }
+        Ok(format!("{:#?}", state))
+    }
+}
+
+impl State {
+    /// Take back the blinds of a hand fresh from `from_deck` and have every player ante
+    /// `ante` instead, all-in when short
+    fn post_antes_for_blinds(&mut self, ante: f64) {
+        for ps in &mut self.players_state {
+            ps.stake += ps.bet_chips;
+            ps.bet_chips = 0.0;
+            ps.pot_chips = ante.min(ps.stake);
+            ps.ante_chips = ps.pot_chips;
+            ps.stake -= ps.pot_chips;
+        }
+        self.pot = self.players_state.iter().map(|ps| ps.pot_chips).sum();
+    }
+
+    /// Deal the rest of a hand nobody can act in before the first action: every player but
+    /// at most one is all-in from the blinds and antes, and that one has nothing to call
+    pub(crate) fn run_out_if_nobody_acts(&mut self) {
+        let max_bet = self
+            .players_state
+            .iter()
+            .map(|ps| ps.bet_chips)
+            .fold(0.0, f64::max);
+        let with_chips: Vec<&PlayerState> = self
+            .players_state
+            .iter()
+            .filter(|ps| ps.active && ps.stake > 0.0)
+            .collect();
+        let to_call = with_chips.iter().any(|ps| ps.bet_chips < max_bet);
+        if self.final_state || with_chips.len() > 1 || to_call {
+            return;
+        }
+        record_decision!(
+            self,
+            TraceEvent::new(TraceKind::ShowdownForcedAllIn, self.stage)
+        );
+        self.complete_to_showdown();
+        self.legal_actions = LegalActions::new();
+    }
+
+    /// Turn a hand fresh from `from_deck` into a bomb pot, see `State::bomb_pot`
+    pub(crate) fn open_bomb_pot(&mut self, ante: f64, double_board: bool) {
+        // No blinds in a bomb pot, only the ante
+        self.post_antes_for_blinds(ante);
+        self.stage = Stage::Flop;
+        self.deal_board_cards(3, double_board);
+        self.start_betting_round();
+        self.legal_actions = if self.final_state {
+            LegalActions::new()
+        } else {
+            available_actions(self)
+        };
+    }
+
+    /// Turn a hand fresh from `from_deck` into an ante-only hand, see `State::ante_only`
+    pub(crate) fn open_ante_only(&mut self, ante: f64) {
+        self.post_antes_for_blinds(ante);
+        self.start_betting_round();
+        self.legal_actions = if self.final_state {
+            LegalActions::new()
+        } else {
+            available_actions(self)
+        };
+    }
+
+    /// Players posting the small and the big blind in a hand of `n_players` dealt with `button`
+    pub fn blind_players(n_players: u64, button: u64) -> (u64, u64) {
+        ((button + 1) % n_players, (button + 2) % n_players)
+    }
+
+    /// Whether the Python getters show the hand of `player`: always with a full view,
+    /// otherwise for the perspective and the players who showed down
+    fn hand_visible(&self, player: u64) -> bool {
+        self.full_view || self.perspective == Some(player) || self.showed_down(player)
+    }
+
+    /// Whether the state is seen as `player`, or with a full view, for the accessors that
+    /// reveal what a player knows
+    fn sees_as(&self, player: u64) -> bool {
+        self.full_view || self.perspective == Some(player)
+    }
+
+    /// Whether `player` showed their hand down: the hand ended at the showdown and they were
+    /// still in it
+    pub(crate) fn showed_down(&self, player: u64) -> bool {
+        self.final_state
+            && self.stage == Stage::Showdown
+            && self
+                .players_state
+                .get(player as usize)
+                .is_some_and(|ps| ps.folded_on.is_none())
+    }
+
+    /// Index in `owed_blinds` of the blind `player` still has to settle before the first action
+    fn pending_obligation(&self, player: u64) -> Result<usize, InitStateError> {
+        if !self.action_list.is_empty() {
+            return Err(InitStateError {
+                msg: "Missed blinds can only be settled before the first action".to_owned(),
+            });
+        }
+        self.owed_blinds
+            .iter()
+            .position(|owed| owed.player == player && owed.settlement == BlindSettlement::Pending)
+            .ok_or_else(|| InitStateError {
+                msg: format!("Player {} owes no blind", player),
+            })
+    }
+
+    /// Throw away the third card `player` still holds, once the Pineapple discards are due
+    fn discard_third_card(&mut self, player: u64) {
+        let due = self
+            .discard_on
+            .is_some_and(|stage| stage as usize <= self.stage as usize);
+        if let Some(ps) = self.players_state.get_mut(player as usize) {
+            if due && ps.third_card.is_some() {
+                ps.discarded = ps.third_card.take();
+                self.update_range_indices();
+            }
+        }
+    }
+
+    /// Copies of the players with the hands the perspective cannot see hidden
+    fn visible_players(&self) -> Players {
+        self.players_state
+            .iter()
+            .map(|ps| PlayerState {
+                hand_hidden: !self.hand_visible(ps.player),
+                ..*ps
+            })
+            .collect()
+    }
+
+    /// Chips in the pot, counted from what every player has put in. Prefer it to the running
+    /// `pot` counter when displaying the pot.
+    pub(crate) fn chips_in_pot(&self) -> f64 {
+        self.players_state
+            .iter()
+            .map(|ps| ps.bet_chips + ps.pot_chips)
+            .sum()
+    }
+
+    /// The action `policy` picks for the current player
+    pub(crate) fn default_action(&self, policy: DefaultPolicy) -> Action {
+        let action = match policy {
+            DefaultPolicy::Fold => ActionEnum::Fold,
+            DefaultPolicy::CheckCall => ActionEnum::CheckCall,
+            DefaultPolicy::CheckElseFold => {
+                let max_bet = self
+                    .players_state
+                    .iter()
+                    .map(|ps| ps.bet_chips)
+                    .fold(0.0, f64::max);
+                match self.players_state.get(self.current_player as usize) {
+                    Some(ps) if ps.bet_chips < max_bet => ActionEnum::Fold,
+                    _ => ActionEnum::CheckCall,
+                }
+            }
+        };
+        Action::new(action, 0.0)
+    }
+
+    /// Apply `action`, measuring the work done when profiling
+    fn step(&self, action: Action) -> State {
+        let before = profiling::snapshot();
+        let mut state = self.transition(action);
+        state.perf = PerfProbe(profiling::snapshot().since(before));
+        // The bug replays from the state the action was applied to
+        if matches!(state.status, StateStatus::EngineError)
+            && matches!(self.status, StateStatus::Ok)
+        {
+            let reason = state
+                .engine_diagnostic
+                .as_deref()
+                .and_then(engine_error_reason)
+                .unwrap_or_default();
+            state.engine_diagnostic = Some(diagnostic_bundle(&reason, self, &[action]));
+        }
+        state
+    }
+
+    fn transition(&self, action: Action) -> State {
+        match self.status {
+            StateStatus::Ok => (),
+            _ => return self.clone(),
+        }
+
+        if self.final_state {
+            return self.clone();
+        }
+
+        if self.action_list.len() >= MAX_ACTIONS_PER_HAND {
+            let mut new_state = self.clone();
+            new_state.engine_error(&format!(
+                "Hand exceeded the maximum of {} actions",
+                MAX_ACTIONS_PER_HAND
+            ));
+            return new_state;
+        }
+
+        // If we're at showdown, no actions are allowed - handle showdown and finish
+        if self.stage == Stage::Showdown {
+            let mut new_state = self.clone();
+            new_state.handle_showdown();
+            return new_state;
+        }
+
+        let mut new_state = self.clone();
+        new_state.discard_third_card(new_state.current_player);
+
+        // Create FSM based on current state
+        let fsm_state: Box<dyn GameStateInterface> =
+            if new_state.stage == Stage::Showdown || new_state.final_state {
+                Box::new(GameOver)
+            } else {
+                // Determine current betting round context
+                let active_players = new_state
+                    .players_state
+                    .iter()
+                    .filter(|ps| ps.active)
+                    .count();
+
+                // Calculate the current maximum bet to determine amount_to_call
+                let max_bet = new_state
+                    .players_state
+                    .iter()
+                    .filter(|ps| ps.active)
+                    .map(|ps| ps.bet_chips)
+                    .fold(0.0f64, f64::max);
+
+                let context =
+                    BettingRoundContext::new(max_bet, active_players, new_state.current_player);
+                Box::new(AwaitingAction::new(new_state.current_player, context))
+            };
+
+        let mut fsm = StateMachine::new(fsm_state);
+
+        let mut new_state = match fsm.apply_action(&mut new_state, action) {
+            Ok(()) => {
+                // Check if we need to transition to next stage
+                if fsm.is_final() && !new_state.final_state {
+                    new_state.advance_to_next_stage_or_showdown();
+
+                    // After advancing to next stage, create a new FSM for the new stage
+                    if !new_state.final_state && new_state.stage != Stage::Showdown {
+                        let active_players = new_state
+                            .players_state
+                            .iter()
+                            .filter(|ps| ps.active)
+                            .count();
+
+                        let max_bet = new_state
+                            .players_state
+                            .iter()
+                            .filter(|ps| ps.active)
+                            .map(|ps| ps.bet_chips)
+                            .fold(0.0f64, f64::max);
+
+                        let context = BettingRoundContext::new(
+                            max_bet,
+                            active_players,
+                            new_state.current_player,
+                        );
+                        let new_fsm_state =
+                            Box::new(AwaitingAction::new(new_state.current_player, context));
+                        let new_fsm = StateMachine::new(new_fsm_state);
+                        new_state.legal_actions = new_fsm.get_legal_actions(&new_state);
+                    } else {
+                        new_state.legal_actions = LegalActions::new();
+                    }
+                } else {
+                    // Update legal actions with current FSM
+                    new_state.legal_actions = fsm.get_legal_actions(&new_state);
+                }
+                new_state
+            }
+            Err(status) => {
+                new_state.status = status;
+                new_state
+            }
+        };
+
+        // The running pot counter must match the chips the players have put in. A mismatch is
+        // an accounting bug, kept for `check_invariants` to report rather than covered up.
+        if let Some(contributed) = invariants::pot_drift(&new_state) {
+            record_decision!(
+                new_state,
+                TraceEvent::new(TraceKind::PotDrift, new_state.stage)
+                    .amount(contributed)
+                    .detail(&format!("The pot is {}", new_state.pot))
+            );
+        }
+        new_state
+    }
+
+    /// Advance to the next stage or handle showdown
+    fn advance_to_next_stage_or_showdown(&mut self) {
+        // Move all bet_chips to pot_chips
+        for player_state in &mut self.players_state {
+            player_state.pot_chips += player_state.bet_chips;
+            player_state.bet_chips = 0.0;
+            player_state.last_stage_action = None; // Reset for new stage
+        }
+        self.close_street(self.stage);
+
+        // Advance stage, the showdown is handled immediately
+        let previous = self.stage;
+        self.stage = match self.stage.next() {
+            Some(Stage::Showdown) | None => {
+                self.stage = Stage::Showdown;
+                self.handle_showdown();
+                return;
+            }
+            Some(stage) => stage,
+        };
+
+        // Deal community cards
+        let cards_to_deal = self.stage.board_card_count() - previous.board_card_count();
+
+        self.deal_board_cards(cards_to_deal, !self.second_board.is_empty());
+
+        record_decision!(
+            self,
+            TraceEvent::new(TraceKind::StreetDealt, self.stage).amount(cards_to_deal as f64)
+        );
+        // Without buckets `range_idx` keeps the index the hand was dealt with
+        if self.range_buckets.is_some() {
+            self.update_range_indices();
+        }
+
+        self.start_betting_round();
+    }
+
+    /// Start a post-flop betting round with the first active player left of the button,
+    /// or run the board out when fewer than two players can still bet
+    fn start_betting_round(&mut self) {
+        // Reset min_bet for new round
+        self.min_bet = 0.0;
+
+        // Check if we should go straight to showdown
+        let active_players: Vec<&PlayerState> =
+            self.players_state.iter().filter(|ps| ps.active).collect();
+
+        let players_with_chips = active_players.iter().filter(|ps| ps.stake > 0.0).count();
+
+        if active_players.len() <= 1 || players_with_chips <= 1 {
+            let kind = if active_players.len() <= 1 {
+                TraceKind::ShowdownForcedOnePlayerLeft
+            } else {
+                TraceKind::ShowdownForcedAllIn
+            };
+            record_decision!(self, TraceEvent::new(kind, self.stage));
             self.complete_to_showdown();
             return;
         }
 
+        // Find first player to act (left of button)
+        let first_player = (self.button + 1) % self.players_state.len() as u64;
+        self.current_player = first_player;
+        let mut attempts = 0;
+
+        while attempts < self.players_state.len() {
+            let player_state = &self.players_state[self.current_player as usize];
+            if player_state.active && player_state.stake > 0.0 {
+                break;
+            }
+
+            self.current_player = (self.current_player + 1) % self.players_state.len() as u64;
+            attempts += 1;
+        }
+
+        if attempts >= self.players_state.len() {
+            // Unreachable while at least two active players have chips (checked above)
+            self.engine_error(&format!(
+                "No player can act at the start of {:?} despite {} players with chips",
+                self.stage, players_with_chips
+            ));
+            return;
+        }
+
         // Create new FSM for the new round
         let active_players = self.players_state.iter().filter(|ps| ps.active).count();
 
@@ -834,66 +2015,94 @@ impl State {
         self.fsm_state = "AwaitingAction".to_string();
     }
 
+    /// Deal `n` cards to the board, then `n` more to the second board when playing two boards
+    fn deal_board_cards(&mut self, n: usize, double_board: bool) {
+        for _ in 0..n {
+            if !self.deck.is_empty() {
+                self.public_cards.push(self.deck.remove(0));
+            }
+        }
+        if double_board {
+            for _ in 0..n {
+                if !self.deck.is_empty() {
+                    self.second_board.push(self.deck.remove(0));
+                }
+            }
+        }
+    }
+
+    /// Abort the hand with `StateStatus::EngineError`, keeping a diagnostic bundle of the
+    /// state as it was, which `step` replaces with the state before the action
+    fn engine_error(&mut self, reason: &str) {
+        record_decision!(
+            self,
+            TraceEvent::new(TraceKind::EngineError, self.stage).detail(reason)
+        );
+
+        self.engine_diagnostic = Some(diagnostic_bundle(reason, self, &[]));
+        self.status = StateStatus::EngineError;
+        self.legal_actions = LegalActions::new();
+        self.final_state = true;
+    }
+
     /// Complete to showdown and handle final outcome
     fn complete_to_showdown(&mut self) {
-        verbose_println!(self, "DEBUG: Completing to showdown");
+        // Betting closed on the street of the last action, before the board it dealt
+        let closed_on = self
+            .action_list
+            .last()
+            .map_or(self.stage, |record| record.stage);
 
         // Deal remaining community cards if needed
+        let double_board = !self.second_board.is_empty();
         match self.stage {
-            Stage::Preflop => {
-                // Deal flop, turn, river
-                for _ in 0..5 {
-                    if !self.deck.is_empty() {
-                        self.public_cards.push(self.deck.remove(0));
-                    }
-                }
-            }
-            Stage::Flop => {
-                // Deal turn, river
-                for _ in 0..2 {
-                    if !self.deck.is_empty() {
-                        self.public_cards.push(self.deck.remove(0));
-                    }
-                }
-            }
-            Stage::Turn => {
-                // Deal river
-                if !self.deck.is_empty() {
-                    self.public_cards.push(self.deck.remove(0));
-                }
-            }
-            _ => {} // Already have all cards
+            Stage::Preflop => self.deal_board_cards(5, double_board), // Flop, turn, river
+            Stage::Flop => self.deal_board_cards(2, double_board),    // Turn, river
+            Stage::Turn => self.deal_board_cards(1, double_board),    // River
+            _ => {}                                                   // Already have all cards
         }
 
+        if self.rules.report_all_in_equities {
+            self.all_in_equities = insurance::all_in_equities(self, closed_on);
+        }
         self.stage = Stage::Showdown;
         self.handle_showdown();
     }
 
     /// Handle showdown logic
     fn handle_showdown(&mut self) {
-        verbose_println!(self, "DEBUG: Handling showdown");
-
+        for player in 0..self.players_state.len() as u64 {
+            self.discard_third_card(player);
+        }
         let active_players: Vec<PlayerState> = self
             .players_state
             .iter()
             .copied()
             .filter(|ps| ps.active)
             .collect();
+        if active_players.len() > 1 {
+            record_decision!(
+                self,
+                TraceEvent::new(TraceKind::Showdown, self.stage)
+                    .players(active_players.iter().map(|ps| ps.player).collect())
+            );
+        }
 
         if active_players.len() <= 1 {
             // Only one player left - they win everything
             if let Some(winner) = active_players.first() {
                 self.set_winners(vec![winner.player]);
             } else {
-                // No active players - should not happen, but handle gracefully
-                self.final_state = true;
+                self.engine_error("Showdown reached with no active players");
+                return;
             }
         } else {
             // Multiple players - evaluate hands
+            let evaluator = self.rules.variant.evaluator();
             let mut player_ranks: Vec<(u64, (u64, u64, u64))> = active_players
                 .iter()
                 .map(|ps| {
-                    let rank = rank_hand(self, ps.hand, &self.public_cards);
+                    let rank = evaluator.rank_hand(ps.hand, &self.public_cards);
                     (ps.player, rank)
                 })
                 .collect();
@@ -916,18 +2125,38 @@ impl State {
         self.final_state = true;
     }
 
+    /// Count the chips each player has in the pot beyond the earlier streets as put in on
+    /// `stage`, the river for the showdown
+    fn close_street(&mut self, stage: Stage) {
+        let street = (stage as usize).min(Stage::River as usize);
+        for ps in &mut self.players_state {
+            let earlier: f64 = ps.invested[..street].iter().sum();
+            ps.invested[street] = ps.pot_chips + ps.bet_chips - earlier;
+        }
+    }
+
     /// Set winners and calculate rewards
     fn set_winners(&mut self, winners: Vec<u64>) {
-        verbose_println!(self, "DEBUG: Setting winners: {:?}", winners);
+        record_decision!(
+            self,
+            TraceEvent::new(TraceKind::Winners, self.stage).players(winners.clone())
+        );
 
         // Move all bet_chips to pot_chips for final calculation
         for p in &mut self.players_state {
             p.pot_chips += p.bet_chips;
             p.bet_chips = 0.0;
         }
+        // The runout of an all-in skips the streets after the last action
+        let last_street = self
+            .action_list
+            .last()
+            .map_or(self.stage, |record| record.stage);
+        self.close_street(last_street);
 
         // Calculate and distribute rewards using side pot logic
         resolve_pots(self, &winners);
+        pay_seven_deuce_bounty(self);
 
         // Set all players to inactive and mark game as final
         for p in &mut self.players_state {
@@ -940,75 +2169,182 @@ impl State {
     }
 }
 
-/// Resolve pots and distribute winnings
-pub fn resolve_pots(state: &mut State, _winners: &[u64]) {
-    // Initialize rewards to zero
-    for p in &mut state.players_state {
-        p.reward = 0.0;
+/// Players dealt into the next hand, and those of them posting a dead big blind: indices in
+/// `owing`, which flags the players at the table who missed the big blind, in seat order with
+/// the button at `button`. A player who owes the blind plays for free in the big blind;
+/// otherwise they post under `MissedBlinds::PostDead`, or sit out under
+/// `MissedBlinds::WaitForBigBlind` unless the hand could not start without them.
+#[pyfunction]
+pub fn missed_blind_seating(
+    owing: Vec<bool>,
+    button: usize,
+    policy: MissedBlinds,
+) -> (Vec<usize>, Vec<usize>) {
+    let everybody: Vec<usize> = (0..owing.len()).collect();
+    if owing.is_empty() {
+        return (everybody, Vec::new());
     }
-
-    let showdown_players: Vec<_> = state
-        .players_state
+    let (_, big_blind) = State::blind_players(owing.len() as u64, button as u64);
+    let dead: Vec<usize> = everybody
         .iter()
-        .filter(|p| p.pot_chips > 0.0)
-        .cloned()
+        .copied()
+        .filter(|&player| owing[player] && player != big_blind as usize)
         .collect();
-
-    if showdown_players.is_empty() {
-        return;
+    match policy {
+        MissedBlinds::PostDead => (everybody, dead),
+        MissedBlinds::WaitForBigBlind => {
+            let dealt: Vec<usize> = everybody
+                .iter()
+                .copied()
+                .filter(|player| !dead.contains(player))
+                .collect();
+            if dealt.len() >= 2 {
+                (dealt, Vec::new())
+            } else {
+                (everybody, Vec::new())
+            }
+        }
     }
+}
 
-    let mut pot_levels: Vec<f64> = showdown_players.iter().map(|p| p.pot_chips).collect();
-    pot_levels.sort_by(|a, b| a.partial_cmp(b).unwrap());
-    pot_levels.dedup();
-
-    let mut last_level = 0.0;
-
-    for &level in &pot_levels {
-        let pot_slice = level - last_level;
-        if pot_slice <= 1e-9 {
-            continue;
-        }
+/// A slice of the pot between two levels of what the players put in, see `pot_slices`
+#[derive(Debug, Clone)]
+pub(crate) struct PotSlice {
+    pub amount: f64,
+    /// Players who put an equal share of `amount` in
+    pub contributors: Vec<u64>,
+    pub eligible: Vec<u64>,
+}
 
-        let contributors: Vec<u64> = state
+/// The chips swept into the pot sliced at the levels the hand is settled on, the antes first,
+/// then the bets. The bets are sliced at the levels the players matched, and a slice can be won
+/// by the players `in_hand` who put that much in. Antes are dead money, sliced apart: every
+/// player in the hand can win them, but a player all-in for part of the ante only wins that
+/// much of each ante.
+pub(crate) fn pot_slices(state: &State, in_hand: impl Fn(&PlayerState) -> bool) -> Vec<PotSlice> {
+    fn slice_levels(
+        state: &State,
+        chips: impl Fn(&PlayerState) -> f64,
+        can_win: impl Fn(&PlayerState, f64) -> bool,
+        slices: &mut Vec<PotSlice>,
+    ) {
+        let mut levels: Vec<f64> = state
             .players_state
             .iter()
-            .filter(|p| p.pot_chips >= level)
-            .map(|p| p.player)
+            .map(&chips)
+            .filter(|&chips| chips > 1e-9)
             .collect();
+        levels.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        levels.dedup();
+
+        let mut last_level = 0.0;
+        for level in levels {
+            let slice = level - last_level;
+            if slice <= 1e-9 {
+                continue;
+            }
+            let contributors: Vec<u64> = state
+                .players_state
+                .iter()
+                .filter(|ps| chips(ps) >= level)
+                .map(|ps| ps.player)
+                .collect();
+            slices.push(PotSlice {
+                amount: slice * contributors.len() as f64,
+                contributors,
+                eligible: state
+                    .players_state
+                    .iter()
+                    .filter(|ps| can_win(ps, level))
+                    .map(|ps| ps.player)
+                    .collect(),
+            });
+            last_level = level;
+        }
+    }
 
-        let total_pot_for_slice = pot_slice * contributors.len() as f64;
+    let bets = |ps: &PlayerState| ps.pot_chips - ps.ante_chips;
+    let mut slices = Vec::new();
+    slice_levels(
+        state,
+        |ps| ps.ante_chips,
+        |ps, level| {
+            let all_in_for_the_ante = bets(ps) <= 1e-9 && ps.stake <= 0.0;
+            in_hand(ps) && (ps.ante_chips >= level || !all_in_for_the_ante)
+        },
+        &mut slices,
+    );
+    slice_levels(
+        state,
+        bets,
+        |ps, level| in_hand(ps) && bets(ps) >= level,
+        &mut slices,
+    );
+    slices
+}
 
-        let eligible_players: Vec<u64> = state
-            .players_state
-            .iter()
-            .filter(|p| p.active && p.pot_chips >= level)
-            .map(|p| p.player)
-            .collect();
+/// Who wins each of the `pot_slices`, with how much of it: a slice nobody `in_hand` can win
+/// goes back to whoever put it in, and with two boards each board wins half of every slice
+pub(crate) fn slice_awards(
+    state: &State,
+    in_hand: impl Fn(&PlayerState) -> bool,
+) -> Vec<(PotSlice, Vec<(u64, f64)>)> {
+    let evaluator = state.rules.variant.evaluator();
+    let boards: Vec<&[Card]> = if state.second_board.is_empty() {
+        vec![&state.public_cards]
+    } else {
+        vec![&state.public_cards, &state.second_board]
+    };
 
-        let mut best_rank = (11, 0, 0);
-        let mut pot_winners: Vec<u64> = Vec::new();
+    pot_slices(state, in_hand)
+        .into_iter()
+        .map(|slice| {
+            // Nobody still in can win the slice, it goes back to whoever put it in
+            if slice.eligible.is_empty() {
+                let refund = slice.amount / slice.contributors.len() as f64;
+                let awards = slice.contributors.iter().map(|&p| (p, refund)).collect();
+                return (slice, awards);
+            }
 
-        for &player_id in &eligible_players {
-            let player_state = &state.players_state[player_id as usize];
-            let rank = rank_hand(state, player_state.hand, &state.public_cards);
+            let pot_per_board = slice.amount / boards.len() as f64;
+            let mut awards = Vec::new();
+            for board in &boards {
+                let mut best_rank = (11, 0, 0);
+                let mut pot_winners: Vec<u64> = Vec::new();
+
+                for &player_id in &slice.eligible {
+                    let player_state = &state.players_state[player_id as usize];
+                    let rank = evaluator.rank_hand(player_state.hand, board);
+
+                    if rank < best_rank {
+                        best_rank = rank;
+                        pot_winners = vec![player_id];
+                    } else if rank == best_rank {
+                        pot_winners.push(player_id);
+                    }
+                }
 
-            if rank < best_rank {
-                best_rank = rank;
-                pot_winners = vec![player_id];
-            } else if rank == best_rank {
-                pot_winners.push(player_id);
+                if !pot_winners.is_empty() {
+                    let reward_per_winner = pot_per_board / pot_winners.len() as f64;
+                    awards.extend(pot_winners.into_iter().map(|p| (p, reward_per_winner)));
+                }
             }
-        }
+            (slice, awards)
+        })
+        .collect()
+}
 
-        if !pot_winners.is_empty() {
-            let reward_per_winner = total_pot_for_slice / pot_winners.len() as f64;
-            for &winner_id in &pot_winners {
-                state.players_state[winner_id as usize].reward += reward_per_winner;
-            }
-        }
+/// Resolve pots and distribute winnings
+pub fn resolve_pots(state: &mut State, _winners: &[u64]) {
+    // Initialize rewards to zero
+    for p in &mut state.players_state {
+        p.reward = 0.0;
+    }
 
-        last_level = level;
+    for (_, awards) in slice_awards(state, |ps| ps.active) {
+        for (player, amount) in awards {
+            state.players_state[player as usize].reward += amount;
+        }
     }
 
     // Finalize rewards by subtracting initial investment
@@ -1017,34 +2353,181 @@ pub fn resolve_pots(state: &mut State, _winners: &[u64]) {
     }
 }
 
+/// Seven-deuce game: whoever wins a pot holding seven-deuce collects the bounty from
+/// every other player, limited to the chips each of them has left after the hand
+fn pay_seven_deuce_bounty(state: &mut State) {
+    let bounty = state.rules.seven_deuce_bounty;
+    if bounty <= 0.0 {
+        return;
+    }
+
+    let collectors: Vec<usize> = state
+        .players_state
+        .iter()
+        .enumerate()
+        .filter(|(_, ps)| {
+            let ranks = [ps.hand.0.rank, ps.hand.1.rank];
+            ps.reward > 0.0 && ranks.contains(&CardRank::R7) && ranks.contains(&CardRank::R2)
+        })
+        .map(|(i, _)| i)
+        .collect();
+
+    for &collector in &collectors {
+        for payer in 0..state.players_state.len() {
+            if collectors.contains(&payer) {
+                continue;
+            }
+            let ps = &state.players_state[payer];
+            let chips_left = ps.stake + ps.pot_chips + ps.reward;
+            let paid = bounty.min(chips_left.max(0.0));
+            state.players_state[payer].reward -= paid;
+            state.players_state[collector].reward += paid;
+        }
+    }
+}
+
+/// Whether the current street already has the maximum number of bets and raises
+pub(crate) fn raise_cap_reached(state: &State) -> bool {
+    match state.rules.max_raises_per_street {
+        Some(cap) => {
+            let raises = state
+                .action_list
+                .iter()
+                .filter(|a| a.stage == state.stage && a.action.action == ActionEnum::BetRaise)
+                .count();
+            raises >= cap as usize
+        }
+        None => false,
+    }
+}
+
+/// See `State::legal_bet_range`
+pub(crate) fn bet_range(state: &State) -> Option<(f64, f64)> {
+    if !state.legal_actions.contains(&ActionEnum::BetRaise) {
+        return None;
+    }
+
+    let current = state.current_player as usize;
+    let ps = &state.players_state[current];
+    let all_in = ps.bet_chips + ps.stake;
+    // Chips nobody can call would only come back to the raiser
+    let covered = state
+        .players_state
+        .iter()
+        .enumerate()
+        .filter(|&(player, other)| player != current && other.active)
+        .map(|(_, other)| other.bet_chips + other.stake)
+        .fold(0.0, f64::max);
+    let max = all_in.min(covered);
+    if max <= state.min_bet {
+        return None;
+    }
+
+    // The engine turns any raise of a short stack into an all-in
+    let short = ps.stake < state.min_bet || ps.stake < 1.0;
+    let min = if short {
+        all_in
+    } else {
+        (state.min_bet * state.rules.min_raise_ratio.max(1.0))
+            .max(state.min_bet + state.bb)
+            .min(all_in)
+    };
+    Some((min, max.max(min)))
+}
+
+/// Total bet of every bet bucket of `State::legal_action_mask`, `None` where illegal
+fn bet_buckets(state: &State, n: usize) -> Vec<Option<f64>> {
+    if !state.legal_actions.contains(&ActionEnum::BetRaise) {
+        return vec![None; n];
+    }
+
+    let ps = &state.players_state[state.current_player as usize];
+    let all_in = ps.bet_chips + ps.stake;
+    let to_call = (state.min_bet - ps.bet_chips).max(0.0);
+    let min_raise_to = state.min_bet * state.rules.min_raise_ratio.max(1.0);
+    // The engine turns any raise of a short stack into an all-in
+    let short = ps.stake < state.min_bet || ps.stake < 1.0;
+
+    (0..n)
+        .map(|k| {
+            if k + 1 == n {
+                return (all_in > state.min_bet).then_some(all_in);
+            }
+            let total = state.min_bet + 2.0 * (k + 1) as f64 / n as f64 * (state.pot + to_call);
+            let legal = !short && total >= min_raise_to && total > state.min_bet && total < all_in;
+            legal.then_some(total)
+        })
+        .collect()
+}
+
+/// See `State::current_leader`
+fn hand_ranking(state: &State) -> Vec<Vec<u64>> {
+    let board = &state.public_cards;
+    let mut ranks: Vec<(u64, (u64, u64, u64))> = state
+        .players_state
+        .iter()
+        .filter(|ps| {
+            !state
+                .action_list
+                .iter()
+                .any(|a| a.player == ps.player && a.action.action == ActionEnum::Fold)
+        })
+        .map(|ps| {
+            let rank = if board.len() >= 3 {
+                state.rules.variant.evaluator().rank_hand(ps.hand, board)
+            } else {
+                // Pairs first, then the high card and the kicker (lower is better)
+                let high = ps.hand.0.rank.max(ps.hand.1.rank) as u64;
+                let low = ps.hand.0.rank.min(ps.hand.1.rank) as u64;
+                let category = if high == low { 9 } else { 10 };
+                (category, 12 - high, 12 - low)
+            };
+            (ps.player, rank)
+        })
+        .collect();
+    ranks.sort_by_key(|&(player, rank)| (rank, player));
+
+    ranks
+        .chunk_by(|a, b| a.1 == b.1)
+        .map(|group| group.iter().map(|(player, _)| *player).collect())
+        .collect()
+}
+
 /// Generate legal actions for the current state - fallback function
+// Only called from Python
+#[allow(dead_code)]
 #[pyfunction]
 pub fn legal_actions(state: &State) -> Vec<ActionEnum> {
+    available_actions(state).into_vec()
+}
+
+/// `legal_actions` without leaving the stack
+pub(crate) fn available_actions(state: &State) -> LegalActions {
     if state.final_state || state.stage == Stage::Showdown {
-        return vec![];
+        return LegalActions::new();
     }
 
     let current_player_state = &state.players_state[state.current_player as usize];
 
     // If player is all-in, they cannot act
     if current_player_state.stake == 0.0 {
-        return vec![];
+        return LegalActions::new();
     }
 
-    let mut legal_actions = vec![ActionEnum::Fold];
+    let mut legal_actions: LegalActions = smallvec![ActionEnum::Fold];
 
     // Always allow CheckCall
     legal_actions.push(ActionEnum::CheckCall);
 
-    // Allow BetRaise if player has chips to bet
-    if current_player_state.stake > 0.0 {
+    // Allow BetRaise if player has chips to bet and the street is not capped
+    if current_player_state.stake > 0.0 && !raise_cap_reached(state) {
         legal_actions.push(ActionEnum::BetRaise);
     }
 
     legal_actions
 }
 
-fn high_card_value(ranks: &Vec<CardRank>) -> u64 {
+pub(crate) fn high_card_value(ranks: &[CardRank]) -> u64 {
     let mut value: u64 = 0;
     for (i, &r) in ranks.iter().sorted().enumerate() {
         value += (13_u64.pow(i as u32)) * (12 - r as u64);
@@ -1052,32 +2535,14 @@ fn high_card_value(ranks: &Vec<CardRank>) -> u64 {
     value
 }
 
-fn rank_hand(
-    _state: &State,
-    private_cards: (Card, Card),
-    public_cards: &Vec<Card>,
-) -> (u64, u64, u64) {
-    let mut cards = public_cards.clone();
-    cards.append(&mut vec![private_cards.0, private_cards.1]);
-
-    // Check if we have enough cards for a valid combination
-    if cards.len() < 5 {
-        // Return worst possible rank if not enough cards
-        return (10, 0, 0);
-    }
-
-    let min_rank = cards
-        .iter()
-        .copied()
-        .combinations(5)
-        .map(|comb| rank_card_combination(comb))
-        .min()
-        .unwrap_or((10, 0, 0));
-
-    min_rank
+/// Rank the best five-card hand from the private and public cards.
+/// Lower tuples are stronger hands: `(1, 0, 0)` is a royal flush and `(10, _, _)` high card.
+/// Hold'em only: the engine ranks hands with the evaluator of `TableRules::variant`.
+pub fn rank_hand(private_cards: (Card, Card), public_cards: &[Card]) -> HandRank {
+    Holdem.rank_hand(private_cards, public_cards)
 }
 
-fn rank_card_combination(cards: Vec<Card>) -> (u64, u64, u64) {
+pub(crate) fn rank_card_combination(cards: Vec<Card>) -> HandRank {
     let mut ordered_cards = cards.clone();
     ordered_cards.sort_by_key(|c| c.rank);
     let suits: Vec<CardSuit> = ordered_cards.iter().map(|c| c.suit).collect();
@@ -1173,11 +2638,925 @@ mod tests {
     #[cfg(test)]
     use proptest::prelude::*;
 
+    #[cfg(test)]
+    #[test]
+    fn raises_follow_table_rules() {
+        let rules = TableRules {
+            min_raise_ratio: 2.0,
+            max_raises_per_street: Some(2),
+            ..TableRules::default()
+        };
+        let state = State::from_seed(2, 0, 0.5, 1.0, 100.0, 1234, false, 0.0)
+            .unwrap()
+            .with_rules(rules)
+            .unwrap();
+
+        // Raises below twice the current bet are bumped up to it
+        let state = state.apply_action(Action::new(ActionEnum::BetRaise, 1.5));
+        assert_eq!(state.min_bet, 2.0);
+        let state = state.apply_action(Action::new(ActionEnum::BetRaise, 3.0));
+        assert_eq!(state.min_bet, 4.0);
+
+        // The street is capped after two raises
+        assert_eq!(
+            state.legal_actions[..],
+            [ActionEnum::Fold, ActionEnum::CheckCall]
+        );
+        assert!(state.with_rules(TableRules::default()).is_err());
+    }
+
+    #[cfg(test)]
+    #[test]
+    fn seven_deuce_bounty_is_paid_to_the_winner() {
+        let seven_deuce = [
+            Card::from_string("C7".to_string()).unwrap(),
+            Card::from_string("D2".to_string()).unwrap(),
+        ];
+        let mut deck: Vec<Card> = Card::collect()
+            .into_iter()
+            .filter(|c| !seven_deuce.contains(c))
+            .collect();
+        // Player 1 is dealt first
+        deck.splice(0..0, seven_deuce);
+
+        let rules = TableRules {
+            seven_deuce_bounty: 5.0,
+            ..TableRules::default()
+        };
+        let state = State::from_deck(2, 0, 0.5, 1.0, 100.0, deck, false, 0, 0.0)
+            .unwrap()
+            .with_rules(rules)
+            .unwrap();
+        let state = state.apply_action(Action::new(ActionEnum::BetRaise, 3.0));
+        let state = state.apply_action(Action::new(ActionEnum::Fold, 0.0));
+
+        assert!(state.final_state);
+        assert_eq!(state.players_state[1].reward, 6.0);
+        assert_eq!(state.players_state[0].reward, -6.0);
+    }
+
+    #[cfg(test)]
+    #[test]
+    fn double_board_bomb_pot_splits_between_boards() {
+        // Player 1 is dealt first, then the flop of each board, then turn and river in pairs
+        let cards = [
+            "SA", "HA", "SK", "HK", "C2", "D7", "C9", "DK", "C5", "D8", "S3", "H6", "H4", "CJ",
+        ];
+        let deck: Vec<Card> = cards
+            .iter()
+            .map(|c| Card::from_string(c.to_string()).unwrap())
+            .collect();
+
+        let state = State::bomb_pot(2, 0, 5.0, 1.0, 100.0, deck.clone(), true, false, 0).unwrap();
+        assert_eq!(state.stage, Stage::Flop);
+        assert_eq!(state.pot, 10.0);
+        assert_eq!(state.public_cards.len(), 3);
+        assert_eq!(state.second_board.len(), 3);
+        assert_eq!(state.current_player, 1);
+
+        let mut state = state;
+        while !state.final_state {
+            state = state.apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+        }
+        assert!(state.check_invariants().is_empty());
+        // Aces win the first board, trip kings the second one
+        assert_eq!(state.players_state[0].reward, 0.0);
+        assert_eq!(state.players_state[1].reward, 0.0);
+    }
+
+    #[cfg(test)]
+    #[test]
+    fn verbose_states_trace_the_engine_decisions() {
+        let state = State::from_seed(3, 0, 1.0, 2.0, 100.0, 7, true, 0.0).unwrap();
+        let state = state.apply_action(Action::new(ActionEnum::Fold, 0.0));
+        let state = state.apply_action(Action::new(ActionEnum::BetRaise, 100.0));
+        let state = state.apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+        assert!(state.final_state);
+
+        let kinds: Vec<TraceKind> = state.trace.iter().map(|event| event.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                TraceKind::Action,
+                TraceKind::Action,
+                TraceKind::Action,
+                TraceKind::RoundOverBetsMatched,
+                TraceKind::StreetDealt,
+                TraceKind::ShowdownForcedAllIn,
+                TraceKind::Showdown,
+                TraceKind::Winners,
+            ]
+        );
+        assert_eq!(state.trace[0].players, vec![0]);
+        assert_eq!(state.trace[0].action, Some(ActionEnum::Fold));
+        assert_eq!(state.trace[4].amount, Some(3.0));
+        assert_eq!(state.trace[6].players, vec![1, 2]);
+
+        // Nothing is collected otherwise
+        let quiet = State::from_seed(3, 0, 1.0, 2.0, 100.0, 7, false, 0.0).unwrap();
+        let quiet = quiet.apply_action(Action::new(ActionEnum::Fold, 0.0));
+        assert!(quiet.trace.is_empty());
+    }
+
+    #[cfg(test)]
+    #[test]
+    fn current_leader_follows_the_board() {
+        // Player 1 holds aces, player 2 kings and player 0 seven-deuce; the flop pairs the kings
+        let cards = [
+            "SA", "HA", "DK", "HK", "D2", "C7", "CK", "S9", "H4", "D5", "S6",
+        ];
+        let deck: Vec<Card> = cards
+            .iter()
+            .map(|c| Card::from_string(c.to_string()).unwrap())
+            .collect();
+        let state = State::from_deck(3, 0, 0.5, 1.0, 100.0, deck, false, 0, 0.0).unwrap();
+        assert_eq!(
+            state.unsafe_full_view().current_leader().unwrap(),
+            vec![vec![1], vec![2], vec![0]]
+        );
+
+        let state = state.apply_action(Action::new(ActionEnum::Fold, 0.0));
+        let state = state.apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+        let state = state.apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+        assert_eq!(state.stage, Stage::Flop);
+        assert_eq!(
+            state.unsafe_full_view().current_leader().unwrap(),
+            vec![vec![2], vec![1]]
+        );
+
+        // Same hole cards in different suits tie
+        let cards = ["SA", "SK", "HA", "HK", "C2", "C3", "C4"];
+        let deck: Vec<Card> = cards
+            .iter()
+            .map(|c| Card::from_string(c.to_string()).unwrap())
+            .collect();
+        let state = State::from_deck(2, 0, 0.5, 1.0, 100.0, deck, false, 0, 0.0).unwrap();
+        assert_eq!(
+            state.unsafe_full_view().current_leader().unwrap(),
+            vec![vec![0, 1]]
+        );
+    }
+
+    #[cfg(test)]
+    #[test]
+    fn range_buckets_follow_the_streets() {
+        // Player 1 holds aces, player 2 kings and player 0 seven-deuce; the flop gives the
+        // kings a set
+        let cards = [
+            "SA", "HA", "DK", "HK", "D2", "C7", "CK", "S9", "H4", "D5", "S6",
+        ];
+        let deck: Vec<Card> = cards
+            .iter()
+            .map(|c| Card::from_string(c.to_string()).unwrap())
+            .collect();
+        let state = State::from_deck(3, 0, 0.5, 1.0, 100.0, deck, false, 0, 0.0).unwrap();
+        let bucketed = state.with_range_buckets(100).unwrap();
+        assert!(state.with_range_buckets(0).is_err());
+        // Before the flop both use the hand class ranks
+        for (a, b) in state.players_state.iter().zip(&bucketed.players_state) {
+            assert_eq!(a.range_idx, b.range_idx);
+        }
+
+        let call = Action::new(ActionEnum::CheckCall, 0.0);
+        let flop = state
+            .apply_action(call)
+            .apply_action(call)
+            .apply_action(call);
+        assert_eq!(flop.stage, Stage::Flop);
+        for (a, b) in state.players_state.iter().zip(&flop.players_state) {
+            assert_eq!(a.range_idx, b.range_idx);
+        }
+
+        let flop = bucketed
+            .apply_action(call)
+            .apply_action(call)
+            .apply_action(call);
+        let buckets: Vec<i64> = flop.players_state.iter().map(|ps| ps.range_idx).collect();
+        assert_eq!(buckets[2], 0);
+        assert!(buckets[1] > buckets[2] && buckets[1] < 10);
+        assert!(buckets[0] > 50);
+        assert_eq!(flop.calculate_range_idx(1), buckets[1]);
+    }
+
+    #[cfg(test)]
+    #[test]
+    fn legal_action_mask_follows_the_bet_range() {
+        let rules = TableRules {
+            max_raises_per_street: Some(1),
+            ..TableRules::default()
+        };
+        let state = State::from_seed(2, 0, 0.5, 1.0, 100.0, 1234, false, 0.0)
+            .unwrap()
+            .with_rules(rules)
+            .unwrap();
+
+        // Half pot, pot and 1.5 pot raises after calling 0.5 into 1.5, then all-in
+        assert_eq!(state.legal_action_mask(4), vec![true; 6]);
+        let raise = state.mask_action(3, 4).unwrap();
+        assert_eq!(raise.amount, 3.0);
+        assert_eq!(state.mask_action(5, 4).unwrap().amount, 100.0);
+        assert!(state.mask_action(6, 4).is_none());
+
+        // The street is capped after one raise
+        let state = state.apply_action(raise);
+        assert_eq!(
+            state.legal_action_mask(4),
+            vec![true, true, false, false, false, false]
+        );
+        assert!(state.mask_action(2, 4).is_none());
+
+        let state = state.apply_action(Action::new(ActionEnum::Fold, 0.0));
+        assert_eq!(state.legal_action_mask(4), vec![false; 6]);
+    }
+
+    #[cfg(test)]
+    #[test]
+    fn bet_range_stops_at_the_covered_stack() {
+        // Heads-up with button 0, the small blind acts first against the button's big blind
+        let mut state = State::from_seed(2, 0, 0.5, 1.0, 100.0, 1234, false, 0.0).unwrap();
+        assert_eq!(state.legal_bet_range(), Some((2.0, 100.0)));
+
+        state.players_state[0].stake = 19.0;
+        assert_eq!(state.legal_bet_range(), Some((2.0, 20.0)));
+
+        // Nothing left to raise against an all-in big blind
+        state.players_state[0].stake = 0.0;
+        assert_eq!(state.legal_bet_range(), None);
+
+        // A short stack can only go all-in
+        let mut state = State::from_seed(2, 0, 0.5, 1.0, 100.0, 1234, false, 0.0).unwrap();
+        state.players_state[1].stake = 0.75;
+        assert_eq!(state.legal_bet_range(), Some((1.25, 1.25)));
+    }
+
+    #[cfg(test)]
+    #[test]
+    fn hands_keep_their_ids() {
+        let state = State::from_seed(2, 0, 0.5, 1.0, 100.0, 1234, false, 0.0).unwrap();
+        let other = State::from_seed(2, 0, 0.5, 1.0, 100.0, 1234, false, 0.0).unwrap();
+        assert_eq!(state.hand_id, other.hand_id);
+        let other = State::from_seed(2, 0, 0.5, 1.0, 100.0, 1235, false, 0.0).unwrap();
+        assert_ne!(state.hand_id, other.hand_id);
+        let deck = seeds::shuffled_deck(1234);
+        let other = State::from_deck(2, 0, 0.5, 1.0, 100.0, deck, false, 1234, 0.0).unwrap();
+        assert_ne!(state.hand_id, other.hand_id);
+        let id: Vec<char> = state.hand_id.chars().collect();
+        assert_eq!(id.len(), 36);
+        assert_eq!(
+            (id[8], id[13], id[14], id[18], id[23]),
+            ('-', '-', '4', '-', '-')
+        );
+        assert!("89ab".contains(id[19]));
+
+        let state = state.with_ids(None, Some("table 1".to_string()), None);
+        let next = state.apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+        assert_eq!(next.hand_id, state.hand_id);
+        assert_eq!(next.table_id.as_deref(), Some("table 1"));
+        assert_eq!(next.session_id, None);
+
+        let next = next.with_ids(Some("hand 7".to_string()), None, Some("run".to_string()));
+        assert_eq!(next.hand_id, "hand 7");
+        assert_eq!(next.table_id.as_deref(), Some("table 1"));
+        assert_eq!(next.view().session_id().as_deref(), Some("run"));
+    }
+
+    #[cfg(test)]
+    #[test]
+    fn dead_cards_are_the_board_and_the_own_hand() {
+        let state = State::from_seed(2, 0, 0.5, 1.0, 100.0, 1234, false, 0.0).unwrap();
+        assert_eq!(state.cards_remaining(), 48);
+        let hand = state.players_state[1].hand;
+        let seen = state.with_perspective(Some(1));
+        assert_eq!(seen.known_dead_cards(1), Some(vec![hand.0, hand.1]));
+        assert_eq!(state.unsafe_full_view().known_dead_cards(2), None);
+
+        let state = state
+            .apply_action(Action::new(ActionEnum::CheckCall, 0.0))
+            .apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+        assert_eq!(state.stage, Stage::Flop);
+        assert_eq!(state.cards_remaining(), state.deck.len());
+        let dead = state.with_perspective(Some(0)).known_dead_cards(0).unwrap();
+        assert_eq!(dead[..3], state.public_cards[..]);
+        assert_eq!(
+            dead[3..],
+            [state.players_state[0].hand.0, state.players_state[0].hand.1]
+        );
+    }
+
+    #[cfg(test)]
+    #[test]
+    fn accessors_keep_to_the_perspective() {
+        let state = State::from_seed(2, 0, 0.5, 1.0, 100.0, 1234, false, 0.0).unwrap();
+        let seen = state.with_perspective(Some(0));
+        assert!(seen.known_dead_cards(0).is_some());
+        assert_eq!(seen.known_dead_cards(1), None);
+        assert_eq!(state.known_dead_cards(0), None);
+        assert!(seen.hand_strength(0, 100).is_some());
+        assert!(seen.hand_strength(1, 100).is_none());
+        assert!(state.hand_strength(0, 100).is_none());
+        assert!(seen.current_leader().is_err());
+
+        let full = state.unsafe_full_view();
+        assert!(full.known_dead_cards(1).is_some());
+        assert!(full.hand_strength(1, 100).is_some());
+        assert_eq!(full.current_leader().unwrap().concat().len(), 2);
+
+        // An aborted hand keeps its report, with every hand, for the full view
+        let mut aborted = seen.clone();
+        let record = ActionRecord {
+            player: 0,
+            stage: Stage::Preflop,
+            action: Action::new(ActionEnum::CheckCall, 0.0),
+            legal_actions: vec![],
+            timestamp: None,
+            think_time: None,
+            forced: false,
+            requested: Action::new(ActionEnum::CheckCall, 0.0),
+            coercion: None,
+            pot_before: 1.5,
+            to_call: 0.5,
+            chips: 0.5,
+        };
+        aborted.action_list = vec![record; MAX_ACTIONS_PER_HAND];
+        let aborted = aborted.apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+        assert!(aborted.engine_diagnostic.is_some());
+        assert!(aborted.py_engine_diagnostic().is_err());
+        assert!(aborted
+            .unsafe_full_view()
+            .py_engine_diagnostic()
+            .unwrap()
+            .is_some());
+    }
+
+    #[cfg(test)]
+    #[test]
+    fn getters_hide_what_the_perspective_cannot_see() {
+        let hidden = |state: &State| -> Vec<bool> {
+            state
+                .py_players_state()
+                .iter()
+                .map(|ps| ps.py_hand().is_none())
+                .collect()
+        };
+
+        // Heads-up, player 1 acts first
+        let state = State::from_seed(2, 0, 0.5, 1.0, 100.0, 1234, false, 0.0).unwrap();
+        assert!(state.py_deck().is_err());
+        assert_eq!(hidden(&state), vec![true, true]);
+        assert!(!format!("{:?}", state.py_players_state()[0]).contains("Card"));
+        assert_eq!(hidden(&state.with_perspective(Some(0))), vec![false, true]);
+        // The perspective stays with player 0 once player 1 is to act
+        let mut next = state.with_perspective(Some(0));
+        while next.current_player != 1 || next.action_list.is_empty() {
+            next = next.apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+        }
+        assert_eq!(hidden(&next), vec![false, true]);
+
+        let full = state.unsafe_full_view();
+        assert_eq!(full.py_deck().unwrap(), state.deck);
+        assert_eq!(hidden(&full), vec![false, false]);
+        assert!(
+            full.apply_action(Action::new(ActionEnum::CheckCall, 0.0))
+                .full_view
+        );
+
+        // Checked down to the showdown: both hands are shown
+        let mut state = state.with_perspective(Some(1));
+        while !state.final_state {
+            state = state.apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+        }
+        assert_eq!(state.perspective, Some(1));
+        assert_eq!(hidden(&state), vec![false, false]);
+    }
+
+    #[cfg(test)]
+    #[test]
+    fn coerced_actions_are_recorded() {
+        let rules = TableRules {
+            min_raise_ratio: 2.0,
+            max_raises_per_street: Some(2),
+            ..TableRules::default()
+        };
+        let state = State::from_seed(2, 0, 0.5, 1.0, 100.0, 1234, false, 0.0)
+            .unwrap()
+            .with_rules(rules)
+            .unwrap();
+
+        let small = Action::new(ActionEnum::BetRaise, 1.5);
+        let state = state.apply_action(small);
+        let record = state.action_list.last().unwrap();
+        assert_eq!(record.coercion, Some(Coercion::MinRaise));
+        assert_eq!(record.requested, small);
+        assert_eq!(record.action.amount, 2.0);
+
+        let state = state.apply_action(Action::new(ActionEnum::BetRaise, 4.0));
+        assert_eq!(state.action_list.last().unwrap().coercion, None);
+
+        // The street is capped after two raises
+        let state = state.apply_action(Action::new(ActionEnum::BetRaise, 8.0));
+        let record = state.action_list.last().unwrap();
+        assert_eq!(record.coercion, Some(Coercion::IllegalAction));
+        assert_eq!(record.action.action, ActionEnum::CheckCall);
+
+        let shove = Action::new(ActionEnum::BetRaise, 500.0);
+        let state = state.apply_action(shove);
+        let record = state.action_list.last().unwrap();
+        assert_eq!(record.coercion, Some(Coercion::AllIn));
+        assert_eq!(state.players_state[record.player as usize].stake, 0.0);
+
+        let state = state.apply_default_action(DefaultPolicy::CheckElseFold);
+        assert_eq!(
+            state.action_list.last().unwrap().coercion,
+            Some(Coercion::Timeout)
+        );
+    }
+
+    #[cfg(test)]
+    #[test]
+    fn default_action_checks_else_folds() {
+        let state = State::from_seed(3, 0, 0.5, 1.0, 100.0, 1234, false, 0.0).unwrap();
+
+        // Facing the big blind: fold
+        let state = state.apply_default_action(DefaultPolicy::CheckElseFold);
+        let record = state.action_list.last().unwrap();
+        assert_eq!(record.action.action, ActionEnum::Fold);
+        assert!(record.forced);
+        assert_eq!(state.from_action.as_ref(), Some(record));
+
+        let state = state.apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+        assert!(!state.action_list.last().unwrap().forced);
+
+        // Big blind option: check
+        let state = state.apply_default_action(DefaultPolicy::CheckElseFold);
+        assert_eq!(state.stage, Stage::Flop);
+        let record = state.action_list.last().unwrap();
+        assert_eq!(record.action.action, ActionEnum::CheckCall);
+        assert!(record.forced);
+    }
+
+    #[cfg(test)]
+    #[test]
+    fn protected_all_in_keeps_the_stack_out_of_the_hand() {
+        let state = State::from_seed(3, 0, 0.5, 1.0, 100.0, 1234, false, 0.0).unwrap();
+        let state = state.apply_action(Action::new(ActionEnum::BetRaise, 4.0));
+        let protected = state.current_player;
+        let state = state.apply_protected_all_in();
+        let record = state.action_list.last().unwrap();
+        assert_eq!(record.player, protected);
+        assert!(record.forced);
+        assert_eq!(record.coercion, Some(Coercion::Disconnected));
+        assert_eq!(record.chips, 0.0);
+        let ps = &state.players_state[protected as usize];
+        assert!(ps.active);
+        assert_eq!((ps.stake, ps.held_back), (0.0, 99.5));
+
+        // The others play on without them and can only lose the blind to them
+        let mut state = state;
+        while !state.final_state {
+            assert_ne!(state.current_player, protected);
+            state = state.apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+        }
+        let ps = &state.players_state[protected as usize];
+        assert!(ps.reward >= -0.5 && ps.reward <= 1.0);
+        assert_eq!(ps.held_back, 99.5);
+    }
+
+    #[cfg(test)]
+    #[test]
+    fn hand_cap_puts_players_all_in() {
+        let rules = TableRules {
+            hand_cap: Some(20.0),
+            ..TableRules::default()
+        };
+        let state = State::from_seed(2, 0, 0.5, 1.0, 100.0, 1234, false, 0.0).unwrap();
+        for cap in [0.5, 0.0, -20.0, f64::NAN, f64::INFINITY] {
+            let rules = TableRules {
+                hand_cap: Some(cap),
+                ..TableRules::default()
+            };
+            assert!(state.with_rules(rules).is_err());
+        }
+        let state = state.with_rules(rules).unwrap();
+        let held_back: Vec<f64> = state.players_state.iter().map(|ps| ps.held_back).collect();
+        assert_eq!(held_back, vec![80.0, 80.0]);
+        assert_eq!(state.legal_bet_range(), Some((2.0, 20.0)));
+
+        // A raise over the cap is all-in for the cap, and the big blind can only call it
+        let state = state.apply_action(Action::new(ActionEnum::BetRaise, 50.0));
+        assert_eq!(state.players_state[1].bet_chips, 20.0);
+        assert_eq!(state.legal_bet_range(), None);
+
+        let state = state.apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+        assert!(state.final_state);
+        assert!(state.check_invariants().is_empty());
+        for ps in &state.players_state {
+            assert_eq!(ps.reward.abs(), 20.0);
+            assert_eq!(ps.held_back, 80.0);
+        }
+    }
+
+    #[cfg(test)]
+    #[test]
+    fn pineapple_players_discard_one_card() {
+        let deck = crate::seeds::shuffled_deck(1234);
+        let state = State::pineapple(3, 0, 0.5, 1.0, 100.0, deck.clone(), false, false, 0).unwrap();
+        assert_eq!(state.players_state[1].hand, (deck[0], deck[1]));
+        assert_eq!(state.players_state[1].third_card, Some(deck[6]));
+        assert_eq!(state.cards_remaining(), 52 - 9);
+        assert!(state.check_invariants().is_empty());
+
+        // Player 1 keeps their third card, player 0 acts first without discarding
+        let state = state.discard(1, deck[0]).unwrap();
+        assert_eq!(state.players_state[1].hand, (deck[6], deck[1]));
+        assert_eq!(state.players_state[1].discarded, Some(deck[0]));
+        assert!(state.discard(1, deck[1]).is_err());
+        assert!(state.discard(2, deck[0]).is_err());
+        let state = state.apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+        assert_eq!(state.players_state[0].third_card, None);
+        assert_eq!(state.players_state[0].discarded, Some(deck[8]));
+
+        // Crazy Pineapple players discard on the flop
+        let state = State::pineapple(2, 0, 0.5, 1.0, 100.0, deck.clone(), true, false, 0).unwrap();
+        assert!(state.discard(1, deck[0]).is_err());
+        let state = state.apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+        let state = state.apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+        assert_eq!(state.stage, Stage::Flop);
+        assert!(state.players_state.iter().all(|ps| ps.third_card.is_some()));
+        let state = state.discard(0, deck[3]).unwrap();
+        assert!(state.check_invariants().is_empty());
+
+        // All-in before the flop: nobody acts on the flop, the third cards go at the showdown
+        let state = State::pineapple(2, 0, 0.5, 1.0, 100.0, deck, true, false, 0).unwrap();
+        let state = state.apply_action(Action::new(ActionEnum::BetRaise, 100.0));
+        let state = state.apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+        assert!(state.final_state);
+        assert!(state.players_state.iter().all(|ps| ps.discarded.is_some()));
+        assert!(state.check_invariants().is_empty());
+    }
+
+    #[cfg(test)]
+    #[test]
+    fn dead_blind_goes_to_the_winner() {
+        let state = State::from_seed(3, 0, 0.5, 1.0, 100.0, 1234, false, 0.0).unwrap();
+        let state = state.post_dead_blind(0, 1.0).unwrap();
+        assert_eq!(state.pot, 2.5);
+        assert!(state.check_invariants().is_empty());
+        assert!(state.post_dead_blind(3, 1.0).is_err());
+
+        // The dead blind does not count toward the button's call
+        assert_eq!(
+            state
+                .explain_action(Action::new(ActionEnum::CheckCall, 0.0))
+                .reason,
+            "Call 1"
+        );
+        let state = state.apply_action(Action::new(ActionEnum::Fold, 0.0));
+        assert!(state.post_dead_blind(1, 1.0).is_err());
+        let state = state.apply_action(Action::new(ActionEnum::Fold, 0.0));
+        assert!(state.final_state);
+        assert_eq!(state.players_state[0].reward, -1.0);
+        assert_eq!(state.players_state[2].reward, 1.5);
+        assert!(state.check_invariants().is_empty());
+    }
+
+    #[cfg(test)]
+    #[test]
+    fn missed_blinds_are_posted_or_waited_out() {
+        // Button 0: player 1 posts the small blind, player 2 the big blind
+        let owing = vec![true, false, true, true];
+        let (dealt, dead) = missed_blind_seating(owing.clone(), 0, MissedBlinds::PostDead);
+        assert_eq!((dealt, dead), (vec![0, 1, 2, 3], vec![0, 3]));
+        let (dealt, dead) = missed_blind_seating(owing, 0, MissedBlinds::WaitForBigBlind);
+        assert_eq!((dealt, dead), (vec![1, 2], vec![]));
+        // Waiting players are dealt in when the hand could not start without them
+        let (dealt, _) = missed_blind_seating(vec![true; 3], 1, MissedBlinds::WaitForBigBlind);
+        assert_eq!(dealt, vec![0, 1, 2]);
+
+        let state = State::from_seed(4, 0, 0.5, 1.0, 100.0, 1234, false, 0.0).unwrap();
+        let state = state.owe_blinds(vec![0, 2, 3]).unwrap();
+        assert!(state.owe_blinds(vec![0]).is_err());
+        assert!(state.owe_blinds(vec![4]).is_err());
+        let settlements: Vec<BlindSettlement> = state
+            .owed_blinds
+            .iter()
+            .map(|owed| owed.settlement)
+            .collect();
+        assert_eq!(
+            settlements,
+            vec![
+                BlindSettlement::Pending,
+                BlindSettlement::BigBlind,
+                BlindSettlement::Pending
+            ]
+        );
+        assert!(state.post_owed_blind(1).is_err());
+        assert!(state.post_owed_blind(2).is_err());
+
+        let state = state.post_owed_blind(0).unwrap();
+        assert_eq!(state.pot, 2.5);
+        assert_eq!(state.owed_blinds[0].settlement, BlindSettlement::PostedDead);
+        assert!(state.post_owed_blind(0).is_err());
+        assert!(state.check_invariants().is_empty());
+
+        // Player 3 plays the hand without posting and still owes the blind after it
+        let state = state.apply_action(Action::new(ActionEnum::Fold, 0.0));
+        assert!(state.owe_blinds(vec![1]).is_err());
+        assert!(state.post_owed_blind(3).is_err());
+        let state = state
+            .apply_action(Action::new(ActionEnum::Fold, 0.0))
+            .apply_action(Action::new(ActionEnum::Fold, 0.0));
+        assert!(state.final_state);
+        assert_eq!(state.players_state[2].reward, 1.5);
+        assert_eq!(state.pending_blinds(), vec![3]);
+        assert!(state.check_invariants().is_empty());
+    }
+
+    #[cfg(test)]
+    #[test]
+    fn folded_players_keep_their_investments() {
+        // Button 0: player 1 posts the small blind, player 2 the big blind
+        let mut state = State::from_seed(3, 0, 0.5, 1.0, 100.0, 1234, false, 0.0).unwrap();
+        for action in [
+            Action::new(ActionEnum::BetRaise, 3.0),
+            Action::new(ActionEnum::Fold, 0.0),
+            Action::new(ActionEnum::CheckCall, 0.0),
+            // Flop: the big blind bets, the button calls
+            Action::new(ActionEnum::BetRaise, 4.0),
+            Action::new(ActionEnum::CheckCall, 0.0),
+            // Turn: the button bets after a check and the big blind folds
+            Action::new(ActionEnum::CheckCall, 0.0),
+            Action::new(ActionEnum::BetRaise, 8.0),
+            Action::new(ActionEnum::Fold, 0.0),
+        ] {
+            state = state.apply_action(action);
+        }
+        assert!(state.final_state);
+
+        let folded_on: Vec<Option<Stage>> =
+            state.players_state.iter().map(|ps| ps.folded_on).collect();
+        assert_eq!(
+            folded_on,
+            vec![None, Some(Stage::Preflop), Some(Stage::Turn)]
+        );
+        let invested: Vec<[f64; 4]> = state.players_state.iter().map(|ps| ps.invested).collect();
+        assert_eq!(
+            invested,
+            vec![
+                [3.0, 4.0, 8.0, 0.0],
+                [0.5, 0.0, 0.0, 0.0],
+                [3.0, 4.0, 0.0, 0.0]
+            ]
+        );
+        let rewards: Vec<f64> = state.players_state.iter().map(|ps| ps.reward).collect();
+        assert_eq!(rewards, vec![7.5, -0.5, -7.0]);
+    }
+
+    #[cfg(test)]
+    #[test]
+    fn big_blind_ante_covers_the_table() {
+        let state = State::from_seed(4, 0, 1.0, 2.0, 100.0, 1234, false, 0.0).unwrap();
+        let bb_ante = state.post_antes(2.0, true, false).unwrap();
+        assert_eq!(bb_ante.pot, 5.0);
+        assert_eq!(bb_ante.players_state[2].pot_chips, 2.0);
+        assert!(bb_ante.check_invariants().is_empty());
+
+        let every_player = state.post_antes(0.5, false, false).unwrap();
+        assert_eq!(every_player.pot, 5.0);
+        assert_eq!(every_player.players_state[3].stake, 99.5);
+
+        // A big blind left with 3 chips cannot cover both the blind and the ante
+        let mut short = state.clone();
+        short.players_state[2].stake = 1.0;
+        let blind_first = short.post_antes(2.0, true, false).unwrap();
+        assert_eq!(blind_first.players_state[2].bet_chips, 2.0);
+        assert_eq!(blind_first.players_state[2].pot_chips, 1.0);
+        let ante_first = short.post_antes(2.0, true, true).unwrap();
+        assert_eq!(ante_first.players_state[2].bet_chips, 1.0);
+        assert_eq!(ante_first.players_state[2].pot_chips, 2.0);
+        assert_eq!(ante_first.players_state[2].stake, 0.0);
+
+        // No blinds in an ante-only hand: the first player can check
+        let deck = crate::seeds::shuffled_deck(1234);
+        let ante_only = State::ante_only(4, 0, 1.0, 2.0, 100.0, deck, false, 1234).unwrap();
+        assert_eq!(ante_only.pot, 4.0);
+        assert_eq!(ante_only.current_player, 1);
+        let state = (0..4).fold(ante_only, |state, _| {
+            state.apply_action(Action::new(ActionEnum::CheckCall, 0.0))
+        });
+        assert_eq!(state.stage, Stage::Flop);
+        assert!(state.check_invariants().is_empty());
+    }
+
+    #[cfg(test)]
+    #[test]
+    fn hands_can_be_dealt_with_antes() {
+        let state = State::from_seed(3, 0, 0.5, 1.0, 100.0, 7, false, 2.0).unwrap();
+        assert_eq!(state.pot, 7.5);
+        assert!(state.players_state.iter().all(|ps| ps.pot_chips == 2.0));
+        assert_eq!(state.players_state[2].stake, 97.0);
+        assert!(state.check_invariants().is_empty());
+        let negative = State::from_seed(3, 0, 0.5, 1.0, 100.0, 7, false, -1.0).unwrap_err();
+        assert_eq!(negative.msg, "The ante cannot be negative");
+        for ante in [f64::NAN, f64::INFINITY] {
+            let error = State::from_seed(3, 0, 0.5, 1.0, 100.0, 7, false, ante).unwrap_err();
+            assert_eq!(error.msg, "The ante must be a finite number");
+        }
+
+        // Everybody all-in with 3 chips, 1 of them anted: the aces on the button win it all
+        let cards = [
+            "SK", "HK", "D7", "C2", "SA", "HA", "DQ", "CJ", "S9", "H4", "D3",
+        ];
+        let deck: Vec<Card> = cards
+            .iter()
+            .map(|c| Card::from_string(c.to_string()).unwrap())
+            .collect();
+        let mut state = State::from_deck(3, 0, 0.5, 1.0, 3.0, deck, false, 0, 1.0).unwrap();
+        assert_eq!(state.pot, 4.5);
+        state = state.apply_action(Action::new(ActionEnum::BetRaise, 2.0));
+        while !state.final_state {
+            state = state.apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+        }
+        let rewards: Vec<f64> = state.players_state.iter().map(|ps| ps.reward).collect();
+        assert_eq!(rewards, vec![6.0, -3.0, -3.0]);
+    }
+
+    #[cfg(test)]
+    #[test]
+    fn short_stack_antes_are_dead_money() {
+        // Player 1 holds kings, player 2 seven-deuce and player 0 on the button aces
+        let cards = [
+            "SK", "HK", "D7", "C2", "SA", "HA", "DQ", "CJ", "S9", "H4", "D3",
+        ];
+        let deck: Vec<Card> = cards
+            .iter()
+            .map(|c| Card::from_string(c.to_string()).unwrap())
+            .collect();
+        let state = State::from_deck(3, 0, 0.5, 1.0, 100.0, deck, false, 0, 0.0).unwrap();
+        let eligible = |pot: &Pot| {
+            let mut players: Vec<u64> = pot.eligible_players.iter().copied().collect();
+            players.sort();
+            (pot.amount, players)
+        };
+
+        // The big blind is all-in for the blind and the table's ante: a call of the blind plays
+        // for the whole pot, the ante is not an uncalled bet
+        let mut short = state.clone();
+        short.players_state[2].stake = 2.0;
+        let mut bb_ante = short.post_antes(2.0, true, false).unwrap();
+        assert_eq!(bb_ante.players_state[2].ante_chips, 2.0);
+        bb_ante = bb_ante.apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+        bb_ante = bb_ante.apply_action(Action::new(ActionEnum::Fold, 0.0));
+        assert!(bb_ante.final_state);
+        let rewards: Vec<f64> = bb_ante.players_state.iter().map(|ps| ps.reward).collect();
+        assert_eq!(rewards, vec![3.5, -0.5, -3.0]);
+        let pots: Vec<_> = contributions::pots(&bb_ante).iter().map(eligible).collect();
+        assert_eq!(pots, vec![(4.5, vec![0, 2])]);
+
+        // The button is all-in for half the ante: the aces win half of each ante, the kings
+        // the rest of the antes and the bets
+        let mut short = state.clone();
+        short.players_state[0].stake = 1.0;
+        let mut partial = short.post_antes(2.0, false, false).unwrap();
+        assert_eq!(partial.players_state[0].ante_chips, 1.0);
+        assert_eq!(partial.current_player, 1);
+        while !partial.final_state {
+            partial = partial.apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+        }
+        assert!(partial.check_invariants().is_empty());
+        let rewards: Vec<f64> = partial.players_state.iter().map(|ps| ps.reward).collect();
+        assert_eq!(rewards, vec![2.0, 1.0, -3.0]);
+        let pots: Vec<_> = contributions::pots(&partial).iter().map(eligible).collect();
+        assert_eq!(pots, vec![(3.0, vec![0, 1, 2]), (4.0, vec![1, 2])]);
+
+        // The blinds fold to the short ante: it only wins as much of each ante, the rest goes
+        // back to the blinds
+        let mut folded = short.post_antes(2.0, false, false).unwrap();
+        for _ in 0..2 {
+            folded = folded.apply_action(Action::new(ActionEnum::Fold, 0.0));
+        }
+        assert!(folded.final_state);
+        let rewards: Vec<f64> = folded.players_state.iter().map(|ps| ps.reward).collect();
+        assert_eq!(rewards, vec![2.0, -1.0, -1.0]);
+    }
+
+    #[cfg(test)]
+    #[test]
+    fn straddlers_act_last_preflop() {
+        let state = State::from_seed(6, 0, 0.5, 1.0, 100.0, 1234, false, 0.0).unwrap();
+
+        // UTG straddle and a re-straddle: action starts after the re-straddler
+        let utg = state.post_straddle(3, 2.0).unwrap();
+        assert!(utg.post_straddle(3, 4.0).is_err());
+        let restraddled = utg.post_straddle(4, 4.0).unwrap();
+        assert_eq!(restraddled.current_player, 5);
+        assert_eq!(restraddled.pot, 7.5);
+        assert!(restraddled.check_invariants().is_empty());
+
+        // Mississippi straddle: the blinds open and the button closes the action
+        let mut state = state.post_straddle(0, 2.0).unwrap();
+        assert!(state.post_straddle(1, 4.0).is_err());
+        for expected in [1, 2, 3, 4, 5] {
+            assert_eq!(state.current_player, expected);
+            state = state.apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+        }
+        assert_eq!(state.current_player, 0);
+        assert_eq!(state.stage, Stage::Preflop);
+        let state = state.apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+        assert_eq!(state.stage, Stage::Flop);
+        assert_eq!(state.pot, 12.0);
+    }
+
+    #[cfg(test)]
+    #[test]
+    fn clock_records_think_time() {
+        let state = State::from_seed(2, 0, 0.5, 1.0, 100.0, 1234, false, 0.0).unwrap();
+        let untimed = state.apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+        assert_eq!(untimed.action_list[0].timestamp, None);
+
+        let state = state.with_clock(Some(100.0));
+        let state = state.apply_action_at(Action::new(ActionEnum::CheckCall, 0.0), 102.5);
+        let state = state.apply_action_at(Action::new(ActionEnum::BetRaise, 4.0), 110.0);
+        let timings: Vec<(Option<f64>, Option<f64>)> = state
+            .action_list
+            .iter()
+            .map(|a| (a.timestamp, a.think_time))
+            .collect();
+        assert_eq!(
+            timings,
+            vec![(Some(102.5), Some(2.5)), (Some(110.0), Some(7.5))]
+        );
+        assert_eq!(state.from_action.as_ref().unwrap().think_time, Some(7.5));
+        assert_eq!(state.clock, Some(110.0));
+
+        // With the clock on, plain actions are stamped with the wall clock
+        let state = state.apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+        assert!(state.action_list[2].timestamp.unwrap() > 110.0);
+    }
+
+    #[cfg(test)]
+    #[test]
+    fn drifted_pot_is_reported() {
+        let mut state = State::from_seed(3, 0, 0.5, 1.0, 100.0, 1234, true, 0.0).unwrap();
+        state.pot = 7.0;
+        assert!(!state.check_invariants().is_empty());
+
+        // The engine does not cover the drift up
+        let next = state.apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+        assert_ne!(next.pot, next.chips_in_pot());
+        assert!(!next.check_invariants().is_empty());
+        let drift = next.trace.iter().find(|e| e.kind == TraceKind::PotDrift);
+        assert_eq!(drift.and_then(|e| e.amount), Some(2.5));
+
+        assert_eq!(state.recompute_pot(), 1.5);
+        assert!(state.check_invariants().is_empty());
+    }
+
+    #[cfg(test)]
+    #[test]
+    fn action_limit_aborts_with_diagnostic() {
+        let mut state = State::from_seed(2, 0, 0.5, 1.0, 100.0, 1234, false, 0.0).unwrap();
+        let record = ActionRecord {
+            player: 0,
+            stage: Stage::Preflop,
+            action: Action::new(ActionEnum::CheckCall, 0.0),
+            legal_actions: vec![],
+            timestamp: None,
+            think_time: None,
+            forced: false,
+            requested: Action::new(ActionEnum::CheckCall, 0.0),
+            coercion: None,
+            pot_before: 1.5,
+            to_call: 0.5,
+            chips: 0.5,
+        };
+        state.action_list = vec![record; MAX_ACTIONS_PER_HAND];
+
+        let new_state = state.apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+        assert!(matches!(new_state.status, StateStatus::EngineError));
+        assert!(new_state.final_state);
+        let diagnostic = new_state.engine_diagnostic.unwrap();
+        let reason = engine_error_reason(&diagnostic).unwrap();
+        assert!(reason.starts_with("Hand exceeded the maximum"));
+
+        // The report loads back and replays to the same error
+        let mut bundle: serde_json::Value = serde_json::from_str(&diagnostic).unwrap();
+        let before = schema::from_document(bundle["state"].take()).unwrap();
+        assert!(matches!(before.status, StateStatus::Ok));
+        assert_eq!(before.action_list.len(), MAX_ACTIONS_PER_HAND);
+        let actions: Vec<Action> = serde_json::from_value(bundle["actions"].take()).unwrap();
+        assert_eq!(actions, vec![Action::new(ActionEnum::CheckCall, 0.0)]);
+        let replayed = actions
+            .into_iter()
+            .fold(before, |state, action| state.apply_action(action));
+        assert!(matches!(replayed.status, StateStatus::EngineError));
+        assert_eq!(replayed.engine_diagnostic.unwrap(), diagnostic);
+    }
+
     #[cfg(test)]
     proptest! {
         #[test]
         fn from_deck_doesnt_crash(n_players in 0..10000, deck: Vec<Card>, sb in 0.5_f64..100.0_f64, bb_mult in 2..5, stake_mult in 100..1000, actions: Vec<Action>) {
-            let initial_state = State::from_deck(n_players as u64, 0, sb, sb * bb_mult as f64, sb * stake_mult as f64, deck, false, 12345);
+            let initial_state = State::from_deck(n_players as u64, 0, sb, sb * bb_mult as f64, sb * stake_mult as f64, deck, false, 12345, 0.0);
             match initial_state {
                 Ok(mut state) => {
                     for action in actions.iter().take(100) {
@@ -1194,7 +3573,7 @@ mod tests {
         #[test]
         fn zero_sum_game(n_players in 2..26, seed: u64, sb in 0.5_f64..100.0_f64, bb_mult in 2..5, stake_mult in 100..1000, actions in prop::collection::vec(Action::arbitrary_with(((), ())).prop_filter("Raise abs amount bellow 1e12",
         |a| a.amount.abs() < 1e12), 1..100)) {
-            let initial_state = State::from_seed(n_players as u64, 0, sb, sb * bb_mult as f64, sb * stake_mult as f64, seed, false);
+            let initial_state = State::from_seed(n_players as u64, 0, sb, sb * bb_mult as f64, sb * stake_mult as f64, seed, false, 0.0);
             match initial_state {
                 Ok(mut state) => {
                     for action in actions {
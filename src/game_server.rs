@@ -1,15 +1,60 @@
+use itertools::Itertools;
+use pyo3::prelude::*;
 use std::collections::HashMap;
-use std::sync::Arc;
-use tracing::info;
-
-use crate::state::action::{Action, ActionEnum};
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, Weak};
+use tokio::sync::RwLock;
+use tracing::{error, info};
+
+use crate::account_data::{self, AccountDeletion};
+use crate::bots::{BotPlayer, BotSettings};
+use crate::chip_format::{ChipFormat, ChipStack};
+use crate::contributions;
+use crate::evaluator::{tie, Tie};
+use crate::events::{EventBus, HandPlayer, HandSummary, Subscriber, TableEvent};
+use crate::game_logic::{missed_blind_seating, now, rank_hand, slice_awards, Pot};
+use crate::hand_archive::HandArchive;
+use crate::hand_strength::{hand_strength, HandCategory};
+use crate::insurance::{insurance_quote, scooped, InsuranceQuote};
+use crate::messages::{self, ClientError, Label, Locale};
+use crate::player_stats::{PlayerStats, PlayerStatsStore};
+use crate::protocol::CardInfo;
+use crate::rake::{take_rake, BadBeat, HandRake, JackpotDrop, Rake, TableRake};
+use crate::redeal::redeal_unknown;
+use crate::runtime;
+use crate::seeds::{hand_seed, shuffled_deck};
+use crate::session_recording::SessionRecorder;
+use crate::state::action::{Action, ActionEnum, ActionRecord, DefaultPolicy};
 use crate::state::card::Card;
+pub use crate::state::rules::MissedBlinds;
+use crate::state::rules::TableRules;
+use crate::state::stage::Stage;
+use crate::state::view::StateView;
 use crate::state::State;
+use crate::store_writer::StoreWriter;
+use crate::table_game::{HandSetup, HoldemConfig, HoldemOpening, TableGame};
+use crate::tournament_clock::{BlindLevel, ClockStatus, TournamentClock};
+use crate::webhooks::{Webhook, Webhooks};
 use crate::websocket_server::{
-    CardInfo, GameStateMessage, HandWinningsMessage, OnMoveMessage, PlayerInfo, WebSocketServer,
-    WinningInfo,
+    ActionClosedMessage, AllInEquityInfo, BadBeatInfo, BlindLevelInfo, BountyInfo, ErrorMessage,
+    GameStateMessage, HandStrengthMessage, HandWinningsMessage, InsuranceOfferMessage,
+    InsuranceResultMessage, KnockoutMessage, OnMoveMessage, PlayerInfo, PlayerStatsMessage,
+    PotInfo, PotUpdateMessage, RegisteredMessage, SeatEquity, SplitInfo, TableLayoutInfo,
+    TournamentClockMessage, WinningInfo,
 };
 
+/// Monte Carlo samples used for the hero-only equity estimate
+const HAND_STRENGTH_SAMPLES: usize = 500;
+
+/// Seconds between two runs of `GameConfig.pseudonymize_after_days` over the hand histories
+const PSEUDONYMIZE_EVERY: f64 = 3_600.0;
+
+/// Whether `days` can be `GameConfig.pseudonymize_after_days`: finite and not negative
+pub fn valid_days(days: f64) -> bool {
+    days.is_finite() && days >= 0.0
+}
+
 #[derive(Debug, Clone)]
 pub enum PlayerAction {
     Fold,
@@ -27,6 +72,22 @@ pub struct GamePlayer {
     pub chips: f64,
     pub connected: bool,
     pub starting_session_chips: f64,
+    pub hand_strength_hints: bool,
+    /// Took a seat once the session had started and has not paid a big blind yet
+    pub owes_blind: bool,
+    /// Straddles the next hand when their seat's turn to straddle comes
+    pub straddle: bool,
+    /// When the player's connection closed, `None` while connected
+    pub disconnected_at: Option<f64>,
+    /// Secret the player was given when they registered, which takes their seat and stack
+    /// back from a new connection
+    pub reconnect_token: String,
+    /// Times the player bought into the tournament, re-entries included
+    pub entries: u32,
+    /// On the player's head at a knockout tournament, see `GameConfig.bounty`
+    pub bounty: f64,
+    /// The half of the bounties of the players they knocked out that is paid to them
+    pub bounties_won: f64,
 }
 
 impl GamePlayer {
@@ -38,29 +99,233 @@ impl GamePlayer {
             chips: initial_chips,
             connected: true,
             starting_session_chips: initial_chips,
+            hand_strength_hints: false,
+            owes_blind: false,
+            straddle: false,
+            disconnected_at: None,
+            reconnect_token: uuid::Uuid::new_v4().to_string(),
+            entries: 0,
+            bounty: 0.0,
+            bounties_won: 0.0,
         }
     }
 }
 
+/// Bot the server plays for, see `GameServer::seat_bot`
+#[derive(Clone)]
+struct SeatedBot {
+    bot: Arc<dyn BotPlayer>,
+    settings: BotSettings,
+}
+
+/// Insurance offered to the favourite of an all-in, waiting for their answer
+#[derive(Debug, Clone)]
+struct PendingInsurance {
+    player_id: String,
+    quote: InsuranceQuote,
+}
+
 #[derive(Clone)]
 pub struct GameServer {
     players: HashMap<String, GamePlayer>,
     seats: HashMap<u8, String>, // seat number -> player_id
     game_state: Option<State>,
-    websocket_server: Option<Arc<WebSocketServer>>,
+    /// Seats dealt into the current hand, in `State` player order
+    hand_seats: Vec<u8>,
+    /// Seats that posted the small and big blind of the current hand, `None` in a hand
+    /// without blinds
+    blind_seats: Option<(u8, u8)>,
+    /// Where what happens at the table is published, see `GameServer::subscribe`
+    events: EventBus,
+    /// The lock the server is shared behind once it serves clients, for the bot tasks
+    handle: Option<Weak<RwLock<GameServer>>>,
     game_config: GameConfig,
     dealer_seat: u8,
     game_running: bool,
+    player_stats: Arc<Mutex<PlayerStatsStore>>,
+    hand_archive: Arc<Mutex<HandArchive>>,
+    /// Where the stats and hand histories are read and written, off the table's lock
+    stores: StoreWriter,
+    /// Player who won the last pot(s) alone, and how many pots in a row
+    win_streak: Option<(String, u32)>,
+    hands_dealt: u64,
+    session_seed: u64,
+    /// Ids every hand is tagged with, see `State::with_ids`
+    table_id: String,
+    session_id: String,
+    pending_insurance: Option<PendingInsurance>,
+    /// Player id -> bot playing that player
+    bots: HashMap<String, SeatedBot>,
+    /// Runs `GameConfig.blind_levels`, from the first hand on
+    clock: Option<TournamentClock>,
+    /// Level and break last broadcast
+    clock_status: Option<ClockStatus>,
+    /// When the action timers stopped for a break or a pause of the clock
+    timers_paused_at: Option<f64>,
+    /// What the house took from the last hand settled
+    hand_rake: HandRake,
+    table_rake: TableRake,
+    /// Players who moved to another table after folding, by the seat they left in the
+    /// current hand, see `GameServer::transfer_out`
+    departed: HashMap<u8, GamePlayer>,
+    /// When the hand histories were last pseudonymized
+    pseudonymized_at: Option<f64>,
+    /// Buy-ins of the tournament, re-entries included
+    entries: u32,
+}
+
+/// Called with a view of the hand whenever the clients are sent the game state
+pub type Observer = Arc<dyn Fn(StateView) + Send + Sync>;
+
+/// How `GameConfig.ante` is posted
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AnteStructure {
+    /// Every player antes on top of the blinds
+    #[default]
+    EveryPlayer,
+    /// The big blind posts the ante for the whole table
+    BigBlind,
+    /// Antes replace the blinds
+    AnteOnly,
+}
+
+/// What the table does for a disconnected player to act once `GameConfig.disconnect_grace`
+/// has run out
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DisconnectProtection {
+    /// Nothing, only `GameConfig.action_timeout` applies
+    #[default]
+    Off,
+    Fold,
+    /// All-in for the chips already put in, see `State::apply_protected_all_in`. The rest of
+    /// the stack sits the hand out.
+    AllIn,
 }
 
+/// Who may open the chain of straddles before the cards are dealt
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Straddles {
+    #[default]
+    Off,
+    /// The player under the gun, re-straddled by the players after them
+    Utg,
+    /// The button (Mississippi straddle): the small blind then opens the action
+    Button,
+}
+
+/// Table settings of the server, also passed to `serve` from Python
+#[pyclass]
 #[derive(Debug, Clone)]
 pub struct GameConfig {
+    #[pyo3(get, set)]
     pub max_players: u8,
+    #[pyo3(get, set)]
     pub default_stack_size: f64,
+    #[pyo3(get, set)]
     pub small_blind: f64,
+    #[pyo3(get, set)]
     pub big_blind: f64,
-    #[allow(dead_code)]
+    /// No antes when 0
+    #[pyo3(get, set)]
     pub ante: f64,
+    #[pyo3(get, set)]
+    pub ante_structure: AnteStructure,
+    /// A player who cannot cover both their blind and the ante pays the ante first
+    #[pyo3(get, set)]
+    pub ante_first: bool,
+    /// JSON file holding lifetime player stats, kept in memory only when unset
+    #[pyo3(get, set)]
+    pub stats_file: Option<String>,
+    /// Directory the hand histories of every account are written to, kept in memory only
+    /// when unset
+    #[pyo3(get, set)]
+    pub hand_history_dir: Option<String>,
+    /// Directory every connection is recorded to, see `session_recording`. No recordings when
+    /// unset
+    #[pyo3(get, set)]
+    pub session_recording_dir: Option<String>,
+    /// Days after which the other players of a hand go by a pseudonym of the table in the
+    /// hand histories, see `HandArchive::pseudonymize_before`. Names are kept when unset, or
+    /// when it is not a finite number of days of at least 0
+    #[pyo3(get, set)]
+    pub pseudonymize_after_days: Option<f64>,
+    /// Send the amounts of the broadcasts written with this format too, and the chips of the
+    /// bets and winnings for animations. Only the raw amounts are sent when unset
+    #[pyo3(get, set)]
+    pub chip_format: Option<ChipFormat>,
+    #[pyo3(get, set)]
+    pub rules: TableRules,
+    /// Offer insurance to the favourite of an all-in on the flop or the turn
+    #[pyo3(get, set)]
+    pub insurance: bool,
+    /// Language of the errors and descriptions sent to clients
+    #[pyo3(get, set)]
+    pub locale: Locale,
+    /// Seconds a player has to act before they check or fold, no limit when unset
+    #[pyo3(get, set)]
+    pub action_timeout: Option<f64>,
+    /// Every hand is dealt from `hand_seed(session_seed, hand_index)`, random when unset
+    #[pyo3(get, set)]
+    pub session_seed: Option<u64>,
+    #[pyo3(get, set)]
+    pub missed_blinds: MissedBlinds,
+    #[pyo3(get, set)]
+    pub straddles: Straddles,
+    /// Straddles per hand, re-straddles included
+    #[pyo3(get, set)]
+    pub max_straddles: u8,
+    /// Id of the table in hand records and messages, a random UUID when unset
+    #[pyo3(get, set)]
+    pub table_id: Option<String>,
+    /// Blind structure of a tournament table, which replaces the blinds and ante above
+    #[pyo3(get, set)]
+    pub blind_levels: Vec<BlindLevel>,
+    /// Levels of `blind_levels` new players can take a seat during, the break after the last
+    /// of them included. Registration stays open when unset.
+    #[pyo3(get, set)]
+    pub late_registration_levels: Option<usize>,
+    /// Paid into the prize pool by every entry of a tournament table, re-entries included
+    #[pyo3(get, set)]
+    pub buy_in: f64,
+    /// Shares of the prize pool paid to the first places of a tournament, from the winner
+    /// down. The winner takes it all when empty.
+    #[pyo3(get, set)]
+    pub payouts: Vec<f64>,
+    /// Seats a satellite awards, in as many equal shares of the prize pool, in place of
+    /// `payouts`
+    #[pyo3(get, set)]
+    pub satellite_seats: Option<u32>,
+    /// Put on the head of every entry of a progressive knockout tournament, on top of
+    /// `buy_in`. No bounties when 0.
+    #[pyo3(get, set)]
+    pub bounty: f64,
+    #[pyo3(get, set)]
+    pub disconnect_protection: DisconnectProtection,
+    /// Seconds a disconnected player's turn waits before `disconnect_protection` acts for them
+    #[pyo3(get, set)]
+    pub disconnect_grace: f64,
+    /// Endpoints told about finished hands and players joining and leaving
+    #[pyo3(get, set)]
+    pub webhooks: Vec<Webhook>,
+    /// Pots of at least this many big blinds are sent to the webhooks as `bigPot` events, no
+    /// alerts when unset
+    #[pyo3(get, set)]
+    pub big_pot_alert: Option<f64>,
+    /// Taken from the winners of every pot, no rake when unset
+    #[pyo3(get, set)]
+    pub rake: Option<Rake>,
+    #[pyo3(get, set)]
+    pub jackpot_drops: Vec<JackpotDrop>,
+    /// Losing hands that hit the bad beat jackpot, not checked when unset
+    #[pyo3(get, set)]
+    pub bad_beat: Option<BadBeat>,
+    /// Bearer token of the admin API, served over plain HTTP on the WebSocket port. The API is
+    /// off when unset
+    #[pyo3(get, set)]
+    pub admin_token: Option<String>,
 }
 
 impl Default for GameConfig {
@@ -71,125 +336,736 @@ impl Default for GameConfig {
             small_blind: 5.0,
             big_blind: 10.0,
             ante: 0.0,
+            ante_structure: AnteStructure::EveryPlayer,
+            ante_first: false,
+            stats_file: None,
+            hand_history_dir: None,
+            session_recording_dir: None,
+            pseudonymize_after_days: None,
+            chip_format: None,
+            rules: TableRules::default(),
+            insurance: false,
+            locale: Locale::En,
+            action_timeout: None,
+            session_seed: None,
+            missed_blinds: MissedBlinds::PostDead,
+            straddles: Straddles::Off,
+            max_straddles: 1,
+            table_id: None,
+            blind_levels: Vec::new(),
+            late_registration_levels: None,
+            buy_in: 0.0,
+            payouts: Vec::new(),
+            satellite_seats: None,
+            bounty: 0.0,
+            disconnect_protection: DisconnectProtection::Off,
+            disconnect_grace: 30.0,
+            webhooks: Vec::new(),
+            big_pot_alert: None,
+            rake: None,
+            jackpot_drops: Vec::new(),
+            bad_beat: None,
+            admin_token: None,
+        }
+    }
+}
+
+#[pymethods]
+impl GameConfig {
+    #[new]
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (max_players=6, default_stack_size=1000.0, small_blind=5.0, big_blind=10.0, ante=0.0, ante_structure=AnteStructure::EveryPlayer, ante_first=false, stats_file=None, rules=TableRules::default(), insurance=false, locale=Locale::En, action_timeout=None, session_seed=None, missed_blinds=MissedBlinds::PostDead, straddles=Straddles::Off, max_straddles=1, table_id=None, blind_levels=Vec::new(), late_registration_levels=None, disconnect_protection=DisconnectProtection::Off, disconnect_grace=30.0, hand_history_dir=None, webhooks=Vec::new(), big_pot_alert=None, rake=None, jackpot_drops=Vec::new(), bad_beat=None, session_recording_dir=None, pseudonymize_after_days=None, chip_format=None, admin_token=None, buy_in=0.0, payouts=Vec::new(), satellite_seats=None, bounty=0.0))]
+    pub fn new(
+        max_players: u8,
+        default_stack_size: f64,
+        small_blind: f64,
+        big_blind: f64,
+        ante: f64,
+        ante_structure: AnteStructure,
+        ante_first: bool,
+        stats_file: Option<String>,
+        rules: TableRules,
+        insurance: bool,
+        locale: Locale,
+        action_timeout: Option<f64>,
+        session_seed: Option<u64>,
+        missed_blinds: MissedBlinds,
+        straddles: Straddles,
+        max_straddles: u8,
+        table_id: Option<String>,
+        blind_levels: Vec<BlindLevel>,
+        late_registration_levels: Option<usize>,
+        disconnect_protection: DisconnectProtection,
+        disconnect_grace: f64,
+        hand_history_dir: Option<String>,
+        webhooks: Vec<Webhook>,
+        big_pot_alert: Option<f64>,
+        rake: Option<Rake>,
+        jackpot_drops: Vec<JackpotDrop>,
+        bad_beat: Option<BadBeat>,
+        session_recording_dir: Option<String>,
+        pseudonymize_after_days: Option<f64>,
+        chip_format: Option<ChipFormat>,
+        admin_token: Option<String>,
+        buy_in: f64,
+        payouts: Vec<f64>,
+        satellite_seats: Option<u32>,
+        bounty: f64,
+    ) -> GameConfig {
+        GameConfig {
+            max_players,
+            default_stack_size,
+            small_blind,
+            big_blind,
+            ante,
+            ante_structure,
+            ante_first,
+            stats_file,
+            hand_history_dir,
+            session_recording_dir,
+            pseudonymize_after_days,
+            chip_format,
+            rules,
+            insurance,
+            locale,
+            action_timeout,
+            session_seed,
+            missed_blinds,
+            straddles,
+            max_straddles,
+            table_id,
+            blind_levels,
+            late_registration_levels,
+            disconnect_protection,
+            disconnect_grace,
+            webhooks,
+            big_pot_alert,
+            rake,
+            jackpot_drops,
+            bad_beat,
+            admin_token,
+            buy_in,
+            payouts,
+            satellite_seats,
+            bounty,
         }
     }
+
+    pub fn __str__(&self) -> PyResult<String> {
+        Ok(format!("{:#?}", self))
+    }
+}
+
+/// Prize pool of `entries` buy-ins of `config.buy_in`, and what it pays each place of
+/// `config.payouts`, or each seat of a satellite, from the winner down
+pub fn prize_pool(config: &GameConfig, entries: u32) -> (f64, Vec<f64>) {
+    let pool = entries as f64 * config.buy_in;
+    let payouts = match (config.satellite_seats, config.payouts.as_slice()) {
+        (Some(seats), _) if seats > 0 => vec![pool / seats as f64; seats as usize],
+        (_, []) => vec![pool],
+        (_, shares) => shares.iter().map(|share| share * pool).collect(),
+    };
+    (pool, payouts)
 }
 
 impl GameServer {
     pub fn new(config: Option<GameConfig>) -> Self {
+        let game_config = config.unwrap_or_default();
+
+        let player_stats = match game_config.stats_file {
+            Some(ref path) => PlayerStatsStore::load(path.into()).unwrap_or_else(|e| {
+                // Never overwrite a file we could not read
+                error!("Failed to load player stats from {}: {}", path, e);
+                PlayerStatsStore::default()
+            }),
+            None => PlayerStatsStore::default(),
+        };
+
+        let hand_archive = match game_config.hand_history_dir {
+            Some(ref dir) => HandArchive::open(dir.into()).unwrap_or_else(|e| {
+                error!("Failed to open the hand histories in {}: {}", dir, e);
+                HandArchive::default()
+            }),
+            None => HandArchive::default(),
+        };
+
+        Self::with_stores(
+            game_config,
+            Arc::new(Mutex::new(player_stats)),
+            Arc::new(Mutex::new(hand_archive)),
+        )
+    }
+
+    /// A table keeping its players' stats and hand histories in stores it shares with other
+    /// tables, e.g. those of a fast-fold pool
+    pub fn with_stores(
+        mut game_config: GameConfig,
+        player_stats: Arc<Mutex<PlayerStatsStore>>,
+        hand_archive: Arc<Mutex<HandArchive>>,
+    ) -> Self {
+        if let Some(days) = game_config
+            .pseudonymize_after_days
+            .filter(|days| !valid_days(*days))
+        {
+            error!("Keeping the names: {} is not a number of days", days);
+            game_config.pseudonymize_after_days = None;
+        }
+        let session_seed = game_config.session_seed.unwrap_or_else(rand::random);
+        info!("Session seed {}", session_seed);
+        let table_id = game_config
+            .table_id
+            .clone()
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        let session_id = uuid::Uuid::new_v4().to_string();
+        info!("Table {}, session {}", table_id, session_id);
+        let webhooks = Webhooks::new(
+            game_config.webhooks.clone(),
+            game_config.big_pot_alert,
+            game_config.locale,
+            &table_id,
+            &session_id,
+        );
+
+        let clock = TournamentClock::new(game_config.blind_levels.clone());
+
+        // Persistence and webhooks follow the table like any other sink. The stores only
+        // care about finished hands, recorded by the store writer.
+        let mut events = EventBus::default();
+        let stores = StoreWriter::new();
+        let writer = stores.clone();
+        let stats = player_stats.clone();
+        let archive = hand_archive.clone();
+        events.subscribe(Arc::new(move |event| {
+            if !matches!(event, TableEvent::HandComplete(_)) {
+                return;
+            }
+            let (event, stats, archive) = (event.clone(), stats.clone(), archive.clone());
+            writer.queue(move || {
+                if let Ok(mut stats) = stats.lock() {
+                    stats.on_event(&event);
+                }
+                if let Ok(mut archive) = archive.lock() {
+                    archive.on_event(&event);
+                }
+            });
+        }));
+        if !game_config.webhooks.is_empty() {
+            events.subscribe(Arc::new(move |event| webhooks.on_event(event)));
+        }
+
         Self {
             players: HashMap::new(),
             seats: HashMap::new(),
             game_state: None,
-            websocket_server: None,
-            game_config: config.unwrap_or_default(),
+            hand_seats: Vec::new(),
+            blind_seats: None,
+            events,
+            handle: None,
+            game_config,
             dealer_seat: 1,
             game_running: false,
+            player_stats,
+            hand_archive,
+            stores,
+            win_streak: None,
+            hands_dealt: 0,
+            session_seed,
+            table_id,
+            session_id,
+            pending_insurance: None,
+            bots: HashMap::new(),
+            clock,
+            clock_status: None,
+            timers_paused_at: None,
+            hand_rake: HandRake::default(),
+            table_rake: TableRake::default(),
+            departed: HashMap::new(),
+            pseudonymized_at: None,
+            entries: 0,
         }
     }
 
+    /// Let the server play its bots once it is shared behind `handle`
+    pub fn attach(&mut self, handle: Weak<RwLock<GameServer>>) {
+        self.handle = Some(handle);
+    }
+
+    /// Rake and jackpot drops taken since the server started
     #[allow(dead_code)]
-    pub fn new_with_websocket(config: Option<GameConfig>, ws_server: Arc<WebSocketServer>) -> Self {
-        let mut server = Self::new(config);
-        server.websocket_server = Some(ws_server);
-        server
+    pub fn table_rake(&self) -> TableRake {
+        self.table_rake.clone()
+    }
+
+    /// Follow everything that happens at the table, from now on
+    pub fn subscribe(&mut self, subscriber: Subscriber) {
+        self.events.subscribe(subscriber);
     }
 
+    /// Where to record the connection of `client_id`, when `GameConfig.session_recording_dir`
+    /// is set
+    pub fn session_recorder(&self, client_id: &str) -> Option<SessionRecorder> {
+        let dir = self.game_config.session_recording_dir.as_ref()?;
+        SessionRecorder::open(
+            dir.as_ref(),
+            &self.session_id,
+            client_id,
+            &self.table_id,
+            self.session_seed,
+        )
+        .map_err(|e| error!("Failed to record the session of {}: {}", client_id, e))
+        .ok()
+    }
+
+    /// Register `player_id` as `name`. A disconnected player who comes back on a new
+    /// connection with the `reconnect_token` they were given gets their seat and stack back;
+    /// without it their name stays taken. So does the name of an account of the hand archive
+    /// in later sessions: its hands are exported by name.
     pub async fn register_player(
         &mut self,
         name: &str,
         player_id: &str,
+        reconnect_token: Option<&str>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let account_token = self.account_token(name, player_id, reconnect_token)?;
+        self.add_player(name, player_id, reconnect_token, account_token)
+            .await
+    }
+
+    /// The reconnect token the hand archive's account `name` was opened with, when
+    /// `player_id` presents it. None when the archive does not know the name or `player_id`
+    /// already goes by it, and `NameTaken` for anyone else.
+    fn account_token(
+        &self,
+        name: &str,
+        player_id: &str,
+        reconnect_token: Option<&str>,
+    ) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        if self.players.get(player_id).is_some_and(|p| p.name == name) {
+            return Ok(None);
+        }
+        let archive = self
+            .hand_archive
+            .lock()
+            .map_err(|_| "The hand archive is unavailable")?;
+        match archive.account_token(name) {
+            Some(token) if Some(token) == reconnect_token => Ok(Some(token.to_string())),
+            Some(_) => Err(ClientError::NameTaken(name.to_string()).into()),
+            None => Ok(None),
+        }
+    }
+
+    /// Open the hand archive's account of `name` with `token` the first time it registers
+    fn open_account(&self, name: &str, token: &str) {
+        let archive = self.hand_archive.clone();
+        let (name, token) = (name.to_string(), token.to_string());
+        self.stores.queue(move || {
+            if let Ok(mut archive) = archive.lock() {
+                if let Err(e) = archive.open_account(&name, &token) {
+                    error!("Failed to open the account of {}: {}", name, e);
+                }
+            }
+        });
+    }
+
+    /// Register `player_id` as `name`, given `account_token` as their reconnect token when
+    /// they take back the name of an account. Bots and players without a connection are
+    /// registered here directly.
+    async fn add_player(
+        &mut self,
+        name: &str,
+        player_id: &str,
+        reconnect_token: Option<&str>,
+        account_token: Option<String>,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        // Check if player already exists (reconnection)
-        if let Some(existing_player) = self.players.get_mut(player_id) {
+        let returning = self
+            .players
+            .values()
+            .find(|p| p.name == name && !p.connected && p.id != player_id)
+            .map(|p| {
+                (
+                    p.id.clone(),
+                    Some(p.reconnect_token.as_str()) == reconnect_token,
+                )
+            });
+        if let Some((_, false)) = returning {
+            return Err(ClientError::NameTaken(name.to_string()).into());
+        }
+        let returning = returning.map(|(old_id, _)| old_id);
+        if let Some(mut player) = returning.and_then(|old_id| self.players.remove(&old_id)) {
+            if let Some(seat) = player.seat {
+                self.seats.insert(seat, player_id.to_string());
+            }
+            if let Some((ref mut winner, _)) = self.win_streak {
+                if *winner == player.id {
+                    *winner = player_id.to_string();
+                }
+            }
+            player.id = player_id.to_string();
+            player.connected = true;
+            player.disconnected_at = None;
+            self.players.insert(player_id.to_string(), player);
+            info!("Player {} reconnected with ID {}", name, player_id);
+        } else if let Some(existing_player) = self.players.get_mut(player_id) {
+            // Check if player already exists (reconnection)
             existing_player.connected = true;
+            existing_player.disconnected_at = None;
             existing_player.name = name.to_string();
+            match account_token {
+                Some(token) => existing_player.reconnect_token = token,
+                None => {
+                    let token = existing_player.reconnect_token.clone();
+                    self.open_account(name, &token);
+                }
+            }
             info!("Player {} reconnected with ID {}", name, player_id);
         } else {
             // Check if name is already taken by another connected player
             let name_taken = self.players.values().any(|p| p.name == name && p.connected);
 
             if name_taken {
-                return Err(format!("Name '{}' is already taken", name).into());
+                return Err(ClientError::NameTaken(name.to_string()).into());
             }
 
-            let player = GamePlayer::new(
+            let mut player = GamePlayer::new(
                 player_id.to_string(),
                 name.to_string(),
                 self.game_config.default_stack_size,
             );
+            match account_token {
+                Some(token) => player.reconnect_token = token,
+                None => self.open_account(name, &player.reconnect_token),
+            }
 
             self.players.insert(player_id.to_string(), player);
             info!("New player {} registered with ID {}", name, player_id);
         }
 
-        self.broadcast_game_state().await;
+        if let Some(player) = self.players.get(player_id) {
+            self.events.publish(TableEvent::Registered {
+                player_id: player_id.to_string(),
+                message: RegisteredMessage {
+                    name: player.name.clone(),
+                    reconnect_token: player.reconnect_token.clone(),
+                },
+            });
+        }
+        self.broadcast_game_state();
         Ok(())
     }
 
+    /// Reconnect token of the player registered as `name`
+    pub(crate) fn reconnect_token(&self, name: &str) -> Option<String> {
+        self.players
+            .values()
+            .find(|p| p.name == name)
+            .map(|p| p.reconnect_token.clone())
+    }
+
     pub async fn seat_player(
         &mut self,
         player_id: &str,
         seat: u8,
     ) -> Result<(), Box<dyn std::error::Error>> {
         if seat < 1 || seat > self.game_config.max_players {
-            return Err(format!("Invalid seat number: {}", seat).into());
+            return Err(ClientError::InvalidSeat(seat).into());
         }
 
-        if self.seats.contains_key(&seat) {
-            return Err(format!("Seat {} is already occupied", seat).into());
+        // The seat of a player who moved on stays theirs until the hand is over
+        if self.seats.contains_key(&seat) || self.departed.contains_key(&seat) {
+            return Err(ClientError::SeatOccupied(seat).into());
         }
 
-        let player = self.players.get_mut(player_id).ok_or("Player not found")?;
+        let registration_open = self.registration_open();
+        let tournament = self.clock.is_some();
+        let player = self
+            .players
+            .get_mut(player_id)
+            .ok_or(ClientError::PlayerNotFound)?;
+
+        // Seated players can still move once late registration has closed
+        if player.seat.is_none() && !registration_open {
+            return Err(ClientError::RegistrationClosed.into());
+        }
 
         // Remove player from current seat if they have one
         if let Some(current_seat) = player.seat {
             self.seats.remove(&current_seat);
         }
 
+        let joined = player.seat.is_none();
+        // A tournament player buys in when they first take a seat, and again with a new stack
+        // once they have lost theirs
+        let buys_in = tournament && joined && (player.entries == 0 || player.chips <= 0.0);
+        if buys_in {
+            player.entries += 1;
+            player.chips = self.game_config.default_stack_size;
+            player.starting_session_chips = player.chips;
+            player.bounty = self.game_config.bounty;
+        }
         player.seat = Some(seat);
+        // Joining or moving once the session has started costs a big blind, except in a
+        // tournament where the players are dealt straight in
+        player.owes_blind = self.hands_dealt > 0 && !tournament;
         self.seats.insert(seat, player_id.to_string());
 
         info!("Player {} took seat {}", player.name, seat);
-        self.broadcast_game_state().await;
+        if buys_in && player.entries > 1 {
+            info!("Player {} re-entered", player.name);
+        }
+        if joined {
+            self.events.publish(TableEvent::PlayerJoined {
+                seat,
+                name: player.name.clone(),
+                chips: player.chips,
+            });
+        }
+        if buys_in {
+            self.entries += 1;
+            self.broadcast_clock();
+        }
+        self.broadcast_game_state();
+        Ok(())
+    }
+
+    /// Take a player away to another table, with their chips. Players dealt into the hand
+    /// can only leave once they have folded; their seat stays empty until the hand is over.
+    pub fn transfer_out(&mut self, player_id: &str) -> Result<GamePlayer, ClientError> {
+        let seat = self
+            .players
+            .get(player_id)
+            .ok_or(ClientError::PlayerNotFound)?
+            .seat;
+        let in_hand = seat.filter(|&seat| self.game_running && self.hand_index(seat).is_some());
+        if let Some(seat) = in_hand {
+            if !self.is_player_folded(seat) {
+                return Err(ClientError::StillInHand);
+            }
+        }
+
+        let mut player = self
+            .players
+            .remove(player_id)
+            .ok_or(ClientError::PlayerNotFound)?;
+        if let Some(seat) = seat {
+            self.seats.remove(&seat);
+        }
+        if let Some(seat) = in_hand {
+            // The chips the player put in front of them stay in this table's pot
+            let folded = self
+                .hand_index(seat)
+                .and_then(|index| self.game_state.as_ref()?.players_state.get(index));
+            if let Some(ps) = folded {
+                player.chips = ps.stake + ps.held_back;
+            }
+            self.departed.insert(seat, player.clone());
+        }
+        player.seat = None;
+        info!("Player {} left the table for another", player.name);
+        self.broadcast_game_state();
+        Ok(player)
+    }
+
+    /// Seat a player coming from another table with their chips. They have paid their way
+    /// in at the other table, so they owe no blind.
+    pub fn transfer_in(&mut self, mut player: GamePlayer, seat: u8) -> Result<(), ClientError> {
+        if seat < 1 || seat > self.game_config.max_players {
+            return Err(ClientError::InvalidSeat(seat));
+        }
+        if self.seats.contains_key(&seat) || self.departed.contains_key(&seat) {
+            return Err(ClientError::SeatOccupied(seat));
+        }
+        if self.players.values().any(|p| p.name == player.name) {
+            return Err(ClientError::NameTaken(player.name));
+        }
+
+        player.seat = Some(seat);
+        player.owes_blind = false;
+        player.straddle = false;
+        info!("Player {} moved in to seat {}", player.name, seat);
+        self.seats.insert(seat, player.id.clone());
+        self.players.insert(player.id.clone(), player);
+        self.broadcast_game_state();
         Ok(())
     }
 
+    pub fn player(&self, player_id: &str) -> Option<&GamePlayer> {
+        self.players.get(player_id)
+    }
+
+    /// Ids of the seated players
+    pub fn seated_players(&self) -> Vec<String> {
+        self.seats.values().cloned().collect()
+    }
+
+    /// Ids of the seated players who folded the current hand
+    #[allow(dead_code)]
+    pub fn folded_players(&self) -> Vec<String> {
+        self.seats
+            .iter()
+            .filter(|(&seat, _)| self.is_player_folded(seat))
+            .map(|(_, player_id)| player_id.clone())
+            .collect()
+    }
+
+    pub fn game_running(&self) -> bool {
+        self.game_running
+    }
+
     pub async fn start_game(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         if self.seats.len() < 2 {
-            return Err("Need at least 2 players to start the game".into());
+            return Err(ClientError::NotEnoughPlayers.into());
         }
 
-        let seated_players = self.seats.len() as u64;
-        let button_player_id = self
-            .seats
-            .get(&self.dealer_seat)
-            .ok_or("No player at dealer seat")?;
-        let _button_player = self
-            .players
-            .get(button_player_id)
-            .ok_or("Button player not found")?;
-
-        // Create deck and initialize game state
-        let deck = Card::collect();
-
-        let game_state = State::from_deck(
-            seated_players,
-            (self.dealer_seat - 1) as u64, // Convert to 0-indexed
-            self.game_config.small_blind,
-            self.game_config.big_blind,
-            self.game_config.default_stack_size,
-            deck,
-            false, // verbose
+        if self.pending_insurance.is_some() {
+            return Err(ClientError::InsurancePending.into());
+        }
+
+        // Tournament tables deal no hands during breaks, the first hand starts the clock
+        if let Some(ref mut clock) = self.clock {
+            if clock.paused() {
+                return Err(ClientError::ClockPaused.into());
+            }
+            if clock.status(now()).on_break {
+                return Err(ClientError::OnBreak.into());
+            }
+            clock.start(now());
+        }
+        self.tick_clock().await;
+        let (small_blind, big_blind, ante) = self.blinds();
+
+        // Kill pot: the blinds go up after a player wins two pots in a row
+        let blinds_multiplier = match (self.game_config.rules.kill_pot_multiplier, &self.win_streak)
+        {
+            (Some(multiplier), Some((_, streak))) if *streak >= 2 => multiplier,
+            _ => 1.0,
+        };
+
+        // Hand #N of the session can be dealt again from the session seed alone
+        let seed = hand_seed(self.session_seed, self.hands_dealt);
+        let deck = shuffled_deck(seed);
+        info!("Hand #{} seed {}", self.hands_dealt, seed);
+
+        // All-in equities are sent with the hand results
+        let rules = TableRules {
+            report_all_in_equities: true,
+            ..self.game_config.rules
+        };
+        self.hands_dealt += 1;
+        // Without an ante there is nothing to play a bomb pot for
+        let bomb_pot = rules.bomb_pot_ante > 0.0
+            && matches!(rules.bomb_pot_every, Some(n) if n > 0 && self.hands_dealt.is_multiple_of(n as u64));
+
+        let (hand_seats, dead_blinds) = self.hand_seating(bomb_pot);
+        let seated_players = hand_seats.len() as u64;
+        let button = hand_seats
+            .iter()
+            .position(|&seat| seat >= self.dealer_seat)
+            .unwrap_or(0) as u64;
+        let small_blind = small_blind * blinds_multiplier;
+        let big_blind = big_blind * blinds_multiplier;
+        let ante_only = ante > 0.0 && self.game_config.ante_structure == AnteStructure::AnteOnly;
+        let blind_seats = (!bomb_pot && !ante_only).then(|| {
+            let seat = |offset: u64| hand_seats[((button + offset) % seated_players) as usize];
+            (seat(1), seat(2))
+        });
+
+        let opening = if bomb_pot {
+            HoldemOpening::BombPot {
+                ante: rules.bomb_pot_ante,
+                double_board: rules.bomb_pot_double_board,
+            }
+        } else if ante_only {
+            HoldemOpening::AnteOnly { ante }
+        } else {
+            HoldemOpening::Blinds {
+                ante,
+                big_blind_ante: self.game_config.ante_structure == AnteStructure::BigBlind,
+                ante_first: self.game_config.ante_first,
+            }
+        };
+        let game_state = State::start_hand(
+            &HoldemConfig {
+                small_blind,
+                big_blind,
+                opening,
+                rules,
+            },
+            HandSetup {
+                n_players: seated_players,
+                button,
+                stacks: self.hand_stacks(&hand_seats),
+                deck,
+                seed,
+            },
         )
-        .map_err(|e| format!("Failed to create game state: {:?}", e))?;
+        .and_then(|state| {
+            let index = |seat: &u8| hand_seats.iter().position(|s| s == seat).unwrap_or(0) as u64;
+            let owing: Vec<u64> = hand_seats
+                .iter()
+                .filter(|seat| !bomb_pot && self.owes_blind(**seat))
+                .map(index)
+                .collect();
+            if owing.is_empty() {
+                return Ok(state);
+            }
+            state
+                .owe_blinds(owing)
+                .and_then(|state| {
+                    dead_blinds
+                        .iter()
+                        .try_fold(state, |state, seat| state.post_owed_blind(index(seat)))
+                })
+                .map_err(|e| e.to_string())
+        })
+        .map(|state| self.post_straddles(state, &hand_seats, big_blind, bomb_pot))
+        .map(|state| state.with_clock(None))
+        .map(|state| {
+            state.with_ids(
+                None,
+                Some(self.table_id.clone()),
+                Some(self.session_id.clone()),
+            )
+        })
+        .map_err(|e| format!("Failed to create game state: {}", e))?;
+        info!("Hand id {}", game_state.hand_id);
+
+        if blinds_multiplier != 1.0 {
+            info!("Kill pot: blinds multiplied by {}", blinds_multiplier);
+        }
+        if bomb_pot {
+            info!("Bomb pot: everybody antes {}", rules.bomb_pot_ante);
+        }
+        for seat in &dead_blinds {
+            info!("Seat {} posts a dead blind of {}", seat, big_blind);
+        }
+
+        // Players dealt in without paying the blind they owe still owe it, straddles are asked
+        // for every hand
+        let pending = game_state.pending_blinds();
+        for (index, seat) in hand_seats.iter().enumerate() {
+            if let Some(player) = self.seats.get(seat).and_then(|id| self.players.get_mut(id)) {
+                player.owes_blind = pending.contains(&(index as u64));
+                player.straddle = false;
+            }
+        }
+        self.hand_seats = hand_seats;
+        self.blind_seats = blind_seats;
 
+        let all_in = game_state.final_state;
         self.game_state = Some(game_state);
         self.game_running = true;
 
         info!("Game started with {} players", seated_players);
-        self.broadcast_game_state().await;
-        self.broadcast_current_player_turn().await;
+        self.broadcast_game_state();
+
+        // A bomb pot ante can put everybody all-in before any action
+        if all_in {
+            return self.handle_game_end().await;
+        }
+
+        self.send_hand_strength_hints();
+        self.broadcast_current_player_turn();
+        self.request_bot_action();
 
         Ok(())
     }
@@ -199,17 +1075,30 @@ impl GameServer {
         player_id: &str,
         action: PlayerAction,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        let (game_action, player_name) = {
-            let game_state = self.game_state.as_ref().ok_or("No active game")?;
+        let (next, player_name) = {
+            let game_state = self.game_state.as_ref().ok_or(ClientError::NoActiveGame)?;
 
-            let player = self.players.get(player_id).ok_or("Player not found")?;
+            let player = self
+                .players
+                .get(player_id)
+                .ok_or(ClientError::PlayerNotFound)?;
 
-            let seat = player.seat.ok_or("Player is not seated")?;
+            let seat = player.seat.ok_or(ClientError::NotSeated)?;
 
             // Check if it's the player's turn
-            let current_player_seat = (game_state.current_player + 1) as u8; // Convert to 1-indexed
-            if seat != current_player_seat {
-                return Err("Not your turn".into());
+            let actor = game_state.current_actor();
+            if actor.map(|p| self.hand_seat(p)) != Some(seat) {
+                return Err(ClientError::NotYourTurn.into());
+            }
+
+            // Raises outside the advertised range are refused rather than adjusted
+            if let PlayerAction::Raise(amount) | PlayerAction::Bet(amount) = action {
+                let (min, max) = game_state
+                    .legal_bet_range()
+                    .ok_or(ClientError::RaiseNotAllowed)?;
+                if amount.is_nan() || amount < min - 1e-9 || amount > max + 1e-9 {
+                    return Err(ClientError::InvalidRaise(min, max).into());
+                }
             }
 
             // Convert PlayerAction to game logic Action
@@ -221,244 +1110,1732 @@ impl GameServer {
                 PlayerAction::Bet(amount) => Action::new(ActionEnum::BetRaise, amount),
             };
 
-            (game_action, player.name.clone())
-        };
-
-        // Apply action to game state
-        if let Some(game_state) = self.game_state.take() {
-            let new_state = game_state.apply_action(game_action);
-            self.game_state = Some(new_state);
-        }
-
-        // Sync player chips from game state
-        self.sync_player_chips_from_game_state();
+            let explanation = game_state.explain_action(game_action);
+            if !explanation.legal {
+                info!("Player {}: {}", player.name, explanation.reason);
+            }
 
-        info!("Player {} performed action: {:?}", player_name, action);
+            let next = game_state.handle_action(actor.unwrap_or_default(), game_action)?;
+            (next, player.name.clone())
+        };
 
-        self.broadcast_game_state().await;
+        let record = self.play(move |_| next).await?;
 
-        // Check if game ended
-        if let Some(ref state) = self.game_state {
-            if state.final_state {
-                self.handle_game_end().await?;
-            } else {
-                self.broadcast_current_player_turn().await;
+        if let Some(ref record) = record {
+            if let Some(coercion) = record.coercion {
+                info!(
+                    "Player {} asked for {:?}, the engine played {:?} ({:?})",
+                    player_name, record.requested, record.action, coercion
+                );
             }
         }
-
+        let think_time = record.and_then(|record| record.think_time).unwrap_or(0.0);
+        info!(
+            "Player {} performed action: {:?} after {:.1}s",
+            player_name, action, think_time
+        );
         Ok(())
     }
 
-    pub async fn player_disconnected(&mut self, player_id: &str) {
-        if let Some(player) = self.players.get_mut(player_id) {
-            player.connected = false;
-            info!("Player {} disconnected", player.name);
-            self.broadcast_game_state().await;
-        }
+    /// Seats dealt into the next hand, and the seats posting a dead big blind, see
+    /// `missed_blind_seating`. Bomb pots deal everybody in for the ante.
+    fn hand_seating(&self, bomb_pot: bool) -> (Vec<u8>, Vec<u8>) {
+        let mut seats: Vec<u8> = self.seats.keys().copied().collect();
+        seats.sort();
+
+        let button = seats
+            .iter()
+            .position(|&seat| seat >= self.dealer_seat)
+            .unwrap_or(0);
+        let owing: Vec<bool> = seats
+            .iter()
+            .map(|seat| !bomb_pot && self.owes_blind(*seat))
+            .collect();
+        let (dealt, dead) = missed_blind_seating(owing, button, self.game_config.missed_blinds);
+        (
+            dealt.iter().map(|&index| seats[index]).collect(),
+            dead.iter().map(|&index| seats[index]).collect(),
+        )
     }
 
-    fn sync_player_chips_from_game_state(&mut self) {
-        if let Some(ref state) = self.game_state {
-            for (seat, player_id) in &self.seats {
-                if let Some(player) = self.players.get_mut(player_id) {
-                    let player_state_index = (*seat - 1) as usize;
-                    if let Some(player_state) = state.players_state.get(player_state_index) {
-                        player.chips = player_state.stake + player_state.bet_chips;
-                    }
-                }
-            }
-        }
+    /// Chips the players at `seats` bring to the hand: their own at a tournament table, where
+    /// they play down to nothing, `GameConfig.default_stack_size` at a cash table
+    fn hand_stacks(&self, seats: &[u8]) -> Vec<f64> {
+        let stack = self.game_config.default_stack_size;
+        seats
+            .iter()
+            .map(|seat| match self.clock {
+                Some(_) => self
+                    .seats
+                    .get(seat)
+                    .and_then(|id| self.players.get(id))
+                    .map_or(stack, |p| p.chips),
+                None => stack,
+            })
+            .collect()
     }
 
-    async fn handle_game_end(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        if let Some(ref state) = self.game_state {
-            // Calculate winnings and update player chips
-            for (seat, player_id) in &self.seats {
-                if let Some(player) = self.players.get_mut(player_id) {
-                    let player_state_index = (*seat - 1) as usize;
-                    if let Some(player_state) = state.players_state.get(player_state_index) {
-                        let total_reward = player_state.stake + player_state.reward;
-                        player.chips = total_reward.max(0.0);
-                    }
+    /// Whether the player at `seat` missed the big blind
+    fn owes_blind(&self, seat: u8) -> bool {
+        self.seats
+            .get(&seat)
+            .and_then(|id| self.players.get(id))
+            .is_some_and(|p| p.owes_blind)
+    }
+
+    /// Post the chain of straddles: each player who asked for it doubles the bet of the
+    /// previous one, until a player declines, the blinds are reached or the chain is full
+    fn post_straddles(
+        &self,
+        mut state: State,
+        hand_seats: &[u8],
+        big_blind: f64,
+        bomb_pot: bool,
+    ) -> State {
+        let n_players = hand_seats.len() as u64;
+        let mut player = match self.game_config.straddles {
+            _ if bomb_pot => return state,
+            Straddles::Off => return state,
+            Straddles::Utg => (state.button + 3) % n_players,
+            Straddles::Button => state.button,
+        };
+        let mut amount = 2.0 * big_blind;
+
+        for _ in 0..self.game_config.max_straddles {
+            let seat = hand_seats[player as usize];
+            let wants_to = self
+                .seats
+                .get(&seat)
+                .and_then(|id| self.players.get(id))
+                .map(|p| p.straddle)
+                .unwrap_or(false);
+            if !wants_to {
+                break;
+            }
+            match state.post_straddle(player, amount) {
+                Ok(next) => state = next,
+                Err(e) => {
+                    info!("Seat {} cannot straddle: {:?}", seat, e);
+                    break;
                 }
             }
-
-            self.broadcast_hand_winnings().await;
+            info!("Seat {} straddles to {}", seat, amount);
+            player = (player + 1) % n_players;
+            amount *= 2.0;
         }
+        state
+    }
 
-        self.game_running = false;
-        self.game_state = None;
-
-        // Rotate dealer
-        self.rotate_dealer();
+    /// Index in the current hand of the player at `seat`, `None` when they were not dealt in
+    fn hand_index(&self, seat: u8) -> Option<usize> {
+        self.hand_seats.iter().position(|&s| s == seat)
+    }
 
-        info!("Game ended");
-        Ok(())
+    /// Seat of the player at `index` in the current hand
+    fn hand_seat(&self, index: u64) -> u8 {
+        self.hand_seats.get(index as usize).copied().unwrap_or(0)
     }
 
-    fn rotate_dealer(&mut self) {
-        let seated_players: Vec<u8> = self.seats.keys().copied().collect();
-        if seated_players.is_empty() {
-            return;
+    /// Small blind, big blind and ante of the next hand: the current level's at a tournament
+    /// table, the configured ones otherwise
+    fn blinds(&self) -> (f64, f64, f64) {
+        match self.clock {
+            Some(ref clock) => {
+                let level = clock.level(now());
+                (level.small_blind, level.big_blind, level.ante)
+            }
+            None => (
+                self.game_config.small_blind,
+                self.game_config.big_blind,
+                self.game_config.ante,
+            ),
         }
+    }
 
-        let mut sorted_seats = seated_players;
-        sorted_seats.sort();
-
-        if let Some(current_index) = sorted_seats.iter().position(|&s| s == self.dealer_seat) {
-            let next_index = (current_index + 1) % sorted_seats.len();
-            self.dealer_seat = sorted_seats[next_index];
-        } else {
-            self.dealer_seat = sorted_seats[0];
+    /// Whether new players can take a seat: until the end of the break after level
+    /// `GameConfig.late_registration_levels` at a tournament table, always otherwise
+    fn registration_open(&self) -> bool {
+        match (&self.clock, self.game_config.late_registration_levels) {
+            (Some(clock), Some(levels)) => clock.status(now()).level < levels,
+            _ => true,
         }
     }
 
-    async fn broadcast_game_state(&self) {
-        if let Some(ref ws_server) = self.websocket_server {
-            let mut players_info = HashMap::new();
-
-            for seat in 1..=self.game_config.max_players {
-                if let Some(player_id) = self.seats.get(&seat) {
-                    if let Some(player) = self.players.get(player_id) {
-                        let player_cards = self.get_player_cards(seat);
+    /// Buy-ins paid into the tournament, and what they pay the places of `GameConfig.payouts`
+    fn prize_pool(&self) -> (f64, Vec<f64>) {
+        prize_pool(&self.game_config, self.entries)
+    }
 
-                        let player_info = PlayerInfo {
-                            name: player.name.clone(),
-                            address: player.id.clone(),
-                            chips: player.chips,
-                            bet: self.get_player_bet(seat),
-                            in_game: player.connected && player.seat.is_some(),
-                            on_move: self.is_player_on_move(seat),
-                            folded: self.is_player_folded(seat),
-                            session_net_win_loss: player.chips - player.starting_session_chips,
-                            cards: player_cards,
-                        };
+    /// Count `entries` buy-ins in the prize pool, for a table of a tournament played over
+    /// several tables, where the players buy in once for all of them
+    pub fn set_entries(&mut self, entries: u32) {
+        if self.entries != entries {
+            self.entries = entries;
+            self.broadcast_clock();
+        }
+    }
 
-                        players_info.insert(seat.to_string(), player_info);
+    /// For each seat in the finished hand, the other seated players who won chips from the
+    /// slices of the pot that seat put chips in, with how much they won there. A side pot a
+    /// player could not win is not theirs to be knocked out of.
+    fn knockers(&self, state: &State) -> HashMap<u8, Vec<(String, f64)>> {
+        let awards = slice_awards(state, |ps| ps.folded_on.is_none());
+        let mut knockers: HashMap<u8, Vec<(String, f64)>> = HashMap::new();
+        for (index, seat) in self.hand_seats.iter().enumerate() {
+            let won_from = knockers.entry(*seat).or_default();
+            for (slice, slice_winners) in &awards {
+                // A slice nobody could win is handed back, not won
+                if slice.eligible.is_empty() || !slice.contributors.contains(&(index as u64)) {
+                    continue;
+                }
+                for &(winner, won) in slice_winners {
+                    let Some(player_id) = self
+                        .hand_seats
+                        .get(winner as usize)
+                        .filter(|_| winner as usize != index)
+                        .and_then(|winner_seat| self.seats.get(winner_seat))
+                    else {
+                        continue;
+                    };
+                    match won_from.iter_mut().find(|(id, _)| id == player_id) {
+                        Some((_, total)) => *total += won,
+                        None => won_from.push((player_id.clone(), won)),
                     }
                 }
             }
-
-            let community_cards = self.get_community_cards();
-            let pot = self.get_pot_size();
-
-            let game_state_msg = GameStateMessage {
-                game_started: self.game_running,
-                players: players_info,
-                community_cards,
-                pot,
-            };
-
-            ws_server.broadcast_game_state(game_state_msg).await;
         }
+        knockers
     }
 
-    async fn broadcast_current_player_turn(&self) {
-        if let Some(ref state) = self.game_state {
-            if let Some(ref ws_server) = self.websocket_server {
-                let current_seat = (state.current_player + 1) as u8;
-
-                if let Some(player_id) = self.seats.get(&current_seat) {
-                    if let Some(player) = self.players.get(player_id) {
-                        let player_cards = self.get_player_cards(current_seat);
-
-                        let on_move_msg = OnMoveMessage {
-                            seat: current_seat,
-                            address: player.id.clone(),
-                            name: player.name.clone(),
-                            chips: player.chips,
-                            bet: self.get_player_bet(current_seat),
-                            on_move: true,
-                            in_game: true,
-                            folded: false,
-                            cards: player_cards,
-                            max_bet_on_table: self.get_max_bet(),
-                            can_check: self.can_player_check(current_seat),
-                            call_amount: self.get_call_amount(current_seat),
-                            min_bet_to_total_value: state.min_bet,
-                            min_raise_to_total_bet: self.get_min_raise_amount(current_seat),
-                            pot_size: state.pot,
-                        };
-
-                        ws_server.broadcast_on_move(on_move_msg).await;
-                    }
+    /// Free the seats of the tournament players who lost their stack in the hand, whose
+    /// bounties go to their `knockers`, see `knockers`. They can buy in again while late
+    /// registration is open.
+    fn unseat_busted(&mut self, knockers: &HashMap<u8, Vec<(String, f64)>>) {
+        let busted: Vec<(u8, String)> = self
+            .seats
+            .iter()
+            .filter(|(_, id)| self.players.get(*id).is_some_and(|p| p.chips <= 0.0))
+            .map(|(seat, id)| (*seat, id.clone()))
+            .collect();
+        for (seat, player_id) in busted {
+            self.seats.remove(&seat);
+            let Some(player) = self.players.get_mut(&player_id) else {
+                continue;
+            };
+            player.seat = None;
+            player.straddle = false;
+            let bounty = std::mem::take(&mut player.bounty);
+            let name = player.name.clone();
+            info!("Player {} busted out of seat {}", name, seat);
+            self.events.publish(TableEvent::PlayerLeft {
+                seat,
+                name: name.clone(),
+                chips: 0.0,
+                session_net: -player.starting_session_chips,
+            });
+            match knockers.get(&seat) {
+                Some(knockers) if bounty > 0.0 && !knockers.is_empty() => {
+                    self.pay_bounty(seat, name, bounty, knockers)
                 }
+                _ => {}
             }
         }
     }
 
-    async fn broadcast_hand_winnings(&self) {
-        if let Some(ref ws_server) = self.websocket_server {
-            let community_cards = self.get_community_cards();
-            let winnings = self.calculate_winnings();
-
-            let winnings_msg = HandWinningsMessage {
-                community_cards,
-                winnings,
+    /// Split the bounty of a busted player between the winners of the pots they put chips in,
+    /// in proportion to the chips each of them won there: half of each share is paid to the
+    /// winner and half goes on their own head
+    fn pay_bounty(&mut self, seat: u8, name: String, bounty: f64, knockers: &[(String, f64)]) {
+        let total: f64 = knockers.iter().map(|(_, won)| won).sum();
+        let mut winners = Vec::new();
+        for (player_id, won) in knockers {
+            let share = bounty * won / total;
+            let Some(winner) = self.players.get_mut(player_id) else {
+                continue;
             };
-
-            ws_server.broadcast_winnings(winnings_msg).await;
+            winner.bounties_won += share / 2.0;
+            winner.bounty += share / 2.0;
+            info!(
+                "Player {} knocked out {} for a bounty of {}",
+                winner.name, name, share
+            );
+            winners.push(BountyInfo {
+                name: winner.name.clone(),
+                won: share / 2.0,
+                bounty: winner.bounty,
+            });
         }
+        self.events.publish(TableEvent::Knockout(KnockoutMessage {
+            seat,
+            name,
+            bounty,
+            winners,
+        }));
     }
 
-    fn get_player_cards(&self, seat: u8) -> Vec<CardInfo> {
-        if let Some(ref state) = self.game_state {
-            let player_index = (seat - 1) as usize;
-            if let Some(player_state) = state.players_state.get(player_index) {
-                return vec![
-                    CardInfo {
-                        suit: player_state.hand.0.suit as u8,
-                        rank: player_state.hand.0.rank as u8 + 2, // Convert to 2-14 range
-                    },
-                    CardInfo {
-                        suit: player_state.hand.1.suit as u8,
-                        rank: player_state.hand.1.rank as u8 + 2,
-                    },
-                ];
+    /// Follow the tournament clock: stop the action timers during breaks and pauses, and tell
+    /// the clients when a level or a break starts. Called by `tick`.
+    pub async fn tick_clock(&mut self) {
+        let Some(status) = self.clock_change() else {
+            return;
+        };
+
+        match (status.on_break || status.paused, self.timers_paused_at) {
+            (true, None) => self.timers_paused_at = Some(now()),
+            (false, Some(paused_at)) => {
+                // The player to act gets back the time they had left
+                self.timers_paused_at = None;
+                if let Some(turn_started) = self.game_state.as_mut().and_then(|s| s.clock.as_mut())
+                {
+                    *turn_started += now() - paused_at;
+                }
             }
+            _ => {}
         }
-        Vec::new()
-    }
 
-    fn get_community_cards(&self) -> Vec<CardInfo> {
-        if let Some(ref state) = self.game_state {
-            return state
-                .public_cards
-                .iter()
-                .map(|card| CardInfo {
-                    suit: card.suit as u8,
-                    rank: card.rank as u8 + 2,
-                })
-                .collect();
+        let last = self.clock_status.replace(status);
+        let new_level =
+            last.is_none_or(|last| last.level != status.level || last.on_break != status.on_break);
+        if new_level {
+            if status.on_break {
+                info!("Break after level {}", status.level + 1);
+            } else {
+                let (small_blind, big_blind, ante) = self.blinds();
+                info!(
+                    "Level {}: blinds {}/{}, ante {}",
+                    status.level + 1,
+                    small_blind,
+                    big_blind,
+                    ante
+                );
+                if self.game_config.late_registration_levels == Some(status.level) {
+                    info!("Late registration closed");
+                }
+            }
+        }
+        if new_level || last.is_some_and(|last| last.paused != status.paused) {
+            self.broadcast_clock();
         }
-        Vec::new()
     }
 
-    fn get_player_bet(&self, seat: u8) -> f64 {
-        if let Some(ref state) = self.game_state {
-            let player_index = (seat - 1) as usize;
-            if let Some(player_state) = state.players_state.get(player_index) {
-                return player_state.bet_chips;
-            }
-        }
-        0.0
+    /// The status of the running clock when it moved to another level, in or out of a break
+    /// or a pause since the last tick, or when the action timers still have to follow it
+    fn clock_change(&self) -> Option<ClockStatus> {
+        let status = match self.clock {
+            Some(ref clock) if clock.started() => clock.status(now()),
+            _ => return None,
+        };
+        let stopped = status.on_break || status.paused;
+        let changed = self.clock_status.is_none_or(|last| {
+            (last.level, last.on_break, last.paused)
+                != (status.level, status.on_break, status.paused)
+        });
+        (changed || stopped != self.timers_paused_at.is_some()).then_some(status)
     }
 
-    fn get_pot_size(&self) -> f64 {
-        self.game_state.as_ref().map(|s| s.pot).unwrap_or(0.0)
+    /// Stop the tournament clock, e.g. to settle a dispute. No hand is dealt until it resumes
+    /// and the action timers stop with it.
+    #[allow(dead_code)]
+    pub async fn pause_clock(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.clock
+            .as_mut()
+            .ok_or("The table has no blind levels")?
+            .pause(now());
+        info!("Tournament clock paused");
+        self.tick_clock().await;
+        Ok(())
+    }
+
+    #[allow(dead_code)]
+    pub async fn resume_clock(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.clock
+            .as_mut()
+            .ok_or("The table has no blind levels")?
+            .resume(now());
+        info!("Tournament clock resumed");
+        self.tick_clock().await;
+        Ok(())
+    }
+
+    /// Play the levels of `clock`, the one clock of the tables of a multi-table tournament
+    pub fn follow_clock(&mut self, clock: &TournamentClock) {
+        self.clock = Some(clock.clone());
+    }
+
+    fn clock_message(&self) -> Option<TournamentClockMessage> {
+        let clock = self.clock.as_ref()?;
+        let now = now();
+        let status = clock.status(now);
+        let level_info = |index: usize| {
+            clock.levels().get(index).map(|level| BlindLevelInfo {
+                level: index + 1,
+                small_blind: level.small_blind,
+                big_blind: level.big_blind,
+                ante: level.ante,
+            })
+        };
+        Some(TournamentClockMessage {
+            level: level_info(status.level)?,
+            next_level: level_info(status.level + 1),
+            on_break: status.on_break,
+            paused: status.paused,
+            started: clock.started(),
+            seconds_remaining: status.remaining,
+            next_break_in: clock.next_break_in(now),
+            registration_open: self.registration_open(),
+            entries: self.entries,
+            prize_pool: self.prize_pool().0,
+            payouts: self.prize_pool().1,
+        })
+    }
+
+    fn broadcast_clock(&self) {
+        if let Some(clock) = self.clock_message() {
+            self.events.publish(TableEvent::TournamentClock(clock));
+        }
+    }
+
+    /// Follow the tournament clock and force the action of players who ran out of time or
+    /// stayed disconnected. Called periodically by the WebSocket server.
+    pub async fn tick(&mut self) {
+        self.tick_clock().await;
+        if let Err(e) = self.check_action_timeout().await {
+            error!("Failed to apply the default action: {}", e);
+        }
+        if let Err(e) = self.check_disconnect_protection().await {
+            error!("Failed to protect a disconnected player: {}", e);
+        }
+        self.pseudonymize_hands();
+    }
+
+    /// Whether `tick` has anything to do. It only reads the server, so that the periodic tick
+    /// can check it under a read lock and leave the table alone most of the time.
+    pub fn tick_due(&self) -> bool {
+        self.clock_change().is_some()
+            || self.timed_out_player().is_some()
+            || self.unprotected_player().is_some()
+            || self.pseudonymize_cutoff().is_some()
+    }
+
+    /// Give the other players of the hands older than `GameConfig.pseudonymize_after_days` a
+    /// pseudonym, at most every `PSEUDONYMIZE_EVERY` seconds. Called by `tick`, the files are
+    /// rewritten by the store writer rather than under the table's lock.
+    fn pseudonymize_hands(&mut self) {
+        let Some(cutoff) = self.pseudonymize_cutoff() else {
+            return;
+        };
+        self.pseudonymized_at = Some(now());
+        let archive = self.hand_archive.clone();
+        self.stores.queue(move || {
+            let Ok(mut archive) = archive.lock() else {
+                return;
+            };
+            match archive.pseudonymize_before(cutoff) {
+                Ok(0) => {}
+                Ok(hands) => info!("Pseudonymized the players of {} hands", hands),
+                Err(e) => error!("Failed to pseudonymize the hand histories: {}", e),
+            }
+        });
+    }
+
+    /// Time before which hands are to be pseudonymized, when it is time to
+    fn pseudonymize_cutoff(&self) -> Option<f64> {
+        let days = self.game_config.pseudonymize_after_days?;
+        let due = self
+            .pseudonymized_at
+            .is_none_or(|at| now() - at >= PSEUDONYMIZE_EVERY);
+        due.then(|| now() - days * 86_400.0)
+    }
+
+    /// The player to act once `GameConfig.action_timeout` has run out on them
+    fn timed_out_player(&self) -> Option<u64> {
+        let timeout = self.game_config.action_timeout?;
+        if self.pending_insurance.is_some() || self.timers_paused_at.is_some() {
+            return None;
+        }
+        let state = self.game_state.as_ref()?;
+        match (state.current_actor(), state.clock) {
+            (Some(player), Some(started)) if now() - started >= timeout => Some(player),
+            _ => None,
+        }
+    }
+
+    /// Check or fold for the player to act once `GameConfig.action_timeout` has run out.
+    /// Called by `tick`.
+    pub async fn check_action_timeout(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(player) = self.timed_out_player() else {
+            return Ok(());
+        };
+
+        let record = self
+            .play(|state| state.apply_default_action(DefaultPolicy::CheckElseFold))
+            .await?;
+
+        let action = record.map(|record| record.action.action);
+        let name = self
+            .seats
+            .get(&self.hand_seat(player))
+            .and_then(|player_id| self.players.get(player_id))
+            .map(|p| p.name.clone())
+            .unwrap_or_default();
+        info!("Player {} timed out, forced {:?}", name, action);
+        Ok(())
+    }
+
+    /// The disconnected player to act once `GameConfig.disconnect_grace` has run out on them
+    fn unprotected_player(&self) -> Option<String> {
+        let protection = self.game_config.disconnect_protection;
+        if protection == DisconnectProtection::Off
+            || self.pending_insurance.is_some()
+            || self.timers_paused_at.is_some()
+        {
+            return None;
+        }
+        let state = self.game_state.as_ref()?;
+        let player = state.current_actor()?;
+        let player_id = self.seats.get(&self.hand_seat(player))?;
+        let disconnected_at = self.players.get(player_id)?.disconnected_at?;
+        let waiting_since = state
+            .clock
+            .map_or(disconnected_at, |t| t.max(disconnected_at));
+        (now() - waiting_since >= self.game_config.disconnect_grace).then(|| player_id.clone())
+    }
+
+    /// Act for a disconnected player to act as told by `GameConfig.disconnect_protection`, once
+    /// `GameConfig.disconnect_grace` has run out since their turn came or they left, whichever
+    /// was last. Called by `tick`.
+    pub async fn check_disconnect_protection(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(player_id) = self.unprotected_player() else {
+            return Ok(());
+        };
+        let protection = self.game_config.disconnect_protection;
+
+        let record = match protection {
+            DisconnectProtection::AllIn => {
+                self.play(|state| state.apply_protected_all_in()).await?
+            }
+            _ => {
+                self.play(|state| state.apply_default_action(DefaultPolicy::Fold))
+                    .await?
+            }
+        };
+
+        let action = record.map(|record| record.action.action);
+        let name = self
+            .players
+            .get(&player_id)
+            .map(|p| p.name.clone())
+            .unwrap_or_default();
+        info!(
+            "Player {} is disconnected, {:?} protection forced {:?}",
+            name, protection, action
+        );
+        Ok(())
+    }
+
+    /// Advance the hand with `apply` and tell the clients what happens next. Returns the
+    /// record of the action applied.
+    async fn play(
+        &mut self,
+        apply: impl FnOnce(&State) -> State,
+    ) -> Result<Option<ActionRecord>, Box<dyn std::error::Error>> {
+        // Apply action to game state
+        let mut street_changed = false;
+        let mut record = None;
+        if let Some(game_state) = self.game_state.take() {
+            let new_state = apply(&game_state);
+            street_changed = new_state.stage != game_state.stage;
+            record = new_state.from_action.clone();
+            self.game_state = Some(new_state);
+        }
+
+        // Sync player chips from game state
+        self.sync_player_chips_from_game_state();
+
+        // The clients sweep the bets into the pot once, before the state shows them gone
+        self.broadcast_action_closed();
+
+        // The board stays at the all-in street until the insurance offer is answered
+        let insurance_offered = self.prepare_insurance();
+        self.broadcast_game_state();
+        self.broadcast_pot_update();
+        if insurance_offered {
+            self.send_insurance_offer();
+            return Ok(record);
+        }
+
+        // Check if game ended
+        if let Some(ref state) = self.game_state {
+            if state.final_state {
+                self.handle_game_end().await?;
+            } else {
+                if street_changed {
+                    self.send_hand_strength_hints();
+                }
+                self.broadcast_current_player_turn();
+                self.request_bot_action();
+            }
+        }
+
+        Ok(record)
+    }
+
+    /// Seat `bot` at `seat` under `name`. The server plays for it on its turns, as told by
+    /// `settings`. Returns the bot's player id.
+    #[allow(dead_code)]
+    pub async fn seat_bot(
+        &mut self,
+        name: &str,
+        seat: u8,
+        bot: Arc<dyn BotPlayer>,
+        settings: BotSettings,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let player_id = format!("bot-{}", uuid::Uuid::new_v4());
+        self.add_player(name, &player_id, None, None).await?;
+        if let Err(e) = self.seat_player(&player_id, seat).await {
+            self.players.remove(&player_id);
+            return Err(e);
+        }
+        self.bots
+            .insert(player_id.clone(), SeatedBot { bot, settings });
+
+        info!("Bot {} took seat {}", name, seat);
+        self.broadcast_game_state();
+        Ok(player_id)
+    }
+
+    /// Seat a player with no connection, who plays through `act`, e.g. from Python code
+    #[allow(dead_code)]
+    pub async fn seat_external(
+        &mut self,
+        name: &str,
+        seat: u8,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let player_id = format!("external-{}", uuid::Uuid::new_v4());
+        self.add_player(name, &player_id, None, None).await?;
+        if let Err(e) = self.seat_player(&player_id, seat).await {
+            self.players.remove(&player_id);
+            return Err(e);
+        }
+
+        info!("Player {} took seat {} without a connection", name, seat);
+        self.broadcast_game_state();
+        Ok(player_id)
+    }
+
+    /// Play `action` for the player, checked like the actions of the clients: raises are
+    /// to `action.amount` in total and refused when out of range, check or call is a check
+    /// when the player can check.
+    #[allow(dead_code)]
+    pub async fn act(
+        &mut self,
+        player_id: &str,
+        action: Action,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let seat = self
+            .players
+            .get(player_id)
+            .ok_or(ClientError::PlayerNotFound)?
+            .seat
+            .ok_or(ClientError::NotSeated)?;
+        let action = match action.action {
+            ActionEnum::Fold => PlayerAction::Fold,
+            ActionEnum::BetRaise => PlayerAction::Raise(action.amount),
+            ActionEnum::CheckCall if self.can_player_check(seat) => PlayerAction::Check,
+            ActionEnum::CheckCall => PlayerAction::Call,
+        };
+        self.handle_action(player_id, action).await
+    }
+
+    /// When a bot is on move, have it play once its think time is over. The server is not
+    /// locked while the bot works out its action.
+    fn request_bot_action(&self) {
+        let (state, handle, player) = match (&self.game_state, &self.handle) {
+            (Some(state), Some(handle)) => match state.current_actor() {
+                Some(player) => (state, handle, player),
+                None => return,
+            },
+            _ => return,
+        };
+        let seat = self.hand_seat(player);
+        let (player_id, settings) = match self
+            .seats
+            .get(&seat)
+            .and_then(|id| self.bots.get(id).map(|bot| (id.clone(), bot.settings)))
+        {
+            Some(seated) => seated,
+            None => return,
+        };
+
+        let turn = (self.hands_dealt, state.action_list.len());
+        let handle = handle.clone();
+        runtime::spawn(async move {
+            runtime::sleep(settings.think_time).await;
+            // The server may have stopped in the meantime
+            let Some(game_server) = handle.upgrade() else {
+                return;
+            };
+            let (bot, view, player) = match game_server.read().await.bot_view(&player_id, turn) {
+                Some(view) => view,
+                None => return,
+            };
+
+            let action = match settings.deadline {
+                Some(deadline) => runtime::timeout(deadline, bot.act(view, player))
+                    .await
+                    .unwrap_or_else(|| {
+                        info!("Bot {} missed its deadline", player_id);
+                        None
+                    }),
+                None => bot.act(view, player).await,
+            };
+
+            let mut game = game_server.write().await;
+            if let Err(e) = game.play_bot_turn(&player_id, turn, action).await {
+                error!("Bot {} failed to act: {}", player_id, e);
+            }
+        });
+    }
+
+    /// What the bot `player_id` is shown on its turn, unless the hand moved on since
+    fn bot_view(
+        &self,
+        player_id: &str,
+        turn: (u64, usize),
+    ) -> Option<(Arc<dyn BotPlayer>, State, u64)> {
+        let (state, seated, player) = match (&self.game_state, self.bots.get(player_id)) {
+            (Some(state), Some(seated)) if (self.hands_dealt, state.action_list.len()) == turn => {
+                (state, seated, state.current_actor()?)
+            }
+            _ => return None,
+        };
+
+        // Bots only see their own cards: the cards hidden from them are dealt again at random
+        let seed = hand_seed(self.session_seed, self.hands_dealt) ^ turn.1 as u64;
+        let state = state.private_view(player);
+        let view = redeal_unknown(&state, seed, player, &HashMap::new()).unwrap_or(state);
+        Some((seated.bot.clone(), view, player))
+    }
+
+    /// Play the action of a bot, or its fallback when it has none, unless the hand moved on
+    /// while it was thinking
+    async fn play_bot_turn(
+        &mut self,
+        player_id: &str,
+        turn: (u64, usize),
+        action: Option<Action>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let (player, fallback) = match (&self.game_state, self.bots.get(player_id)) {
+            (Some(state), Some(seated)) if (self.hands_dealt, state.action_list.len()) == turn => {
+                match state.current_actor() {
+                    Some(player) => (player, seated.settings.fallback),
+                    None => return Ok(()),
+                }
+            }
+            _ => return Ok(()),
+        };
+
+        let action = match action {
+            Some(action) => action,
+            None => {
+                let record = self
+                    .play(|state| state.apply_default_action(fallback))
+                    .await?;
+                let action = record.map(|record| record.action.action);
+                info!("Bot {} forced {:?}", player_id, action);
+                return Ok(());
+            }
+        };
+        // Bots go through the same checks as the clients, their raises are sized to fit
+        let raise_range = self.game_state.as_ref().and_then(State::legal_bet_range);
+        let action = match (action.action, raise_range) {
+            (ActionEnum::Fold, _) => PlayerAction::Fold,
+            (ActionEnum::BetRaise, Some((min, max))) => {
+                PlayerAction::Raise(action.amount.max(min).min(max))
+            }
+            _ if self.can_player_check(self.hand_seat(player)) => PlayerAction::Check,
+            _ => PlayerAction::Call,
+        };
+        self.handle_action(player_id, action).await
+    }
+
+    /// Ask to straddle the next hand, or take the request back
+    pub async fn set_straddle(
+        &mut self,
+        player_id: &str,
+        enabled: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if self.game_config.straddles == Straddles::Off {
+            return Err(ClientError::StraddlesDisabled.into());
+        }
+        let player = self
+            .players
+            .get_mut(player_id)
+            .ok_or(ClientError::PlayerNotFound)?;
+        if player.seat.is_none() {
+            return Err(ClientError::NotSeated.into());
+        }
+        player.straddle = enabled;
+        info!("Player {} set straddle to {}", player.name, enabled);
+
+        self.broadcast_game_state();
+        Ok(())
+    }
+
+    /// Opt a player in or out of the private hand strength indicator
+    pub async fn set_hand_strength_hints(
+        &mut self,
+        player_id: &str,
+        enabled: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let player = self
+            .players
+            .get_mut(player_id)
+            .ok_or(ClientError::PlayerNotFound)?;
+        player.hand_strength_hints = enabled;
+        info!(
+            "Player {} set hand strength hints to {}",
+            player.name, enabled
+        );
+
+        // Send the current street's indicator straight away when opting in mid-hand
+        if enabled {
+            self.send_hand_strength_hints();
+        }
+        Ok(())
+    }
+
+    /// Lifetime stats of the account registered under `name`, read once the hands that
+    /// finished before are recorded
+    pub fn player_stats(&self, name: &str) -> impl Future<Output = PlayerStats> + Send + 'static {
+        let stats = self.player_stats.clone();
+        let name = name.to_string();
+        let read = self.stores.run(move || {
+            stats
+                .lock()
+                .map(|stats| stats.get(&name))
+                .unwrap_or_default()
+        });
+        async move { read.await.unwrap_or_default() }
+    }
+
+    /// The `playerStats` message of the lifetime stats of `name`, for the clients and the admin
+    /// API
+    pub fn player_stats_message(
+        &self,
+        name: &str,
+    ) -> impl Future<Output = PlayerStatsMessage> + Send + 'static {
+        let read = self.player_stats(name);
+        let name = name.to_string();
+        async move {
+            let stats = read.await;
+            PlayerStatsMessage {
+                name,
+                hands_played: stats.hands_played,
+                net_winnings: stats.net_winnings,
+                biggest_pot: stats.biggest_pot,
+                luck_adjusted_winnings: stats.luck_adjusted_winnings(),
+                last_hand_id: stats.last_hand_id,
+                all_in: stats.all_in,
+                session_id: stats.session_id,
+                session_all_in: stats.session_all_in,
+            }
+        }
+    }
+
+    /// The bearer token the admin API takes, `None` when it is off
+    pub fn admin_token(&self) -> Option<&str> {
+        self.game_config
+            .admin_token
+            .as_deref()
+            .filter(|token| !token.is_empty())
+    }
+
+    /// Reply to a `playerStats` request with the lifetime stats of `name`,
+    /// or of the requesting player's own account when no name is given, once they are read
+    pub async fn send_player_stats(
+        &self,
+        player_id: &str,
+        name: Option<&str>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let name = match name {
+            Some(name) => name.to_string(),
+            None => self
+                .players
+                .get(player_id)
+                .ok_or(ClientError::PlayerNotFound)?
+                .name
+                .clone(),
+        };
+
+        let read = self.player_stats_message(&name);
+        let events = self.events.clone();
+        let player_id = player_id.to_string();
+        runtime::spawn(async move {
+            let message = read.await;
+            events.publish(TableEvent::PlayerStats { player_id, message });
+        });
+        Ok(())
+    }
+
+    /// ZIP file of the hand histories of the account registered under `name`, and how many
+    /// hands it holds. The files are read by the store writer once the future is awaited,
+    /// which does not need the table anymore.
+    pub fn export_hands_zip(
+        &self,
+        name: &str,
+    ) -> impl Future<Output = Result<(Vec<u8>, usize), String>> + Send + 'static {
+        let archive = self.hand_archive.clone();
+        let name = name.to_string();
+        self.on_stores(move || {
+            archive
+                .lock()
+                .map_err(|_| "The hand archive is unavailable".to_string())?
+                .export_zip(&name)
+                .map_err(|e| e.to_string())
+        })
+    }
+
+    /// Run `job` on the store writer, after the hands that finished before are recorded
+    fn on_stores<T: Send + 'static>(
+        &self,
+        job: impl FnOnce() -> Result<T, String> + Send + 'static,
+    ) -> impl Future<Output = Result<T, String>> + Send + 'static {
+        let result = self.stores.run(job);
+        async move {
+            result
+                .await
+                .unwrap_or_else(|| Err("The job on the stores panicked".to_string()))
+        }
+    }
+
+    /// Where the connections are recorded, see `GameConfig.session_recording_dir`
+    fn recordings_dir(&self) -> Option<PathBuf> {
+        self.game_config
+            .session_recording_dir
+            .as_ref()
+            .map(PathBuf::from)
+    }
+
+    /// ZIP file of everything stored about the account registered under `name`, see
+    /// `account_data::export_account`. Written by the store writer like `export_hands_zip`.
+    #[allow(dead_code)]
+    pub fn export_account(
+        &self,
+        name: &str,
+    ) -> impl Future<Output = Result<Vec<u8>, String>> + Send + 'static {
+        let (stats, archive) = (self.player_stats.clone(), self.hand_archive.clone());
+        let (recordings, name) = (self.recordings_dir(), name.to_string());
+        self.on_stores(move || {
+            let stats = stats
+                .lock()
+                .map_err(|_| "The player stats are unavailable")?;
+            let archive = archive
+                .lock()
+                .map_err(|_| "The hand archive is unavailable")?;
+            account_data::export_account(&stats, &archive, recordings.as_deref(), &name)
+                .map_err(|e| e.to_string())
+        })
+    }
+
+    /// Delete everything stored about the account registered under `name`, see
+    /// `account_data::delete_account`. Refused while the account is at the table, whose next
+    /// hand would store it again. Deleted by the store writer like `export_hands_zip`.
+    #[allow(dead_code)]
+    pub fn delete_account(
+        &self,
+        name: &str,
+    ) -> impl Future<Output = Result<AccountDeletion, String>> + Send + 'static {
+        let at_table = self.players.values().any(|player| player.name == name);
+        let (stats, archive) = (self.player_stats.clone(), self.hand_archive.clone());
+        let (recordings, name) = (self.recordings_dir(), name.to_string());
+        self.on_stores(move || {
+            if at_table {
+                return Err(format!("{} is at the table", name));
+            }
+            let mut stats = stats
+                .lock()
+                .map_err(|_| "The player stats are unavailable")?;
+            let mut archive = archive
+                .lock()
+                .map_err(|_| "The hand archive is unavailable")?;
+            let deletion = account_data::delete_account(
+                &mut stats,
+                &mut archive,
+                recordings.as_deref(),
+                &name,
+            )
+            .map_err(|e| e.to_string())?;
+            info!("Deleted the data of {}: {:?}", name, deletion);
+            Ok(deletion)
+        })
+    }
+
+    /// Reply to an `exportHands` request with where to download a ZIP file of the requesting
+    /// player's hand histories, once it is written in the background
+    pub async fn export_hands(&self, player_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let name = self
+            .players
+            .get(player_id)
+            .ok_or(ClientError::PlayerNotFound)?
+            .name
+            .clone();
+        let export = self.export_hands_zip(&name);
+        let events = self.events.clone();
+        let locale = self.game_config.locale;
+        let player_id = player_id.to_string();
+        runtime::spawn(async move {
+            match export.await {
+                Ok((_, 0)) => {
+                    let error = ClientError::NoHandHistories;
+                    events.publish(TableEvent::Error {
+                        player_id,
+                        message: ErrorMessage {
+                            code: error.code().to_string(),
+                            message: error.text(locale),
+                        },
+                    });
+                }
+                Ok((zip, hands)) => {
+                    info!("Exported {} hands of {}", hands, name);
+                    events.publish(TableEvent::HandsExport {
+                        player_id,
+                        zip: Arc::new(zip),
+                        hands,
+                    });
+                }
+                Err(e) => error!("Failed to export the hands of {}: {}", name, e),
+            }
+        });
+        Ok(())
+    }
+
+    /// Tell a client why its request was rejected, in the configured locale
+    pub fn send_error(&self, player_id: &str, error: &ClientError) {
+        self.events.publish(TableEvent::Error {
+            player_id: player_id.to_string(),
+            message: ErrorMessage {
+                code: error.code().to_string(),
+                message: error.text(self.game_config.locale),
+            },
+        });
+    }
+
+    pub async fn player_disconnected(&mut self, player_id: &str) {
+        if let Some(player) = self.players.get_mut(player_id) {
+            player.connected = false;
+            player.disconnected_at = Some(now());
+            info!("Player {} disconnected", player.name);
+            if let Some(seat) = player.seat {
+                self.events.publish(TableEvent::PlayerLeft {
+                    seat,
+                    name: player.name.clone(),
+                    chips: player.chips,
+                    session_net: player.chips - player.starting_session_chips,
+                });
+            }
+            self.broadcast_game_state();
+        }
+
+        // A player who leaves declines the insurance offer so the hand can be settled
+        let offered_to_player = self
+            .pending_insurance
+            .as_ref()
+            .map(|pending| pending.player_id == player_id)
+            .unwrap_or(false);
+        if offered_to_player {
+            if let Err(e) = self.resolve_insurance(player_id, 0.0).await {
+                error!("Failed to settle insurance: {}", e);
+            }
+        }
+    }
+
+    /// Find the favourite of an all-in that just ended the hand and hold the hand open
+    /// for their insurance decision
+    fn prepare_insurance(&mut self) -> bool {
+        if !self.game_config.insurance {
+            return false;
+        }
+
+        let state = match self.game_state {
+            Some(ref state) if state.final_state => state,
+            _ => return false,
+        };
+
+        for (index, seat) in self.hand_seats.iter().enumerate() {
+            let player_id = match self.seats.get(seat) {
+                Some(player_id) => player_id,
+                None => continue,
+            };
+            let connected = self
+                .players
+                .get(player_id)
+                .map(|p| p.connected)
+                .unwrap_or(false);
+            if !connected {
+                continue;
+            }
+            if let Some(quote) = insurance_quote(state, index as u64) {
+                self.pending_insurance = Some(PendingInsurance {
+                    player_id: player_id.clone(),
+                    quote,
+                });
+                return true;
+            }
+        }
+        false
+    }
+
+    fn send_insurance_offer(&self) {
+        if let Some(ref pending) = self.pending_insurance {
+            let quote = &pending.quote;
+            let message = InsuranceOfferMessage {
+                seat: self.hand_seat(quote.player),
+                stage: format!("{:?}", quote.stage),
+                equity: quote.equity,
+                scoop_probability: quote.scoop_probability,
+                outs: quote.outs,
+                payout_ratio: quote.payout_ratio,
+                max_premium: quote.max_premium(),
+            };
+            self.events.publish(TableEvent::InsuranceOffer {
+                player_id: pending.player_id.clone(),
+                message,
+            });
+        }
+    }
+
+    /// Record the answer to an insurance offer (a premium of 0 declines it), run the board out
+    /// and settle: the premium is paid to the house, which pays `premium * payout_ratio`
+    /// back if the insured player does not scoop the pot
+    pub async fn resolve_insurance(
+        &mut self,
+        player_id: &str,
+        premium: f64,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let pending = match self.pending_insurance.take() {
+            Some(pending) if pending.player_id == player_id => pending,
+            other => {
+                self.pending_insurance = other;
+                return Err(ClientError::NoInsuranceOffer.into());
+            }
+        };
+
+        let quote = pending.quote;
+        let premium = premium.clamp(0.0, quote.max_premium());
+        let insured_scooped = self
+            .game_state
+            .as_ref()
+            .map(|state| scooped(state, quote.player))
+            .unwrap_or(true);
+        let payout = if insured_scooped {
+            0.0
+        } else {
+            premium * quote.payout_ratio
+        };
+
+        self.broadcast_game_state();
+        self.handle_game_end().await?;
+
+        if let Some(player) = self.players.get_mut(player_id) {
+            player.chips = (player.chips - premium + payout).max(0.0);
+            info!(
+                "Player {} insured for {} and received {}",
+                player.name, premium, payout
+            );
+        }
+
+        self.events.publish(TableEvent::InsuranceResult {
+            player_id: player_id.to_string(),
+            message: InsuranceResultMessage {
+                seat: self.hand_seat(quote.player),
+                premium,
+                payout,
+            },
+        });
+        self.broadcast_game_state();
+        Ok(())
+    }
+
+    fn sync_player_chips_from_game_state(&mut self) {
+        if let Some(ref state) = self.game_state {
+            for (seat, player_id) in &self.seats {
+                if let Some(player) = self.players.get_mut(player_id) {
+                    let player_state = self
+                        .hand_seats
+                        .iter()
+                        .position(|s| s == seat)
+                        .and_then(|index| state.players_state.get(index));
+                    if let Some(player_state) = player_state {
+                        // The bet of a player who folded is no longer theirs
+                        let bet = if player_state.active {
+                            player_state.bet_chips
+                        } else {
+                            0.0
+                        };
+                        player.chips = player_state.stake + player_state.held_back + bet;
+                    }
+                }
+            }
+        }
+    }
+
+    async fn handle_game_end(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        // Who would knock out the players of each seat if they busted
+        let mut knockers = HashMap::new();
+        if let Some(ref state) = self.game_state {
+            self.hand_rake = take_rake(
+                state,
+                self.game_config.rake,
+                &self.game_config.jackpot_drops,
+                self.game_config.bad_beat.as_ref(),
+            );
+            self.table_rake.add(&self.hand_rake);
+            if let Some(ref hit) = self.hand_rake.bad_beat {
+                info!(
+                    "Bad beat in hand {}: {:?} lost to {:?}, {} jackpot at {}",
+                    state.hand_id,
+                    hit.losing_hand,
+                    hit.winning_hand,
+                    hit.jackpot,
+                    self.table_rake
+                        .jackpots
+                        .get(&hit.jackpot)
+                        .copied()
+                        .unwrap_or(0.0)
+                );
+            }
+
+            // Calculate winnings and update player chips, also of the players who moved to
+            // another table after folding
+            let settled = |seat: &u8| {
+                let player_state = self
+                    .hand_seats
+                    .iter()
+                    .position(|s| s == seat)
+                    .and_then(|index| state.players_state.get(index))?;
+                // The reward is net of what the player put in the pot
+                let total_reward = player_state.stake
+                    + player_state.held_back
+                    + player_state.pot_chips
+                    + player_state.reward
+                    - self.hand_rake.taken_from(player_state.player as usize);
+                Some(total_reward.max(0.0))
+            };
+            let settlements: Vec<(u8, f64)> = self
+                .hand_seats
+                .iter()
+                .filter_map(|seat| Some((*seat, settled(seat)?)))
+                .collect();
+            for (seat, chips) in settlements {
+                if let Some(player) = self.departed.get_mut(&seat) {
+                    // They took their stack with them when they left, which must be what the
+                    // hand leaves them
+                    if (player.chips - chips).abs() > 1e-9 {
+                        error!(
+                            "Player {} left with {} but settled with {}",
+                            player.name, player.chips, chips
+                        );
+                    }
+                    player.chips = chips;
+                } else if let Some(player) = self
+                    .seats
+                    .get(&seat)
+                    .and_then(|player_id| self.players.get_mut(player_id))
+                {
+                    player.chips = chips;
+                }
+            }
+
+            // Track consecutive pots won by a single player for kill pots
+            let result = state.result().unwrap_or_default();
+            let pot_winners: Vec<&String> = self
+                .seats
+                .iter()
+                .filter(|(seat, _)| {
+                    self.hand_seats
+                        .iter()
+                        .position(|s| s == *seat)
+                        .and_then(|index| result.get(index))
+                        .is_some_and(|&won| won > 0.0)
+                })
+                .map(|(_, player_id)| player_id)
+                .collect();
+            knockers = self.knockers(state);
+            self.win_streak = match (pot_winners.as_slice(), self.win_streak.take()) {
+                ([winner], Some((last, streak))) if **winner == last => Some((last, streak + 1)),
+                ([winner], _) => Some(((*winner).clone(), 1)),
+                _ => None,
+            };
+
+            self.broadcast_hand_winnings();
+
+            // The sinks record the stats and hand histories and call the webhooks
+            let players = self
+                .hand_seats
+                .iter()
+                .map(|seat| {
+                    let player = self
+                        .seats
+                        .get(seat)
+                        .and_then(|id| self.players.get(id))
+                        .or_else(|| self.departed.get(seat));
+                    HandPlayer {
+                        seat: *seat,
+                        name: player.map(|p| p.name.clone()).unwrap_or_default(),
+                        chips: player.map_or(0.0, |p| p.chips),
+                    }
+                })
+                .collect();
+            self.events
+                .publish(TableEvent::HandComplete(Arc::new(HandSummary {
+                    state: state.clone(),
+                    table_id: self.table_id.clone(),
+                    players,
+                    community_cards: self.get_community_cards(),
+                    winnings: self.calculate_winnings(),
+                    rake: self.hand_rake.clone(),
+                })));
+        }
+
+        self.game_running = false;
+        self.game_state = None;
+        self.blind_seats = None;
+        self.departed.clear();
+        if self.clock.is_some() {
+            self.unseat_busted(&knockers);
+        }
+
+        // Rotate dealer
+        self.rotate_dealer();
+
+        // The clients were last sent the stacks before the pot was awarded
+        self.broadcast_game_state();
+
+        info!("Game ended");
+        Ok(())
+    }
+
+    fn rotate_dealer(&mut self) {
+        let seated_players: Vec<u8> = self.seats.keys().copied().collect();
+        if seated_players.is_empty() {
+            return;
+        }
+
+        let mut sorted_seats = seated_players;
+        sorted_seats.sort();
+
+        if let Some(current_index) = sorted_seats.iter().position(|&s| s == self.dealer_seat) {
+            let next_index = (current_index + 1) % sorted_seats.len();
+            self.dealer_seat = sorted_seats[next_index];
+        } else {
+            self.dealer_seat = sorted_seats[0];
+        }
+    }
+
+    /// The seats around the table, with the button and blinds of the hand being played
+    fn table_layout(&self) -> TableLayoutInfo {
+        let max_seats = self.game_config.max_players;
+        TableLayoutInfo {
+            max_seats,
+            seat_order: (1..=max_seats).collect(),
+            dealer_seat: self
+                .game_state
+                .as_ref()
+                .map(|state| self.hand_seat(state.button)),
+            small_blind_seat: self.blind_seats.map(|(small_blind, _)| small_blind),
+            big_blind_seat: self.blind_seats.map(|(_, big_blind)| big_blind),
+        }
+    }
+
+    /// `chips` written with `GameConfig.chip_format`, when it is set
+    fn chips_text(&self, chips: f64) -> Option<String> {
+        let format = self.game_config.chip_format.as_ref()?;
+        Some(format.format(chips))
+    }
+
+    /// The chips `chips` are stacked with, when `GameConfig.chip_format` is set
+    fn chip_stacks(&self, chips: f64) -> Option<Vec<ChipStack>> {
+        let format = self.game_config.chip_format.as_ref()?;
+        Some(format.breakdown(chips))
+    }
+
+    fn broadcast_game_state(&self) {
+        let mut players_info = HashMap::new();
+        let knockout = self.game_config.bounty > 0.0;
+
+        for seat in 1..=self.game_config.max_players {
+            if let Some(player_id) = self.seats.get(&seat) {
+                if let Some(player) = self.players.get(player_id) {
+                    let player_cards = self.get_player_cards(seat);
+
+                    let player_info = PlayerInfo {
+                        name: player.name.clone(),
+                        address: player.id.clone(),
+                        chips: player.chips,
+                        bet: self.get_player_bet(seat),
+                        // Players waiting for the big blind sit the hand out
+                        in_game: player.connected
+                            && player.seat.is_some()
+                            && (!self.game_running || self.hand_index(seat).is_some()),
+                        on_move: self.is_player_on_move(seat),
+                        folded: self.is_player_folded(seat),
+                        session_net_win_loss: player.chips - player.starting_session_chips,
+                        cards: Some(player_cards),
+                        owes_blind: player.owes_blind,
+                        straddle: player.straddle,
+                        bot: self.bots.contains_key(&player.id),
+                        chips_text: self.chips_text(player.chips),
+                        bet_text: self.chips_text(self.get_player_bet(seat)),
+                        bounty: knockout.then_some(player.bounty),
+                        bounties_won: knockout.then_some(player.bounties_won),
+                    };
+
+                    players_info.insert(seat.to_string(), player_info);
+                }
+            }
+        }
+
+        let community_cards = self.get_community_cards();
+        let second_board = self.get_second_board();
+        let pot = self.get_pot_size();
+
+        let game_state_msg = GameStateMessage {
+            game_started: self.game_running,
+            hand_id: self.game_state.as_ref().map(|state| state.hand_id.clone()),
+            table_id: self.table_id.clone(),
+            session_id: self.session_id.clone(),
+            players: players_info,
+            community_cards,
+            second_board,
+            pot,
+            pot_text: self.chips_text(pot),
+            table: self.table_layout(),
+            tournament_clock: self.clock_message(),
+        };
+
+        // The runout stays hidden from observers until a pending insurance offer is answered
+        let view = match self.game_state {
+            Some(ref state) if self.pending_insurance.is_none() => Some(state.public_view()),
+            _ => None,
+        };
+        self.events.publish(TableEvent::GameState {
+            message: game_state_msg,
+            view,
+        });
+    }
+
+    /// Watch the hands played at the table, e.g. to log them. Observers share one view of
+    /// the hand per update and must return quickly, as the table waits for them.
+    #[allow(dead_code)]
+    pub fn add_observer(&mut self, observer: Observer) {
+        self.subscribe(Arc::new(move |event| {
+            if let TableEvent::GameState {
+                view: Some(view), ..
+            } = event
+            {
+                observer(view.clone());
+            }
+        }));
+    }
+
+    fn broadcast_current_player_turn(&self) {
+        if let Some((state, player)) = self
+            .game_state
+            .as_ref()
+            .and_then(|state| Some((state, state.current_actor()?)))
+        {
+            let current_seat = self.hand_seat(player);
+
+            if let Some(player_id) = self.seats.get(&current_seat) {
+                if let Some(player) = self.players.get(player_id) {
+                    let player_cards = self.get_player_cards(current_seat);
+
+                    let raise_range = state.legal_bet_range();
+                    let on_move_msg = OnMoveMessage {
+                        seat: current_seat,
+                        address: player.id.clone(),
+                        name: player.name.clone(),
+                        chips: player.chips,
+                        bet: self.get_player_bet(current_seat),
+                        on_move: true,
+                        in_game: true,
+                        folded: false,
+                        cards: player_cards,
+                        max_bet_on_table: self.get_max_bet(),
+                        can_check: self.can_player_check(current_seat),
+                        call_amount: self.get_call_amount(current_seat),
+                        min_bet_to_total_value: state.min_bet,
+                        can_raise: raise_range.is_some(),
+                        min_raise_to_total_bet: raise_range.map_or(0.0, |(min, _)| min),
+                        max_raise_to_total_bet: raise_range.map_or(0.0, |(_, max)| max),
+                        pot_size: state.chips_in_pot(),
+                    };
+
+                    self.events.publish(TableEvent::OnMove(on_move_msg));
+                }
+            }
+        }
+    }
+
+    fn broadcast_action_closed(&self) {
+        let state = match self.game_state {
+            Some(ref state) => state,
+            None => return,
+        };
+        let summary = match state.closed_street() {
+            Some(summary) => summary,
+            None => return,
+        };
+        let bets = summary
+            .contributions
+            .iter()
+            .enumerate()
+            .map(|(index, chips)| (self.hand_seat(index as u64).to_string(), *chips))
+            .collect();
+        self.events
+            .publish(TableEvent::ActionClosed(ActionClosedMessage {
+                hand_id: state.hand_id.clone(),
+                stage: format!("{:?}", summary.stage),
+                bets,
+                street_total: summary.total,
+                pot: summary.pot,
+            }));
+    }
+
+    /// Show the pot after the last action as the main pot and the side pots, each with the
+    /// seats that can still win it.
+    fn broadcast_pot_update(&self) {
+        let state = match self.game_state {
+            Some(ref state) => state,
+            None => return,
+        };
+        let seats = |pot: &Pot| {
+            let mut seats: Vec<u8> = pot
+                .eligible_players
+                .iter()
+                .map(|&index| self.hand_seat(index))
+                .collect();
+            seats.sort();
+            PotInfo {
+                amount: pot.amount,
+                eligible_seats: seats,
+                amount_text: self.chips_text(pot.amount),
+            }
+        };
+
+        let mut pots = contributions::pots(state).into_iter();
+        // Before the first street is swept in, the main pot is empty and open to everyone in
+        let main_pot = pots.next().unwrap_or_else(|| {
+            let mut pot = Pot::new();
+            pot.eligible_players = state
+                .players_state
+                .iter()
+                .filter(|ps| ps.folded_on.is_none())
+                .map(|ps| ps.player)
+                .collect();
+            pot
+        });
+        let player_bets: HashMap<String, f64> = state
+            .players_state
+            .iter()
+            .map(|ps| (self.hand_seat(ps.player).to_string(), ps.bet_chips))
+            .collect();
+        let bet_stacks = self.game_config.chip_format.as_ref().map(|format| {
+            player_bets
+                .iter()
+                .map(|(seat, bet)| (seat.clone(), format.breakdown(*bet)))
+                .collect()
+        });
+        self.events.publish(TableEvent::PotUpdate(PotUpdateMessage {
+            hand_id: state.hand_id.clone(),
+            main_pot: seats(&main_pot),
+            side_pots: pots.map(|pot| seats(&pot)).collect(),
+            player_bets,
+            total: state.pot,
+            total_text: self.chips_text(state.pot),
+            bet_stacks,
+        }));
+    }
+
+    /// Send each opted-in player still in the hand their own hand strength indicator.
+    /// These messages go to the player's private channel only, never to the broadcast.
+    fn send_hand_strength_hints(&self) {
+        if let Some(ref state) = self.game_state {
+            for (seat, player_id) in &self.seats {
+                let player = match self.players.get(player_id) {
+                    Some(player) if player.hand_strength_hints && player.connected => player,
+                    _ => continue,
+                };
+
+                let player_index = match self.hand_index(*seat) {
+                    Some(index) => index as u64,
+                    None => continue,
+                };
+                let in_hand = state
+                    .players_state
+                    .get(player_index as usize)
+                    .map(|ps| ps.active)
+                    .unwrap_or(false);
+                if !in_hand {
+                    continue;
+                }
+
+                if let Some(strength) = hand_strength(state, player_index, HAND_STRENGTH_SAMPLES) {
+                    let message = HandStrengthMessage {
+                        seat: *seat,
+                        stage: format!("{:?}", strength.stage),
+                        hand_category: strength
+                            .category
+                            .description(self.game_config.locale)
+                            .to_string(),
+                        outs: strength.outs,
+                        equity: strength.equity,
+                    };
+                    self.events.publish(TableEvent::HandStrength {
+                        player_id: player.id.clone(),
+                        message,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Equity of each seat still in on every street of an all-in runout
+    fn all_in_equities(&self) -> Vec<AllInEquityInfo> {
+        let state = match self.game_state {
+            Some(ref state) => state,
+            None => return Vec::new(),
+        };
+        state
+            .all_in_equities
+            .iter()
+            .map(|entry| AllInEquityInfo {
+                stage: format!("{:?}", entry.stage),
+                equities: entry
+                    .players
+                    .iter()
+                    .zip(&entry.equities)
+                    .map(|(&player, &equity)| SeatEquity {
+                        seat: self.hand_seat(player),
+                        equity,
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+
+    fn broadcast_hand_winnings(&self) {
+        let community_cards = self.get_community_cards();
+        let winnings = self.calculate_winnings();
+
+        let winnings_msg = HandWinningsMessage {
+            hand_id: self
+                .game_state
+                .as_ref()
+                .map(|state| state.hand_id.clone())
+                .unwrap_or_default(),
+            table_id: self.table_id.clone(),
+            session_id: self.session_id.clone(),
+            community_cards,
+            winnings,
+            all_in_equities: self.all_in_equities(),
+            rake: self.hand_rake.rake,
+            jackpot_drop: self.hand_rake.dropped(),
+            bad_beat: self.hand_rake.bad_beat.as_ref().map(|hit| {
+                let locale = self.game_config.locale;
+                BadBeatInfo {
+                    jackpot: hit.jackpot.clone(),
+                    pool: self
+                        .table_rake
+                        .jackpots
+                        .get(&hit.jackpot)
+                        .copied()
+                        .unwrap_or(0.0),
+                    losing_seat: self.hand_seat(hit.loser),
+                    losing_hand: hit.losing_hand.description(locale).to_string(),
+                    winning_seat: self.hand_seat(hit.winner),
+                    winning_hand: hit.winning_hand.description(locale).to_string(),
+                }
+            }),
+        };
+
+        self.events.publish(TableEvent::HandWinnings(winnings_msg));
+    }
+
+    fn get_player_cards(&self, seat: u8) -> Vec<CardInfo> {
+        if let Some(ref state) = self.game_state {
+            let player_state = self
+                .hand_index(seat)
+                .and_then(|index| state.players_state.get(index));
+            if let Some(player_state) = player_state {
+                return vec![player_state.hand.0.into(), player_state.hand.1.into()];
+            }
+        }
+        Vec::new()
+    }
+
+    fn get_community_cards(&self) -> Vec<CardInfo> {
+        if let Some(ref state) = self.game_state {
+            // Hide the runout while an insurance offer is pending
+            let visible = match self.pending_insurance {
+                Some(ref pending) if pending.quote.stage == Stage::Flop => 3,
+                Some(_) => 4,
+                None => state.public_cards.len(),
+            };
+            return state
+                .public_cards
+                .iter()
+                .take(visible)
+                .map(|&card| CardInfo::from(card))
+                .collect();
+        }
+        Vec::new()
+    }
+
+    fn get_second_board(&self) -> Vec<CardInfo> {
+        if let Some(ref state) = self.game_state {
+            return state
+                .second_board
+                .iter()
+                .map(|&card| CardInfo::from(card))
+                .collect();
+        }
+        Vec::new()
+    }
+
+    fn get_player_bet(&self, seat: u8) -> f64 {
+        if let Some(ref state) = self.game_state {
+            let player_state = self
+                .hand_index(seat)
+                .and_then(|index| state.players_state.get(index));
+            if let Some(player_state) = player_state {
+                return player_state.bet_chips;
+            }
+        }
+        0.0
+    }
+
+    fn get_pot_size(&self) -> f64 {
+        self.game_state
+            .as_ref()
+            .map(|s| s.chips_in_pot())
+            .unwrap_or(0.0)
     }
 
     fn is_player_on_move(&self, seat: u8) -> bool {
         if let Some(ref state) = self.game_state {
-            return (state.current_player + 1) as u8 == seat;
+            return self.hand_index(seat).map(|index| index as u64) == state.current_actor();
         }
         false
     }
 
     fn is_player_folded(&self, seat: u8) -> bool {
         if let Some(ref state) = self.game_state {
-            let player_index = (seat - 1) as usize;
-            if let Some(player_state) = state.players_state.get(player_index) {
+            let player_state = self
+                .hand_index(seat)
+                .and_then(|index| state.players_state.get(index));
+            if let Some(player_state) = player_state {
                 return !player_state.active;
             }
         }
@@ -478,8 +2855,10 @@ impl GameServer {
 
     fn can_player_check(&self, seat: u8) -> bool {
         if let Some(ref state) = self.game_state {
-            let player_index = (seat - 1) as usize;
-            if let Some(player_state) = state.players_state.get(player_index) {
+            let player_state = self
+                .hand_index(seat)
+                .and_then(|index| state.players_state.get(index));
+            if let Some(player_state) = player_state {
                 return player_state.bet_chips >= state.min_bet;
             }
         }
@@ -488,8 +2867,10 @@ impl GameServer {
 
     fn get_call_amount(&self, seat: u8) -> f64 {
         if let Some(ref state) = self.game_state {
-            let player_index = (seat - 1) as usize;
-            if let Some(player_state) = state.players_state.get(player_index) {
+            let player_state = self
+                .hand_index(seat)
+                .and_then(|index| state.players_state.get(index));
+            if let Some(player_state) = player_state {
                 let call_amount = state.min_bet - player_state.bet_chips;
                 return call_amount.max(0.0).min(player_state.stake);
             }
@@ -497,40 +2878,59 @@ impl GameServer {
         0.0
     }
 
-    fn get_min_raise_amount(&self, _seat: u8) -> f64 {
-        if let Some(ref state) = self.game_state {
-            return state.min_bet + state.bb;
-        }
-        0.0
-    }
-
     fn calculate_winnings(&self) -> Vec<WinningInfo> {
+        let locale = self.game_config.locale;
         let mut winnings = Vec::new();
 
         if let Some(ref state) = self.game_state {
             for (seat, player_id) in &self.seats {
                 if let Some(player) = self.players.get(player_id) {
-                    let player_index = (*seat - 1) as usize;
-                    if let Some(player_state) = state.players_state.get(player_index) {
+                    let player_state = self
+                        .hand_index(*seat)
+                        .and_then(|index| state.players_state.get(index));
+                    if let Some(player_state) = player_state {
                         if player_state.reward > 0.0 {
-                            let hole_cards = vec![
-                                CardInfo {
-                                    suit: player_state.hand.0.suit as u8,
-                                    rank: player_state.hand.0.rank as u8 + 2,
-                                },
-                                CardInfo {
-                                    suit: player_state.hand.1.suit as u8,
-                                    rank: player_state.hand.1.rank as u8 + 2,
-                                },
-                            ];
+                            let hole_cards =
+                                vec![player_state.hand.0.into(), player_state.hand.1.into()];
+                            let winning_cards = if shown_down(state) {
+                                state
+                                    .rules
+                                    .variant
+                                    .evaluator()
+                                    .best_five(player_state.hand, &state.public_cards)
+                            } else {
+                                vec![]
+                            };
+                            let split = split_with(state, player_state.player).map(|players| {
+                                let tie = tie(
+                                    state.rules.variant.evaluator(),
+                                    player_state.hand,
+                                    &state.public_cards,
+                                );
+                                SplitInfo {
+                                    seats: players.iter().map(|&p| self.hand_seat(p)).collect(),
+                                    board_plays: tie == Tie::Board,
+                                    description: messages::split(&tie, players.len(), locale),
+                                }
+                            });
 
+                            let amount_won = player_state.reward
+                                - self.hand_rake.taken_from(player_state.player as usize);
                             winnings.push(WinningInfo {
                                 seat_id: *seat,
                                 player_name: player.name.clone(),
-                                amount_won: player_state.reward,
-                                pot_description: "Main Pot".to_string(),
-                                hand_description: "Winner".to_string(), // TODO: Implement proper hand evaluation
+                                amount_won,
+                                pot_description: Label::MainPot.text(locale).to_string(),
+                                hand_description: hand_description(
+                                    state,
+                                    player_state.hand,
+                                    locale,
+                                ),
                                 hole_cards,
+                                winning_cards,
+                                split,
+                                amount_won_text: self.chips_text(amount_won),
+                                won_stacks: self.chip_stacks(amount_won),
                             });
                         }
                     }
@@ -541,3 +2941,53 @@ impl GameServer {
         winnings
     }
 }
+
+/// Whether the hand was won at a showdown with a flop dealt, rather than by everybody else
+/// folding
+fn shown_down(state: &State) -> bool {
+    let showdown = state
+        .players_state
+        .iter()
+        .filter(|ps| {
+            !state
+                .action_list
+                .iter()
+                .any(|a| a.player == ps.player && a.action.action == ActionEnum::Fold)
+        })
+        .count()
+        > 1;
+    showdown && state.public_cards.len() >= 3
+}
+
+/// Players who split the first pot `player` split at the showdown, with hands of the same
+/// rank. Hands are only compared on the first board.
+fn split_with(state: &State, player: u64) -> Option<Vec<u64>> {
+    if !shown_down(state) || !state.second_board.is_empty() {
+        return None;
+    }
+    let evaluator = state.rules.variant.evaluator();
+    let rank =
+        |p: u64| evaluator.rank_hand(state.players_state[p as usize].hand, &state.public_cards);
+    contributions::pots(state).into_iter().find_map(|pot| {
+        let best = pot.eligible_players.iter().map(|&p| rank(p)).min()?;
+        let winners: Vec<u64> = pot
+            .eligible_players
+            .iter()
+            .copied()
+            .filter(|&p| rank(p) == best)
+            .sorted()
+            .collect();
+        (winners.len() > 1 && winners.contains(&player)).then_some(winners)
+    })
+}
+
+/// Category of the winning hand, or a plain label when everybody else folded
+fn hand_description(state: &State, hand: (Card, Card), locale: Locale) -> String {
+    if shown_down(state) {
+        HandCategory::from_rank(rank_hand(hand, &state.public_cards))
+            .description(locale)
+            .to_string()
+    } else {
+        Label::Uncontested.text(locale).to_string()
+    }
+}
@@ -0,0 +1,499 @@
+// hand_archive.rs - Hand histories of every account, exported as ZIP files
+use crate::datasets::split_hands;
+use crate::events::TableEvent;
+use crate::hand_history::{hand_table, hand_time, rename_players, write_hand_history};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{Seek, Write};
+use std::path::{Path, PathBuf};
+use tracing::error;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+/// File of the pseudonyms in the hand history directory
+const PSEUDONYMS_FILE: &str = "pseudonyms.json";
+
+/// File of the reconnect tokens the accounts were opened with, in the hand history directory
+const TOKENS_FILE: &str = "accounts.json";
+
+/// Hand histories keyed by account (the registered player name), each hand written from the
+/// account's seat by `write_hand_history`. Appended to one `.txt` file per account when a
+/// directory is configured, kept in memory otherwise.
+#[derive(Debug, Clone, Default)]
+pub struct HandArchive {
+    dir: Option<PathBuf>,
+    /// Hands of each account when there is no directory
+    hands: HashMap<String, Vec<String>>,
+    /// Pseudonyms of the players of each table, kept in `pseudonyms.json` next to the hands
+    pseudonyms: HashMap<String, TablePseudonyms>,
+    /// Reconnect token each account was opened with, kept in `accounts.json`. Only a player
+    /// presenting it registers under the account's name, and so exports its hands.
+    tokens: HashMap<String, String>,
+}
+
+/// The names a table's players go by in the hands of the other accounts once pseudonymized
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct TablePseudonyms {
+    /// Player name -> pseudonym
+    names: HashMap<String, String>,
+    /// Pseudonyms of deleted accounts, which nothing links back to a name anymore
+    #[serde(default)]
+    forgotten: Vec<String>,
+}
+
+impl TablePseudonyms {
+    fn is_pseudonym(&self, name: &str) -> bool {
+        self.names.values().any(|pseudonym| pseudonym == name)
+            || self.forgotten.iter().any(|pseudonym| pseudonym == name)
+    }
+
+    /// Pseudonym of `name` at the table, drawn the first time it is needed
+    fn pseudonym(&mut self, name: &str) -> String {
+        if let Some(pseudonym) = self.names.get(name) {
+            return pseudonym.clone();
+        }
+        let pseudonym = loop {
+            let pseudonym = format!("Player-{:08x}", rand::random::<u32>());
+            if !self.is_pseudonym(&pseudonym) {
+                break pseudonym;
+            }
+        };
+        self.names.insert(name.to_string(), pseudonym.clone());
+        pseudonym
+    }
+
+    /// Unlink `name` from its pseudonym
+    fn forget(&mut self, name: &str) {
+        if let Some(pseudonym) = self.names.remove(name) {
+            self.forgotten.push(pseudonym);
+        }
+    }
+}
+
+impl HandArchive {
+    /// Archive the hands under `dir`, created when it does not exist yet
+    pub fn open(dir: PathBuf) -> Result<Self, Box<dyn std::error::Error>> {
+        std::fs::create_dir_all(&dir)?;
+        let pseudonyms = Self::load_json(&dir.join(PSEUDONYMS_FILE))?;
+        let tokens = Self::load_json(&dir.join(TOKENS_FILE))?;
+        Ok(Self {
+            dir: Some(dir),
+            hands: HashMap::new(),
+            pseudonyms,
+            tokens,
+        })
+    }
+
+    /// Content of a JSON file of the directory, empty when there is none yet
+    fn load_json<T: serde::de::DeserializeOwned + Default>(
+        path: &Path,
+    ) -> Result<T, Box<dyn std::error::Error>> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => Ok(serde_json::from_str(&content)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(T::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Write `value` to a JSON file of the directory, through a temporary file
+    fn save_json<T: Serialize>(
+        &self,
+        file: &str,
+        value: &T,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(ref dir) = self.dir {
+            let path = dir.join(file);
+            let tmp_path = path.with_extension("tmp");
+            std::fs::write(&tmp_path, serde_json::to_string_pretty(value)?)?;
+            std::fs::rename(&tmp_path, path)?;
+        }
+        Ok(())
+    }
+
+    /// Reconnect token the account `account` was opened with, None for a name it never saw
+    pub fn account_token(&self, account: &str) -> Option<&str> {
+        self.tokens.get(account).map(String::as_str)
+    }
+
+    /// Open the account `account` with `token`, unless it was already opened
+    pub fn open_account(
+        &mut self,
+        account: &str,
+        token: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if self.tokens.contains_key(account) {
+            return Ok(());
+        }
+        self.tokens.insert(account.to_string(), token.to_string());
+        self.save_json(TOKENS_FILE, &self.tokens)
+    }
+
+    /// File of `account`, any character that could leave the directory percent-encoded
+    fn account_file(dir: &Path, account: &str) -> PathBuf {
+        let mut name = String::new();
+        for byte in account.bytes() {
+            match byte {
+                b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'_' | b'-' => name.push(byte as char),
+                _ => name.push_str(&format!("%{:02X}", byte)),
+            }
+        }
+        dir.join(format!("{}.txt", name))
+    }
+
+    /// Account of a file named by `account_file`
+    fn file_account(path: &Path) -> Option<String> {
+        let name = path.file_name()?.to_str()?.strip_suffix(".txt")?;
+        let mut bytes = Vec::new();
+        let mut rest = name.as_bytes();
+        while let Some((&byte, tail)) = rest.split_first() {
+            if byte == b'%' {
+                let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+                bytes.push(u8::from_str_radix(hex, 16).ok()?);
+                rest = &tail[2..];
+            } else {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+        String::from_utf8(bytes).ok()
+    }
+
+    /// Every account with hands in the archive
+    pub fn accounts(&self) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        match self.dir {
+            Some(ref dir) => {
+                let mut accounts = Vec::new();
+                for entry in std::fs::read_dir(dir)? {
+                    accounts.extend(Self::file_account(&entry?.path()));
+                }
+                Ok(accounts)
+            }
+            None => Ok(self.hands.keys().cloned().collect()),
+        }
+    }
+
+    pub fn record_hand(
+        &mut self,
+        account: &str,
+        history: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        match self.dir {
+            Some(ref dir) => {
+                let mut file = std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(Self::account_file(dir, account))?;
+                // Hands are separated by blank lines, as in the files of the poker sites
+                write!(file, "{}\n\n", history.trim_end())?;
+            }
+            None => self
+                .hands
+                .entry(account.to_string())
+                .or_default()
+                .push(history.to_string()),
+        }
+        Ok(())
+    }
+
+    /// Archive a settled hand for every named player, written from their own seat
+    pub fn on_event(&mut self, event: &TableEvent) {
+        let TableEvent::HandComplete(hand) = event else {
+            return;
+        };
+        let seats: Vec<(u8, String)> = hand
+            .players
+            .iter()
+            .map(|player| (player.seat, player.name.clone()))
+            .collect();
+        for (index, (_, name)) in seats.iter().enumerate() {
+            if name.is_empty() {
+                continue;
+            }
+            let history = write_hand_history(
+                &hand.state,
+                &hand.table_id,
+                &seats,
+                Some(index as u64),
+                &hand.rake.taken,
+            );
+            if let Err(e) = self.record_hand(name, &history) {
+                error!(
+                    "Failed to archive hand {} for {}: {}",
+                    hand.state.hand_id, name, e
+                );
+            }
+        }
+    }
+
+    /// Hands of `account`, oldest first
+    pub fn hands(&self, account: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        match self.dir {
+            Some(ref dir) => match std::fs::read_to_string(Self::account_file(dir, account)) {
+                Ok(text) => Ok(split_hands(&text)
+                    .into_iter()
+                    .map(|hand| hand.trim_end().to_string())
+                    .collect()),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+                Err(e) => Err(e.into()),
+            },
+            None => Ok(self.hands.get(account).cloned().unwrap_or_default()),
+        }
+    }
+
+    /// Replace the hands of `account`, none removing the account
+    fn store_hands(
+        &mut self,
+        account: &str,
+        hands: Vec<String>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        match self.dir {
+            Some(ref dir) => {
+                let path = Self::account_file(dir, account);
+                if hands.is_empty() {
+                    return match std::fs::remove_file(&path) {
+                        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+                        _ => Ok(()),
+                    };
+                }
+                let text: String = hands
+                    .iter()
+                    .map(|hand| format!("{}\n\n", hand.trim_end()))
+                    .collect();
+                // Through a temporary file, so a crash never loses the hands
+                let tmp_path = path.with_extension("tmp");
+                std::fs::write(&tmp_path, text)?;
+                std::fs::rename(&tmp_path, path)?;
+            }
+            None if hands.is_empty() => {
+                self.hands.remove(account);
+            }
+            None => {
+                self.hands.insert(account.to_string(), hands);
+            }
+        }
+        Ok(())
+    }
+
+    fn save_pseudonyms(&self) -> Result<(), Box<dyn std::error::Error>> {
+        self.save_json(PSEUDONYMS_FILE, &self.pseudonyms)
+    }
+
+    /// Rename players in the hands of every account, `rename(account, table, name)` giving
+    /// the new name of `name` in a hand of `account` played at `table`, and the number of
+    /// hands changed
+    fn rename_in_hands(
+        &mut self,
+        mut rename: impl FnMut(&mut Self, &str, &str, &str, &str) -> Option<String>,
+    ) -> Result<usize, Box<dyn std::error::Error>> {
+        let mut changed = 0;
+        for account in self.accounts()? {
+            let mut hands = self.hands(&account)?;
+            let mut account_changed = false;
+            for hand in hands.iter_mut() {
+                let table = hand_table(hand).unwrap_or_default().to_string();
+                let renamed =
+                    rename_players(hand, |name| rename(self, &account, &table, hand, name));
+                if renamed != *hand {
+                    *hand = renamed;
+                    account_changed = true;
+                    changed += 1;
+                }
+            }
+            if account_changed {
+                self.store_hands(&account, hands)?;
+            }
+        }
+        self.save_pseudonyms()?;
+        Ok(changed)
+    }
+
+    /// Replace the names of the other players with their pseudonym at the table in every hand
+    /// played before `cutoff`, in seconds since the Unix epoch. Each account keeps its own
+    /// name, and hands without a date are left alone. Returns the number of hands changed.
+    pub fn pseudonymize_before(
+        &mut self,
+        cutoff: f64,
+    ) -> Result<usize, Box<dyn std::error::Error>> {
+        self.rename_in_hands(|archive, account, table, hand, name| {
+            if name == account || !hand_time(hand).is_some_and(|time| time < cutoff) {
+                return None;
+            }
+            let pseudonyms = archive.pseudonyms.entry(table.to_string()).or_default();
+            (!pseudonyms.is_pseudonym(name)).then(|| pseudonyms.pseudonym(name))
+        })
+    }
+
+    /// Delete the hands of `account` and replace its name with a pseudonym in the hands of
+    /// the other accounts, then forget which name the pseudonyms stood for. Returns the
+    /// number of hands of `account` deleted.
+    pub fn delete_account(&mut self, account: &str) -> Result<usize, Box<dyn std::error::Error>> {
+        let deleted = self.hands(account)?.len();
+        self.store_hands(account, Vec::new())?;
+        self.rename_in_hands(|archive, _, table, _, name| {
+            (name == account).then(|| {
+                archive
+                    .pseudonyms
+                    .entry(table.to_string())
+                    .or_default()
+                    .pseudonym(name)
+            })
+        })?;
+        for pseudonyms in self.pseudonyms.values_mut() {
+            pseudonyms.forget(account);
+        }
+        self.save_pseudonyms()?;
+        // The name is free to be registered again
+        if self.tokens.remove(account).is_some() {
+            self.save_json(TOKENS_FILE, &self.tokens)?;
+        }
+        Ok(deleted)
+    }
+
+    /// Add the hands of `account` to `zip` as `<prefix><hand id>.txt`, one file per hand, and
+    /// return the number of hands
+    pub fn write_zip<W: Write + Seek>(
+        &self,
+        zip: &mut ZipWriter<W>,
+        account: &str,
+        prefix: &str,
+    ) -> Result<usize, Box<dyn std::error::Error>> {
+        let hands = self.hands(account)?;
+        let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+        for (index, hand) in hands.iter().enumerate() {
+            let hand_id = hand
+                .lines()
+                .next()
+                .and_then(|header| header.split('#').nth(1))
+                .and_then(|rest| rest.split(':').next())
+                .map(str::trim)
+                .filter(|id| !id.is_empty() && !id.contains(['/', '\\']))
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("hand-{}", index + 1));
+            zip.start_file(format!("{}{}.txt", prefix, hand_id), options)?;
+            zip.write_all(hand.as_bytes())?;
+            zip.write_all(b"\n")?;
+        }
+        Ok(hands.len())
+    }
+
+    /// ZIP file of the hands of `account`, one `<hand id>.txt` per hand, and the number of hands
+    pub fn export_zip(
+        &self,
+        account: &str,
+    ) -> Result<(Vec<u8>, usize), Box<dyn std::error::Error>> {
+        let mut zip = ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        let hands = self.write_zip(&mut zip, account, "")?;
+        Ok((zip.finish()?.into_inner(), hands))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn hand(id: &str) -> String {
+        format!(
+            "PokerStars Hand #{}: Hold'em No Limit (1/2)\nSeat 1: a/b (200 in chips)",
+            id
+        )
+    }
+
+    #[test]
+    fn hands_export_as_zip() {
+        let dir = std::env::temp_dir().join(format!("pokers_archive_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let mut archive = HandArchive::open(dir.clone()).unwrap();
+        archive.record_hand("a/b", &hand("1")).unwrap();
+        archive
+            .record_hand("a/b", &format!("{}\n", hand("2")))
+            .unwrap();
+        archive.record_hand("carol", &hand("3")).unwrap();
+        assert!(dir.join("a%2Fb.txt").exists());
+
+        // The hands survive a restart
+        let archive = HandArchive::open(dir.clone()).unwrap();
+        assert_eq!(archive.hands("a/b").unwrap(), vec![hand("1"), hand("2")]);
+        assert!(archive.hands("dave").unwrap().is_empty());
+
+        let (bytes, count) = archive.export_zip("a/b").unwrap();
+        assert_eq!(count, 2);
+        let mut zip = zip::ZipArchive::new(std::io::Cursor::new(bytes)).unwrap();
+        let mut text = String::new();
+        zip.by_name("2.txt")
+            .unwrap()
+            .read_to_string(&mut text)
+            .unwrap();
+        assert_eq!(text, format!("{}\n", hand("2")));
+        assert_eq!(zip.len(), 2);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let mut archive = HandArchive::default();
+        archive.record_hand("carol", &hand("3")).unwrap();
+        assert_eq!(archive.export_zip("carol").unwrap().1, 1);
+        assert_eq!(archive.export_zip("dave").unwrap().1, 0);
+    }
+
+    #[test]
+    fn accounts_keep_the_token_they_were_opened_with() {
+        let dir = std::env::temp_dir().join(format!("pokers_accounts_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let mut archive = HandArchive::open(dir.clone()).unwrap();
+        archive.open_account("alice", "first").unwrap();
+        archive.open_account("alice", "second").unwrap();
+        archive.record_hand("alice", &hand("1")).unwrap();
+        assert_eq!(archive.account_token("alice"), Some("first"));
+        assert_eq!(archive.account_token("bob"), None);
+        // Only the hand files are accounts
+        assert_eq!(archive.accounts().unwrap(), ["alice"]);
+
+        let mut archive = HandArchive::open(dir.clone()).unwrap();
+        assert_eq!(archive.account_token("alice"), Some("first"));
+        archive.delete_account("alice").unwrap();
+        assert_eq!(archive.account_token("alice"), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn old_hands_are_pseudonymized_per_table() {
+        let hand = |table: &str, date: &str| {
+            format!(
+                "PokerStars Hand #1: Hold'em No Limit (1/2) - {} UTC\n\
+                 Table '{}' 2-max Seat #1 is the button\n\
+                 Seat 1: alice (200 in chips)\n\
+                 Seat 2: bob (200 in chips)\n\
+                 bob: folds",
+                date, table
+            )
+        };
+        let mut archive = HandArchive::default();
+        for hand in [
+            hand("main", "2026/10/01 8:00:00"),
+            hand("side", "2026/10/01 9:00:00"),
+            hand("main", "2026/10/17 8:00:00"),
+        ] {
+            archive.record_hand("alice", &hand).unwrap();
+        }
+        // 2026/10/10
+        let cutoff = 1_791_590_400.0;
+        assert_eq!(archive.pseudonymize_before(cutoff).unwrap(), 2);
+        let hands = archive.hands("alice").unwrap();
+        // Alice keeps her own name, bob goes by a pseudonym of each table
+        let bob = |hand: &str| {
+            hand.lines()
+                .find_map(|line| line.strip_suffix(": folds"))
+                .unwrap()
+                .to_string()
+        };
+        assert!(hands.iter().all(|hand| hand.contains("Seat 1: alice (")));
+        assert!(bob(&hands[0]).starts_with("Player-"));
+        assert!(bob(&hands[1]).starts_with("Player-"));
+        assert_ne!(bob(&hands[0]), bob(&hands[1]));
+        assert_eq!(bob(&hands[2]), "bob");
+        // Pseudonyms are never pseudonymized again
+        assert_eq!(archive.pseudonymize_before(cutoff).unwrap(), 0);
+        assert_eq!(archive.hands("alice").unwrap(), hands);
+    }
+}
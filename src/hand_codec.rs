@@ -0,0 +1,605 @@
+// hand_codec.rs - Compact binary storage of hands, actions as bet buckets of the action mask
+use crate::state::action::{Action, ActionEnum};
+use crate::state::card::{Card, N_CARDS};
+use crate::state::rules::TableRules;
+use crate::state::{PlayerState, State};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+pub const MAGIC: &[u8; 8] = b"PKRSHC1\0";
+pub const VERSION: u32 = 2;
+
+/// Set on an action token when the exact total bet follows it
+const EXACT: u8 = 0x80;
+
+/// Bets that land within this many chips of a bucket are stored as the bucket, and replayed
+/// chips within this many of the hand's are the same chips
+const TOLERANCE: f64 = 1e-9;
+
+/// Flags of the antes of a deal, see `State::post_antes`
+const ANTE: u8 = 1;
+const ANTE_BIG_BLIND: u8 = 2;
+const ANTE_FIRST: u8 = 4;
+
+/// Index of a set of distinct card indices among all the sets of its size (colexicographic
+/// rank), the same whatever order the cards are in
+fn combination_index(cards: &[u8]) -> u32 {
+    let mut sorted = cards.to_vec();
+    sorted.sort_unstable();
+    sorted
+        .iter()
+        .enumerate()
+        .map(|(k, &card)| binomial(card as u32, k as u32 + 1))
+        .sum()
+}
+
+/// The `k` card indices of `combination_index`, in increasing order
+fn from_combination_index(mut index: u32, k: u32) -> Vec<u8> {
+    let mut cards = Vec::with_capacity(k as usize);
+    for size in (1..=k).rev() {
+        let mut card = size - 1;
+        while binomial(card + 1, size) <= index {
+            card += 1;
+        }
+        index -= binomial(card, size);
+        cards.push(card as u8);
+    }
+    cards.reverse();
+    cards
+}
+
+fn binomial(n: u32, k: u32) -> u32 {
+    if k > n {
+        return 0;
+    }
+    (0..k).fold(1, |acc, i| acc * (n - i) / (i + 1))
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Bytes of an encoded hand or file, read front to back
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        if self.bytes.len() < n {
+            return Err("Truncated hand data".to_owned());
+        }
+        let (taken, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn f64(&mut self) -> Result<f64, String> {
+        Ok(f64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn varint(&mut self) -> Result<u64, String> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.u8()?;
+            value |= ((byte & 0x7F) as u64) << shift;
+            if byte < 0x80 {
+                return Ok(value);
+            }
+        }
+        Err("Invalid length in hand data".to_owned())
+    }
+
+    fn card(&mut self) -> Result<Card, String> {
+        let index = self.u8()?;
+        Card::from_index(index).ok_or_else(|| format!("Invalid card {}", index))
+    }
+}
+
+/// The hand `state` starts from, before the first action: its players, stacks, blinds, antes,
+/// rules and cards, the cards nobody saw dealt from the rest of the deck
+struct Deal {
+    hand_id: String,
+    button: u64,
+    sb: f64,
+    bb: f64,
+    /// Ante posted by every player, or by the big blind alone with `ANTE_BIG_BLIND`, 0.0 without
+    /// the `ANTE` flag
+    ante: f64,
+    ante_flags: u8,
+    rules: TableRules,
+    /// Starting stacks, blinds and antes included, by seat
+    stacks: Vec<f64>,
+    /// Hole cards by seat
+    hands: Vec<(Card, Card)>,
+    /// Board in the order it is dealt, the flop cards in index order
+    board: Vec<Card>,
+}
+
+impl Deal {
+    fn of(state: &State) -> Deal {
+        let mut board: Vec<Card> = state.public_cards.to_vec();
+        if board.len() >= 3 {
+            board[..3].sort_by_key(Card::index);
+        }
+        let ante = state
+            .players_state
+            .iter()
+            .map(|ps| ps.ante_chips)
+            .fold(0.0, f64::max);
+        let (_, big_blind) = State::blind_players(state.players_state.len() as u64, state.button);
+        let big_blind_ante = state
+            .players_state
+            .iter()
+            .all(|ps| ps.ante_chips <= 0.0 || ps.player == big_blind);
+        Deal {
+            hand_id: state.hand_id.clone(),
+            button: state.button,
+            sb: state.sb,
+            bb: state.bb,
+            ante,
+            ante_flags: match ante > 0.0 {
+                true if big_blind_ante => ANTE | ANTE_BIG_BLIND,
+                true => ANTE,
+                false => 0,
+            },
+            rules: state.rules,
+            stacks: state
+                .players_state
+                .iter()
+                .map(|ps| ps.stake + ps.bet_chips + ps.pot_chips + ps.held_back)
+                .collect(),
+            hands: state.players_state.iter().map(|ps| ps.hand).collect(),
+            board,
+        }
+    }
+
+    /// The state before the first action, dealt the same way as `load_hand_histories` does,
+    /// then played under the rules and antes of the deal like the server starts its hands
+    fn initial_state(&self) -> Result<State, String> {
+        let n = self.stacks.len();
+        let dealt: Vec<Card> = self
+            .hands
+            .iter()
+            .flat_map(|h| [h.0, h.1])
+            .chain(self.board.iter().copied())
+            .collect();
+        let mut deck = Vec::with_capacity(N_CARDS);
+        for i in 0..n {
+            let hand = self.hands[(self.button as usize + i + 1) % n];
+            deck.extend([hand.0, hand.1]);
+        }
+        deck.extend(self.board.iter().copied());
+        deck.extend(Card::collect().into_iter().filter(|c| !dealt.contains(c)));
+
+        let max_stack = self.stacks.iter().copied().fold(self.bb, f64::max);
+        let mut state = State::from_deck(
+            n as u64,
+            self.button,
+            self.sb,
+            self.bb,
+            max_stack,
+            deck,
+            false,
+            0,
+            0.0,
+        )
+        .map_err(|_| "Invalid blinds or stacks".to_owned())?
+        .with_ids(Some(self.hand_id.clone()), None, None);
+        for ps in &mut state.players_state {
+            ps.stake = self.stacks[ps.player as usize] - ps.bet_chips;
+        }
+        if self.rules != TableRules::default() {
+            state = state.with_rules(self.rules).map_err(|e| e.to_string())?;
+        }
+        if self.ante_flags & ANTE != 0 {
+            state = state
+                .post_antes(
+                    self.ante,
+                    self.ante_flags & ANTE_BIG_BLIND != 0,
+                    self.ante_flags & ANTE_FIRST != 0,
+                )
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(state)
+    }
+
+    fn write(&self, out: &mut Vec<u8>) {
+        write_varint(out, self.hand_id.len() as u64);
+        out.extend(self.hand_id.as_bytes());
+        out.extend([self.stacks.len() as u8, self.button as u8]);
+        out.extend(self.sb.to_le_bytes());
+        out.extend(self.bb.to_le_bytes());
+        out.push(self.ante_flags);
+        if self.ante_flags & ANTE != 0 {
+            out.extend(self.ante.to_le_bytes());
+        }
+        // Rules only take bytes on the tables that change them
+        match self.rules == TableRules::default() {
+            true => write_varint(out, 0),
+            false => {
+                let rules = serde_json::to_vec(&self.rules).expect("rules serialize");
+                write_varint(out, rules.len() as u64);
+                out.extend(rules);
+            }
+        }
+        // Most tables start everybody with the same stack
+        let equal = self
+            .stacks
+            .iter()
+            .all(|&s| (s - self.stacks[0]).abs() <= TOLERANCE);
+        out.push(equal as u8);
+        let stacks = if equal {
+            &self.stacks[..1]
+        } else {
+            &self.stacks
+        };
+        for stack in stacks {
+            out.extend(stack.to_le_bytes());
+        }
+        for hand in &self.hands {
+            out.extend([hand.0.index(), hand.1.index()]);
+        }
+        out.push(self.board.len() as u8);
+        if self.board.len() >= 3 {
+            let flop: Vec<u8> = self.board[..3].iter().map(Card::index).collect();
+            out.extend((combination_index(&flop) as u16).to_le_bytes());
+            out.extend(self.board[3..].iter().map(Card::index));
+        }
+    }
+
+    fn read(reader: &mut Reader) -> Result<Deal, String> {
+        let length = reader.varint()? as usize;
+        let hand_id = String::from_utf8(reader.take(length)?.to_vec())
+            .map_err(|_| "Invalid hand id".to_owned())?;
+        let (n, button) = (reader.u8()? as usize, reader.u8()? as u64);
+        let (sb, bb) = (reader.f64()?, reader.f64()?);
+        let ante_flags = reader.u8()?;
+        let ante = match ante_flags & ANTE {
+            0 => 0.0,
+            _ => reader.f64()?,
+        };
+        let rules = match reader.varint()? as usize {
+            0 => TableRules::default(),
+            length => serde_json::from_slice(reader.take(length)?)
+                .map_err(|e| format!("Invalid table rules: {}", e))?,
+        };
+        let stacks = match reader.u8()? {
+            0 => (0..n).map(|_| reader.f64()).collect::<Result<_, _>>()?,
+            _ => vec![reader.f64()?; n],
+        };
+        let hands = (0..n)
+            .map(|_| Ok((reader.card()?, reader.card()?)))
+            .collect::<Result<_, String>>()?;
+        let mut board = Vec::new();
+        match reader.u8()? {
+            0 => {}
+            length @ 3..=5 => {
+                let index = u16::from_le_bytes(reader.take(2)?.try_into().unwrap());
+                for card in from_combination_index(index as u32, 3) {
+                    board.push(Card::from_index(card).ok_or("Invalid flop")?);
+                }
+                for _ in 3..length {
+                    board.push(reader.card()?);
+                }
+            }
+            length => return Err(format!("Invalid board of {} cards", length)),
+        }
+        Ok(Deal {
+            hand_id,
+            button,
+            sb,
+            bb,
+            ante,
+            ante_flags,
+            rules,
+            stacks,
+            hands,
+            board,
+        })
+    }
+}
+
+/// Entry of `State::legal_action_mask(n_bet_buckets)` closest to `action`, and whether the
+/// entry plays it exactly
+fn action_token(state: &State, action: Action, n_bet_buckets: usize) -> (u8, bool) {
+    match action.action {
+        ActionEnum::Fold => (0, true),
+        ActionEnum::CheckCall => (1, true),
+        ActionEnum::BetRaise => {
+            let distance = |index: usize| {
+                state
+                    .mask_action(index, n_bet_buckets)
+                    .map(|bucket| (bucket.amount - action.amount).abs())
+            };
+            let nearest = (2..2 + n_bet_buckets)
+                .filter_map(|index| distance(index).map(|d| (index, d)))
+                .min_by(|a, b| a.1.total_cmp(&b.1));
+            match nearest {
+                Some((index, d)) => (index as u8, d <= TOLERANCE),
+                // No bucket is legal: the raise of a capped or short stack
+                None => (2 + n_bet_buckets as u8 - 1, false),
+            }
+        }
+    }
+}
+
+/// Chips of the same player in two replays of a hand, equal up to `TOLERANCE`
+fn same_chips(a: &State, b: &State, chips: impl Fn(&PlayerState) -> f64) -> bool {
+    a.players_state
+        .iter()
+        .zip(b.players_state.iter())
+        .all(|(x, y)| (chips(x) - chips(y)).abs() <= TOLERANCE)
+}
+
+/// Append `state` to `out`: the deal, then one byte per action, the index of the action in
+/// `legal_action_mask(n_bet_buckets)`, followed by the exact total bet when the bet is off
+/// the buckets. The hand is replayed to check that decoding gives back the same actions, and
+/// rejected when it does not, e.g. for bomb pots, straddles or dead blinds.
+pub fn encode_hand(state: &State, n_bet_buckets: usize, out: &mut Vec<u8>) -> Result<(), String> {
+    if !state.second_board.is_empty() {
+        return Err("Hands with a second board cannot be compressed".to_owned());
+    }
+    let start = out.len();
+    let rejected = |out: &mut Vec<u8>, reason: String| {
+        out.truncate(start);
+        Err(format!(
+            "Hand {} does not replay from its deal: {}",
+            state.hand_id, reason
+        ))
+    };
+
+    let mut deal = Deal::of(state);
+    let mut replay = deal.initial_state()?;
+    // Only a player short of their blind and the ante tells whether the ante was paid first
+    if !same_chips(&replay, state, |ps| ps.ante_chips) {
+        deal.ante_flags |= ANTE_FIRST;
+        replay = deal.initial_state()?;
+    }
+    deal.write(out);
+    if !same_chips(&replay, state, |ps| ps.ante_chips) {
+        return rejected(out, "the antes differ".to_owned());
+    }
+    write_varint(out, state.action_list.len() as u64);
+
+    for record in &state.action_list {
+        let (token, exact) = action_token(&replay, record.action, n_bet_buckets);
+        let action = match replay.mask_action(token as usize, n_bet_buckets) {
+            Some(action) if exact => {
+                out.push(token);
+                action
+            }
+            _ if record.action.action == ActionEnum::BetRaise => {
+                out.push(token | EXACT);
+                out.extend(record.action.amount.to_le_bytes());
+                record.action
+            }
+            _ => return rejected(out, format!("{:?} was not legal", record.action.action)),
+        };
+        replay = replay.apply_action(action);
+        let replayed = replay.action_list.last();
+        if replayed.is_none_or(|r| {
+            r.action.action != record.action.action
+                || (r.action.amount - record.action.amount).abs() > TOLERANCE
+                || (r.chips - record.chips).abs() > TOLERANCE
+        }) {
+            return rejected(
+                out,
+                format!("{:?} on the {:?}", record.action, record.stage),
+            );
+        }
+    }
+    if !same_chips(&replay, state, |ps| ps.reward) {
+        return rejected(out, "the rewards differ".to_owned());
+    }
+    Ok(())
+}
+
+/// Read back a hand of `encode_hand`, played again through the engine: the state before the
+/// first action and the final state
+fn decode_hand(reader: &mut Reader, n_bet_buckets: usize) -> Result<(State, State), String> {
+    let initial = Deal::read(reader)?.initial_state()?;
+    let mut state = initial.clone();
+    for _ in 0..reader.varint()? {
+        let token = reader.u8()?;
+        let action = if token & EXACT != 0 {
+            Action::new(ActionEnum::BetRaise, reader.f64()?)
+        } else {
+            state
+                .mask_action(token as usize, n_bet_buckets)
+                .ok_or_else(|| format!("Illegal action {} in hand {}", token, state.hand_id))?
+        };
+        state = state.apply_action(action);
+    }
+    Ok((initial, state))
+}
+
+/// Hands in the compressed format, after a header with the number of bet buckets
+pub fn encode_hands(states: &[State], n_bet_buckets: usize) -> Result<Vec<u8>, String> {
+    if !(1..EXACT as usize - 2).contains(&n_bet_buckets) {
+        return Err(format!(
+            "n_bet_buckets must be between 1 and {}",
+            EXACT as usize - 3
+        ));
+    }
+    let mut out = Vec::with_capacity(64 * states.len());
+    out.extend(MAGIC);
+    out.extend(VERSION.to_le_bytes());
+    out.push(n_bet_buckets as u8);
+    write_varint(&mut out, states.len() as u64);
+    for state in states {
+        encode_hand(state, n_bet_buckets, &mut out)?;
+    }
+    Ok(out)
+}
+
+pub fn decode_hands(bytes: &[u8]) -> Result<Vec<State>, String> {
+    Ok(decode_deals(bytes)?
+        .into_iter()
+        .map(|(_, state)| state)
+        .collect())
+}
+
+/// Hands of `encode_hands`, each as the state it was dealt in and its final state, to replay
+/// the decisions in between
+pub fn decode_deals(bytes: &[u8]) -> Result<Vec<(State, State)>, String> {
+    let mut reader = Reader { bytes };
+    if reader.take(MAGIC.len()).ok() != Some(&MAGIC[..]) {
+        return Err("Not a compressed hands file".to_owned());
+    }
+    let version = u32::from_le_bytes(reader.take(4)?.try_into().unwrap());
+    if version != VERSION {
+        return Err(format!("Unsupported compressed hands version {}", version));
+    }
+    let n_bet_buckets = reader.u8()? as usize;
+    let count = reader.varint()?;
+    (0..count)
+        .map(|_| decode_hand(&mut reader, n_bet_buckets))
+        .collect()
+}
+
+/// Compress hands for storage: each action takes one byte, the entry of
+/// `State.legal_action_mask(n_bet_buckets)` it played, plus the exact total bet when the bet
+/// is off the buckets; the flop is stored as one index of the unordered set of its cards
+#[pyfunction]
+#[pyo3(signature = (states, n_bet_buckets=10))]
+pub fn compress_hands(py: Python, states: Vec<State>, n_bet_buckets: usize) -> PyResult<PyObject> {
+    let bytes = encode_hands(&states, n_bet_buckets).map_err(PyValueError::new_err)?;
+    Ok(PyBytes::new(py, &bytes).into())
+}
+
+/// The hands of `compress_hands`, replayed through the engine
+#[pyfunction]
+pub fn decompress_hands(data: &[u8]) -> PyResult<Vec<State>> {
+    decode_hands(data).map_err(PyValueError::new_err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::schema;
+
+    /// The engine recorded the same actions in both hands, chips within `TOLERANCE`
+    fn assert_same_actions(read: &State, state: &State) {
+        assert_eq!(read.action_list.len(), state.action_list.len());
+        for (a, b) in read.action_list.iter().zip(&state.action_list) {
+            assert_eq!((a.player, a.action.action), (b.player, b.action.action));
+            assert!((a.action.amount - b.action.amount).abs() <= TOLERANCE);
+            assert!((a.chips - b.chips).abs() <= TOLERANCE);
+        }
+        assert!(same_chips(read, state, |ps| ps.reward));
+    }
+
+    fn played(seed: u64) -> State {
+        let mut state = State::from_seed(4, seed % 4, 1.0, 2.0, 200.0, seed, false, 0.0).unwrap();
+        let mut k = seed;
+        while !state.final_state {
+            k = k
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            let action = match (k >> 33) % 6 {
+                0 => Action::new(ActionEnum::Fold, 0.0),
+                1 | 2 => Action::new(ActionEnum::CheckCall, 0.0),
+                // A bet off the buckets
+                3 => Action::new(ActionEnum::BetRaise, state.min_bet + 7.25),
+                _ => state
+                    .mask_action(2 + (k >> 40) as usize % 10, 10)
+                    .unwrap_or(Action::new(ActionEnum::CheckCall, 0.0)),
+            };
+            state = state.apply_action(action);
+        }
+        state
+    }
+
+    #[test]
+    fn combination_indices_are_a_bijection() {
+        assert_eq!(combination_index(&[2, 0, 1]), 0);
+        assert_eq!(combination_index(&[51, 50, 49]), binomial(52, 3) - 1);
+        for index in (0..binomial(52, 3)).step_by(97) {
+            let cards = from_combination_index(index, 3);
+            assert_eq!(combination_index(&cards), index);
+        }
+    }
+
+    #[test]
+    fn hands_replay_from_the_compressed_format() {
+        let states: Vec<State> = (0..40).map(played).collect();
+        let bytes = encode_hands(&states, 10).unwrap();
+        let decoded = decode_hands(&bytes).unwrap();
+
+        assert_eq!(decoded.len(), states.len());
+        for (state, read) in states.iter().zip(&decoded) {
+            assert_same_actions(read, state);
+            assert_eq!(read.hand_id, state.hand_id);
+            assert!(read.final_state);
+            for (a, b) in read.players_state.iter().zip(&state.players_state) {
+                assert_eq!(a.hand, b.hand);
+            }
+            let mut board = state.public_cards.to_vec();
+            let mut read_board = read.public_cards.to_vec();
+            board.sort_by_key(Card::index);
+            read_board.sort_by_key(Card::index);
+            assert_eq!(read_board, board);
+        }
+
+        let json: usize = states
+            .iter()
+            .map(|s| schema::to_document(s).unwrap().to_string().len())
+            .sum();
+        assert!(
+            bytes.len() * 20 < json,
+            "{} bytes against {}",
+            bytes.len(),
+            json
+        );
+    }
+
+    #[test]
+    fn antes_and_table_rules_are_kept() {
+        let rules = TableRules {
+            hand_cap: Some(60.0),
+            seven_deuce_bounty: 1.0,
+            ..TableRules::default()
+        };
+        let mut hands = Vec::new();
+        for (seed, big_blind_ante, ante_first) in [(1, false, false), (2, true, true)] {
+            let mut state = State::from_seed(4, 1, 1.0, 2.0, 200.0, seed, false, 0.0).unwrap();
+            // The big blind is short of the ante
+            state.players_state[3].stake = 0.25;
+            state = state
+                .with_rules(rules)
+                .and_then(|state| state.post_antes(0.5, big_blind_ante, ante_first))
+                .unwrap();
+            while !state.final_state {
+                state = state.apply_action(Action::new(ActionEnum::BetRaise, state.min_bet));
+            }
+            hands.push(state);
+        }
+        let decoded = decode_hands(&encode_hands(&hands, 10).unwrap()).unwrap();
+        for (state, read) in hands.iter().zip(&decoded) {
+            assert_eq!(read.rules, rules);
+            assert_same_actions(read, state);
+            assert!(same_chips(read, state, |ps| ps.ante_chips));
+            assert!(same_chips(read, state, |ps| ps.held_back));
+        }
+    }
+
+    #[test]
+    fn hands_that_do_not_replay_are_rejected() {
+        let deck = crate::seeds::shuffled_deck(5);
+        let bomb_pot = State::bomb_pot(3, 0, 2.0, 2.0, 100.0, deck, false, false, 5).unwrap();
+        let bomb_pot = bomb_pot.apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+        assert!(encode_hands(&[bomb_pot], 10).is_err());
+        assert!(encode_hands(&[], 0).is_err());
+        assert!(decode_hands(b"PKRSHC1\0").is_err());
+    }
+}
@@ -0,0 +1,613 @@
+// hand_history.rs - Finished hands written as PokerStars hand histories
+use crate::state::action::{ActionEnum, Coercion};
+use crate::state::card::{Card, CardSuit};
+use crate::state::rules::GameVariant;
+use crate::state::stage::Stage;
+use crate::state::State;
+use std::fmt::Write;
+
+/// "Ah" for the ace of hearts, the notation of the histories
+pub(crate) fn card_text(card: Card) -> String {
+    let rank = card.rank.to_char();
+    let suit = match card.suit {
+        CardSuit::Clubs => 'c',
+        CardSuit::Diamonds => 'd',
+        CardSuit::Hearts => 'h',
+        CardSuit::Spades => 's',
+    };
+    format!("{}{}", rank, suit)
+}
+
+fn cards_text(cards: &[Card]) -> String {
+    let cards: Vec<String> = cards.iter().map(|&card| card_text(card)).collect();
+    format!("[{}]", cards.join(" "))
+}
+
+/// Game of the header, named the way the sites name it
+fn game_text(variant: GameVariant) -> &'static str {
+    match variant {
+        GameVariant::Holdem => "Hold'em No Limit",
+        GameVariant::ShortDeck => "6+ Hold'em No Limit",
+        GameVariant::AceToFive => "Hold'em Ace-to-Five Lowball No Limit",
+    }
+}
+
+/// Chips without a trailing ".0"
+fn amount(chips: f64) -> String {
+    format!("{}", (chips * 100.0).round() / 100.0)
+}
+
+/// "2026/10/17 8:00:00 UTC" for seconds since the Unix epoch
+fn date_text(timestamp: f64) -> String {
+    let seconds = timestamp.max(0.0) as i64;
+    let (days, time) = (seconds / 86_400, seconds % 86_400);
+    // Civil date of a day count, after Howard Hinnant's days_from_civil
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{}/{:02}/{:02} {}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        time / 3_600,
+        time % 3_600 / 60,
+        time % 60
+    )
+}
+
+/// Seconds since the Unix epoch of a "2026/10/17 8:00:00 UTC" written by `date_text`
+fn parse_date_text(text: &str) -> Option<f64> {
+    let (date, time) = text.strip_suffix(" UTC")?.split_once(' ')?;
+    let mut date = date.split('/').map(str::parse::<i64>);
+    let (year, month, day) = (date.next()?.ok()?, date.next()?.ok()?, date.next()?.ok()?);
+    let mut time = time.split(':').map(str::parse::<i64>);
+    let (hours, minutes, seconds) = (time.next()?.ok()?, time.next()?.ok()?, time.next()?.ok()?);
+    // Howard Hinnant's days_from_civil
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+    Some((days * 86_400 + hours * 3_600 + minutes * 60 + seconds) as f64)
+}
+
+/// When the hand of a history written by `write_hand_history` was played, in seconds since
+/// the Unix epoch. None when its header has no date.
+pub fn hand_time(history: &str) -> Option<f64> {
+    let header = history.lines().next()?;
+    parse_date_text(header.rsplit_once(" - ")?.1)
+}
+
+/// Table of a hand history written by `write_hand_history`
+pub fn hand_table(history: &str) -> Option<&str> {
+    let line = history.lines().find(|line| line.starts_with("Table '"))?;
+    line["Table '".len()..]
+        .rsplit_once("' ")
+        .map(|(table, _)| table)
+}
+
+/// Length of the "Seat 3: " a line starts with
+fn seat_prefix(line: &str) -> Option<usize> {
+    let rest = line.strip_prefix("Seat ")?;
+    let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+    (digits > 0 && rest[digits..].starts_with(": ")).then_some("Seat ".len() + digits + 2)
+}
+
+/// A hand history written by `write_hand_history` with its players renamed: `rename` is
+/// called with the name of every player seated and returns their new name, or None to keep
+/// it. Names are only replaced where the history writes a player, never inside the table
+/// name.
+pub fn rename_players(history: &str, mut rename: impl FnMut(&str) -> Option<String>) -> String {
+    let mut names: Vec<(&str, Option<String>)> = history
+        .lines()
+        .take_while(|line| !line.starts_with("*** "))
+        .filter_map(|line| {
+            let name = line[seat_prefix(line)?..].rsplit_once(" (")?.0;
+            Some((name, rename(name)))
+        })
+        .collect();
+    // Longest first, so a name never matches the start of a longer one
+    names.sort_by_key(|(name, _)| std::cmp::Reverse(name.len()));
+    // The new name of the player `rest` starts with, followed by one of `ends`, and the
+    // length of their name
+    let player = |rest: &str, ends: &[&str]| {
+        names.iter().find_map(|(name, renamed)| {
+            let after = rest.strip_prefix(name)?;
+            ends.iter()
+                .any(|end| match *end {
+                    "" => after.is_empty(),
+                    end => after.starts_with(end),
+                })
+                .then(|| {
+                    (
+                        renamed.clone().unwrap_or_else(|| name.to_string()),
+                        name.len(),
+                    )
+                })
+        })
+    };
+
+    let mut text = String::new();
+    for line in history.lines() {
+        let (start, ends): (usize, &[&str]) = if let Some(prefix) = seat_prefix(line) {
+            (prefix, &[" "])
+        } else if line.starts_with("Dealt to ") {
+            ("Dealt to ".len(), &[" ["])
+        } else if line.starts_with("Uncalled bet (") {
+            match line.find(") returned to ") {
+                Some(at) => (at + ") returned to ".len(), &[""]),
+                None => (line.len(), &[]),
+            }
+        } else {
+            (0, &[": ", " has timed out", " collected "])
+        };
+        match player(&line[start..], ends) {
+            Some((name, len)) => {
+                text.push_str(&line[..start]);
+                text.push_str(&name);
+                text.push_str(&line[start + len..]);
+            }
+            None => text.push_str(line),
+        }
+        text.push('\n');
+    }
+    if !history.ends_with('\n') {
+        text.pop();
+    }
+    text
+}
+
+/// Header of a street and the board it deals
+fn street_header(stage: Stage, board: &[Card]) -> Option<String> {
+    match stage {
+        Stage::Flop if board.len() >= 3 => {
+            Some(format!("*** FLOP *** {}", cards_text(&board[..3])))
+        }
+        Stage::Turn if board.len() >= 4 => Some(format!(
+            "*** TURN *** {} {}",
+            cards_text(&board[..3]),
+            cards_text(&board[3..4])
+        )),
+        Stage::River if board.len() >= 5 => Some(format!(
+            "*** RIVER *** {} {}",
+            cards_text(&board[..4]),
+            cards_text(&board[4..5])
+        )),
+        _ => None,
+    }
+}
+
+/// The finished hand `state` as a PokerStars hand history, which `load_hand_histories` reads
+/// back. `seats` holds the seat number and name of each player, in `State` player order, and
+/// `hero` the player whose hole cards are dealt face up; the other hands only show at the
+/// showdown. Chips the blinds cannot account for before the first action (antes, dead blinds,
+/// straddles, bomb pots) are posted as antes. `taken` holds the rake and jackpot drops taken
+/// from each player's winnings, empty for an unraked game.
+pub fn write_hand_history(
+    state: &State,
+    table: &str,
+    seats: &[(u8, String)],
+    hero: Option<u64>,
+    taken: &[f64],
+) -> String {
+    let n_players = state.players_state.len();
+    let name = |player: u64| {
+        seats
+            .get(player as usize)
+            .map(|(_, name)| name.clone())
+            .unwrap_or_else(|| format!("Player {}", player))
+    };
+    let seat = |player: u64| {
+        seats
+            .get(player as usize)
+            .map(|(seat, _)| *seat)
+            .unwrap_or(player as u8 + 1)
+    };
+    let invested =
+        |player: usize| -> f64 { state.players_state[player].invested.iter().sum::<f64>() };
+
+    let mut text = String::new();
+    let date = state
+        .action_list
+        .iter()
+        .find_map(|record| record.timestamp)
+        .map(|timestamp| format!(" - {}", date_text(timestamp)))
+        .unwrap_or_default();
+    let _ = writeln!(
+        text,
+        "PokerStars Hand #{}: {} ({}/{}){}",
+        state.hand_id,
+        game_text(state.rules.variant),
+        amount(state.sb),
+        amount(state.bb),
+        date
+    );
+    let _ = writeln!(
+        text,
+        "Table '{}' {}-max Seat #{} is the button",
+        table,
+        n_players,
+        seat(state.button)
+    );
+
+    // Stacks the hand started with, what is left of them is tracked to mark the all-ins
+    let mut stacks: Vec<f64> = state
+        .players_state
+        .iter()
+        .enumerate()
+        .map(|(player, ps)| ps.stake + ps.held_back + invested(player))
+        .collect();
+    for (player, stack) in stacks.iter().enumerate() {
+        let _ = writeln!(
+            text,
+            "Seat {}: {} ({} in chips)",
+            seat(player as u64),
+            name(player as u64),
+            amount(*stack)
+        );
+    }
+
+    // Forced bets: everything put in preflop before acting
+    let mut forced: Vec<f64> = (0..n_players)
+        .map(|player| {
+            let acted: f64 = state
+                .action_list
+                .iter()
+                .filter(|record| record.player as usize == player && record.stage == Stage::Preflop)
+                .map(|record| record.chips)
+                .sum();
+            let ps = &state.players_state[player];
+            (ps.invested[0] - ps.ante_chips - acted).max(0.0)
+        })
+        .collect();
+    // The blinds come out of the forced bets first, the rest is posted as antes before them
+    // with the antes themselves
+    let mut street_bet = 0.0f64;
+    let mut blinds = Vec::new();
+    for (offset, blind, label) in [(1, state.sb, "small blind"), (2, state.bb, "big blind")] {
+        let player = (state.button as usize + offset) % n_players;
+        let posted = blind.min(forced[player]);
+        if posted > 0.0 {
+            forced[player] -= posted;
+            street_bet = street_bet.max(posted);
+            blinds.push((player, label, posted));
+        }
+    }
+    for (ante, ps) in forced.iter_mut().zip(&state.players_state) {
+        *ante += ps.ante_chips;
+    }
+    for (player, ante) in forced.iter().enumerate() {
+        if *ante > 0.0 {
+            stacks[player] -= ante;
+            let _ = writeln!(
+                text,
+                "{}: posts the ante {}",
+                name(player as u64),
+                amount(*ante)
+            );
+        }
+    }
+    for (player, label, posted) in blinds {
+        stacks[player] -= posted;
+        let _ = writeln!(
+            text,
+            "{}: posts {} {}",
+            name(player as u64),
+            label,
+            amount(posted)
+        );
+    }
+
+    let _ = writeln!(text, "*** HOLE CARDS ***");
+    if let Some(hero) = hero.filter(|&hero| (hero as usize) < n_players) {
+        let hand = state.players_state[hero as usize].hand;
+        let _ = writeln!(
+            text,
+            "Dealt to {} {}",
+            name(hero),
+            cards_text(&[hand.0, hand.1])
+        );
+    }
+
+    let board = &state.public_cards[..];
+    let mut stage = Stage::Preflop;
+    for record in &state.action_list {
+        if record.stage != stage {
+            for street in [Stage::Flop, Stage::Turn, Stage::River] {
+                if street > stage && street <= record.stage {
+                    if let Some(header) = street_header(street, board) {
+                        let _ = writeln!(text, "{}", header);
+                    }
+                }
+            }
+            stage = record.stage;
+            street_bet = 0.0;
+        }
+
+        let player = record.player as usize;
+        let who = name(record.player);
+        match record.coercion {
+            Some(Coercion::Timeout) => {
+                let _ = writeln!(text, "{} has timed out", who);
+            }
+            Some(Coercion::Disconnected) => {
+                let _ = writeln!(text, "{} has timed out while disconnected", who);
+                continue;
+            }
+            _ => (),
+        }
+        stacks[player] -= record.chips;
+        let all_in = if stacks[player] <= 0.0 && record.chips > 0.0 {
+            " and is all-in"
+        } else {
+            ""
+        };
+        let line = match record.action.action {
+            ActionEnum::Fold => "folds".to_string(),
+            ActionEnum::CheckCall if record.chips == 0.0 => "checks".to_string(),
+            ActionEnum::CheckCall => format!("calls {}", amount(record.chips)),
+            // An all-in for less than the call is a call
+            ActionEnum::BetRaise if record.chips <= record.to_call => {
+                format!("calls {}", amount(record.chips))
+            }
+            ActionEnum::BetRaise if street_bet == 0.0 => {
+                street_bet = record.action.amount;
+                format!("bets {}", amount(record.chips))
+            }
+            ActionEnum::BetRaise => {
+                let raise = record.action.amount - street_bet;
+                street_bet = street_bet.max(record.action.amount);
+                format!(
+                    "raises {} to {}",
+                    amount(raise),
+                    amount(record.action.amount)
+                )
+            }
+        };
+        let _ = writeln!(text, "{}: {}{}", who, line, all_in);
+    }
+    // The rest of the board, run out after an all-in
+    for street in [Stage::Flop, Stage::Turn, Stage::River] {
+        if street > stage {
+            if let Some(header) = street_header(street, board) {
+                let _ = writeln!(text, "{}", header);
+            }
+        }
+    }
+
+    // The bet nobody called on the last street goes back to its owner. Antes are dead money,
+    // not part of the preflop bets.
+    let mut uncalled = vec![0.0; n_players];
+    let last_street = stage.min(Stage::River) as usize;
+    let mut bets: Vec<(usize, f64)> = state
+        .players_state
+        .iter()
+        .enumerate()
+        .map(|(player, ps)| {
+            let antes = if last_street == 0 { ps.ante_chips } else { 0.0 };
+            (player, ps.invested[last_street] - antes)
+        })
+        .collect();
+    bets.sort_by(|a, b| b.1.total_cmp(&a.1));
+    if let [(player, top), (_, second), ..] = bets[..] {
+        if top > second {
+            uncalled[player] = top - second;
+            let _ = writeln!(
+                text,
+                "Uncalled bet ({}) returned to {}",
+                amount(top - second),
+                name(player as u64)
+            );
+        }
+    }
+
+    let in_to_the_end: Vec<usize> = (0..n_players)
+        .filter(|&player| state.players_state[player].folded_on.is_none())
+        .collect();
+    let showdown = in_to_the_end.len() > 1;
+    if showdown {
+        let _ = writeln!(text, "*** SHOW DOWN ***");
+        for &player in &in_to_the_end {
+            let hand = state.players_state[player].hand;
+            let _ = writeln!(
+                text,
+                "{}: shows {}",
+                name(player as u64),
+                cards_text(&[hand.0, hand.1])
+            );
+        }
+    }
+    let collected: Vec<f64> = (0..n_players)
+        .map(|player| {
+            let taken = taken.get(player).copied().unwrap_or(0.0);
+            state.players_state[player].reward + invested(player) - uncalled[player] - taken
+        })
+        .collect();
+    for (player, won) in collected.iter().enumerate() {
+        if *won > 0.0 {
+            let _ = writeln!(
+                text,
+                "{} collected {} from pot",
+                name(player as u64),
+                amount(*won)
+            );
+        }
+    }
+
+    let _ = writeln!(text, "*** SUMMARY ***");
+    let _ = writeln!(
+        text,
+        "Total pot {} | Rake {}",
+        amount(state.chips_in_pot() - uncalled.iter().sum::<f64>()),
+        amount(taken.iter().sum())
+    );
+    if !board.is_empty() {
+        let _ = writeln!(text, "Board {}", cards_text(board));
+    }
+    for (player, (ps, won)) in state.players_state.iter().zip(&collected).enumerate() {
+        let result = match ps.folded_on {
+            Some(Stage::Preflop) => "folded before Flop".to_string(),
+            Some(street) => format!("folded on the {:?}", street),
+            None if showdown => format!(
+                "showed {} and {}",
+                cards_text(&[ps.hand.0, ps.hand.1]),
+                if *won > 0.0 {
+                    format!("won ({})", amount(*won))
+                } else {
+                    "lost".to_string()
+                }
+            ),
+            None => format!("collected ({})", amount(*won)),
+        };
+        let _ = writeln!(
+            text,
+            "Seat {}: {} {}",
+            seat(player as u64),
+            name(player as u64),
+            result
+        );
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datasets::load_hand_histories;
+    use crate::state::action::Action;
+    use crate::state::rules::TableRules;
+
+    #[test]
+    fn histories_load_back() {
+        // Button 0: player 1 posts the small blind, player 2 the big blind
+        let mut state = State::from_seed(3, 0, 1.0, 2.0, 200.0, 7, false, 0.0).unwrap();
+        for action in [
+            Action::new(ActionEnum::BetRaise, 6.0),
+            Action::new(ActionEnum::Fold, 0.0),
+            Action::new(ActionEnum::CheckCall, 0.0),
+            Action::new(ActionEnum::CheckCall, 0.0),
+            Action::new(ActionEnum::BetRaise, 8.0),
+            Action::new(ActionEnum::CheckCall, 0.0),
+            Action::new(ActionEnum::CheckCall, 0.0),
+            Action::new(ActionEnum::BetRaise, 30.0),
+            Action::new(ActionEnum::Fold, 0.0),
+        ] {
+            state = state.apply_action(action);
+        }
+        assert!(state.final_state);
+
+        let seats = [
+            (1, "Alice".to_string()),
+            (3, "Bob".to_string()),
+            (6, "Carol".to_string()),
+        ];
+        let history = write_hand_history(&state, "main", &seats, Some(0), &[]);
+        assert!(history.contains("Table 'main' 3-max Seat #1 is the button"));
+        assert!(history.contains("Bob: posts small blind 1\n"));
+        assert!(history.contains("Alice: raises 4 to 6\n"));
+        assert!(history.contains("Dealt to Alice ["));
+        assert!(history.contains("Uncalled bet (30) returned to "));
+        assert!(!history.contains("*** SHOW DOWN ***"));
+
+        let path = std::env::temp_dir().join(format!("pokers_history_{}.txt", std::process::id()));
+        std::fs::write(&path, format!("{}\n\n{}", history, history)).unwrap();
+        let dataset = load_hand_histories(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!((dataset.hands, dataset.skipped), (2, 0));
+        // Alice's decisions, her cards are the only ones dealt face up
+        assert!(dataset
+            .samples
+            .iter()
+            .all(|sample| sample.player == "Alice"));
+        assert!(!dataset.samples.is_empty());
+    }
+
+    #[test]
+    fn antes_are_not_uncalled_bets() {
+        // The big blind posts the ante for the table and everybody folds to it: only the part
+        // of the blind the small blind did not call goes back
+        let mut state = State::from_seed(3, 0, 1.0, 2.0, 200.0, 7, false, 0.0)
+            .unwrap()
+            .post_antes(2.0, true, false)
+            .unwrap();
+        for _ in 0..2 {
+            state = state.apply_action(Action::new(ActionEnum::Fold, 0.0));
+        }
+        assert!(state.final_state);
+
+        let seats = [
+            (1, "Alice".to_string()),
+            (3, "Bob".to_string()),
+            (6, "Carol".to_string()),
+        ];
+        let history = write_hand_history(&state, "main", &seats, None, &[]);
+        assert!(history.contains("Carol: posts the ante 2\n"));
+        assert!(history.contains("Carol: posts big blind 2\n"));
+        assert!(history.contains("Uncalled bet (1) returned to Carol\n"));
+        assert!(history.contains("Carol collected 4 from pot\n"));
+    }
+
+    #[test]
+    fn the_header_names_the_variant() {
+        let state = State::from_seed(3, 0, 1.0, 2.0, 200.0, 7, false, 0.0).unwrap();
+        let history = write_hand_history(&state, "main", &[], None, &[]);
+        assert!(history.contains(": Hold'em No Limit (1/2)"));
+
+        let short_deck = state
+            .with_rules(TableRules {
+                variant: GameVariant::ShortDeck,
+                ..Default::default()
+            })
+            .unwrap();
+        let history = write_hand_history(&short_deck, "main", &[], None, &[]);
+        assert!(history.contains(": 6+ Hold'em No Limit (1/2)"));
+    }
+
+    #[test]
+    fn dates_are_utc() {
+        assert_eq!(date_text(0.0), "1970/01/01 0:00:00 UTC");
+        assert_eq!(date_text(1_792_224_000.0), "2026/10/17 8:00:00 UTC");
+        for timestamp in [0.0, 951_782_400.0, 1_792_224_000.0, 1_792_310_399.0] {
+            assert_eq!(parse_date_text(&date_text(timestamp)), Some(timestamp));
+        }
+    }
+
+    #[test]
+    fn players_are_renamed_where_they_are_written() {
+        // Everyone checks down to the showdown
+        let mut state = State::from_seed(3, 0, 1.0, 2.0, 200.0, 7, false, 0.0).unwrap();
+        while !state.final_state {
+            state = state.apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+        }
+        let seats = [
+            (1, "Al".to_string()),
+            (3, "Al Bo".to_string()),
+            (6, "Table".to_string()),
+        ];
+        let history = write_hand_history(&state, "Al Bo's table", &seats, Some(1), &[]);
+        assert_eq!(hand_table(&history), Some("Al Bo's table"));
+        assert_eq!(hand_time(&history), None);
+
+        let renamed = rename_players(&history, |name| {
+            (name != "Al Bo").then(|| format!("P-{}", name.len()))
+        });
+        assert!(renamed.contains("Table 'Al Bo's table' 3-max"));
+        assert!(renamed.contains("Seat 1: P-2 (200 in chips)\n"));
+        assert!(renamed.contains("Seat 3: Al Bo (200 in chips)\n"));
+        assert!(renamed.contains("Seat 6: P-5 (200 in chips)\n"));
+        assert!(renamed.contains("Dealt to Al Bo ["));
+        assert!(renamed.contains("P-2: shows ["));
+        assert!(renamed.contains("Seat 6: P-5 showed ["));
+        assert!(!renamed.contains("Al:") && !renamed.contains("Table:"));
+        assert_eq!(renamed.lines().count(), history.lines().count());
+        assert_eq!(rename_players(&history, |_| None), history);
+    }
+}
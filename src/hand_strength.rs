@@ -0,0 +1,280 @@
+// hand_strength.rs
+use crate::cards::live_cards;
+use crate::evaluator::{Evaluator, HandRank};
+use crate::game_logic::rank_hand;
+use crate::messages::{self, Locale};
+use crate::state::card::Card;
+use crate::state::stage::Stage;
+use crate::state::State;
+use itertools::Itertools;
+use pyo3::prelude::*;
+use rand::{seq::SliceRandom, SeedableRng};
+
+/// Standard poker hand categories, from weakest to strongest
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum HandCategory {
+    HighCard,
+    Pair,
+    TwoPair,
+    ThreeOfAKind,
+    Straight,
+    Flush,
+    FullHouse,
+    FourOfAKind,
+    StraightFlush,
+    RoyalFlush,
+}
+
+impl HandCategory {
+    /// Map the first component of an evaluator rank (1 = royal flush, 10 = high card)
+    pub fn from_rank(rank: (u64, u64, u64)) -> HandCategory {
+        match rank.0 {
+            1 => HandCategory::RoyalFlush,
+            2 => HandCategory::StraightFlush,
+            3 => HandCategory::FourOfAKind,
+            4 => HandCategory::FullHouse,
+            5 => HandCategory::Flush,
+            6 => HandCategory::Straight,
+            7 => HandCategory::ThreeOfAKind,
+            8 => HandCategory::TwoPair,
+            9 => HandCategory::Pair,
+            _ => HandCategory::HighCard,
+        }
+    }
+}
+
+#[pymethods]
+impl HandCategory {
+    /// Name of the category in `locale`
+    #[pyo3(signature = (locale=Locale::En))]
+    pub fn description(&self, locale: Locale) -> &'static str {
+        messages::hand_category(*self, locale)
+    }
+}
+
+/// Hero-only view of a player's current hand strength
+#[pyclass]
+#[derive(Debug, Clone)]
+pub struct HandStrength {
+    #[pyo3(get)]
+    pub player: u64,
+
+    #[pyo3(get)]
+    pub stage: Stage,
+
+    #[pyo3(get)]
+    pub category: HandCategory,
+
+    #[pyo3(get)]
+    pub outs: u32,
+
+    #[pyo3(get)]
+    pub equity: f64,
+}
+
+#[pymethods]
+impl HandStrength {
+    pub fn __str__(&self) -> PyResult<String> {
+        Ok(format!("{:#?}", self))
+    }
+}
+
+/// Category of the best hand made with the hole cards and the current board.
+/// Before the flop only pairs and high cards are possible.
+fn current_category(hand: (Card, Card), board: &[Card]) -> HandCategory {
+    if board.len() + 2 < 5 {
+        if hand.0.rank == hand.1.rank {
+            HandCategory::Pair
+        } else {
+            HandCategory::HighCard
+        }
+    } else {
+        HandCategory::from_rank(rank_hand(hand, board))
+    }
+}
+
+/// Cards not visible to the player: everything except their hole cards and the board
+fn unseen_cards(hand: (Card, Card), board: &[Card]) -> Vec<Card> {
+    let mut seen = vec![hand.0, hand.1];
+    seen.extend(board);
+    live_cards(&seen)
+}
+
+/// Count the unseen cards that would improve the hand to a better category on the next street.
+/// Only meaningful on the flop and the turn.
+fn count_outs(hand: (Card, Card), board: &[Card], unseen: &[Card]) -> u32 {
+    if board.len() < 3 || board.len() >= 5 {
+        return 0;
+    }
+
+    let category = current_category(hand, board);
+    unseen
+        .iter()
+        .filter(|&&card| {
+            let mut next_board = board.to_vec();
+            next_board.push(card);
+            current_category(hand, &next_board) > category
+        })
+        .count() as u32
+}
+
+/// Monte Carlo equity of the hand against a single uniformly random opponent hand
+fn equity_vs_random(
+    evaluator: &dyn Evaluator,
+    hand: (Card, Card),
+    board: &[Card],
+    unseen: &[Card],
+    n_samples: usize,
+    seed: u64,
+) -> f64 {
+    if n_samples == 0 {
+        return 0.0;
+    }
+
+    let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+    let mut cards = unseen.to_vec();
+    let missing_board = 5 - board.len().min(5);
+    let mut score = 0.0;
+
+    for _ in 0..n_samples {
+        let (sample, _) = cards.partial_shuffle(&mut rng, 2 + missing_board);
+        let opponent = (sample[0], sample[1]);
+        let mut full_board = board.to_vec();
+        full_board.extend_from_slice(&sample[2..]);
+
+        let hero_rank = evaluator.rank_hand(hand, &full_board);
+        let villain_rank = evaluator.rank_hand(opponent, &full_board);
+        if hero_rank < villain_rank {
+            score += 1.0;
+        } else if hero_rank == villain_rank {
+            score += 0.5;
+        }
+    }
+
+    score / n_samples as f64
+}
+
+/// Share of the opponent hands each of `hands` beats at showdown on the current board, ties
+/// counting half, sliced into `n_buckets` equal buckets with 0 for the strongest hands. Every
+/// opponent hand is counted, so hands that only differ by a permutation of suits share a
+/// bucket. Cards still to come are not considered: draws rank by what they make now.
+pub fn strength_buckets(
+    evaluator: &dyn Evaluator,
+    hands: &[(Card, Card)],
+    board: &[Card],
+    n_buckets: u32,
+) -> Vec<u32> {
+    let n_buckets = n_buckets.max(1);
+    let opponents: Vec<(Card, Card, HandRank)> = live_cards(board)
+        .into_iter()
+        .tuple_combinations()
+        .map(|(a, b)| (a, b, evaluator.rank_hand((a, b), board)))
+        .collect();
+
+    hands
+        .iter()
+        .map(|&hand| {
+            let rank = evaluator.rank_hand(hand, board);
+            let mut score = 0.0;
+            let mut count = 0;
+            for &(a, b, other) in &opponents {
+                if [a, b].iter().any(|&c| c == hand.0 || c == hand.1) {
+                    continue;
+                }
+                count += 1;
+                if rank < other {
+                    score += 1.0;
+                } else if rank == other {
+                    score += 0.5;
+                }
+            }
+            let strength = score / count.max(1) as f64;
+            (((1.0 - strength) * n_buckets as f64) as u32).min(n_buckets - 1)
+        })
+        .collect()
+}
+
+/// Compute the hand strength indicator for `player` using only the information that player can see
+pub fn hand_strength(state: &State, player: u64, n_samples: usize) -> Option<HandStrength> {
+    let player_state = state.players_state.get(player as usize)?;
+    let hand = player_state.hand;
+    let board = &state.public_cards;
+    let unseen = unseen_cards(hand, board);
+
+    Some(HandStrength {
+        player,
+        stage: state.stage,
+        category: current_category(hand, board),
+        outs: count_outs(hand, board, &unseen),
+        equity: equity_vs_random(
+            state.rules.variant.evaluator(),
+            hand,
+            board,
+            &unseen,
+            n_samples,
+            state.seed ^ (player << 32) ^ board.len() as u64,
+        ),
+    })
+}
+
+/// The winner of the showdown of `state` and the player with the best hand beaten, with the
+/// ranks of their hands. `None` without a showdown on a full board, when nobody lost to the best
+/// hand and for double boards.
+pub(crate) fn beaten_hand(state: &State) -> Option<((u64, HandRank), (u64, HandRank))> {
+    if state.public_cards.len() < 5 || !state.second_board.is_empty() {
+        return None;
+    }
+    let evaluator = state.rules.variant.evaluator();
+    let ranks: Vec<(u64, HandRank)> = state
+        .players_state
+        .iter()
+        .filter(|ps| ps.folded_on.is_none())
+        .map(|ps| (ps.player, evaluator.rank_hand(ps.hand, &state.public_cards)))
+        .collect();
+    // Lower ranks are stronger hands
+    let &(winner, best) = ranks.iter().min_by_key(|(_, rank)| *rank)?;
+    let &(loser, beaten) = ranks
+        .iter()
+        .filter(|(_, rank)| *rank > best)
+        .min_by_key(|(_, rank)| *rank)?;
+    Some(((winner, best), (loser, beaten)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pocket_aces_are_favourites_preflop() {
+        let deck = ["SA", "HA", "D7", "C2", "C9", "DT", "S3", "H4", "CK"]
+            .iter()
+            .map(|s| Card::from_string(s.to_string()).unwrap())
+            .collect();
+        // Cards are dealt starting left of the button, so player 1 holds the aces
+        let state = State::from_deck(2, 0, 0.5, 1.0, 100.0, deck, false, 0, 0.0).unwrap();
+
+        let strength = hand_strength(&state, 1, 2000).unwrap();
+        assert_eq!(strength.category, HandCategory::Pair);
+        assert_eq!(strength.outs, 0);
+        assert!(strength.equity > 0.8);
+    }
+
+    #[test]
+    fn strength_buckets_ignore_suits() {
+        let cards = |s: &[&str]| -> Vec<Card> {
+            s.iter()
+                .map(|c| Card::from_string(c.to_string()).unwrap())
+                .collect()
+        };
+        let board = cards(&["CK", "S9", "H4", "D5", "S6"]);
+        let hands = cards(&["H7", "H8", "D7", "C8", "SA", "HA", "D2", "C8"]);
+        let hands: Vec<(Card, Card)> = hands.chunks(2).map(|h| (h[0], h[1])).collect();
+
+        let buckets = strength_buckets(&crate::evaluator::Holdem, &hands, &board, 20);
+        // The straight is the nuts whatever its suits, the missed low cards the weakest
+        assert_eq!(buckets[..2], [0, 0]);
+        assert!(buckets[2] > 0);
+        assert!(buckets[3] >= 15, "{:?}", buckets);
+    }
+}
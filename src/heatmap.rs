@@ -0,0 +1,268 @@
+// heatmap.rs - Preflop strategy heatmaps for user-provided policies
+use crate::node_lock::position_to_act;
+use crate::range::{grid_rank, hand_class_name, HandRange, N_HAND_CLASSES};
+use crate::state::action::{Action, ActionEnum};
+use crate::state::card::{Card, CardSuit};
+use crate::state::State;
+use crate::visualization::range_grid_html;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use serde::{Serialize, Serializer};
+
+/// Action frequencies of a policy over the 169 hand classes for one position and stack depth
+#[pyclass]
+#[derive(Debug, Clone, Serialize)]
+pub struct HeatmapTable {
+    #[pyo3(get)]
+    pub position: u64,
+
+    #[pyo3(get)]
+    pub stack_bb: f64,
+
+    /// 169 rows in grid order, one frequency per action
+    #[pyo3(get)]
+    #[serde(rename = "hands", serialize_with = "by_hand_class")]
+    pub frequencies: Vec<Vec<f64>>,
+}
+
+#[pyclass]
+#[derive(Debug, Clone, Serialize)]
+pub struct StrategyHeatmap {
+    #[pyo3(get)]
+    pub n_players: u64,
+
+    #[pyo3(get)]
+    #[serde(rename = "actions")]
+    pub action_names: Vec<String>,
+
+    #[pyo3(get)]
+    pub tables: Vec<HeatmapTable>,
+}
+
+/// The rows of a table keyed by hand class name, in grid order
+fn by_hand_class<S: Serializer>(
+    frequencies: &[Vec<f64>],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_map(
+        frequencies
+            .iter()
+            .enumerate()
+            .map(|(idx, freqs)| (hand_class_name(idx / 13, idx % 13), freqs)),
+    )
+}
+
+/// A representative combo for a grid cell: spades and hearts, or two spades when suited
+fn representative_combo(row: usize, col: usize) -> (Card, Card) {
+    let high = grid_rank(row.min(col));
+    let low = grid_rank(row.max(col));
+    let second_suit = if row < col {
+        CardSuit::Spades
+    } else {
+        CardSuit::Hearts
+    };
+    (
+        Card::new(CardSuit::Spades, high),
+        Card::new(second_suit, low),
+    )
+}
+
+/// Build the decision state for `position` (seats counted from the button at 0) holding `hand`:
+/// everybody before the hero folds, except that the small blind completes when the hero is
+/// the big blind so that the big blind has a decision to make.
+fn decision_state(
+    n_players: u64,
+    position: u64,
+    hand: (Card, Card),
+    stake: f64,
+    sb: f64,
+    bb: f64,
+) -> Result<State, String> {
+    // Cards are dealt two at a time starting left of the button
+    let deal_idx = ((position + n_players - 1) % n_players) as usize;
+    let mut deck: Vec<Card> = Card::collect()
+        .into_iter()
+        .filter(|c| *c != hand.0 && *c != hand.1)
+        .collect();
+    deck.insert(2 * deal_idx, hand.1);
+    deck.insert(2 * deal_idx, hand.0);
+
+    let mut state = State::from_deck(n_players, 0, sb, bb, stake, deck, false, 0, 0.0)
+        .map_err(|e| format!("{:?}", e))?;
+
+    let sb_position = 1 % n_players;
+    let bb_position = 2 % n_players;
+    while position_to_act(&state) != position && !state.final_state {
+        let action = if position == bb_position && position_to_act(&state) == sb_position {
+            ActionEnum::CheckCall
+        } else {
+            ActionEnum::Fold
+        };
+        state = state.apply_action(Action::new(action, 0.0));
+    }
+
+    if state.final_state {
+        return Err(format!("Position {} never gets to act", position));
+    }
+    Ok(state.with_perspective(Some(state.current_player)))
+}
+
+#[pymethods]
+impl StrategyHeatmap {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    pub fn to_csv(&self) -> String {
+        let header = format!("position,stack_bb,hand,{}", self.action_names.join(","));
+        self.tables.iter().fold(header, |csv, table| {
+            table
+                .frequencies
+                .iter()
+                .enumerate()
+                .fold(csv, |csv, (idx, freqs)| {
+                    let freqs = freqs
+                        .iter()
+                        .map(|f| f.to_string())
+                        .collect::<Vec<String>>()
+                        .join(",");
+                    format!(
+                        "{csv}\n{},{},{},{}",
+                        table.position,
+                        table.stack_bb,
+                        hand_class_name(idx / 13, idx % 13),
+                        freqs
+                    )
+                })
+        })
+    }
+
+    /// One 13x13 grid per table and action, shaded by frequency
+    pub fn to_html(&self) -> String {
+        let body = self
+            .tables
+            .iter()
+            .map(|table| {
+                let grids = self
+                    .action_names
+                    .iter()
+                    .enumerate()
+                    .map(|(a, name)| {
+                        let range = HandRange {
+                            weights: table
+                                .frequencies
+                                .iter()
+                                .map(|f| f.get(a).copied().unwrap_or(0.0))
+                                .collect(),
+                        };
+                        format!("<div><h3>{}</h3>{}</div>", name, range_grid_html(&range))
+                    })
+                    .collect::<String>();
+                format!(
+                    "<h2>Position {}, {}bb</h2><div style=\"display: flex; gap: 16px;\">{}</div>",
+                    table.position, table.stack_bb, grids
+                )
+            })
+            .collect::<String>();
+
+        format!("<html><body>{}</body></html>", body)
+    }
+
+    pub fn __str__(&self) -> PyResult<String> {
+        Ok(format!("{:#?}", self))
+    }
+}
+
+/// Query `policy(state) -> list[float]` (one frequency per action) for all 169 preflop hand
+/// classes at every position and stack depth, on states built by the engine
+#[pyfunction]
+#[pyo3(signature = (policy, n_players, positions, stacks_bb, sb=0.5, bb=1.0, action_names=None))]
+pub fn strategy_heatmap(
+    policy: &PyAny,
+    n_players: u64,
+    positions: Vec<u64>,
+    stacks_bb: Vec<f64>,
+    sb: f64,
+    bb: f64,
+    action_names: Option<Vec<String>>,
+) -> PyResult<StrategyHeatmap> {
+    let action_names = action_names.unwrap_or_else(|| {
+        vec![
+            "Fold".to_string(),
+            "CheckCall".to_string(),
+            "BetRaise".to_string(),
+        ]
+    });
+
+    let mut tables = Vec::new();
+    for &position in &positions {
+        if position >= n_players {
+            return Err(PyValueError::new_err(format!(
+                "Position {} is not at a {} players table",
+                position, n_players
+            )));
+        }
+
+        for &stack_bb in &stacks_bb {
+            let mut frequencies = Vec::with_capacity(N_HAND_CLASSES);
+            for idx in 0..N_HAND_CLASSES {
+                let hand = representative_combo(idx / 13, idx % 13);
+                let state = decision_state(n_players, position, hand, stack_bb * bb, sb, bb)
+                    .map_err(PyValueError::new_err)?;
+                let freqs: Vec<f64> = policy.call1((state,))?.extract()?;
+                if freqs.len() != action_names.len() {
+                    return Err(PyValueError::new_err(format!(
+                        "The policy returned {} frequencies for {} actions",
+                        freqs.len(),
+                        action_names.len()
+                    )));
+                }
+                frequencies.push(freqs);
+            }
+            tables.push(HeatmapTable {
+                position,
+                stack_bb,
+                frequencies,
+            });
+        }
+    }
+
+    Ok(StrategyHeatmap {
+        n_players,
+        action_names,
+        tables,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decision_state_reaches_the_hero() {
+        for position in 0..6 {
+            let hand = representative_combo(0, 1);
+            let state = decision_state(6, position, hand, 100.0, 0.5, 1.0).unwrap();
+            assert_eq!(state.current_player, position);
+            assert_eq!(state.players_state[position as usize].hand, hand);
+        }
+    }
+
+    #[test]
+    fn json_keeps_the_grid_order() {
+        let heatmap = StrategyHeatmap {
+            n_players: 2,
+            action_names: vec!["Fold".to_string(), "Call \"all\"".to_string()],
+            tables: vec![HeatmapTable {
+                position: 0,
+                stack_bb: 100.0,
+                frequencies: (0..N_HAND_CLASSES).map(|i| vec![i as f64, 0.0]).collect(),
+            }],
+        };
+        let json = heatmap.to_json();
+        let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed["actions"][1], "Call \"all\"");
+        assert_eq!(parsed["tables"][0]["hands"]["AKs"][0], 1.0);
+        assert!(json.find("\"AA\"").unwrap() < json.find("\"AKs\"").unwrap());
+    }
+}
@@ -0,0 +1,348 @@
+// hybrid_agent.rs - Agent playing preflop charts, solver strategies and a heuristic in turn
+use crate::betting_line::betting_line;
+use crate::hand_strength::hand_strength;
+use crate::node_lock::{matches_pattern, position_to_act, LockedAction};
+use crate::range::{hand_class, HandRange};
+use crate::seeds::hand_seed;
+use crate::solver_strategy::SolverStrategy;
+use crate::state::action::{Action, ActionEnum, DefaultPolicy};
+use crate::state::card::Card;
+use crate::state::stage::Stage;
+use crate::state::State;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::HashMap;
+
+/// Preflop chart of a decision: the hands that play each action, with the weight of each hand
+/// class as its frequency. Hands outside every range check, or fold to a bet.
+#[pyclass]
+#[derive(Debug, Clone)]
+pub struct PreflopChart {
+    /// Pattern of `State.betting_line` at the decision, as for `NodeLock`
+    #[pyo3(get)]
+    pub line: String,
+
+    /// Seats after the button of the player the chart is for, any player when `None`
+    #[pyo3(get)]
+    pub position: Option<u64>,
+
+    /// Range of each action token, sorted by token
+    #[pyo3(get)]
+    pub ranges: Vec<(String, HandRange)>,
+
+    actions: Vec<LockedAction>,
+}
+
+impl PreflopChart {
+    /// Whether the chart is for the current decision of `state`
+    fn matches(&self, state: &State) -> bool {
+        state.stage == Stage::Preflop
+            && !state.final_state
+            && !state.legal_actions.is_empty()
+            && self.position.is_none_or(|p| p == position_to_act(state))
+            && matches_pattern(self.line.as_bytes(), betting_line(state).as_bytes())
+    }
+
+    /// The action the chart plays with `hand`, sampled as `NodeLock` samples its strategy
+    fn action(&self, state: &State, hand: (Card, Card)) -> Option<Action> {
+        if !self.matches(state) {
+            return None;
+        }
+        let class = hand_class(hand);
+        let weights: Vec<f64> = self
+            .ranges
+            .iter()
+            .map(|(_, range)| range.weights[class].max(0.0))
+            .collect();
+        // Frequencies adding up to more than 1 are scaled down
+        let total: f64 = weights.iter().sum::<f64>().max(1.0);
+        let seed = hand_seed(state.seed, state.action_list.len() as u64);
+        let mut draw = StdRng::seed_from_u64(seed).gen::<f64>() * total;
+        for (weight, action) in weights.iter().zip(&self.actions) {
+            draw -= weight;
+            if draw < 0.0 {
+                return Some(action.action(state));
+            }
+        }
+        Some(state.default_action(DefaultPolicy::CheckElseFold))
+    }
+}
+
+#[pymethods]
+impl PreflopChart {
+    #[new]
+    #[pyo3(signature = (line, ranges, position=None))]
+    pub fn new(
+        line: String,
+        ranges: HashMap<String, HandRange>,
+        position: Option<u64>,
+    ) -> PyResult<PreflopChart> {
+        let mut ranges: Vec<(String, HandRange)> = ranges.into_iter().collect();
+        ranges.sort_by(|a, b| a.0.cmp(&b.0));
+        let actions = ranges
+            .iter()
+            .map(|(token, _)| {
+                LockedAction::parse(token)
+                    .ok_or_else(|| PyValueError::new_err(format!("Invalid action '{}'", token)))
+            })
+            .collect::<PyResult<Vec<LockedAction>>>()?;
+        Ok(PreflopChart {
+            line,
+            position,
+            ranges,
+            actions,
+        })
+    }
+
+    pub fn __str__(&self) -> PyResult<String> {
+        Ok(format!("{:#?}", self))
+    }
+}
+
+/// Composite agent, a strong baseline out of the box: the first preflop chart matching a
+/// preflop decision, the solver strategy at the nodes it solves, and a fallback elsewhere. The
+/// fallback is a `DefaultPolicy`, or without one a heuristic on the hand's equity against
+/// random hands: bet or raise `bet_size` of the pot from `value_equity` up, call when the
+/// price is right, check or fold otherwise.
+#[pyclass]
+#[derive(Debug, Clone)]
+pub struct HybridAgent {
+    #[pyo3(get)]
+    pub charts: Vec<PreflopChart>,
+    #[pyo3(get)]
+    pub solver: Option<SolverStrategy>,
+    #[pyo3(get)]
+    pub fallback: Option<DefaultPolicy>,
+    /// Equity against the players left from which the heuristic bets for value
+    #[pyo3(get)]
+    pub value_equity: f64,
+    /// Bets and raises of the heuristic, as a fraction of the pot
+    #[pyo3(get)]
+    pub bet_size: f64,
+    /// Runouts the heuristic's equity is measured over
+    #[pyo3(get)]
+    pub samples: usize,
+}
+
+impl HybridAgent {
+    pub fn checked(
+        charts: Vec<PreflopChart>,
+        solver: Option<SolverStrategy>,
+        fallback: Option<DefaultPolicy>,
+        value_equity: f64,
+        bet_size: f64,
+        samples: usize,
+    ) -> Result<HybridAgent, String> {
+        if !(0.0..=1.0).contains(&value_equity) {
+            return Err(format!(
+                "The value equity is a share from 0 to 1, got {}",
+                value_equity
+            ));
+        }
+        if !(bet_size > 0.0 && bet_size.is_finite()) {
+            return Err(format!("Bet sizes must be positive, got {}", bet_size));
+        }
+        Ok(HybridAgent {
+            charts,
+            solver,
+            fallback,
+            value_equity,
+            bet_size,
+            samples: samples.max(1),
+        })
+    }
+
+    /// The heuristic fallback. Against several players the equity against one random hand is
+    /// raised to the power of their number.
+    fn heuristic(&self, state: &State, player: u64) -> Action {
+        let Some(strength) = hand_strength(state, player, self.samples) else {
+            return state.default_action(DefaultPolicy::CheckElseFold);
+        };
+        let opponents = state
+            .players_state
+            .iter()
+            .filter(|ps| ps.active && ps.player != player)
+            .count();
+        let equity = strength.equity.powi(opponents.max(1) as i32);
+
+        let ps = &state.players_state[player as usize];
+        let max_bet = state
+            .players_state
+            .iter()
+            .map(|ps| ps.bet_chips)
+            .fold(0.0, f64::max);
+        let to_call = (max_bet - ps.bet_chips).min(ps.stake);
+        if equity >= self.value_equity && state.legal_actions.contains(&ActionEnum::BetRaise) {
+            LockedAction::BetRaise(self.bet_size).action(state)
+        } else if to_call <= 0.0 || equity >= to_call / (state.pot + to_call) {
+            Action::new(ActionEnum::CheckCall, 0.0)
+        } else {
+            Action::new(ActionEnum::Fold, 0.0)
+        }
+    }
+
+    /// Action of `player` at their decision in `state`
+    pub fn decide(&self, state: &State, player: u64) -> Action {
+        let Some(ps) = state.players_state.get(player as usize) else {
+            return state.default_action(DefaultPolicy::CheckElseFold);
+        };
+        let charted = self
+            .charts
+            .iter()
+            .find_map(|chart| chart.action(state, ps.hand));
+        let solved = || {
+            self.solver
+                .as_ref()
+                .and_then(|solver| solver.sample(state, ps.hand))
+        };
+        charted
+            .or_else(solved)
+            .unwrap_or_else(|| match self.fallback {
+                Some(policy) => state.default_action(policy),
+                None => self.heuristic(state, player),
+            })
+    }
+}
+
+#[pymethods]
+impl HybridAgent {
+    #[new]
+    #[pyo3(signature = (charts=Vec::new(), solver=None, fallback=None, value_equity=0.65, bet_size=0.66, samples=200))]
+    pub fn new(
+        charts: Vec<PreflopChart>,
+        solver: Option<SolverStrategy>,
+        fallback: Option<DefaultPolicy>,
+        value_equity: f64,
+        bet_size: f64,
+        samples: usize,
+    ) -> PyResult<HybridAgent> {
+        HybridAgent::checked(charts, solver, fallback, value_equity, bet_size, samples)
+            .map_err(PyValueError::new_err)
+    }
+
+    /// Agent policy `agent(state, player) -> Action`, for `evaluate_matchup`, `round_robin` or
+    /// `ServerHandle.add_bot`
+    pub fn __call__(&self, state: &State, player: u64) -> Action {
+        self.decide(state, player)
+    }
+
+    pub fn __str__(&self) -> PyResult<String> {
+        Ok(format!("{:#?}", self))
+    }
+}
+
+#[cfg(feature = "server")]
+impl crate::bots::BotPlayer for HybridAgent {
+    fn act(
+        &self,
+        state: State,
+        player: u64,
+    ) -> futures_util::future::BoxFuture<'_, Option<Action>> {
+        Box::pin(async move { Some(self.decide(&state, player)) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cards(text: &str) -> Vec<Card> {
+        text.split_whitespace()
+            .map(|name| Card::from_string(name.to_string()).unwrap())
+            .collect()
+    }
+
+    /// Heads-up with button 0, player 1 holding `first` and player 0 `second`. Player 1 posts
+    /// the small blind and acts first preflop.
+    fn deal(first: &str, second: &str, board: &str) -> State {
+        let mut deck = cards(first);
+        deck.extend(cards(second));
+        deck.extend(cards(board));
+        for card in Card::collect() {
+            if !deck.contains(&card) {
+                deck.push(card);
+            }
+        }
+        State::from_deck(2, 0, 0.5, 1.0, 100.0, deck, false, 0, 0.0).unwrap()
+    }
+
+    fn small_blind_opens() -> PreflopChart {
+        let ranges = HashMap::from([
+            (
+                "r150".to_string(),
+                HandRange::from_string("AA, KK").unwrap(),
+            ),
+            ("c".to_string(), HandRange::from_string("JTs").unwrap()),
+        ]);
+        PreflopChart::new(String::new(), ranges, Some(1)).unwrap()
+    }
+
+    #[test]
+    fn charts_then_solver_then_fallback() {
+        let mut solver = SolverStrategy::default();
+        solver
+            .insert("", "9h 8c 2d", "Jh Th", vec![("b75".to_string(), 1.0)])
+            .unwrap();
+        let agent = HybridAgent::checked(
+            vec![small_blind_opens()],
+            Some(solver),
+            Some(DefaultPolicy::CheckCall),
+            0.65,
+            0.66,
+            100,
+        )
+        .unwrap();
+
+        // Aces open to 1.5 times the pot of 2 once called
+        let aces = deal("SA HA", "D7 C2", "S9 S8 S2 C5 D4");
+        assert_eq!(
+            agent.decide(&aces, 1),
+            Action::new(ActionEnum::BetRaise, 4.0)
+        );
+        // Seven deuce is in no range and folds the small blind
+        let junk = deal("D7 C2", "SA HA", "S9 S8 S2 C5 D4");
+        assert_eq!(agent.decide(&junk, 1).action, ActionEnum::Fold);
+
+        // The suited connector limps, then plays the solver's bet on the flop, solved on
+        // another suit
+        let mut state = deal("DJ DT", "SA HA", "D9 H8 C2 C5 S4");
+        let limp = agent.decide(&state, 1);
+        assert_eq!(limp, Action::new(ActionEnum::CheckCall, 0.0));
+        state = state.apply_action(limp);
+        // The big blind is not charted: its fallback checks
+        assert_eq!(
+            agent.decide(&state, 0),
+            Action::new(ActionEnum::CheckCall, 0.0)
+        );
+        state = state.apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+        assert_eq!(state.stage, Stage::Flop);
+        assert_eq!(
+            agent.decide(&state, 1),
+            Action::new(ActionEnum::BetRaise, 1.5)
+        );
+    }
+
+    #[test]
+    fn heuristic_bets_value_and_folds_air() {
+        let agent = HybridAgent::checked(Vec::new(), None, None, 0.65, 0.5, 200).unwrap();
+        let mut state = deal("SA HA", "D7 C2", "DA CA S9 H4 D3");
+        while state.stage == Stage::Preflop {
+            let action = agent.decide(&state, state.current_player);
+            state = state.apply_action(action);
+        }
+        // Quads bet half the pot
+        let pot = state.pot;
+        let bet = agent.decide(&state, 1);
+        assert_eq!(bet, Action::new(ActionEnum::BetRaise, pot / 2.0));
+
+        // Seven deuce is not getting the price to call a raise to 20
+        let state = deal("SA HA", "D7 C2", "DA CA S9 H4 D3")
+            .apply_action(Action::new(ActionEnum::BetRaise, 20.0));
+        assert_eq!(agent.decide(&state, 0).action, ActionEnum::Fold);
+
+        assert!(HybridAgent::checked(Vec::new(), None, None, 1.5, 0.5, 200).is_err());
+        assert!(HybridAgent::checked(Vec::new(), None, None, 0.5, 0.0, 200).is_err());
+    }
+}
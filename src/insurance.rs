@@ -0,0 +1,467 @@
+// insurance.rs - Insurance pricing and all-in equities from runout enumeration
+use crate::cards::live_cards;
+use crate::game_logic::resolve_pots;
+use crate::state::action::ActionEnum;
+use crate::state::card::Card;
+use crate::state::stage::Stage;
+use crate::state::{AllInEquity, State, StateStatus};
+use itertools::Itertools;
+use pyo3::prelude::*;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+/// Runouts sampled for preflop all-ins, where there are too many to enumerate
+const PREFLOP_RUNOUTS: usize = 2_000;
+
+/// Insurance offered to the favourite of an all-in on the flop or the turn.
+/// Paying `premium` returns `premium * payout_ratio` if the player does not scoop the pot.
+#[pyclass]
+#[derive(Debug, Clone)]
+pub struct InsuranceQuote {
+    #[pyo3(get)]
+    pub player: u64,
+
+    /// Street on which the money went in
+    #[pyo3(get)]
+    pub stage: Stage,
+
+    /// Share of the pot won over all runouts, ties split
+    #[pyo3(get)]
+    pub equity: f64,
+
+    /// Probability of winning the whole pot
+    #[pyo3(get)]
+    pub scoop_probability: f64,
+
+    /// Unseen cards that put the player behind or level on the next street
+    #[pyo3(get)]
+    pub outs: u32,
+
+    /// Fair odds paid against the player scooping
+    #[pyo3(get)]
+    pub payout_ratio: f64,
+
+    /// The most the player can win from the other all-in players, which caps the payout
+    #[pyo3(get)]
+    pub max_payout: f64,
+}
+
+#[pymethods]
+impl InsuranceQuote {
+    /// Largest premium whose payout stays within `max_payout`
+    pub fn max_premium(&self) -> f64 {
+        self.max_payout / self.payout_ratio
+    }
+
+    pub fn __str__(&self) -> PyResult<String> {
+        Ok(format!("{:#?}", self))
+    }
+}
+
+/// Street, board and players still in the hand when the betting ended in an all-in,
+/// before the rest of the board was run out. `None` for hands decided any other way.
+fn all_in_spot(state: &State) -> Option<(Stage, Vec<Card>, Vec<u64>)> {
+    if !state.final_state || !state.second_board.is_empty() {
+        return None;
+    }
+
+    let stage = state.action_list.last()?.stage;
+    let board_len = match stage {
+        Stage::Flop => 3,
+        Stage::Turn => 4,
+        _ => return None,
+    };
+
+    let contestants: Vec<u64> = state
+        .players_state
+        .iter()
+        .map(|ps| ps.player)
+        .filter(|&player| {
+            !state
+                .action_list
+                .iter()
+                .any(|a| a.player == player && a.action.action == ActionEnum::Fold)
+        })
+        .collect();
+    if contestants.len() < 2 || state.public_cards.len() < board_len {
+        return None;
+    }
+
+    Some((stage, state.public_cards[..board_len].to_vec(), contestants))
+}
+
+/// Winners among `contestants` on a complete board
+fn winners(state: &State, contestants: &[u64], board: &[Card]) -> Vec<u64> {
+    let evaluator = state.rules.variant.evaluator();
+    let ranks: Vec<(u64, (u64, u64, u64))> = contestants
+        .iter()
+        .map(|&p| {
+            (
+                p,
+                evaluator.rank_hand(state.players_state[p as usize].hand, board),
+            )
+        })
+        .collect();
+    let best = ranks.iter().map(|(_, rank)| *rank).min().unwrap();
+    ranks
+        .into_iter()
+        .filter(|(_, rank)| *rank == best)
+        .map(|(p, _)| p)
+        .collect()
+}
+
+/// Add the pot shares and scoops of `contestants` on one complete board
+fn add_runout(
+    state: &State,
+    contestants: &[u64],
+    board: &[Card],
+    equity: &mut [f64],
+    scoop: &mut [f64],
+) {
+    let winners = winners(state, contestants, board);
+    for (i, player) in contestants.iter().enumerate() {
+        if winners.contains(player) {
+            equity[i] += 1.0 / winners.len() as f64;
+            if winners.len() == 1 {
+                scoop[i] += 1.0;
+            }
+        }
+    }
+}
+
+/// Exact equity and scoop probability of every contestant over all runouts of `board`
+fn runout_equities(
+    state: &State,
+    contestants: &[u64],
+    board: &[Card],
+    unseen: &[Card],
+) -> (Vec<f64>, Vec<f64>) {
+    let mut equity = vec![0.0; contestants.len()];
+    let mut scoop = vec![0.0; contestants.len()];
+    let mut n_runouts = 0;
+
+    for runout in unseen.iter().combinations(5 - board.len()) {
+        let mut full_board = board.to_vec();
+        full_board.extend(runout.into_iter().copied());
+
+        add_runout(state, contestants, &full_board, &mut equity, &mut scoop);
+        n_runouts += 1;
+    }
+
+    let n = n_runouts.max(1) as f64;
+    (
+        equity.into_iter().map(|e| e / n).collect(),
+        scoop.into_iter().map(|s| s / n).collect(),
+    )
+}
+
+/// Equity and scoop probability of every contestant over `n` random runouts of `board`
+fn sampled_equities(
+    state: &State,
+    contestants: &[u64],
+    board: &[Card],
+    unseen: &[Card],
+    n: usize,
+) -> (Vec<f64>, Vec<f64>) {
+    let mut equity = vec![0.0; contestants.len()];
+    let mut scoop = vec![0.0; contestants.len()];
+    let mut rng = StdRng::seed_from_u64(state.seed);
+    let mut cards = unseen.to_vec();
+
+    for _ in 0..n {
+        let (runout, _) = cards.partial_shuffle(&mut rng, 5 - board.len());
+        let mut full_board = board.to_vec();
+        full_board.extend_from_slice(runout);
+
+        add_runout(state, contestants, &full_board, &mut equity, &mut scoop);
+    }
+
+    let n = n.max(1) as f64;
+    (
+        equity.into_iter().map(|e| e / n).collect(),
+        scoop.into_iter().map(|s| s / n).collect(),
+    )
+}
+
+/// Equities of the players still in on every street from `closed_on`, the street the betting
+/// closed on, to the turn, once the board has been run out. Flop and turn equities are exact,
+/// preflop ones are sampled with the hand's seed. Empty for double boards and single players.
+pub fn all_in_equities(state: &State, closed_on: Stage) -> Vec<AllInEquity> {
+    let players: Vec<u64> = state
+        .players_state
+        .iter()
+        .filter(|ps| ps.active)
+        .map(|ps| ps.player)
+        .collect();
+    if players.len() < 2 || !state.second_board.is_empty() || state.public_cards.len() < 5 {
+        return Vec::new();
+    }
+
+    let mut stages = Vec::new();
+    let mut stage = Some(closed_on);
+    while let Some(street) = stage.filter(|&s| s < Stage::River) {
+        stages.push(street);
+        stage = street.next();
+    }
+
+    stages
+        .into_iter()
+        .map(|stage| street_equities(state, &players, stage))
+        .collect()
+}
+
+/// Equities of `players` once the board of `stage` is dealt, from the full runout of the
+/// finished hand `state`. Exact from the flop on, sampled with the hand's seed preflop.
+pub(crate) fn street_equities(state: &State, players: &[u64], stage: Stage) -> AllInEquity {
+    let board = &state.public_cards[..stage.board_card_count()];
+    let mut dead = board.to_vec();
+    for &p in players {
+        let hand = state.players_state[p as usize].hand;
+        dead.extend([hand.0, hand.1]);
+    }
+    let unseen = live_cards(&dead);
+
+    let (equities, _) = if stage == Stage::Preflop {
+        sampled_equities(state, players, board, &unseen, PREFLOP_RUNOUTS)
+    } else {
+        runout_equities(state, players, board, &unseen)
+    };
+    AllInEquity {
+        stage,
+        players: players.to_vec(),
+        equities,
+    }
+}
+
+/// Reward every player can expect from a hand decided by running the board out after an
+/// all-in: the average of their rewards over every runout from the street the betting closed
+/// on, the pots and side pots split as at the showdown. Exact from the flop on, sampled with
+/// the hand's seed preflop. `None` for hands decided any other way, all-ins on the river
+/// included, and for double boards.
+pub fn all_in_ev(state: &State) -> Option<Vec<f64>> {
+    let closed_on = state.action_list.last()?.stage;
+    let players: Vec<u64> = state
+        .players_state
+        .iter()
+        .filter(|ps| ps.folded_on.is_none())
+        .map(|ps| ps.player)
+        .collect();
+    if !state.final_state
+        || !matches!(state.status, StateStatus::Ok)
+        || !state.second_board.is_empty()
+        || closed_on >= Stage::River
+        || players.len() < 2
+        || state.public_cards.len() < 5
+    {
+        return None;
+    }
+
+    let board = &state.public_cards[..closed_on.board_card_count()];
+    let mut dead = board.to_vec();
+    for &p in &players {
+        let hand = state.players_state[p as usize].hand;
+        dead.extend([hand.0, hand.1]);
+    }
+    let mut unseen = live_cards(&dead);
+
+    // The showdown left every player inactive, which keeps them out of the pots
+    let mut runout_state = state.clone();
+    for ps in &mut runout_state.players_state {
+        ps.active = ps.folded_on.is_none();
+    }
+    let mut ev = vec![0.0; state.players_state.len()];
+    let mut add_runout = |runout: &[&Card]| {
+        runout_state.public_cards.truncate(board.len());
+        runout_state
+            .public_cards
+            .extend(runout.iter().copied().copied());
+        resolve_pots(&mut runout_state, &[]);
+        for (e, ps) in ev.iter_mut().zip(&runout_state.players_state) {
+            *e += ps.reward;
+        }
+    };
+    let n_runouts = if closed_on == Stage::Preflop {
+        let mut rng = StdRng::seed_from_u64(state.seed);
+        for _ in 0..PREFLOP_RUNOUTS {
+            let (runout, _) = unseen.partial_shuffle(&mut rng, 5 - board.len());
+            add_runout(&runout.iter().collect::<Vec<_>>());
+        }
+        PREFLOP_RUNOUTS
+    } else {
+        let mut n = 0;
+        for runout in unseen.iter().combinations(5 - board.len()) {
+            add_runout(&runout);
+            n += 1;
+        }
+        n
+    };
+
+    Some(ev.into_iter().map(|e| e / n_runouts as f64).collect())
+}
+
+/// Price insurance for `player` in a hand that ended with an all-in on the flop or the turn.
+/// Only the favourite (strictly highest equity) who does not always scoop gets a quote.
+pub fn insurance_quote(state: &State, player: u64) -> Option<InsuranceQuote> {
+    let (stage, board, contestants) = all_in_spot(state)?;
+    let idx = contestants.iter().position(|&p| p == player)?;
+
+    let mut dead = board.clone();
+    for &p in &contestants {
+        let hand = state.players_state[p as usize].hand;
+        dead.extend([hand.0, hand.1]);
+    }
+    let unseen = live_cards(&dead);
+
+    let (equity, scoop) = runout_equities(state, &contestants, &board, &unseen);
+    let is_favourite = equity
+        .iter()
+        .enumerate()
+        .all(|(i, e)| i == idx || *e < equity[idx]);
+    if !is_favourite || scoop[idx] <= 0.0 || scoop[idx] >= 1.0 {
+        return None;
+    }
+
+    let outs = unseen
+        .iter()
+        .filter(|&&card| {
+            let mut next_board = board.clone();
+            next_board.push(card);
+            let next_unseen: Vec<Card> = unseen.iter().copied().filter(|c| *c != card).collect();
+            let (next_equity, _) = runout_equities(state, &contestants, &next_board, &next_unseen);
+            next_equity
+                .iter()
+                .enumerate()
+                .any(|(i, e)| i != idx && *e >= next_equity[idx])
+        })
+        .count() as u32;
+
+    // The player can only win from each opponent what they matched themselves
+    let own_contribution = state.players_state[player as usize].pot_chips;
+    let max_payout = contestants
+        .iter()
+        .filter(|&&p| p != player)
+        .map(|&p| {
+            state.players_state[p as usize]
+                .pot_chips
+                .min(own_contribution)
+        })
+        .sum();
+
+    Some(InsuranceQuote {
+        player,
+        stage,
+        equity: equity[idx],
+        scoop_probability: scoop[idx],
+        outs,
+        payout_ratio: scoop[idx] / (1.0 - scoop[idx]),
+        max_payout,
+    })
+}
+
+/// Whether `player` won the whole pot on the final board, which settles an insurance quote
+pub fn scooped(state: &State, player: u64) -> bool {
+    match all_in_spot(state) {
+        Some((_, _, contestants)) => winners(state, &contestants, &state.public_cards) == [player],
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::action::Action;
+    use crate::state::rules::TableRules;
+
+    #[test]
+    fn favourite_gets_fair_insurance_on_the_turn() {
+        // Player 1 holds aces, player 0 a flush draw; the flop is dealt after both hands
+        let cards = [
+            "SA", "HA", "CK", "CQ", "C2", "C7", "D9", "S3", "H4", "D5", "S6",
+        ];
+        let deck: Vec<Card> = cards
+            .iter()
+            .map(|c| Card::from_string(c.to_string()).unwrap())
+            .collect();
+        let state = State::from_deck(2, 0, 0.5, 1.0, 100.0, deck, false, 0, 0.0).unwrap();
+
+        // Limp and check to the turn, then all-in
+        let mut state = state;
+        for _ in 0..4 {
+            state = state.apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+        }
+        assert_eq!(state.stage, Stage::Turn);
+        state = state.apply_action(Action::new(ActionEnum::BetRaise, 100.0));
+        state = state.apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+        assert!(state.final_state);
+
+        let quote = insurance_quote(&state, 1).unwrap();
+        assert_eq!(quote.stage, Stage::Turn);
+        // Only the nine remaining clubs beat the aces; pairing the king or queen does not
+        assert_eq!(quote.outs, 9);
+        assert!((quote.scoop_probability - 35.0 / 44.0).abs() < 1e-9);
+        assert!((quote.payout_ratio - 35.0 / 9.0).abs() < 1e-9);
+        assert_eq!(quote.max_payout, 100.0);
+        assert!(insurance_quote(&state, 0).is_none());
+    }
+
+    #[test]
+    fn all_in_ev_averages_the_runouts() {
+        let cards = [
+            "SA", "HA", "CK", "CQ", "C2", "C7", "D9", "S3", "H4", "D5", "S6",
+        ];
+        let deck: Vec<Card> = cards
+            .iter()
+            .map(|c| Card::from_string(c.to_string()).unwrap())
+            .collect();
+        let mut state = State::from_deck(2, 0, 0.5, 1.0, 100.0, deck, false, 0, 0.0).unwrap();
+        for _ in 0..4 {
+            state = state.apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+        }
+        assert!(all_in_ev(&state).is_none());
+        state = state.apply_action(Action::new(ActionEnum::BetRaise, 100.0));
+        state = state.apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+
+        // The aces win the pot of 200 on 35 rivers out of 44
+        let ev = all_in_ev(&state).unwrap();
+        assert!((ev[1] - (35.0 / 44.0 * 200.0 - 100.0)).abs() < 1e-9);
+        assert!((ev[0] + ev[1]).abs() < 1e-9);
+        // The river was the 6 of spades: the aces held
+        assert_eq!(state.players_state[1].reward, 100.0);
+    }
+
+    #[test]
+    fn all_in_equities_follow_the_runout() {
+        let cards = [
+            "SA", "HA", "CK", "CQ", "C2", "C7", "D9", "S3", "H4", "D5", "S6",
+        ];
+        let deck: Vec<Card> = cards
+            .iter()
+            .map(|c| Card::from_string(c.to_string()).unwrap())
+            .collect();
+        let state = State::from_deck(2, 0, 0.5, 1.0, 100.0, deck, false, 0, 0.0).unwrap();
+        let all_in = |state: &State| {
+            let state = state.apply_action(Action::new(ActionEnum::BetRaise, 100.0));
+            state.apply_action(Action::new(ActionEnum::CheckCall, 0.0))
+        };
+        assert!(all_in(&state).all_in_equities.is_empty());
+
+        // All-in preflop: equities on the preflop, the flop and the turn
+        let rules = TableRules {
+            report_all_in_equities: true,
+            ..TableRules::default()
+        };
+        let state = all_in(&state.with_rules(rules).unwrap());
+        assert!(state.final_state);
+        let stages: Vec<Stage> = state.all_in_equities.iter().map(|e| e.stage).collect();
+        assert_eq!(stages, vec![Stage::Preflop, Stage::Flop, Stage::Turn]);
+
+        for entry in &state.all_in_equities {
+            assert_eq!(entry.players, vec![0, 1]);
+            assert!((entry.equities.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+        }
+        let aces = |i: usize| state.all_in_equities[i].equities[1];
+        assert!(aces(0) > 0.75 && aces(0) < 0.9);
+        assert!((aces(2) - 35.0 / 44.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,100 @@
+// invariants.rs - Engine audit invariants shared by tests, fuzzing and users
+use crate::game_logic::engine_error_reason;
+use crate::state::card::Card;
+use crate::state::State;
+
+const EPSILON: f64 = 1e-6;
+
+/// The chips the players have put in when `state.pot` has drifted away from them
+pub fn pot_drift(state: &State) -> Option<f64> {
+    let contributed = state.chips_in_pot();
+    if (contributed - state.pot).abs() > EPSILON * state.pot.abs().max(1.0) {
+        Some(contributed)
+    } else {
+        None
+    }
+}
+
+/// Check the engine invariants that must hold after every action.
+/// Returns a description of each violated invariant, empty when the state is consistent.
+pub fn check_invariants(state: &State) -> Vec<String> {
+    let mut violations = Vec::new();
+    let n_players = state.players_state.len();
+
+    if state.current_player as usize >= n_players {
+        violations.push(format!(
+            "current_player {} out of range for {} players",
+            state.current_player, n_players
+        ));
+    }
+
+    // Chip conservation: the pot is exactly what the players have put in
+    if let Some(contributed) = pot_drift(state) {
+        violations.push(format!(
+            "pot {} differs from the chips contributed by the players {}",
+            state.pot, contributed
+        ));
+    }
+
+    for ps in &state.players_state {
+        if ps.stake < 0.0 || ps.bet_chips < 0.0 || ps.pot_chips < 0.0 {
+            violations.push(format!(
+                "player {} has negative chips (stake {}, bet {}, pot {})",
+                ps.player, ps.stake, ps.bet_chips, ps.pot_chips
+            ));
+        }
+    }
+
+    // Every card can only be in one place
+    let mut seen: Vec<Card> = Vec::with_capacity(52);
+    let all_cards = state
+        .players_state
+        .iter()
+        .flat_map(|ps| {
+            [ps.hand.0, ps.hand.1]
+                .into_iter()
+                .chain(ps.third_card)
+                .chain(ps.discarded)
+        })
+        .chain(state.public_cards.iter().copied())
+        .chain(state.second_board.iter().copied())
+        .chain(state.deck.iter().copied());
+    for card in all_cards {
+        if seen.contains(&card) {
+            violations.push(format!("card {} appears more than once", card));
+        } else {
+            seen.push(card);
+        }
+    }
+
+    let max_public_cards = state.stage.board_card_count();
+    if state.public_cards.len() > max_public_cards {
+        violations.push(format!(
+            "{} public cards dealt at {:?}",
+            state.public_cards.len(),
+            state.stage
+        ));
+    }
+    if !state.second_board.is_empty() && state.second_board.len() != state.public_cards.len() {
+        violations.push(format!(
+            "second board has {} cards but the first board has {}",
+            state.second_board.len(),
+            state.public_cards.len()
+        ));
+    }
+
+    if let Some(ref diagnostic) = state.engine_diagnostic {
+        let reason = engine_error_reason(diagnostic).unwrap_or_default();
+        violations.push(format!("Engine error: {}", reason));
+    }
+
+    // Final states distribute exactly the chips in the pot
+    if state.final_state && state.engine_diagnostic.is_none() {
+        let reward_sum: f64 = state.players_state.iter().map(|ps| ps.reward).sum();
+        if reward_sum.abs() > EPSILON * state.pot.abs().max(1.0) {
+            violations.push(format!("rewards sum to {} instead of 0", reward_sum));
+        }
+    }
+
+    violations
+}
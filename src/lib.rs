@@ -1,14 +1,95 @@
 // lib.rs
 use pyo3::prelude::*;
+pub mod betting_line;
+pub mod cards;
+pub mod chip_format;
+pub mod contributions;
+pub mod datasets;
+pub mod eval_service;
+pub mod evaluator;
+pub mod explain;
 pub mod game_logic;
+pub mod hand_codec;
+pub mod hand_history;
+pub mod hand_strength;
+pub mod heatmap;
+pub mod hybrid_agent;
+pub mod insurance;
+pub mod invariants;
+pub mod matchup;
+pub mod mcts;
+pub mod messages;
+pub mod node_lock;
+pub mod ofc;
+pub mod outcomes;
 pub mod parallel;
+pub mod profiling;
+pub mod range;
+pub mod redeal;
+pub mod search_context;
+pub mod seeds;
+pub mod self_play;
+pub mod solver_strategy;
+pub mod spots;
 pub mod state;
+pub mod table_game;
+pub mod trainer;
 pub mod visualization;
 
-// WebSocket server modules (not exposed to Python)
-#[cfg(feature = "websocket")]
+#[cfg(test)]
+mod scenarios;
+#[cfg(all(test, feature = "server"))]
+mod simulation;
+
+// Parquet trajectory recording, only with the `parquet` feature
+#[cfg(feature = "parquet")]
+pub mod trajectories;
+
+// Memory-mapped replay buffer, only with the `replay-buffer` feature
+#[cfg(feature = "replay-buffer")]
+pub mod replay_buffer;
+
+// ONNX policy networks played as agents, only with the `onnx` feature
+#[cfg(feature = "onnx")]
+pub mod onnx_policy;
+
+// WebSocket server modules, exposed to Python through `serve`. The server runs on the runtime
+// picked by `runtime-tokio` or `runtime-smol`; the `websocket` feature is the server on tokio.
+#[cfg(feature = "server")]
+pub mod account_data;
+#[cfg(feature = "server")]
+pub mod bots;
+#[cfg(feature = "server")]
+pub mod events;
+#[cfg(feature = "server")]
+pub mod fast_fold;
+#[cfg(feature = "server")]
 pub mod game_server;
-#[cfg(feature = "websocket")]
+#[cfg(feature = "server")]
+pub mod hand_archive;
+#[cfg(feature = "server")]
+pub mod player_stats;
+#[cfg(feature = "server")]
+pub mod protocol;
+#[cfg(feature = "server")]
+pub mod rake;
+#[cfg(feature = "server")]
+pub mod runtime;
+#[cfg(feature = "server")]
+pub mod serve;
+#[cfg(feature = "server")]
+pub mod session_recording;
+#[cfg(feature = "server")]
+pub mod store_writer;
+#[cfg(feature = "server")]
+pub mod tournament;
+#[cfg(feature = "server")]
+pub mod tournament_clock;
+#[cfg(feature = "server")]
+pub mod tournament_server;
+#[cfg(feature = "server")]
+pub mod webhooks;
+#[cfg(feature = "server")]
 pub mod websocket_server;
 
 /// A Python module implemented in Rust.
@@ -18,13 +99,121 @@ fn pokers(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<state::State>()?;
     m.add_class::<state::PlayerState>()?;
     m.add_class::<state::StateStatus>()?;
+    m.add_class::<state::AllInEquity>()?;
+    m.add_class::<state::BlindObligation>()?;
+    m.add_class::<state::BlindSettlement>()?;
+    m.add_class::<state::rules::TableRules>()?;
+    m.add_class::<state::rules::GameVariant>()?;
+    m.add_class::<state::rules::MissedBlinds>()?;
     m.add_class::<state::stage::Stage>()?;
+    m.add_class::<state::view::StateView>()?;
+    m.add_class::<state::view::PlayerView>()?;
     m.add_class::<state::action::ActionEnum>()?;
     m.add_class::<state::action::Action>()?;
     m.add_class::<state::action::ActionRecord>()?;
+    m.add_class::<state::action::DefaultPolicy>()?;
+    m.add_class::<state::action::Coercion>()?;
+    m.add_class::<state::trace::TraceKind>()?;
+    m.add_class::<state::trace::TraceEvent>()?;
     m.add_class::<state::card::Card>()?;
+    m.add_class::<state::deck::Deck>()?;
+    m.add_class::<messages::Locale>()?;
+    m.add_class::<hand_strength::HandCategory>()?;
+    m.add_class::<hand_strength::HandStrength>()?;
+    m.add_class::<contributions::StreetAction>()?;
+    m.add_class::<contributions::StreetContributions>()?;
+    m.add_class::<contributions::StreetSummary>()?;
+    m.add_class::<explain::ActionExplanation>()?;
+    m.add_class::<insurance::InsuranceQuote>()?;
+    m.add_class::<datasets::DecisionSample>()?;
+    m.add_class::<datasets::HandHistoryDataset>()?;
+    m.add_class::<range::HandRange>()?;
+    m.add_class::<heatmap::HeatmapTable>()?;
+    m.add_class::<heatmap::StrategyHeatmap>()?;
+    m.add_class::<parallel::Job>()?;
+    m.add_class::<profiling::PerfCounters>()?;
+    m.add_class::<search_context::SearchContext>()?;
+    m.add_class::<search_context::SearchNode>()?;
+    m.add_class::<node_lock::NodeLock>()?;
+    m.add_class::<solver_strategy::SolverStrategy>()?;
+    m.add_class::<hybrid_agent::PreflopChart>()?;
+    m.add_class::<hybrid_agent::HybridAgent>()?;
+    m.add_class::<mcts::MctsAgent>()?;
+    m.add_class::<matchup::PositionResult>()?;
+    m.add_class::<matchup::StreetAggression>()?;
+    m.add_class::<matchup::MatchupReport>()?;
+    m.add_class::<matchup::LeagueTable>()?;
+    m.add_class::<outcomes::OutcomeLabel>()?;
+    m.add_class::<outcomes::OutcomeCriteria>()?;
+    m.add_class::<outcomes::HandOutcome>()?;
+    m.add_class::<self_play::SelfPlayTable>()?;
+    m.add_class::<self_play::OpponentPool>()?;
+    m.add_class::<self_play::Curriculum>()?;
+    m.add_class::<spots::SpotSpec>()?;
+    m.add_class::<spots::PotType>()?;
+    m.add_class::<spots::FlopTexture>()?;
+    m.add_class::<trainer::OptionEv>()?;
+    m.add_class::<trainer::ActionGrade>()?;
+    m.add_class::<trainer::Trainer>()?;
+    m.add_class::<ofc::OfcVariant>()?;
+    m.add_class::<ofc::OfcRow>()?;
+    m.add_class::<ofc::OfcHand>()?;
+    m.add_class::<ofc::OfcState>()?;
+    m.add_class::<chip_format::ChipFormat>()?;
     m.add_function(wrap_pyfunction!(visualization::visualize_state, m)?)?;
     m.add_function(wrap_pyfunction!(visualization::visualize_trace, m)?)?;
+    m.add_function(wrap_pyfunction!(visualization::render_range_grid, m)?)?;
     m.add_function(wrap_pyfunction!(parallel::parallel_apply_action, m)?)?;
+    m.add_function(wrap_pyfunction!(parallel::batch_evaluate, m)?)?;
+    m.add_function(wrap_pyfunction!(parallel::equity_matrix, m)?)?;
+    m.add_function(wrap_pyfunction!(seeds::hand_seed, m)?)?;
+    m.add_function(wrap_pyfunction!(game_logic::missed_blind_seating, m)?)?;
+    m.add_function(wrap_pyfunction!(spots::generate_spots, m)?)?;
+    m.add_function(wrap_pyfunction!(trainer::grade_action, m)?)?;
+    m.add_function(wrap_pyfunction!(heatmap::strategy_heatmap, m)?)?;
+    m.add_function(wrap_pyfunction!(matchup::evaluate_matchup, m)?)?;
+    m.add_function(wrap_pyfunction!(matchup::round_robin, m)?)?;
+    m.add_function(wrap_pyfunction!(evaluator::compare_hands, m)?)?;
+    m.add_function(wrap_pyfunction!(evaluator::best_hand, m)?)?;
+    m.add_function(wrap_pyfunction!(outcomes::classify_hand_outcome, m)?)?;
+    m.add_function(wrap_pyfunction!(state::schema::migrate_state, m)?)?;
+    m.add("SCHEMA_VERSION", state::schema::SCHEMA_VERSION)?;
+    m.add_function(wrap_pyfunction!(datasets::load_hand_histories, m)?)?;
+    m.add_function(wrap_pyfunction!(datasets::py_observation, m)?)?;
+    m.add_function(wrap_pyfunction!(datasets::complete_observation, m)?)?;
+    m.add_function(wrap_pyfunction!(hand_codec::compress_hands, m)?)?;
+    m.add_function(wrap_pyfunction!(hand_codec::decompress_hands, m)?)?;
+    m.add_function(wrap_pyfunction!(cards::cards_one_hot, m)?)?;
+    m.add_function(wrap_pyfunction!(cards::all_combos, m)?)?;
+    m.add_function(wrap_pyfunction!(cards::combos_for_range, m)?)?;
+    m.add_function(wrap_pyfunction!(cards::sample_combos, m)?)?;
+    m.add_function(wrap_pyfunction!(range::hand_class_index, m)?)?;
+    m.add_function(wrap_pyfunction!(range::hand_class_string, m)?)?;
+    m.add_function(wrap_pyfunction!(range::hand_class_of, m)?)?;
+    m.add_function(wrap_pyfunction!(range::hand_class_strength, m)?)?;
+    m.add_function(wrap_pyfunction!(range::hand_class_by_strength, m)?)?;
+    #[cfg(feature = "parquet")]
+    m.add_class::<trajectories::TrajectoryRecorder>()?;
+    #[cfg(feature = "replay-buffer")]
+    m.add_class::<replay_buffer::ReplayBuffer>()?;
+    #[cfg(feature = "onnx")]
+    m.add_class::<onnx_policy::OnnxPolicy>()?;
+    #[cfg(feature = "server")]
+    {
+        m.add_class::<game_server::GameConfig>()?;
+        m.add_class::<game_server::AnteStructure>()?;
+        m.add_class::<game_server::Straddles>()?;
+        m.add_class::<game_server::DisconnectProtection>()?;
+        m.add_class::<tournament_clock::BlindLevel>()?;
+        m.add_class::<rake::Rake>()?;
+        m.add_class::<rake::JackpotDrop>()?;
+        m.add_class::<rake::BadBeat>()?;
+        m.add_class::<rake::TableRake>()?;
+        m.add_class::<account_data::AccountDeletion>()?;
+        m.add_class::<webhooks::Webhook>()?;
+        m.add_class::<webhooks::WebhookFormat>()?;
+        m.add_class::<serve::ServerHandle>()?;
+        m.add_function(wrap_pyfunction!(serve::serve, m)?)?;
+    }
     Ok(())
 }
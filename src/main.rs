@@ -1,23 +1,140 @@
 use std::net::SocketAddr;
+use std::path::Path;
 use std::sync::Arc;
 use tracing::{error, info};
 use tracing_subscriber::fmt;
 
+mod account_data;
+mod betting_line;
+#[allow(dead_code)]
+mod bots;
+#[allow(dead_code)]
+mod cards;
+mod chip_format;
+mod contributions;
+// Only the splitting of hand history files, for the hand archive
+#[allow(dead_code)]
+mod datasets;
+mod eval_service;
+#[allow(dead_code)]
+mod evaluator;
+mod events;
+mod explain;
 mod game_logic;
 mod game_server;
+mod hand_archive;
+// Only the reading of compressed hands, for the datasets
+#[allow(dead_code)]
+mod hand_codec;
+mod hand_history;
+mod hand_strength;
+mod insurance;
+mod invariants;
+// The visualization labels are only used by the Python module
+#[allow(dead_code)]
+mod messages;
+#[cfg(feature = "onnx")]
+mod onnx_policy;
+mod player_stats;
+mod profiling;
+mod protocol;
+mod rake;
+// Only the ranges used by State.redeal_unknown
+#[allow(dead_code)]
+mod range;
+mod redeal;
+mod runtime;
+mod seeds;
+mod session_recording;
 mod state;
+mod store_writer;
+mod table_game;
+mod tournament;
+mod tournament_clock;
+mod tournament_server;
+mod webhooks;
 mod websocket_server;
 
-use game_server::GameConfig;
+use chip_format::ChipFormat;
+use game_server::{AnteStructure, DisconnectProtection, GameConfig, MissedBlinds, Straddles};
+use hand_archive::HandArchive;
+use hand_strength::HandCategory;
+use messages::Locale;
+use player_stats::PlayerStatsStore;
+use rake::{BadBeat, JackpotDrop, Rake};
+use tournament_clock::BlindLevel;
+use tournament_server::TournamentServer;
+use webhooks::Webhook;
 use websocket_server::WebSocketServer;
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // `eval [address]` serves only the evaluator and equities, without the async runtime
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("eval") {
+        fmt::init();
+        let address = args.get(2).map_or("127.0.0.1:9100", String::as_str);
+        info!("Evaluation service on {}", address);
+        return Ok(eval_service::serve(address)?);
+    }
+    runtime::Runtime::new()?.block_on(run())
+}
+
+async fn run() -> Result<(), Box<dyn std::error::Error>> {
     // Initialize tracing
     fmt::init();
 
-    // Parse command line arguments for port
+    // `replay <dir>` replays a recorded session instead of serving, see `POKERS_RECORD_SESSIONS`
     let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("replay") {
+        let dir = args
+            .get(2)
+            .ok_or("Usage: websocket_server replay <session recording dir>")?;
+        return replay(Path::new(dir), config_from_env()?).await;
+    }
+    // `export-account <name> <zip file>` and `delete-account <name>` answer the requests of a
+    // player for their data, with the server stopped
+    match (args.get(1).map(String::as_str), args.get(2)) {
+        (Some("export-account"), Some(name)) => {
+            let path = args
+                .get(3)
+                .ok_or("Usage: websocket_server export-account <name> <zip file>")?;
+            let config = config_from_env()?;
+            let (stats, archive) = account_stores(&config)?;
+            let zip = account_data::export_account(
+                &stats,
+                &archive,
+                config.session_recording_dir.as_deref().map(Path::new),
+                name,
+            )?;
+            std::fs::write(path, zip)?;
+            println!("Exported the data of {} to {}", name, path);
+            return Ok(());
+        }
+        (Some("delete-account"), Some(name)) => {
+            let config = config_from_env()?;
+            let (mut stats, mut archive) = account_stores(&config)?;
+            let deletion = account_data::delete_account(
+                &mut stats,
+                &mut archive,
+                config.session_recording_dir.as_deref().map(Path::new),
+                name,
+            )?;
+            println!(
+                "Deleted the stats ({}), {} hands and {} recorded sessions of {}",
+                if deletion.stats { "yes" } else { "none" },
+                deletion.hands,
+                deletion.sessions,
+                name
+            );
+            return Ok(());
+        }
+        (Some("export-account" | "delete-account"), None) => {
+            return Err("Usage: websocket_server export-account|delete-account <name>".into());
+        }
+        _ => {}
+    }
+
+    // Parse command line arguments for port
     let port = if args.len() > 1 {
         args[1].parse::<u16>().unwrap_or(9000)
     } else {
@@ -25,18 +142,23 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     };
 
     let addr: SocketAddr = format!("127.0.0.1:{}", port).parse()?;
+    let config = config_from_env()?;
 
-    // Create game configuration
-    let config = GameConfig {
-        max_players: 6,
-        default_stack_size: 1000.0,
-        small_blind: 5.0,
-        big_blind: 10.0,
-        ante: 0.0,
-    };
+    // A tournament over as many tables as it takes, e.g. POKERS_TOURNAMENT=sunday with
+    // POKERS_BLIND_LEVELS, in place of the single table
+    if let Ok(tournament_id) = std::env::var("POKERS_TOURNAMENT") {
+        let server = TournamentServer::new(&tournament_id, config)?;
+        info!("Starting tournament {} on {}", tournament_id, addr);
+        if let Err(e) = server.start(addr).await {
+            error!("Server error: {}", e);
+        }
+        return Ok(());
+    }
 
     // Create WebSocket server with config
     let ws_server = Arc::new(WebSocketServer::new_with_config(config));
+    #[cfg(feature = "onnx")]
+    seat_onnx_bots(&ws_server).await?;
 
     info!("Starting Poker WebSocket Server on {}", addr);
 
@@ -47,3 +169,301 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+/// Bots played by policy networks exported to ONNX, e.g.
+/// POKERS_ONNX_BOTS=2=policy.onnx,4=policy.onnx, with POKERS_ONNX_BET_BUCKETS bet sizes (10
+/// by default, see `OnnxPolicy`)
+#[cfg(feature = "onnx")]
+async fn seat_onnx_bots(server: &WebSocketServer) -> Result<(), Box<dyn std::error::Error>> {
+    let Ok(list) = std::env::var("POKERS_ONNX_BOTS") else {
+        return Ok(());
+    };
+    let n_bet_buckets = match std::env::var("POKERS_ONNX_BET_BUCKETS") {
+        Ok(n) => n.parse().map_err(|_| "Invalid POKERS_ONNX_BET_BUCKETS")?,
+        Err(_) => 10,
+    };
+    for bot in list.split(',') {
+        let (seat, path) = bot.split_once('=').ok_or("Invalid POKERS_ONNX_BOTS")?;
+        let seat: u8 = seat
+            .trim()
+            .parse()
+            .map_err(|_| "Invalid POKERS_ONNX_BOTS")?;
+        let policy = onnx_policy::OnnxPolicy::load(path.trim(), n_bet_buckets, true)?;
+        server
+            .game_server()
+            .write()
+            .await
+            .seat_bot(
+                &format!("Bot {}", seat),
+                seat,
+                Arc::new(policy),
+                bots::BotSettings::default(),
+            )
+            .await?;
+    }
+    Ok(())
+}
+
+/// The player stats and hand histories the server keeps with `config`
+fn account_stores(
+    config: &GameConfig,
+) -> Result<(PlayerStatsStore, HandArchive), Box<dyn std::error::Error>> {
+    let stats = match config.stats_file {
+        Some(ref path) => PlayerStatsStore::load(path.into())?,
+        None => PlayerStatsStore::default(),
+    };
+    let archive = match config.hand_history_dir {
+        Some(ref dir) => HandArchive::open(dir.into())?,
+        None => HandArchive::default(),
+    };
+    Ok((stats, archive))
+}
+
+/// Replay the recordings of a session and report, for every client, whether it was sent what
+/// it was sent then
+async fn replay(dir: &Path, config: GameConfig) -> Result<(), Box<dyn std::error::Error>> {
+    for client in session_recording::replay(dir, config).await? {
+        match client.divergence {
+            None => println!(
+                "{}: the {} messages recorded were sent again",
+                client.client_id, client.recorded
+            ),
+            Some(divergence) => {
+                println!(
+                    "{}: {} messages recorded, {} replayed, {} #{} differs",
+                    client.client_id,
+                    client.recorded,
+                    client.replayed.len(),
+                    divergence.message_type,
+                    divergence.index + 1
+                );
+                let show = |message: Option<serde_json::Value>| {
+                    message.map_or("none".to_string(), |message| message.to_string())
+                };
+                println!("  recorded: {}", show(divergence.recorded));
+                println!("  replayed: {}", show(divergence.replayed));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The table settings, from the `POKERS_*` environment variables
+fn config_from_env() -> Result<GameConfig, Box<dyn std::error::Error>> {
+    // Language of the messages sent to clients, e.g. POKERS_LOCALE=zh
+    let locale = std::env::var("POKERS_LOCALE")
+        .ok()
+        .and_then(|code| Locale::from_code(&code))
+        .unwrap_or_default();
+
+    // Seconds players have to act before they check or fold, e.g. POKERS_ACTION_TIMEOUT=30
+    let action_timeout = std::env::var("POKERS_ACTION_TIMEOUT")
+        .ok()
+        .and_then(|secs| secs.parse::<f64>().ok());
+
+    // Session seed to reproduce every hand of a run, e.g. POKERS_SESSION_SEED=42
+    let session_seed = std::env::var("POKERS_SESSION_SEED")
+        .ok()
+        .and_then(|seed| seed.parse::<u64>().ok());
+
+    // Id of the table in hand records and messages, e.g. POKERS_TABLE_ID=main; random otherwise
+    let table_id = std::env::var("POKERS_TABLE_ID").ok();
+
+    // Players joining mid-session post a dead blind, or wait for the big blind with
+    // POKERS_MISSED_BLINDS=wait
+    let missed_blinds = match std::env::var("POKERS_MISSED_BLINDS").as_deref() {
+        Ok("wait") => MissedBlinds::WaitForBigBlind,
+        _ => MissedBlinds::PostDead,
+    };
+
+    // Straddles are off unless POKERS_STRADDLE is utg or button (Mississippi),
+    // POKERS_MAX_STRADDLES allows re-straddles
+    let straddles = match std::env::var("POKERS_STRADDLE").as_deref() {
+        Ok("utg") => Straddles::Utg,
+        Ok("button") => Straddles::Button,
+        _ => Straddles::Off,
+    };
+    let max_straddles = std::env::var("POKERS_MAX_STRADDLES")
+        .ok()
+        .and_then(|max| max.parse::<u8>().ok())
+        .unwrap_or(1);
+
+    // Antes, e.g. POKERS_ANTE=10 POKERS_ANTE_STRUCTURE=bb for a big blind ante. A player who
+    // cannot cover both pays the blind first, or the ante with POKERS_ANTE_FIRST=1
+    let ante = std::env::var("POKERS_ANTE")
+        .ok()
+        .and_then(|ante| ante.parse::<f64>().ok())
+        .unwrap_or(0.0);
+    let ante_structure = match std::env::var("POKERS_ANTE_STRUCTURE").as_deref() {
+        Ok("bb") => AnteStructure::BigBlind,
+        Ok("only") => AnteStructure::AnteOnly,
+        _ => AnteStructure::EveryPlayer,
+    };
+    let ante_first = std::env::var("POKERS_ANTE_FIRST").is_ok_and(|v| v == "1");
+
+    // Blind structure of a tournament table, e.g. POKERS_BLIND_LEVELS=5/10:10,10/20:10+5 for
+    // two 10 minute levels and a 5 minute break; fixed blinds otherwise
+    let blind_levels = match std::env::var("POKERS_BLIND_LEVELS") {
+        Ok(schedule) => BlindLevel::parse(&schedule)?,
+        Err(_) => Vec::new(),
+    };
+    // Levels new players can join during, e.g. POKERS_LATE_REGISTRATION_LEVELS=4; open otherwise
+    let late_registration_levels = std::env::var("POKERS_LATE_REGISTRATION_LEVELS")
+        .ok()
+        .and_then(|levels| levels.parse::<usize>().ok());
+    // Prize pool of a tournament table, e.g. POKERS_BUY_IN=100 POKERS_PAYOUTS=0.65,0.35 for
+    // the first two places; winner takes all without payouts
+    let buy_in = std::env::var("POKERS_BUY_IN")
+        .ok()
+        .and_then(|buy_in| buy_in.parse::<f64>().ok())
+        .filter(|buy_in| buy_in.is_finite() && *buy_in >= 0.0)
+        .unwrap_or(0.0);
+    let payouts = match std::env::var("POKERS_PAYOUTS") {
+        Ok(shares) => shares
+            .split(',')
+            .map(|share| match share.trim().parse::<f64>() {
+                Ok(share) if share.is_finite() && share >= 0.0 => Ok(share),
+                _ => Err(format!("Invalid payout share: {}", share)),
+            })
+            .collect::<Result<Vec<f64>, String>>()?,
+        Err(_) => Vec::new(),
+    };
+    // Satellites pay POKERS_SATELLITE_SEATS equal seats in place of the payouts, and
+    // progressive knockouts put POKERS_BOUNTY on every entry's head
+    let satellite_seats = std::env::var("POKERS_SATELLITE_SEATS")
+        .ok()
+        .and_then(|seats| seats.parse::<u32>().ok())
+        .filter(|seats| *seats > 0);
+    let bounty = std::env::var("POKERS_BOUNTY")
+        .ok()
+        .and_then(|bounty| bounty.parse::<f64>().ok())
+        .filter(|bounty| bounty.is_finite() && *bounty >= 0.0)
+        .unwrap_or(0.0);
+
+    // Disconnected players to act are folded with POKERS_DISCONNECT_PROTECTION=fold, or stay in
+    // all-in for what they put in with allin, once POKERS_DISCONNECT_GRACE seconds have passed
+    let disconnect_protection = match std::env::var("POKERS_DISCONNECT_PROTECTION").as_deref() {
+        Ok("fold") => DisconnectProtection::Fold,
+        Ok("allin") => DisconnectProtection::AllIn,
+        _ => DisconnectProtection::Off,
+    };
+    let disconnect_grace = std::env::var("POKERS_DISCONNECT_GRACE")
+        .ok()
+        .and_then(|secs| secs.parse::<f64>().ok())
+        .unwrap_or(30.0);
+
+    // Endpoints told about finished hands and players coming and going, e.g.
+    // POKERS_WEBHOOKS=https://example.com/hooks,discord=https://discord.com/api/webhooks/...
+    // POKERS_BIG_POT_ALERT=100 also sends pots of 100 big blinds or more as big pots
+    let webhooks = match std::env::var("POKERS_WEBHOOKS") {
+        Ok(list) => Webhook::parse(&list)?,
+        Err(_) => Vec::new(),
+    };
+    let big_pot_alert = std::env::var("POKERS_BIG_POT_ALERT")
+        .ok()
+        .and_then(|bbs| bbs.parse::<f64>().ok());
+
+    // Rake of every pot that sees a flop, e.g. POKERS_RAKE=0.05 POKERS_RAKE_CAP=30, and the
+    // jackpot drops of pots above a threshold, e.g. POKERS_JACKPOT_DROPS=badBeat:1@20.
+    // POKERS_BAD_BEAT=badBeat reports quads or better beaten at the showdown for that jackpot
+    let rake = match std::env::var("POKERS_RAKE") {
+        Ok(rate) => {
+            let rate = rate.parse::<f64>().map_err(|_| "Invalid POKERS_RAKE")?;
+            let cap = std::env::var("POKERS_RAKE_CAP")
+                .ok()
+                .and_then(|cap| cap.parse::<f64>().ok());
+            Some(Rake::checked(rate, cap, true)?)
+        }
+        Err(_) => None,
+    };
+    let jackpot_drops = match std::env::var("POKERS_JACKPOT_DROPS") {
+        Ok(list) => JackpotDrop::parse(&list)?,
+        Err(_) => Vec::new(),
+    };
+    let bad_beat = std::env::var("POKERS_BAD_BEAT")
+        .ok()
+        .map(|jackpot| BadBeat::new(jackpot, HandCategory::FourOfAKind));
+
+    // Every connection is recorded to POKERS_RECORD_SESSIONS/<session id>/<client id>.jsonl,
+    // e.g. POKERS_RECORD_SESSIONS=recordings, to be replayed with `replay <session dir>`
+    let session_recording_dir = std::env::var("POKERS_RECORD_SESSIONS").ok();
+    // The other players of the hand histories go by a pseudonym of the table once the hands
+    // are older than POKERS_PSEUDONYMIZE_AFTER_DAYS, e.g. POKERS_PSEUDONYMIZE_AFTER_DAYS=90
+    let pseudonymize_after_days = match std::env::var("POKERS_PSEUDONYMIZE_AFTER_DAYS") {
+        Ok(days) => Some(
+            days.parse::<f64>()
+                .ok()
+                .filter(|days| game_server::valid_days(*days))
+                .ok_or("Invalid POKERS_PSEUDONYMIZE_AFTER_DAYS")?,
+        ),
+        Err(_) => None,
+    };
+
+    // Amounts are also broadcast as money with POKERS_CURRENCY, POKERS_CHIP_VALUE or
+    // POKERS_DECIMALS, e.g. POKERS_CURRENCY=$ POKERS_CHIP_VALUE=0.01 POKERS_DECIMALS=2 for
+    // chips counted in cents
+    let currency = std::env::var("POKERS_CURRENCY").ok();
+    let chip_value = std::env::var("POKERS_CHIP_VALUE").ok();
+    let decimals = std::env::var("POKERS_DECIMALS").ok();
+    let chip_format = if currency.is_some() || chip_value.is_some() || decimals.is_some() {
+        let chip_value = match chip_value {
+            Some(value) => value.parse().map_err(|_| "Invalid POKERS_CHIP_VALUE")?,
+            None => 1.0,
+        };
+        let decimals = match decimals {
+            Some(decimals) => Some(decimals.parse().map_err(|_| "Invalid POKERS_DECIMALS")?),
+            None => None,
+        };
+        let format =
+            ChipFormat::checked(chip_value, ChipFormat::default().denominations, decimals)?;
+        Some(ChipFormat {
+            symbol: currency.unwrap_or_default(),
+            ..format
+        })
+    } else {
+        None
+    };
+
+    // The admin API answers the requests bearing POKERS_ADMIN_TOKEN, off when unset
+    let admin_token = std::env::var("POKERS_ADMIN_TOKEN")
+        .ok()
+        .filter(|token| !token.is_empty());
+
+    Ok(GameConfig {
+        max_players: 6,
+        default_stack_size: 1000.0,
+        small_blind: 5.0,
+        big_blind: 10.0,
+        ante,
+        ante_structure,
+        ante_first,
+        stats_file: Some("player_stats.json".to_string()),
+        hand_history_dir: Some("hand_histories".to_string()),
+        session_recording_dir,
+        pseudonymize_after_days,
+        chip_format,
+        rules: Default::default(),
+        insurance: false,
+        locale,
+        action_timeout,
+        session_seed,
+        missed_blinds,
+        straddles,
+        max_straddles,
+        table_id,
+        blind_levels,
+        late_registration_levels,
+        buy_in,
+        payouts,
+        satellite_seats,
+        bounty,
+        disconnect_protection,
+        disconnect_grace,
+        webhooks,
+        big_pot_alert,
+        rake,
+        jackpot_drops,
+        bad_beat,
+        admin_token,
+    })
+}
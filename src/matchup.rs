@@ -0,0 +1,592 @@
+// matchup.rs - Head-to-head evaluation of two agents
+use crate::parallel::{Job, JobProgress};
+use crate::seeds::hand_seed;
+use crate::state::action::{Action, ActionEnum};
+use crate::state::stage::Stage;
+use crate::state::{State, StateStatus};
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+use serde::{Serialize, Serializer};
+
+/// Blinds of every hand, so that results are in big blinds
+const SMALL_BLIND: f64 = 0.5;
+const BIG_BLIND: f64 = 1.0;
+
+const BOOTSTRAP_SAMPLES: usize = 1000;
+const STREETS: [Stage; 4] = [Stage::Preflop, Stage::Flop, Stage::Turn, Stage::River];
+const AGENTS: [&str; 2] = ["a", "b"];
+const ELO_ITERATIONS: usize = 200;
+
+/// Win rate of agent A from one position
+#[pyclass]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PositionResult {
+    /// `small_blind` or `big_blind`
+    #[pyo3(get)]
+    pub position: String,
+
+    #[pyo3(get)]
+    pub hands: u64,
+
+    /// Big blinds won per 100 hands
+    #[pyo3(get)]
+    pub win_rate: f64,
+}
+
+/// Decisions of one agent on one street
+#[pyclass]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StreetAggression {
+    /// `a` or `b`
+    #[pyo3(get)]
+    pub agent: String,
+
+    #[pyo3(get)]
+    #[serde(serialize_with = "stage_name")]
+    pub stage: Stage,
+
+    #[pyo3(get)]
+    pub decisions: u64,
+
+    #[pyo3(get)]
+    pub bets_raises: u64,
+
+    /// All-ins for less than the call included
+    #[pyo3(get)]
+    pub calls: u64,
+
+    #[pyo3(get)]
+    pub checks: u64,
+
+    #[pyo3(get)]
+    pub folds: u64,
+}
+
+#[pymethods]
+impl StreetAggression {
+    /// Share of the decisions that bet or raise, 0 without decisions
+    #[getter]
+    pub fn aggression_frequency(&self) -> f64 {
+        if self.decisions == 0 {
+            return 0.0;
+        }
+        self.bets_raises as f64 / self.decisions as f64
+    }
+
+    /// Bets and raises per call, `None` without calls
+    #[getter]
+    pub fn aggression_factor(&self) -> Option<f64> {
+        (self.calls > 0).then(|| self.bets_raises as f64 / self.calls as f64)
+    }
+
+    pub fn __str__(&self) -> PyResult<String> {
+        Ok(format!("{:#?}", self))
+    }
+}
+
+/// Stages by their Python names, `preflop` to `river`
+fn stage_name<S: Serializer>(stage: &Stage, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(stage.__str__())
+}
+
+/// Streets with their aggression frequency and factor next to the counts
+fn with_ratios<S: Serializer>(
+    aggression: &[StreetAggression],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    #[derive(Serialize)]
+    struct WithRatios<'a> {
+        #[serde(flatten)]
+        street: &'a StreetAggression,
+        aggression_frequency: f64,
+        aggression_factor: Option<f64>,
+    }
+
+    serializer.collect_seq(aggression.iter().map(|street| WithRatios {
+        street,
+        aggression_frequency: street.aggression_frequency(),
+        aggression_factor: street.aggression_factor(),
+    }))
+}
+
+/// Results of agent A against agent B, see `evaluate_matchup`
+#[pyclass]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MatchupReport {
+    /// Hands played, twice the deals when duplicate
+    #[pyo3(get)]
+    pub hands: u64,
+
+    #[pyo3(get)]
+    pub duplicate: bool,
+
+    #[pyo3(get)]
+    pub seed: u64,
+
+    /// Big blinds agent A won per 100 hands, what agent B lost
+    #[pyo3(get)]
+    pub win_rate: f64,
+
+    /// 95% bootstrap confidence interval of `win_rate`, resampling deals
+    #[pyo3(get)]
+    pub ci_low: f64,
+
+    #[pyo3(get)]
+    pub ci_high: f64,
+
+    /// Deals agent A won chips on, over both seatings when duplicate
+    #[pyo3(get)]
+    pub deals_won: u64,
+
+    #[pyo3(get)]
+    pub deals_lost: u64,
+
+    #[pyo3(get)]
+    pub positions: Vec<PositionResult>,
+
+    /// Agent A's streets, then agent B's
+    #[pyo3(get)]
+    #[serde(serialize_with = "with_ratios")]
+    pub aggression: Vec<StreetAggression>,
+}
+
+#[pymethods]
+impl MatchupReport {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    pub fn __str__(&self) -> PyResult<String> {
+        Ok(format!("{:#?}", self))
+    }
+}
+
+/// Results of every pair of agents on the same deals, see `round_robin`
+#[pyclass]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LeagueTable {
+    #[pyo3(get)]
+    pub names: Vec<String>,
+
+    /// `cross_table[i][j]`: big blinds agent `i` won per 100 hands against agent `j`
+    #[pyo3(get)]
+    pub cross_table: Vec<Vec<f64>>,
+
+    /// Elo-style rating of every agent, 1500 on average
+    #[pyo3(get)]
+    pub ratings: Vec<f64>,
+
+    /// Duplicate matchup of every pair `i < j`, in order, with agent `i` as agent A
+    #[pyo3(get)]
+    pub matchups: Vec<MatchupReport>,
+}
+
+#[pymethods]
+impl LeagueTable {
+    /// Names and ratings, best first
+    pub fn standings(&self) -> Vec<(String, f64)> {
+        let mut standings: Vec<(String, f64)> = self
+            .names
+            .iter()
+            .cloned()
+            .zip(self.ratings.iter().copied())
+            .collect();
+        standings.sort_by(|a, b| b.1.total_cmp(&a.1));
+        standings
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    pub fn __str__(&self) -> PyResult<String> {
+        Ok(format!("{:#?}", self))
+    }
+}
+
+/// Play hand `seed` heads-up to the end, `agents[p]` (0 for A, 1 for B) acting for player `p`.
+/// `act(agent, state, player)` is shown the hand from the player's perspective only.
+fn play_hand<F>(seed: u64, stack_bb: f64, agents: [usize; 2], act: &mut F) -> PyResult<State>
+where
+    F: FnMut(usize, State, u64) -> PyResult<Action>,
+{
+    let mut state = State::from_seed(2, 0, SMALL_BLIND, BIG_BLIND, stack_bb, seed, false, 0.0)
+        .map_err(|e| PyValueError::new_err(format!("{:?}", e)))?;
+    while !state.final_state {
+        if !matches!(state.status, StateStatus::Ok) {
+            return Err(PyRuntimeError::new_err(format!(
+                "Hand with seed {} was aborted: {:?}",
+                seed, state.status
+            )));
+        }
+        let player = state.current_player;
+        let action = act(
+            agents[player as usize],
+            state.with_perspective(Some(player)),
+            player,
+        )?;
+        state = state.apply_action(action);
+    }
+    Ok(state)
+}
+
+/// Percentiles of the mean of `samples` over bootstrap resamples
+fn bootstrap_interval(samples: &[f64], seed: u64) -> (f64, f64) {
+    if samples.is_empty() {
+        return (0.0, 0.0);
+    }
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut means: Vec<f64> = (0..BOOTSTRAP_SAMPLES)
+        .map(|_| {
+            let total: f64 = (0..samples.len())
+                .map(|_| samples[rng.gen_range(0..samples.len())])
+                .sum();
+            total / samples.len() as f64
+        })
+        .collect();
+    means.sort_by(f64::total_cmp);
+    let low = (BOOTSTRAP_SAMPLES as f64 * 0.025) as usize;
+    let high = ((BOOTSTRAP_SAMPLES as f64 * 0.975) as usize).min(BOOTSTRAP_SAMPLES - 1);
+    (means[low], means[high])
+}
+
+/// See `evaluate_matchup`, with `act(agent, state, player)` choosing for agent 0 (A) or 1 (B)
+pub fn run_matchup<F>(
+    n_hands: u64,
+    seed: u64,
+    duplicate: bool,
+    stack_bb: f64,
+    mut act: F,
+) -> PyResult<MatchupReport>
+where
+    F: FnMut(usize, State, u64) -> PyResult<Action>,
+{
+    // With the button at 0, player 1 posts the small blind heads-up
+    let positions = ["big_blind", "small_blind"];
+    let mut position_results = [(0u64, 0.0); 2];
+    let mut aggression: Vec<StreetAggression> = AGENTS
+        .iter()
+        .flat_map(|agent| {
+            STREETS.iter().map(|&stage| StreetAggression {
+                agent: agent.to_string(),
+                stage,
+                decisions: 0,
+                bets_raises: 0,
+                calls: 0,
+                checks: 0,
+                folds: 0,
+            })
+        })
+        .collect();
+
+    // A's result per deal, averaged over both seatings when duplicate
+    let mut samples = Vec::with_capacity(n_hands as usize);
+    for deal in 0..n_hands {
+        let deal_seed = hand_seed(seed, deal);
+        // Without duplicate the agents swap seats every hand
+        let seatings = if duplicate {
+            vec![0, 1]
+        } else {
+            vec![(deal % 2) as usize]
+        };
+
+        let mut total = 0.0;
+        for &seat_a in &seatings {
+            let agents = if seat_a == 0 { [0, 1] } else { [1, 0] };
+            let state = play_hand(deal_seed, stack_bb, agents, &mut act)?;
+
+            let reward = state.players_state[seat_a].reward;
+            total += reward;
+            position_results[seat_a].0 += 1;
+            position_results[seat_a].1 += reward;
+
+            for record in &state.action_list {
+                let Some(street) = STREETS.iter().position(|&s| s == record.stage) else {
+                    continue;
+                };
+                let tally =
+                    &mut aggression[agents[record.player as usize] * STREETS.len() + street];
+                tally.decisions += 1;
+                match record.action.action {
+                    ActionEnum::Fold => tally.folds += 1,
+                    ActionEnum::CheckCall if record.to_call > 0.0 => tally.calls += 1,
+                    ActionEnum::CheckCall => tally.checks += 1,
+                    ActionEnum::BetRaise if record.chips <= record.to_call => tally.calls += 1,
+                    ActionEnum::BetRaise => tally.bets_raises += 1,
+                }
+            }
+        }
+        samples.push(total / seatings.len() as f64);
+    }
+
+    let hands = position_results.iter().map(|(hands, _)| hands).sum();
+    let win_rate = if samples.is_empty() {
+        0.0
+    } else {
+        samples.iter().sum::<f64>() / samples.len() as f64 * 100.0
+    };
+    let (ci_low, ci_high) = bootstrap_interval(&samples, seed);
+    let deals_won = samples.iter().filter(|&&sample| sample > 0.0).count() as u64;
+    let deals_lost = samples.iter().filter(|&&sample| sample < 0.0).count() as u64;
+
+    Ok(MatchupReport {
+        hands,
+        duplicate,
+        seed,
+        win_rate,
+        ci_low: ci_low * 100.0,
+        ci_high: ci_high * 100.0,
+        deals_won,
+        deals_lost,
+        positions: position_results
+            .iter()
+            .zip(positions)
+            .rev()
+            .map(|(&(hands, won), position)| PositionResult {
+                position: position.to_string(),
+                hands,
+                win_rate: if hands > 0 {
+                    won / hands as f64 * 100.0
+                } else {
+                    0.0
+                },
+            })
+            .collect(),
+        aggression,
+    })
+}
+
+/// Bradley-Terry strengths fitted to the score of every matchup `(i, j, score of i, deals)`
+/// by minorization-maximization, on the Elo scale. Every pair counts one more tied deal, so
+/// that an agent that never scores keeps a finite rating.
+fn elo_ratings(n_agents: usize, results: &[(usize, usize, f64, f64)]) -> Vec<f64> {
+    let mut scores = vec![0.0; n_agents];
+    let mut games = vec![vec![0.0; n_agents]; n_agents];
+    for &(i, j, score, deals) in results {
+        scores[i] += score + 0.5;
+        scores[j] += deals - score + 0.5;
+        games[i][j] += deals + 1.0;
+        games[j][i] += deals + 1.0;
+    }
+
+    let mut strengths = vec![1.0; n_agents];
+    for _ in 0..ELO_ITERATIONS {
+        let next: Vec<f64> = (0..n_agents)
+            .map(|i| {
+                let expected: f64 = (0..n_agents)
+                    .filter(|&j| j != i)
+                    .map(|j| games[i][j] / (strengths[i] + strengths[j]))
+                    .sum();
+                if expected > 0.0 {
+                    scores[i] / expected
+                } else {
+                    1.0
+                }
+            })
+            .collect();
+        // Keep the geometric mean at 1, so that ratings average 1500
+        let log_mean = next.iter().map(|s| s.ln()).sum::<f64>() / n_agents as f64;
+        strengths = next.iter().map(|s| s / log_mean.exp()).collect();
+    }
+    strengths
+        .iter()
+        .map(|s| 1500.0 + 400.0 * s.log10())
+        .collect()
+}
+
+/// See `round_robin`, with `act(agent, state, player)` choosing for any of the agents
+pub fn run_league<F>(
+    names: &[String],
+    n_hands: u64,
+    seed: u64,
+    stack_bb: f64,
+    progress: &JobProgress,
+    act: F,
+) -> Result<LeagueTable, String>
+where
+    F: Fn(usize, State, u64) -> PyResult<Action> + Sync,
+{
+    let pairs: Vec<(usize, usize)> = (0..names.len())
+        .flat_map(|i| (i + 1..names.len()).map(move |j| (i, j)))
+        .collect();
+    // Every pair plays the same deals
+    let matchups = pairs
+        .par_iter()
+        .map(|&(i, j)| {
+            if progress.cancelled() {
+                return Err("The league was cancelled".to_string());
+            }
+            let report = run_matchup(n_hands, seed, true, stack_bb, |agent, state, player| {
+                act([i, j][agent], state, player)
+            })
+            .map_err(|e| format!("{} against {}: {}", names[i], names[j], e))?;
+            progress.step();
+            Ok(report)
+        })
+        .collect::<Result<Vec<MatchupReport>, String>>()?;
+
+    let mut cross_table = vec![vec![0.0; names.len()]; names.len()];
+    let mut results = Vec::with_capacity(pairs.len());
+    for (&(i, j), report) in pairs.iter().zip(&matchups) {
+        cross_table[i][j] = report.win_rate;
+        cross_table[j][i] = 0.0 - report.win_rate;
+        let tied = n_hands - report.deals_won - report.deals_lost;
+        let score = report.deals_won as f64 + 0.5 * tied as f64;
+        results.push((i, j, score, n_hands as f64));
+    }
+
+    Ok(LeagueTable {
+        names: names.to_vec(),
+        cross_table,
+        ratings: elo_ratings(names.len(), &results),
+        matchups,
+    })
+}
+
+/// Play `agent_a` against `agent_b` heads-up with 0.5/1 blinds and `stack_bb` stacks, both
+/// `Callable[[State, int], Action]` like the bots of the table server. Hand `n` is dealt from
+/// `hand_seed(seed, n)`. When `duplicate`, every deal is played twice with the agents in each
+/// other's seats, so that the cards even out; otherwise the agents change seats every hand.
+#[pyfunction]
+#[pyo3(signature = (agent_a, agent_b, n_hands, seed=0, duplicate=true, stack_bb=100.0))]
+pub fn evaluate_matchup(
+    agent_a: &PyAny,
+    agent_b: &PyAny,
+    n_hands: u64,
+    seed: u64,
+    duplicate: bool,
+    stack_bb: f64,
+) -> PyResult<MatchupReport> {
+    let agents = [agent_a, agent_b];
+    run_matchup(
+        n_hands,
+        seed,
+        duplicate,
+        stack_bb,
+        |agent, state, player| agents[agent].call1((state, player))?.extract(),
+    )
+}
+
+/// Start a league in the background: every pair of `agents` plays a duplicate matchup (see
+/// `evaluate_matchup`) over the same `n_hands` deals, the pairs in parallel on `threads`
+/// threads. The job's result is a `LeagueTable`. The agents only run one at a time, as they
+/// need the GIL, but the hands are played around them concurrently.
+#[pyfunction]
+#[pyo3(signature = (agents, n_hands, seed=0, stack_bb=100.0, names=None, threads=None))]
+pub fn round_robin(
+    agents: Vec<PyObject>,
+    n_hands: u64,
+    seed: u64,
+    stack_bb: f64,
+    names: Option<Vec<String>>,
+    threads: Option<usize>,
+) -> PyResult<Job> {
+    if agents.len() < 2 {
+        return Err(PyValueError::new_err("A league needs at least two agents"));
+    }
+    let names =
+        names.unwrap_or_else(|| (0..agents.len()).map(|i| format!("Agent {}", i)).collect());
+    if names.len() != agents.len() {
+        return Err(PyValueError::new_err(format!(
+            "Got {} names for {} agents",
+            names.len(),
+            agents.len()
+        )));
+    }
+
+    let pairs = agents.len() * (agents.len() - 1) / 2;
+    Job::try_spawn(pairs, threads, move |progress| {
+        run_league(
+            &names,
+            n_hands,
+            seed,
+            stack_bb,
+            progress,
+            |agent, state, player| {
+                Python::with_gil(|py| agents[agent].call1(py, (state, player))?.extract(py))
+            },
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn raiser_beats_folder() {
+        // A raises the pot, B folds whatever it faces
+        let report = run_matchup(20, 7, true, 100.0, |agent, state, _| {
+            Ok(match agent {
+                0 => Action::new(ActionEnum::BetRaise, state.pot * 2.0),
+                _ => Action::new(ActionEnum::Fold, 0.0),
+            })
+        })
+        .unwrap();
+
+        // A wins the big blind from the small blind, and the small blind from the big blind
+        assert_eq!(report.hands, 40);
+        assert_eq!(report.win_rate, 75.0);
+        assert_eq!((report.ci_low, report.ci_high), (75.0, 75.0));
+        assert_eq!(
+            report.positions[0],
+            PositionResult {
+                position: "small_blind".to_string(),
+                hands: 20,
+                win_rate: 100.0
+            }
+        );
+        assert_eq!(report.positions[1].win_rate, 50.0);
+
+        let preflop_a = &report.aggression[0];
+        assert_eq!((preflop_a.decisions, preflop_a.bets_raises), (20, 20));
+        assert_eq!(preflop_a.aggression_factor(), None);
+        let preflop_b = &report.aggression[STREETS.len()];
+        assert_eq!((preflop_b.decisions, preflop_b.folds), (40, 40));
+        let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(json["aggression"][0]["stage"], "preflop");
+        assert_eq!(json["aggression"][0]["aggression_frequency"], 1.0);
+        assert!(json["aggression"][0]["aggression_factor"].is_null());
+    }
+
+    #[test]
+    fn league_ranks_the_folder_last() {
+        // 0 raises the pot, 1 folds whenever it can, 2 checks and calls
+        let names: Vec<String> = ["raiser", "folder", "caller"]
+            .iter()
+            .map(|n| n.to_string())
+            .collect();
+        let league = run_league(
+            &names,
+            20,
+            3,
+            100.0,
+            &JobProgress::new(3),
+            |agent, state, _| {
+                Ok(match agent {
+                    0 => Action::new(ActionEnum::BetRaise, state.pot * 2.0),
+                    1 => Action::new(ActionEnum::Fold, 0.0),
+                    _ => Action::new(ActionEnum::CheckCall, 0.0),
+                })
+            },
+        )
+        .unwrap();
+
+        assert_eq!(league.matchups.len(), 3);
+        assert_eq!(league.cross_table[0][1], 75.0);
+        assert_eq!(league.cross_table[1][0], -75.0);
+        assert_eq!(league.cross_table[2][2], 0.0);
+        assert_eq!(league.standings()[2].0, "folder");
+        assert!(league.ratings[0] > 1500.0);
+        let mean = league.ratings.iter().sum::<f64>() / 3.0;
+        assert!((mean - 1500.0).abs() < 1e-6);
+
+        let json: serde_json::Value = serde_json::from_str(&league.to_json()).unwrap();
+        assert_eq!(json["names"][1], "folder");
+        assert_eq!(json["cross_table"][0][1], 75.0);
+        assert_eq!(json["matchups"].as_array().unwrap().len(), 3);
+    }
+}
@@ -0,0 +1,317 @@
+// mcts.rs - Determinized Monte Carlo tree search agent over the bet bucket abstraction
+use crate::redeal::redeal_unknown;
+use crate::seeds::hand_seed;
+use crate::state::action::{Action, DefaultPolicy};
+use crate::state::State;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use std::collections::HashMap;
+
+/// A node of the search tree, reached by one action of the abstraction from its parent
+#[derive(Debug, Default)]
+struct Node {
+    /// Player who took the action leading here
+    player: u64,
+    visits: u32,
+    /// Sum of that player's rewards over the visits, in pots of the root
+    value: f64,
+    /// Index of the action in `State.legal_action_mask` and the child it leads to
+    children: Vec<(usize, usize)>,
+}
+
+/// Reference agent searching each decision with determinized MCTS. Every simulation deals the
+/// cards the player cannot see again (`State.redeal_unknown`), walks down the tree picking
+/// actions by UCB1 among fold, check or call and the bet buckets of `legal_action_mask`,
+/// expands one node, then plays the hand out with the `rollout` policy. The action visited the
+/// most is played.
+#[pyclass]
+#[derive(Debug, Clone)]
+pub struct MctsAgent {
+    /// Simulations per decision
+    #[pyo3(get)]
+    pub simulations: usize,
+    /// UCB1 exploration constant, rewards being measured in pots of the searched decision
+    #[pyo3(get)]
+    pub exploration: f64,
+    /// Bet sizes of the abstraction, see `State.legal_action_mask`
+    #[pyo3(get)]
+    pub n_bet_buckets: usize,
+    /// Policy every player follows once a simulation leaves the tree
+    #[pyo3(get)]
+    pub rollout: DefaultPolicy,
+    #[pyo3(get)]
+    pub seed: u64,
+}
+
+impl MctsAgent {
+    pub fn checked(
+        simulations: usize,
+        exploration: f64,
+        n_bet_buckets: usize,
+        rollout: DefaultPolicy,
+        seed: u64,
+    ) -> Result<MctsAgent, String> {
+        if simulations == 0 {
+            return Err("The search needs at least one simulation".to_owned());
+        }
+        if !(exploration >= 0.0 && exploration.is_finite()) {
+            return Err(format!(
+                "The exploration constant cannot be negative, got {}",
+                exploration
+            ));
+        }
+        if n_bet_buckets == 0 {
+            return Err("The abstraction needs at least one bet size".to_owned());
+        }
+        Ok(MctsAgent {
+            simulations,
+            exploration,
+            n_bet_buckets,
+            rollout,
+            seed,
+        })
+    }
+
+    /// Indices of the legal actions of the abstraction in `state`
+    fn legal(&self, state: &State) -> Vec<usize> {
+        state
+            .legal_action_mask(self.n_bet_buckets)
+            .iter()
+            .enumerate()
+            .filter_map(|(index, legal)| legal.then_some(index))
+            .collect()
+    }
+
+    /// Child of `node` maximizing UCB1 for the player choosing among `legal`
+    fn select(&self, tree: &[Node], node: usize, legal: &[usize]) -> Option<(usize, usize)> {
+        let parent_visits = (tree[node].visits.max(1) as f64).ln();
+        let ucb = |child: &Node| {
+            let visits = child.visits.max(1) as f64;
+            child.value / visits + self.exploration * (parent_visits / visits).sqrt()
+        };
+        tree[node]
+            .children
+            .iter()
+            .filter(|(index, _)| legal.contains(index))
+            .copied()
+            .max_by(|a, b| ucb(&tree[a.1]).total_cmp(&ucb(&tree[b.1])))
+    }
+
+    /// One simulation from the determinized root `state`
+    fn simulate(&self, tree: &mut Vec<Node>, mut state: State, scale: f64, rng: &mut StdRng) {
+        let mut path = vec![0];
+        let mut node = 0;
+        while !state.final_state {
+            let legal = self.legal(&state);
+            if legal.is_empty() {
+                break;
+            }
+            let player = state.current_player;
+            let untried: Vec<usize> = legal
+                .iter()
+                .copied()
+                .filter(|index| tree[node].children.iter().all(|(i, _)| i != index))
+                .collect();
+            let (index, child) = match untried.choose(rng) {
+                Some(&index) => {
+                    tree.push(Node {
+                        player,
+                        ..Node::default()
+                    });
+                    let child = tree.len() - 1;
+                    tree[node].children.push((index, child));
+                    (index, child)
+                }
+                None => match self.select(tree, node, &legal) {
+                    Some(edge) => edge,
+                    None => break,
+                },
+            };
+            let Some(action) = state.mask_action(index, self.n_bet_buckets) else {
+                break;
+            };
+            state = state.apply_action(action);
+            path.push(child);
+            node = child;
+            if tree[child].visits == 0 {
+                break;
+            }
+        }
+
+        while !state.final_state {
+            state = state.apply_action(state.default_action(self.rollout));
+        }
+        for &node in &path {
+            let node = &mut tree[node];
+            node.visits += 1;
+            if let Some(ps) = state.players_state.get(node.player as usize) {
+                node.value += ps.reward / scale;
+            }
+        }
+    }
+
+    /// Search the current decision of `state` for `player`: every action of the abstraction
+    /// tried at the root, with its visits and the player's average reward in chips
+    pub fn search(&self, state: &State, player: u64) -> Vec<(Action, u32, f64)> {
+        if state.final_state || player != state.current_player {
+            return Vec::new();
+        }
+        let scale = state.pot.max(state.bb).max(f64::MIN_POSITIVE);
+        let base = hand_seed(self.seed ^ state.seed, state.action_list.len() as u64);
+        let mut tree = vec![Node {
+            player,
+            ..Node::default()
+        }];
+        for simulation in 0..self.simulations {
+            let seed = hand_seed(base, simulation as u64);
+            // Without enough cards to deal every opponent, the cards are searched as they are
+            let world = redeal_unknown(state, seed, player, &HashMap::new())
+                .unwrap_or_else(|_| state.clone());
+            let mut rng = StdRng::seed_from_u64(seed);
+            self.simulate(&mut tree, world, scale, &mut rng);
+        }
+        tree[0]
+            .children
+            .iter()
+            .filter_map(|&(index, child)| {
+                let node = &tree[child];
+                let action = state.mask_action(index, self.n_bet_buckets)?;
+                Some((
+                    action,
+                    node.visits,
+                    scale * node.value / node.visits.max(1) as f64,
+                ))
+            })
+            .collect()
+    }
+
+    /// Action of `player` at their decision in `state`
+    pub fn decide(&self, state: &State, player: u64) -> Action {
+        self.search(state, player)
+            .into_iter()
+            .max_by_key(|(_, visits, _)| *visits)
+            .map(|(action, _, _)| action)
+            .unwrap_or_else(|| state.default_action(DefaultPolicy::CheckElseFold))
+    }
+}
+
+#[pymethods]
+impl MctsAgent {
+    #[new]
+    #[pyo3(signature = (simulations=1000, exploration=1.0, n_bet_buckets=4, rollout=DefaultPolicy::CheckCall, seed=0))]
+    pub fn new(
+        simulations: usize,
+        exploration: f64,
+        n_bet_buckets: usize,
+        rollout: DefaultPolicy,
+        seed: u64,
+    ) -> PyResult<MctsAgent> {
+        MctsAgent::checked(simulations, exploration, n_bet_buckets, rollout, seed)
+            .map_err(PyValueError::new_err)
+    }
+
+    /// Agent policy `agent(state, player) -> Action`, for `evaluate_matchup`, `round_robin` or
+    /// `ServerHandle.add_bot`. The search runs without holding the GIL.
+    pub fn __call__(&self, py: Python, state: &State, player: u64) -> Action {
+        py.allow_threads(|| self.decide(state, player))
+    }
+
+    /// Actions of the abstraction searched at the current decision of `state`, with their
+    /// visits and the average reward of `player` (by default the player to act) in chips
+    #[pyo3(name = "search", signature = (state, player=None))]
+    pub fn py_search(
+        &self,
+        py: Python,
+        state: &State,
+        player: Option<u64>,
+    ) -> Vec<(Action, u32, f64)> {
+        py.allow_threads(|| self.search(state, player.unwrap_or(state.current_player)))
+    }
+
+    pub fn __str__(&self) -> PyResult<String> {
+        Ok(format!("{:#?}", self))
+    }
+}
+
+#[cfg(feature = "server")]
+impl crate::bots::BotPlayer for MctsAgent {
+    fn act(
+        &self,
+        state: State,
+        player: u64,
+    ) -> futures_util::future::BoxFuture<'_, Option<Action>> {
+        let agent = self.clone();
+        Box::pin(async move {
+            // The search is CPU-bound, away from the runtime's workers
+            crate::runtime::spawn_blocking(move || agent.decide(&state, player))
+                .await
+                .ok()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::action::ActionEnum;
+    use crate::state::card::Card;
+    use crate::state::stage::Stage;
+
+    fn cards(text: &str) -> Vec<Card> {
+        text.split_whitespace()
+            .map(|name| Card::from_string(name.to_string()).unwrap())
+            .collect()
+    }
+
+    /// Heads-up with button 0, player 1 holding `first` and player 0 `second`, checked to the
+    /// river of `board` with player 1 to act
+    fn river(first: &str, second: &str, board: &str) -> State {
+        let mut deck = cards(first);
+        deck.extend(cards(second));
+        deck.extend(cards(board));
+        for card in Card::collect() {
+            if !deck.contains(&card) {
+                deck.push(card);
+            }
+        }
+        let mut state = State::from_deck(2, 0, 0.5, 1.0, 100.0, deck, false, 0, 0.0).unwrap();
+        while state.stage != Stage::River {
+            state = state.apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+        }
+        state
+    }
+
+    #[test]
+    fn searches_the_abstraction() {
+        let agent = MctsAgent::checked(300, 1.0, 4, DefaultPolicy::CheckCall, 3).unwrap();
+
+        // The nuts facing a pot bet never folds
+        let state = river("D7 C2", "SA HA", "DA CA S9 H4 D3")
+            .apply_action(Action::new(ActionEnum::BetRaise, 2.0));
+        assert_eq!(state.current_player, 0);
+        let nuts = agent.decide(&state, 0);
+        assert_ne!(nuts.action, ActionEnum::Fold);
+
+        // Seven high facing a pot bet on a board it cannot win folds
+        let state = river("SA HA", "D7 C2", "DA CA S9 H4 D3")
+            .apply_action(Action::new(ActionEnum::BetRaise, 2.0));
+        let searched = agent.search(&state, 0);
+        assert_eq!(searched.len(), 6);
+        assert_eq!(
+            searched.iter().map(|(_, visits, _)| visits).sum::<u32>(),
+            300
+        );
+        assert_eq!(agent.decide(&state, 0).action, ActionEnum::Fold);
+
+        // The search only depends on the seed
+        assert_eq!(agent.search(&state, 0), searched);
+        assert!(agent.search(&state, 1).is_empty());
+
+        assert!(MctsAgent::checked(0, 1.0, 4, DefaultPolicy::CheckCall, 0).is_err());
+        assert!(MctsAgent::checked(10, -1.0, 4, DefaultPolicy::CheckCall, 0).is_err());
+        assert!(MctsAgent::checked(10, 1.0, 0, DefaultPolicy::CheckCall, 0).is_err());
+    }
+}
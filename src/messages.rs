@@ -0,0 +1,286 @@
+// messages.rs - Catalog of the user-facing strings, one entry per locale
+use crate::evaluator::Tie;
+use crate::hand_strength::HandCategory;
+use crate::state::action::ActionEnum;
+use crate::state::card::CardRank;
+use crate::state::stage::Stage;
+use itertools::Itertools;
+use pyo3::prelude::*;
+
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Locale {
+    #[default]
+    En,
+    Zh,
+}
+
+impl Locale {
+    /// Parse a language tag such as "en", "zh" or "zh-CN"
+    pub fn from_code(code: &str) -> Option<Locale> {
+        let language = code.split(['-', '_']).next()?.to_ascii_lowercase();
+        match language.as_str() {
+            "en" => Some(Locale::En),
+            "zh" => Some(Locale::Zh),
+            _ => None,
+        }
+    }
+}
+
+/// Errors reported to clients. `code` is stable across locales so clients never need to
+/// match on the text.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClientError {
+    NameTaken(String),
+    InvalidSeat(u8),
+    SeatOccupied(u8),
+    PlayerNotFound,
+    NotSeated,
+    NotEnoughPlayers,
+    InsurancePending,
+    NoActiveGame,
+    NotYourTurn,
+    NoInsuranceOffer,
+    StraddlesDisabled,
+    OnBreak,
+    ClockPaused,
+    RegistrationClosed,
+    RaiseNotAllowed,
+    /// Smallest and largest total bet allowed
+    InvalidRaise(f64, f64),
+    NoHandHistories,
+    /// Leaving the table before folding the hand
+    StillInHand,
+}
+
+impl ClientError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ClientError::NameTaken(_) => "nameTaken",
+            ClientError::InvalidSeat(_) => "invalidSeat",
+            ClientError::SeatOccupied(_) => "seatOccupied",
+            ClientError::PlayerNotFound => "playerNotFound",
+            ClientError::NotSeated => "notSeated",
+            ClientError::NotEnoughPlayers => "notEnoughPlayers",
+            ClientError::InsurancePending => "insurancePending",
+            ClientError::NoActiveGame => "noActiveGame",
+            ClientError::NotYourTurn => "notYourTurn",
+            ClientError::NoInsuranceOffer => "noInsuranceOffer",
+            ClientError::StraddlesDisabled => "straddlesDisabled",
+            ClientError::OnBreak => "onBreak",
+            ClientError::ClockPaused => "clockPaused",
+            ClientError::RegistrationClosed => "registrationClosed",
+            ClientError::RaiseNotAllowed => "raiseNotAllowed",
+            ClientError::InvalidRaise(..) => "invalidRaise",
+            ClientError::NoHandHistories => "noHandHistories",
+            ClientError::StillInHand => "stillInHand",
+        }
+    }
+
+    pub fn text(&self, locale: Locale) -> String {
+        match locale {
+            Locale::En => match self {
+                ClientError::NameTaken(name) => format!("Name '{}' is already taken", name),
+                ClientError::InvalidSeat(seat) => format!("Invalid seat number: {}", seat),
+                ClientError::SeatOccupied(seat) => format!("Seat {} is already occupied", seat),
+                ClientError::PlayerNotFound => "Player not found".to_owned(),
+                ClientError::NotSeated => "Player is not seated".to_owned(),
+                ClientError::NotEnoughPlayers => {
+                    "Need at least 2 players to start the game".to_owned()
+                }
+                ClientError::InsurancePending => "Waiting for an insurance decision".to_owned(),
+                ClientError::NoActiveGame => "No active game".to_owned(),
+                ClientError::NotYourTurn => "Not your turn".to_owned(),
+                ClientError::NoInsuranceOffer => "No insurance offer for this player".to_owned(),
+                ClientError::StraddlesDisabled => {
+                    "Straddles are not allowed at this table".to_owned()
+                }
+                ClientError::OnBreak => "The tournament is on a break".to_owned(),
+                ClientError::ClockPaused => "The tournament clock is paused".to_owned(),
+                ClientError::RegistrationClosed => "Late registration is closed".to_owned(),
+                ClientError::RaiseNotAllowed => "You cannot raise now".to_owned(),
+                ClientError::InvalidRaise(min, max) => {
+                    format!("Raise to between {} and {}", min, max)
+                }
+                ClientError::NoHandHistories => "No hands to export yet".to_owned(),
+                ClientError::StillInHand => "Fold or finish the hand first".to_owned(),
+            },
+            Locale::Zh => match self {
+                ClientError::NameTaken(name) => format!("名字“{}”已被占用", name),
+                ClientError::InvalidSeat(seat) => format!("无效的座位号：{}", seat),
+                ClientError::SeatOccupied(seat) => format!("{}号座位已有人", seat),
+                ClientError::PlayerNotFound => "找不到该玩家".to_owned(),
+                ClientError::NotSeated => "玩家尚未入座".to_owned(),
+                ClientError::NotEnoughPlayers => "至少需要2名玩家才能开始游戏".to_owned(),
+                ClientError::InsurancePending => "正在等待保险决定".to_owned(),
+                ClientError::NoActiveGame => "当前没有进行中的牌局".to_owned(),
+                ClientError::NotYourTurn => "还没轮到你".to_owned(),
+                ClientError::NoInsuranceOffer => "该玩家没有保险报价".to_owned(),
+                ClientError::StraddlesDisabled => "本桌不允许抓头".to_owned(),
+                ClientError::OnBreak => "比赛正在休息".to_owned(),
+                ClientError::ClockPaused => "比赛计时已暂停".to_owned(),
+                ClientError::RegistrationClosed => "延迟报名已截止".to_owned(),
+                ClientError::RaiseNotAllowed => "现在不能加注".to_owned(),
+                ClientError::InvalidRaise(min, max) => {
+                    format!("加注总额须在{}到{}之间", min, max)
+                }
+                ClientError::NoHandHistories => "还没有可导出的牌局".to_owned(),
+                ClientError::StillInHand => "请先弃牌或打完这手牌".to_owned(),
+            },
+        }
+    }
+}
+
+/// Server logs stay in English
+impl std::fmt::Display for ClientError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.text(Locale::En))
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+/// Short labels shown next to the game data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Label {
+    Pot,
+    Board,
+    MainPot,
+    /// Hand description of a pot won without a showdown
+    Uncontested,
+}
+
+impl Label {
+    pub fn text(&self, locale: Locale) -> &'static str {
+        match (self, locale) {
+            (Label::Pot, Locale::En) => "pot",
+            (Label::Pot, Locale::Zh) => "底池",
+            (Label::Board, Locale::En) => "public",
+            (Label::Board, Locale::Zh) => "公共牌",
+            (Label::MainPot, Locale::En) => "Main Pot",
+            (Label::MainPot, Locale::Zh) => "主池",
+            (Label::Uncontested, Locale::En) => "Winner",
+            (Label::Uncontested, Locale::Zh) => "获胜",
+        }
+    }
+}
+
+pub fn hand_category(category: HandCategory, locale: Locale) -> &'static str {
+    match locale {
+        Locale::En => match category {
+            HandCategory::HighCard => "High Card",
+            HandCategory::Pair => "Pair",
+            HandCategory::TwoPair => "Two Pair",
+            HandCategory::ThreeOfAKind => "Three of a Kind",
+            HandCategory::Straight => "Straight",
+            HandCategory::Flush => "Flush",
+            HandCategory::FullHouse => "Full House",
+            HandCategory::FourOfAKind => "Four of a Kind",
+            HandCategory::StraightFlush => "Straight Flush",
+            HandCategory::RoyalFlush => "Royal Flush",
+        },
+        Locale::Zh => match category {
+            HandCategory::HighCard => "高牌",
+            HandCategory::Pair => "一对",
+            HandCategory::TwoPair => "两对",
+            HandCategory::ThreeOfAKind => "三条",
+            HandCategory::Straight => "顺子",
+            HandCategory::Flush => "同花",
+            HandCategory::FullHouse => "葫芦",
+            HandCategory::FourOfAKind => "四条",
+            HandCategory::StraightFlush => "同花顺",
+            HandCategory::RoyalFlush => "皇家同花顺",
+        },
+    }
+}
+
+/// Why a pot was split between `n_players`, e.g. "Split with A-K kickers"
+pub fn split(tie: &Tie, n_players: usize, locale: Locale) -> String {
+    let kickers = |ranks: &[CardRank]| ranks.iter().map(|rank| rank.to_char()).join("-");
+    match locale {
+        Locale::En => match tie {
+            Tie::Board if n_players == 2 => "Both play the board".to_owned(),
+            Tie::Board => "All play the board".to_owned(),
+            Tie::Kickers(ranks) if ranks.len() == 1 => {
+                format!("Split with {} kicker", kickers(ranks))
+            }
+            Tie::Kickers(ranks) => format!("Split with {} kickers", kickers(ranks)),
+            Tie::SameHand => "Split with the same hand".to_owned(),
+        },
+        Locale::Zh => match tie {
+            Tie::Board if n_players == 2 => "双方都打公共牌".to_owned(),
+            Tie::Board => "所有人都打公共牌".to_owned(),
+            Tie::Kickers(ranks) => format!("{}踢脚相同，平分底池", kickers(ranks)),
+            Tie::SameHand => "牌力相同，平分底池".to_owned(),
+        },
+    }
+}
+
+pub fn stage(stage: Stage, locale: Locale) -> &'static str {
+    match locale {
+        Locale::En => match stage {
+            Stage::Preflop => "Preflop",
+            Stage::Flop => "Flop",
+            Stage::Turn => "Turn",
+            Stage::River => "River",
+            Stage::Showdown => "Showdown",
+        },
+        Locale::Zh => match stage {
+            Stage::Preflop => "翻牌前",
+            Stage::Flop => "翻牌",
+            Stage::Turn => "转牌",
+            Stage::River => "河牌",
+            Stage::Showdown => "摊牌",
+        },
+    }
+}
+
+pub fn action(action: ActionEnum, locale: Locale) -> &'static str {
+    match locale {
+        Locale::En => match action {
+            ActionEnum::Fold => "Fold",
+            ActionEnum::CheckCall => "CheckCall",
+            ActionEnum::BetRaise => "BetRaise",
+        },
+        Locale::Zh => match action {
+            ActionEnum::Fold => "弃牌",
+            ActionEnum::CheckCall => "过牌/跟注",
+            ActionEnum::BetRaise => "下注/加注",
+        },
+    }
+}
+
+/// Terminal width of `text`, where CJK characters take two columns
+pub fn display_width(text: &str) -> usize {
+    text.chars()
+        .map(|c| match c {
+            '\u{2e80}'..='\u{9fff}' | '\u{ac00}'..='\u{d7a3}' | '\u{ff00}'..='\u{ff60}' => 2,
+            _ => 1,
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::card::CardRank;
+
+    #[test]
+    fn catalog_covers_every_locale() {
+        assert_eq!(Locale::from_code("zh-CN"), Some(Locale::Zh));
+        assert_eq!(Locale::from_code("EN_us"), Some(Locale::En));
+        assert_eq!(Locale::from_code("fr"), None);
+
+        let error = ClientError::SeatOccupied(3);
+        assert_eq!(error.code(), "seatOccupied");
+        assert_eq!(error.to_string(), "Seat 3 is already occupied");
+        assert_eq!(error.text(Locale::Zh), "3号座位已有人");
+
+        assert_eq!(hand_category(HandCategory::FullHouse, Locale::Zh), "葫芦");
+        assert_eq!(display_width(stage(Stage::Preflop, Locale::Zh)), 6);
+
+        let kickers = Tie::Kickers(vec![CardRank::RA, CardRank::RK]);
+        assert_eq!(split(&kickers, 2, Locale::En), "Split with A-K kickers");
+        assert_eq!(split(&Tie::Board, 2, Locale::En), "Both play the board");
+    }
+}
@@ -0,0 +1,246 @@
+// node_lock.rs - Fixed strategies for chosen players at chosen betting lines
+use crate::betting_line::betting_line;
+use crate::seeds::hand_seed;
+use crate::state::action::{Action, ActionEnum};
+use crate::state::State;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::HashMap;
+
+/// Action of a strategy, in the tokens of `State.betting_line`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum LockedAction {
+    Fold,
+    CheckCall,
+    /// Bet or raise by this fraction of the pot, as in the betting line
+    BetRaise(f64),
+}
+
+impl LockedAction {
+    pub(crate) fn parse(token: &str) -> Option<LockedAction> {
+        match token {
+            "f" => Some(LockedAction::Fold),
+            "x" | "c" => Some(LockedAction::CheckCall),
+            _ => {
+                let size = token
+                    .strip_prefix('b')
+                    .or_else(|| token.strip_prefix('r'))?
+                    .parse::<f64>()
+                    .ok()?;
+                (size > 0.0).then_some(LockedAction::BetRaise(size / 100.0))
+            }
+        }
+    }
+
+    /// The engine action for the current player of `state`, sized like the betting line
+    pub(crate) fn action(self, state: &State) -> Action {
+        match self {
+            LockedAction::Fold => Action::new(ActionEnum::Fold, 0.0),
+            LockedAction::CheckCall => Action::new(ActionEnum::CheckCall, 0.0),
+            LockedAction::BetRaise(size) => {
+                let bet = state.players_state[state.current_player as usize].bet_chips;
+                let max_bet = state
+                    .players_state
+                    .iter()
+                    .filter(|ps| ps.active)
+                    .map(|ps| ps.bet_chips)
+                    .fold(bet, f64::max);
+                let to_call = max_bet - bet;
+                let amount = max_bet + size * (state.pot + to_call);
+                Action::new(ActionEnum::BetRaise, amount)
+            }
+        }
+    }
+}
+
+/// Whether `line` matches `pattern`, where `*` stands for any characters within a street
+/// (anything but `,`) and `?` for any one character
+pub(crate) fn matches_pattern(pattern: &[u8], line: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => line.is_empty(),
+        Some((b'*', rest)) => (0..=line.len())
+            .take_while(|&skip| skip == 0 || line[skip - 1] != b',')
+            .any(|skip| matches_pattern(rest, &line[skip..])),
+        Some((&c, rest)) => match line.split_first() {
+            Some((&l, line)) if c == b'?' || c == l => matches_pattern(rest, line),
+            _ => false,
+        },
+    }
+}
+
+/// Plays a fixed strategy for a player at the betting lines matching a pattern, whatever
+/// action their agent chose, for what-if analyses against a known strategy
+#[pyclass]
+#[derive(Debug, Clone)]
+pub struct NodeLock {
+    /// Pattern of `State.betting_line` at the decision
+    #[pyo3(get)]
+    pub line: String,
+
+    /// Seats after the button of the locked player (0 the button, 1 the small blind, 2 the
+    /// big blind), any player when `None`
+    #[pyo3(get)]
+    pub position: Option<u64>,
+
+    /// Probability of each action token, normalized and sorted by token
+    #[pyo3(get)]
+    pub strategy: Vec<(String, f64)>,
+
+    actions: Vec<LockedAction>,
+}
+
+#[pymethods]
+impl NodeLock {
+    #[new]
+    #[pyo3(signature = (line, strategy, position=None))]
+    pub fn new(
+        line: String,
+        strategy: HashMap<String, f64>,
+        position: Option<u64>,
+    ) -> PyResult<NodeLock> {
+        let mut strategy: Vec<(String, f64)> = strategy.into_iter().collect();
+        strategy.sort_by(|a, b| a.0.cmp(&b.0));
+        if strategy
+            .iter()
+            .any(|(_, weight)| weight.is_nan() || *weight < 0.0)
+        {
+            return Err(PyValueError::new_err("Weights cannot be negative"));
+        }
+        let total: f64 = strategy.iter().map(|(_, weight)| weight).sum();
+        if total <= 0.0 || !total.is_finite() {
+            return Err(PyValueError::new_err(
+                "The strategy needs an action with a positive weight",
+            ));
+        }
+
+        let actions = strategy
+            .iter()
+            .map(|(token, _)| {
+                LockedAction::parse(token)
+                    .ok_or_else(|| PyValueError::new_err(format!("Invalid action '{}'", token)))
+            })
+            .collect::<PyResult<Vec<LockedAction>>>()?;
+        for (_, weight) in strategy.iter_mut() {
+            *weight /= total;
+        }
+
+        Ok(NodeLock {
+            line,
+            position,
+            strategy,
+            actions,
+        })
+    }
+
+    /// Whether the current decision of `state` is locked
+    pub fn matches(&self, state: &State) -> bool {
+        if state.final_state || state.legal_actions.is_empty() {
+            return false;
+        }
+        let position = position_to_act(state);
+        self.position.is_none_or(|p| p == position)
+            && matches_pattern(self.line.as_bytes(), betting_line(state).as_bytes())
+    }
+
+    /// The action the strategy plays at the current decision of `state`, `None` when the
+    /// decision is not locked. Mixed strategies are sampled from the hand's seed and the
+    /// number of actions so far, so replaying a hand replays its locked actions.
+    pub fn action(&self, state: &State) -> Option<Action> {
+        if !self.matches(state) {
+            return None;
+        }
+        let seed = hand_seed(state.seed, state.action_list.len() as u64);
+        let mut draw: f64 = StdRng::seed_from_u64(seed).gen();
+        for ((_, weight), action) in self.strategy.iter().zip(&self.actions) {
+            draw -= weight;
+            if draw < 0.0 {
+                return Some(action.action(state));
+            }
+        }
+        self.actions.last().map(|action| action.action(state))
+    }
+
+    pub fn __str__(&self) -> PyResult<String> {
+        Ok(format!("{:#?}", self))
+    }
+}
+
+/// Seats after the button of the player to act: 0 the button, 1 the small blind, 2 the big
+/// blind
+pub(crate) fn position_to_act(state: &State) -> u64 {
+    let n_players = state.players_state.len() as u64;
+    (state.current_player + n_players - state.button % n_players) % n_players
+}
+
+/// The action of the first lock matching the current decision of `state`
+pub fn locked_action(locks: &[NodeLock], state: &State) -> Option<Action> {
+    locks.iter().find_map(|lock| lock.action(state))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lock(line: &str, strategy: &[(&str, f64)], position: Option<u64>) -> NodeLock {
+        let strategy = strategy.iter().map(|(t, w)| (t.to_string(), *w)).collect();
+        NodeLock::new(line.to_string(), strategy, position).unwrap()
+    }
+
+    #[test]
+    fn patterns_stay_within_a_street() {
+        let matches =
+            |pattern: &str, line: &str| matches_pattern(pattern.as_bytes(), line.as_bytes());
+        assert!(matches("*, x-b*", "r100-c, x-b33"));
+        assert!(matches("*", "f-f-r150"));
+        assert!(!matches("*", "r100-c, x-b33"));
+        assert!(!matches("*, x-b*", "r100-c, x-x, x-b50"));
+        assert!(matches("r???-c, *", "r100-c, x"));
+        assert!(!matches("r??-c, *", "r100-c, x"));
+    }
+
+    #[test]
+    fn locked_big_blind_calls_flop_bets() {
+        // Heads-up with button 0, the big blind is the button
+        let mut state = State::from_seed(2, 0, 1.0, 2.0, 200.0, 3, false, 0.0).unwrap();
+        let calls = lock("*, b*", &[("c", 1.0)], Some(0));
+        for action in [
+            Action::new(ActionEnum::BetRaise, 6.0),
+            Action::new(ActionEnum::CheckCall, 0.0),
+        ] {
+            assert_eq!(calls.action(&state), None);
+            state = state.apply_action(action);
+        }
+        let state = state.apply_action(Action::new(ActionEnum::BetRaise, 4.0));
+        assert_eq!(betting_line(&state), "r100-c, b33");
+        assert_eq!(
+            calls.action(&state),
+            Some(Action::new(ActionEnum::CheckCall, 0.0))
+        );
+
+        // The same spot for the small blind is not locked
+        assert!(!lock("*, b*", &[("c", 1.0)], Some(1)).matches(&state));
+
+        // A pot-sized raise: call 4, then raise by the pot of 20
+        let raise = lock("*, b*", &[("r100", 1.0)], None)
+            .action(&state)
+            .unwrap();
+        assert_eq!(raise.amount, 24.0);
+        let state = state.apply_action(raise);
+        assert_eq!(betting_line(&state), "r100-c, b33-r100");
+    }
+
+    #[test]
+    fn invalid_strategies_are_rejected() {
+        let new = |strategy: &[(&str, f64)]| {
+            let strategy = strategy.iter().map(|(t, w)| (t.to_string(), *w)).collect();
+            NodeLock::new("*".to_string(), strategy, None)
+        };
+        assert!(new(&[("c", 1.0), ("b50", 1.0)]).is_ok());
+        assert!(new(&[("k", 1.0)]).is_err());
+        assert!(new(&[("b", 1.0)]).is_err());
+        assert!(new(&[("c", 0.0)]).is_err());
+        assert!(new(&[("c", -1.0), ("f", 2.0)]).is_err());
+    }
+}
@@ -0,0 +1,719 @@
+// ofc.rs - Open-face Chinese poker: three rows set card by card, fouls, royalties and fantasyland
+use crate::evaluator::rank_five;
+use crate::seeds::shuffled_deck;
+use crate::state::card::{Card, CardRank};
+use crate::table_game::{HandSetup, TableGame};
+use itertools::Itertools;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::cmp::Reverse;
+
+/// Points for winning all three rows against an opponent, on top of the three rows
+const SCOOP_BONUS: i64 = 3;
+
+/// How the cards are dealt after the first five
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OfcVariant {
+    /// One card at a time, eight times
+    Regular,
+    /// Three cards at a time, four times: two are set and one is discarded
+    Pineapple,
+}
+
+impl OfcVariant {
+    /// Cards dealt on a player's `turn`, out of fantasyland
+    fn deal_size(self, turn: u32) -> usize {
+        match (self, turn) {
+            (_, 0) => 5,
+            (OfcVariant::Regular, _) => 1,
+            (OfcVariant::Pineapple, _) => 3,
+        }
+    }
+
+    /// Cards dealt at once to a player in fantasyland
+    fn fantasyland_size(self) -> usize {
+        match self {
+            OfcVariant::Regular => 13,
+            OfcVariant::Pineapple => 14,
+        }
+    }
+
+    /// Most cards a player can be dealt in a hand
+    fn cards_per_player(self) -> usize {
+        match self {
+            OfcVariant::Regular => 13,
+            OfcVariant::Pineapple => 17,
+        }
+    }
+}
+
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OfcRow {
+    /// Three cards
+    Top,
+    Middle,
+    Bottom,
+}
+
+impl OfcRow {
+    fn size(self) -> usize {
+        match self {
+            OfcRow::Top => 3,
+            OfcRow::Middle | OfcRow::Bottom => 5,
+        }
+    }
+}
+
+/// Strength of a row, stronger rows compare greater: the hand category, then the ranks from
+/// the most significant. The missing cards of the top row rank below every card.
+type RowStrength = (Reverse<u64>, [u8; 5]);
+
+fn row_strength(cards: &[Card]) -> RowStrength {
+    // Ranks from 1 for a deuce, larger groups first, then higher ranks
+    let groups: Vec<(usize, u8)> = cards
+        .iter()
+        .map(|c| c.rank as u8 + 1)
+        .counts()
+        .into_iter()
+        .map(|(rank, n)| (n, rank))
+        .sorted()
+        .rev()
+        .collect();
+    let category = match <&[Card; 5]>::try_from(cards) {
+        Ok(five) => rank_five(five).0,
+        Err(_) => match groups.first().map_or(0, |g| g.0) {
+            3 => 7,
+            2 => 9,
+            _ => 10,
+        },
+    };
+
+    let mut ranks = [0; 5];
+    let ordered = groups
+        .iter()
+        .flat_map(|&(n, rank)| std::iter::repeat_n(rank, n));
+    for (slot, rank) in ranks.iter_mut().zip(ordered) {
+        *slot = rank;
+    }
+    // The ace of a five-high straight plays low
+    let straight = matches!(category, 2 | 6);
+    if straight && ranks == [13, 4, 3, 2, 1] {
+        ranks = [4, 3, 2, 1, 0];
+    }
+    (Reverse(category), ranks)
+}
+
+/// Royalties of a full row
+fn royalties(row: OfcRow, cards: &[Card]) -> i64 {
+    let (Reverse(category), ranks) = row_strength(cards);
+    let pair_of_sixes = CardRank::R6 as u8 + 1;
+    match row {
+        // 1 for sixes up to 9 for aces, 10 for deuces trips up to 22 for aces
+        OfcRow::Top => match category {
+            7 => 9 + ranks[0] as i64,
+            9 if ranks[0] >= pair_of_sixes => (ranks[0] - pair_of_sixes) as i64 + 1,
+            _ => 0,
+        },
+        OfcRow::Middle => match category {
+            1 => 50,
+            2 => 30,
+            3 => 20,
+            4 => 12,
+            5 => 8,
+            6 => 4,
+            7 => 2,
+            _ => 0,
+        },
+        OfcRow::Bottom => match category {
+            1 => 25,
+            2 => 15,
+            3 => 10,
+            4 => 6,
+            5 => 4,
+            6 => 2,
+            _ => 0,
+        },
+    }
+}
+
+/// The three rows of one player, set face up
+#[pyclass]
+#[derive(Debug, Clone, Default)]
+pub struct OfcHand {
+    #[pyo3(get)]
+    pub top: Vec<Card>,
+
+    #[pyo3(get)]
+    pub middle: Vec<Card>,
+
+    #[pyo3(get)]
+    pub bottom: Vec<Card>,
+}
+
+impl OfcHand {
+    fn row(&self, row: OfcRow) -> &Vec<Card> {
+        match row {
+            OfcRow::Top => &self.top,
+            OfcRow::Middle => &self.middle,
+            OfcRow::Bottom => &self.bottom,
+        }
+    }
+
+    fn row_mut(&mut self, row: OfcRow) -> &mut Vec<Card> {
+        match row {
+            OfcRow::Top => &mut self.top,
+            OfcRow::Middle => &mut self.middle,
+            OfcRow::Bottom => &mut self.bottom,
+        }
+    }
+
+    /// Rows won against `other`, from -3 to 3
+    fn rows_won(&self, other: &OfcHand) -> i64 {
+        [OfcRow::Top, OfcRow::Middle, OfcRow::Bottom]
+            .into_iter()
+            .map(|row| row_strength(self.row(row)).cmp(&row_strength(other.row(row))) as i64)
+            .sum()
+    }
+
+    /// Points won against `other` once both hands are complete: a point per row, the scoop
+    /// bonus and the difference of the royalties. A fouled hand loses every row and scores
+    /// no royalties.
+    fn score_against(&self, other: &OfcHand) -> i64 {
+        let scoop = 3 + SCOOP_BONUS;
+        match (self.fouled(), other.fouled()) {
+            (true, true) => 0,
+            (true, false) => -scoop - other.royalties(),
+            (false, true) => scoop + self.royalties(),
+            (false, false) => {
+                let rows = match self.rows_won(other) {
+                    3 => scoop,
+                    -3 => -scoop,
+                    rows => rows,
+                };
+                rows + self.royalties() - other.royalties()
+            }
+        }
+    }
+
+    /// Whether the hand plays the next one in fantasyland. A player enters it with queens or
+    /// better on top, and stays with trips on top, a full house or better in the middle, or
+    /// quads or better at the bottom.
+    fn earns_fantasyland(&self, in_fantasyland: bool) -> bool {
+        if !self.is_complete() || self.fouled() {
+            return false;
+        }
+        let (Reverse(top), ranks) = row_strength(&self.top);
+        if in_fantasyland {
+            let (Reverse(middle), _) = row_strength(&self.middle);
+            let (Reverse(bottom), _) = row_strength(&self.bottom);
+            top == 7 || middle <= 4 || bottom <= 3
+        } else {
+            top == 7 || (top == 9 && ranks[0] > CardRank::RQ as u8)
+        }
+    }
+}
+
+#[pymethods]
+impl OfcHand {
+    /// Every row is full
+    pub fn is_complete(&self) -> bool {
+        [OfcRow::Top, OfcRow::Middle, OfcRow::Bottom]
+            .into_iter()
+            .all(|row| self.row(row).len() == row.size())
+    }
+
+    /// A complete hand whose middle row beats the bottom one, or whose top row beats the
+    /// middle one
+    pub fn fouled(&self) -> bool {
+        let (top, middle, bottom) = (
+            row_strength(&self.top),
+            row_strength(&self.middle),
+            row_strength(&self.bottom),
+        );
+        self.is_complete() && (middle > bottom || top > middle)
+    }
+
+    /// Royalties of a complete hand, 0 when fouled or not complete
+    pub fn royalties(&self) -> i64 {
+        if !self.is_complete() || self.fouled() {
+            return 0;
+        }
+        [OfcRow::Top, OfcRow::Middle, OfcRow::Bottom]
+            .into_iter()
+            .map(|row| royalties(row, self.row(row)))
+            .sum()
+    }
+
+    pub fn __str__(&self) -> PyResult<String> {
+        Ok(format!("{:#?}", self))
+    }
+}
+
+/// A hand of open-face Chinese poker. Players take turns from the left of the button, each
+/// setting the cards dealt to them into their rows, until every hand is complete.
+#[pyclass]
+#[derive(Debug, Clone)]
+pub struct OfcState {
+    #[pyo3(get)]
+    pub variant: OfcVariant,
+
+    #[pyo3(get)]
+    pub button: u64,
+
+    /// Rows of each player
+    #[pyo3(get)]
+    pub hands: Vec<OfcHand>,
+
+    /// Players dealt their whole hand at once this hand
+    #[pyo3(get)]
+    pub fantasyland: Vec<bool>,
+
+    #[pyo3(get)]
+    pub current_player: u64,
+
+    /// Cards the current player has to set, seen only by them
+    #[pyo3(get)]
+    pub dealt: Vec<Card>,
+
+    /// Turns each player has taken, 0 for the first five cards
+    #[pyo3(get)]
+    pub turns: Vec<u32>,
+
+    #[pyo3(get)]
+    pub final_state: bool,
+
+    /// Points each player won or lost against every other player, once the hand is over
+    #[pyo3(get)]
+    pub scores: Vec<i64>,
+
+    /// Players who play the next hand in fantasyland, once the hand is over
+    #[pyo3(get)]
+    pub fantasyland_next: Vec<bool>,
+
+    /// Cards thrown away by each player, seen only by them
+    discards: Vec<Vec<Card>>,
+
+    deck: Vec<Card>,
+}
+
+impl OfcState {
+    pub fn checked(
+        n_players: u64,
+        button: u64,
+        deck: Vec<Card>,
+        variant: OfcVariant,
+        fantasyland: Option<Vec<bool>>,
+    ) -> Result<OfcState, String> {
+        let n = n_players as usize;
+        let max_players = 52 / variant.cards_per_player();
+        if !(2..=max_players).contains(&n) {
+            return Err(format!(
+                "{:?} OFC is played by 2 to {} players",
+                variant, max_players
+            ));
+        }
+        if button >= n_players {
+            return Err("The button must be between the players".to_owned());
+        }
+        if deck.len() < n * variant.cards_per_player() {
+            return Err(format!(
+                "The deck must hold at least {} cards per player",
+                variant.cards_per_player()
+            ));
+        }
+        if deck.iter().unique().count() != deck.len() {
+            return Err("The deck holds a card more than once".to_owned());
+        }
+        let fantasyland = fantasyland.unwrap_or_else(|| vec![false; n]);
+        if fantasyland.len() != n {
+            return Err("fantasyland needs an entry per player".to_owned());
+        }
+
+        let mut state = OfcState {
+            variant,
+            button,
+            hands: vec![OfcHand::default(); n],
+            fantasyland,
+            current_player: (button + 1) % n_players,
+            dealt: Vec::new(),
+            turns: vec![0; n],
+            final_state: false,
+            scores: vec![0; n],
+            fantasyland_next: vec![false; n],
+            discards: vec![Vec::new(); n],
+            deck,
+        };
+        state.deal();
+        Ok(state)
+    }
+
+    /// Deal the current player the cards of their turn
+    fn deal(&mut self) {
+        let player = self.current_player as usize;
+        let n = match self.fantasyland[player] {
+            true => self.variant.fantasyland_size(),
+            false => self.variant.deal_size(self.turns[player]),
+        };
+        self.dealt = self.deck.drain(..n).collect();
+    }
+
+    /// Cards the current player throws away on this turn
+    fn discards_due(&self) -> usize {
+        let player = self.current_player as usize;
+        let turn_of_three = self.turns[player] > 0 || self.fantasyland[player];
+        match self.variant {
+            OfcVariant::Pineapple if turn_of_three => 1,
+            _ => 0,
+        }
+    }
+
+    /// Score the complete hands and see who plays the next hand in fantasyland
+    fn settle(&mut self) {
+        self.final_state = true;
+        self.dealt.clear();
+        for (i, hand) in self.hands.iter().enumerate() {
+            self.scores[i] = self
+                .hands
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(_, other)| hand.score_against(other))
+                .sum();
+            self.fantasyland_next[i] = hand.earns_fantasyland(self.fantasyland[i]);
+        }
+    }
+
+    pub fn apply(
+        &self,
+        placements: &[(Card, OfcRow)],
+        discard: Option<Card>,
+    ) -> Result<OfcState, String> {
+        if self.final_state {
+            return Err("The hand is over".to_owned());
+        }
+        if discard.is_some() as usize != self.discards_due() {
+            return Err(match self.discards_due() {
+                0 => "No card is discarded on this turn".to_owned(),
+                _ => "One card must be discarded on this turn".to_owned(),
+            });
+        }
+        let mut set: Vec<Card> = placements.iter().map(|p| p.0).chain(discard).collect();
+        let mut dealt = self.dealt.clone();
+        set.sort_by_key(|c| c.index());
+        dealt.sort_by_key(|c| c.index());
+        if set != dealt {
+            return Err("Every card dealt must be set or discarded, once".to_owned());
+        }
+
+        let mut state = self.clone();
+        let player = self.current_player as usize;
+        for &(card, row) in placements {
+            let cards = state.hands[player].row_mut(row);
+            if cards.len() == row.size() {
+                return Err(format!("The {:?} row is full", row));
+            }
+            cards.push(card);
+        }
+        state.discards[player].extend(discard);
+        state.turns[player] += 1;
+
+        // Next player still setting cards, in turn from the left of the button
+        let n = state.hands.len() as u64;
+        let next = (1..=n)
+            .map(|i| (self.current_player + i) % n)
+            .find(|&p| !state.hands[p as usize].is_complete());
+        match next {
+            Some(next) => {
+                state.current_player = next;
+                state.deal();
+            }
+            None => state.settle(),
+        }
+        Ok(state)
+    }
+}
+
+#[pymethods]
+impl OfcState {
+    /// Hand dealt from `shuffled_deck(seed)`. `fantasyland` flags the players dealt their
+    /// whole hand at once, e.g. the `fantasyland_next` of the previous hand.
+    #[staticmethod]
+    #[pyo3(signature = (n_players, button, seed, variant=OfcVariant::Pineapple, fantasyland=None))]
+    pub fn from_seed(
+        n_players: u64,
+        button: u64,
+        seed: u64,
+        variant: OfcVariant,
+        fantasyland: Option<Vec<bool>>,
+    ) -> PyResult<OfcState> {
+        OfcState::checked(n_players, button, shuffled_deck(seed), variant, fantasyland)
+            .map_err(PyValueError::new_err)
+    }
+
+    #[staticmethod]
+    #[pyo3(signature = (n_players, button, deck, variant=OfcVariant::Pineapple, fantasyland=None))]
+    pub fn from_deck(
+        n_players: u64,
+        button: u64,
+        deck: Vec<Card>,
+        variant: OfcVariant,
+        fantasyland: Option<Vec<bool>>,
+    ) -> PyResult<OfcState> {
+        OfcState::checked(n_players, button, deck, variant, fantasyland)
+            .map_err(PyValueError::new_err)
+    }
+
+    /// Set the cards dealt to the current player into their rows, throwing `discard` away on
+    /// the turns of three cards of Pineapple OFC
+    #[pyo3(signature = (placements, discard=None))]
+    pub fn set_cards(
+        &self,
+        placements: Vec<(Card, OfcRow)>,
+        discard: Option<Card>,
+    ) -> PyResult<OfcState> {
+        self.apply(&placements, discard)
+            .map_err(PyValueError::new_err)
+    }
+
+    /// Cards `player` has thrown away
+    pub fn discards(&self, player: u64) -> Option<Vec<Card>> {
+        self.discards.get(player as usize).cloned()
+    }
+
+    /// The hand as `player` sees it: the rows of every player, and only their own dealt and
+    /// discarded cards
+    pub fn seen_by(&self, player: u64) -> OfcState {
+        let mut state = self.clone();
+        if player != self.current_player {
+            state.dealt.clear();
+        }
+        for (i, discards) in state.discards.iter_mut().enumerate() {
+            if i as u64 != player {
+                discards.clear();
+            }
+        }
+        state
+    }
+
+    /// Cards left in the deck, without revealing them
+    pub fn cards_remaining(&self) -> usize {
+        self.deck.len()
+    }
+
+    pub fn __str__(&self) -> PyResult<String> {
+        let mut state = self.clone();
+        state.deck.clear();
+        Ok(format!("{:#?}", state))
+    }
+}
+
+/// Deal of the open-face Chinese poker hands of a table
+#[derive(Debug, Clone, Copy)]
+pub struct OfcConfig {
+    pub variant: OfcVariant,
+}
+
+impl TableGame for OfcState {
+    type Config = OfcConfig;
+    /// The cards dealt set into rows, and the card thrown away in Pineapple OFC
+    type Move = (Vec<(Card, OfcRow)>, Option<Card>);
+    type PrivateView = OfcState;
+    type PublicView = OfcState;
+
+    /// The stacks play no part, the result is in points
+    fn start_hand(config: &OfcConfig, setup: HandSetup) -> Result<OfcState, String> {
+        OfcState::checked(
+            setup.n_players,
+            setup.button,
+            setup.deck,
+            config.variant,
+            None,
+        )
+    }
+
+    fn handle_action(
+        &self,
+        player: u64,
+        (placements, discard): Self::Move,
+    ) -> Result<OfcState, String> {
+        if self.current_actor() != Some(player) {
+            return Err(format!("Player {} is not to act", player));
+        }
+        self.apply(&placements, discard)
+    }
+
+    fn current_actor(&self) -> Option<u64> {
+        (!self.final_state).then_some(self.current_player)
+    }
+
+    /// The rows, which are face up
+    fn public_view(&self) -> OfcState {
+        self.seen_by(u64::MAX)
+    }
+
+    fn private_view(&self, player: u64) -> OfcState {
+        self.seen_by(player)
+    }
+
+    fn result(&self) -> Option<Vec<f64>> {
+        self.final_state
+            .then(|| self.scores.iter().map(|&points| points as f64).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cards(s: &str) -> Vec<Card> {
+        s.split_whitespace()
+            .map(|c| Card::from_string(c.to_owned()).unwrap())
+            .collect()
+    }
+
+    fn hand(top: &str, middle: &str, bottom: &str) -> OfcHand {
+        OfcHand {
+            top: cards(top),
+            middle: cards(middle),
+            bottom: cards(bottom),
+        }
+    }
+
+    #[test]
+    fn fouls_and_royalties() {
+        // Queens on top, a flush in the middle and a full house at the bottom
+        let good = hand("SQ HQ D2", "H2 H5 H7 H9 HJ", "C3 D3 S3 C8 D8");
+        assert!(!good.fouled());
+        assert_eq!(good.royalties(), 7 + 8 + 6);
+        assert!(good.earns_fantasyland(false));
+        assert!(!good.earns_fantasyland(true));
+
+        // A pair on top over a weaker pair in the middle fouls
+        let fouled = hand("SK HK D2", "C4 D4 S7 C9 DJ", "CA DA SA C8 D8");
+        assert!(fouled.fouled());
+        assert_eq!(fouled.royalties(), 0);
+        assert!(!fouled.earns_fantasyland(false));
+
+        // The same pair plays the kickers: queens with an ace on top beat queens with a king
+        let kicker = hand("SQ HQ DA", "CQ DQ SK C9 D2", "CA DA SA C8 D8");
+        assert!(kicker.fouled());
+
+        assert_eq!(royalties(OfcRow::Top, &cards("S6 H6 D2")), 1);
+        assert_eq!(royalties(OfcRow::Top, &cards("SA HA D2")), 9);
+        assert_eq!(royalties(OfcRow::Top, &cards("S2 H2 D2")), 10);
+        assert_eq!(royalties(OfcRow::Top, &cards("SA HA DA")), 22);
+        // The wheel is the lowest straight
+        assert!(row_strength(&cards("CA D2 S3 C4 D5")) < row_strength(&cards("C2 D3 S4 C5 D6")));
+
+        // Scooped by a fouled hand: 6 points and the royalties
+        assert_eq!(good.score_against(&fouled), 6 + 21);
+        assert_eq!(fouled.score_against(&good), -6 - 21);
+    }
+
+    #[test]
+    fn hand_is_played_through_table_game() {
+        let config = OfcConfig {
+            variant: OfcVariant::Regular,
+        };
+        let setup = HandSetup {
+            n_players: 2,
+            button: 0,
+            stacks: vec![0.0; 2],
+            deck: shuffled_deck(9),
+            seed: 9,
+        };
+        let mut state = OfcState::start_hand(&config, setup).unwrap();
+        assert!(state.public_view().dealt.is_empty());
+        assert!(state
+            .handle_action(1 - state.current_player, (vec![], None))
+            .is_err());
+
+        // Fill the bottom, then the middle, then the top, from what the player is shown
+        while let Some(player) = state.current_actor() {
+            let view = state.private_view(player);
+            let hand = &view.hands[player as usize];
+            let mut free = [OfcRow::Bottom, OfcRow::Middle, OfcRow::Top]
+                .into_iter()
+                .flat_map(|row| std::iter::repeat_n(row, row.size() - hand.row(row).len()));
+            let placements = view
+                .dealt
+                .iter()
+                .map(|&card| (card, free.next().unwrap()))
+                .collect();
+            state = state.handle_action(player, (placements, None)).unwrap();
+        }
+        assert_eq!(state.result().unwrap().iter().sum::<f64>(), 0.0);
+    }
+
+    #[test]
+    fn pineapple_hand_is_scored_zero_sum() {
+        let mut state =
+            OfcState::checked(3, 0, shuffled_deck(5), OfcVariant::Pineapple, None).unwrap();
+        assert_eq!(state.current_player, 1);
+        assert_eq!(state.dealt.len(), 5);
+
+        // Fill the bottom, then the middle, then the top, discarding the last card dealt
+        while !state.final_state {
+            let hand = &state.hands[state.current_player as usize];
+            let mut free: Vec<OfcRow> = [OfcRow::Bottom, OfcRow::Middle, OfcRow::Top]
+                .into_iter()
+                .flat_map(|row| std::iter::repeat_n(row, row.size() - hand.row(row).len()))
+                .collect();
+            let mut dealt = state.dealt.clone();
+            let discard = (state.discards_due() == 1).then(|| dealt.pop().unwrap());
+            let placements: Vec<(Card, OfcRow)> =
+                dealt.iter().map(|&card| (card, free.remove(0))).collect();
+            state = state.apply(&placements, discard).unwrap();
+        }
+
+        assert!(state.hands.iter().all(|hand| hand.is_complete()));
+        assert_eq!(state.turns, vec![5, 5, 5]);
+        assert_eq!(state.scores.iter().sum::<i64>(), 0);
+        assert_eq!(state.cards_remaining(), 52 - 3 * 17);
+        let seen = state
+            .hands
+            .iter()
+            .flat_map(|hand| hand.top.iter().chain(&hand.middle).chain(&hand.bottom))
+            .chain(state.discards.iter().flatten())
+            .unique()
+            .count();
+        assert_eq!(seen, 3 * 17);
+    }
+
+    #[test]
+    fn fantasyland_sets_the_whole_hand_at_once() {
+        let state = OfcState::checked(
+            2,
+            0,
+            shuffled_deck(9),
+            OfcVariant::Pineapple,
+            Some(vec![true, false]),
+        )
+        .unwrap();
+        // Player 1 acts first with five cards, player 0 then gets fourteen
+        let placements: Vec<(Card, OfcRow)> = state
+            .dealt
+            .iter()
+            .map(|&card| (card, OfcRow::Bottom))
+            .collect();
+        let state = state.apply(&placements, None).unwrap();
+        assert_eq!(state.current_player, 0);
+        assert_eq!(state.dealt.len(), 14);
+
+        let mut dealt = state.dealt.clone();
+        let discard = dealt.pop();
+        let rows = [OfcRow::Top; 3]
+            .into_iter()
+            .chain([OfcRow::Middle; 5])
+            .chain([OfcRow::Bottom; 5]);
+        let placements: Vec<(Card, OfcRow)> = dealt.into_iter().zip(rows).collect();
+        assert!(state.apply(&placements[..12], discard).is_err());
+        let state = state.apply(&placements, discard).unwrap();
+        assert!(state.hands[0].is_complete());
+        // Only player 1 is left to play
+        assert_eq!(state.current_player, 1);
+        assert_eq!(state.dealt.len(), 3);
+    }
+}
@@ -0,0 +1,287 @@
+// onnx_policy.rs - Policy networks exported to ONNX, played as agents without Python
+use crate::datasets::{observation, OBSERVATION_SIZE};
+use crate::seeds::hand_seed;
+use crate::state::action::{Action, DefaultPolicy};
+use crate::state::State;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::sync::Arc;
+use tract_onnx::prelude::*;
+
+type Plan = TypedSimplePlan<TypedModel>;
+
+/// A policy network exported to ONNX: from a batch of one observation (`observation(state,
+/// player)`, 1 × `OBSERVATION_SIZE` floats) to the logits of the actions of
+/// `State.legal_action_mask(n_bet_buckets)`. Illegal actions are masked out, then the agent
+/// plays the most likely action, or samples one when `greedy` is off.
+#[pyclass]
+#[derive(Clone)]
+pub struct OnnxPolicy {
+    plan: Arc<Plan>,
+    /// Where the model was loaded from
+    #[pyo3(get)]
+    pub path: String,
+    #[pyo3(get)]
+    pub n_bet_buckets: usize,
+    #[pyo3(get)]
+    pub greedy: bool,
+}
+
+impl std::fmt::Debug for OnnxPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OnnxPolicy")
+            .field("path", &self.path)
+            .field("n_bet_buckets", &self.n_bet_buckets)
+            .field("greedy", &self.greedy)
+            .finish()
+    }
+}
+
+impl OnnxPolicy {
+    /// Load the model at `path`
+    pub fn load(path: &str, n_bet_buckets: usize, greedy: bool) -> Result<OnnxPolicy, String> {
+        let model = tract_onnx::onnx()
+            .model_for_path(path)
+            .map_err(|e| format!("Cannot load the model {}: {}", path, e))?;
+        OnnxPolicy::from_model(model, path, n_bet_buckets, greedy)
+    }
+
+    /// Optimize `model` for single observations and check the size of its logits
+    fn from_model(
+        model: InferenceModel,
+        path: &str,
+        n_bet_buckets: usize,
+        greedy: bool,
+    ) -> Result<OnnxPolicy, String> {
+        let plan = model
+            .with_input_fact(0, f32::fact([1, OBSERVATION_SIZE]).into())
+            .and_then(|model| model.into_optimized())
+            .and_then(|model| model.into_runnable())
+            .map_err(|e| format!("The model {} does not take observations: {}", path, e))?;
+        let policy = OnnxPolicy {
+            plan: Arc::new(plan),
+            path: path.to_owned(),
+            n_bet_buckets,
+            greedy,
+        };
+        let logits = policy.run(vec![0.0; OBSERVATION_SIZE])?;
+        if logits.len() != 2 + n_bet_buckets {
+            return Err(format!(
+                "The model {} has {} logits, {} bet buckets take {}",
+                path,
+                logits.len(),
+                n_bet_buckets,
+                2 + n_bet_buckets
+            ));
+        }
+        Ok(policy)
+    }
+
+    fn run(&self, obs: Vec<f32>) -> Result<Vec<f32>, String> {
+        let input = tract_ndarray::Array2::from_shape_vec((1, OBSERVATION_SIZE), obs)
+            .map_err(|e| e.to_string())?;
+        let outputs = self
+            .plan
+            .run(tvec!(Tensor::from(input).into()))
+            .map_err(|e| format!("The model {} failed: {}", self.path, e))?;
+        let logits = outputs[0]
+            .to_array_view::<f32>()
+            .map_err(|e| e.to_string())?;
+        Ok(logits.iter().copied().collect())
+    }
+
+    /// Logits of the model at the decision of `player` in `state`
+    pub fn logits(&self, state: &State, player: u64) -> Result<Vec<f32>, String> {
+        if player as usize >= state.players_state.len() {
+            return Err(format!("Player {} is not at the table", player));
+        }
+        self.run(observation(state, player))
+    }
+
+    /// Action of `player` at their decision in `state`. A sampled action is drawn from the
+    /// hand's seed and the number of actions so far.
+    pub fn decide(&self, state: &State, player: u64) -> Result<Action, String> {
+        if state.final_state || player != state.current_player {
+            return Ok(state.default_action(DefaultPolicy::CheckElseFold));
+        }
+        let logits = self.logits(state, player)?;
+        let legal: Vec<(usize, f32)> = state
+            .legal_action_mask(self.n_bet_buckets)
+            .iter()
+            .zip(logits)
+            .enumerate()
+            .filter(|(_, (legal, logit))| **legal && !logit.is_nan())
+            .map(|(index, (_, logit))| (index, logit))
+            .collect();
+        let Some(&(best, max)) = legal.iter().max_by(|a, b| a.1.total_cmp(&b.1)) else {
+            return Ok(state.default_action(DefaultPolicy::CheckElseFold));
+        };
+        let index = if self.greedy {
+            best
+        } else {
+            // Softmax over the legal actions
+            let weights: Vec<f64> = legal
+                .iter()
+                .map(|(_, logit)| ((logit - max) as f64).exp())
+                .collect();
+            let seed = hand_seed(state.seed, state.action_list.len() as u64);
+            let mut draw = StdRng::seed_from_u64(seed).gen::<f64>() * weights.iter().sum::<f64>();
+            legal
+                .iter()
+                .zip(&weights)
+                .find(|(_, weight)| {
+                    draw -= *weight;
+                    draw < 0.0
+                })
+                .map_or(best, |((index, _), _)| *index)
+        };
+        Ok(state
+            .mask_action(index, self.n_bet_buckets)
+            .unwrap_or_else(|| state.default_action(DefaultPolicy::CheckElseFold)))
+    }
+}
+
+#[pymethods]
+impl OnnxPolicy {
+    #[new]
+    #[pyo3(signature = (path, n_bet_buckets=10, greedy=true))]
+    pub fn new(path: &str, n_bet_buckets: usize, greedy: bool) -> PyResult<OnnxPolicy> {
+        OnnxPolicy::load(path, n_bet_buckets, greedy).map_err(PyValueError::new_err)
+    }
+
+    /// Agent policy `policy(state, player) -> Action`, for `evaluate_matchup`, `round_robin`
+    /// or `ServerHandle.add_bot`
+    pub fn __call__(&self, py: Python, state: &State, player: u64) -> PyResult<Action> {
+        py.allow_threads(|| self.decide(state, player))
+            .map_err(PyValueError::new_err)
+    }
+
+    /// Logits of the model at the decision of `player`, by default the player to act
+    #[pyo3(name = "logits", signature = (state, player=None))]
+    pub fn py_logits(&self, state: &State, player: Option<u64>) -> PyResult<Vec<f32>> {
+        self.logits(state, player.unwrap_or(state.current_player))
+            .map_err(PyValueError::new_err)
+    }
+
+    pub fn __str__(&self) -> PyResult<String> {
+        Ok(format!("{:#?}", self))
+    }
+}
+
+#[cfg(feature = "server")]
+impl crate::bots::BotPlayer for OnnxPolicy {
+    fn act(
+        &self,
+        state: State,
+        player: u64,
+    ) -> futures_util::future::BoxFuture<'_, Option<Action>> {
+        let policy = self.clone();
+        Box::pin(async move {
+            match crate::runtime::spawn_blocking(move || policy.decide(&state, player)).await {
+                Ok(Ok(action)) => Some(action),
+                Ok(Err(e)) => {
+                    tracing::error!("ONNX policy failed: {}", e);
+                    None
+                }
+                Err(_) => {
+                    tracing::error!("ONNX policy panicked");
+                    None
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datasets::{STAGE_FEATURES, STAGE_OFFSET};
+    use crate::state::action::ActionEnum;
+    use tract_onnx::pb;
+
+    /// A model multiplying the observation by `weights` (OBSERVATION_SIZE × n logits)
+    fn linear_model(weights: Vec<f32>, n: usize) -> InferenceModel {
+        let value = |name: &str| pb::ValueInfoProto {
+            name: name.to_owned(),
+            r#type: Some(pb::TypeProto {
+                value: Some(pb::type_proto::Value::TensorType(pb::type_proto::Tensor {
+                    elem_type: pb::tensor_proto::DataType::Float as i32,
+                    shape: None,
+                })),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let graph = pb::GraphProto {
+            node: vec![pb::NodeProto {
+                input: vec!["obs".to_owned(), "weights".to_owned()],
+                output: vec!["logits".to_owned()],
+                op_type: "MatMul".to_owned(),
+                ..Default::default()
+            }],
+            initializer: vec![pb::TensorProto {
+                dims: vec![OBSERVATION_SIZE as i64, n as i64],
+                data_type: pb::tensor_proto::DataType::Float as i32,
+                float_data: weights,
+                name: "weights".to_owned(),
+                ..Default::default()
+            }],
+            input: vec![value("obs")],
+            output: vec![value("logits")],
+            ..Default::default()
+        };
+        let proto = pb::ModelProto {
+            ir_version: 7,
+            opset_import: vec![pb::OperatorSetIdProto {
+                domain: String::new(),
+                version: 13,
+            }],
+            graph: Some(graph),
+            ..Default::default()
+        };
+        tract_onnx::onnx().model_for_proto_model(&proto).unwrap()
+    }
+
+    /// Logits of 1 for the action `index` of the mask whatever the observation, 0 for the
+    /// others: the stage one-hot of the observation always holds a single 1
+    fn always(index: usize, n: usize) -> InferenceModel {
+        let mut weights = vec![0.0; OBSERVATION_SIZE * n];
+        for stage in STAGE_OFFSET..STAGE_OFFSET + STAGE_FEATURES {
+            weights[stage * n + index] = 1.0;
+        }
+        linear_model(weights, n)
+    }
+
+    #[test]
+    fn plays_the_best_legal_logit() {
+        let state = State::from_seed(3, 0, 0.5, 1.0, 100.0, 7, false, 0.0).unwrap();
+
+        let caller = OnnxPolicy::from_model(always(1, 6), "caller", 4, true).unwrap();
+        assert_eq!(
+            caller.logits(&state, 0).unwrap(),
+            vec![0.0, 1.0, 0.0, 0.0, 0.0, 0.0]
+        );
+        assert_eq!(
+            caller.decide(&state, state.current_player).unwrap(),
+            Action::new(ActionEnum::CheckCall, 0.0)
+        );
+
+        // Going all-in is the last bucket
+        let shover = OnnxPolicy::from_model(always(5, 6), "shover", 4, true).unwrap();
+        let shove = shover.decide(&state, state.current_player).unwrap();
+        assert_eq!(shove, Action::new(ActionEnum::BetRaise, 100.0));
+
+        // Facing a shove the raises are masked out, and the tie of fold and call goes to the
+        // call
+        let state = state.apply_action(shove);
+        assert_eq!(
+            shover.decide(&state, state.current_player).unwrap(),
+            Action::new(ActionEnum::CheckCall, 0.0)
+        );
+
+        // The logits must match the bet buckets
+        assert!(OnnxPolicy::from_model(always(1, 6), "caller", 10, true).is_err());
+    }
+}
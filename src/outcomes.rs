@@ -0,0 +1,268 @@
+// outcomes.rs - Bad beats, coolers and suckouts among finished hands
+use crate::hand_strength::{beaten_hand, HandCategory};
+use crate::insurance::street_equities;
+use crate::state::stage::Stage;
+use crate::state::{AllInEquity, State, StateStatus};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+/// What made a showdown remarkable, see `classify_hand_outcome`
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutcomeLabel {
+    /// The loser was a big favourite and lost with a strong hand, both as set by the criteria
+    BadBeat,
+    /// Two strong hands where the loser was never ahead
+    Cooler,
+    /// The winner was behind on the turn and the river turned it around
+    Suckout,
+}
+
+/// Thresholds of `classify_hand_outcome`
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OutcomeCriteria {
+    /// Equity the loser of a bad beat had on some street before the river, no equity
+    /// condition when unset
+    #[pyo3(get)]
+    pub bad_beat_equity: Option<f64>,
+    /// Category the losing hand of a bad beat reaches at least, e.g. `FourOfAKind` for a bad
+    /// beat jackpot; any hand when unset
+    #[pyo3(get)]
+    pub bad_beat_min_hand: Option<HandCategory>,
+    /// Category both hands of a cooler reach at least
+    #[pyo3(get)]
+    pub cooler_min_hand: HandCategory,
+    /// Turn equity the winner of a suckout was below
+    #[pyo3(get)]
+    pub suckout_equity: f64,
+}
+
+impl Default for OutcomeCriteria {
+    fn default() -> Self {
+        Self {
+            bad_beat_equity: Some(0.8),
+            bad_beat_min_hand: None,
+            cooler_min_hand: HandCategory::Flush,
+            suckout_equity: 0.5,
+        }
+    }
+}
+
+impl OutcomeCriteria {
+    pub fn checked(
+        bad_beat_equity: Option<f64>,
+        bad_beat_min_hand: Option<HandCategory>,
+        cooler_min_hand: HandCategory,
+        suckout_equity: f64,
+    ) -> Result<OutcomeCriteria, String> {
+        let is_equity = |equity: f64| (0.0..=1.0).contains(&equity);
+        if !bad_beat_equity.is_none_or(is_equity) || !is_equity(suckout_equity) {
+            return Err("Equity thresholds must be between 0 and 1".to_owned());
+        }
+        Ok(OutcomeCriteria {
+            bad_beat_equity,
+            bad_beat_min_hand,
+            cooler_min_hand,
+            suckout_equity,
+        })
+    }
+}
+
+#[pymethods]
+impl OutcomeCriteria {
+    #[new]
+    #[pyo3(signature = (bad_beat_equity=0.8, bad_beat_min_hand=None, cooler_min_hand=HandCategory::Flush, suckout_equity=0.5))]
+    pub fn new(
+        bad_beat_equity: Option<f64>,
+        bad_beat_min_hand: Option<HandCategory>,
+        cooler_min_hand: HandCategory,
+        suckout_equity: f64,
+    ) -> PyResult<OutcomeCriteria> {
+        OutcomeCriteria::checked(
+            bad_beat_equity,
+            bad_beat_min_hand,
+            cooler_min_hand,
+            suckout_equity,
+        )
+        .map_err(PyValueError::new_err)
+    }
+
+    pub fn __str__(&self) -> PyResult<String> {
+        Ok(format!("{:#?}", self))
+    }
+}
+
+/// The showdown of a finished hand between its winner and the best losing hand
+#[pyclass]
+#[derive(Debug, Clone, PartialEq)]
+pub struct HandOutcome {
+    /// Empty for an unremarkable showdown
+    #[pyo3(get)]
+    pub labels: Vec<OutcomeLabel>,
+
+    #[pyo3(get)]
+    pub winner: u64,
+
+    #[pyo3(get)]
+    pub loser: u64,
+
+    #[pyo3(get)]
+    pub winning_hand: HandCategory,
+
+    #[pyo3(get)]
+    pub losing_hand: HandCategory,
+
+    /// Equities of the players at the showdown on the preflop, the flop and the turn
+    #[pyo3(get)]
+    pub equities: Vec<AllInEquity>,
+}
+
+#[pymethods]
+impl HandOutcome {
+    pub fn has(&self, label: OutcomeLabel) -> bool {
+        self.labels.contains(&label)
+    }
+
+    pub fn __str__(&self) -> PyResult<String> {
+        Ok(format!("{:#?}", self))
+    }
+}
+
+/// Label the showdown of the finished hand `state`. The equities on each street come from
+/// `State.all_in_equities` where the hand reported them, and are computed for the other
+/// streets. `None` without a showdown, for split pots without a losing hand and for double
+/// boards.
+#[pyfunction]
+#[pyo3(signature = (state, criteria=None))]
+pub fn classify_hand_outcome(
+    state: &State,
+    criteria: Option<OutcomeCriteria>,
+) -> Option<HandOutcome> {
+    let criteria = criteria.unwrap_or_default();
+    if !state.final_state || !matches!(state.status, StateStatus::Ok) {
+        return None;
+    }
+    let ((winner, best), (loser, beaten)) = beaten_hand(state)?;
+
+    let players: Vec<u64> = state
+        .players_state
+        .iter()
+        .filter(|ps| ps.folded_on.is_none())
+        .map(|ps| ps.player)
+        .collect();
+
+    let equities: Vec<AllInEquity> = [Stage::Preflop, Stage::Flop, Stage::Turn]
+        .into_iter()
+        .map(|stage| {
+            state
+                .all_in_equities
+                .iter()
+                .find(|annotated| annotated.stage == stage && annotated.players == players)
+                .cloned()
+                .unwrap_or_else(|| street_equities(state, &players, stage))
+        })
+        .collect();
+    let equity = |player: u64| -> Vec<f64> {
+        let index = players
+            .iter()
+            .position(|&p| p == player)
+            .unwrap_or_default();
+        equities.iter().map(|e| e.equities[index]).collect()
+    };
+    let (winner_equity, loser_equity) = (equity(winner), equity(loser));
+
+    let winning_hand = HandCategory::from_rank(best);
+    let losing_hand = HandCategory::from_rank(beaten);
+    let mut labels = Vec::new();
+    let was_favourite = criteria
+        .bad_beat_equity
+        .is_none_or(|threshold| loser_equity.iter().any(|&e| e >= threshold));
+    let strong_loser = criteria
+        .bad_beat_min_hand
+        .is_none_or(|category| losing_hand >= category);
+    if was_favourite && strong_loser {
+        labels.push(OutcomeLabel::BadBeat);
+    }
+    // From the flop on the winner stayed ahead
+    let never_ahead =
+        (1..equities.len()).all(|street| loser_equity[street] < winner_equity[street]);
+    if never_ahead && losing_hand >= criteria.cooler_min_hand {
+        labels.push(OutcomeLabel::Cooler);
+    }
+    if winner_equity[Stage::Turn as usize] < criteria.suckout_equity {
+        labels.push(OutcomeLabel::Suckout);
+    }
+
+    Some(HandOutcome {
+        labels,
+        winner,
+        loser,
+        winning_hand,
+        losing_hand,
+        equities,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::action::{Action, ActionEnum};
+    use crate::state::card::Card;
+
+    /// Heads-up hand checked down from `deck`: player 1 is dealt the first two cards, player 0
+    /// the next two, then the board
+    fn checked_down(deck: &[&str]) -> State {
+        let deck = deck
+            .iter()
+            .map(|name| Card::from_string(name.to_string()).unwrap())
+            .collect();
+        let mut state = State::from_deck(2, 0, 1.0, 2.0, 100.0, deck, false, 0, 0.0).unwrap();
+        while !state.final_state {
+            state = state.apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+        }
+        state
+    }
+
+    #[test]
+    fn kings_hit_the_river_against_aces() {
+        let state = checked_down(&["SA", "HA", "SK", "HK", "C2", "D7", "S9", "CJ", "DK"]);
+        let outcome = classify_hand_outcome(&state, None).unwrap();
+        assert_eq!((outcome.winner, outcome.loser), (0, 1));
+        assert_eq!(outcome.winning_hand, HandCategory::ThreeOfAKind);
+        assert_eq!(
+            outcome.labels,
+            vec![OutcomeLabel::BadBeat, OutcomeLabel::Suckout]
+        );
+
+        // Aces losing is no bad beat for a jackpot that needs quads
+        let criteria = OutcomeCriteria::checked(
+            None,
+            Some(HandCategory::FourOfAKind),
+            HandCategory::Flush,
+            0.5,
+        )
+        .unwrap();
+        let outcome = classify_hand_outcome(&state, Some(criteria)).unwrap();
+        assert_eq!(outcome.labels, vec![OutcomeLabel::Suckout]);
+    }
+
+    #[test]
+    fn full_house_against_flopped_quads() {
+        let state = checked_down(&["C7", "D7", "C8", "D8", "H8", "S8", "H7", "C2", "D3"]);
+        let outcome = classify_hand_outcome(&state, None).unwrap();
+        assert_eq!(outcome.winning_hand, HandCategory::FourOfAKind);
+        assert_eq!(outcome.losing_hand, HandCategory::FullHouse);
+        assert_eq!(outcome.labels, vec![OutcomeLabel::Cooler]);
+        assert!(outcome.has(OutcomeLabel::Cooler));
+        assert_eq!(outcome.equities.len(), 3);
+    }
+
+    #[test]
+    fn no_showdown_no_outcome() {
+        let state = State::from_seed(2, 0, 1.0, 2.0, 100.0, 3, false, 0.0).unwrap();
+        let state = state.apply_action(Action::new(ActionEnum::Fold, 0.0));
+        assert!(state.final_state);
+        assert!(classify_hand_outcome(&state, None).is_none());
+    }
+}
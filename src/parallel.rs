@@ -1,14 +1,495 @@
 // parallel.rs
+use crate::cards::all_combos;
+use crate::evaluator::{pack_rank, Evaluator};
+use crate::node_lock::{locked_action, NodeLock};
+use crate::range::{hand_class, N_HAND_CLASSES};
+use crate::seeds::hand_seed;
 use crate::state::action::Action;
+use crate::state::card::Card;
+use crate::state::rules::GameVariant;
 use crate::state::State;
+use pyo3::buffer::{Element, PyBuffer};
+use pyo3::exceptions::{PyRuntimeError, PyTimeoutError, PyValueError};
 use pyo3::prelude::*;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
 use rayon::prelude::*;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
 
+/// Apply `actions` to `states` in parallel. At the decisions matched by one of `locks`, the
+/// locked strategy is played instead of the given action.
 #[pyfunction]
-pub fn parallel_apply_action(states: Vec<State>, actions: Vec<Action>) -> Vec<State> {
-    states
+#[pyo3(signature = (states, actions, locks=None))]
+pub fn parallel_apply_action(
+    states: Vec<State>,
+    actions: Vec<Action>,
+    locks: Option<Vec<NodeLock>>,
+) -> Vec<State> {
+    let locks = locks.unwrap_or_default();
+    apply_and_record(&states, actions, &locks, |_, _, _| None::<()>).0
+}
+
+/// `parallel_apply_action`, with what `record(env, state, next)` makes of each state and the
+/// state it led to, for the recorders of batched simulations
+pub(crate) fn apply_and_record<T: Send>(
+    states: &[State],
+    actions: Vec<Action>,
+    locks: &[NodeLock],
+    record: impl Fn(usize, &State, &State) -> Option<T> + Sync,
+) -> (Vec<State>, Vec<T>) {
+    let (next_states, records): (Vec<State>, Vec<Option<T>>) = states
         .par_iter()
         .zip(actions)
-        .map(|(s, a)| s.apply_action(a))
-        .collect()
+        .enumerate()
+        .map(|(env, (state, action))| {
+            let next = state.apply_action(locked_action(locks, state).unwrap_or(action));
+            let record = record(env, state, &next);
+            (next, record)
+        })
+        .unzip();
+    (next_states, records.into_iter().flatten().collect())
+}
+
+/// Rows of a 2-d array of integers read through the buffer protocol, if it holds `T`s
+fn buffer_rows<T: Element + Copy>(
+    py: Python,
+    cards: &PyAny,
+    to_index: fn(T) -> i64,
+) -> Option<PyResult<(Vec<i64>, usize)>> {
+    let buffer = PyBuffer::<T>::get(cards).ok()?;
+    if buffer.dimensions() != 2 {
+        return Some(Err(PyValueError::new_err(format!(
+            "Expected a 2-d array of card indices, got {} dimensions",
+            buffer.dimensions()
+        ))));
+    }
+    let width = buffer.shape()[1];
+    Some(
+        buffer
+            .to_vec(py)
+            .map(|cards| (cards.into_iter().map(to_index).collect(), width)),
+    )
+}
+
+/// Card indices of a numpy array of any integer type or a list of rows, flattened, and the
+/// number of cards per row
+fn card_rows(py: Python, cards: &PyAny) -> PyResult<(Vec<i64>, usize)> {
+    let from_buffer = buffer_rows::<i64>(py, cards, |i| i)
+        .or_else(|| buffer_rows::<i32>(py, cards, i64::from))
+        .or_else(|| buffer_rows::<i16>(py, cards, i64::from))
+        .or_else(|| buffer_rows::<i8>(py, cards, i64::from))
+        .or_else(|| buffer_rows::<u64>(py, cards, |i| i.min(i64::MAX as u64) as i64))
+        .or_else(|| buffer_rows::<u32>(py, cards, i64::from))
+        .or_else(|| buffer_rows::<u16>(py, cards, i64::from))
+        .or_else(|| buffer_rows::<u8>(py, cards, i64::from));
+    if let Some(rows) = from_buffer {
+        return rows;
+    }
+
+    let rows: Vec<Vec<i64>> = cards.extract()?;
+    let width = rows.first().map_or(0, |row| row.len());
+    if rows.iter().any(|row| row.len() != width) {
+        return Err(PyValueError::new_err(
+            "Every row must have the same number of cards",
+        ));
+    }
+    Ok((rows.concat(), width))
+}
+
+/// Rank of every (hand, board) pair, ranked in parallel without holding the GIL.
+/// `hands` is an (n, 2) and `boards` an (n, 3 to 5) array of card indices, in the order of
+/// `Card.collect()`. Ranks are packed into one integer each, lower is stronger.
+#[pyfunction]
+#[pyo3(signature = (hands, boards, variant=GameVariant::Holdem))]
+pub fn batch_evaluate(
+    py: Python,
+    hands: &PyAny,
+    boards: &PyAny,
+    variant: GameVariant,
+) -> PyResult<Vec<u64>> {
+    let (hands, hand_width) = card_rows(py, hands)?;
+    let (boards, board_width) = card_rows(py, boards)?;
+    if hand_width != 2 {
+        return Err(PyValueError::new_err(format!(
+            "Hands must have 2 cards, got {}",
+            hand_width
+        )));
+    }
+    if !(3..=5).contains(&board_width) {
+        return Err(PyValueError::new_err(format!(
+            "Boards must have 3 to 5 cards, got {}",
+            board_width
+        )));
+    }
+    if hands.len() / 2 != boards.len() / board_width {
+        return Err(PyValueError::new_err(format!(
+            "Got {} hands and {} boards",
+            hands.len() / 2,
+            boards.len() / board_width
+        )));
+    }
+
+    let evaluator = variant.evaluator();
+    py.allow_threads(|| {
+        hands
+            .par_chunks(2)
+            .zip(boards.par_chunks(board_width))
+            .enumerate()
+            .map(|(row, (hand, board))| {
+                let mut cards = [Card::from_index(0).unwrap(); 7];
+                let n = board.len() + 2;
+                for (slot, &index) in cards.iter_mut().zip(hand.iter().chain(board)) {
+                    *slot = u8::try_from(index)
+                        .ok()
+                        .and_then(Card::from_index)
+                        .ok_or_else(|| format!("Row {}: {} is not a card index", row, index))?;
+                }
+                if (1..n).any(|i| cards[..i].contains(&cards[i])) {
+                    return Err(format!("Row {}: a card is used more than once", row));
+                }
+                Ok(pack_rank(evaluator.eval(&cards[..n])))
+            })
+            .collect::<Result<Vec<u64>, String>>()
+    })
+    .map_err(PyValueError::new_err)
+}
+
+enum JobOutcome {
+    Running,
+    Finished(Box<dyn FnOnce(Python) -> PyObject + Send>),
+    Converted(PyObject),
+    Failed(String),
+}
+
+/// Progress and cancellation, shared by a job and its handle
+pub struct JobProgress {
+    done: AtomicUsize,
+    total: usize,
+    cancelled: AtomicBool,
+    outcome: Mutex<JobOutcome>,
+    finished: Condvar,
+}
+
+impl JobProgress {
+    pub(crate) fn new(total: usize) -> JobProgress {
+        JobProgress {
+            done: AtomicUsize::new(0),
+            total,
+            cancelled: AtomicBool::new(false),
+            outcome: Mutex::new(JobOutcome::Running),
+            finished: Condvar::new(),
+        }
+    }
+
+    /// Count one more of the `total` steps as done
+    pub fn step(&self) {
+        self.done.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Jobs check this between steps and stop early when it is set
+    pub fn cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Handle of a computation running in the background on its own thread pool
+#[pyclass]
+pub struct Job {
+    progress: Arc<JobProgress>,
+}
+
+impl Job {
+    /// Run `work` of `total` steps on a new pool of `threads` threads (rayon's default when
+    /// `None`). Parallel iterators inside `work` use that pool.
+    pub fn spawn<T, F>(total: usize, threads: Option<usize>, work: F) -> PyResult<Job>
+    where
+        T: IntoPy<PyObject> + Send + 'static,
+        F: FnOnce(&JobProgress) -> T + Send + 'static,
+    {
+        Job::try_spawn(total, threads, move |progress| Ok(work(progress)))
+    }
+
+    /// `spawn` for work that can fail, `result` then raises `RuntimeError` with the error
+    pub fn try_spawn<T, F>(total: usize, threads: Option<usize>, work: F) -> PyResult<Job>
+    where
+        T: IntoPy<PyObject> + Send + 'static,
+        F: FnOnce(&JobProgress) -> Result<T, String> + Send + 'static,
+    {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads.unwrap_or(0))
+            .thread_name(|i| format!("pokers-job-{}", i))
+            .build()
+            .map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
+        let progress = Arc::new(JobProgress::new(total));
+
+        let shared = progress.clone();
+        std::thread::spawn(move || {
+            let outcome = match catch_unwind(AssertUnwindSafe(|| pool.install(|| work(&shared)))) {
+                Ok(Ok(value)) => JobOutcome::Finished(Box::new(move |py| value.into_py(py))),
+                Ok(Err(error)) => JobOutcome::Failed(error),
+                Err(_) => JobOutcome::Failed("The job panicked".to_string()),
+            };
+            *shared.outcome.lock().unwrap() = outcome;
+            shared.finished.notify_all();
+        });
+        Ok(Job { progress })
+    }
+}
+
+#[pymethods]
+impl Job {
+    /// Fraction of the steps done, from 0 to 1
+    pub fn progress(&self) -> f64 {
+        match self.progress.total {
+            0 => 1.0,
+            total => self.progress.done.load(Ordering::Relaxed) as f64 / total as f64,
+        }
+    }
+
+    /// Ask the job to stop, it does at its next step
+    pub fn cancel(&self) {
+        self.progress.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn cancelled(&self) -> bool {
+        self.progress.cancelled()
+    }
+
+    /// Whether the job stopped, finished or cancelled
+    pub fn done(&self) -> bool {
+        !matches!(*self.progress.outcome.lock().unwrap(), JobOutcome::Running)
+    }
+
+    /// Wait for the job and return its result. Raises `TimeoutError` when it is still running
+    /// after `timeout` seconds and `RuntimeError` when it was cancelled or failed.
+    #[pyo3(signature = (timeout=None))]
+    pub fn result(&self, py: Python, timeout: Option<f64>) -> PyResult<PyObject> {
+        let timeout = timeout
+            .map(|seconds| {
+                Duration::try_from_secs_f64(seconds).map_err(|_| {
+                    PyValueError::new_err(format!("Invalid timeout of {} seconds", seconds))
+                })
+            })
+            .transpose()?;
+        let finished = py.allow_threads(|| {
+            let outcome = self.progress.outcome.lock().unwrap();
+            let running = |outcome: &mut JobOutcome| matches!(outcome, JobOutcome::Running);
+            match timeout {
+                Some(timeout) => {
+                    let (outcome, _) = self
+                        .progress
+                        .finished
+                        .wait_timeout_while(outcome, timeout, running)
+                        .unwrap();
+                    !matches!(*outcome, JobOutcome::Running)
+                }
+                None => {
+                    drop(self.progress.finished.wait_while(outcome, running).unwrap());
+                    true
+                }
+            }
+        });
+        if !finished {
+            return Err(PyTimeoutError::new_err("The job is still running"));
+        }
+        if self.cancelled() {
+            return Err(PyRuntimeError::new_err("The job was cancelled"));
+        }
+
+        let mut outcome = self.progress.outcome.lock().unwrap();
+        match std::mem::replace(&mut *outcome, JobOutcome::Running) {
+            JobOutcome::Finished(convert) => {
+                let value = convert(py);
+                *outcome = JobOutcome::Converted(value.clone_ref(py));
+                Ok(value)
+            }
+            JobOutcome::Converted(value) => {
+                *outcome = JobOutcome::Converted(value.clone_ref(py));
+                Ok(value)
+            }
+            JobOutcome::Failed(error) => {
+                *outcome = JobOutcome::Failed(error.clone());
+                Err(PyRuntimeError::new_err(error))
+            }
+            JobOutcome::Running => unreachable!("the job finished"),
+        }
+    }
+}
+
+impl Drop for Job {
+    /// Nobody can read the result any more
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+/// Equity of a random combo of class `row` against one of class `col` over `samples`
+/// runouts of `board`, or NaN when no two combos of the classes can be dealt together
+fn class_equity(
+    evaluator: &dyn Evaluator,
+    board: &[Card],
+    combos: &[Vec<(Card, Card)>],
+    (row, col): (usize, usize),
+    samples: usize,
+    rng: &mut StdRng,
+) -> f64 {
+    let overlaps =
+        |a: (Card, Card), b: (Card, Card)| a.0 == b.0 || a.0 == b.1 || a.1 == b.0 || a.1 == b.1;
+    let matchups: Vec<((Card, Card), (Card, Card))> = combos[row]
+        .iter()
+        .flat_map(|&a| combos[col].iter().map(move |&b| (a, b)))
+        .filter(|&(a, b)| !overlaps(a, b))
+        .collect();
+    if matchups.is_empty() {
+        return f64::NAN;
+    }
+    if row == col {
+        return 0.5;
+    }
+
+    let deck = Card::collect();
+    let mut runout = board.to_vec();
+    let mut score = 0.0;
+    for _ in 0..samples {
+        let (a, b) = *matchups.choose(rng).unwrap();
+        let hole_cards = [a.0, a.1, b.0, b.1];
+        runout.truncate(board.len());
+        while runout.len() < 5 {
+            let card = deck[rng.gen_range(0..deck.len())];
+            if !runout.contains(&card) && !hole_cards.contains(&card) {
+                runout.push(card);
+            }
+        }
+        score += match evaluator
+            .rank_hand(a, &runout)
+            .cmp(&evaluator.rank_hand(b, &runout))
+        {
+            std::cmp::Ordering::Less => 1.0,
+            std::cmp::Ordering::Equal => 0.5,
+            std::cmp::Ordering::Greater => 0.0,
+        };
+    }
+    score / samples as f64
+}
+
+/// The matrix of `equity_matrix`, with the cells left after a cancellation set to NaN
+fn class_equities(
+    board: &[Card],
+    samples: usize,
+    seed: u64,
+    variant: GameVariant,
+    progress: &JobProgress,
+) -> Vec<Vec<f64>> {
+    let mut combos = vec![Vec::new(); N_HAND_CLASSES];
+    for combo in all_combos(board.to_vec()) {
+        combos[hand_class(combo)].push(combo);
+    }
+    let cells: Vec<(usize, usize)> = (0..N_HAND_CLASSES)
+        .flat_map(|row| (row..N_HAND_CLASSES).map(move |col| (row, col)))
+        .collect();
+
+    let evaluator = variant.evaluator();
+    let equities: Vec<f64> = cells
+        .par_iter()
+        .enumerate()
+        .map(|(i, &cell)| {
+            if progress.cancelled() {
+                return f64::NAN;
+            }
+            let mut rng = StdRng::seed_from_u64(hand_seed(seed, i as u64));
+            let equity = class_equity(evaluator, board, &combos, cell, samples, &mut rng);
+            progress.step();
+            equity
+        })
+        .collect();
+
+    let mut matrix = vec![vec![f64::NAN; N_HAND_CLASSES]; N_HAND_CLASSES];
+    for (&(row, col), equity) in cells.iter().zip(equities) {
+        matrix[row][col] = equity;
+        matrix[col][row] = 1.0 - equity;
+    }
+    matrix
+}
+
+/// Start computing the 169 x 169 matrix of hand class equities on `board` in the background.
+/// Cell `[row][col]` (grid order) is the equity of class `row` against class `col` over
+/// `samples` random matchups and runouts, NaN when the two classes cannot be dealt together.
+/// The result only depends on `seed`.
+#[pyfunction]
+#[pyo3(signature = (board=Vec::new(), samples=1000, seed=0, threads=None, variant=GameVariant::Holdem))]
+pub fn equity_matrix(
+    board: Vec<Card>,
+    samples: usize,
+    seed: u64,
+    threads: Option<usize>,
+    variant: GameVariant,
+) -> PyResult<Job> {
+    if board.len() > 5 || (1..board.len()).any(|i| board[..i].contains(&board[i])) {
+        return Err(PyValueError::new_err(
+            "The board must have at most 5 different cards",
+        ));
+    }
+    if samples == 0 {
+        return Err(PyValueError::new_err("samples must be positive"));
+    }
+
+    let cells = N_HAND_CLASSES * (N_HAND_CLASSES + 1) / 2;
+    Job::spawn(cells, threads, move |progress| {
+        class_equities(&board, samples, seed, variant, progress)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::HandRank;
+
+    #[test]
+    fn class_equities_are_complementary() {
+        let board: Vec<Card> = ["SA", "HA", "DA", "C8", "D3"]
+            .iter()
+            .map(|c| Card::from_string(c.to_string()).unwrap())
+            .collect();
+        let (aces, kings, seven_deuce) = (0, 14, 12 * 13 + 7);
+
+        let progress = JobProgress::new(N_HAND_CLASSES * (N_HAND_CLASSES + 1) / 2);
+        let matrix = class_equities(&board, 5, 3, GameVariant::Holdem, &progress);
+        assert_eq!(progress.done.load(Ordering::Relaxed), progress.total);
+        // A single ace is left
+        assert!(matrix[aces][kings].is_nan());
+        assert_eq!(matrix[kings][kings], 0.5);
+        assert_eq!(matrix[kings][seven_deuce], 1.0);
+        for (row, equities) in matrix.iter().enumerate() {
+            for (col, equity) in equities.iter().enumerate() {
+                let sum = equity + matrix[col][row];
+                assert!(sum.is_nan() || (sum - 1.0).abs() < 1e-9);
+            }
+        }
+
+        let cancelled = JobProgress::new(progress.total);
+        cancelled.cancelled.store(true, Ordering::Relaxed);
+        let matrix = class_equities(&board, 5, 3, GameVariant::Holdem, &cancelled);
+        assert_eq!(cancelled.done.load(Ordering::Relaxed), 0);
+        assert!(matrix[kings][seven_deuce].is_nan());
+    }
+
+    #[test]
+    fn packed_ranks_keep_their_order() {
+        let deck = Card::collect();
+        let mut ranks: Vec<HandRank> = deck
+            .windows(7)
+            .map(|cards| GameVariant::Holdem.evaluator().eval(cards))
+            .collect();
+        ranks.push((1, 0, 0));
+        ranks.push((10, 371_292, 0));
+        for a in &ranks {
+            for b in &ranks {
+                assert_eq!(a.cmp(b), pack_rank(*a).cmp(&pack_rank(*b)));
+            }
+        }
+    }
 }
@@ -0,0 +1,252 @@
+// player_stats.rs - Lifetime player results that survive server restarts
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use tracing::error;
+
+use crate::events::{HandSummary, TableEvent};
+use crate::insurance::all_in_ev;
+use crate::state::PlayerState;
+
+/// `expected`, what the player at `index` of `hand` would have won on average over the
+/// runouts, after the rake and jackpot drops. They come out of what the winners collect in
+/// proportion, the same total whichever runout came, so the player expects to pay that share
+/// of what they expected to collect.
+fn after_rake(hand: &HandSummary, index: usize, expected: f64) -> f64 {
+    let invested = |ps: &PlayerState| ps.invested.iter().sum::<f64>();
+    let collected: f64 = hand
+        .state
+        .players_state
+        .iter()
+        .map(|ps| (ps.reward + invested(ps)).max(0.0))
+        .sum();
+    let taken: f64 = hand.rake.taken.iter().sum();
+    match hand.state.players_state.get(index) {
+        Some(ps) if collected > 0.0 => expected - (expected + invested(ps)) * taken / collected,
+        _ => expected,
+    }
+}
+
+/// Results of the hands a player was all-in in with cards to come, see `State.all_in_ev`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AllInResults {
+    pub hands: u64,
+    /// What the player would have won on average over every runout, after the share of the
+    /// rake and jackpot drops they would have paid
+    pub expected: f64,
+    /// What the player won with the actual runouts, after the rake like `net_winnings`
+    pub won: f64,
+}
+
+impl AllInResults {
+    fn add(&mut self, expected: f64, won: f64) {
+        self.hands += 1;
+        self.expected += expected;
+        self.won += won;
+    }
+
+    /// Chips won above expectation, negative when the runouts ran bad
+    pub fn luck(&self) -> f64 {
+        self.won - self.expected
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerStats {
+    pub hands_played: u64,
+    pub net_winnings: f64,
+    /// Largest pot the player has won
+    pub biggest_pot: f64,
+    /// `State.hand_id` of the last hand recorded, to join the stats with hand records
+    #[serde(default)]
+    pub last_hand_id: Option<String>,
+    #[serde(default)]
+    pub all_in: AllInResults,
+    /// Session of the last hand recorded, `State.session_id`
+    #[serde(default)]
+    pub session_id: Option<String>,
+    /// All-in results of the hands of `session_id`, started over with every new session
+    #[serde(default)]
+    pub session_all_in: AllInResults,
+}
+
+impl PlayerStats {
+    /// Net winnings with the all-in hands counted at their expected value rather than at the
+    /// runouts the player got
+    pub fn luck_adjusted_winnings(&self) -> f64 {
+        self.net_winnings - self.all_in.luck()
+    }
+}
+
+/// Lifetime stats keyed by account (the registered player name), unlike the
+/// per-connection `starting_session_chips` which resets on every reconnect.
+/// Backed by a JSON file when a path is configured, in memory otherwise.
+#[derive(Debug, Clone, Default)]
+pub struct PlayerStatsStore {
+    path: Option<PathBuf>,
+    stats: HashMap<String, PlayerStats>,
+}
+
+impl PlayerStatsStore {
+    /// Load the store from `path`, starting empty when the file does not exist yet
+    pub fn load(path: PathBuf) -> Result<Self, Box<dyn std::error::Error>> {
+        let stats = match std::fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self {
+            path: Some(path),
+            stats,
+        })
+    }
+
+    pub fn get(&self, account: &str) -> PlayerStats {
+        self.stats.get(account).cloned().unwrap_or_default()
+    }
+
+    /// Stats of `account`, None when it never played a hand
+    pub fn find(&self, account: &str) -> Option<&PlayerStats> {
+        self.stats.get(account)
+    }
+
+    /// Forget `account` and save the store
+    pub fn remove(
+        &mut self,
+        account: &str,
+    ) -> Result<Option<PlayerStats>, Box<dyn std::error::Error>> {
+        let stats = self.stats.remove(account);
+        if stats.is_some() {
+            self.save()?;
+        }
+        Ok(stats)
+    }
+
+    pub fn record_hand(&mut self, account: &str, net: f64, pot: f64, hand_id: &str) {
+        let stats = self.stats.entry(account.to_string()).or_default();
+        stats.hands_played += 1;
+        stats.last_hand_id = Some(hand_id.to_string());
+        stats.net_winnings += net;
+        if net > 0.0 {
+            stats.biggest_pot = stats.biggest_pot.max(pot);
+        }
+    }
+
+    /// Record the expected and actual reward of a hand `account` was all-in in, in session
+    /// `session_id`
+    pub fn record_all_in(
+        &mut self,
+        account: &str,
+        session_id: Option<&str>,
+        expected: f64,
+        won: f64,
+    ) {
+        let stats = self.stats.entry(account.to_string()).or_default();
+        if stats.session_id.as_deref() != session_id {
+            stats.session_id = session_id.map(str::to_string);
+            stats.session_all_in = AllInResults::default();
+        }
+        stats.all_in.add(expected, won);
+        stats.session_all_in.add(expected, won);
+    }
+
+    /// Record every named player of a settled hand and save the store. Run by the store
+    /// writer, off the table's lock: the all-in equities enumerate the runouts.
+    pub fn on_event(&mut self, event: &TableEvent) {
+        if let TableEvent::HandComplete(hand) = event {
+            let state = &hand.state;
+            let all_in_ev = all_in_ev(state);
+            for (index, player) in hand.players.iter().enumerate() {
+                if player.name.is_empty() {
+                    continue;
+                }
+                self.record_hand(
+                    &player.name,
+                    hand.net(index),
+                    state.chips_in_pot(),
+                    &state.hand_id,
+                );
+                // Players who folded before the all-in had no stake in the runout
+                let ps = state.players_state.get(index);
+                if let (Some(ev), Some(ps)) = (&all_in_ev, ps) {
+                    if ps.folded_on.is_none() {
+                        let session_id = state.session_id.as_deref();
+                        self.record_all_in(
+                            &player.name,
+                            session_id,
+                            after_rake(hand, index, ev[index]),
+                            hand.net(index),
+                        );
+                    }
+                }
+            }
+            if let Err(e) = self.save() {
+                error!("Failed to save player stats: {}", e);
+            }
+        }
+    }
+
+    /// Write the store to disk through a temporary file so a crash never leaves it truncated
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(ref path) = self.path {
+            let tmp_path = path.with_extension("tmp");
+            std::fs::write(&tmp_path, serde_json::to_string_pretty(&self.stats)?)?;
+            std::fs::rename(&tmp_path, path)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats_survive_reload() {
+        let path = std::env::temp_dir().join(format!("pokers_stats_{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut store = PlayerStatsStore::load(path.clone()).unwrap();
+        store.record_hand("alice", 30.0, 60.0, "hand-1");
+        store.record_hand("alice", -10.0, 100.0, "hand-2");
+        store.save().unwrap();
+
+        let reloaded = PlayerStatsStore::load(path.clone()).unwrap();
+        assert_eq!(
+            reloaded.get("alice"),
+            PlayerStats {
+                hands_played: 2,
+                net_winnings: 20.0,
+                biggest_pot: 60.0,
+                last_hand_id: Some("hand-2".to_string()),
+                ..PlayerStats::default()
+            }
+        );
+        assert_eq!(reloaded.get("bob"), PlayerStats::default());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn all_in_results_start_over_each_session() {
+        let mut store = PlayerStatsStore::default();
+        store.record_hand("alice", 100.0, 200.0, "hand-1");
+        store.record_all_in("alice", Some("session-1"), 60.0, 100.0);
+        store.record_all_in("alice", Some("session-2"), 20.0, -50.0);
+
+        let stats = store.get("alice");
+        assert_eq!(stats.all_in.hands, 2);
+        assert_eq!(stats.all_in.luck(), -30.0);
+        assert_eq!(stats.luck_adjusted_winnings(), 130.0);
+        assert_eq!(stats.session_id.as_deref(), Some("session-2"));
+        assert_eq!(
+            stats.session_all_in,
+            AllInResults {
+                hands: 1,
+                expected: 20.0,
+                won: -50.0,
+            }
+        );
+    }
+}
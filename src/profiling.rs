@@ -0,0 +1,156 @@
+// profiling.rs - Counters of the work done by the engine per applied action
+use pyo3::prelude::*;
+
+/// Work done on the thread while applying one action. Only counted when the crate is built
+/// with the `profiling` feature, zero otherwise.
+#[pyclass]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PerfCounters {
+    #[pyo3(get)]
+    pub state_clones: u64,
+
+    /// Heap allocations, reallocations included
+    #[pyo3(get)]
+    pub allocations: u64,
+
+    #[pyo3(get)]
+    pub allocated_bytes: u64,
+
+    /// Hands ranked by the evaluator
+    #[pyo3(get)]
+    pub evaluator_calls: u64,
+}
+
+#[pymethods]
+impl PerfCounters {
+    pub fn __str__(&self) -> PyResult<String> {
+        Ok(format!("{:#?}", self))
+    }
+}
+
+impl PerfCounters {
+    const ZERO: PerfCounters = PerfCounters {
+        state_clones: 0,
+        allocations: 0,
+        allocated_bytes: 0,
+        evaluator_calls: 0,
+    };
+
+    /// Work done between the `before` snapshot and this one
+    pub fn since(self, before: PerfCounters) -> PerfCounters {
+        PerfCounters {
+            state_clones: self.state_clones.wrapping_sub(before.state_clones),
+            allocations: self.allocations.wrapping_sub(before.allocations),
+            allocated_bytes: self.allocated_bytes.wrapping_sub(before.allocated_bytes),
+            evaluator_calls: self.evaluator_calls.wrapping_sub(before.evaluator_calls),
+        }
+    }
+}
+
+/// Counters of the action that produced a `State`. Cloning the probe is cloning the state,
+/// so it counts the clone.
+#[derive(Debug, Default, PartialEq)]
+pub struct PerfProbe(pub PerfCounters);
+
+impl Clone for PerfProbe {
+    fn clone(&self) -> PerfProbe {
+        count(|c| c.state_clones += 1);
+        PerfProbe(self.0)
+    }
+}
+
+#[cfg(feature = "profiling")]
+thread_local! {
+    // No destructor and a const initializer, so the allocator can use it
+    static COUNTERS: std::cell::Cell<PerfCounters> = const { std::cell::Cell::new(PerfCounters::ZERO) };
+}
+
+#[inline]
+#[allow(unused_variables)]
+fn count(update: impl FnOnce(&mut PerfCounters)) {
+    #[cfg(feature = "profiling")]
+    let _ = COUNTERS.try_with(|counters| {
+        let mut value = counters.get();
+        update(&mut value);
+        counters.set(value);
+    });
+}
+
+#[inline]
+pub fn count_evaluation() {
+    count(|c| c.evaluator_calls += 1);
+}
+
+/// Counters of the current thread so far
+pub fn snapshot() -> PerfCounters {
+    #[cfg(feature = "profiling")]
+    return COUNTERS
+        .try_with(|counters| counters.get())
+        .unwrap_or(PerfCounters::ZERO);
+    #[cfg(not(feature = "profiling"))]
+    PerfCounters::ZERO
+}
+
+/// The system allocator, counting the allocations of every thread
+#[cfg(feature = "profiling")]
+struct CountingAllocator;
+
+#[cfg(feature = "profiling")]
+unsafe impl std::alloc::GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: std::alloc::Layout) -> *mut u8 {
+        count(|c| {
+            c.allocations += 1;
+            c.allocated_bytes += layout.size() as u64;
+        });
+        std::alloc::System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: std::alloc::Layout) -> *mut u8 {
+        count(|c| {
+            c.allocations += 1;
+            c.allocated_bytes += layout.size() as u64;
+        });
+        std::alloc::System.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: std::alloc::Layout, new_size: usize) -> *mut u8 {
+        count(|c| {
+            c.allocations += 1;
+            c.allocated_bytes += new_size.saturating_sub(layout.size()) as u64;
+        });
+        std::alloc::System.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: std::alloc::Layout) {
+        std::alloc::System.dealloc(ptr, layout)
+    }
+}
+
+#[cfg(feature = "profiling")]
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+#[cfg(test)]
+mod tests {
+    use crate::state::action::{Action, ActionEnum};
+    use crate::state::State;
+
+    #[test]
+    fn counters_of_the_last_action() {
+        let mut state = State::from_seed(2, 0, 0.5, 1.0, 100.0, 0, false, 0.0).unwrap();
+        // Check it down to the showdown
+        while !state.final_state {
+            state = state.apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+        }
+
+        let profiling = cfg!(feature = "profiling");
+        assert_eq!(state.perf_counters().is_some(), profiling);
+        let Some(counters) = state.perf_counters() else {
+            return;
+        };
+        assert!(counters.state_clones >= 1);
+        assert!(counters.allocations >= 1);
+        assert!(counters.allocated_bytes > 0);
+        assert!(counters.evaluator_calls >= 2);
+    }
+}
@@ -0,0 +1,105 @@
+// protocol.rs - Encoding of cards in the table server messages
+use crate::state::card::{Card, CardRank, CardSuit};
+use serde::{Deserialize, Serialize};
+
+/// Wire value of each suit
+pub const SUITS: [(CardSuit, u8); 4] = [
+    (CardSuit::Clubs, 0),
+    (CardSuit::Diamonds, 1),
+    (CardSuit::Hearts, 2),
+    (CardSuit::Spades, 3),
+];
+
+/// Wire value of each rank, from 2 to 14 for the ace
+pub const RANKS: [(CardRank, u8); 13] = [
+    (CardRank::R2, 2),
+    (CardRank::R3, 3),
+    (CardRank::R4, 4),
+    (CardRank::R5, 5),
+    (CardRank::R6, 6),
+    (CardRank::R7, 7),
+    (CardRank::R8, 8),
+    (CardRank::R9, 9),
+    (CardRank::RT, 10),
+    (CardRank::RJ, 11),
+    (CardRank::RQ, 12),
+    (CardRank::RK, 13),
+    (CardRank::RA, 14),
+];
+
+/// A card in a server message. Every message encodes cards through this type, so clients
+/// can read either the suit and rank or the index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CardInfo {
+    /// See `SUITS`
+    pub suit: u8,
+    /// See `RANKS`
+    pub rank: u8,
+    /// `Card.index()`, from 0 to 51
+    pub index: u8,
+}
+
+impl From<Card> for CardInfo {
+    fn from(card: Card) -> CardInfo {
+        let suit = SUITS.iter().find(|(s, _)| *s == card.suit).unwrap().1;
+        let rank = RANKS.iter().find(|(r, _)| *r == card.rank).unwrap().1;
+        CardInfo {
+            suit,
+            rank,
+            index: card.index(),
+        }
+    }
+}
+
+impl TryFrom<CardInfo> for Card {
+    type Error = String;
+
+    /// Fails on an unknown suit or rank, or an index of another card
+    fn try_from(info: CardInfo) -> Result<Card, String> {
+        let suit = SUITS
+            .iter()
+            .find(|(_, value)| *value == info.suit)
+            .ok_or_else(|| format!("Unknown suit {}", info.suit))?
+            .0;
+        let rank = RANKS
+            .iter()
+            .find(|(_, value)| *value == info.rank)
+            .ok_or_else(|| format!("Unknown rank {}", info.rank))?
+            .0;
+        let card = Card::new(suit, rank);
+        if card.index() != info.index {
+            return Err(format!(
+                "Index {} is not the index of {}, {}",
+                info.index,
+                card,
+                card.index()
+            ));
+        }
+        Ok(card)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cards_survive_the_round_trip() {
+        for card in Card::collect() {
+            let json = serde_json::to_string(&CardInfo::from(card)).unwrap();
+            let info: CardInfo = serde_json::from_str(&json).unwrap();
+            assert_eq!(Card::try_from(info), Ok(card));
+        }
+
+        let ace = Card::from_string("CA".to_string()).unwrap();
+        assert_eq!(
+            serde_json::to_value(CardInfo::from(ace)).unwrap(),
+            serde_json::json!({"suit": 0, "rank": 14, "index": 12})
+        );
+        let info = |suit, rank, index| CardInfo { suit, rank, index };
+        assert!(Card::try_from(info(4, 14, 12)).is_err());
+        assert!(Card::try_from(info(0, 1, 12)).is_err());
+        assert!(Card::try_from(info(0, 14, 13)).is_err());
+    }
+}
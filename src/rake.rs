@@ -0,0 +1,446 @@
+// rake.rs - Rake and jackpot drops taken from the pots of a table, and the bad beat detector
+use crate::hand_strength::{beaten_hand, HandCategory};
+use crate::state::stage::Stage;
+use crate::state::State;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::collections::HashMap;
+
+/// Share of every pot kept by the house
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rake {
+    /// Share of the pot, 0.05 for 5%
+    #[pyo3(get)]
+    pub rate: f64,
+    /// Most taken from a pot, uncapped when unset
+    #[pyo3(get)]
+    pub cap: Option<f64>,
+    /// Hands that end before the flop is dealt are not raked
+    #[pyo3(get)]
+    pub no_flop_no_drop: bool,
+}
+
+impl Rake {
+    pub fn checked(rate: f64, cap: Option<f64>, no_flop_no_drop: bool) -> Result<Rake, String> {
+        if rate.is_nan() || !(0.0..1.0).contains(&rate) {
+            return Err("The rake rate must be at least 0 and below 1".to_owned());
+        }
+        if cap.is_some_and(|cap| cap.is_nan() || cap < 0.0) {
+            return Err("The rake cap cannot be negative".to_owned());
+        }
+        Ok(Rake {
+            rate,
+            cap,
+            no_flop_no_drop,
+        })
+    }
+
+    /// Rake of a pot of `pot` chips
+    fn of(&self, pot: f64) -> f64 {
+        let rake = pot * self.rate;
+        self.cap.map_or(rake, |cap| rake.min(cap))
+    }
+}
+
+#[pymethods]
+impl Rake {
+    #[new]
+    #[pyo3(signature = (rate, cap=None, no_flop_no_drop=true))]
+    pub fn new(rate: f64, cap: Option<f64>, no_flop_no_drop: bool) -> PyResult<Rake> {
+        Rake::checked(rate, cap, no_flop_no_drop).map_err(PyValueError::new_err)
+    }
+
+    pub fn __str__(&self) -> PyResult<String> {
+        Ok(format!("{:#?}", self))
+    }
+}
+
+/// A fixed amount set aside for the jackpot `name` from every pot of at least `threshold`
+/// chips that saw a flop
+#[pyclass]
+#[derive(Debug, Clone, PartialEq)]
+pub struct JackpotDrop {
+    #[pyo3(get)]
+    pub name: String,
+    #[pyo3(get)]
+    pub amount: f64,
+    #[pyo3(get)]
+    pub threshold: f64,
+}
+
+impl JackpotDrop {
+    pub fn checked(name: String, amount: f64, threshold: f64) -> Result<JackpotDrop, String> {
+        if name.is_empty() {
+            return Err("A jackpot needs a name".to_owned());
+        }
+        if amount.is_nan() || amount < 0.0 || threshold.is_nan() || threshold < amount {
+            return Err(format!(
+                "The drop of jackpot '{}' cannot be negative or above its threshold",
+                name
+            ));
+        }
+        Ok(JackpotDrop {
+            name,
+            amount,
+            threshold,
+        })
+    }
+
+    /// See `JackpotDrop.parse_list`
+    pub fn parse(list: &str) -> Result<Vec<JackpotDrop>, String> {
+        list.split(',')
+            .map(|drop| drop.trim())
+            .filter(|drop| !drop.is_empty())
+            .map(|drop| {
+                let invalid = || format!("Invalid jackpot drop '{}'", drop);
+                let (name, amounts) = drop.split_once(':').ok_or_else(invalid)?;
+                let (amount, threshold) = amounts.split_once('@').ok_or_else(invalid)?;
+                let amount = amount.trim().parse::<f64>().map_err(|_| invalid())?;
+                let threshold = threshold.trim().parse::<f64>().map_err(|_| invalid())?;
+                JackpotDrop::checked(name.trim().to_string(), amount, threshold)
+            })
+            .collect()
+    }
+}
+
+#[pymethods]
+impl JackpotDrop {
+    #[new]
+    pub fn new(name: String, amount: f64, threshold: f64) -> PyResult<JackpotDrop> {
+        JackpotDrop::checked(name, amount, threshold).map_err(PyValueError::new_err)
+    }
+
+    /// Drops separated by commas, each written `name:amount@threshold`, e.g.
+    /// `badBeat:1@20,highHand:0.5@20`
+    #[staticmethod]
+    pub fn parse_list(list: &str) -> PyResult<Vec<JackpotDrop>> {
+        JackpotDrop::parse(list).map_err(PyValueError::new_err)
+    }
+
+    pub fn __str__(&self) -> PyResult<String> {
+        Ok(format!("{:#?}", self))
+    }
+}
+
+/// A bad beat: a hand of at least `min_losing_hand` losing at the showdown. The hit is
+/// reported with the pool of the jackpot `jackpot`, paying it out is left to the operator.
+#[pyclass]
+#[derive(Debug, Clone, PartialEq)]
+pub struct BadBeat {
+    #[pyo3(get, set)]
+    pub jackpot: String,
+    #[pyo3(get, set)]
+    pub min_losing_hand: HandCategory,
+}
+
+#[pymethods]
+impl BadBeat {
+    #[new]
+    #[pyo3(signature = (jackpot, min_losing_hand=HandCategory::FourOfAKind))]
+    pub fn new(jackpot: String, min_losing_hand: HandCategory) -> BadBeat {
+        BadBeat {
+            jackpot,
+            min_losing_hand,
+        }
+    }
+
+    pub fn __str__(&self) -> PyResult<String> {
+        Ok(format!("{:#?}", self))
+    }
+}
+
+/// A hand that qualified for the bad beat jackpot
+#[derive(Debug, Clone, PartialEq)]
+pub struct BadBeatHit {
+    pub jackpot: String,
+    /// Player index of the best losing hand, and the hand
+    pub loser: u64,
+    pub losing_hand: HandCategory,
+    pub winner: u64,
+    pub winning_hand: HandCategory,
+}
+
+/// What the house took from one hand
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HandRake {
+    /// Rake taken on each street from the preflop to the river, as the pot grew
+    pub by_street: [f64; 4],
+    pub rake: f64,
+    /// Chips dropped into each jackpot
+    pub drops: Vec<(String, f64)>,
+    /// Chips taken from the winnings of each player, in `State.players_state` order
+    pub taken: Vec<f64>,
+    pub bad_beat: Option<BadBeatHit>,
+}
+
+impl HandRake {
+    /// Chips dropped into the jackpots
+    pub fn dropped(&self) -> f64 {
+        self.drops.iter().map(|(_, amount)| amount).sum()
+    }
+
+    /// Chips taken from `player`
+    pub fn taken_from(&self, player: usize) -> f64 {
+        self.taken.get(player).copied().unwrap_or(0.0)
+    }
+}
+
+/// Rake and jackpots of a table since the server started
+#[pyclass]
+#[derive(Debug, Clone, Default)]
+pub struct TableRake {
+    /// Hands settled
+    #[pyo3(get)]
+    pub hands: u64,
+    /// Hands the house took chips from
+    #[pyo3(get)]
+    pub hands_raked: u64,
+    #[pyo3(get)]
+    pub rake: f64,
+    /// Rake taken on each street from the preflop to the river
+    #[pyo3(get)]
+    pub rake_by_street: [f64; 4],
+    /// Chips dropped into each jackpot
+    #[pyo3(get)]
+    pub jackpots: HashMap<String, f64>,
+    #[pyo3(get)]
+    pub bad_beats: u64,
+}
+
+impl TableRake {
+    pub fn add(&mut self, hand: &HandRake) {
+        self.hands += 1;
+        if hand.rake + hand.dropped() > 0.0 {
+            self.hands_raked += 1;
+        }
+        self.rake += hand.rake;
+        for (total, street) in self.rake_by_street.iter_mut().zip(hand.by_street) {
+            *total += street;
+        }
+        for (name, amount) in &hand.drops {
+            *self.jackpots.entry(name.clone()).or_default() += amount;
+        }
+        if hand.bad_beat.is_some() {
+            self.bad_beats += 1;
+        }
+    }
+}
+
+#[pymethods]
+impl TableRake {
+    pub fn __str__(&self) -> PyResult<String> {
+        Ok(format!("{:#?}", self))
+    }
+}
+
+/// Rake and jackpot drops of the finished hand `state`, and who they are taken from. The rake
+/// of each street is what the pot at its end adds to the rake, so the cap is reached on the
+/// street that fills it. Uncalled bets are not raked; the chips are taken from the winners
+/// in proportion to what they collect.
+pub fn take_rake(
+    state: &State,
+    rake: Option<Rake>,
+    drops: &[JackpotDrop],
+    bad_beat: Option<&BadBeat>,
+) -> HandRake {
+    let n_players = state.players_state.len();
+    let mut hand = HandRake {
+        taken: vec![0.0; n_players],
+        bad_beat: bad_beat.and_then(|bad_beat| find_bad_beat(state, bad_beat)),
+        ..HandRake::default()
+    };
+
+    // The bet nobody called on the last street goes back to its owner
+    let last_street = state
+        .action_list
+        .last()
+        .map_or(state.stage, |record| record.stage)
+        .min(Stage::River) as usize;
+    let mut bets: Vec<f64> = state
+        .players_state
+        .iter()
+        .map(|ps| ps.invested[last_street])
+        .collect();
+    bets.sort_by(|a, b| b.total_cmp(a));
+    let uncalled = match bets[..] {
+        [top, second, ..] => top - second,
+        _ => 0.0,
+    };
+    let pot_after = |street: usize| -> f64 {
+        let pot: f64 = state
+            .players_state
+            .iter()
+            .map(|ps| ps.invested[..=street].iter().sum::<f64>())
+            .sum();
+        if street >= last_street {
+            pot - uncalled
+        } else {
+            pot
+        }
+    };
+    let pot = pot_after(Stage::River as usize);
+
+    // Hands won preflop by a fold never see a flop, an all-in runout does
+    let showdown = state
+        .players_state
+        .iter()
+        .filter(|ps| ps.folded_on.is_none())
+        .count()
+        > 1;
+    let flop_dealt = last_street > Stage::Preflop as usize || showdown;
+    if let Some(rake) = rake.filter(|rake| flop_dealt || !rake.no_flop_no_drop) {
+        let mut raked = 0.0;
+        for (street, share) in hand.by_street.iter_mut().enumerate() {
+            let total = rake.of(pot_after(street));
+            *share = total - raked;
+            raked = total;
+        }
+        hand.rake = raked;
+    }
+    if flop_dealt {
+        let mut left = pot - hand.rake;
+        for drop in drops.iter().filter(|drop| pot >= drop.threshold) {
+            let amount = drop.amount.min(left);
+            left -= amount;
+            hand.drops.push((drop.name.clone(), amount));
+        }
+    }
+
+    let collected: Vec<f64> = state
+        .players_state
+        .iter()
+        .map(|ps| (ps.reward + ps.invested.iter().sum::<f64>()).max(0.0))
+        .collect();
+    let total_collected: f64 = collected.iter().sum();
+    let take = hand.rake + hand.dropped();
+    if take > 0.0 && total_collected > 0.0 {
+        for (taken, collected) in hand.taken.iter_mut().zip(&collected) {
+            *taken = take * collected / total_collected;
+        }
+    }
+    hand
+}
+
+/// The best hand beaten at the showdown of `state`, when it qualifies for `bad_beat`
+fn find_bad_beat(state: &State, bad_beat: &BadBeat) -> Option<BadBeatHit> {
+    let ((winner, best), (loser, beaten)) = beaten_hand(state)?;
+    let losing_hand = HandCategory::from_rank(beaten);
+    (losing_hand >= bad_beat.min_losing_hand).then(|| BadBeatHit {
+        jackpot: bad_beat.jackpot.clone(),
+        loser,
+        losing_hand,
+        winner,
+        winning_hand: HandCategory::from_rank(best),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::action::{Action, ActionEnum};
+    use crate::state::card::Card;
+
+    const EPSILON: f64 = 1e-9;
+
+    fn cards(names: &[&str]) -> Vec<Card> {
+        names
+            .iter()
+            .map(|name| Card::from_string(name.to_string()).unwrap())
+            .collect()
+    }
+
+    /// Heads-up hand called down to the showdown, with a pot bet on the flop
+    fn called_down(deck: &[&str]) -> State {
+        let mut state =
+            State::from_deck(2, 0, 1.0, 2.0, 1000.0, cards(deck), false, 0, 0.0).unwrap();
+        for action in [
+            Action::new(ActionEnum::CheckCall, 0.0),
+            Action::new(ActionEnum::CheckCall, 0.0),
+            Action::new(ActionEnum::BetRaise, 4.0),
+            Action::new(ActionEnum::CheckCall, 0.0),
+        ] {
+            state = state.apply_action(action);
+        }
+        while !state.final_state {
+            state = state.apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+        }
+        state
+    }
+
+    #[test]
+    fn rake_is_capped_on_the_street_that_fills_it() {
+        let state = called_down(&["S2", "H3", "D7", "C8", "C9", "DT", "HJ", "S4", "D5"]);
+        let rake = Rake::checked(0.1, Some(1.0), true).unwrap();
+        let drops = [JackpotDrop::checked("badBeat".to_string(), 0.5, 10.0).unwrap()];
+        let hand = take_rake(&state, Some(rake), &drops, None);
+
+        // 4 chips preflop, 12 with the flop bet called
+        assert!((hand.by_street[0] - 0.4).abs() < EPSILON);
+        assert!((hand.by_street[1] - 0.6).abs() < EPSILON);
+        assert_eq!(hand.by_street[2..], [0.0, 0.0]);
+        assert!((hand.rake - 1.0).abs() < EPSILON);
+        assert_eq!(hand.drops, vec![("badBeat".to_string(), 0.5)]);
+
+        let taken: f64 = hand.taken.iter().sum();
+        assert!((taken - 1.5).abs() < EPSILON);
+        let winners = state.players_state.iter().filter(|ps| ps.reward > 0.0);
+        for ps in winners {
+            assert!(hand.taken_from(ps.player as usize) > 0.0);
+        }
+
+        let mut table = TableRake::default();
+        table.add(&hand);
+        table.add(&hand);
+        assert_eq!((table.hands, table.hands_raked), (2, 2));
+        assert!((table.jackpots["badBeat"] - 1.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn no_flop_no_drop() {
+        let state = State::from_seed(3, 0, 1.0, 2.0, 100.0, 7, false, 0.0).unwrap();
+        let state = state.apply_action(Action::new(ActionEnum::BetRaise, 6.0));
+        let state = state.apply_action(Action::new(ActionEnum::Fold, 0.0));
+        let state = state.apply_action(Action::new(ActionEnum::Fold, 0.0));
+        assert!(state.final_state);
+
+        let rake = Rake::checked(0.05, None, true).unwrap();
+        let drops = [JackpotDrop::checked("badBeat".to_string(), 0.5, 1.0).unwrap()];
+        let hand = take_rake(&state, Some(rake), &drops, None);
+        assert_eq!(hand.rake + hand.dropped(), 0.0);
+
+        // The raise nobody called is not raked
+        let rake = Rake::checked(0.05, None, false).unwrap();
+        let hand = take_rake(&state, Some(rake), &drops, None);
+        assert!((hand.rake - 0.05 * 5.0).abs() < EPSILON);
+        assert!(hand.drops.is_empty());
+    }
+
+    #[test]
+    fn quads_beaten_by_a_straight_flush() {
+        // Player 0 makes a straight flush, player 1 quad sevens
+        let state = called_down(&["S7", "SK", "H8", "H9", "H7", "H6", "H5", "C7", "D7"]);
+        let bad_beat = BadBeat::new("badBeat".to_string(), HandCategory::FourOfAKind);
+        let hit = take_rake(&state, None, &[], Some(&bad_beat))
+            .bad_beat
+            .unwrap();
+        assert_eq!((hit.winner, hit.loser), (0, 1));
+        assert_eq!(hit.losing_hand, HandCategory::FourOfAKind);
+        assert_eq!(hit.winning_hand, HandCategory::StraightFlush);
+
+        let bad_beat = BadBeat::new("badBeat".to_string(), HandCategory::StraightFlush);
+        assert!(take_rake(&state, None, &[], Some(&bad_beat))
+            .bad_beat
+            .is_none());
+    }
+
+    #[test]
+    fn jackpot_drops_parse() {
+        let drops = JackpotDrop::parse("badBeat:1@20, highHand:0.5@20").unwrap();
+        assert_eq!(drops.len(), 2);
+        assert_eq!(drops[1].name, "highHand");
+        assert_eq!((drops[1].amount, drops[1].threshold), (0.5, 20.0));
+        assert!(JackpotDrop::parse("badBeat:1").is_err());
+        assert!(JackpotDrop::parse("badBeat:5@2").is_err());
+    }
+}
@@ -0,0 +1,262 @@
+// range.rs
+use crate::state::card::{Card, CardRank};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+/// Number of starting hand classes (13 pairs, 78 suited, 78 offsuit)
+pub const N_HAND_CLASSES: usize = 169;
+
+/// Grid rows and columns go from aces down to deuces
+pub const GRID_RANKS: [char; 13] = [
+    'A', 'K', 'Q', 'J', 'T', '9', '8', '7', '6', '5', '4', '3', '2',
+];
+
+/// Card rank of a grid row or column
+pub fn grid_rank(idx: usize) -> CardRank {
+    match idx {
+        0 => CardRank::RA,
+        1 => CardRank::RK,
+        2 => CardRank::RQ,
+        3 => CardRank::RJ,
+        4 => CardRank::RT,
+        5 => CardRank::R9,
+        6 => CardRank::R8,
+        7 => CardRank::R7,
+        8 => CardRank::R6,
+        9 => CardRank::R5,
+        10 => CardRank::R4,
+        11 => CardRank::R3,
+        _ => CardRank::R2,
+    }
+}
+
+/// Name of the hand class in a grid cell: pairs on the diagonal, suited hands above it
+/// and offsuit hands below it ("AA", "AKs", "AKo")
+pub fn hand_class_name(row: usize, col: usize) -> String {
+    if row == col {
+        format!("{}{}", GRID_RANKS[row], GRID_RANKS[col])
+    } else if row < col {
+        format!("{}{}s", GRID_RANKS[row], GRID_RANKS[col])
+    } else {
+        format!("{}{}o", GRID_RANKS[col], GRID_RANKS[row])
+    }
+}
+
+/// Grid index (row * 13 + col) of a hand class name like "AKs", "T9o" or "22"
+pub fn parse_hand_class(name: &str) -> Option<usize> {
+    let chars: Vec<char> = name.trim().to_uppercase().chars().collect();
+    let rank_idx = |c: char| GRID_RANKS.iter().position(|&r| r == c);
+
+    match chars.len() {
+        2 if chars[0] == chars[1] => {
+            let idx = rank_idx(chars[0])?;
+            Some(idx * 13 + idx)
+        }
+        3 if chars[0] != chars[1] => {
+            let a = rank_idx(chars[0])?;
+            let b = rank_idx(chars[1])?;
+            let (high, low) = (a.min(b), a.max(b));
+            match chars[2] {
+                'S' => Some(high * 13 + low),
+                'O' => Some(low * 13 + high),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Grid index (row * 13 + col) of the hand class of two hole cards
+pub fn hand_class(hand: (Card, Card)) -> usize {
+    let high = 12 - hand.0.rank.max(hand.1.rank) as usize;
+    let low = 12 - hand.0.rank.min(hand.1.rank) as usize;
+    if hand.0.suit == hand.1.suit {
+        high * 13 + low
+    } else {
+        low * 13 + high
+    }
+}
+
+/// Preflop strength of every hand class in grid order, from 1 (AA) to 169 (72o). The
+/// `range_idx` of a player before the flop is this rank minus one.
+///
+/// The ordering is frozen: features and models built on `range_idx` or
+/// `hand_class_strength` keep their meaning across releases. Another ordering would come
+/// as a new table, not as an edit of this one.
+#[rustfmt::skip]
+const PREFLOP_RANKS: [u8; N_HAND_CLASSES] = [
+    //  A    K    Q    J    T    9    8    7    6    5    4    3    2
+      1,   4,   6,   8,  12,  19,  24,  30,  33,  28,  32,  34,  39, // A
+     11,   2,   7,   9,  14,  22,  37,  44,  53,  55,  58,  59,  60, // K
+     18,  20,   3,  13,  15,  25,  43,  61,  66,  69,  71,  72,  75, // Q
+     27,  31,  35,   5,  16,  26,  41,  64,  79,  82,  86,  87,  89, // J
+     42,  45,  49,  47,  10,  23,  38,  57,  74,  93,  95,  96,  98, // T
+     76,  81,  83,  80,  73,  17,  40,  54,  68,  88, 106, 107, 111, // 9
+     91, 112, 115, 108, 100,  99,  21,  48,  62,  78,  94, 116, 118, // 8
+    102, 122, 131, 129, 124, 119, 114,  29,  56,  67,  85, 103, 120, // 7
+    113, 125, 137, 147, 140, 134, 126, 121,  36,  63,  70,  90, 110, // 6
+    101, 128, 141, 149, 157, 150, 139, 130, 123,  46,  65,  77,  92, // 5
+    104, 132, 143, 152, 158, 164, 156, 145, 136, 127,  50,  84,  97, // 4
+    109, 133, 144, 153, 160, 165, 167, 161, 148, 138, 142,  52, 105, // 3
+    117, 135, 146, 155, 162, 166, 168, 169, 163, 151, 154, 159,  51, // 2
+];
+
+/// Preflop strength rank, 1 to 169, of the hand class at a grid index
+pub fn preflop_rank(grid_index: usize) -> u32 {
+    PREFLOP_RANKS[grid_index] as u32
+}
+
+fn check_grid_index(index: usize) -> PyResult<usize> {
+    if index < N_HAND_CLASSES {
+        Ok(index)
+    } else {
+        Err(PyValueError::new_err(format!(
+            "Grid indices go from 0 to {}, got {}",
+            N_HAND_CLASSES - 1,
+            index
+        )))
+    }
+}
+
+/// Grid index (row * 13 + col) of a hand class like "AKs", "T9o" or "22"
+#[pyfunction]
+pub fn hand_class_index(name: &str) -> PyResult<usize> {
+    parse_hand_class(name)
+        .ok_or_else(|| PyValueError::new_err(format!("Invalid hand class '{}'", name)))
+}
+
+/// Hand class at a grid index, the inverse of `hand_class_index`
+#[pyfunction]
+pub fn hand_class_string(index: usize) -> PyResult<String> {
+    let index = check_grid_index(index)?;
+    Ok(hand_class_name(index / 13, index % 13))
+}
+
+/// Grid index of the hand class of two hole cards
+#[pyfunction]
+pub fn hand_class_of(hand: (Card, Card)) -> usize {
+    hand_class(hand)
+}
+
+/// Preflop strength rank, 1 (AA) to 169 (72o), of the hand class at a grid index
+#[pyfunction]
+pub fn hand_class_strength(index: usize) -> PyResult<u32> {
+    Ok(preflop_rank(check_grid_index(index)?))
+}
+
+/// Grid index of the hand class with preflop strength rank `rank`
+#[pyfunction]
+pub fn hand_class_by_strength(rank: u32) -> PyResult<usize> {
+    PREFLOP_RANKS
+        .iter()
+        .position(|&r| r as u32 == rank)
+        .ok_or_else(|| PyValueError::new_err(format!("Ranks go from 1 to 169, got {}", rank)))
+}
+
+/// Weighted preflop range over the 169 hand classes, stored in 13x13 grid order
+#[pyclass]
+#[derive(Debug, Clone, PartialEq)]
+pub struct HandRange {
+    #[pyo3(get)]
+    pub weights: Vec<f64>,
+}
+
+impl HandRange {
+    pub fn weight(&self, row: usize, col: usize) -> f64 {
+        self.weights[row * 13 + col]
+    }
+}
+
+#[pymethods]
+impl HandRange {
+    /// Build a range from 169 weights in grid order
+    #[staticmethod]
+    pub fn from_weights(weights: Vec<f64>) -> PyResult<HandRange> {
+        if weights.len() != N_HAND_CLASSES {
+            return Err(PyValueError::new_err(format!(
+                "A range needs {} weights, got {}",
+                N_HAND_CLASSES,
+                weights.len()
+            )));
+        }
+        Ok(HandRange { weights })
+    }
+
+    /// Parse a comma separated list of hand classes with optional weights, e.g. "AA, AKs:0.5, KQo"
+    #[staticmethod]
+    pub fn from_string(string: &str) -> PyResult<HandRange> {
+        let mut weights = vec![0.0; N_HAND_CLASSES];
+        for token in string
+            .split(',')
+            .map(|t| t.trim())
+            .filter(|t| !t.is_empty())
+        {
+            let (name, weight) = match token.split_once(':') {
+                Some((name, weight)) => (
+                    name,
+                    weight.trim().parse::<f64>().map_err(|_| {
+                        PyValueError::new_err(format!("Invalid weight in '{}'", token))
+                    })?,
+                ),
+                None => (token, 1.0),
+            };
+            let idx = parse_hand_class(name)
+                .ok_or_else(|| PyValueError::new_err(format!("Invalid hand class '{}'", name)))?;
+            weights[idx] = weight;
+        }
+        Ok(HandRange { weights })
+    }
+
+    pub fn __str__(&self) -> PyResult<String> {
+        Ok(format!("{:#?}", self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hand_class_names_round_trip() {
+        for row in 0..13 {
+            for col in 0..13 {
+                let name = hand_class_name(row, col);
+                assert_eq!(parse_hand_class(&name), Some(row * 13 + col), "{}", name);
+            }
+        }
+        assert_eq!(parse_hand_class("KAs"), parse_hand_class("AKs"));
+        assert_eq!(parse_hand_class("AK"), None);
+    }
+
+    #[test]
+    fn preflop_ranks_are_frozen() {
+        let mut by_strength = vec![String::new(); N_HAND_CLASSES];
+        for index in 0..N_HAND_CLASSES {
+            let rank = hand_class_strength(index).unwrap();
+            assert_eq!(hand_class_by_strength(rank).unwrap(), index);
+            by_strength[rank as usize - 1] = hand_class_string(index).unwrap();
+        }
+        // Changing this list breaks every feature built on `range_idx`
+        assert_eq!(
+            by_strength[..24].join(" "),
+            "AA KK QQ AKs JJ AQs KQs AJs KJs TT AKo ATs QJs KTs QTs JTs 99 AQo A9s KQo 88 \
+             K9s T9s A8s"
+        );
+        assert_eq!(by_strength[31..34].join(" "), "A4s A6s A3s");
+        assert_eq!(
+            by_strength[159..].join(" "),
+            "T3o 73o T2o 62o 94o 93o 92o 83o 82o 72o"
+        );
+
+        let seven_deuce = (
+            Card::from_string("S7".to_string()).unwrap(),
+            Card::from_string("H2".to_string()).unwrap(),
+        );
+        assert_eq!(
+            hand_class_string(hand_class_of(seven_deuce)).unwrap(),
+            "72o"
+        );
+        assert!(hand_class_string(N_HAND_CLASSES).is_err());
+        assert!(hand_class_by_strength(0).is_err());
+    }
+}
@@ -0,0 +1,104 @@
+// redeal.rs - Re-sampling the cards a player cannot see, for determinized search
+use crate::cards::{combos_for_range, live_cards, sample_combo};
+use crate::range::HandRange;
+use crate::state::State;
+use rand::rngs::StdRng;
+use rand::{seq::SliceRandom, SeedableRng};
+use std::collections::HashMap;
+
+/// See `State::redeal_unknown`
+pub fn redeal_unknown(
+    state: &State,
+    seed: u64,
+    hero: u64,
+    constraints: &HashMap<u64, HandRange>,
+) -> Result<State, String> {
+    let n_players = state.players_state.len() as u64;
+    if hero >= n_players {
+        return Err(format!("Player {} is not at the table", hero));
+    }
+    if let Some(player) = constraints
+        .keys()
+        .find(|&&player| player == hero || player >= n_players)
+    {
+        return Err(format!("Cannot constrain the cards of player {}", player));
+    }
+
+    let hero_hand = state.players_state[hero as usize].hand;
+    let mut dead = vec![hero_hand.0, hero_hand.1];
+    dead.extend(&state.public_cards);
+    dead.extend(&state.second_board);
+    let mut unknown = live_cards(&dead);
+    if unknown.len() < 2 * (n_players as usize - 1) {
+        return Err("Not enough unknown cards to deal every opponent".to_owned());
+    }
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    unknown.shuffle(&mut rng);
+
+    // Constrained players are dealt first, while their ranges still have the most combos
+    let mut opponents: Vec<u64> = (0..n_players).filter(|&p| p != hero).collect();
+    opponents.sort_by_key(|p| !constraints.contains_key(p));
+
+    let mut next = state.clone();
+    for player in opponents {
+        let hand = match constraints.get(&player) {
+            Some(range) => sample_combo(&combos_for_range(range, dead.clone()), &mut rng)
+                .ok_or_else(|| format!("No hand of the range of player {} is left", player))?,
+            None => (unknown[0], unknown[1]),
+        };
+        unknown.retain(|c| *c != hand.0 && *c != hand.1);
+        dead.extend([hand.0, hand.1]);
+        next.players_state[player as usize].hand = hand;
+    }
+
+    // The rest of the deck is shuffled too, keeping its size
+    unknown.truncate(state.deck.len());
+    next.deck = unknown;
+    next.update_range_indices();
+    Ok(next)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::range::hand_class;
+    use crate::state::action::{Action, ActionEnum};
+
+    #[test]
+    fn keeps_what_the_hero_sees() {
+        let state = State::from_seed(4, 0, 0.5, 1.0, 100.0, 7, false, 0.0).unwrap();
+        let state = state.apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+        let hero = state.current_player;
+        let aces = HandRange::from_string("AA").unwrap();
+        let constraints = HashMap::from([((hero + 1) % 4, aces)]);
+
+        let redealt = redeal_unknown(&state, 42, hero, &constraints).unwrap();
+        assert_eq!(
+            redealt.players_state[hero as usize].hand,
+            state.players_state[hero as usize].hand
+        );
+        assert_eq!(redealt.pot, state.pot);
+        assert_eq!(redealt.action_list.len(), state.action_list.len());
+        assert_eq!(redealt.deck.len(), state.deck.len());
+
+        let villain = redealt.players_state[((hero + 1) % 4) as usize].hand;
+        assert_eq!(hand_class(villain), 0);
+
+        // Every card is still dealt exactly once
+        assert_eq!(redealt.deck.len() + 2 * redealt.players_state.len(), 52);
+        assert!(redealt.check_invariants().is_empty());
+
+        assert_eq!(
+            redeal_unknown(&state, 42, hero, &constraints).unwrap().deck,
+            redealt.deck
+        );
+        assert!(redeal_unknown(
+            &state,
+            42,
+            hero,
+            &HashMap::from([(hero, HandRange::from_string("AA").unwrap())])
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,265 @@
+// replay_buffer.rs - Memory-mapped ring of fixed-size transitions for self-play
+use crate::datasets::{observation, OBSERVATION_SIZE};
+use crate::node_lock::NodeLock;
+use crate::parallel::apply_and_record;
+use crate::state::action::{Action, ActionEnum};
+use crate::state::{State, StateStatus};
+use memmap2::MmapMut;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::fs::OpenOptions;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+pub const MAGIC: &[u8; 8] = b"PKRSRB1\0";
+pub const VERSION: u32 = 1;
+pub const HEADER_SIZE: usize = 64;
+/// Offset in the header of the number of transitions written so far
+pub const WRITTEN_OFFSET: usize = 32;
+/// Fields of a record before its observation
+const RECORD_FIELDS_SIZE: usize = 40;
+/// Records are padded to 8 bytes so that their index can be read atomically
+pub const RECORD_SIZE: usize = (RECORD_FIELDS_SIZE + 4 * OBSERVATION_SIZE).div_ceil(8) * 8;
+
+/// One decision of one environment, amounts in big blinds
+#[derive(Debug, Clone, PartialEq)]
+struct Transition {
+    seed: u64,
+    env: u32,
+    player: u8,
+    stage: u8,
+    action: u8,
+    /// 0 for a genuine decision, the `Coercion` otherwise
+    coercion: u8,
+    amount: f32,
+    /// Reward of the acting player, non-zero only when the step ends the hand
+    reward: f32,
+    /// Fold, CheckCall, BetRaise
+    legal_actions: [bool; 3],
+    done: bool,
+    observation: Vec<f32>,
+}
+
+impl Transition {
+    /// `None` when `state` does not expect an action (hand over or engine error)
+    fn record(env: usize, state: &State, next: &State) -> Option<Transition> {
+        if state.final_state || !matches!(state.status, StateStatus::Ok) {
+            return None;
+        }
+        let record = next.action_list.get(state.action_list.len())?;
+        let bb = state.bb;
+
+        Some(Transition {
+            seed: state.seed,
+            env: env as u32,
+            player: state.current_player as u8,
+            stage: state.stage as u8,
+            action: record.action.action as u8,
+            coercion: record.coercion.map_or(0, |c| c as u8),
+            amount: (record.action.amount / bb) as f32,
+            reward: if next.final_state {
+                (next.players_state[state.current_player as usize].reward / bb) as f32
+            } else {
+                0.0
+            },
+            legal_actions: [
+                ActionEnum::Fold,
+                ActionEnum::CheckCall,
+                ActionEnum::BetRaise,
+            ]
+            .map(|a| state.legal_actions.contains(&a)),
+            done: next.final_state,
+            observation: observation(state, state.current_player),
+        })
+    }
+
+    /// Everything but the index, which is written last
+    fn write(&self, record: &mut [u8]) {
+        record[8..16].copy_from_slice(&self.seed.to_le_bytes());
+        record[16..20].copy_from_slice(&self.env.to_le_bytes());
+        record[20..24].copy_from_slice(&[self.player, self.stage, self.action, self.coercion]);
+        record[24..28].copy_from_slice(&self.amount.to_le_bytes());
+        record[28..32].copy_from_slice(&self.reward.to_le_bytes());
+        for (byte, &legal) in record[32..35].iter_mut().zip(&self.legal_actions) {
+            *byte = legal as u8;
+        }
+        record[35] = self.done as u8;
+        for (bytes, value) in record[RECORD_FIELDS_SIZE..]
+            .chunks_exact_mut(4)
+            .zip(&self.observation)
+        {
+            bytes.copy_from_slice(&value.to_le_bytes());
+        }
+    }
+}
+
+/// The `u64` at `offset` of the map, which must be 8-byte aligned
+fn atomic_at(map: &mut MmapMut, offset: usize) -> &AtomicU64 {
+    let ptr = map[offset..offset + 8].as_mut_ptr() as *mut u64;
+    // Maps start on a page boundary and every offset used is a multiple of 8
+    unsafe { AtomicU64::from_ptr(ptr) }
+}
+
+/// Writes every decision of a batched simulation to a ring of fixed-size records in a
+/// memory-mapped file, for training processes to read without any serialization. Once
+/// `capacity` records were written, the oldest ones are overwritten.
+#[pyclass]
+pub struct ReplayBuffer {
+    map: Option<MmapMut>,
+
+    #[pyo3(get)]
+    pub capacity: u64,
+
+    /// Transitions written so far, the oldest ones may have been overwritten
+    #[pyo3(get)]
+    pub written: u64,
+}
+
+#[pymethods]
+impl ReplayBuffer {
+    /// Create the file at `path`, replacing any file there
+    #[new]
+    pub fn new(path: &str, capacity: u64) -> PyResult<ReplayBuffer> {
+        if capacity == 0 {
+            return Err(PyValueError::new_err("capacity must be positive"));
+        }
+        let error =
+            |e: std::io::Error| PyValueError::new_err(format!("Cannot map {}: {}", path, e));
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)
+            .map_err(error)?;
+        file.set_len(HEADER_SIZE as u64 + capacity * RECORD_SIZE as u64)
+            .map_err(error)?;
+        let mut map = unsafe { MmapMut::map_mut(&file) }.map_err(error)?;
+
+        map[0..8].copy_from_slice(MAGIC);
+        map[8..12].copy_from_slice(&VERSION.to_le_bytes());
+        map[12..16].copy_from_slice(&(RECORD_SIZE as u32).to_le_bytes());
+        map[16..20].copy_from_slice(&(OBSERVATION_SIZE as u32).to_le_bytes());
+        map[20..24].copy_from_slice(&(HEADER_SIZE as u32).to_le_bytes());
+        map[24..32].copy_from_slice(&capacity.to_le_bytes());
+
+        Ok(ReplayBuffer {
+            map: Some(map),
+            capacity,
+            written: 0,
+        })
+    }
+
+    /// Bytes per record
+    #[staticmethod]
+    pub fn record_size() -> usize {
+        RECORD_SIZE
+    }
+
+    /// Apply `actions` to `states` in parallel like `parallel_apply_action`, writing one
+    /// transition per state that was waiting for an action
+    #[pyo3(signature = (states, actions, locks=None))]
+    pub fn step(
+        &mut self,
+        states: Vec<State>,
+        actions: Vec<Action>,
+        locks: Option<Vec<NodeLock>>,
+    ) -> PyResult<Vec<State>> {
+        if self.map.is_none() {
+            return Err(PyValueError::new_err("The replay buffer is closed"));
+        }
+
+        let locks = locks.unwrap_or_default();
+        let (next_states, transitions) =
+            apply_and_record(&states, actions, &locks, Transition::record);
+
+        let map = self.map.as_mut().expect("checked above");
+        for transition in transitions {
+            let offset = HEADER_SIZE + (self.written % self.capacity) as usize * RECORD_SIZE;
+            // Readers see a record as complete once its index is the one they expect
+            atomic_at(map, offset).store(u64::MAX, Ordering::Release);
+            transition.write(&mut map[offset..offset + RECORD_SIZE]);
+            atomic_at(map, offset).store(self.written, Ordering::Release);
+            self.written += 1;
+            atomic_at(map, WRITTEN_OFFSET).store(self.written, Ordering::Release);
+        }
+        Ok(next_states)
+    }
+
+    /// Write the records to the file, for readers that do not map it
+    pub fn flush(&self) -> PyResult<()> {
+        match self.map {
+            Some(ref map) => map
+                .flush()
+                .map_err(|e| PyValueError::new_err(e.to_string())),
+            None => Err(PyValueError::new_err("The replay buffer is closed")),
+        }
+    }
+
+    pub fn close(&mut self) -> PyResult<()> {
+        if self.map.is_some() {
+            self.flush()?;
+        }
+        self.map = None;
+        Ok(())
+    }
+
+    pub fn __enter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+
+    pub fn __exit__(
+        &mut self,
+        _exc_type: &PyAny,
+        _exc_value: &PyAny,
+        _traceback: &PyAny,
+    ) -> PyResult<()> {
+        self.close()
+    }
+}
+
+impl Drop for ReplayBuffer {
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ring_overwrites_the_oldest_records() {
+        let path =
+            std::env::temp_dir().join(format!("pokers_replay_buffer_{}.bin", std::process::id()));
+        let mut buffer = ReplayBuffer::new(path.to_str().unwrap(), 2).unwrap();
+
+        let states: Vec<State> = (0..3)
+            .map(|seed| State::from_seed(2, 0, 0.5, 1.0, 100.0, seed, false, 0.0).unwrap())
+            .collect();
+        let actions = vec![Action::new(ActionEnum::Fold, 0.0); states.len()];
+        let states = buffer.step(states, actions.clone(), None).unwrap();
+        // The hands are over, nothing more to record
+        buffer.step(states, actions, None).unwrap();
+        assert_eq!(buffer.written, 3);
+        buffer.close().unwrap();
+
+        let bytes = std::fs::read(&path).unwrap();
+        assert_eq!(bytes.len(), HEADER_SIZE + 2 * RECORD_SIZE);
+        assert_eq!(&bytes[0..8], MAGIC);
+        let u64_at =
+            |offset: usize| u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap());
+        assert_eq!(u64_at(24), 2);
+        assert_eq!(u64_at(WRITTEN_OFFSET), 3);
+
+        // The third transition (env 2, seed 2) went to the first slot
+        let record = &bytes[HEADER_SIZE..HEADER_SIZE + RECORD_SIZE];
+        assert_eq!(u64_at(HEADER_SIZE), 2);
+        assert_eq!(u64_at(HEADER_SIZE + 8), 2);
+        assert_eq!(u32::from_le_bytes(record[16..20].try_into().unwrap()), 2);
+        assert_eq!(record[22], ActionEnum::Fold as u8);
+        assert_eq!(f32::from_le_bytes(record[28..32].try_into().unwrap()), -0.5);
+        assert_eq!(record[35], 1);
+        assert_eq!(u64_at(HEADER_SIZE + RECORD_SIZE), 1);
+        std::fs::remove_file(path).unwrap();
+    }
+}
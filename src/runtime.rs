@@ -0,0 +1,288 @@
+// runtime.rs - The async runtime the table server runs on: tokio with `runtime-tokio`, smol
+// with `runtime-smol`. The server only uses what is here, plus the runtime-independent channels
+// and locks of `tokio::sync`.
+use std::future::Future;
+use std::io;
+use std::time::Duration;
+
+#[cfg(not(any(feature = "runtime-tokio", feature = "runtime-smol")))]
+compile_error!("The table server needs a runtime: enable `runtime-tokio` or `runtime-smol`");
+
+#[cfg(feature = "runtime-tokio")]
+pub use tokio::io::{AsyncReadExt, AsyncWriteExt};
+#[cfg(feature = "runtime-tokio")]
+pub use tokio::net::{TcpListener, TcpStream};
+#[cfg(feature = "runtime-tokio")]
+pub use tokio_tungstenite::accept_async;
+
+#[cfg(all(feature = "runtime-smol", not(feature = "runtime-tokio")))]
+pub use async_tungstenite::accept_async;
+#[cfg(all(feature = "runtime-smol", not(feature = "runtime-tokio")))]
+pub use smol::io::{AsyncReadExt, AsyncWriteExt};
+#[cfg(all(feature = "runtime-smol", not(feature = "runtime-tokio")))]
+pub use smol::net::{TcpListener, TcpStream};
+
+/// A spawned task. It keeps running when the handle is dropped, unless aborted.
+pub struct Task {
+    #[cfg(feature = "runtime-tokio")]
+    handle: tokio::task::JoinHandle<()>,
+    #[cfg(all(feature = "runtime-smol", not(feature = "runtime-tokio")))]
+    task: Option<smol::Task<()>>,
+}
+
+impl Task {
+    #[cfg(feature = "runtime-tokio")]
+    pub fn abort(self) {
+        self.handle.abort();
+    }
+
+    #[cfg(all(feature = "runtime-smol", not(feature = "runtime-tokio")))]
+    pub fn abort(mut self) {
+        // Dropping a smol task cancels it
+        drop(self.task.take());
+    }
+}
+
+#[cfg(all(feature = "runtime-smol", not(feature = "runtime-tokio")))]
+impl Drop for Task {
+    fn drop(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.detach();
+        }
+    }
+}
+
+/// Run `future` in the background, on the runtime of the caller
+pub fn spawn<F>(future: F) -> Task
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    #[cfg(feature = "runtime-tokio")]
+    return Task {
+        handle: tokio::spawn(future),
+    };
+    #[cfg(all(feature = "runtime-smol", not(feature = "runtime-tokio")))]
+    return Task {
+        task: Some(smol_backend::spawn(future)),
+    };
+}
+
+/// Run `f` on a thread where it may block, `Err` with the panic when it panicked
+pub async fn spawn_blocking<T, F>(f: F) -> std::thread::Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let f = move || std::panic::catch_unwind(std::panic::AssertUnwindSafe(f));
+    #[cfg(feature = "runtime-tokio")]
+    return tokio::task::spawn_blocking(f)
+        .await
+        .unwrap_or_else(|e| Err(Box::new(e)));
+    #[cfg(all(feature = "runtime-smol", not(feature = "runtime-tokio")))]
+    return smol::unblock(f).await;
+}
+
+pub async fn sleep(duration: Duration) {
+    #[cfg(feature = "runtime-tokio")]
+    tokio::time::sleep(duration).await;
+    #[cfg(all(feature = "runtime-smol", not(feature = "runtime-tokio")))]
+    smol::Timer::after(duration).await;
+}
+
+/// What `future` returns, or `None` when it takes longer than `duration`
+pub async fn timeout<F: Future>(duration: Duration, future: F) -> Option<F::Output> {
+    #[cfg(feature = "runtime-tokio")]
+    return tokio::time::timeout(duration, future).await.ok();
+    #[cfg(all(feature = "runtime-smol", not(feature = "runtime-tokio")))]
+    return smol::future::or(async { Some(future.await) }, async {
+        smol::Timer::after(duration).await;
+        None
+    })
+    .await;
+}
+
+/// Flush and close the writing half of `stream`
+pub async fn shutdown(stream: &mut TcpStream) -> io::Result<()> {
+    #[cfg(feature = "runtime-tokio")]
+    return stream.shutdown().await;
+    #[cfg(all(feature = "runtime-smol", not(feature = "runtime-tokio")))]
+    return stream.close().await;
+}
+
+/// A runtime of its own, for a server started outside of any runtime, e.g. from Python.
+/// Dropping it cancels the tasks still running, after giving its threads a second to finish
+/// what they are doing, such as handing a result to Python.
+pub struct Runtime {
+    #[cfg(feature = "runtime-tokio")]
+    runtime: Option<tokio::runtime::Runtime>,
+    #[cfg(all(feature = "runtime-smol", not(feature = "runtime-tokio")))]
+    runtime: smol_backend::Runtime,
+}
+
+impl Runtime {
+    pub fn new() -> io::Result<Runtime> {
+        #[cfg(feature = "runtime-tokio")]
+        return Ok(Runtime {
+            runtime: Some(
+                tokio::runtime::Builder::new_multi_thread()
+                    .enable_all()
+                    .build()?,
+            ),
+        });
+        #[cfg(all(feature = "runtime-smol", not(feature = "runtime-tokio")))]
+        return Ok(Runtime {
+            runtime: smol_backend::Runtime::new()?,
+        });
+    }
+
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.handle().block_on(future)
+    }
+
+    /// To run futures on the runtime from other threads
+    pub fn handle(&self) -> Handle {
+        #[cfg(feature = "runtime-tokio")]
+        return Handle {
+            handle: self.runtime.as_ref().unwrap().handle().clone(),
+        };
+        #[cfg(all(feature = "runtime-smol", not(feature = "runtime-tokio")))]
+        return Handle {
+            executor: self.runtime.executor(),
+        };
+    }
+}
+
+#[cfg(feature = "runtime-tokio")]
+impl Drop for Runtime {
+    fn drop(&mut self) {
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_timeout(Duration::from_secs(1));
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Handle {
+    #[cfg(feature = "runtime-tokio")]
+    handle: tokio::runtime::Handle,
+    #[cfg(all(feature = "runtime-smol", not(feature = "runtime-tokio")))]
+    executor: std::sync::Weak<smol::Executor<'static>>,
+}
+
+impl Handle {
+    /// Run `future` to completion on the calling thread, which must not be a runtime worker
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        #[cfg(feature = "runtime-tokio")]
+        return self.handle.block_on(future);
+        #[cfg(all(feature = "runtime-smol", not(feature = "runtime-tokio")))]
+        return smol_backend::block_on(&self.executor, future);
+    }
+
+    /// Run `future` in the background on the runtime, from any thread
+    #[allow(dead_code)]
+    pub fn spawn<F>(&self, future: F) -> Task
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        #[cfg(feature = "runtime-tokio")]
+        return Task {
+            handle: self.handle.spawn(future),
+        };
+        #[cfg(all(feature = "runtime-smol", not(feature = "runtime-tokio")))]
+        return Task {
+            task: Some(match self.executor.upgrade() {
+                Some(executor) => executor.spawn(future),
+                None => smol::spawn(future),
+            }),
+        };
+    }
+}
+
+/// smol has no runtime context: the executor a task spawns on is the one running the thread
+#[cfg(all(feature = "runtime-smol", not(feature = "runtime-tokio")))]
+mod smol_backend {
+    use smol::channel::{self, Sender};
+    use smol::Executor;
+    use std::cell::RefCell;
+    use std::future::Future;
+    use std::io;
+    use std::sync::{Arc, Weak};
+    use std::thread::JoinHandle;
+    use std::time::{Duration, Instant};
+
+    thread_local! {
+        static EXECUTOR: RefCell<Weak<Executor<'static>>> = const { RefCell::new(Weak::new()) };
+    }
+
+    /// On the executor of the thread, smol's global one outside of a `Runtime`
+    pub fn spawn<F>(future: F) -> smol::Task<()>
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        match EXECUTOR.with(|executor| executor.borrow().upgrade()) {
+            Some(executor) => executor.spawn(future),
+            None => smol::spawn(future),
+        }
+    }
+
+    /// Run `future` on `executor`, helping it run its tasks meanwhile
+    pub fn block_on<F: Future>(executor: &Weak<Executor<'static>>, future: F) -> F::Output {
+        let Some(executor) = executor.upgrade() else {
+            return smol::block_on(future);
+        };
+        let previous = EXECUTOR.with(|current| current.replace(Arc::downgrade(&executor)));
+        let output = smol::block_on(executor.run(future));
+        EXECUTOR.with(|current| *current.borrow_mut() = previous);
+        output
+    }
+
+    /// An executor run by a thread per core until dropped
+    pub struct Runtime {
+        executor: Arc<Executor<'static>>,
+        /// Closed when dropped, which stops the threads
+        stop: Option<Sender<()>>,
+        threads: Vec<JoinHandle<()>>,
+    }
+
+    impl Runtime {
+        pub fn new() -> io::Result<Runtime> {
+            let executor = Arc::new(Executor::new());
+            let (stop, stopped) = channel::bounded::<()>(1);
+            let workers = std::thread::available_parallelism().map_or(1, |n| n.get());
+            let mut threads = Vec::with_capacity(workers);
+            for i in 0..workers {
+                let executor = Arc::downgrade(&executor);
+                let stopped = stopped.clone();
+                threads.push(
+                    std::thread::Builder::new()
+                        .name(format!("smol-worker-{}", i))
+                        .spawn(move || {
+                            let _ = block_on(&executor, stopped.recv());
+                        })?,
+                );
+            }
+            Ok(Runtime {
+                executor,
+                stop: Some(stop),
+                threads,
+            })
+        }
+
+        pub fn executor(&self) -> Weak<Executor<'static>> {
+            Arc::downgrade(&self.executor)
+        }
+    }
+
+    impl Drop for Runtime {
+        fn drop(&mut self) {
+            // The threads stop between two polls, the tasks left are dropped with the executor
+            drop(self.stop.take());
+            let deadline = Instant::now() + Duration::from_secs(1);
+            for thread in self.threads.drain(..) {
+                while !thread.is_finished() && Instant::now() < deadline {
+                    std::thread::sleep(Duration::from_millis(1));
+                }
+            }
+        }
+    }
+}
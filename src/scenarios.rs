@@ -0,0 +1,212 @@
+// scenarios.rs - Golden hand scenarios shared with the Python test suite
+//
+// Each file in tests/test_files/scenarios describes a hand declaratively: the table setup,
+// the scripted actions, and what the engine must report after every step. Adding a
+// regression case for a rule bug only needs a new JSON or TOML file.
+use crate::state::action::{Action, ActionEnum};
+use crate::state::card::Card;
+use crate::state::rules::TableRules;
+use crate::state::State;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+const SCENARIOS_DIR: &str = "tests/test_files/scenarios";
+const EPSILON: f64 = 1e-9;
+
+#[derive(Debug, Deserialize)]
+struct Scenario {
+    name: String,
+    n_players: u64,
+    button: u64,
+    sb: f64,
+    bb: f64,
+    stake: f64,
+    /// Each player's own stack in player order, in place of `stake`
+    #[serde(default)]
+    stacks: Option<Vec<f64>>,
+    /// Posted by every player, see `State::post_antes`
+    #[serde(default)]
+    ante: f64,
+    /// The rules left out keep their defaults
+    #[serde(default)]
+    rules: Option<TableRules>,
+    deck: Vec<String>,
+    #[serde(default)]
+    initial: Expectation,
+    steps: Vec<Step>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Step {
+    player: u64,
+    action: String,
+    #[serde(default)]
+    amount: f64,
+    #[serde(default)]
+    expect: Expectation,
+}
+
+/// Fields left out of a scenario are not checked
+#[derive(Debug, Default, Deserialize)]
+struct Expectation {
+    current_player: Option<u64>,
+    stage: Option<String>,
+    pot: Option<f64>,
+    min_bet: Option<f64>,
+    legal_actions: Option<Vec<String>>,
+    bet_chips: Option<Vec<f64>>,
+    final_state: Option<bool>,
+    /// Per player, in player order
+    rewards: Option<Vec<f64>>,
+    /// The players who won chips in the hand, in player order
+    winners: Option<Vec<u64>>,
+}
+
+fn parse_action(name: &str) -> ActionEnum {
+    match name {
+        "Fold" => ActionEnum::Fold,
+        "CheckCall" => ActionEnum::CheckCall,
+        "BetRaise" => ActionEnum::BetRaise,
+        _ => panic!("Unknown action {}", name),
+    }
+}
+
+fn check(scenario: &str, step: &str, state: &State, expect: &Expectation) {
+    let ctx = format!("scenario '{}', {}", scenario, step);
+
+    if let Some(current_player) = expect.current_player {
+        assert_eq!(
+            state.current_player, current_player,
+            "{}: current_player",
+            ctx
+        );
+    }
+    if let Some(ref stage) = expect.stage {
+        assert_eq!(&format!("{:?}", state.stage), stage, "{}: stage", ctx);
+    }
+    if let Some(pot) = expect.pot {
+        assert!(
+            (state.pot - pot).abs() < EPSILON,
+            "{}: pot {} != {}",
+            ctx,
+            state.pot,
+            pot
+        );
+    }
+    if let Some(min_bet) = expect.min_bet {
+        assert!(
+            (state.min_bet - min_bet).abs() < EPSILON,
+            "{}: min_bet {} != {}",
+            ctx,
+            state.min_bet,
+            min_bet
+        );
+    }
+    if let Some(ref legal_actions) = expect.legal_actions {
+        let expected: Vec<ActionEnum> = legal_actions.iter().map(|a| parse_action(a)).collect();
+        assert_eq!(state.legal_actions[..], expected, "{}: legal_actions", ctx);
+    }
+    if let Some(ref bet_chips) = expect.bet_chips {
+        let actual: Vec<f64> = state.players_state.iter().map(|ps| ps.bet_chips).collect();
+        assert_eq!(&actual, bet_chips, "{}: bet_chips", ctx);
+    }
+    if let Some(final_state) = expect.final_state {
+        assert_eq!(state.final_state, final_state, "{}: final_state", ctx);
+    }
+    if let Some(ref rewards) = expect.rewards {
+        assert_eq!(state.players_state.len(), rewards.len(), "{}: rewards", ctx);
+        for (ps, reward) in state.players_state.iter().zip(rewards) {
+            assert!(
+                (ps.reward - reward).abs() < EPSILON,
+                "{}: player {} reward {} != {}",
+                ctx,
+                ps.player,
+                ps.reward,
+                reward
+            );
+        }
+    }
+    if let Some(ref winners) = expect.winners {
+        let actual: Vec<u64> = state
+            .players_state
+            .iter()
+            .filter(|ps| ps.reward > 0.0)
+            .map(|ps| ps.player)
+            .collect();
+        assert_eq!(&actual, winners, "{}: winners", ctx);
+    }
+}
+
+fn run_scenario(path: &Path) {
+    let content = std::fs::read_to_string(path).unwrap();
+    let scenario: Result<Scenario, String> = match path.extension().and_then(|e| e.to_str()) {
+        Some("toml") => toml::from_str(&content).map_err(|e| e.to_string()),
+        _ => serde_json::from_str(&content).map_err(|e| e.to_string()),
+    };
+    let scenario =
+        scenario.unwrap_or_else(|e| panic!("Invalid scenario {}: {}", path.display(), e));
+
+    let deck: Vec<Card> = scenario
+        .deck
+        .iter()
+        .map(|c| Card::from_string(c.clone()).unwrap_or_else(|| panic!("Invalid card {}", c)))
+        .collect();
+
+    let mut state = State::from_deck(
+        scenario.n_players,
+        scenario.button,
+        scenario.sb,
+        scenario.bb,
+        scenario.stake,
+        deck,
+        false,
+        0,
+        0.0,
+    )
+    .and_then(|state| match scenario.stacks {
+        Some(ref stacks) => state.with_stacks(stacks.clone()),
+        None => Ok(state),
+    })
+    .and_then(|state| state.with_rules(scenario.rules.unwrap_or_default()))
+    .and_then(|state| match scenario.ante > 0.0 {
+        true => state.post_antes(scenario.ante, false, false),
+        false => Ok(state),
+    })
+    .unwrap_or_else(|e| panic!("scenario '{}': {:?}", scenario.name, e));
+    check(&scenario.name, "initial state", &state, &scenario.initial);
+
+    for (i, step) in scenario.steps.iter().enumerate() {
+        let label = format!("step {}", i);
+        assert_eq!(
+            state.current_player, step.player,
+            "scenario '{}', {}: actor",
+            scenario.name, label
+        );
+        state = state.apply_action(Action::new(parse_action(&step.action), step.amount));
+        check(&scenario.name, &label, &state, &step.expect);
+    }
+}
+
+fn scenario_files() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join(SCENARIOS_DIR);
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            path.extension()
+                .map(|e| e == "json" || e == "toml")
+                .unwrap_or(false)
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+#[test]
+fn golden_scenarios() {
+    let files = scenario_files();
+    assert!(!files.is_empty());
+    for path in files {
+        run_scenario(&path);
+    }
+}
@@ -0,0 +1,174 @@
+// search_context.rs - Nodes of a tree search sharing the immutable parts of their hand
+use crate::state::action::{Action, ActionEnum};
+use crate::state::card::Card;
+use crate::state::stage::Stage;
+use crate::state::State;
+use pyo3::prelude::*;
+use std::sync::Arc;
+
+/// What every node of a hand holds the same copy of
+#[derive(Debug)]
+struct SharedHand {
+    /// Deck of the root. Cards are dealt from its front, so the deck of any node is a suffix.
+    deck: Vec<Card>,
+    hand_id: String,
+    table_id: Option<String>,
+    session_id: Option<String>,
+}
+
+/// Interns the deck and the ids of a hand once for a whole search tree. Its nodes only keep
+/// what changes from one decision to the next, which matters for searches with millions of
+/// nodes. Contexts and nodes can be shared between threads.
+#[pyclass]
+#[derive(Debug, Clone)]
+pub struct SearchContext {
+    shared: Arc<SharedHand>,
+    root: SearchNode,
+}
+
+#[pymethods]
+impl SearchContext {
+    #[new]
+    pub fn new(state: &State) -> SearchContext {
+        let shared = Arc::new(SharedHand {
+            deck: state.deck.clone(),
+            hand_id: state.hand_id.clone(),
+            table_id: state.table_id.clone(),
+            session_id: state.session_id.clone(),
+        });
+        let root = SearchNode::strip(&shared, state.clone());
+        SearchContext { shared, root }
+    }
+
+    #[getter]
+    pub fn root(&self) -> SearchNode {
+        self.root.clone()
+    }
+
+    /// Cards in the deck of the root
+    #[getter]
+    pub fn deck_size(&self) -> usize {
+        self.shared.deck.len()
+    }
+
+    /// Whether `node` was expanded from this context's root
+    pub fn contains(&self, node: &SearchNode) -> bool {
+        Arc::ptr_eq(&self.shared, &node.shared)
+    }
+}
+
+/// A state of a `SearchContext`, without its own copy of the deck and ids
+#[pyclass]
+#[derive(Debug, Clone)]
+pub struct SearchNode {
+    shared: Arc<SharedHand>,
+    /// The state with an empty deck and empty ids
+    state: State,
+    /// Cards dealt from the root's deck
+    dealt: usize,
+}
+
+impl SearchNode {
+    fn strip(shared: &Arc<SharedHand>, mut state: State) -> SearchNode {
+        let dealt = shared.deck.len().saturating_sub(state.deck.len());
+        state.deck = Vec::new();
+        state.hand_id = String::new();
+        state.table_id = None;
+        state.session_id = None;
+        SearchNode {
+            shared: Arc::clone(shared),
+            state,
+            dealt,
+        }
+    }
+}
+
+#[pymethods]
+impl SearchNode {
+    /// The full state of the node, deck and ids included
+    pub fn state(&self) -> State {
+        let mut state = self.state.clone();
+        state.deck = self.shared.deck[self.dealt..].to_vec();
+        state.hand_id = self.shared.hand_id.clone();
+        state.table_id = self.shared.table_id.clone();
+        state.session_id = self.shared.session_id.clone();
+        state
+    }
+
+    /// The child node reached by `action`, see `State.apply_action`
+    pub fn apply_action(&self, action: Action) -> SearchNode {
+        SearchNode::strip(&self.shared, self.state().apply_action(action))
+    }
+
+    #[getter]
+    pub fn current_player(&self) -> u64 {
+        self.state.current_player
+    }
+
+    #[getter]
+    pub fn stage(&self) -> Stage {
+        self.state.stage
+    }
+
+    #[getter]
+    pub fn pot(&self) -> f64 {
+        self.state.pot
+    }
+
+    #[getter]
+    pub fn final_state(&self) -> bool {
+        self.state.final_state
+    }
+
+    #[getter]
+    pub fn legal_actions(&self) -> Vec<ActionEnum> {
+        self.state.legal_actions.to_vec()
+    }
+
+    /// Reward of every player, non-zero once the hand is over
+    pub fn rewards(&self) -> Vec<f64> {
+        self.state
+            .players_state
+            .iter()
+            .map(|ps| ps.reward)
+            .collect()
+    }
+
+    pub fn __str__(&self) -> PyResult<String> {
+        self.state().__str__()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nodes_replay_the_hand() {
+        let state = State::from_seed(3, 0, 0.5, 1.0, 100.0, 7, false, 0.0)
+            .unwrap()
+            .with_ids(None, Some("table".to_string()), None);
+        let context = SearchContext::new(&state);
+        assert_eq!(context.deck_size(), 52 - 6);
+
+        let mut state = state;
+        let mut node = context.root();
+        while !state.final_state {
+            let action = Action::new(ActionEnum::CheckCall, 0.0);
+            state = state.apply_action(action);
+            node = node.apply_action(action);
+            assert!(node.state.deck.is_empty());
+
+            let full = node.state();
+            assert_eq!(full.deck, state.deck);
+            assert_eq!(full.public_cards, state.public_cards);
+            assert_eq!(full.hand_id, state.hand_id);
+            assert_eq!(full.table_id.as_deref(), Some("table"));
+            assert_eq!(full.action_list, state.action_list);
+        }
+        assert!(node.final_state());
+        assert!(context.contains(&node));
+        let rewards: Vec<f64> = state.players_state.iter().map(|ps| ps.reward).collect();
+        assert_eq!(node.rewards(), rewards);
+    }
+}
@@ -0,0 +1,704 @@
+// self_play.rs - One policy playing every seat of a table, hand after hand
+use crate::datasets::{observation, MAX_SEATS};
+use crate::game_logic::available_actions;
+use crate::hybrid_agent::HybridAgent;
+use crate::node_lock::{locked_action, NodeLock};
+use crate::seeds::hand_seed;
+use crate::state::action::{Action, DefaultPolicy};
+use crate::state::State;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Mixed into the session seed for the draws of the opponents, so they do not follow the deck
+const OPPONENT_STREAM: u64 = 0x6F70_706F_6E65_6E74;
+
+/// Mixed into the session seed for the draws of the curriculum
+const CURRICULUM_STREAM: u64 = 0x6375_7272_6963_756C;
+
+/// Agent of an `OpponentPool`
+#[derive(Debug, Clone)]
+enum Opponent {
+    /// Plays the action of a `DefaultPolicy` at every decision
+    Rule(DefaultPolicy),
+    /// Plays the strategy of the first lock that matches, the fallback's action elsewhere
+    Locks(Vec<NodeLock>, DefaultPolicy),
+    /// Plays its charts, solver strategy and fallback in turn
+    Hybrid(Box<HybridAgent>),
+    /// `Callable[[State, int], Action]`, called at each of its decisions
+    Python(PyObject),
+}
+
+impl Opponent {
+    fn act(&self, state: &State) -> PyResult<Action> {
+        match self {
+            Opponent::Rule(policy) => Ok(state.default_action(*policy)),
+            Opponent::Locks(locks, fallback) => {
+                Ok(locked_action(locks, state).unwrap_or_else(|| state.default_action(*fallback)))
+            }
+            Opponent::Hybrid(agent) => Ok(agent.decide(state, state.current_player)),
+            Opponent::Python(policy) => Python::with_gil(|py| {
+                policy
+                    .call1(
+                        py,
+                        (
+                            state.with_perspective(Some(state.current_player)),
+                            state.current_player,
+                        ),
+                    )?
+                    .extract(py)
+            }),
+        }
+    }
+}
+
+/// Agents the opponents of a `SelfPlayTable` are drawn from. At the start of every hand each
+/// opponent seat draws an agent among those playing it, with probability proportional to the
+/// agents' weights.
+#[pyclass]
+#[derive(Debug, Clone, Default)]
+pub struct OpponentPool {
+    /// Each agent with its weight and the seats it plays, every seat when `None`
+    agents: Vec<(Opponent, f64, Option<Vec<u64>>)>,
+}
+
+impl OpponentPool {
+    fn add(&mut self, agent: Opponent, weight: f64, seats: Option<Vec<u64>>) -> PyResult<()> {
+        if !(weight > 0.0 && weight.is_finite()) {
+            return Err(PyValueError::new_err(format!(
+                "The weight of an agent must be positive, got {}",
+                weight
+            )));
+        }
+        self.agents.push((agent, weight, seats));
+        Ok(())
+    }
+
+    /// Weight of each agent at `seat`, 0 for the agents that do not play it
+    fn seat_weights(&self, seat: u64) -> Vec<f64> {
+        self.agents
+            .iter()
+            .map(|(_, weight, seats)| match seats {
+                Some(seats) if !seats.contains(&seat) => 0.0,
+                _ => *weight,
+            })
+            .collect()
+    }
+
+    /// Index of an agent playing `seat` drawn by weight
+    fn draw(&self, seat: u64, rng: &mut StdRng) -> usize {
+        draw_weighted(&self.seat_weights(seat), rng).expect("every seat has an agent")
+    }
+}
+
+#[pymethods]
+impl OpponentPool {
+    #[new]
+    pub fn new() -> OpponentPool {
+        OpponentPool::default()
+    }
+
+    /// Add an agent that plays `policy` at every decision. Agents play the `seats` given, or
+    /// any seat.
+    #[pyo3(signature = (policy, weight=1.0, seats=None))]
+    pub fn add_rule(
+        &mut self,
+        policy: DefaultPolicy,
+        weight: f64,
+        seats: Option<Vec<u64>>,
+    ) -> PyResult<()> {
+        self.add(Opponent::Rule(policy), weight, seats)
+    }
+
+    /// Add an agent that plays the strategy of the first of `locks` matching the decision,
+    /// and `fallback` where none does
+    #[pyo3(signature = (locks, weight=1.0, fallback=DefaultPolicy::CheckElseFold, seats=None))]
+    pub fn add_locks(
+        &mut self,
+        locks: Vec<NodeLock>,
+        weight: f64,
+        fallback: DefaultPolicy,
+        seats: Option<Vec<u64>>,
+    ) -> PyResult<()> {
+        self.add(Opponent::Locks(locks, fallback), weight, seats)
+    }
+
+    /// Add a `HybridAgent`, played without calling back into Python
+    #[pyo3(signature = (agent, weight=1.0, seats=None))]
+    pub fn add_hybrid(
+        &mut self,
+        agent: HybridAgent,
+        weight: f64,
+        seats: Option<Vec<u64>>,
+    ) -> PyResult<()> {
+        self.add(Opponent::Hybrid(Box::new(agent)), weight, seats)
+    }
+
+    /// Add a Python agent `policy(state, player) -> Action`, e.g. a previous checkpoint
+    #[pyo3(signature = (policy, weight=1.0, seats=None))]
+    pub fn add_policy(
+        &mut self,
+        policy: PyObject,
+        weight: f64,
+        seats: Option<Vec<u64>>,
+    ) -> PyResult<()> {
+        self.add(Opponent::Python(policy), weight, seats)
+    }
+
+    /// Weight of each agent, in the order they were added
+    #[getter]
+    pub fn weights(&self) -> Vec<f64> {
+        self.agents.iter().map(|(_, weight, _)| *weight).collect()
+    }
+
+    pub fn __len__(&self) -> usize {
+        self.agents.len()
+    }
+
+    pub fn __str__(&self) -> PyResult<String> {
+        Ok(format!("{:#?}", self))
+    }
+}
+
+/// Distributions the hands of a `SelfPlayTable` are dealt from, for curriculum training and
+/// domain randomization. Every hand draws its blinds, stacks and button anew.
+#[pyclass]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Curriculum {
+    /// Range the starting stacks are drawn from uniformly, in big blinds; the table's `stake`
+    /// when unset
+    #[pyo3(get)]
+    pub stack_bb: Option<(f64, f64)>,
+    /// One stack drawn for the whole table rather than one per seat
+    #[pyo3(get)]
+    pub equal_stacks: bool,
+    /// Blind levels (small blind, big blind) to draw from; the table's blinds when empty
+    #[pyo3(get)]
+    pub blinds: Vec<(f64, f64)>,
+    /// Weight of each blind level, all equal when not given
+    #[pyo3(get)]
+    pub blind_weights: Vec<f64>,
+    /// The button is drawn uniformly rather than moving one seat a hand
+    #[pyo3(get)]
+    pub random_button: bool,
+}
+
+impl Curriculum {
+    pub fn checked(
+        stack_bb: Option<(f64, f64)>,
+        equal_stacks: bool,
+        blinds: Vec<(f64, f64)>,
+        blind_weights: Option<Vec<f64>>,
+        random_button: bool,
+    ) -> Result<Curriculum, String> {
+        if let Some((min, max)) = stack_bb {
+            if !(1.0 <= min && min <= max && max.is_finite()) {
+                return Err("Stacks must range from at least 1 big blind up".to_owned());
+            }
+        }
+        if blinds
+            .iter()
+            .any(|&(sb, bb)| !(sb > 0.0 && sb <= bb && bb.is_finite()))
+        {
+            return Err("Small blinds must be positive and at most the big blind".to_owned());
+        }
+        let blind_weights = blind_weights.unwrap_or_else(|| vec![1.0; blinds.len()]);
+        if blind_weights.len() != blinds.len() {
+            return Err("There must be one weight per blind level".to_owned());
+        }
+        if blind_weights.iter().any(|&w| !(w > 0.0 && w.is_finite())) {
+            return Err("Blind weights must be positive".to_owned());
+        }
+        Ok(Curriculum {
+            stack_bb,
+            equal_stacks,
+            blinds,
+            blind_weights,
+            random_button,
+        })
+    }
+
+    /// Blinds, button and starting stacks of a hand at a table of `n_players`, the table's
+    /// blinds and stake where the curriculum draws none
+    fn draw(
+        &self,
+        rng: &mut StdRng,
+        n_players: u64,
+        hand_index: u64,
+        blinds: (f64, f64),
+        stake: f64,
+    ) -> ((f64, f64), u64, Vec<f64>) {
+        let blinds = match draw_weighted(&self.blind_weights, rng) {
+            Some(level) => self.blinds[level],
+            None => blinds,
+        };
+        let button = if self.random_button {
+            rng.gen_range(0..n_players)
+        } else {
+            hand_index % n_players
+        };
+        let stacks = match self.stack_bb {
+            Some((min, max)) => {
+                let mut draw = || rng.gen_range(min..=max) * blinds.1;
+                if self.equal_stacks {
+                    vec![draw(); n_players as usize]
+                } else {
+                    (0..n_players).map(|_| draw()).collect()
+                }
+            }
+            None => vec![stake; n_players as usize],
+        };
+        (blinds, button, stacks)
+    }
+}
+
+#[pymethods]
+impl Curriculum {
+    #[new]
+    #[pyo3(signature = (stack_bb=None, equal_stacks=false, blinds=Vec::new(), blind_weights=None, random_button=false))]
+    pub fn new(
+        stack_bb: Option<(f64, f64)>,
+        equal_stacks: bool,
+        blinds: Vec<(f64, f64)>,
+        blind_weights: Option<Vec<f64>>,
+        random_button: bool,
+    ) -> PyResult<Curriculum> {
+        Curriculum::checked(stack_bb, equal_stacks, blinds, blind_weights, random_button)
+            .map_err(PyValueError::new_err)
+    }
+
+    pub fn __str__(&self) -> PyResult<String> {
+        Ok(format!("{:#?}", self))
+    }
+}
+
+/// Index drawn with probability proportional to `weights`, `None` when there are none
+fn draw_weighted(weights: &[f64], rng: &mut StdRng) -> Option<usize> {
+    let total: f64 = weights.iter().sum();
+    let mut x = rng.gen::<f64>() * total;
+    for (i, weight) in weights.iter().enumerate() {
+        if x < *weight {
+            return Some(i);
+        }
+        x -= weight;
+    }
+    weights.len().checked_sub(1)
+}
+
+/// A table where a single policy plays every seat, the usual self-play setup. At each
+/// decision the observations of all seats are stacked in seat order, next to the seat to act
+/// and its legal action mask, and the policy answers with an index of the mask. Hand `n` is
+/// dealt with `hand_seed(seed, n)` and the button on seat `n % n_players`, every seat
+/// starting it with `stake`, unless a `Curriculum` draws the blinds, button and stacks.
+///
+/// With an `OpponentPool`, the policy only plays the `hero` seat. The other seats are played
+/// by agents drawn from the pool for each hand, without going back to the caller.
+#[pyclass]
+#[derive(Debug, Clone)]
+pub struct SelfPlayTable {
+    state: State,
+    blinds: (f64, f64),
+    stake: f64,
+    seed: u64,
+    n_bet_buckets: usize,
+    opponents: Option<OpponentPool>,
+    curriculum: Option<Curriculum>,
+
+    /// Seat of the policy when there are opponents
+    #[pyo3(get)]
+    pub hero: u64,
+
+    /// Hands finished so far, the index of the hand being played
+    #[pyo3(get)]
+    pub hand_index: u64,
+
+    /// Index in the pool of the agent playing each seat this hand, `None` for the policy's
+    #[pyo3(get)]
+    pub seat_agents: Vec<Option<usize>>,
+}
+
+impl SelfPlayTable {
+    fn deal(&self, hand_index: u64) -> PyResult<State> {
+        let n_players = self.state.players_state.len() as u64;
+        let ((sb, bb), button, stacks) = match &self.curriculum {
+            Some(curriculum) => {
+                let seed = hand_seed(self.seed ^ CURRICULUM_STREAM, hand_index);
+                let mut rng = StdRng::seed_from_u64(seed);
+                curriculum.draw(&mut rng, n_players, hand_index, self.blinds, self.stake)
+            }
+            None => (
+                self.blinds,
+                hand_index % n_players,
+                vec![self.stake; n_players as usize],
+            ),
+        };
+
+        let max_stack = stacks.iter().copied().fold(bb, f64::max);
+        let seed = hand_seed(self.seed, hand_index);
+        let mut state = State::from_seed(n_players, button, sb, bb, max_stack, seed, false, 0.0)?;
+        for ps in &mut state.players_state {
+            ps.stake = stacks[ps.player as usize] - ps.bet_chips;
+        }
+        state.legal_actions = available_actions(&state);
+        Ok(state)
+    }
+
+    /// Deal hand `hand_index` and play it up to the first decision of the policy, or to its end
+    /// when the opponents finish it first, e.g. when everybody folds to the hero's big blind
+    fn start_hand(&mut self, hand_index: u64) -> PyResult<()> {
+        self.state = self.deal(hand_index)?;
+        self.hand_index = hand_index;
+        let mut rng = StdRng::seed_from_u64(hand_seed(self.seed ^ OPPONENT_STREAM, hand_index));
+        let n_players = self.state.players_state.len() as u64;
+        self.seat_agents = (0..n_players)
+            .map(|seat| match &self.opponents {
+                Some(pool) if seat != self.hero => Some(pool.draw(seat, &mut rng)),
+                _ => None,
+            })
+            .collect();
+        self.play_opponents()
+    }
+
+    /// Seat of the policy: the seat to act, or the hero once the opponents finished the hand
+    fn policy_seat(&self) -> u64 {
+        match self.state.final_state {
+            true => self.hero,
+            false => self.state.current_player,
+        }
+    }
+
+    /// Play the opponents until the hero is to act or the hand is over
+    fn play_opponents(&mut self) -> PyResult<()> {
+        while let Some(pool) = &self.opponents {
+            let seat = self.state.current_player;
+            if self.state.final_state || seat == self.hero {
+                break;
+            }
+            let agent = self.seat_agents[seat as usize].expect("opponent seats have an agent");
+            let action = pool.agents[agent].0.act(&self.state)?;
+            self.state = self.state.apply_action(action);
+        }
+        Ok(())
+    }
+}
+
+#[pymethods]
+impl SelfPlayTable {
+    #[new]
+    #[pyo3(signature = (n_players, sb, bb, stake, seed=0, n_bet_buckets=10, opponents=None, hero=0, curriculum=None))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        n_players: u64,
+        sb: f64,
+        bb: f64,
+        stake: f64,
+        seed: u64,
+        n_bet_buckets: usize,
+        opponents: Option<OpponentPool>,
+        hero: u64,
+        curriculum: Option<Curriculum>,
+    ) -> PyResult<SelfPlayTable> {
+        if n_players as usize > MAX_SEATS {
+            return Err(PyValueError::new_err(format!(
+                "Observations hold at most {} seats, got {}",
+                MAX_SEATS, n_players
+            )));
+        }
+        if hero >= n_players {
+            return Err(PyValueError::new_err("The hero must be one of the seats"));
+        }
+        if opponents
+            .as_ref()
+            .is_some_and(|pool| pool.agents.is_empty())
+        {
+            return Err(PyValueError::new_err("The opponent pool is empty"));
+        }
+        if let Some(pool) = &opponents {
+            if let Some(seat) = (0..n_players)
+                .filter(|&seat| seat != hero)
+                .find(|&seat| pool.seat_weights(seat).iter().all(|&w| w == 0.0))
+            {
+                return Err(PyValueError::new_err(format!(
+                    "No agent of the pool plays seat {}",
+                    seat
+                )));
+            }
+        }
+        let state = State::from_seed(n_players, 0, sb, bb, stake, hand_seed(seed, 0), false, 0.0)?;
+        let mut table = SelfPlayTable {
+            state,
+            blinds: (sb, bb),
+            stake,
+            seed,
+            n_bet_buckets,
+            opponents,
+            curriculum,
+            hero,
+            hand_index: 0,
+            seat_agents: Vec::new(),
+        };
+        table.start_hand(0)?;
+        Ok(table)
+    }
+
+    /// State of the hand being played, seen from the seat to act
+    #[getter]
+    pub fn state(&self) -> State {
+        self.state.with_perspective(Some(self.policy_seat()))
+    }
+
+    /// Seat to act, the hero's when the opponents finished the hand before the hero's turn
+    #[getter]
+    pub fn seat(&self) -> u64 {
+        self.policy_seat()
+    }
+
+    /// The opponents finished the hand before the hero's first decision: the mask is empty, and
+    /// the next `step` only returns the rewards of the hand
+    #[getter]
+    pub fn hand_over(&self) -> bool {
+        self.state.final_state
+    }
+
+    /// `observation(state, seat)` of every seat, in seat order. Each only holds its own hole
+    /// cards, so the whole stack can go through the policy in one batch.
+    pub fn observations(&self) -> Vec<Vec<f32>> {
+        (0..self.state.players_state.len() as u64)
+            .map(|seat| observation(&self.state, seat))
+            .collect()
+    }
+
+    /// `State.legal_action_mask` of the seat to act
+    pub fn legal_action_mask(&self) -> Vec<bool> {
+        self.state.legal_action_mask(self.n_bet_buckets)
+    }
+
+    /// Play entry `action` of `legal_action_mask()` for the seat to act, then the opponents up
+    /// to the hero's next decision. When the hand ends the next one is dealt and the rewards
+    /// of every seat in the finished hand, in big blinds, are returned. On a hand the
+    /// opponents finished, `action` is not played and only the rewards are returned.
+    pub fn step(&mut self, action: usize) -> PyResult<Option<Vec<f64>>> {
+        if !self.state.final_state {
+            let chosen = self
+                .state
+                .mask_action(action, self.n_bet_buckets)
+                .ok_or_else(|| {
+                    PyValueError::new_err(format!(
+                        "Action {} is not legal for seat {}",
+                        action, self.state.current_player
+                    ))
+                })?;
+            self.state = self.state.apply_action(chosen);
+            self.play_opponents()?;
+            if !self.state.final_state {
+                return Ok(None);
+            }
+        }
+
+        let rewards = self
+            .state
+            .players_state
+            .iter()
+            .map(|ps| ps.reward / self.state.bb)
+            .collect();
+        self.start_hand(self.hand_index + 1)?;
+        Ok(Some(rewards))
+    }
+
+    pub fn __str__(&self) -> PyResult<String> {
+        Ok(format!("{:#?}", self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datasets::OBSERVATION_SIZE;
+    use crate::state::action::ActionEnum;
+
+    #[test]
+    fn one_policy_plays_every_seat() {
+        let mut table = SelfPlayTable::new(3, 0.5, 1.0, 50.0, 7, 4, None, 0, None).unwrap();
+        let mut finished = Vec::new();
+        let mut seats = Vec::new();
+
+        while finished.len() < 4 {
+            let observations = table.observations();
+            assert_eq!(observations.len(), 3);
+            assert!(observations.iter().all(|obs| obs.len() == OBSERVATION_SIZE));
+            seats.push(table.seat());
+
+            // Raise the smallest bucket when possible, otherwise check or call
+            let mask = table.legal_action_mask();
+            assert_eq!(mask.len(), 6);
+            let raises = table.state().action_list.len() < 3;
+            let action = match mask[2..].iter().position(|&legal| legal) {
+                Some(bucket) if raises => bucket + 2,
+                _ => 1,
+            };
+            if let Some(rewards) = table.step(action).unwrap() {
+                assert!(rewards.iter().sum::<f64>().abs() < 1e-9);
+                finished.push(rewards);
+            }
+        }
+
+        assert_eq!(table.hand_index, 4);
+        assert_eq!(table.state().button, 1);
+        assert_eq!(table.state().seed, hand_seed(7, 4));
+        seats.sort();
+        seats.dedup();
+        assert_eq!(seats, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn illegal_actions_are_refused() {
+        let mut table = SelfPlayTable::new(2, 0.5, 1.0, 50.0, 0, 10, None, 0, None).unwrap();
+        let state = table.state();
+        // Fold, CheckCall and 10 buckets: there is no entry 12
+        assert!(table.step(12).is_err());
+        assert_eq!(table.state().action_list, state.action_list);
+        assert!(SelfPlayTable::new(11, 0.5, 1.0, 50.0, 0, 10, None, 0, None).is_err());
+    }
+
+    #[test]
+    fn opponents_are_drawn_from_the_pool() {
+        let mut pool = OpponentPool::new();
+        assert!(pool.add_rule(DefaultPolicy::Fold, 0.0, None).is_err());
+        pool.add_rule(DefaultPolicy::CheckCall, 7.0, None).unwrap();
+        pool.add_locks(Vec::new(), 3.0, DefaultPolicy::Fold, None)
+            .unwrap();
+        assert_eq!(pool.weights(), vec![7.0, 3.0]);
+
+        let mut table = SelfPlayTable::new(4, 0.5, 1.0, 50.0, 3, 10, Some(pool), 2, None).unwrap();
+        let mut draws = [0; 2];
+        let mut counted = None;
+        while table.hand_index < 200 {
+            // The policy is only asked at the hero's decisions
+            assert_eq!(table.seat(), 2);
+            assert_eq!(table.seat_agents[2], None);
+            let hand_index = table.hand_index;
+            if counted != Some(hand_index) {
+                for agent in table.seat_agents.iter().flatten() {
+                    draws[*agent] += 1;
+                }
+                counted = Some(hand_index);
+            }
+            // Check when possible, fold otherwise; the agents then play the hand out
+            let action = table.state().default_action(DefaultPolicy::CheckElseFold);
+            let index = if action.action == ActionEnum::Fold {
+                0
+            } else {
+                1
+            };
+            if let Some(rewards) = table.step(index).unwrap() {
+                assert!(rewards.iter().sum::<f64>().abs() < 1e-9);
+                assert!(table.hand_index > hand_index);
+            }
+        }
+        // Three opponents a hand, drawn 70/30
+        let share = draws[0] as f64 / (draws[0] + draws[1]) as f64;
+        assert!(draws[0] + draws[1] > 500);
+        assert!((0.63..0.77).contains(&share), "{:?}", draws);
+
+        assert!(
+            SelfPlayTable::new(4, 0.5, 1.0, 50.0, 3, 10, Some(OpponentPool::new()), 0, None)
+                .is_err()
+        );
+        assert!(SelfPlayTable::new(4, 0.5, 1.0, 50.0, 3, 10, None, 4, None).is_err());
+    }
+
+    #[test]
+    fn hands_the_opponents_finish_end_with_their_rewards() {
+        let mut pool = OpponentPool::new();
+        pool.add_rule(DefaultPolicy::Fold, 1.0, None).unwrap();
+        let mut table = SelfPlayTable::new(3, 0.5, 1.0, 50.0, 5, 10, Some(pool), 0, None).unwrap();
+
+        // The hero opens on the button and both blinds fold
+        assert!(!table.hand_over());
+        let rewards = table.step(2).unwrap().unwrap();
+        assert_eq!(rewards, vec![1.5, -0.5, -1.0]);
+
+        // Both fold to the hero's big blind before the hero acts
+        assert_eq!(table.hand_index, 1);
+        assert!(table.hand_over());
+        assert_eq!(table.seat(), 0);
+        assert!(table.legal_action_mask().iter().all(|legal| !legal));
+        let rewards = table.step(0).unwrap().unwrap();
+        assert_eq!(rewards, vec![0.5, 0.0, -0.5]);
+        assert_eq!(table.hand_index, 2);
+        assert!(!table.hand_over());
+    }
+
+    #[test]
+    fn agents_play_their_seats() {
+        let mut pool = OpponentPool::new();
+        pool.add_rule(DefaultPolicy::CheckCall, 1.0, Some(vec![1]))
+            .unwrap();
+        let agent = HybridAgent::checked(Vec::new(), None, None, 0.65, 0.66, 50).unwrap();
+        pool.add_hybrid(agent, 1.0, Some(vec![2, 3])).unwrap();
+
+        let mut table =
+            SelfPlayTable::new(4, 0.5, 1.0, 50.0, 9, 10, Some(pool.clone()), 0, None).unwrap();
+        while table.hand_index < 20 {
+            assert_eq!(table.seat_agents, vec![None, Some(0), Some(1), Some(1)]);
+            let action = table.state().default_action(DefaultPolicy::CheckElseFold);
+            let index = if action.action == ActionEnum::Fold {
+                0
+            } else {
+                1
+            };
+            table.step(index).unwrap();
+        }
+
+        // Nobody plays seat 0 once the hero moves to seat 3
+        let error = SelfPlayTable::new(4, 0.5, 1.0, 50.0, 9, 10, Some(pool), 3, None);
+        assert!(error.is_err());
+    }
+
+    #[test]
+    fn curriculum_draws_every_hand() {
+        assert!(Curriculum::checked(Some((0.5, 10.0)), false, Vec::new(), None, false).is_err());
+        assert!(Curriculum::checked(None, false, vec![(2.0, 1.0)], None, false).is_err());
+        assert!(Curriculum::checked(None, false, vec![(0.5, 1.0)], Some(vec![]), false).is_err());
+
+        let blinds = vec![(0.5, 1.0), (1.0, 2.0)];
+        let curriculum = Curriculum::checked(Some((10.0, 200.0)), false, blinds, None, true);
+        let table =
+            SelfPlayTable::new(6, 0.5, 1.0, 100.0, 5, 10, None, 0, curriculum.ok()).unwrap();
+        let mut buttons = Vec::new();
+        let mut big_blinds = Vec::new();
+        for hand_index in 0..60 {
+            let state = table.deal(hand_index).unwrap();
+            for ps in &state.players_state {
+                let stack_bb = (ps.stake + ps.bet_chips) / state.bb;
+                assert!((10.0..=200.0).contains(&stack_bb));
+            }
+            assert_eq!(state.legal_actions, available_actions(&state));
+            buttons.push(state.button);
+            big_blinds.push(state.bb);
+            // Hands are dealt again the same
+            let again = table.deal(hand_index).unwrap();
+            assert_eq!(
+                format!("{:?}", again.players_state),
+                format!("{:?}", state.players_state)
+            );
+        }
+        buttons.sort();
+        buttons.dedup();
+        assert_eq!(buttons.len(), 6);
+        assert!(big_blinds.contains(&1.0) && big_blinds.contains(&2.0));
+
+        let curriculum = Curriculum::checked(Some((10.0, 200.0)), true, Vec::new(), None, false);
+        let table =
+            SelfPlayTable::new(3, 0.5, 1.0, 100.0, 5, 10, None, 0, curriculum.ok()).unwrap();
+        let state = table.deal(4).unwrap();
+        assert_eq!(state.button, 1);
+        let stacks: Vec<f64> = state
+            .players_state
+            .iter()
+            .map(|ps| ps.stake + ps.bet_chips)
+            .collect();
+        assert!(stacks.iter().all(|&stack| (stack - stacks[0]).abs() < 1e-9));
+    }
+}
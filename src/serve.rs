@@ -0,0 +1,496 @@
+// serve.rs - Running the WebSocket table server from Python
+use crate::account_data::AccountDeletion;
+use crate::bots::{BotPlayer, BotSettings, PyBot};
+use crate::game_server::{GameConfig, GameServer};
+use crate::hybrid_agent::HybridAgent;
+use crate::mcts::MctsAgent;
+use crate::rake::TableRake;
+use crate::runtime::{self, Runtime, TcpListener};
+use crate::state::action::{Action, DefaultPolicy};
+use crate::state::view::StateView;
+use crate::websocket_server::WebSocketServer;
+use pyo3::exceptions::{PyOSError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::{mpsc, Arc, Mutex, OnceLock};
+use std::thread::JoinHandle;
+use tokio::sync::{mpsc as channel, oneshot, RwLock};
+use tracing::error;
+
+/// A table server running on a background thread, stopped by `stop` or when dropped
+#[pyclass]
+pub struct ServerHandle {
+    #[pyo3(get)]
+    pub host: String,
+
+    /// Port the server listens on, picked by the OS when `serve` was given port 0
+    #[pyo3(get)]
+    pub port: u16,
+
+    game_server: Arc<RwLock<GameServer>>,
+    runtime: runtime::Handle,
+    shutdown: Option<oneshot::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+    /// Players seated by `seat_player`, by seat
+    seats: Mutex<HashMap<u8, String>>,
+    /// Messages for `next_event`, subscribed to on its first call
+    events: OnceLock<Arc<tokio::sync::Mutex<channel::UnboundedReceiver<String>>>>,
+}
+
+impl ServerHandle {
+    /// The table, to seat bots or inspect the hand from Rust
+    pub fn game_server(&self) -> Arc<RwLock<GameServer>> {
+        self.game_server.clone()
+    }
+
+    fn clock_call(&self, py: Python, pause: bool) -> PyResult<()> {
+        if self.shutdown.is_none() {
+            return Err(PyOSError::new_err("The server is stopped"));
+        }
+        let game_server = self.game_server.clone();
+        py.allow_threads(|| {
+            self.runtime.block_on(async move {
+                let mut game_server = game_server.write().await;
+                let result = if pause {
+                    game_server.pause_clock().await
+                } else {
+                    game_server.resume_clock().await
+                };
+                result.map_err(|e| PyValueError::new_err(e.to_string()))
+            })
+        })
+    }
+
+    /// An asyncio future of the running event loop, resolved with what `future` returns once
+    /// it is done on the server's runtime
+    fn awaitable<F, T>(&self, py: Python, future: F) -> PyResult<PyObject>
+    where
+        F: Future<Output = PyResult<T>> + Send + 'static,
+        T: IntoPy<PyObject> + Send + 'static,
+    {
+        let event_loop = py.import("asyncio")?.call_method0("get_running_loop")?;
+        let py_future: PyObject = event_loop.call_method0("create_future")?.into();
+        let mut resolver = Resolver {
+            event_loop: event_loop.into(),
+            future: Some(py_future.clone_ref(py)),
+        };
+        self.runtime.spawn(async move {
+            let result = future.await;
+            Python::with_gil(|py| resolver.resolve(py, result.map(|value| value.into_py(py))));
+        });
+        Ok(py_future)
+    }
+}
+
+/// Resolves an asyncio future from the server's threads. When the task is dropped unfinished,
+/// because the server stopped, the future fails rather than waiting forever.
+struct Resolver {
+    event_loop: PyObject,
+    future: Option<PyObject>,
+}
+
+impl Resolver {
+    fn resolve(&mut self, py: Python, result: PyResult<PyObject>) {
+        let Some(future) = self.future.take() else {
+            return;
+        };
+        let (method, value) = match result {
+            Ok(value) => ("set_result", value),
+            Err(e) => ("set_exception", e.into_value(py).into_py(py)),
+        };
+        // Fails when the event loop is closed, with no one left to tell
+        let _ = wrap_pyfunction!(resolve_future, py).and_then(|resolve| {
+            self.event_loop.call_method1(
+                py,
+                "call_soon_threadsafe",
+                (resolve, future, method, value),
+            )
+        });
+    }
+}
+
+impl Drop for Resolver {
+    fn drop(&mut self) {
+        if self.future.is_some() {
+            Python::with_gil(|py| {
+                self.resolve(py, Err(PyOSError::new_err("The server is stopped")));
+            });
+        }
+    }
+}
+
+/// Run by the event loop: sets the result of `future` unless it was cancelled meanwhile
+#[pyfunction]
+fn resolve_future(future: &PyAny, method: &str, value: PyObject) -> PyResult<()> {
+    if !future.call_method0("done")?.is_true()? {
+        future.call_method1(method, (value,))?;
+    }
+    Ok(())
+}
+
+/// A JSON message, handed to Python as parsed by `json.loads`
+struct Json(String);
+
+impl IntoPy<PyObject> for Json {
+    fn into_py(self, py: Python) -> PyObject {
+        py.import("json")
+            .and_then(|json| json.call_method1("loads", (self.0,)))
+            .map_or_else(|_| py.None(), Into::into)
+    }
+}
+
+/// The bot playing `policy` in Rust, when it is one of the agents of the crate
+fn rust_bot(py: Python, policy: &PyObject) -> Option<Arc<dyn BotPlayer>> {
+    if let Ok(agent) = policy.extract::<HybridAgent>(py) {
+        return Some(Arc::new(agent));
+    }
+    if let Ok(agent) = policy.extract::<MctsAgent>(py) {
+        return Some(Arc::new(agent));
+    }
+    #[cfg(feature = "onnx")]
+    if let Ok(policy) = policy.extract::<crate::onnx_policy::OnnxPolicy>(py) {
+        return Some(Arc::new(policy));
+    }
+    None
+}
+
+#[pymethods]
+impl ServerHandle {
+    /// URL clients connect to
+    #[getter]
+    pub fn url(&self) -> String {
+        format!("ws://{}:{}", self.host, self.port)
+    }
+
+    #[getter]
+    pub fn running(&self) -> bool {
+        self.thread.as_ref().is_some_and(|t| !t.is_finished())
+    }
+
+    /// Seat a bot played by `policy(state, player) -> Action`. The server calls it on the bot's
+    /// turns, after `think_time` seconds, with the cards the bot cannot see dealt at random.
+    /// When it takes longer than `deadline` seconds or raises, `fallback` is played for it. A
+    /// `HybridAgent`, an `MctsAgent` or an `OnnxPolicy` is played in Rust, without taking the
+    /// GIL.
+    #[pyo3(signature = (seat, policy, name=None, think_time=0.0, deadline=None, fallback=DefaultPolicy::CheckElseFold))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_bot(
+        &self,
+        py: Python,
+        seat: u8,
+        policy: PyObject,
+        name: Option<String>,
+        think_time: f64,
+        deadline: Option<f64>,
+        fallback: DefaultPolicy,
+    ) -> PyResult<()> {
+        if self.shutdown.is_none() {
+            return Err(PyOSError::new_err("The server is stopped"));
+        }
+        let settings =
+            BotSettings::checked(think_time, deadline, fallback).map_err(PyValueError::new_err)?;
+        let name = name.unwrap_or_else(|| format!("Bot {}", seat));
+        let bot = rust_bot(py, &policy).unwrap_or_else(|| Arc::new(PyBot::new(policy)));
+        let game_server = self.game_server.clone();
+        // Bots already seated may need the GIL to finish their turn
+        py.allow_threads(|| {
+            self.runtime.block_on(async move {
+                game_server
+                    .write()
+                    .await
+                    .seat_bot(&name, seat, bot, settings)
+                    .await
+                    .map(|_| ())
+                    .map_err(|e| PyOSError::new_err(e.to_string()))
+            })
+        })
+    }
+
+    /// Seat a player played from Python with `act`, e.g. by asyncio code reacting to
+    /// `next_event`
+    #[pyo3(signature = (seat, name=None))]
+    pub fn seat_player(&self, py: Python, seat: u8, name: Option<String>) -> PyResult<()> {
+        if self.shutdown.is_none() {
+            return Err(PyOSError::new_err("The server is stopped"));
+        }
+        let name = name.unwrap_or_else(|| format!("Player {}", seat));
+        let game_server = self.game_server.clone();
+        let player_id = py.allow_threads(|| {
+            self.runtime.block_on(async move {
+                game_server
+                    .write()
+                    .await
+                    .seat_external(&name, seat)
+                    .await
+                    .map_err(|e| PyValueError::new_err(e.to_string()))
+            })
+        })?;
+        self.seats.lock().unwrap().insert(seat, player_id);
+        Ok(())
+    }
+
+    /// Deal a hand, as a client sending `startGame` does
+    pub fn start_game(&self, py: Python) -> PyResult<()> {
+        if self.shutdown.is_none() {
+            return Err(PyOSError::new_err("The server is stopped"));
+        }
+        let game_server = self.game_server.clone();
+        py.allow_threads(|| {
+            self.runtime.block_on(async move {
+                game_server
+                    .write()
+                    .await
+                    .start_game()
+                    .await
+                    .map_err(|e| PyValueError::new_err(e.to_string()))
+            })
+        })
+    }
+
+    /// Awaitable playing `action` for the player seated at `seat` by `seat_player`. Raises
+    /// `ValueError` when the action is refused, e.g. out of turn or a raise out of range.
+    pub fn act(&self, py: Python, seat: u8, action: Action) -> PyResult<PyObject> {
+        if self.shutdown.is_none() {
+            return Err(PyOSError::new_err("The server is stopped"));
+        }
+        let player_id = self.seats.lock().unwrap().get(&seat).cloned();
+        let player_id = player_id.ok_or_else(|| {
+            PyValueError::new_err(format!("No player was seated at {} by seat_player", seat))
+        })?;
+        let game_server = self.game_server.clone();
+        self.awaitable(py, async move {
+            game_server
+                .write()
+                .await
+                .act(&player_id, action)
+                .await
+                .map_err(|e| PyValueError::new_err(e.to_string()))
+        })
+    }
+
+    /// Awaitable of the next message the clients at the table are sent, as a dict with its
+    /// `messageType` and `data`: `gameState`, `onmove`, `actionClosed`, `potUpdate`,
+    /// `tournamentClock` or `handWinnings`. The messages are queued from the first call on,
+    /// until read.
+    pub fn next_event(&self, py: Python) -> PyResult<PyObject> {
+        if self.shutdown.is_none() {
+            return Err(PyOSError::new_err("The server is stopped"));
+        }
+        let events = self
+            .events
+            .get_or_init(|| {
+                let (sender, receiver) = channel::unbounded_channel();
+                let game_server = self.game_server.clone();
+                py.allow_threads(|| {
+                    self.runtime.block_on(async move {
+                        game_server.write().await.subscribe(Arc::new(move |event| {
+                            let message = event.broadcast();
+                            if let Some(json) = message.and_then(|m| serde_json::to_string(&m).ok())
+                            {
+                                let _ = sender.send(json);
+                            }
+                        }));
+                    })
+                });
+                Arc::new(tokio::sync::Mutex::new(receiver))
+            })
+            .clone();
+        self.awaitable(py, async move {
+            let message = events.lock().await.recv().await;
+            message
+                .map(Json)
+                .ok_or_else(|| PyOSError::new_err("The server is stopped"))
+        })
+    }
+
+    /// Stop the clock of a tournament table: no hand is dealt and the action timers stop until
+    /// `resume_clock`
+    pub fn pause_clock(&self, py: Python) -> PyResult<()> {
+        self.clock_call(py, true)
+    }
+
+    pub fn resume_clock(&self, py: Python) -> PyResult<()> {
+        self.clock_call(py, false)
+    }
+
+    /// ZIP file of the PokerStars hand histories of the account registered under `name`, one
+    /// file per hand, as downloaded by clients with `exportHands`
+    pub fn export_hands(&self, py: Python, name: &str) -> PyResult<PyObject> {
+        if self.shutdown.is_none() {
+            return Err(PyOSError::new_err("The server is stopped"));
+        }
+        let game_server = self.game_server.clone();
+        let (zip, _) = py.allow_threads(|| {
+            self.runtime.block_on(async move {
+                let export = game_server.read().await.export_hands_zip(name);
+                export.await.map_err(PyOSError::new_err)
+            })
+        })?;
+        Ok(PyBytes::new(py, &zip).into())
+    }
+
+    /// ZIP file of everything the server stores about the account `name`: its lifetime
+    /// stats, hand histories and session recordings
+    pub fn export_account(&self, py: Python, name: &str) -> PyResult<PyObject> {
+        if self.shutdown.is_none() {
+            return Err(PyOSError::new_err("The server is stopped"));
+        }
+        let game_server = self.game_server.clone();
+        let zip = py.allow_threads(|| {
+            self.runtime.block_on(async move {
+                let export = game_server.read().await.export_account(name);
+                export.await.map_err(PyOSError::new_err)
+            })
+        })?;
+        Ok(PyBytes::new(py, &zip).into())
+    }
+
+    /// Delete everything the server stores about the account `name`, which must not be at the
+    /// table. Its name goes by a pseudonym in the hands of the other players.
+    pub fn delete_account(&self, py: Python, name: &str) -> PyResult<AccountDeletion> {
+        if self.shutdown.is_none() {
+            return Err(PyOSError::new_err("The server is stopped"));
+        }
+        let game_server = self.game_server.clone();
+        py.allow_threads(|| {
+            self.runtime.block_on(async move {
+                let deletion = game_server.read().await.delete_account(name);
+                deletion.await.map_err(PyOSError::new_err)
+            })
+        })
+    }
+
+    /// Rake and jackpot drops the table took since the server started
+    pub fn table_rake(&self, py: Python) -> PyResult<TableRake> {
+        if self.shutdown.is_none() {
+            return Err(PyOSError::new_err("The server is stopped"));
+        }
+        let game_server = self.game_server.clone();
+        Ok(py.allow_threads(|| {
+            self.runtime
+                .block_on(async move { game_server.read().await.table_rake() })
+        }))
+    }
+
+    /// Call `callback(view)` with a `StateView` of the hand every time the clients are sent the
+    /// game state. Callbacks run in order on a thread of their own, so a slow callback does
+    /// not hold up the table.
+    pub fn add_observer(&self, py: Python, callback: PyObject) -> PyResult<()> {
+        if self.shutdown.is_none() {
+            return Err(PyOSError::new_err("The server is stopped"));
+        }
+        let (sender, receiver) = mpsc::channel::<StateView>();
+        std::thread::spawn(move || {
+            // Ends with the server, which drops the sender
+            for view in receiver {
+                Python::with_gil(|py| {
+                    if let Err(e) = callback.call1(py, (view,)) {
+                        error!("Observer failed: {}", e);
+                    }
+                });
+            }
+        });
+
+        let game_server = self.game_server.clone();
+        py.allow_threads(|| {
+            self.runtime.block_on(async move {
+                game_server
+                    .write()
+                    .await
+                    .add_observer(Arc::new(move |view| {
+                        let _ = sender.send(view);
+                    }));
+            })
+        });
+        Ok(())
+    }
+
+    /// Close the server and wait for its thread. Safe to call more than once.
+    pub fn stop(&mut self, py: Python) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+        if let Some(thread) = self.thread.take() {
+            py.allow_threads(|| {
+                let _ = thread.join();
+            });
+        }
+    }
+
+    pub fn __enter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+
+    pub fn __exit__(
+        &mut self,
+        py: Python,
+        _exc_type: PyObject,
+        _exc_value: PyObject,
+        _traceback: PyObject,
+    ) {
+        self.stop(py);
+    }
+
+    pub fn __repr__(&self) -> String {
+        format!("ServerHandle({})", self.url())
+    }
+}
+
+impl Drop for ServerHandle {
+    fn drop(&mut self) {
+        // The thread exits on its own once the runtime is shut down
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+/// Start a table server on a background thread with its own runtime and return
+/// once it is listening. Port 0 picks a free port, see `ServerHandle.port`.
+#[pyfunction]
+#[pyo3(signature = (config=None, host="127.0.0.1", port=0))]
+pub fn serve(config: Option<GameConfig>, host: &str, port: u16) -> PyResult<ServerHandle> {
+    let addr: SocketAddr = format!("{}:{}", host, port)
+        .parse()
+        .map_err(|e| PyOSError::new_err(format!("Invalid address {}:{}: {}", host, port, e)))?;
+    let runtime = Runtime::new()
+        .map_err(|e| PyOSError::new_err(format!("Failed to start the runtime: {}", e)))?;
+    let listener = runtime
+        .block_on(TcpListener::bind(addr))
+        .map_err(|e| PyOSError::new_err(format!("Failed to listen on {}: {}", addr, e)))?;
+    let port = listener.local_addr()?.port();
+
+    let ws_server = WebSocketServer::new_with_config(config.unwrap_or_default());
+    let game_server = ws_server.game_server();
+    let (shutdown, stopped) = oneshot::channel();
+    let handle = runtime.handle();
+
+    let thread = std::thread::spawn(move || {
+        runtime.block_on(async move {
+            tokio::select! {
+                result = ws_server.serve(listener) => {
+                    if let Err(e) = result {
+                        error!("Server error: {}", e);
+                    }
+                }
+                _ = stopped => {}
+            }
+        });
+        // Dropping the runtime cancels the connection and timeout tasks
+        drop(runtime);
+    });
+
+    Ok(ServerHandle {
+        host: host.to_owned(),
+        port,
+        game_server,
+        runtime: handle,
+        shutdown: Some(shutdown),
+        thread: Some(thread),
+        seats: Mutex::new(HashMap::new()),
+        events: OnceLock::new(),
+    })
+}
@@ -0,0 +1,609 @@
+// session_recording.rs - Recordings of the WebSocket connections of a table, and their replay
+//
+// With `GameConfig.session_recording_dir` set, every connection is written to
+// `<dir>/<session id>/<client id>.jsonl`: one JSON object per line for the connection opening,
+// every message it received and sent, and its closing, each with the time it happened. The
+// messages sent are stored without the hole cards of the other players, so a recording can
+// be attached to a bug report. The replay feeds the messages of every client of a session
+// back through the message handlers at their recorded times, on a table dealing from the same
+// seed, and compares what each client is sent with its recording.
+use crate::events::TableEvent;
+use crate::game_logic::{now, set_simulated_time};
+use crate::game_server::GameConfig;
+use crate::websocket_server::{handle_message, LocalClient, WebSocketServer, TICK};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc::UnboundedReceiver;
+use tracing::warn;
+
+/// Fields drawn at random or read from the clock, which a replay cannot reproduce
+const UNREPRODUCIBLE: [&str; 6] = [
+    "handId",
+    "lastHandId",
+    "sessionId",
+    "path",
+    "secondsRemaining",
+    "nextBreakIn",
+];
+
+/// A line of a recording
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum RecordedEntry {
+    /// The first line: the connection opened at a table dealing from `session_seed`
+    #[serde(rename_all = "camelCase")]
+    Open {
+        time: f64,
+        client_id: String,
+        table_id: String,
+        session_seed: u64,
+    },
+    /// A message from the client, parsed when it is JSON and as sent otherwise
+    Received {
+        time: f64,
+        message: Value,
+    },
+    /// A message to the client, see `redact`
+    Sent {
+        time: f64,
+        message: Value,
+    },
+    Closed {
+        time: f64,
+    },
+}
+
+impl RecordedEntry {
+    pub fn time(&self) -> f64 {
+        match *self {
+            RecordedEntry::Open { time, .. }
+            | RecordedEntry::Received { time, .. }
+            | RecordedEntry::Sent { time, .. }
+            | RecordedEntry::Closed { time } => time,
+        }
+    }
+}
+
+/// Take the hole cards of the other players out of a message to `client_id`. The server
+/// sends them to every client, the client only shows them at the showdown. Reconnect tokens
+/// are secrets and are taken out of every message.
+pub fn redact(message: &mut Value, client_id: &str) {
+    let message_type = message["messageType"]
+        .as_str()
+        .unwrap_or_default()
+        .to_owned();
+    let Some(data) = message.get_mut("data") else {
+        return;
+    };
+    let others = |player: &Value| player["address"].as_str() != Some(client_id);
+    match message_type.as_str() {
+        "gameState" | "gameStateDelta" => {
+            if let Some(players) = data.get_mut("players").and_then(Value::as_object_mut) {
+                for player in players.values_mut().filter(|player| others(player)) {
+                    if let Some(player) = player.as_object_mut() {
+                        player.remove("cards");
+                    }
+                }
+            }
+        }
+        "onmove" if others(data) => data["cards"] = Value::Array(Vec::new()),
+        "registered" | "registerPlayer" => {
+            if let Some(token) = data.get_mut("reconnectToken").filter(|t| !t.is_null()) {
+                *token = Value::String(REDACTED.to_owned());
+            }
+        }
+        _ => {}
+    }
+}
+
+/// What secrets are replaced with in the recordings
+const REDACTED: &str = "redacted";
+
+/// Writes the recording of a connection, every entry as it happens
+#[derive(Clone)]
+pub struct SessionRecorder {
+    client_id: String,
+    file: Arc<Mutex<File>>,
+}
+
+impl SessionRecorder {
+    /// Start `<dir>/<session_id>/<client_id>.jsonl`
+    pub fn open(
+        dir: &Path,
+        session_id: &str,
+        client_id: &str,
+        table_id: &str,
+        session_seed: u64,
+    ) -> std::io::Result<Self> {
+        let dir = dir.join(session_id);
+        std::fs::create_dir_all(&dir)?;
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join(format!("{}.jsonl", client_id)))?;
+        let recorder = Self {
+            client_id: client_id.to_owned(),
+            file: Arc::new(Mutex::new(file)),
+        };
+        recorder.write(&RecordedEntry::Open {
+            time: now(),
+            client_id: client_id.to_owned(),
+            table_id: table_id.to_owned(),
+            session_seed,
+        });
+        Ok(recorder)
+    }
+
+    pub fn received(&self, text: &str) {
+        let mut message =
+            serde_json::from_str(text).unwrap_or_else(|_| Value::String(text.to_owned()));
+        redact(&mut message, &self.client_id);
+        self.write(&RecordedEntry::Received {
+            time: now(),
+            message,
+        });
+    }
+
+    pub fn sent(&self, text: &str) {
+        let Ok(mut message) = serde_json::from_str(text) else {
+            return;
+        };
+        redact(&mut message, &self.client_id);
+        self.write(&RecordedEntry::Sent {
+            time: now(),
+            message,
+        });
+    }
+
+    pub fn closed(&self) {
+        self.write(&RecordedEntry::Closed { time: now() });
+    }
+
+    /// A line at a time, so a recording cut short by a crash is still readable
+    fn write(&self, entry: &RecordedEntry) {
+        let Ok(line) = serde_json::to_string(entry) else {
+            return;
+        };
+        let mut file = self.file.lock().unwrap();
+        if let Err(e) = writeln!(file, "{}", line) {
+            warn!("Failed to record the session of {}: {}", self.client_id, e);
+        }
+    }
+}
+
+/// The recording of a connection, read back
+#[derive(Debug, Clone)]
+pub struct Recording {
+    pub client_id: String,
+    pub table_id: String,
+    pub session_seed: u64,
+    pub entries: Vec<RecordedEntry>,
+}
+
+impl Recording {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut entries = Vec::new();
+        for line in BufReader::new(File::open(path)?).lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                entries.push(serde_json::from_str::<RecordedEntry>(&line)?);
+            }
+        }
+        match entries.first() {
+            Some(RecordedEntry::Open {
+                client_id,
+                table_id,
+                session_seed,
+                ..
+            }) => Ok(Self {
+                client_id: client_id.clone(),
+                table_id: table_id.clone(),
+                session_seed: *session_seed,
+                entries,
+            }),
+            _ => Err(format!("{} does not start with the connection", path.display()).into()),
+        }
+    }
+
+    /// Every recording of the session in `dir`
+    pub fn load_session(dir: &Path) -> Result<Vec<Self>, Box<dyn std::error::Error>> {
+        let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<_, _>>()?;
+        paths.retain(|path| path.extension().is_some_and(|ext| ext == "jsonl"));
+        paths.sort();
+        paths.iter().map(|path| Self::load(path)).collect()
+    }
+
+    fn sent(&self) -> Vec<Value> {
+        self.entries
+            .iter()
+            .filter_map(|entry| match entry {
+                RecordedEntry::Sent { message, .. } => Some(message.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Whether the client of the recording at `path` registered as `account`. Lines that do not
+/// parse, like the last one of a recording cut short, are skipped.
+fn registered_as(path: &Path, account: &str) -> std::io::Result<bool> {
+    for line in BufReader::new(File::open(path)?).lines() {
+        if let Ok(RecordedEntry::Received { message, .. }) = serde_json::from_str(&line?) {
+            if message["messageType"] == "registerPlayer" && message["data"]["name"] == account {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
+
+/// Recordings under `dir`, `<session id>/<client id>.jsonl`, of the connections that
+/// registered as `account`
+pub fn account_recordings(
+    dir: &Path,
+    account: &str,
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let sessions = match std::fs::read_dir(dir) {
+        Ok(sessions) => sessions,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut paths = Vec::new();
+    for session in sessions {
+        let session = session?.path();
+        if !session.is_dir() {
+            continue;
+        }
+        for entry in std::fs::read_dir(&session)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "jsonl") && registered_as(&path, account)?
+            {
+                paths.push(path);
+            }
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// The first message a replayed client was sent differently, counted among the messages of
+/// its type
+#[derive(Debug, Clone, PartialEq)]
+pub struct Divergence {
+    pub message_type: String,
+    pub index: usize,
+    /// `None` when the replay sent more messages of the type
+    pub recorded: Option<Value>,
+    /// `None` when the replay sent fewer
+    pub replayed: Option<Value>,
+}
+
+/// How the replay of a connection compares with its recording
+#[derive(Debug, Clone)]
+pub struct ClientReplay {
+    pub client_id: String,
+    pub recorded: usize,
+    /// What the client was sent in the replay, without the hole cards of the other players
+    pub replayed: Vec<Value>,
+    /// `None` when the client was sent the same messages
+    pub divergence: Option<Divergence>,
+}
+
+/// Replay the session recorded in `dir` at a table set up with `config`, which should be the
+/// config of the recorded server: its seed and table id are taken from the recording, and the
+/// stats, hand histories, recordings and webhooks are left out. The clock is simulated on the
+/// calling thread and moved forward in server ticks, so timeouts play out as recorded without
+/// waiting for them.
+pub async fn replay(
+    dir: &Path,
+    mut config: GameConfig,
+) -> Result<Vec<ClientReplay>, Box<dyn std::error::Error>> {
+    let recordings = Recording::load_session(dir)?;
+    let Some(first) = recordings.first() else {
+        return Err(format!("No recordings in {}", dir.display()).into());
+    };
+    config.session_seed = Some(first.session_seed);
+    config.table_id = Some(first.table_id.clone());
+    config.stats_file = None;
+    config.hand_history_dir = None;
+    config.session_recording_dir = None;
+    config.webhooks = Vec::new();
+
+    // What the clients did, in the order it happened
+    let mut timeline: Vec<(&str, &RecordedEntry)> = recordings
+        .iter()
+        .flat_map(|recording| {
+            recording
+                .entries
+                .iter()
+                .filter(|entry| !matches!(entry, RecordedEntry::Sent { .. }))
+                .map(|entry| (recording.client_id.as_str(), entry))
+        })
+        .collect();
+    timeline.sort_by(|a, b| a.1.time().total_cmp(&b.1.time()));
+
+    let server = WebSocketServer::new_with_config(config);
+    let game_server = server.game_server();
+    let mut events = server.follow_table().await;
+    let mut clients: HashMap<&str, LocalClient> = HashMap::new();
+    let mut replayed: HashMap<&str, Vec<Value>> = HashMap::new();
+    let mut next_tick = timeline.first().map_or(0.0, |(_, entry)| entry.time());
+
+    for (client_id, entry) in timeline {
+        while next_tick <= entry.time() {
+            set_simulated_time(Some(next_tick));
+            game_server.write().await.tick().await;
+            deliver_events(&server, &mut events, &mut clients, &mut replayed).await;
+            next_tick += TICK.as_secs_f64();
+        }
+        set_simulated_time(Some(entry.time()));
+
+        match entry {
+            RecordedEntry::Open { .. } => {
+                clients.insert(client_id, server.local_client(client_id).await);
+            }
+            RecordedEntry::Received { message, .. } => {
+                let text = match message {
+                    Value::String(text) => text.clone(),
+                    message => {
+                        // The replayed server hands out reconnect tokens of its own
+                        let mut message = message.clone();
+                        if message["messageType"] == "registerPlayer"
+                            && message["data"]["reconnectToken"].is_string()
+                        {
+                            let name = message["data"]["name"].as_str().unwrap_or_default();
+                            let token = game_server.read().await.reconnect_token(name);
+                            message["data"]["reconnectToken"] = token.into();
+                        }
+                        message.to_string()
+                    }
+                };
+                if let Some(client) = clients.get(client_id) {
+                    // Rejected messages are answered with an error, as they were
+                    let _ =
+                        handle_message(&text, client_id, &game_server, &client.capabilities).await;
+                }
+            }
+            RecordedEntry::Closed { .. } => {
+                // The connection is gone before the table hears of it
+                clients.remove(client_id);
+                server.forget_client(client_id).await;
+                game_server
+                    .write()
+                    .await
+                    .player_disconnected(client_id)
+                    .await;
+            }
+            RecordedEntry::Sent { .. } => {}
+        }
+        deliver_events(&server, &mut events, &mut clients, &mut replayed).await;
+    }
+    set_simulated_time(None);
+
+    Ok(recordings
+        .iter()
+        .map(|recording| {
+            let recorded = recording.sent();
+            let replayed = replayed
+                .remove(recording.client_id.as_str())
+                .unwrap_or_default();
+            ClientReplay {
+                client_id: recording.client_id.clone(),
+                recorded: recorded.len(),
+                divergence: divergence(&recorded, &replayed),
+                replayed,
+            }
+        })
+        .collect())
+}
+
+/// Deliver the events of the table so far, and take what every client was sent
+async fn deliver_events<'a>(
+    server: &WebSocketServer,
+    events: &mut UnboundedReceiver<TableEvent>,
+    clients: &mut HashMap<&'a str, LocalClient>,
+    replayed: &mut HashMap<&'a str, Vec<Value>>,
+) {
+    while let Ok(event) = events.try_recv() {
+        server.deliver(event).await;
+        for (&client_id, client) in clients.iter_mut() {
+            for text in client.drain() {
+                if let Ok(mut message) = serde_json::from_str(&text) {
+                    redact(&mut message, client_id);
+                    replayed.entry(client_id).or_default().push(message);
+                }
+            }
+        }
+    }
+}
+
+/// Compare the messages of each type in turn, as the direct messages and the broadcasts of a
+/// connection may be sent in either order when they come close together
+fn divergence(recorded: &[Value], replayed: &[Value]) -> Option<Divergence> {
+    let message_type = |message: &Value| {
+        message["messageType"]
+            .as_str()
+            .unwrap_or_default()
+            .to_owned()
+    };
+    let mut types: Vec<String> = Vec::new();
+    for message in recorded.iter().chain(replayed) {
+        let message_type = message_type(message);
+        if !types.contains(&message_type) {
+            types.push(message_type);
+        }
+    }
+    types.into_iter().find_map(|wanted| {
+        let of_type = |messages: &[Value]| -> Vec<Value> {
+            messages
+                .iter()
+                .filter(|message| message_type(message) == wanted)
+                .map(reproducible)
+                .collect()
+        };
+        let (recorded, replayed) = (of_type(recorded), of_type(replayed));
+        let index = (0..recorded.len().max(replayed.len()))
+            .find(|&i| recorded.get(i) != replayed.get(i))?;
+        Some(Divergence {
+            message_type: wanted,
+            index,
+            recorded: recorded.get(index).cloned(),
+            replayed: replayed.get(index).cloned(),
+        })
+    })
+}
+
+/// `message` without its `UNREPRODUCIBLE` fields
+fn reproducible(message: &Value) -> Value {
+    match message {
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .filter(|(key, _)| !UNREPRODUCIBLE.contains(&key.as_str()))
+                .map(|(key, value)| (key.clone(), reproducible(value)))
+                .collect(),
+        ),
+        Value::Array(values) => Value::Array(values.iter().map(reproducible).collect()),
+        value => value.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const START: f64 = 1_700_000_000.0;
+
+    fn write(dir: &Path, client_id: &str, entries: &[RecordedEntry]) {
+        let lines: Vec<String> = entries
+            .iter()
+            .map(|entry| serde_json::to_string(entry).unwrap())
+            .collect();
+        std::fs::write(dir.join(format!("{}.jsonl", client_id)), lines.join("\n")).unwrap();
+    }
+
+    #[test]
+    fn only_the_players_own_cards_are_recorded() {
+        let cards = json!([{ "rank": 12, "suit": 0, "index": 51 }]);
+        let mut state = json!({ "messageType": "gameState", "data": { "players": {
+            "1": { "address": "alice", "cards": cards },
+            "2": { "address": "bob", "cards": cards },
+        } } });
+        redact(&mut state, "alice");
+        assert_eq!(state["data"]["players"]["1"]["cards"], cards);
+        assert!(state["data"]["players"]["2"].get("cards").is_none());
+
+        let mut on_move =
+            json!({ "messageType": "onmove", "data": { "address": "bob", "cards": cards } });
+        redact(&mut on_move, "alice");
+        assert_eq!(on_move["data"]["cards"], json!([]));
+        redact(&mut on_move, "bob");
+        assert_eq!(on_move["data"]["cards"], json!([]));
+    }
+
+    #[tokio::test]
+    async fn replay_sends_the_recorded_messages_again() {
+        let dir = std::env::temp_dir().join(format!("pokers_recording_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = GameConfig {
+            session_seed: Some(3),
+            ..Default::default()
+        };
+
+        // Both sit down, alice deals and both try to fold, one of them out of turn
+        let mut sessions: Vec<(&str, Vec<RecordedEntry>)> = Vec::new();
+        for (i, (name, seat)) in [("alice", 1), ("bob", 2)].into_iter().enumerate() {
+            let time = START + i as f64;
+            let mut entries = vec![RecordedEntry::Open {
+                time,
+                client_id: name.to_string(),
+                table_id: "main".to_string(),
+                session_seed: 3,
+            }];
+            for (offset, message) in [
+                json!({ "messageType": "registerPlayer", "data": { "name": name } }),
+                json!({ "messageType": "takeSeat", "data": { "seat": seat } }),
+            ]
+            .into_iter()
+            .enumerate()
+            {
+                entries.push(RecordedEntry::Received {
+                    time: time + 0.1 * (offset + 1) as f64,
+                    message,
+                });
+            }
+            sessions.push((name, entries));
+        }
+        sessions[0].1.push(RecordedEntry::Received {
+            time: START + 5.0,
+            message: json!({ "messageType": "startGame", "data": {} }),
+        });
+        for (i, (_, entries)) in sessions.iter_mut().enumerate() {
+            entries.push(RecordedEntry::Received {
+                time: START + 6.0 + i as f64,
+                message: json!({ "messageType": "fold", "data": {} }),
+            });
+        }
+        for (client_id, entries) in &sessions {
+            write(&dir, client_id, entries);
+        }
+
+        // Nothing sent was recorded
+        let first = replay(&dir, config.clone()).await.unwrap();
+        assert!(first.iter().all(|client| client.divergence.is_some()));
+
+        // With what the replay sent recorded, the next replay sends the same
+        for (client_id, entries) in &mut sessions {
+            let replayed = first.iter().find(|c| c.client_id == *client_id).unwrap();
+            assert!(replayed
+                .replayed
+                .iter()
+                .any(|m| m["messageType"] == "handWinnings"));
+            entries.extend(replayed.replayed.iter().map(|message| RecordedEntry::Sent {
+                time: START + 10.0,
+                message: message.clone(),
+            }));
+            write(&dir, client_id, entries);
+        }
+        let second = replay(&dir, config.clone()).await.unwrap();
+        for client in &second {
+            assert_eq!(client.divergence, None, "{}", client.client_id);
+            assert_eq!(client.recorded, client.replayed.len());
+        }
+        // The out of turn fold was answered with an error, again
+        assert!(second
+            .iter()
+            .any(|client| client.replayed.iter().any(|m| m["messageType"] == "error")));
+
+        // A pot that was not that big
+        let (client_id, entries) = &mut sessions[0];
+        let mut states = entries.iter_mut().filter_map(|entry| match entry {
+            RecordedEntry::Sent { message, .. } if message["messageType"] == "gameState" => {
+                Some(message)
+            }
+            _ => None,
+        });
+        let state = states.nth(1).unwrap();
+        state["data"]["pot"] = json!(1_000_000.0);
+        write(&dir, client_id, entries);
+        let third = replay(&dir, config).await.unwrap();
+        let divergence = third[0].divergence.clone().unwrap();
+        assert_eq!(divergence.message_type, "gameState");
+        assert_eq!(divergence.index, 1);
+        assert_eq!(
+            divergence.recorded.unwrap()["data"]["pot"],
+            json!(1_000_000.0)
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,1198 @@
+// simulation.rs - In-process harness driving the table server through scripted clients
+//
+// The clients send their messages through the same dispatch as the WebSocket server and
+// receive the events the server would send over their socket, without any network. Time is
+// simulated: the clock only moves when a test calls `advance`, so timeouts and disconnect
+// grace periods play out instantly and the same script always deals the same hands.
+use crate::events::TableEvent;
+use crate::game_logic::set_simulated_time;
+use crate::game_server::{GameConfig, GameServer};
+use crate::websocket_server::{
+    handle_message, ClientCapabilities, GameStateMessage, OnMoveMessage, WebSocketMessage,
+};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{watch, RwLock};
+
+/// Simulated time the session starts at
+const START: f64 = 1_700_000_000.0;
+/// Seed of the session when the config leaves it unset, so every run deals the same hands
+const SEED: u64 = 42;
+
+#[derive(Default)]
+struct Received {
+    /// Events each open connection was sent, by client id
+    inboxes: HashMap<String, Vec<TableEvent>>,
+    state: Option<GameStateMessage>,
+    on_move: Option<OnMoveMessage>,
+}
+
+impl Received {
+    /// Deliver `event` like the WebSocket server: private events to their player, the rest
+    /// to every connection
+    fn route(&mut self, event: &TableEvent) {
+        let recipient = match event {
+            TableEvent::GameState { message, .. } => {
+                self.state = Some(message.clone());
+                None
+            }
+            TableEvent::OnMove(message) => {
+                self.on_move = Some(message.clone());
+                None
+            }
+            TableEvent::HandWinnings(_) => {
+                self.on_move = None;
+                None
+            }
+            TableEvent::HandStrength { player_id, .. }
+            | TableEvent::InsuranceOffer { player_id, .. }
+            | TableEvent::InsuranceResult { player_id, .. }
+            | TableEvent::PlayerStats { player_id, .. }
+            | TableEvent::Registered { player_id, .. }
+            | TableEvent::HandsExport { player_id, .. }
+            | TableEvent::Error { player_id, .. } => Some(player_id),
+            TableEvent::ActionClosed(_)
+            | TableEvent::PotUpdate(_)
+            | TableEvent::TournamentClock(_)
+            | TableEvent::Knockout(_) => None,
+            TableEvent::PlayerJoined { .. }
+            | TableEvent::PlayerLeft { .. }
+            | TableEvent::HandComplete(_) => return,
+        };
+        for (client_id, inbox) in self.inboxes.iter_mut() {
+            if recipient.is_none_or(|player_id| player_id == client_id) {
+                inbox.push(event.clone());
+            }
+        }
+    }
+}
+
+pub(crate) struct Simulation {
+    game: Arc<RwLock<GameServer>>,
+    received: Arc<Mutex<Received>>,
+    capabilities: watch::Sender<ClientCapabilities>,
+    connections: usize,
+    time: f64,
+}
+
+impl Simulation {
+    pub(crate) fn new(mut config: GameConfig) -> Self {
+        config.session_seed.get_or_insert(SEED);
+        set_simulated_time(Some(START));
+
+        let received = Arc::new(Mutex::new(Received::default()));
+        let mut server = GameServer::new(Some(config));
+        let router = received.clone();
+        server.subscribe(Arc::new(move |event| router.lock().unwrap().route(event)));
+        let game = Arc::new(RwLock::new(server));
+        game.try_write().unwrap().attach(Arc::downgrade(&game));
+
+        Self {
+            game,
+            received,
+            capabilities: watch::channel(ClientCapabilities::default()).0,
+            connections: 0,
+            time: START,
+        }
+    }
+
+    /// Open a connection, returns its client id
+    pub(crate) fn connect(&mut self) -> String {
+        self.connections += 1;
+        let client_id = format!("client-{}", self.connections);
+        self.received
+            .lock()
+            .unwrap()
+            .inboxes
+            .insert(client_id.clone(), Vec::new());
+        client_id
+    }
+
+    /// Open a connection, register as `name` and take `seat`
+    pub(crate) async fn join(&mut self, name: &str, seat: u8) -> String {
+        let client_id = self.connect();
+        self.send(&client_id, "registerPlayer", json!({ "name": name }))
+            .await
+            .unwrap();
+        self.send(&client_id, "takeSeat", json!({ "seat": seat }))
+            .await
+            .unwrap();
+        client_id
+    }
+
+    /// Send a message from `client_id`, as the WebSocket server would dispatch it
+    pub(crate) async fn send(
+        &self,
+        client_id: &str,
+        message_type: &str,
+        data: Value,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let message = WebSocketMessage {
+            message_type: message_type.to_string(),
+            data,
+        };
+        let text = serde_json::to_string(&message)?;
+        handle_message(&text, client_id, &self.game, &self.capabilities).await
+    }
+
+    /// Close the connection of `client_id`
+    pub(crate) async fn disconnect(&self, client_id: &str) {
+        self.received.lock().unwrap().inboxes.remove(client_id);
+        self.game.write().await.player_disconnected(client_id).await;
+    }
+
+    /// Move the clock `seconds` forward and let the server catch up, like its periodic tick
+    pub(crate) async fn advance(&mut self, seconds: f64) {
+        self.time += seconds;
+        set_simulated_time(Some(self.time));
+        self.game.write().await.tick().await;
+    }
+
+    /// Take the events `client_id` was sent since last asked
+    pub(crate) fn received(&self, client_id: &str) -> Vec<TableEvent> {
+        self.received
+            .lock()
+            .unwrap()
+            .inboxes
+            .get_mut(client_id)
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// The table as last broadcast
+    pub(crate) fn state(&self) -> GameStateMessage {
+        self.received.lock().unwrap().state.clone().unwrap()
+    }
+
+    /// The player to act, `None` between hands
+    pub(crate) fn on_move(&self) -> Option<OnMoveMessage> {
+        self.received.lock().unwrap().on_move.clone()
+    }
+
+    /// Chips of the seated players, by name
+    pub(crate) fn stacks(&self) -> HashMap<String, f64> {
+        self.state()
+            .players
+            .into_values()
+            .map(|player| (player.name, player.chips))
+            .collect()
+    }
+}
+
+impl Drop for Simulation {
+    fn drop(&mut self) {
+        set_simulated_time(None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip_format::{ChipFormat, ChipStack};
+    use crate::game_server::{DisconnectProtection, MissedBlinds};
+    use crate::rake::{JackpotDrop, Rake};
+    use crate::state::action::{Action, ActionEnum};
+    use crate::state::rules::TableRules;
+    use crate::tournament_clock::BlindLevel;
+    use crate::websocket_server::{
+        admin_response, broadcast_text, Broadcast, GameStateDeltaMessage, KnockoutMessage,
+        PlayerInfo, PlayerStatsMessage, PotUpdateMessage,
+    };
+
+    const EPSILON: f64 = 1e-9;
+
+    fn player<'a>(state: &'a GameStateMessage, name: &str) -> &'a PlayerInfo {
+        state.players.values().find(|p| p.name == name).unwrap()
+    }
+
+    #[tokio::test]
+    async fn bomb_pots_are_off_without_an_ante() {
+        let mut sim = Simulation::new(GameConfig {
+            rules: TableRules {
+                bomb_pot_every: Some(1),
+                ..TableRules::default()
+            },
+            ..Default::default()
+        });
+        let alice = sim.join("alice", 1).await;
+        sim.join("bob", 2).await;
+        sim.send(&alice, "startGame", json!({})).await.unwrap();
+
+        // A regular hand with blinds
+        let state = sim.state();
+        assert!(state.game_started);
+        assert!((state.pot - 15.0).abs() < EPSILON);
+        assert!(state.table.big_blind_seat.is_some());
+    }
+
+    /// Wait for the event of `client_id` a background task sends, taking the events before it
+    async fn next_event(
+        sim: &Simulation,
+        client_id: &str,
+        wanted: impl Fn(&TableEvent) -> bool,
+    ) -> TableEvent {
+        for _ in 0..1000 {
+            if let Some(event) = sim.received(client_id).into_iter().find(&wanted) {
+                return event;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+        }
+        panic!("{} was never sent the event", client_id);
+    }
+
+    #[tokio::test]
+    async fn hands_are_exported_in_the_background() {
+        let mut sim = Simulation::new(GameConfig::default());
+        let alice = sim.join("alice", 1).await;
+        let bob = sim.join("bob", 2).await;
+
+        // Nothing to export before the first hand
+        sim.send(&alice, "exportHands", json!({})).await.unwrap();
+        let error = next_event(&sim, &alice, |e| matches!(e, TableEvent::Error { .. })).await;
+        assert!(
+            matches!(error, TableEvent::Error { message, .. } if message.code == "noHandHistories")
+        );
+
+        sim.send(&alice, "startGame", json!({})).await.unwrap();
+        while let Some(on_move) = sim.on_move() {
+            sim.send(&on_move.address, "fold", json!({})).await.unwrap();
+        }
+        sim.send(&alice, "exportHands", json!({})).await.unwrap();
+        let export = next_event(&sim, &alice, |e| {
+            matches!(e, TableEvent::HandsExport { .. })
+        })
+        .await;
+        assert!(matches!(export, TableEvent::HandsExport { hands: 1, .. }));
+        assert!(!sim
+            .received(&bob)
+            .iter()
+            .any(|event| matches!(event, TableEvent::HandsExport { .. })));
+    }
+
+    #[tokio::test]
+    async fn archived_names_need_their_token_in_later_sessions() {
+        let dir = std::env::temp_dir().join(format!("pokers_sim_accounts_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let config = GameConfig {
+            hand_history_dir: Some(dir.to_string_lossy().into_owned()),
+            ..Default::default()
+        };
+        let registered = |events: Vec<TableEvent>| {
+            events.into_iter().find_map(|event| match event {
+                TableEvent::Registered { message, .. } => Some(message.reconnect_token),
+                _ => None,
+            })
+        };
+
+        let mut sim = Simulation::new(config.clone());
+        let alice = sim.join("alice", 1).await;
+        let token = registered(sim.received(&alice)).unwrap();
+        drop(sim);
+
+        // Whoever comes next under the name cannot take the account's hands
+        let mut sim = Simulation::new(config);
+        let impostor = sim.connect();
+        assert!(sim
+            .send(&impostor, "registerPlayer", json!({ "name": "alice" }))
+            .await
+            .is_err());
+        let alice = sim.connect();
+        let register = json!({ "name": "alice", "reconnectToken": token });
+        sim.send(&alice, "registerPlayer", register).await.unwrap();
+        assert_eq!(registered(sim.received(&alice)), Some(token));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn invalid_pseudonymization_days_keep_the_names() {
+        for (days, due) in [(-1.0, false), (f64::NAN, false), (90.0, true)] {
+            let sim = Simulation::new(GameConfig {
+                pseudonymize_after_days: Some(days),
+                ..Default::default()
+            });
+            assert_eq!(sim.game.read().await.tick_due(), due, "{} days", days);
+        }
+    }
+
+    #[tokio::test]
+    async fn action_timeout_checks_or_folds() {
+        let mut sim = Simulation::new(GameConfig {
+            action_timeout: Some(20.0),
+            ..Default::default()
+        });
+        let alice = sim.join("alice", 1).await;
+        sim.join("bob", 2).await;
+        sim.send(&alice, "startGame", json!({})).await.unwrap();
+
+        let first = sim.on_move().unwrap();
+        sim.advance(19.0).await;
+        assert_eq!(sim.on_move().unwrap().address, first.address);
+        assert!(!sim.game.read().await.tick_due());
+        set_simulated_time(Some(sim.time + 1.0));
+        assert!(sim.game.read().await.tick_due());
+
+        // Facing the big blind, the player to act cannot check and folds
+        sim.advance(1.0).await;
+        assert!(sim.on_move().is_none());
+        let stacks = sim.stacks();
+        assert!((stacks.values().sum::<f64>() - 2000.0).abs() < EPSILON);
+        assert!(stacks
+            .values()
+            .any(|&chips| (chips - 1005.0).abs() < EPSILON));
+
+        let winnings = sim
+            .received(&alice)
+            .into_iter()
+            .filter(|event| matches!(event, TableEvent::HandWinnings(_)))
+            .count();
+        assert_eq!(winnings, 1);
+    }
+
+    #[tokio::test]
+    async fn each_street_closes_once() {
+        let mut sim = Simulation::new(GameConfig::default());
+        let alice = sim.join("alice", 1).await;
+        sim.join("bob", 2).await;
+        sim.send(&alice, "startGame", json!({})).await.unwrap();
+        sim.received(&alice);
+
+        // Called and checked down
+        while let Some(on_move) = sim.on_move() {
+            let action = if on_move.can_check { "check" } else { "call" };
+            sim.send(&on_move.address, action, json!({})).await.unwrap();
+        }
+
+        let events = sim.received(&alice);
+        let closed: Vec<(usize, String, f64)> = events
+            .iter()
+            .enumerate()
+            .filter_map(|(index, event)| match event {
+                TableEvent::ActionClosed(message) => {
+                    Some((index, message.stage.clone(), message.pot))
+                }
+                _ => None,
+            })
+            .collect();
+        let stages: Vec<&str> = closed.iter().map(|(_, stage, _)| stage.as_str()).collect();
+        assert_eq!(stages, vec!["Preflop", "Flop", "Turn", "River"]);
+        assert!(closed
+            .iter()
+            .all(|(_, _, pot)| (pot - 20.0).abs() < EPSILON));
+        // The bets are swept in before the clients see the next street
+        for (index, _, _) in closed {
+            assert!(matches!(events[index + 1], TableEvent::GameState { .. }));
+        }
+    }
+
+    #[tokio::test]
+    async fn pot_update_after_every_action() {
+        let mut sim = Simulation::new(GameConfig::default());
+        let alice = sim.join("alice", 1).await;
+        sim.join("bob", 2).await;
+        sim.send(&alice, "startGame", json!({})).await.unwrap();
+        sim.received(&alice);
+
+        let mut actions = 0;
+        while let Some(on_move) = sim.on_move() {
+            let action = if on_move.can_check { "check" } else { "call" };
+            sim.send(&on_move.address, action, json!({})).await.unwrap();
+            actions += 1;
+        }
+
+        let updates: Vec<PotUpdateMessage> = sim
+            .received(&alice)
+            .into_iter()
+            .filter_map(|event| match event {
+                TableEvent::PotUpdate(message) => Some(message),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(updates.len(), actions);
+        for update in &updates {
+            let pots =
+                update.main_pot.amount + update.side_pots.iter().map(|pot| pot.amount).sum::<f64>();
+            let bets: f64 = update.player_bets.values().sum();
+            assert!((pots + bets - update.total).abs() < EPSILON);
+        }
+        let last = updates.last().unwrap();
+        assert!((last.main_pot.amount - 20.0).abs() < EPSILON);
+        assert_eq!(last.main_pot.eligible_seats, vec![1, 2]);
+        assert!(last.side_pots.is_empty());
+    }
+
+    #[tokio::test]
+    async fn amounts_are_broadcast_in_money() {
+        let mut sim = Simulation::new(GameConfig {
+            chip_format: Some(ChipFormat {
+                symbol: "$".to_string(),
+                ..ChipFormat::checked(0.01, vec![1.0, 5.0], Some(2)).unwrap()
+            }),
+            ..Default::default()
+        });
+        let alice = sim.join("alice", 1).await;
+        sim.join("bob", 2).await;
+        sim.send(&alice, "startGame", json!({})).await.unwrap();
+
+        let state = sim.state();
+        assert_eq!(state.pot_text.as_deref(), Some("$0.15"));
+        let alice_chips = player(&state, "alice").chips;
+        let expected = format!("${:.2}", alice_chips / 100.0);
+        assert_eq!(player(&state, "alice").chips_text, Some(expected));
+        let on_move = sim.on_move().unwrap();
+        sim.send(&on_move.address, "call", json!({})).await.unwrap();
+        let update = sim
+            .received(&alice)
+            .into_iter()
+            .rev()
+            .find_map(|event| match event {
+                TableEvent::PotUpdate(message) => Some(message),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(update.total_text.as_deref(), Some("$0.20"));
+        // 10 chips in front of each player, stacked in two 5s
+        let stacks = update.bet_stacks.unwrap();
+        for seat in ["1", "2"] {
+            assert_eq!(
+                stacks[seat],
+                [ChipStack {
+                    denomination: 5.0,
+                    count: 2
+                }]
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn table_layout_shows_the_button_and_blinds() {
+        let mut sim = Simulation::new(GameConfig::default());
+        let alice = sim.join("alice", 1).await;
+        sim.join("bob", 3).await;
+        sim.join("carol", 5).await;
+        let table = sim.state().table;
+        assert_eq!(table.max_seats, 6);
+        assert_eq!(table.seat_order, vec![1, 2, 3, 4, 5, 6]);
+        assert_eq!(table.dealer_seat, None);
+
+        sim.send(&alice, "startGame", json!({})).await.unwrap();
+        let table = sim.state().table;
+        assert_eq!(
+            (
+                table.dealer_seat,
+                table.small_blind_seat,
+                table.big_blind_seat
+            ),
+            (Some(1), Some(3), Some(5))
+        );
+
+        while let Some(on_move) = sim.on_move() {
+            sim.send(&on_move.address, "fold", json!({})).await.unwrap();
+        }
+        let table = sim.state().table;
+        assert_eq!((table.dealer_seat, table.big_blind_seat), (None, None));
+    }
+
+    #[tokio::test]
+    async fn late_players_post_or_wait_for_the_big_blind() {
+        for missed_blinds in [MissedBlinds::PostDead, MissedBlinds::WaitForBigBlind] {
+            let mut sim = Simulation::new(GameConfig {
+                missed_blinds,
+                ..Default::default()
+            });
+            let alice = sim.join("alice", 1).await;
+            sim.join("bob", 3).await;
+            sim.join("carol", 5).await;
+            sim.send(&alice, "startGame", json!({})).await.unwrap();
+            while let Some(on_move) = sim.on_move() {
+                sim.send(&on_move.address, "fold", json!({})).await.unwrap();
+            }
+
+            // Dave sits down after the big blind of the next hand
+            sim.join("dave", 2).await;
+            assert!(player(&sim.state(), "dave").owes_blind);
+            sim.send(&alice, "startGame", json!({})).await.unwrap();
+            let state = sim.state();
+            let table = &state.table;
+            assert_eq!(
+                (table.small_blind_seat, table.big_blind_seat),
+                (Some(5), Some(1))
+            );
+            let dave = player(&state, "dave");
+            let dealt = missed_blinds == MissedBlinds::PostDead;
+            assert_eq!(dave.cards.as_ref().is_some_and(|c| !c.is_empty()), dealt);
+            match missed_blinds {
+                MissedBlinds::PostDead => {
+                    assert!((state.pot - 25.0).abs() < EPSILON);
+                    assert!(dave.in_game && !dave.owes_blind);
+                }
+                MissedBlinds::WaitForBigBlind => {
+                    assert!((state.pot - 15.0).abs() < EPSILON);
+                    assert!(!dave.in_game && dave.owes_blind);
+                }
+            }
+
+            // A player waiting for the big blind has no share of the pot
+            while let Some(on_move) = sim.on_move() {
+                let action = if on_move.can_check { "check" } else { "call" };
+                sim.send(&on_move.address, action, json!({})).await.unwrap();
+            }
+            let winnings = sim
+                .received(&alice)
+                .into_iter()
+                .filter_map(|event| match event {
+                    TableEvent::HandWinnings(message) => Some(message),
+                    _ => None,
+                })
+                .last()
+                .unwrap();
+            assert!(dealt || winnings.winnings.iter().all(|w| w.player_name != "dave"));
+            let total: f64 = sim.stacks().values().sum();
+            assert!((total - 4000.0).abs() < EPSILON);
+        }
+    }
+
+    /// What a low-bandwidth client makes of a `gameStateDelta`: `previous` with the seats
+    /// that changed, keeping the cards the delta leaves out
+    fn apply_delta(previous: &GameStateMessage, delta: GameStateDeltaMessage) -> GameStateMessage {
+        let mut state = previous.clone();
+        for seat in &delta.left_seats {
+            state.players.remove(seat);
+        }
+        for (seat, player) in delta.players {
+            let cards = player
+                .cards
+                .or_else(|| state.players.get(&seat).and_then(|p| p.cards.clone()));
+            state.players.insert(seat, PlayerInfo { cards, ..player });
+        }
+        GameStateMessage {
+            game_started: delta.game_started,
+            hand_id: delta.hand_id,
+            community_cards: delta.community_cards,
+            second_board: delta.second_board,
+            pot: delta.pot,
+            pot_text: delta.pot_text,
+            table: delta.table.unwrap_or(state.table.clone()),
+            tournament_clock: delta.tournament_clock,
+            ..state
+        }
+    }
+
+    /// The `data` of a message the clients are sent
+    fn data<T: serde::de::DeserializeOwned>(text: &str, message_type: &str) -> T {
+        let message: WebSocketMessage = serde_json::from_str(text).unwrap();
+        assert_eq!(message.message_type, message_type);
+        serde_json::from_value(message.data).unwrap()
+    }
+
+    #[tokio::test]
+    async fn deltas_rebuild_every_game_state() {
+        let mut sim = Simulation::new(GameConfig {
+            chip_format: Some(ChipFormat {
+                symbol: "$".to_string(),
+                ..ChipFormat::checked(0.01, vec![1.0, 5.0], Some(2)).unwrap()
+            }),
+            ..Default::default()
+        });
+        let alice = sim.join("alice", 1).await;
+        sim.join("bob", 3).await;
+        sim.join("carol", 5).await;
+        sim.send(&alice, "startGame", json!({})).await.unwrap();
+        while let Some(on_move) = sim.on_move() {
+            let action = if on_move.can_check { "check" } else { "call" };
+            sim.send(&on_move.address, action, json!({})).await.unwrap();
+        }
+
+        let mut states: Vec<GameStateMessage> = sim
+            .received(&alice)
+            .into_iter()
+            .filter_map(|event| match event {
+                TableEvent::GameState { message, .. } => Some(message),
+                _ => None,
+            })
+            .collect();
+        // And a seat emptied
+        let mut left = states.last().unwrap().clone();
+        left.players.remove("5");
+        states.push(left);
+        assert!(states.len() > 10);
+
+        for pair in states.windows(2) {
+            let (previous, state) = (&pair[0], &pair[1]);
+            let json = serde_json::to_string(&state.delta(previous)).unwrap();
+            let rebuilt = apply_delta(previous, serde_json::from_str(&json).unwrap());
+            assert_eq!(
+                serde_json::to_value(&rebuilt).unwrap(),
+                serde_json::to_value(state).unwrap()
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn low_bandwidth_clients_get_deltas_after_a_full_state() {
+        let mut sim = Simulation::new(GameConfig::default());
+        let alice = sim.join("alice", 1).await;
+        sim.join("bob", 3).await;
+        sim.send(&alice, "startGame", json!({})).await.unwrap();
+        let states: Vec<Arc<GameStateMessage>> = sim
+            .received(&alice)
+            .into_iter()
+            .filter_map(|event| match event {
+                TableEvent::GameState { message, .. } => Some(Arc::new(message)),
+                _ => None,
+            })
+            .collect();
+        let broadcast = |state: &Arc<GameStateMessage>| Broadcast::GameState {
+            state: state.clone(),
+            json: serde_json::to_string(&WebSocketMessage {
+                message_type: "gameState".to_string(),
+                data: serde_json::to_value(state.as_ref()).unwrap(),
+            })
+            .unwrap(),
+        };
+
+        let capabilities = json!({ "lowBandwidth": true });
+        sim.send(&alice, "capabilities", capabilities)
+            .await
+            .unwrap();
+        assert!(sim.capabilities.borrow().low_bandwidth);
+        // The game state is not touched by the capabilities
+        assert!(sim.received(&alice).is_empty());
+
+        let mut last_state = None;
+        let full = broadcast_text(broadcast(&states[0]), true, &mut last_state);
+        let first: GameStateMessage = data(&full, "gameState");
+        let delta = broadcast_text(broadcast(&states[1]), true, &mut last_state);
+        let rebuilt = apply_delta(&first, data(&delta, "gameStateDelta"));
+        assert_eq!(
+            serde_json::to_value(&rebuilt).unwrap(),
+            serde_json::to_value(states[1].as_ref()).unwrap()
+        );
+
+        // Back to full states, and a full one first when deltas are asked for again
+        sim.send(&alice, "capabilities", json!({})).await.unwrap();
+        assert!(!sim.capabilities.borrow().low_bandwidth);
+        let full = broadcast_text(broadcast(&states[2]), false, &mut last_state);
+        data::<GameStateMessage>(&full, "gameState");
+        let full = broadcast_text(broadcast(&states[2]), true, &mut last_state);
+        data::<GameStateMessage>(&full, "gameState");
+    }
+
+    #[tokio::test]
+    async fn admin_api_reads_the_lifetime_stats() {
+        let mut sim = Simulation::new(GameConfig {
+            admin_token: Some("secret".to_string()),
+            ..Default::default()
+        });
+        let alice = sim.join("alice smith", 1).await;
+        sim.join("bob", 2).await;
+        sim.send(&alice, "startGame", json!({})).await.unwrap();
+        let on_move = sim.on_move().unwrap();
+        sim.send(&on_move.address, "fold", json!({})).await.unwrap();
+
+        let path = "players/alice%20smith/stats";
+        let response = admin_response(&sim.game, path, Some("Bearer secret")).await;
+        assert_eq!(response.status, 200);
+        let stats: PlayerStatsMessage = serde_json::from_slice(&response.body).unwrap();
+        assert_eq!(
+            (stats.name.as_str(), stats.hands_played),
+            ("alice smith", 1)
+        );
+        assert_eq!(
+            stats.net_winnings,
+            sim.game
+                .read()
+                .await
+                .player_stats("alice smith")
+                .await
+                .net_winnings
+        );
+
+        assert_eq!(admin_response(&sim.game, path, None).await.status, 401);
+        assert_eq!(
+            admin_response(&sim.game, path, Some("Bearer guess"))
+                .await
+                .status,
+            401
+        );
+        let unknown = admin_response(&sim.game, "tables", Some("Bearer secret")).await;
+        assert_eq!(unknown.status, 404);
+
+        // No token, no admin API
+        let sim = Simulation::new(GameConfig::default());
+        assert_eq!(
+            admin_response(&sim.game, path, Some("Bearer "))
+                .await
+                .status,
+            404
+        );
+    }
+
+    #[tokio::test]
+    async fn admin_api_exports_the_hand_histories() {
+        let mut sim = Simulation::new(GameConfig {
+            admin_token: Some("secret".to_string()),
+            ..Default::default()
+        });
+        let alice = sim.join("alice smith", 1).await;
+        sim.join("bob", 2).await;
+        let path = "players/alice%20smith/hands";
+        let none = admin_response(&sim.game, path, Some("Bearer secret")).await;
+        assert_eq!(none.status, 404);
+
+        sim.send(&alice, "startGame", json!({})).await.unwrap();
+        while let Some(on_move) = sim.on_move() {
+            sim.send(&on_move.address, "fold", json!({})).await.unwrap();
+        }
+        let response = admin_response(&sim.game, path, Some("Bearer secret")).await;
+        assert_eq!(
+            (response.status, response.content_type),
+            (200, "application/zip")
+        );
+        sim.send(&alice, "exportHands", json!({})).await.unwrap();
+        let export = next_event(&sim, &alice, |e| {
+            matches!(e, TableEvent::HandsExport { .. })
+        })
+        .await;
+        let TableEvent::HandsExport { zip, .. } = export else {
+            unreachable!()
+        };
+        assert_eq!(response.body, *zip);
+
+        assert_eq!(admin_response(&sim.game, path, None).await.status, 401);
+        assert_eq!(
+            admin_response(&sim.game, path, Some("Bearer guess"))
+                .await
+                .status,
+            401
+        );
+    }
+
+    #[tokio::test]
+    async fn all_in_luck_is_counted_after_the_rake() {
+        let mut sim = Simulation::new(GameConfig {
+            rake: Some(Rake::checked(0.05, None, false).unwrap()),
+            ..Default::default()
+        });
+        let alice = sim.join("alice", 1).await;
+        sim.join("bob", 2).await;
+
+        // All-in preflop and called, with the whole board to come
+        sim.send(&alice, "startGame", json!({})).await.unwrap();
+        let on_move = sim.on_move().unwrap();
+        let raise = json!({ "action": "raise", "amount": 1000.0 });
+        sim.send(&on_move.address, "raise", raise).await.unwrap();
+        let on_move = sim.on_move().unwrap();
+        sim.send(&on_move.address, "call", json!({})).await.unwrap();
+        assert!(sim.on_move().is_none());
+
+        let game = sim.game.read().await;
+        let (alice, bob) = (
+            game.player_stats("alice").await,
+            game.player_stats("bob").await,
+        );
+        for stats in [&alice, &bob] {
+            assert_eq!(stats.all_in.hands, 1);
+            assert!((stats.all_in.won - stats.net_winnings).abs() < EPSILON);
+        }
+        // Over the runouts, the two players expect to pay the rake of the 2000 chip pot
+        let expected = alice.all_in.expected + bob.all_in.expected;
+        assert!((expected + 100.0).abs() < 1e-6, "{}", expected);
+    }
+
+    #[tokio::test]
+    async fn rake_leaves_the_table() {
+        let mut sim = Simulation::new(GameConfig {
+            rake: Some(Rake::checked(0.05, Some(3.0), true).unwrap()),
+            jackpot_drops: vec![JackpotDrop::checked("badBeat".to_string(), 1.0, 20.0).unwrap()],
+            ..Default::default()
+        });
+        let alice = sim.join("alice", 1).await;
+        sim.join("bob", 2).await;
+
+        // A hand folded preflop is not raked, one checked down to the river is
+        for hand in 0..2 {
+            sim.send(&alice, "startGame", json!({})).await.unwrap();
+            while let Some(on_move) = sim.on_move() {
+                let action = match (hand, on_move.can_check) {
+                    (0, _) => "fold",
+                    (_, true) => "check",
+                    (_, false) => "call",
+                };
+                sim.send(&on_move.address, action, json!({})).await.unwrap();
+            }
+        }
+
+        let total: f64 = sim.stacks().values().sum();
+        assert!((total - (2000.0 - 1.0 - 1.0)).abs() < EPSILON);
+        let rake = sim.game.read().await.table_rake();
+        assert_eq!((rake.hands, rake.hands_raked), (2, 1));
+        assert!((rake.rake - 1.0).abs() < EPSILON);
+        assert_eq!(rake.rake_by_street, [1.0, 0.0, 0.0, 0.0]);
+        assert!((rake.jackpots["badBeat"] - 1.0).abs() < EPSILON);
+
+        let winnings: Vec<_> = sim
+            .received(&alice)
+            .into_iter()
+            .filter_map(|event| match event {
+                TableEvent::HandWinnings(message) => Some(message),
+                _ => None,
+            })
+            .collect();
+        let [folded, winnings] = &winnings[..] else {
+            panic!("expected two hands, got {}", winnings.len());
+        };
+        assert_eq!((winnings.rake, winnings.jackpot_drop), (1.0, 1.0));
+        let won: f64 = winnings.winnings.iter().map(|w| w.amount_won).sum();
+        assert!((won - 8.0).abs() < EPSILON);
+
+        // The five cards played are only pointed at after a showdown
+        assert!(folded.winnings.iter().all(|w| w.winning_cards.is_empty()));
+        for won in &winnings.winnings {
+            assert_eq!(won.winning_cards.len(), 5);
+            assert!(won.winning_cards.iter().all(|&i| i < 7));
+        }
+    }
+
+    #[tokio::test]
+    async fn side_pots_conserve_chips_over_hands() {
+        let mut sim = Simulation::new(GameConfig::default());
+        let alice = sim.join("alice", 1).await;
+        sim.join("bob", 2).await;
+        sim.join("carol", 3).await;
+
+        // Folding to the big blind first leaves stacks of 995, 1000 and 1005, so every
+        // three-way all-in after that has a side pot. Then everybody shoves or calls
+        // whatever they face until one player has every chip.
+        let mut hands = 0;
+        while sim.stacks().values().filter(|&&chips| chips > 0.0).count() > 1 {
+            sim.send(&alice, "startGame", json!({})).await.unwrap();
+            hands += 1;
+            while let Some(on_move) = sim.on_move() {
+                let result = if hands == 1 {
+                    sim.send(&on_move.address, "fold", json!({})).await
+                } else if on_move.can_raise {
+                    let amount = on_move.max_raise_to_total_bet;
+                    sim.send(
+                        &on_move.address,
+                        "raise",
+                        json!({ "action": "raise", "amount": amount }),
+                    )
+                    .await
+                } else {
+                    sim.send(&on_move.address, "call", json!({})).await
+                };
+                result.unwrap();
+            }
+            let total: f64 = sim.stacks().values().sum();
+            assert!(
+                (total - 3000.0).abs() < EPSILON,
+                "hand {}: {}",
+                hands,
+                total
+            );
+            assert!(hands < 50);
+        }
+        assert!(sim
+            .stacks()
+            .values()
+            .any(|&chips| (chips - 3000.0).abs() < EPSILON));
+    }
+
+    #[tokio::test]
+    async fn reconnecting_player_gets_their_seat_back() {
+        let mut sim = Simulation::new(GameConfig {
+            disconnect_protection: DisconnectProtection::Fold,
+            disconnect_grace: 10.0,
+            ..Default::default()
+        });
+        let alice = sim.join("alice", 1).await;
+        let bob = sim.join("bob", 4).await;
+        sim.send(&alice, "startGame", json!({})).await.unwrap();
+        let chips = player(&sim.state(), "bob").chips;
+        let token = sim
+            .received(&bob)
+            .into_iter()
+            .find_map(|event| match event {
+                TableEvent::Registered { message, .. } => Some(message.reconnect_token),
+                _ => None,
+            })
+            .unwrap();
+
+        sim.disconnect(&bob).await;
+        assert!(!player(&sim.state(), "bob").in_game);
+
+        // The name alone does not take the seat
+        let impostor = sim.connect();
+        assert!(sim
+            .send(&impostor, "registerPlayer", json!({ "name": "bob" }))
+            .await
+            .is_err());
+        let wrong = json!({ "name": "bob", "reconnectToken": "guess" });
+        assert!(sim.send(&impostor, "registerPlayer", wrong).await.is_err());
+
+        // Back before the grace period runs out: nothing is forced
+        sim.advance(5.0).await;
+        let bob = sim.connect();
+        let register = json!({ "name": "bob", "reconnectToken": token });
+        sim.send(&bob, "registerPlayer", register).await.unwrap();
+        sim.advance(20.0).await;
+
+        let state = sim.state();
+        let returned = player(&state, "bob");
+        assert_eq!(returned.address, bob);
+        assert!(returned.in_game);
+        assert!((returned.chips - chips).abs() < EPSILON);
+        assert!(state.game_started);
+        assert_eq!(state.players.len(), 2);
+
+        // The seat answers to the new connection
+        let on_move = sim.on_move().unwrap();
+        let to_act = if on_move.name == "bob" { &bob } else { &alice };
+        sim.send(to_act, "call", json!({})).await.unwrap();
+        assert!(sim
+            .received(&bob)
+            .iter()
+            .all(|event| !matches!(event, TableEvent::Error { .. })));
+    }
+
+    #[tokio::test]
+    async fn disconnected_player_is_folded_after_grace() {
+        let mut sim = Simulation::new(GameConfig {
+            disconnect_protection: DisconnectProtection::Fold,
+            disconnect_grace: 10.0,
+            ..Default::default()
+        });
+        let alice = sim.join("alice", 1).await;
+        sim.join("bob", 2).await;
+        sim.send(&alice, "startGame", json!({})).await.unwrap();
+
+        let first = sim.on_move().unwrap();
+        sim.disconnect(&first.address).await;
+        sim.advance(9.0).await;
+        assert!(sim.on_move().is_some());
+        sim.advance(1.0).await;
+        assert!(sim.on_move().is_none());
+        assert!((player(&sim.state(), &first.name).chips - 995.0).abs() < EPSILON);
+    }
+
+    #[tokio::test]
+    async fn external_player_acts_without_a_connection() {
+        let mut sim = Simulation::new(GameConfig::default());
+        let alice = sim.join("alice", 1).await;
+        let bot = sim
+            .game
+            .write()
+            .await
+            .seat_external("script", 2)
+            .await
+            .unwrap();
+        sim.send(&alice, "startGame", json!({})).await.unwrap();
+
+        let mut refused = false;
+        while let Some(on_move) = sim.on_move() {
+            if on_move.address != bot {
+                let action = if on_move.can_check { "check" } else { "call" };
+                sim.send(&on_move.address, action, json!({})).await.unwrap();
+                continue;
+            }
+            let mut game = sim.game.write().await;
+            if !refused {
+                // Raises are checked like those of the clients
+                let raise = Action::new(ActionEnum::BetRaise, 1e9);
+                assert!(game.act(&bot, raise).await.is_err());
+                refused = true;
+            }
+            let check_call = Action::new(ActionEnum::CheckCall, 0.0);
+            game.act(&bot, check_call).await.unwrap();
+        }
+        assert!(refused);
+
+        let events = sim.received(&alice);
+        let types: Vec<String> = events
+            .iter()
+            .filter_map(TableEvent::broadcast)
+            .map(|message| message.message_type)
+            .collect();
+        assert!(types.contains(&"onmove".to_string()));
+        assert!(types.contains(&"handWinnings".to_string()));
+        let chips: f64 = sim.stacks().values().sum();
+        assert!((chips - 2000.0).abs() < EPSILON);
+    }
+
+    #[tokio::test]
+    async fn busted_tournament_players_re_enter_until_registration_closes() {
+        let level = |sb, bb| BlindLevel::checked(sb, bb, 600.0, 0.0, 0.0).unwrap();
+        let mut sim = Simulation::new(GameConfig {
+            blind_levels: vec![level(5.0, 10.0), level(10.0, 20.0)],
+            late_registration_levels: Some(1),
+            buy_in: 100.0,
+            payouts: vec![0.7, 0.3],
+            ..Default::default()
+        });
+        let alice = sim.join("alice", 1).await;
+        let bob = sim.join("bob", 2).await;
+
+        // All-in and called: the loser has nothing left and loses their seat
+        sim.send(&alice, "startGame", json!({})).await.unwrap();
+        let on_move = sim.on_move().unwrap();
+        let raise = json!({ "action": "raise", "amount": 1000.0 });
+        sim.send(&on_move.address, "raise", raise).await.unwrap();
+        let on_move = sim.on_move().unwrap();
+        sim.send(&on_move.address, "call", json!({})).await.unwrap();
+        let stacks = sim.stacks();
+        assert_eq!(stacks.len(), 1);
+        let (winner, chips) = stacks.into_iter().next().unwrap();
+        assert!((chips - 2000.0).abs() < EPSILON);
+        let (loser, loser_name) = match winner.as_str() {
+            "alice" => (&bob, "bob"),
+            _ => (&alice, "alice"),
+        };
+
+        // Re-entering buys a new stack into the prize pool
+        sim.send(loser, "takeSeat", json!({ "seat": 3 }))
+            .await
+            .unwrap();
+        assert!((sim.stacks()[loser_name] - 1000.0).abs() < EPSILON);
+        let clock = sim.state().tournament_clock.unwrap();
+        assert_eq!(clock.entries, 3);
+        assert!((clock.prize_pool - 300.0).abs() < EPSILON);
+        assert_eq!(clock.payouts.len(), 2);
+        assert!((clock.payouts[0] - 210.0).abs() < EPSILON);
+        assert!((clock.payouts[1] - 90.0).abs() < EPSILON);
+
+        // The winner plays on with what they won
+        sim.send(&alice, "startGame", json!({})).await.unwrap();
+        let on_move = sim.on_move().unwrap();
+        sim.send(&on_move.address, "fold", json!({})).await.unwrap();
+        let stacks = sim.stacks();
+        assert!((stacks.values().sum::<f64>() - 3000.0).abs() < EPSILON);
+        assert!((stacks[&winner] - 2000.0).abs() <= 10.0);
+
+        // Once the first level is over, nobody else takes a seat
+        sim.advance(601.0).await;
+        let clock = sim
+            .received(&alice)
+            .into_iter()
+            .rev()
+            .find_map(|event| match event {
+                TableEvent::TournamentClock(clock) => Some(clock),
+                _ => None,
+            });
+        assert!(!clock.unwrap().registration_open);
+        let carol = sim.connect();
+        sim.send(&carol, "registerPlayer", json!({ "name": "carol" }))
+            .await
+            .unwrap();
+        let _ = sim.send(&carol, "takeSeat", json!({ "seat": 4 })).await;
+        let error = next_event(&sim, &carol, |e| matches!(e, TableEvent::Error { .. })).await;
+        assert!(
+            matches!(error, TableEvent::Error { message, .. } if message.code == "registrationClosed")
+        );
+        assert!(!sim.stacks().contains_key("carol"));
+    }
+
+    #[tokio::test]
+    async fn knockouts_pay_half_the_bounty_and_put_half_on_the_winner() {
+        let mut sim = Simulation::new(GameConfig {
+            blind_levels: vec![BlindLevel::checked(5.0, 10.0, 600.0, 0.0, 0.0).unwrap()],
+            buy_in: 100.0,
+            bounty: 100.0,
+            ..Default::default()
+        });
+        let alice = sim.join("alice", 1).await;
+        sim.join("bob", 2).await;
+        let state = sim.state();
+        assert_eq!(player(&state, "alice").bounty, Some(100.0));
+        assert_eq!(player(&state, "alice").bounties_won, Some(0.0));
+
+        // All-in and called: the winner collects the loser's bounty
+        sim.send(&alice, "startGame", json!({})).await.unwrap();
+        let on_move = sim.on_move().unwrap();
+        let raise = json!({ "action": "raise", "amount": 1000.0 });
+        sim.send(&on_move.address, "raise", raise).await.unwrap();
+        let on_move = sim.on_move().unwrap();
+        sim.send(&on_move.address, "call", json!({})).await.unwrap();
+        let (winner, _) = sim.stacks().into_iter().next().unwrap();
+        let loser = if winner == "alice" { "bob" } else { "alice" };
+
+        let knockout = sim
+            .received(&alice)
+            .into_iter()
+            .find_map(|event| match event {
+                TableEvent::Knockout(knockout) => Some(knockout),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(knockout.name, loser);
+        assert!((knockout.bounty - 100.0).abs() < EPSILON);
+        assert_eq!(knockout.winners.len(), 1);
+        assert_eq!(knockout.winners[0].name, winner);
+        assert!((knockout.winners[0].won - 50.0).abs() < EPSILON);
+        assert!((knockout.winners[0].bounty - 150.0).abs() < EPSILON);
+
+        let state = sim.state();
+        assert_eq!(player(&state, &winner).bounty, Some(150.0));
+        assert_eq!(player(&state, &winner).bounties_won, Some(50.0));
+    }
+
+    #[tokio::test]
+    async fn bounties_follow_the_pots_the_busted_player_was_in() {
+        let mut sim = Simulation::new(GameConfig {
+            blind_levels: vec![BlindLevel::checked(5.0, 10.0, 600.0, 0.0, 0.0).unwrap()],
+            buy_in: 100.0,
+            bounty: 100.0,
+            session_seed: Some(3),
+            ..Default::default()
+        });
+        let alice = sim.join("alice", 1).await;
+        sim.join("bob", 2).await;
+        sim.join("carol", 3).await;
+
+        // Folding to the big blind leaves bob 995, alice 1000 and carol 1005
+        sim.send(&alice, "startGame", json!({})).await.unwrap();
+        while let Some(on_move) = sim.on_move() {
+            sim.send(&on_move.address, "fold", json!({})).await.unwrap();
+        }
+        // Three-way all-in: bob wins the main pot, carol the side pot against alice, and
+        // alice busts
+        sim.send(&alice, "startGame", json!({})).await.unwrap();
+        while let Some(on_move) = sim.on_move() {
+            let result = if on_move.can_raise {
+                let amount = on_move.max_raise_to_total_bet;
+                let raise = json!({ "action": "raise", "amount": amount });
+                sim.send(&on_move.address, "raise", raise).await
+            } else {
+                sim.send(&on_move.address, "call", json!({})).await
+            };
+            result.unwrap();
+        }
+        let stacks = sim.stacks();
+        assert_eq!((stacks["bob"], stacks["carol"]), (2985.0, 15.0));
+
+        // Alice's bounty is split by what each of them won of the pots she put chips in
+        let knockouts: Vec<KnockoutMessage> = sim
+            .received(&alice)
+            .into_iter()
+            .filter_map(|event| match event {
+                TableEvent::Knockout(knockout) => Some(knockout),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(knockouts.len(), 1);
+        assert_eq!(knockouts[0].name, "alice");
+        let won: Vec<(&str, f64)> = knockouts[0]
+            .winners
+            .iter()
+            .map(|w| (w.name.as_str(), w.won))
+            .collect();
+        assert_eq!(won.len(), 2);
+        for (name, won) in won {
+            let share = match name {
+                "bob" => 2985.0 / 2995.0,
+                _ => 10.0 / 2995.0,
+            };
+            assert!((won - 50.0 * share).abs() < EPSILON, "{}: {}", name, won);
+        }
+    }
+}
@@ -0,0 +1,550 @@
+// solver_strategy.rs - Strategies exported by external solvers, looked up at the decisions
+// of a hand
+//
+// A node is a decision of the solver's tree: the betting line from the flop on and the board.
+// Its strategy gives each hand the frequency of the actions, in the tokens of
+// `State.betting_line`. Boards and hands are keyed up to a relabeling of the suits, so a
+// solve of As Kd 7h also plays on Ac Kh 7d.
+use crate::betting_line::postflop_line;
+use crate::datasets::parse_card;
+use crate::hand_history::card_text;
+use crate::node_lock::LockedAction;
+use crate::seeds::hand_seed;
+use crate::state::action::{Action, ActionEnum};
+use crate::state::card::{Card, CardSuit};
+use crate::state::State;
+use itertools::Itertools;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use strum::IntoEnumIterator;
+
+/// Frequency of each action token, normalized and sorted by token
+type HandStrategy = Vec<(String, f64)>;
+
+/// A suit for each suit, by index
+type Relabeling = [usize; 4];
+
+/// The node of the JSON export
+#[derive(Deserialize)]
+struct JsonNode {
+    #[serde(default)]
+    line: String,
+    board: String,
+    /// Frequency of each action token for each hand
+    strategy: HashMap<String, HashMap<String, f64>>,
+}
+
+/// A JSON export, `{"nodes": [...]}` or the list of nodes itself
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonExport {
+    Nodes { nodes: Vec<JsonNode> },
+    List(Vec<JsonNode>),
+}
+
+/// Cards written together or apart, e.g. "AsKd7h" or "As Kd 7h"
+fn parse_card_list(text: &str) -> Option<Vec<Card>> {
+    let text: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+    if !text.len().is_multiple_of(2) || !text.is_ascii() {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| parse_card(&text[i..i + 2]))
+        .collect()
+}
+
+fn relabel(card: Card, relabeling: &Relabeling) -> Card {
+    let suit = CardSuit::iter()
+        .nth(relabeling[card.suit as usize])
+        .unwrap();
+    Card::new(suit, card.rank)
+}
+
+/// Text of `cards` relabeled, the first `sorted` of them put in order, highest rank first
+fn cards_key(cards: &[Card], relabeling: &Relabeling, sorted: usize) -> String {
+    let mut cards: Vec<Card> = cards.iter().map(|&c| relabel(c, relabeling)).collect();
+    let n = sorted.min(cards.len());
+    cards[..n].sort_by_key(|c| (std::cmp::Reverse(c.rank), c.suit));
+    cards.iter().map(|&c| card_text(c)).collect()
+}
+
+/// The key of `board` under the relabelings of the suits that write it first in order, with
+/// those relabelings. The flop is a set of cards, the turn and the river follow it in order.
+fn board_key(board: &[Card]) -> (String, Vec<Relabeling>) {
+    let mut best = None::<String>;
+    let mut relabelings = Vec::new();
+    for permutation in (0..4).permutations(4) {
+        let relabeling: Relabeling = [
+            permutation[0],
+            permutation[1],
+            permutation[2],
+            permutation[3],
+        ];
+        let key = cards_key(board, &relabeling, 3);
+        match &best {
+            Some(best_key) if key > *best_key => continue,
+            Some(best_key) if key == *best_key => {}
+            _ => {
+                best = Some(key);
+                relabelings.clear();
+            }
+        }
+        relabelings.push(relabeling);
+    }
+    (best.unwrap_or_default(), relabelings)
+}
+
+/// The key of `hand` on a board written first in order by `relabelings`
+fn hand_key(hand: &[Card], relabelings: &[Relabeling]) -> String {
+    relabelings
+        .iter()
+        .map(|relabeling| cards_key(hand, relabeling, 2))
+        .min()
+        .unwrap_or_default()
+}
+
+/// Column of a CSV export: an action token, or "Check", "Call", "Fold", "Bet <size>" or
+/// "Raise <size>" with the size in percent of the pot as in the betting line
+fn column_token(column: &str) -> Option<String> {
+    let column = column.trim().trim_end_matches('%').to_lowercase();
+    let token = match column.split_whitespace().collect::<Vec<_>>()[..] {
+        ["check"] => "x".to_owned(),
+        ["call"] => "c".to_owned(),
+        ["fold"] => "f".to_owned(),
+        ["bet", size] => format!("b{}", size),
+        ["raise", size] => format!("r{}", size),
+        [token] => token.to_owned(),
+        _ => return None,
+    };
+    LockedAction::parse(&token).map(|_| token)
+}
+
+/// Fields of a CSV record, quotes allowing commas and doubled quotes within a field
+fn csv_fields(record: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = record.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                fields.last_mut().unwrap().push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            _ => fields.last_mut().unwrap().push(c),
+        }
+    }
+    fields.iter().map(|field| field.trim().to_owned()).collect()
+}
+
+fn collect_files(path: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    if path.is_file() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+    let mut entries: Vec<PathBuf> = std::fs::read_dir(path)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<std::io::Result<_>>()?;
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() {
+            collect_files(&entry, files)?;
+        } else if entry
+            .extension()
+            .is_some_and(|ext| ext == "csv" || ext == "json")
+        {
+            files.push(entry);
+        }
+    }
+    Ok(())
+}
+
+/// Node strategies imported from solver exports, played at the decisions of a hand that
+/// reach a node. Calling it as `strategy(state, player)` plays it as a bot policy, e.g. for
+/// `ServerHandle.add_bot`, checking or folding off the solved tree.
+#[pyclass]
+#[derive(Clone, Default)]
+pub struct SolverStrategy {
+    /// Strategy of each hand at each (line, board) node
+    nodes: HashMap<(String, String), HashMap<String, HandStrategy>>,
+}
+
+impl std::fmt::Debug for SolverStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("SolverStrategy")
+            .field("nodes", &self.nodes.len())
+            .field(
+                "hands",
+                &self.nodes.values().map(HashMap::len).sum::<usize>(),
+            )
+            .finish()
+    }
+}
+
+impl SolverStrategy {
+    /// Set the strategy of `hand` at the node of `line` on `board`, replacing any earlier one
+    pub fn insert(
+        &mut self,
+        line: &str,
+        board: &str,
+        hand: &str,
+        strategy: Vec<(String, f64)>,
+    ) -> Result<(), String> {
+        let board_cards = parse_card_list(board)
+            .filter(|cards| (3..=5).contains(&cards.len()))
+            .ok_or_else(|| format!("Invalid board '{}'", board))?;
+        let hand_cards = parse_card_list(hand)
+            .filter(|cards| cards.len() == 2 && cards[0] != cards[1])
+            .filter(|cards| cards.iter().all(|c| !board_cards.contains(c)))
+            .ok_or_else(|| format!("Invalid hand '{}' on {}", hand, board))?;
+
+        let mut strategy: HandStrategy = strategy
+            .into_iter()
+            .filter(|(_, frequency)| *frequency != 0.0)
+            .collect();
+        if let Some((token, _)) = strategy
+            .iter()
+            .find(|(token, _)| LockedAction::parse(token).is_none())
+        {
+            return Err(format!("Invalid action '{}'", token));
+        }
+        if strategy
+            .iter()
+            .any(|(_, frequency)| !(frequency.is_finite() && *frequency > 0.0))
+        {
+            return Err(format!("Frequencies of {} cannot be negative", hand));
+        }
+        let total: f64 = strategy.iter().map(|(_, frequency)| frequency).sum();
+        if total <= 0.0 {
+            // A hand the solver never holds at the node
+            return Ok(());
+        }
+        strategy.sort_by(|a, b| a.0.cmp(&b.0));
+        for (_, frequency) in strategy.iter_mut() {
+            *frequency /= total;
+        }
+
+        let (board, relabelings) = board_key(&board_cards);
+        let hand = hand_key(&hand_cards, &relabelings);
+        self.nodes
+            .entry((line.trim().to_owned(), board))
+            .or_default()
+            .insert(hand, strategy);
+        Ok(())
+    }
+
+    /// Read a CSV export: a header of `line,board,hand` followed by a column per action, then a
+    /// record per hand with its frequencies. Returns the records read.
+    pub fn read_csv(&mut self, text: &str) -> Result<usize, String> {
+        let mut records = text
+            .trim_start_matches('\u{feff}')
+            .lines()
+            .enumerate()
+            .filter(|(_, record)| !record.trim().is_empty());
+        let Some((_, header)) = records.next() else {
+            return Ok(0);
+        };
+        let header = csv_fields(header);
+        let names: Vec<String> = header.iter().take(3).map(|c| c.to_lowercase()).collect();
+        if names != ["line", "board", "hand"] {
+            return Err("CSV exports start with the columns line, board, hand".to_owned());
+        }
+        let tokens = header[3..]
+            .iter()
+            .map(|column| {
+                column_token(column).ok_or_else(|| format!("Invalid action column '{}'", column))
+            })
+            .collect::<Result<Vec<String>, String>>()?;
+
+        let mut read = 0;
+        for (index, record) in records {
+            let fields = csv_fields(record);
+            if fields.len() != header.len() {
+                return Err(format!("Line {} has {} fields", index + 1, fields.len()));
+            }
+            let strategy = tokens
+                .iter()
+                .zip(&fields[3..])
+                .map(|(token, field)| {
+                    let frequency = if field.is_empty() {
+                        Ok(0.0)
+                    } else {
+                        field.parse()
+                    };
+                    frequency
+                        .map(|frequency| (token.clone(), frequency))
+                        .map_err(|_| format!("Invalid frequency '{}' on line {}", field, index + 1))
+                })
+                .collect::<Result<HandStrategy, String>>()?;
+            self.insert(&fields[0], &fields[1], &fields[2], strategy)
+                .map_err(|e| format!("{} on line {}", e, index + 1))?;
+            read += 1;
+        }
+        Ok(read)
+    }
+
+    /// Read a JSON export, `{"nodes": [{"line": ..., "board": ..., "strategy": {hand: {token:
+    /// frequency}}}]}`. Returns the hands read.
+    pub fn read_json(&mut self, text: &str) -> Result<usize, String> {
+        let export: JsonExport = serde_json::from_str(text).map_err(|e| e.to_string())?;
+        let nodes = match export {
+            JsonExport::Nodes { nodes } | JsonExport::List(nodes) => nodes,
+        };
+        let mut read = 0;
+        for node in nodes {
+            for (hand, strategy) in node.strategy {
+                self.insert(
+                    &node.line,
+                    &node.board,
+                    &hand,
+                    strategy.into_iter().collect(),
+                )?;
+                read += 1;
+            }
+        }
+        Ok(read)
+    }
+
+    /// The strategy of `hand` at the current decision of `state`
+    pub fn lookup(&self, state: &State, hand: (Card, Card)) -> Option<&HandStrategy> {
+        if state.final_state || state.legal_actions.is_empty() || state.public_cards.len() < 3 {
+            return None;
+        }
+        let (board, relabelings) = board_key(&state.public_cards);
+        let node = self.nodes.get(&(postflop_line(state), board))?;
+        node.get(&hand_key(&[hand.0, hand.1], &relabelings))
+    }
+
+    /// The action the strategy plays with `hand` at the current decision of `state`, sampled
+    /// from the hand's seed and the number of actions so far as with `NodeLock`
+    pub fn sample(&self, state: &State, hand: (Card, Card)) -> Option<Action> {
+        let strategy = self.lookup(state, hand)?;
+        let seed = hand_seed(state.seed, state.action_list.len() as u64);
+        let mut draw: f64 = StdRng::seed_from_u64(seed).gen();
+        let (token, _) = strategy
+            .iter()
+            .find(|(_, frequency)| {
+                draw -= frequency;
+                draw < 0.0
+            })
+            .or(strategy.last())?;
+        LockedAction::parse(token).map(|action| action.action(state))
+    }
+}
+
+#[pymethods]
+impl SolverStrategy {
+    #[new]
+    pub fn new() -> SolverStrategy {
+        SolverStrategy::default()
+    }
+
+    /// Read every `.csv` and `.json` export under `path`, a file or a directory searched
+    /// recursively
+    #[staticmethod]
+    pub fn load(path: &str) -> PyResult<SolverStrategy> {
+        let mut files = Vec::new();
+        collect_files(Path::new(path), &mut files)
+            .map_err(|e| PyValueError::new_err(format!("Cannot read {}: {}", path, e)))?;
+        let mut strategy = SolverStrategy::default();
+        for file in files {
+            let text = std::fs::read_to_string(&file).map_err(|e| {
+                PyValueError::new_err(format!("Cannot read {}: {}", file.display(), e))
+            })?;
+            let read = if file.extension().is_some_and(|ext| ext == "json") {
+                strategy.read_json(&text)
+            } else {
+                strategy.read_csv(&text)
+            };
+            read.map_err(|e| PyValueError::new_err(format!("{}: {}", file.display(), e)))?;
+        }
+        Ok(strategy)
+    }
+
+    /// Add the hands of a CSV export, returning how many were read
+    pub fn add_csv(&mut self, text: &str) -> PyResult<usize> {
+        self.read_csv(text).map_err(PyValueError::new_err)
+    }
+
+    /// Add the hands of a JSON export, returning how many were read
+    pub fn add_json(&mut self, text: &str) -> PyResult<usize> {
+        self.read_json(text).map_err(PyValueError::new_err)
+    }
+
+    /// Set the strategy of `hand` at the node of `line` on `board`
+    #[pyo3(name = "add")]
+    pub fn py_add(
+        &mut self,
+        line: &str,
+        board: &str,
+        hand: &str,
+        strategy: HashMap<String, f64>,
+    ) -> PyResult<()> {
+        self.insert(line, board, hand, strategy.into_iter().collect())
+            .map_err(PyValueError::new_err)
+    }
+
+    /// Frequency of each action token for `hand` (the current player's by default) at the
+    /// current decision of `state`, `None` off the solved tree
+    #[pyo3(signature = (state, hand=None))]
+    pub fn strategy_for(
+        &self,
+        state: &State,
+        hand: Option<(Card, Card)>,
+    ) -> Option<Vec<(String, f64)>> {
+        let hand = hand.or_else(|| current_hand(state))?;
+        self.lookup(state, hand).cloned()
+    }
+
+    /// The action played with `hand` (the current player's by default) at the current decision
+    /// of `state`, `None` off the solved tree
+    #[pyo3(signature = (state, hand=None))]
+    pub fn action_for(&self, state: &State, hand: Option<(Card, Card)>) -> Option<Action> {
+        let hand = hand.or_else(|| current_hand(state))?;
+        self.sample(state, hand)
+    }
+
+    /// Bot policy: the strategy's action for `player`, a check or a fold off the solved tree
+    pub fn __call__(&self, state: &State, player: u64) -> Action {
+        let hand = state.players_state.get(player as usize).map(|ps| ps.hand);
+        if let Some(action) = hand.and_then(|hand| self.sample(state, hand)) {
+            return action;
+        }
+        if state.legal_actions.contains(&ActionEnum::Fold) {
+            let ps = &state.players_state[state.current_player as usize];
+            let facing_bet = state
+                .players_state
+                .iter()
+                .any(|other| other.bet_chips > ps.bet_chips);
+            if facing_bet {
+                return Action::new(ActionEnum::Fold, 0.0);
+            }
+        }
+        Action::new(ActionEnum::CheckCall, 0.0)
+    }
+
+    /// Nodes imported
+    pub fn __len__(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn __str__(&self) -> PyResult<String> {
+        Ok(format!("{:#?}", self))
+    }
+}
+
+fn current_hand(state: &State) -> Option<(Card, Card)> {
+    state
+        .players_state
+        .get(state.current_player as usize)
+        .map(|ps| ps.hand)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::stage::Stage;
+
+    /// A heads-up hand where player 1 holds `first` and player 0 `second`, called to the flop
+    fn flop(first: &str, second: &str, board: &str) -> State {
+        let mut deck = parse_card_list(first).unwrap();
+        deck.extend(parse_card_list(second).unwrap());
+        deck.extend(parse_card_list(board).unwrap());
+        for card in Card::collect() {
+            if !deck.contains(&card) {
+                deck.push(card);
+            }
+        }
+        let mut state = State::from_deck(2, 0, 0.5, 1.0, 100.0, deck, false, 0, 0.0).unwrap();
+        while state.stage == Stage::Preflop {
+            state = state.apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+        }
+        state
+    }
+
+    #[test]
+    fn csv_strategies_play_on_isomorphic_boards() {
+        let mut strategy = SolverStrategy::default();
+        let csv = "line,board,hand,Check,Bet 33,b75\n\
+                   ,As Kd 7h,AhAd,0.2,0.8,0\n\
+                   ,AsKd7h,7c7d,0,50,50\n\
+                   \"x-b33\",AsKd7h,AhAd,,,\n";
+        assert_eq!(strategy.read_csv(csv).unwrap(), 3);
+        assert_eq!(strategy.nodes.len(), 1);
+
+        // The same flop with the suits of the spades and hearts swapped, in another order
+        let state = flop("AsAd", "2c3c", "7s Kd Ah");
+        assert_eq!(
+            strategy.lookup(&state, state.players_state[1].hand),
+            Some(&vec![("b33".to_owned(), 0.8), ("x".to_owned(), 0.2)])
+        );
+        // Sevens hold a diamond and a club there, as they do on the solved flop
+        let sevens = (
+            Card::from_string("D7".to_owned()).unwrap(),
+            Card::from_string("C7".to_owned()).unwrap(),
+        );
+        let mixed = strategy.lookup(&state, sevens).unwrap();
+        assert_eq!(
+            mixed,
+            &vec![("b33".to_owned(), 0.5), ("b75".to_owned(), 0.5)]
+        );
+        assert!(strategy
+            .lookup(&state, state.players_state[0].hand)
+            .is_none());
+
+        let bet = strategy.sample(&state, sevens).unwrap();
+        assert_eq!(bet.action, ActionEnum::BetRaise);
+        assert!(bet.amount == 0.66 || bet.amount == 1.5);
+        // Off the tree the policy checks, and folds when facing a bet
+        let state = state.apply_action(Action::new(ActionEnum::BetRaise, 1.0));
+        assert_eq!(strategy.__call__(&state, 0).action, ActionEnum::Fold);
+
+        assert!(strategy.read_csv("board,hand\n").is_err());
+        assert!(strategy.read_csv("line,board,hand,k\n").is_err());
+        assert!(strategy
+            .read_csv("line,board,hand,x\n,AsKd7h,AsAd,1\n")
+            .is_err());
+    }
+
+    #[test]
+    fn json_strategies_follow_the_line() {
+        let mut strategy = SolverStrategy::default();
+        let json = r#"{"nodes": [
+            {"line": "x-b50", "board": "Qh Jh 2c", "strategy": {
+                "AhKh": {"r100": 1.0},
+                "3d3s": {"f": 3.0, "c": 1.0}
+            }}
+        ]}"#;
+        assert_eq!(strategy.read_json(json).unwrap(), 2);
+        assert!(strategy.read_json("[]").is_ok());
+
+        let state = flop("3d3s", "AhKh", "QhJh2c");
+        assert_eq!(state.current_player, 1);
+        assert!(strategy
+            .lookup(&state, state.players_state[1].hand)
+            .is_none());
+        let state = state.apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+        let state = state.apply_action(Action::new(ActionEnum::BetRaise, 1.0));
+        assert_eq!(postflop_line(&state), "x-b50");
+        assert_eq!(
+            strategy.lookup(&state, state.players_state[1].hand),
+            Some(&vec![("c".to_owned(), 0.25), ("f".to_owned(), 0.75)])
+        );
+
+        // The check-raise of the flush draw, a raise by the pot of 4 once called
+        let state = flop("AhKh", "3d3s", "QhJh2c")
+            .apply_action(Action::new(ActionEnum::CheckCall, 0.0))
+            .apply_action(Action::new(ActionEnum::BetRaise, 1.0));
+        assert_eq!(
+            strategy.__call__(&state, 1),
+            Action::new(ActionEnum::BetRaise, 5.0)
+        );
+    }
+}
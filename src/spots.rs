@@ -0,0 +1,380 @@
+// spots.rs - Hands dealt and played up to the flop of a chosen training spot
+use crate::cards::{all_combos, combos_for_range, live_cards, sample_combo};
+use crate::datasets::MAX_SEATS;
+use crate::node_lock::position_to_act;
+use crate::range::HandRange;
+use crate::seeds::hand_seed;
+use crate::state::action::{Action, ActionEnum};
+use crate::state::card::Card;
+use crate::state::stage::Stage;
+use crate::state::{State, StateStatus};
+use itertools::Itertools;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rayon::prelude::*;
+
+/// Blinds of every spot, so that amounts are in big blinds
+const SMALL_BLIND: f64 = 0.5;
+const BIG_BLIND: f64 = 1.0;
+
+/// Random flops tried before listing the flops of a texture
+const FLOP_TRIES: usize = 200;
+
+/// Preflop action between the hero and the villain, everybody else folding
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PotType {
+    /// The first of the two calls, the other checks or calls
+    Limped,
+    /// The first of the two raises, the other calls
+    SingleRaised,
+    /// The first of the two raises, the other re-raises and the first calls
+    ThreeBet,
+}
+
+/// Suits and ranks of a flop
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlopTexture {
+    /// Three suits
+    Rainbow,
+    /// Two cards of one suit, so a flush draw is possible
+    TwoTone,
+    /// One suit
+    Monotone,
+    /// Two or three cards of one rank
+    Paired,
+}
+
+impl FlopTexture {
+    fn matches(self, flop: &[Card]) -> bool {
+        let suits = flop.iter().map(|c| c.suit).unique().count();
+        match self {
+            FlopTexture::Rainbow => suits == 3,
+            FlopTexture::TwoTone => suits == 2,
+            FlopTexture::Monotone => suits == 1,
+            FlopTexture::Paired => flop.iter().map(|c| c.rank).unique().count() < 3,
+        }
+    }
+}
+
+/// Constraints of a training spot. Positions count seats after the button: 0 is the button,
+/// 1 the small blind, 2 the big blind. Amounts are in big blinds.
+#[pyclass]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpotSpec {
+    #[pyo3(get)]
+    pub n_players: u64,
+    #[pyo3(get)]
+    pub hero: u64,
+    #[pyo3(get)]
+    pub villain: u64,
+    #[pyo3(get)]
+    pub pot: PotType,
+    /// Starting stack of every player
+    #[pyo3(get)]
+    pub stack_bb: f64,
+    /// Range the hero's hand is drawn from, any hand when unset
+    #[pyo3(get)]
+    pub hero_range: Option<HandRange>,
+    /// Range the villain's hand is drawn from, any hand when unset
+    #[pyo3(get)]
+    pub villain_range: Option<HandRange>,
+    /// Texture of the flop, any flop when unset
+    #[pyo3(get)]
+    pub flop: Option<FlopTexture>,
+    /// Total of the first raise
+    #[pyo3(get)]
+    pub open_bb: f64,
+    /// Total of the re-raise in a `ThreeBet` pot, as a multiple of the first raise
+    #[pyo3(get)]
+    pub three_bet: f64,
+}
+
+impl SpotSpec {
+    #[allow(clippy::too_many_arguments)]
+    pub fn checked(
+        n_players: u64,
+        hero: u64,
+        villain: u64,
+        pot: PotType,
+        stack_bb: f64,
+        hero_range: Option<HandRange>,
+        villain_range: Option<HandRange>,
+        flop: Option<FlopTexture>,
+        open_bb: f64,
+        three_bet: f64,
+    ) -> Result<SpotSpec, String> {
+        if !(2..=MAX_SEATS as u64).contains(&n_players) {
+            return Err(format!("Spots have 2 to {} players", MAX_SEATS));
+        }
+        if hero >= n_players || villain >= n_players || hero == villain {
+            return Err("The hero and the villain must be two different seats".to_owned());
+        }
+        if !(stack_bb >= 1.0 && stack_bb.is_finite()) {
+            return Err("Stacks must be at least 1 big blind".to_owned());
+        }
+        if !(open_bb >= 2.0 && three_bet >= 2.0) {
+            return Err("Raises must at least double the bet".to_owned());
+        }
+        Ok(SpotSpec {
+            n_players,
+            hero,
+            villain,
+            pot,
+            stack_bb,
+            hero_range,
+            villain_range,
+            flop,
+            open_bb,
+            three_bet,
+        })
+    }
+
+    /// Deal spot `seed` and play its preflop line
+    pub fn generate(&self, seed: u64) -> Result<State, String> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let hero = draw_hand(self.hero_range.as_ref(), &[], &mut rng)
+            .ok_or("No hand of the hero's range can be dealt")?;
+        let villain = draw_hand(self.villain_range.as_ref(), &[hero.0, hero.1], &mut rng)
+            .ok_or("No hand of the villain's range can be dealt")?;
+        let mut dead = vec![hero.0, hero.1, villain.0, villain.1];
+        let mut rest = live_cards(&dead);
+        rest.shuffle(&mut rng);
+        let flop = match self.flop {
+            Some(texture) => draw_flop(texture, &rest, &mut rng).ok_or("No flop fits")?,
+            None => rest[..3].to_vec(),
+        };
+        dead.extend(&flop);
+        rest.retain(|c| !dead.contains(c));
+
+        // Hands are dealt from the small blind on, the button being seat 0
+        let n = self.n_players;
+        let mut deck = Vec::with_capacity(52);
+        for seat in (1..=n).map(|i| i % n) {
+            let hand = match seat {
+                _ if seat == self.hero => hero,
+                _ if seat == self.villain => villain,
+                _ => (rest.pop().unwrap(), rest.pop().unwrap()),
+            };
+            deck.extend([hand.0, hand.1]);
+        }
+        deck.extend(flop);
+        deck.extend(rest);
+
+        let state = State::from_deck(
+            n,
+            0,
+            SMALL_BLIND,
+            BIG_BLIND,
+            self.stack_bb,
+            deck,
+            false,
+            seed,
+            0.0,
+        )
+        .map_err(|_| "Invalid stacks".to_owned())?;
+        self.play_preflop(state)
+    }
+
+    /// Play the pot's line between the hero and the villain, folding everybody else
+    fn play_preflop(&self, mut state: State) -> Result<State, String> {
+        let mut line = 0;
+        while state.stage == Stage::Preflop && !state.final_state {
+            let seat = position_to_act(&state);
+            let action = if seat != self.hero && seat != self.villain {
+                Action::new(ActionEnum::Fold, 0.0)
+            } else {
+                line += 1;
+                match (self.pot, line) {
+                    (PotType::SingleRaised | PotType::ThreeBet, 1) => {
+                        Action::new(ActionEnum::BetRaise, self.open_bb * BIG_BLIND)
+                    }
+                    (PotType::ThreeBet, 2) => {
+                        let total = self.open_bb * self.three_bet * BIG_BLIND;
+                        Action::new(ActionEnum::BetRaise, total)
+                    }
+                    _ => Action::new(ActionEnum::CheckCall, 0.0),
+                }
+            };
+            state = state.apply_action(action);
+            if state
+                .action_list
+                .last()
+                .is_some_and(|r| r.coercion.is_some())
+            {
+                return Err(format!(
+                    "The preflop line does not fit in {} big blinds",
+                    self.stack_bb
+                ));
+            }
+        }
+
+        if !matches!(state.status, StateStatus::Ok) || state.final_state {
+            return Err("The hand did not reach the flop".to_owned());
+        }
+        Ok(state)
+    }
+}
+
+#[pymethods]
+impl SpotSpec {
+    #[new]
+    #[pyo3(signature = (n_players=6, hero=2, villain=0, pot=PotType::SingleRaised, stack_bb=100.0, hero_range=None, villain_range=None, flop=None, open_bb=2.5, three_bet=3.0))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        n_players: u64,
+        hero: u64,
+        villain: u64,
+        pot: PotType,
+        stack_bb: f64,
+        hero_range: Option<HandRange>,
+        villain_range: Option<HandRange>,
+        flop: Option<FlopTexture>,
+        open_bb: f64,
+        three_bet: f64,
+    ) -> PyResult<SpotSpec> {
+        SpotSpec::checked(
+            n_players,
+            hero,
+            villain,
+            pot,
+            stack_bb,
+            hero_range,
+            villain_range,
+            flop,
+            open_bb,
+            three_bet,
+        )
+        .map_err(PyValueError::new_err)
+    }
+
+    pub fn __str__(&self) -> PyResult<String> {
+        Ok(format!("{:#?}", self))
+    }
+}
+
+/// A hand drawn from `range` by weight, or uniformly without a range
+fn draw_hand(range: Option<&HandRange>, dead: &[Card], rng: &mut StdRng) -> Option<(Card, Card)> {
+    let combos = match range {
+        Some(range) => combos_for_range(range, dead.to_vec()),
+        None => all_combos(dead.to_vec())
+            .into_iter()
+            .map(|combo| (combo, 1.0))
+            .collect(),
+    };
+    sample_combo(&combos, rng)
+}
+
+/// A flop of `texture` drawn uniformly from `cards`. Random flops are tried first; the fitting
+/// flops are only listed when the texture is too rare for them.
+fn draw_flop(texture: FlopTexture, cards: &[Card], rng: &mut StdRng) -> Option<Vec<Card>> {
+    for _ in 0..FLOP_TRIES {
+        let flop: Vec<Card> = cards.choose_multiple(rng, 3).copied().collect();
+        if texture.matches(&flop) {
+            return Some(flop);
+        }
+    }
+    let flops: Vec<Vec<Card>> = cards
+        .iter()
+        .copied()
+        .combinations(3)
+        .filter(|flop| texture.matches(flop))
+        .collect();
+    flops.choose(rng).cloned()
+}
+
+/// `n_spots` hands fitting `spec`, each played to the first decision on the flop with 0.5/1
+/// blinds and the button on seat 0. Spot `i` is dealt from `hand_seed(seed, i)`; the spots
+/// are generated in parallel.
+#[pyfunction]
+#[pyo3(signature = (spec, n_spots, seed=0))]
+pub fn generate_spots(spec: &SpotSpec, n_spots: usize, seed: u64) -> PyResult<Vec<State>> {
+    (0..n_spots as u64)
+        .into_par_iter()
+        .map(|i| spec.generate(hand_seed(seed, i)))
+        .collect::<Result<Vec<State>, String>>()
+        .map_err(PyValueError::new_err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::range::hand_class;
+
+    #[test]
+    fn single_raised_pot_in_the_big_blind() {
+        let connectors = HandRange::from_string("54s, 65s, 76s, 87s, 98s, T9s").unwrap();
+        let spec = SpotSpec::checked(
+            6,
+            2,
+            0,
+            PotType::SingleRaised,
+            40.0,
+            Some(connectors.clone()),
+            None,
+            Some(FlopTexture::TwoTone),
+            2.5,
+            3.0,
+        )
+        .unwrap();
+
+        let spots = generate_spots(&spec, 20, 3).unwrap();
+        for state in &spots {
+            assert_eq!(state.stage, Stage::Flop);
+            assert_eq!(state.public_cards.len(), 3);
+            assert!(FlopTexture::TwoTone.matches(&state.public_cards));
+            let hero = &state.players_state[2];
+            assert!(connectors.weights[hand_class(hero.hand)] > 0.0);
+            assert_eq!(hero.stake, 37.5);
+            assert_eq!(state.pot, 5.5);
+            let active: Vec<u64> = state
+                .players_state
+                .iter()
+                .filter(|ps| ps.active)
+                .map(|ps| ps.player)
+                .collect();
+            assert_eq!(active, vec![0, 2]);
+        }
+        let again = generate_spots(&spec, 20, 3).unwrap();
+        assert_eq!(
+            again[7].players_state[2].hand,
+            spots[7].players_state[2].hand
+        );
+        assert_eq!(again[7].public_cards, spots[7].public_cards);
+    }
+
+    #[test]
+    fn three_bet_pot_blind_versus_blind() {
+        let spec = SpotSpec::checked(
+            6,
+            1,
+            2,
+            PotType::ThreeBet,
+            100.0,
+            None,
+            None,
+            Some(FlopTexture::Paired),
+            2.5,
+            3.0,
+        )
+        .unwrap();
+        let state = &generate_spots(&spec, 1, 0).unwrap()[0];
+        // The small blind opens to 2.5, the big blind makes it 7.5 and the small blind calls
+        assert_eq!(state.pot, 15.0);
+        assert_eq!(state.current_player, 1);
+        assert!(FlopTexture::Paired.matches(&state.public_cards));
+
+        let short = SpotSpec {
+            stack_bb: 5.0,
+            ..spec.clone()
+        };
+        assert!(generate_spots(&short, 1, 0).is_err());
+        assert!(
+            SpotSpec::checked(6, 2, 2, PotType::Limped, 100.0, None, None, None, 2.5, 3.0).is_err()
+        );
+    }
+}
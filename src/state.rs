@@ -2,25 +2,65 @@
 #[cfg(test)]
 use proptest_derive::Arbitrary;
 use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
 pub mod action;
 pub mod card;
+pub mod deck;
+pub mod rules;
+pub mod schema;
 pub mod stage;
+pub mod trace;
+pub mod view;
+use crate::hand_strength::strength_buckets;
+use crate::profiling::PerfProbe;
+use crate::range::{hand_class, preflop_rank};
 use action::{ActionEnum, ActionRecord};
 use card::Card;
+use rules::TableRules;
+use smallvec::SmallVec;
 use stage::Stage;
+use trace::TraceEvent;
+
+/// Players kept inline, tables up to this size never allocate for their players
+pub const INLINE_PLAYERS: usize = 10;
+
+/// Seats of a hand, inline up to `INLINE_PLAYERS`
+pub type Players = SmallVec<[PlayerState; INLINE_PLAYERS]>;
+/// Community cards, never more than five on a board
+pub type Board = SmallVec<[Card; 5]>;
+/// Fold, CheckCall and BetRaise at most
+pub type LegalActions = SmallVec<[ActionEnum; 3]>;
+
+#[cfg(test)]
+fn small_vec<A: smallvec::Array>(
+    max_len: usize,
+) -> impl proptest::strategy::Strategy<Value = SmallVec<A>>
+where
+    A::Item: proptest::arbitrary::Arbitrary,
+{
+    use proptest::strategy::Strategy;
+    proptest::collection::vec(proptest::arbitrary::any::<A::Item>(), 0..=max_len)
+        .prop_map(SmallVec::from_vec)
+}
 
 #[pyclass]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[cfg_attr(test, derive(Arbitrary))]
 pub struct State {
     #[pyo3(get, set)]
     pub current_player: u64,
 
-    #[pyo3(get, set)]
-    pub players_state: Vec<PlayerState>,
+    /// Read through `State::py_players_state`, which hides the hands the perspective cannot see
+    #[cfg_attr(test, proptest(strategy = "small_vec(INLINE_PLAYERS)"))]
+    pub players_state: Players,
+
+    /// Read and written as a list through `State::py_public_cards`
+    #[cfg_attr(test, proptest(strategy = "small_vec(5)"))]
+    pub public_cards: Board,
 
+    /// Second board of a double-board bomb pot, empty otherwise
     #[pyo3(get, set)]
-    pub public_cards: Vec<Card>,
+    pub second_board: Vec<Card>,
 
     #[pyo3(get, set)]
     pub stage: Stage,
@@ -34,10 +74,12 @@ pub struct State {
     #[pyo3(get, set)]
     pub action_list: Vec<ActionRecord>,
 
-    #[pyo3(get, set)]
-    pub legal_actions: Vec<ActionEnum>,
+    /// Read and written as a list through `State::py_legal_actions`
+    #[cfg_attr(test, proptest(strategy = "small_vec(3)"))]
+    pub legal_actions: LegalActions,
 
-    #[pyo3(get, set)]
+    /// Read through `State::py_deck`, only for a full view
+    #[pyo3(set)]
     pub deck: Vec<Card>,
 
     #[pyo3(get, set)]
@@ -64,20 +106,98 @@ pub struct State {
     #[pyo3(get, set)]
     pub seed: u64,
 
+    /// Rule variations, see `State::with_rules`
+    #[pyo3(get)]
+    pub rules: TableRules,
+
+    /// Time the current player's turn started, in seconds since the Unix epoch.
+    /// `None` unless the clock was started with `State::with_clock`.
+    #[pyo3(get)]
+    pub clock: Option<f64>,
+
+    /// Equities of the players still in when the betting closed before the river and the
+    /// board was run out, one entry per street from that point. Empty otherwise.
+    #[pyo3(get)]
+    pub all_in_equities: Vec<AllInEquity>,
+
+    /// JSON report when `status` is `EngineError`: the `reason`, the `state` document the
+    /// failing `actions` were applied to, which replay the error. It holds the deck and every
+    /// hand, so Python only reads it from `unsafe_full_view`.
+    pub engine_diagnostic: Option<String>,
+
+    /// Decisions the engine took since the hand started, in order: why a player was passed
+    /// over, why a betting round ended, why the board was run out. Only collected while
+    /// `verbose` is set.
+    #[pyo3(get)]
+    #[cfg_attr(test, proptest(value = "Vec::new()"))]
+    pub trace: Vec<TraceEvent>,
+
+    /// Unique id of the hand, a random UUID unless set with `State::with_ids`. States that
+    /// follow from each other share it.
+    #[pyo3(get)]
+    pub hand_id: String,
+
+    /// Table the hand is played at, when set with `State::with_ids`
+    #[pyo3(get)]
+    pub table_id: Option<String>,
+
+    /// Session (run of hands) the hand belongs to, when set with `State::with_ids`
+    #[pyo3(get)]
+    pub session_id: Option<String>,
+
+    /// Player whose hand the Python getters show, nobody's when `None`.
+    /// See `State::with_perspective`.
+    #[pyo3(get)]
+    pub perspective: Option<u64>,
+
+    /// Whether the Python getters show the deck and every hand, see `State::unsafe_full_view`
+    #[pyo3(get)]
+    pub full_view: bool,
+
+    /// Number of hand strength buckets `range_idx` takes after the flop, see
+    /// `State::with_range_buckets`. `None` keeps the preflop index the hand was dealt with.
+    #[pyo3(get)]
+    #[cfg_attr(test, proptest(value = "None"))]
+    #[serde(default)]
+    pub range_buckets: Option<u32>,
+
+    /// Street from which the players of a Pineapple hand discard their third card, see
+    /// `State::pineapple`. `None` for hold'em hands.
+    #[pyo3(get)]
+    #[cfg_attr(test, proptest(value = "None"))]
+    #[serde(default)]
+    pub discard_on: Option<Stage>,
+
+    /// Big blinds owed by players who missed them, and how each is settled on this hand, see
+    /// `State::owe_blinds`
+    #[pyo3(get)]
+    #[cfg_attr(test, proptest(value = "Vec::new()"))]
+    #[serde(default)]
+    pub owed_blinds: Vec<BlindObligation>,
+
+    /// Work done by the action that led to this state, see `State::perf_counters`
+    #[cfg_attr(test, proptest(value = "PerfProbe::default()"))]
+    #[serde(skip)]
+    pub perf: PerfProbe,
+
     // Internal state machine context (not exposed to Python directly)
     pub fsm_state: String, // Store state machine state as string for serialization
 }
 
 #[pyclass]
-#[derive(Debug, Clone, Copy)]
+#[derive(Clone, Copy, Serialize, Deserialize)]
 #[cfg_attr(test, derive(Arbitrary))]
 pub struct PlayerState {
     #[pyo3(get, set)]
     pub player: u64,
 
-    #[pyo3(get, set)]
+    /// Read through `PlayerState::py_hand`, `None` when hidden
+    #[pyo3(set)]
     pub hand: (Card, Card),
 
+    /// Set on the copies handed to Python when the state's perspective cannot see the hand
+    pub hand_hidden: bool,
+
     #[pyo3(get, set)]
     pub bet_chips: f64,
 
@@ -98,225 +218,160 @@ pub struct PlayerState {
 
     #[pyo3(get, set)]
     pub last_stage_action: Option<ActionEnum>,
+
+    /// Street the player folded on, `None` for a player who did not fold. Unlike `active`,
+    /// which every player loses once the hand is over, it is kept in the final state.
+    #[pyo3(get)]
+    pub folded_on: Option<Stage>,
+
+    /// Chips put in on each street from the preflop to the river, blinds and antes included.
+    /// A street is counted once its betting closes, and stays counted after a fold.
+    #[pyo3(get)]
+    pub invested: [f64; 4],
+
+    /// Chips of `pot_chips` posted as antes. Antes are dead money: bets are not matched against
+    /// them, and a player all-in for part of the ante only wins that much of each ante.
+    #[pyo3(get)]
+    #[cfg_attr(test, proptest(value = "0.0"))]
+    #[serde(default)]
+    pub ante_chips: f64,
+
+    /// Stack kept out of the hand by `State::apply_protected_all_in`, or over the hand cap of
+    /// `TableRules.hand_cap`. The chips are still the player's, on top of `stake`.
+    #[pyo3(get)]
+    pub held_back: f64,
+
+    /// Third hole card of a Pineapple hand, until the player discards. Read through
+    /// `PlayerState::py_third_card`, see `State::pineapple`.
+    #[cfg_attr(test, proptest(value = "None"))]
+    #[serde(default)]
+    pub third_card: Option<Card>,
+
+    /// Card the player of a Pineapple hand threw away, read through
+    /// `PlayerState::py_discarded`
+    #[cfg_attr(test, proptest(value = "None"))]
+    #[serde(default)]
+    pub discarded: Option<Card>,
 }
 
 #[pymethods]
 impl PlayerState {
+    #[getter(hand)]
+    pub fn py_hand(&self) -> Option<(Card, Card)> {
+        (!self.hand_hidden).then_some(self.hand)
+    }
+
+    #[getter(third_card)]
+    pub fn py_third_card(&self) -> Option<Card> {
+        self.third_card.filter(|_| !self.hand_hidden)
+    }
+
+    #[getter(discarded)]
+    pub fn py_discarded(&self) -> Option<Card> {
+        self.discarded.filter(|_| !self.hand_hidden)
+    }
+
     pub fn __str__(&self) -> PyResult<String> {
         Ok(format!("{:#?}", self))
     }
 }
 
+impl std::fmt::Debug for PlayerState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PlayerState")
+            .field("player", &self.player)
+            .field("hand", &self.py_hand())
+            .field("bet_chips", &self.bet_chips)
+            .field("pot_chips", &self.pot_chips)
+            .field("stake", &self.stake)
+            .field("reward", &self.reward)
+            .field("active", &self.active)
+            .field("range_idx", &self.range_idx)
+            .field("last_stage_action", &self.last_stage_action)
+            .field("folded_on", &self.folded_on)
+            .field("invested", &self.invested)
+            .field("ante_chips", &self.ante_chips)
+            .field("held_back", &self.held_back)
+            .field("third_card", &self.py_third_card())
+            .field("discarded", &self.py_discarded())
+            .finish()
+    }
+}
+
+/// Share of the pot each player still in could expect on `stage`, over the runouts of the rest
+/// of the board, ties split
 #[pyclass]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(Arbitrary))]
+pub struct AllInEquity {
+    #[pyo3(get)]
+    pub stage: Stage,
+
+    #[pyo3(get)]
+    pub players: Vec<u64>,
+
+    /// Equity of each of `players`, in the same order
+    #[pyo3(get)]
+    pub equities: Vec<f64>,
+}
+
+#[pymethods]
+impl AllInEquity {
+    pub fn __str__(&self) -> PyResult<String> {
+        Ok(format!("{:#?}", self))
+    }
+}
+
+/// A big blind owed by a player who missed it, sitting out or joining mid-session
+#[pyclass]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlindObligation {
+    #[pyo3(get)]
+    pub player: u64,
+
+    /// Chips owed, the big blind of the hand
+    #[pyo3(get)]
+    pub amount: f64,
+
+    #[pyo3(get)]
+    pub settlement: BlindSettlement,
+}
+
+#[pymethods]
+impl BlindObligation {
+    pub fn __str__(&self) -> PyResult<String> {
+        Ok(format!("{:#?}", self))
+    }
+}
+
+/// How a missed big blind is settled on the hand
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BlindSettlement {
+    /// Not settled before the first action: the player plays the hand and still owes the blind
+    Pending,
+    /// The player is in the big blind and pays it as usual
+    BigBlind,
+    /// Posted dead into the pot
+    PostedDead,
+}
+
+#[pyclass]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 #[cfg_attr(test, derive(Arbitrary))]
 pub enum StateStatus {
     Ok,
     IllegalAction,
     HighBet,
+    EngineError,
 }
 
 impl State {
-    /// Hand ranking lookup table - maps card combination to rank (1-169)
-    /// Based on the C++ evaluate_2cards function
-    const HAND_RANKINGS: &'static [(i32, i32, i32, i32)] = &[
-        // Pairs (high_rank == low_rank, suited ignored)
-        (12, 12, 0, 1), // AA
-        (11, 11, 0, 2), // KK
-        (10, 10, 0, 3), // QQ
-        (9, 9, 0, 5),   // JJ
-        (8, 8, 0, 10),  // TT
-        (7, 7, 0, 17),  // 99
-        (6, 6, 0, 21),  // 88
-        (5, 5, 0, 29),  // 77
-        (4, 4, 0, 36),  // 66
-        (3, 3, 0, 46),  // 55
-        (2, 2, 0, 50),  // 44
-        (0, 0, 0, 51),  // 22
-        (1, 1, 0, 52),  // 33
-        // Suited hands (high_rank > low_rank, suited=1)
-        (12, 11, 1, 4), // AKs
-        (12, 10, 1, 6), // AQs
-        (11, 10, 1, 7), // KQs
-        (12, 9, 1, 8),  // AJs
-        (11, 9, 1, 9),  // KJs
-        (12, 8, 1, 12), // ATs
-        (10, 9, 1, 13), // QJs
-        (11, 8, 1, 14), // KTs
-        (10, 8, 1, 15), // QTs
-        (9, 8, 1, 16),  // JTs
-        (12, 7, 1, 19), // A9s
-        (11, 7, 1, 22), // K9s
-        (8, 7, 1, 23),  // T9s
-        (12, 6, 1, 24), // A8s
-        (10, 7, 1, 25), // Q9s
-        (9, 7, 1, 26),  // J9s
-        (12, 3, 1, 28), // A5s
-        (12, 5, 1, 30), // A7s
-        (11, 6, 1, 37), // K8s
-        (8, 6, 1, 38),  // T8s
-        (12, 0, 1, 39), // A2s
-        (7, 6, 1, 40),  // 98s
-        (9, 6, 1, 41),  // J8s
-        (10, 6, 1, 43), // Q8s
-        (11, 5, 1, 44), // K7s
-        (6, 5, 1, 48),  // 87s
-        (11, 4, 1, 53), // K6s
-        (7, 5, 1, 54),  // 97s
-        (11, 3, 1, 55), // K5s
-        (5, 4, 1, 56),  // 76s
-        (8, 5, 1, 57),  // T7s
-        (11, 2, 1, 58), // K4s
-        (11, 1, 1, 59), // K3s
-        (11, 0, 1, 60), // K2s
-        (10, 5, 1, 61), // Q7s
-        (6, 4, 1, 62),  // 86s
-        (4, 3, 1, 63),  // 65s
-        (9, 5, 1, 64),  // J7s
-        (3, 2, 1, 65),  // 54s
-        (10, 4, 1, 66), // Q6s
-        (5, 3, 1, 67),  // 75s
-        (7, 4, 1, 68),  // 96s
-        (10, 3, 1, 69), // Q5s
-        (4, 2, 1, 70),  // 64s
-        (10, 2, 1, 71), // Q4s
-        (10, 1, 1, 72), // Q3s
-        (8, 4, 1, 74),  // T6s
-        (10, 0, 1, 75), // Q2s
-        (3, 1, 1, 77),  // 53s
-        (6, 3, 1, 78),  // 85s
-        (9, 4, 1, 79),  // J6s
-        (9, 3, 1, 82),  // J5s
-        (2, 1, 1, 84),  // 43s
-        (5, 2, 1, 85),  // 74s
-        (9, 2, 1, 86),  // J4s
-        (9, 1, 1, 87),  // J3s
-        (7, 3, 1, 88),  // 95s
-        (9, 0, 1, 89),  // J2s
-        (4, 1, 1, 90),  // 63s
-        (3, 0, 1, 92),  // 52s
-        (8, 3, 1, 93),  // T5s
-        (6, 2, 1, 94),  // 84s
-        (8, 2, 1, 95),  // T4s
-        (8, 1, 1, 96),  // T3s
-        (2, 0, 1, 97),  // 42s
-        (8, 0, 1, 98),  // T2s
-        (5, 1, 1, 103), // 73s
-        (1, 0, 1, 105), // 32s
-        (7, 2, 1, 106), // 94s
-        (7, 1, 1, 107), // 93s
-        (4, 0, 1, 110), // 62s
-        (7, 0, 1, 111), // 92s
-        (6, 1, 1, 116), // 83s
-        (6, 0, 1, 118), // 82s
-        (5, 0, 1, 120), // 72s
-        // Offsuit hands (high_rank > low_rank, suited=0)
-        (12, 11, 0, 11), // AKo
-        (12, 10, 0, 18), // AQo
-        (11, 10, 0, 20), // KQo
-        (12, 9, 0, 27),  // AJo
-        (11, 9, 0, 31),  // KJo
-        (10, 9, 0, 35),  // QJo
-        (12, 8, 0, 42),  // ATo
-        (11, 8, 0, 45),  // KTo
-        (9, 8, 0, 47),   // JTo
-        (10, 8, 0, 49),  // QTo
-        (8, 7, 0, 73),   // T9o
-        (12, 7, 0, 76),  // A9o
-        (9, 7, 0, 80),   // J9o
-        (11, 7, 0, 81),  // K9o
-        (10, 7, 0, 83),  // Q9o
-        (12, 6, 0, 91),  // A8o
-        (7, 6, 0, 99),   // 98o
-        (8, 6, 0, 100),  // T8o
-        (12, 3, 0, 101), // A5o
-        (12, 5, 0, 102), // A7o
-        (12, 2, 0, 104), // A4o
-        (9, 6, 0, 108),  // J8o
-        (12, 1, 0, 109), // A3o
-        (11, 6, 0, 112), // K8o
-        (12, 4, 0, 113), // A6o
-        (6, 5, 0, 114),  // 87o
-        (10, 6, 0, 115), // Q8o
-        (12, 0, 0, 117), // A2o
-        (7, 5, 0, 119),  // 97o
-        (5, 4, 0, 121),  // 76o
-        (11, 5, 0, 122), // K7o
-        (4, 3, 0, 123),  // 65o
-        (8, 5, 0, 124),  // T7o
-        (11, 4, 0, 125), // K6o
-        (6, 4, 0, 126),  // 86o
-        (3, 2, 0, 127),  // 54o
-        (11, 3, 0, 128), // K5o
-        (9, 5, 0, 129),  // J7o
-        (5, 3, 0, 130),  // 75o
-        (10, 5, 0, 131), // Q7o
-        (11, 2, 0, 132), // K4o
-        (11, 1, 0, 133), // K3o
-        (7, 4, 0, 134),  // 96o
-        (11, 0, 0, 135), // K2o
-        (4, 2, 0, 136),  // 64o
-        (10, 4, 0, 137), // Q6o
-        (3, 1, 0, 138),  // 53o
-        (6, 3, 0, 139),  // 85o
-        (8, 4, 0, 140),  // T6o
-        (10, 3, 0, 141), // Q5o
-        (2, 1, 0, 142),  // 43o
-        (10, 2, 0, 143), // Q4o
-        (10, 1, 0, 144), // Q3o
-        (5, 2, 0, 145),  // 74o
-        (10, 0, 0, 146), // Q2o
-        (9, 4, 0, 147),  // J6o
-        (4, 1, 0, 148),  // 63o
-        (9, 3, 0, 149),  // J5o
-        (7, 3, 0, 150),  // 95o
-        (3, 0, 0, 151),  // 52o
-        (9, 2, 0, 152),  // J4o
-        (9, 1, 0, 153),  // J3o
-        (2, 0, 0, 154),  // 42o
-        (9, 0, 0, 155),  // J2o
-        (6, 2, 0, 156),  // 84o
-        (8, 3, 0, 157),  // T5o
-        (8, 2, 0, 158),  // T4o
-        (1, 0, 0, 159),  // 32o
-        (8, 1, 0, 160),  // T3o
-        (5, 1, 0, 161),  // 73o
-        (8, 0, 0, 162),  // T2o
-        (4, 0, 0, 163),  // 62o
-        (7, 2, 0, 164),  // 94o
-        (7, 1, 0, 165),  // 93o
-        (7, 0, 0, 166),  // 92o
-        (6, 1, 0, 167),  // 83o
-        (6, 0, 0, 168),  // 82o
-        (5, 0, 0, 169),  // 72o
-    ];
-
-    /// Evaluate 2 cards (hole cards in Texas Hold'em)
+    /// Preflop strength of 2 hole cards, see `range::preflop_rank`
     /// Returns ranking based on winning probability: 1 (strongest AA) to 169 (weakest 72o)
     /// Higher rank number = weaker hand
     fn evaluate_2cards(&self, card1: Card, card2: Card) -> i32 {
-        // Convert CardRank to 0-12 (where 0=2, 12=A)
-        let rank_a = card1.rank as i32;
-        let rank_b = card2.rank as i32;
-        let suit_a = card1.suit as i32;
-        let suit_b = card2.suit as i32;
-
-        // Ensure high_rank >= low_rank for table lookup
-        let high_rank = rank_a.max(rank_b);
-        let low_rank = rank_a.min(rank_b);
-
-        // Check if suited
-        let is_suited = if suit_a == suit_b { 1 } else { 0 };
-
-        // Search through the hand rankings table
-        for &(hr, lr, suited, rank) in Self::HAND_RANKINGS {
-            if hr == high_rank && lr == low_rank && (high_rank == low_rank || suited == is_suited) {
-                return rank;
-            }
-        }
-
-        // Fallback - should never reach here with valid input
-        169
+        preflop_rank(hand_class((card1, card2))) as i32
     }
 
     /// Calculate range index for a player based on their hole cards
@@ -335,6 +390,10 @@ impl State {
             let hand_rank = self.evaluate_2cards(hand.0, hand.1);
             // Convert from 1-169 to 0-168 index
             return (hand_rank - 1) as i64;
+        } else if let Some(n_buckets) = self.range_buckets {
+            // Postflop with buckets: strength of the hand on the board
+            let evaluator = self.rules.variant.evaluator();
+            strength_buckets(evaluator, &[hand], &self.public_cards, n_buckets)[0] as i64
         } else {
             // Postflop: use canonical suit mapping approach
             // Get canonical suit mapping from community cards
@@ -408,6 +467,16 @@ impl State {
 
     /// Update range_idx for all players
     pub fn update_range_indices(&mut self) {
+        if let (Some(n_buckets), false) = (self.range_buckets, self.stage == Stage::Preflop) {
+            // Rank the opponent hands once for every player
+            let hands: Vec<(Card, Card)> = self.players_state.iter().map(|ps| ps.hand).collect();
+            let evaluator = self.rules.variant.evaluator();
+            let buckets = strength_buckets(evaluator, &hands, &self.public_cards, n_buckets);
+            for (player, bucket) in self.players_state.iter_mut().zip(buckets) {
+                player.range_idx = bucket as i64;
+            }
+            return;
+        }
         for i in 0..self.players_state.len() {
             let range_idx = self.calculate_range_idx(i);
             self.players_state[i].range_idx = range_idx;
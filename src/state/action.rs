@@ -4,10 +4,11 @@ use crate::state::stage::Stage;
 #[cfg(test)]
 use proptest_derive::Arbitrary;
 use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
 use strum_macros::EnumIter;
 
 #[pyclass]
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 #[cfg_attr(test, derive(Arbitrary))]
 pub struct Action {
     #[pyo3(get, set)]
@@ -30,7 +31,7 @@ impl Action {
 }
 
 #[pyclass]
-#[derive(Debug, Clone, Copy, EnumIter, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, EnumIter, PartialEq, Eq, Serialize, Deserialize)]
 #[cfg_attr(test, derive(Arbitrary))]
 pub enum ActionEnum {
     Fold,
@@ -38,8 +39,39 @@ pub enum ActionEnum {
     BetRaise,
 }
 
+/// Action forced on a player who did not act in time
 #[pyclass]
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Copy, EnumIter, PartialEq, Eq, Default)]
+#[cfg_attr(test, derive(Arbitrary))]
+pub enum DefaultPolicy {
+    /// Check when there is nothing to call, fold otherwise
+    #[default]
+    CheckElseFold,
+    Fold,
+    CheckCall,
+}
+
+/// Rule by which the engine replaced the action a player asked for. The values start at 1 so
+/// that 0 can stand for a genuine decision in datasets.
+#[pyclass]
+#[derive(Debug, Clone, Copy, EnumIter, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(Arbitrary))]
+pub enum Coercion {
+    /// The player ran out of time and their `DefaultPolicy` acted
+    Timeout = 1,
+    /// The action was not legal: a call became a fold, a bet a call or a fold
+    IllegalAction = 2,
+    /// The bet was below the minimum raise and was raised to it
+    MinRaise = 3,
+    /// The player was put all-in: the bet was more than the stack, the stack was below the
+    /// minimum raise, or calling would have left less than one chip
+    AllIn = 4,
+    /// The player was disconnected and stays in, all-in for the chips already put in
+    Disconnected = 5,
+}
+
+#[pyclass]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[cfg_attr(test, derive(Arbitrary))]
 pub struct ActionRecord {
     #[pyo3(get, set)]
@@ -53,4 +85,37 @@ pub struct ActionRecord {
 
     #[pyo3(get, set)]
     pub legal_actions: Vec<ActionEnum>,
+
+    /// Wall-clock time of the action in seconds since the Unix epoch, when the clock is on
+    #[pyo3(get, set)]
+    pub timestamp: Option<f64>,
+
+    /// Seconds the player took to act, when the clock is on
+    #[pyo3(get, set)]
+    pub think_time: Option<f64>,
+
+    /// Applied by `State.apply_default_action` or `State.apply_protected_all_in` rather than
+    /// chosen by the player
+    #[pyo3(get, set)]
+    pub forced: bool,
+
+    /// The action as asked for, before the engine changed it; the policy's action on a timeout
+    #[pyo3(get, set)]
+    pub requested: Action,
+
+    /// Why `action` differs from `requested`, `None` for a genuine decision
+    #[pyo3(get, set)]
+    pub coercion: Option<Coercion>,
+
+    /// Pot before the action, the bets of the street included
+    #[pyo3(get, set)]
+    pub pot_before: f64,
+
+    /// Chips the player needed to call, 0 when they could check
+    #[pyo3(get, set)]
+    pub to_call: f64,
+
+    /// Chips the action put in
+    #[pyo3(get, set)]
+    pub chips: f64,
 }
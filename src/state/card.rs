@@ -2,12 +2,17 @@
 #![allow(unused)]
 #[cfg(test)]
 use proptest_derive::Arbitrary;
+use pyo3::basic::CompareOp;
 use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
 use strum::IntoEnumIterator;
 use strum_macros::EnumIter;
 
+/// Cards in a deck, the length of one-hot card encodings
+pub const N_CARDS: usize = 52;
+
 #[pyclass]
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[cfg_attr(test, derive(Arbitrary))]
 pub struct Card {
     #[pyo3(get, set)]
@@ -73,6 +78,34 @@ impl Card {
             .flat_map(|&s| ranks.iter().map(move |&r| Card { suit: s, rank: r }))
             .collect::<Vec<Card>>()
     }
+
+    /// Index from 0 to 51, `suit * 13 + rank`: the position of the card in `collect()`
+    pub fn index(&self) -> u8 {
+        self.suit as u8 * 13 + self.rank as u8
+    }
+
+    #[staticmethod]
+    pub fn from_index(index: u8) -> Option<Card> {
+        let suit = CardSuit::iter().nth(index as usize / 13)?;
+        let rank = CardRank::iter().nth(index as usize % 13)?;
+        Some(Card { suit, rank })
+    }
+
+    /// 52 zeros with a one at `index()`
+    pub fn one_hot(&self) -> Vec<f32> {
+        let mut encoding = vec![0.0; N_CARDS];
+        encoding[self.index() as usize] = 1.0;
+        encoding
+    }
+
+    pub fn __hash__(&self) -> u64 {
+        self.index() as u64
+    }
+
+    /// Cards are equal when they have the same index and ordered by it
+    pub fn __richcmp__(&self, other: &Card, op: CompareOp) -> bool {
+        op.matches(self.index().cmp(&other.index()))
+    }
 }
 
 impl core::fmt::Display for Card {
@@ -83,17 +116,14 @@ impl core::fmt::Display for Card {
             CardSuit::Hearts => '♥',
             CardSuit::Spades => '♠',
         };
-        write!(
-            f,
-            "{}{}",
-            format!("{:?}", self.rank).chars().nth(1).unwrap(),
-            suit_symbol
-        )
+        write!(f, "{}{}", self.rank.to_char(), suit_symbol)
     }
 }
 
 #[pyclass]
-#[derive(Debug, Clone, Copy, EnumIter, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(
+    Debug, Clone, Copy, EnumIter, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
 #[cfg_attr(test, derive(Arbitrary))]
 pub enum CardSuit {
     Clubs,
@@ -103,7 +133,9 @@ pub enum CardSuit {
 }
 
 #[pyclass]
-#[derive(Debug, Clone, Copy, EnumIter, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(
+    Debug, Clone, Copy, EnumIter, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
 #[cfg_attr(test, derive(Arbitrary))]
 pub enum CardRank {
     R2,
@@ -120,3 +152,31 @@ pub enum CardRank {
     RK,
     RA,
 }
+
+impl CardRank {
+    /// The rank as written in card names, '2' to '9', 'T', 'J', 'Q', 'K' and 'A'
+    pub fn to_char(self) -> char {
+        "23456789TJQKA".as_bytes()[self as usize] as char
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn indices_follow_the_deck_order() {
+        for (i, card) in Card::collect().into_iter().enumerate() {
+            assert_eq!(card.index() as usize, i);
+            assert_eq!(Card::from_index(i as u8), Some(card));
+            assert_eq!(card.one_hot().iter().position(|&x| x == 1.0), Some(i));
+        }
+        assert_eq!(Card::from_index(N_CARDS as u8), None);
+        assert_eq!(Card::from_string("SA".to_string()).unwrap().index(), 51);
+        for card in Card::collect() {
+            let suit = format!("{:?}", card.suit).chars().next().unwrap();
+            let name = format!("{}{}", suit, card.rank.to_char());
+            assert_eq!(Card::from_string(name), Some(card));
+        }
+    }
+}
@@ -0,0 +1,127 @@
+// state/deck.rs
+use crate::state::card::Card;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use rand::{seq::SliceRandom, SeedableRng};
+
+/// A deck that keeps track of the cards dealt and burned, so a card can never be used twice
+#[pyclass]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Deck {
+    cards: Vec<Card>,
+    dealt: Vec<Card>,
+    burned: Vec<Card>,
+}
+
+#[pymethods]
+impl Deck {
+    /// A full deck, shuffled like `State.from_seed` when a seed is given
+    #[new]
+    #[pyo3(signature = (seed=None))]
+    pub fn new(seed: Option<u64>) -> Deck {
+        let mut deck = Deck {
+            cards: Card::collect(),
+            dealt: Vec::new(),
+            burned: Vec::new(),
+        };
+        if let Some(seed) = seed {
+            deck.shuffle(seed);
+        }
+        deck
+    }
+
+    /// Shuffle the cards still in the deck
+    pub fn shuffle(&mut self, seed: u64) {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+        self.cards.shuffle(&mut rng);
+    }
+
+    pub fn deal(&mut self, n: usize) -> PyResult<Vec<Card>> {
+        if n > self.cards.len() {
+            return Err(PyValueError::new_err(format!(
+                "Cannot deal {} cards, only {} left",
+                n,
+                self.cards.len()
+            )));
+        }
+        let cards: Vec<Card> = self.cards.drain(..n).collect();
+        self.dealt.extend_from_slice(&cards);
+        Ok(cards)
+    }
+
+    pub fn burn(&mut self) -> PyResult<Card> {
+        if self.cards.is_empty() {
+            return Err(PyValueError::new_err(
+                "Cannot burn a card from an empty deck",
+            ));
+        }
+        let card = self.cards.remove(0);
+        self.burned.push(card);
+        Ok(card)
+    }
+
+    /// Take known cards (hole cards, a fixed board) out of the deck
+    pub fn remove(&mut self, cards: Vec<Card>) -> PyResult<()> {
+        for (i, card) in cards.iter().enumerate() {
+            if !self.cards.contains(card) || cards[..i].contains(card) {
+                return Err(PyValueError::new_err(format!(
+                    "Card {} is not in the deck",
+                    card
+                )));
+            }
+        }
+        self.cards.retain(|c| !cards.contains(c));
+        self.dealt.extend_from_slice(&cards);
+        Ok(())
+    }
+
+    /// Cards still in the deck, in dealing order
+    pub fn remaining(&self) -> Vec<Card> {
+        self.cards.clone()
+    }
+
+    /// Cards dealt or removed so far
+    pub fn dealt(&self) -> Vec<Card> {
+        self.dealt.clone()
+    }
+
+    pub fn burned(&self) -> Vec<Card> {
+        self.burned.clone()
+    }
+
+    pub fn __len__(&self) -> usize {
+        self.cards.len()
+    }
+
+    pub fn __str__(&self) -> PyResult<String> {
+        Ok(format!("{:#?}", self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::State;
+
+    #[test]
+    fn seeded_deck_matches_from_seed() {
+        let state = State::from_seed(2, 0, 0.5, 1.0, 100.0, 42, false, 0.0).unwrap();
+        let mut deck = Deck::new(Some(42));
+
+        let hands = deck.deal(4).unwrap();
+        assert_eq!(hands[0], state.players_state[1].hand.0);
+        assert_eq!(deck.remaining(), state.deck);
+
+        let burned = deck.burn().unwrap();
+        assert_eq!(deck.burned(), vec![burned]);
+        assert_eq!(
+            deck.__len__() + deck.dealt().len() + deck.burned().len(),
+            52
+        );
+
+        let next = deck.remaining()[0];
+        deck.remove(vec![next]).unwrap();
+        assert!(deck.remove(vec![next]).is_err());
+        assert!(deck.deal(100).is_err());
+    }
+}
@@ -0,0 +1,184 @@
+// state/rules.rs
+#![allow(unused)]
+use crate::evaluator::{AceToFive, Evaluator, Holdem, ShortDeck};
+#[cfg(test)]
+use proptest_derive::Arbitrary;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Game played at the table, which selects the hand evaluator
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[cfg_attr(test, derive(Arbitrary))]
+pub enum GameVariant {
+    #[default]
+    Holdem,
+    /// Short deck (6+) hold'em. The engine still deals the deck it is given, so short deck
+    /// hands are created with `State::from_deck` and a 36-card deck.
+    ShortDeck,
+    /// Ace-to-five lowball: the lowest hand wins the pot
+    AceToFive,
+}
+
+impl GameVariant {
+    pub fn evaluator(self) -> &'static dyn Evaluator {
+        match self {
+            GameVariant::Holdem => &Holdem,
+            GameVariant::ShortDeck => &ShortDeck,
+            GameVariant::AceToFive => &AceToFive,
+        }
+    }
+}
+
+/// How a player who takes a seat once the session has started joins the game, see
+/// `missed_blind_seating`
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MissedBlinds {
+    /// Post a dead big blind on the next hand
+    #[default]
+    PostDead,
+    /// Sit out until the big blind reaches them
+    WaitForBigBlind,
+}
+
+/// Home-game rule variations applied by the engine on top of standard no-limit hold'em
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(Arbitrary))]
+#[serde(default)]
+pub struct TableRules {
+    /// A raise must be to at least this multiple of the current bet (1.0: any raise)
+    #[pyo3(get, set)]
+    pub min_raise_ratio: f64,
+
+    /// Cap on bets and raises in a single betting round, as in limit games
+    #[pyo3(get, set)]
+    pub max_raises_per_street: Option<u32>,
+
+    /// Blinds multiplier for the hand after a player wins two pots in a row.
+    /// Kill pots span hands, so they are triggered by the server and not by a single `State`.
+    #[pyo3(get, set)]
+    pub kill_pot_multiplier: Option<f64>,
+
+    /// Paid by every other player to whoever wins a pot holding seven-deuce (0.0: off)
+    #[pyo3(get, set)]
+    pub seven_deuce_bounty: f64,
+
+    /// Deal a bomb pot (see `State::bomb_pot`) every N hands, when `bomb_pot_ante` is set.
+    /// Applied by the server.
+    #[pyo3(get, set)]
+    pub bomb_pot_every: Option<u32>,
+
+    #[pyo3(get, set)]
+    pub bomb_pot_ante: f64,
+
+    #[pyo3(get, set)]
+    pub bomb_pot_double_board: bool,
+
+    #[pyo3(get, set)]
+    pub variant: GameVariant,
+
+    /// Record `State.all_in_equities` when the board is run out after an all-in. Off by
+    /// default, as a preflop all-in costs thousands of hand evaluations.
+    #[pyo3(get, set)]
+    pub report_all_in_equities: bool,
+
+    /// Most a player can put in a single hand, blinds and antes included. A player who has
+    /// put in the cap is all-in, the rest of their stack stays out of the hand.
+    #[pyo3(get, set)]
+    pub hand_cap: Option<f64>,
+}
+
+impl Default for TableRules {
+    fn default() -> Self {
+        TableRules {
+            min_raise_ratio: 1.0,
+            max_raises_per_street: None,
+            kill_pot_multiplier: None,
+            seven_deuce_bounty: 0.0,
+            bomb_pot_every: None,
+            bomb_pot_ante: 0.0,
+            bomb_pot_double_board: false,
+            variant: GameVariant::Holdem,
+            report_all_in_equities: false,
+            hand_cap: None,
+        }
+    }
+}
+
+impl TableRules {
+    /// The rules, if their amounts and multipliers are finite and positive. The bounty and the
+    /// bomb pot ante can also be 0.
+    pub fn checked(self) -> Result<Self, String> {
+        let positive = |value: f64| value.is_finite() && value > 0.0;
+        if !positive(self.min_raise_ratio) {
+            return Err(format!(
+                "The minimum raise ratio must be positive, got {}",
+                self.min_raise_ratio
+            ));
+        }
+        if let Some(multiplier) = self.kill_pot_multiplier.filter(|&m| !positive(m)) {
+            return Err(format!(
+                "The kill pot multiplier must be positive, got {}",
+                multiplier
+            ));
+        }
+        if !(self.seven_deuce_bounty.is_finite() && self.seven_deuce_bounty >= 0.0) {
+            return Err(format!(
+                "The seven-deuce bounty cannot be negative, got {}",
+                self.seven_deuce_bounty
+            ));
+        }
+        if !(self.bomb_pot_ante.is_finite() && self.bomb_pot_ante >= 0.0) {
+            return Err(format!(
+                "The bomb pot ante cannot be negative, got {}",
+                self.bomb_pot_ante
+            ));
+        }
+        if let Some(cap) = self.hand_cap.filter(|&cap| !positive(cap)) {
+            return Err(format!("The hand cap must be positive, got {}", cap));
+        }
+        Ok(self)
+    }
+}
+
+#[pymethods]
+impl TableRules {
+    /// Rules are given by keyword, as most of them are left to their defaults
+    #[new]
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (*, min_raise_ratio=1.0, max_raises_per_street=None, kill_pot_multiplier=None, seven_deuce_bounty=0.0, bomb_pot_every=None, bomb_pot_ante=0.0, bomb_pot_double_board=false, variant=GameVariant::Holdem, report_all_in_equities=false, hand_cap=None))]
+    pub fn new(
+        min_raise_ratio: f64,
+        max_raises_per_street: Option<u32>,
+        kill_pot_multiplier: Option<f64>,
+        seven_deuce_bounty: f64,
+        bomb_pot_every: Option<u32>,
+        bomb_pot_ante: f64,
+        bomb_pot_double_board: bool,
+        variant: GameVariant,
+        report_all_in_equities: bool,
+        hand_cap: Option<f64>,
+    ) -> PyResult<TableRules> {
+        TableRules {
+            min_raise_ratio,
+            max_raises_per_street,
+            kill_pot_multiplier,
+            seven_deuce_bounty,
+            bomb_pot_every,
+            bomb_pot_ante,
+            bomb_pot_double_board,
+            variant,
+            report_all_in_equities,
+            hand_cap,
+        }
+        .checked()
+        .map_err(PyValueError::new_err)
+    }
+
+    pub fn __str__(&self) -> PyResult<String> {
+        Ok(format!("{:#?}", self))
+    }
+}
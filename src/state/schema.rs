@@ -0,0 +1,121 @@
+// state/schema.rs
+use crate::state::State;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use serde_json::{json, Value};
+
+/// Version of the documents `State::to_json` writes. Bump it when a change to `State`, or
+/// to a type it holds, stops older documents from reading back, and add the migration from
+/// the previous version to `MIGRATIONS`. A field added with `#[serde(default)]` needs
+/// neither.
+pub const SCHEMA_VERSION: u64 = 1;
+
+/// Rewrites a document in place from one version to the next
+pub type Migration = fn(&mut Value) -> Result<(), String>;
+
+/// `MIGRATIONS[i]` upgrades a document of version `i + 1` to version `i + 2`
+const MIGRATIONS: &[Migration] = &[];
+
+/// Versioned document of `state`, with every hand and the deck
+pub fn to_document(state: &State) -> Result<Value, String> {
+    let state = serde_json::to_value(state).map_err(|e| e.to_string())?;
+    Ok(json!({ "schema_version": SCHEMA_VERSION, "state": state }))
+}
+
+/// Read a state back from a document of this or an older version
+pub fn from_document(document: Value) -> Result<State, String> {
+    let mut document = migrate(document)?;
+    serde_json::from_value(document["state"].take())
+        .map_err(|e| format!("Invalid state document: {}", e))
+}
+
+/// Upgrade a document of an older version to `SCHEMA_VERSION`
+pub fn migrate(document: Value) -> Result<Value, String> {
+    migrate_with(document, MIGRATIONS)
+}
+
+/// Upgrade the JSON document of a state to `SCHEMA_VERSION`, e.g. to store it again
+#[pyfunction]
+pub fn migrate_state(json: &str) -> PyResult<String> {
+    let document = serde_json::from_str(json)
+        .map_err(|e| PyValueError::new_err(format!("Invalid state document: {}", e)))?;
+    migrate(document)
+        .map(|document| document.to_string())
+        .map_err(PyValueError::new_err)
+}
+
+fn migrate_with(mut document: Value, migrations: &[Migration]) -> Result<Value, String> {
+    let latest = migrations.len() as u64 + 1;
+    let mut version = match document.get("schema_version").and_then(Value::as_u64) {
+        Some(version) if (1..=latest).contains(&version) => version,
+        Some(version) if version > latest => {
+            return Err(format!(
+                "State schema version {} is newer than the version {} this release reads",
+                version, latest
+            ))
+        }
+        _ => return Err("Not a state document: no valid schema_version".to_owned()),
+    };
+    while version < latest {
+        migrations[version as usize - 1](&mut document)
+            .map_err(|e| format!("Migrating from schema version {}: {}", version, e))?;
+        version += 1;
+        document["schema_version"] = json!(version);
+    }
+    Ok(document)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::action::{Action, ActionEnum};
+
+    #[test]
+    fn documents_read_back() {
+        let state = State::from_seed(3, 0, 1.0, 2.0, 100.0, 11, true, 0.0).unwrap();
+        let state = state.apply_action(Action::new(ActionEnum::BetRaise, 6.0));
+        let state = state.apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+
+        let document = to_document(&state).unwrap();
+        assert_eq!(document["schema_version"], json!(SCHEMA_VERSION));
+        let read = from_document(document.clone()).unwrap();
+        assert_eq!(to_document(&read).unwrap(), document);
+
+        // The hand goes on from where it was saved
+        let next = Action::new(ActionEnum::CheckCall, 0.0);
+        assert_eq!(
+            to_document(&read.apply_action(next)).unwrap()["state"]["action_list"],
+            to_document(&state.apply_action(next)).unwrap()["state"]["action_list"]
+        );
+    }
+
+    #[test]
+    fn every_version_has_a_migration() {
+        assert_eq!(MIGRATIONS.len() as u64 + 1, SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn older_documents_are_migrated() {
+        fn rename_pot(document: &mut Value) -> Result<(), String> {
+            let pot = document["state"]
+                .as_object_mut()
+                .and_then(|state| state.remove("chips"))
+                .ok_or("no chips")?;
+            document["state"]["pot"] = pot;
+            Ok(())
+        }
+
+        let old = json!({ "schema_version": 1, "state": { "chips": 3.0 } });
+        let document = migrate_with(old, &[rename_pot]).unwrap();
+        assert_eq!(
+            document,
+            json!({ "schema_version": 2, "state": { "pot": 3.0 } })
+        );
+
+        let newer = json!({ "schema_version": 3, "state": {} });
+        assert!(migrate_with(newer, &[rename_pot]).is_err());
+        let broken = json!({ "schema_version": 1, "state": {} });
+        assert!(migrate_with(broken, &[rename_pot]).is_err());
+        assert!(migrate(json!({ "state": {} })).is_err());
+    }
+}
@@ -2,11 +2,17 @@
 #![allow(unused)]
 #[cfg(test)]
 use proptest_derive::Arbitrary;
+use pyo3::basic::CompareOp;
+use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
 use strum_macros::EnumIter;
 
 #[pyclass]
-#[derive(Debug, Clone, Copy, PartialEq, EnumIter)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, EnumIter, Serialize, Deserialize,
+)]
 #[repr(u32)]
 #[cfg_attr(test, derive(Arbitrary))]
 pub enum Stage {
@@ -16,3 +22,87 @@ pub enum Stage {
     River = 3,
     Showdown = 4,
 }
+
+#[pymethods]
+impl Stage {
+    /// Street after this one, `None` after the showdown
+    pub fn next(&self) -> Option<Stage> {
+        match self {
+            Stage::Preflop => Some(Stage::Flop),
+            Stage::Flop => Some(Stage::Turn),
+            Stage::Turn => Some(Stage::River),
+            Stage::River => Some(Stage::Showdown),
+            Stage::Showdown => None,
+        }
+    }
+
+    /// Number of board cards dealt by this street
+    pub fn board_card_count(&self) -> usize {
+        match self {
+            Stage::Preflop => 0,
+            Stage::Flop => 3,
+            Stage::Turn => 4,
+            Stage::River | Stage::Showdown => 5,
+        }
+    }
+
+    /// Street named `name`, case insensitive: "preflop" ("pre-flop"), "flop", "turn", "river"
+    /// or "showdown" ("show down")
+    #[staticmethod]
+    #[pyo3(name = "from_str")]
+    pub fn parse(name: &str) -> PyResult<Stage> {
+        name.parse().map_err(PyValueError::new_err)
+    }
+
+    pub fn __str__(&self) -> &'static str {
+        match self {
+            Stage::Preflop => "preflop",
+            Stage::Flop => "flop",
+            Stage::Turn => "turn",
+            Stage::River => "river",
+            Stage::Showdown => "showdown",
+        }
+    }
+
+    fn __richcmp__(&self, other: Stage, op: CompareOp) -> bool {
+        op.matches(self.cmp(&other))
+    }
+
+    fn __hash__(&self) -> u64 {
+        *self as u64
+    }
+}
+
+impl FromStr for Stage {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.trim().to_lowercase().as_str() {
+            "preflop" | "pre-flop" => Ok(Stage::Preflop),
+            "flop" => Ok(Stage::Flop),
+            "turn" => Ok(Stage::Turn),
+            "river" => Ok(Stage::River),
+            "showdown" | "show down" => Ok(Stage::Showdown),
+            _ => Err(format!("Unknown street {:?}", name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn streets_follow_each_other() {
+        assert_eq!(Stage::Preflop.next(), Some(Stage::Flop));
+        assert_eq!(Stage::Showdown.next(), None);
+        assert!(Stage::Flop < Stage::Turn);
+        assert_eq!(Stage::River.board_card_count(), 5);
+
+        for name in ["preflop", "flop", "turn", "river", "showdown"] {
+            assert_eq!(name.parse::<Stage>().unwrap().__str__(), name);
+        }
+        assert_eq!("Show Down".parse(), Ok(Stage::Showdown));
+        assert!("fourth street".parse::<Stage>().is_err());
+    }
+}
@@ -0,0 +1,104 @@
+// state/trace.rs
+use crate::state::action::ActionEnum;
+use crate::state::stage::Stage;
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Decision the engine took, see `TraceEvent`
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TraceKind {
+    /// `players[0]` played `action` for `amount`, after the engine made it legal
+    Action,
+    /// `players[0]` was passed over because they folded
+    SkippedFolded,
+    /// `players[0]` was passed over because they are all-in
+    SkippedAllIn,
+    /// Everybody acted and matched the bets, or is all-in
+    RoundOverBetsMatched,
+    /// Everybody else folded
+    RoundOverOnePlayerLeft,
+    /// No player left in the hand can bet
+    RoundOverNobodyToAct,
+    /// The betting moved on to `stage`, dealing `amount` cards
+    StreetDealt,
+    /// The board was run out because everybody else folded
+    ShowdownForcedOnePlayerLeft,
+    /// The board was run out because fewer than two players can still bet
+    ShowdownForcedAllIn,
+    /// The hands of `players` were compared
+    Showdown,
+    /// `players` won the pot
+    Winners,
+    /// The pot did not match the chips put in, `amount`. It is left as it is for
+    /// `State.check_invariants` to report.
+    PotDrift,
+    /// The hand was aborted, `detail` says why
+    EngineError,
+}
+
+/// One decision of the engine, collected in `State.trace` when the state is verbose
+#[pyclass]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TraceEvent {
+    #[pyo3(get)]
+    pub kind: TraceKind,
+
+    /// Street the decision was taken on
+    #[pyo3(get)]
+    pub stage: Stage,
+
+    /// Players the decision is about
+    #[pyo3(get)]
+    pub players: Vec<u64>,
+
+    #[pyo3(get)]
+    pub action: Option<ActionEnum>,
+
+    #[pyo3(get)]
+    pub amount: Option<f64>,
+
+    #[pyo3(get)]
+    pub detail: Option<String>,
+}
+
+impl TraceEvent {
+    pub fn new(kind: TraceKind, stage: Stage) -> Self {
+        Self {
+            kind,
+            stage,
+            players: Vec::new(),
+            action: None,
+            amount: None,
+            detail: None,
+        }
+    }
+
+    pub fn players(mut self, players: Vec<u64>) -> Self {
+        self.players = players;
+        self
+    }
+
+    pub fn action(mut self, action: ActionEnum, amount: f64) -> Self {
+        self.action = Some(action);
+        self.amount = Some(amount);
+        self
+    }
+
+    pub fn amount(mut self, amount: f64) -> Self {
+        self.amount = Some(amount);
+        self
+    }
+
+    pub fn detail(mut self, detail: &str) -> Self {
+        self.detail = Some(detail.to_owned());
+        self
+    }
+}
+
+#[pymethods]
+impl TraceEvent {
+    pub fn __str__(&self) -> PyResult<String> {
+        Ok(format!("{:#?}", self))
+    }
+}
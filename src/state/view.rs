@@ -0,0 +1,250 @@
+// state/view.rs - Read-only public view of a state, cheap to clone and share
+use crate::state::action::{ActionEnum, ActionRecord};
+use crate::state::card::Card;
+use crate::state::stage::Stage;
+use crate::state::State;
+use pyo3::prelude::*;
+use std::sync::Arc;
+
+/// What everybody at the table can see of a player
+#[pyclass]
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayerView {
+    #[pyo3(get)]
+    pub player: u64,
+
+    #[pyo3(get)]
+    pub stake: f64,
+
+    #[pyo3(get)]
+    pub bet_chips: f64,
+
+    #[pyo3(get)]
+    pub pot_chips: f64,
+
+    #[pyo3(get)]
+    pub active: bool,
+
+    #[pyo3(get)]
+    pub reward: f64,
+
+    #[pyo3(get)]
+    pub last_stage_action: Option<ActionEnum>,
+
+    /// Hole cards, once shown down
+    #[pyo3(get)]
+    pub shown_hand: Option<(Card, Card)>,
+}
+
+#[pymethods]
+impl PlayerView {
+    pub fn __str__(&self) -> PyResult<String> {
+        Ok(format!("{:#?}", self))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct PublicState {
+    current_player: u64,
+    players: Vec<PlayerView>,
+    public_cards: Vec<Card>,
+    second_board: Vec<Card>,
+    stage: Stage,
+    button: u64,
+    from_action: Option<ActionRecord>,
+    legal_actions: Vec<ActionEnum>,
+    pot: f64,
+    min_bet: f64,
+    sb: f64,
+    bb: f64,
+    final_state: bool,
+    hand_id: String,
+    table_id: Option<String>,
+    session_id: Option<String>,
+}
+
+/// The public information of a `State`, without the deck, the hidden hole cards or the
+/// action history. Clones share the same data, so a view can be handed to every observer
+/// of a table for the price of a reference count.
+#[pyclass]
+#[derive(Debug, Clone, PartialEq)]
+pub struct StateView {
+    inner: Arc<PublicState>,
+}
+
+impl StateView {
+    pub fn new(state: &State) -> StateView {
+        let players = state
+            .players_state
+            .iter()
+            .map(|ps| PlayerView {
+                player: ps.player,
+                stake: ps.stake,
+                bet_chips: ps.bet_chips,
+                pot_chips: ps.pot_chips,
+                active: ps.active,
+                reward: ps.reward,
+                last_stage_action: ps.last_stage_action,
+                shown_hand: state.showed_down(ps.player).then_some(ps.hand),
+            })
+            .collect();
+
+        StateView {
+            inner: Arc::new(PublicState {
+                current_player: state.current_player,
+                players,
+                public_cards: state.public_cards.to_vec(),
+                second_board: state.second_board.clone(),
+                stage: state.stage,
+                button: state.button,
+                from_action: state.from_action.clone(),
+                legal_actions: state.legal_actions.to_vec(),
+                pot: state.chips_in_pot(),
+                min_bet: state.min_bet,
+                sb: state.sb,
+                bb: state.bb,
+                final_state: state.final_state,
+                hand_id: state.hand_id.clone(),
+                table_id: state.table_id.clone(),
+                session_id: state.session_id.clone(),
+            }),
+        }
+    }
+
+    pub fn players(&self) -> &[PlayerView] {
+        &self.inner.players
+    }
+}
+
+#[pymethods]
+impl StateView {
+    #[getter]
+    pub fn current_player(&self) -> u64 {
+        self.inner.current_player
+    }
+
+    #[getter(players)]
+    fn py_players(&self) -> Vec<PlayerView> {
+        self.inner.players.clone()
+    }
+
+    #[getter]
+    pub fn public_cards(&self) -> Vec<Card> {
+        self.inner.public_cards.clone()
+    }
+
+    #[getter]
+    pub fn second_board(&self) -> Vec<Card> {
+        self.inner.second_board.clone()
+    }
+
+    #[getter]
+    pub fn stage(&self) -> Stage {
+        self.inner.stage
+    }
+
+    #[getter]
+    pub fn button(&self) -> u64 {
+        self.inner.button
+    }
+
+    #[getter(from_action)]
+    pub fn last_action(&self) -> Option<ActionRecord> {
+        self.inner.from_action.clone()
+    }
+
+    #[getter]
+    pub fn legal_actions(&self) -> Vec<ActionEnum> {
+        self.inner.legal_actions.clone()
+    }
+
+    /// Chips in the pot, bets of the current street included
+    #[getter]
+    pub fn pot(&self) -> f64 {
+        self.inner.pot
+    }
+
+    #[getter]
+    pub fn min_bet(&self) -> f64 {
+        self.inner.min_bet
+    }
+
+    #[getter]
+    pub fn sb(&self) -> f64 {
+        self.inner.sb
+    }
+
+    #[getter]
+    pub fn bb(&self) -> f64 {
+        self.inner.bb
+    }
+
+    #[getter]
+    pub fn final_state(&self) -> bool {
+        self.inner.final_state
+    }
+
+    #[getter]
+    pub fn hand_id(&self) -> String {
+        self.inner.hand_id.clone()
+    }
+
+    #[getter]
+    pub fn table_id(&self) -> Option<String> {
+        self.inner.table_id.clone()
+    }
+
+    #[getter]
+    pub fn session_id(&self) -> Option<String> {
+        self.inner.session_id.clone()
+    }
+
+    pub fn __str__(&self) -> PyResult<String> {
+        Ok(format!("{:#?}", self.inner))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::action::Action;
+
+    #[test]
+    fn views_hide_what_players_cannot_see() {
+        let state = State::from_seed(2, 0, 0.5, 1.0, 100.0, 1234, false, 0.0).unwrap();
+        let view = state.view();
+        assert!(view.players().iter().all(|p| p.shown_hand.is_none()));
+        assert_eq!(view.pot(), 1.5);
+        assert!(Arc::ptr_eq(&view.inner, &view.clone().inner));
+
+        // Checked down to the showdown: both hands are shown
+        let mut state = state;
+        while !state.final_state {
+            state = state.apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+        }
+        let view = state.view();
+        assert_eq!(view.stage(), Stage::Showdown);
+        assert_eq!(
+            view.players()[1].shown_hand,
+            Some(state.players_state[1].hand)
+        );
+        assert_eq!(view.public_cards().len(), 5);
+
+        // A player who folded keeps their hand hidden at the showdown
+        let mut state = State::from_seed(3, 0, 0.5, 1.0, 100.0, 1234, false, 0.0).unwrap();
+        let folder = state.current_player;
+        state = state.apply_action(Action::new(ActionEnum::Fold, 0.0));
+        while !state.final_state {
+            state = state.apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+        }
+        let view = state.view();
+        assert_eq!(view.stage(), Stage::Showdown);
+        let shown: Vec<bool> = view
+            .players()
+            .iter()
+            .map(|p| p.shown_hand.is_some())
+            .collect();
+        assert_eq!(shown.iter().filter(|&&s| s).count(), 2);
+        assert!(!shown[folder as usize]);
+    }
+}
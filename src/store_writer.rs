@@ -0,0 +1,114 @@
+// store_writer.rs - The thread a table's player stats and hand histories are written from
+use std::future::Future;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use tokio::sync::{mpsc, oneshot};
+use tracing::error;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Runs the work on the stores of a table one job at a time and in order, on a thread of its
+/// own so that file IO never holds up the table: hands are recorded in the order they finish,
+/// and a read queued after a hand sees it. Dropping the last clone waits for the jobs queued.
+#[derive(Clone)]
+pub struct StoreWriter {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    sender: Option<mpsc::UnboundedSender<Job>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl StoreWriter {
+    pub fn new() -> Self {
+        let (sender, mut receiver) = mpsc::unbounded_channel::<Job>();
+        let thread = std::thread::Builder::new()
+            .name("store-writer".to_string())
+            .spawn(move || {
+                while let Some(job) = receiver.blocking_recv() {
+                    if std::panic::catch_unwind(std::panic::AssertUnwindSafe(job)).is_err() {
+                        error!("A job on the player stats or hand histories panicked");
+                    }
+                }
+            })
+            .map_err(|e| error!("Failed to start the store writer: {}", e))
+            .ok();
+        Self {
+            inner: Arc::new(Inner {
+                sender: thread.is_some().then_some(sender),
+                thread,
+            }),
+        }
+    }
+
+    /// Run `job` after the jobs queued before it, on the calling thread when the writer could
+    /// not be started
+    pub fn queue(&self, job: impl FnOnce() + Send + 'static) {
+        match self.inner.sender {
+            Some(ref sender) => {
+                let _ = sender.send(Box::new(job));
+            }
+            None => job(),
+        }
+    }
+
+    /// Run `job` like `queue`, the future resolving to what it returns, or None when it
+    /// panicked
+    pub fn run<T: Send + 'static>(
+        &self,
+        job: impl FnOnce() -> T + Send + 'static,
+    ) -> impl Future<Output = Option<T>> + Send + 'static {
+        let (sender, receiver) = oneshot::channel();
+        self.queue(move || {
+            let _ = sender.send(job());
+        });
+        async move { receiver.await.ok() }
+    }
+}
+
+impl Default for StoreWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        // Closing the channel stops the thread once it is done with the jobs left
+        drop(self.sender.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[tokio::test]
+    async fn jobs_run_in_order_before_the_writer_is_gone() {
+        let done = Arc::new(Mutex::new(Vec::new()));
+        let writer = StoreWriter::new();
+        for job in 0..100 {
+            let done = done.clone();
+            writer.queue(move || done.lock().unwrap().push(job));
+        }
+        // A read queued after the writes sees them
+        let read = writer.run({
+            let done = done.clone();
+            move || done.lock().unwrap().len()
+        });
+        assert_eq!(read.await, Some(100));
+
+        let done_later = done.clone();
+        writer.queue(move || {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            done_later.lock().unwrap().push(100);
+        });
+        drop(writer);
+        assert_eq!(*done.lock().unwrap(), (0..=100).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,284 @@
+// table_game.rs - What the table server needs from a game to host its hands
+use crate::state::action::Action;
+use crate::state::card::Card;
+use crate::state::rules::TableRules;
+use crate::state::view::StateView;
+use crate::state::State;
+
+/// Players, button and deck of a hand about to be dealt, the same for every game
+#[derive(Debug, Clone)]
+pub struct HandSetup {
+    pub n_players: u64,
+    pub button: u64,
+    /// Chips each player brings to the hand, in player order
+    pub stacks: Vec<f64>,
+    pub deck: Vec<Card>,
+    pub seed: u64,
+}
+
+/// A game the table server can host. The server seats the players, keeps their chips and the
+/// clock and talks to the clients; the game deals the hands, says who acts, what each player
+/// and the observers see, and who won. Players are numbered from 0 in the order of
+/// `HandSetup`, the server maps them to seats.
+pub trait TableGame: Clone + Send + Sync + Sized {
+    /// Settings every hand of a table is dealt with
+    type Config;
+    /// What a player plays on their turn
+    type Move;
+    /// The hand as a player sees it
+    type PrivateView;
+    /// The hand as the observers see it
+    type PublicView;
+
+    fn start_hand(config: &Self::Config, setup: HandSetup) -> Result<Self, String>;
+
+    /// Play the move of `player`, who must be the current actor
+    fn handle_action(&self, player: u64, action: Self::Move) -> Result<Self, String>;
+
+    /// Player to act, `None` once the hand is over
+    fn current_actor(&self) -> Option<u64>;
+
+    fn public_view(&self) -> Self::PublicView;
+
+    fn private_view(&self, player: u64) -> Self::PrivateView;
+
+    /// What each player won or lost once the hand is over, in the game's unit
+    fn result(&self) -> Option<Vec<f64>>;
+}
+
+/// How the chips go in before the first action of a hold'em hand
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HoldemOpening {
+    /// Small and big blind, and `ante` from every player or from the big blind alone with
+    /// `big_blind_ante`, see `State::post_antes`
+    Blinds {
+        ante: f64,
+        big_blind_ante: bool,
+        ante_first: bool,
+    },
+    /// Every player antes and nobody posts a blind, see `State::ante_only`
+    AnteOnly { ante: f64 },
+    /// See `State::bomb_pot`
+    BombPot { ante: f64, double_board: bool },
+}
+
+/// Blinds, antes and rules of a hold'em hand
+#[derive(Debug, Clone, Copy)]
+pub struct HoldemConfig {
+    pub small_blind: f64,
+    pub big_blind: f64,
+    pub opening: HoldemOpening,
+    pub rules: TableRules,
+}
+
+impl TableGame for State {
+    type Config = HoldemConfig;
+    type Move = Action;
+    type PrivateView = State;
+    type PublicView = StateView;
+
+    fn start_hand(config: &HoldemConfig, setup: HandSetup) -> Result<State, String> {
+        let HandSetup {
+            n_players,
+            button,
+            stacks,
+            deck,
+            seed,
+        } = setup;
+        if let HoldemOpening::AnteOnly { ante } | HoldemOpening::BombPot { ante, .. } =
+            config.opening
+        {
+            if ante <= 0.0 {
+                return Err("The ante must be greater than 0".to_owned());
+            }
+        }
+        let bb = config.big_blind;
+        // Dealt with the largest stack, then each player's own when they differ
+        let stack = stacks.iter().copied().fold(0.0, f64::max);
+        let dealt = State::from_deck(
+            n_players,
+            button,
+            config.small_blind,
+            bb,
+            stack.max(bb),
+            deck,
+            false, // verbose
+            seed,
+            0.0,
+        )
+        .and_then(
+            |state| match stack >= bb && stacks.iter().all(|s| *s == stack) {
+                true => Ok(state),
+                false => state.with_stacks(stacks),
+            },
+        );
+        match config.opening {
+            HoldemOpening::Blinds {
+                ante,
+                big_blind_ante,
+                ante_first,
+            } => dealt
+                .and_then(|state| state.with_rules(config.rules))
+                .and_then(|state| match ante > 0.0 {
+                    true => state.post_antes(ante, big_blind_ante, ante_first),
+                    false => Ok(state),
+                })
+                .map(|mut state| {
+                    state.run_out_if_nobody_acts();
+                    state
+                }),
+            HoldemOpening::AnteOnly { ante } => dealt.and_then(|mut state| {
+                state.open_ante_only(ante);
+                state.with_rules(config.rules)
+            }),
+            HoldemOpening::BombPot { ante, double_board } => dealt.and_then(|mut state| {
+                state.open_bomb_pot(ante, double_board);
+                state.with_rules(config.rules)
+            }),
+        }
+        .map_err(|e| e.to_string())
+    }
+
+    fn handle_action(&self, player: u64, action: Action) -> Result<State, String> {
+        if self.current_actor() != Some(player) {
+            return Err(format!("Player {} is not to act", player));
+        }
+        Ok(self.apply_action(action))
+    }
+
+    fn current_actor(&self) -> Option<u64> {
+        (!self.final_state).then_some(self.current_player)
+    }
+
+    fn public_view(&self) -> StateView {
+        self.view()
+    }
+
+    fn private_view(&self, player: u64) -> State {
+        self.with_perspective(Some(player))
+    }
+
+    /// Chips won or lost, net of what each player put in
+    fn result(&self) -> Option<Vec<f64>> {
+        self.final_state
+            .then(|| self.players_state.iter().map(|ps| ps.reward).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::seeds::shuffled_deck;
+    use crate::state::action::ActionEnum;
+    use crate::state::stage::Stage;
+
+    #[test]
+    fn holdem_hand_is_played_through_the_trait() {
+        let config = HoldemConfig {
+            small_blind: 1.0,
+            big_blind: 2.0,
+            opening: HoldemOpening::Blinds {
+                ante: 0.0,
+                big_blind_ante: false,
+                ante_first: false,
+            },
+            rules: TableRules::default(),
+        };
+        let setup = HandSetup {
+            n_players: 3,
+            button: 0,
+            stacks: vec![100.0; 3],
+            deck: shuffled_deck(9),
+            seed: 9,
+        };
+        let mut state = State::start_hand(&config, setup).unwrap();
+        let call = Action::new(ActionEnum::CheckCall, 0.0);
+        assert!(state.handle_action(state.current_player + 1, call).is_err());
+        assert_eq!(state.private_view(1).perspective, Some(1));
+
+        while let Some(player) = state.current_actor() {
+            assert_eq!(state.result(), None);
+            state = state.handle_action(player, call).unwrap();
+        }
+        let result = state.result().unwrap();
+        assert!(result.iter().sum::<f64>().abs() < 1e-9);
+    }
+
+    #[test]
+    fn every_opening_is_dealt_by_start_hand() {
+        let setup = || HandSetup {
+            n_players: 3,
+            button: 0,
+            stacks: vec![100.0; 3],
+            deck: shuffled_deck(4),
+            seed: 4,
+        };
+        let config = |opening| HoldemConfig {
+            small_blind: 1.0,
+            big_blind: 2.0,
+            opening,
+            rules: TableRules::default(),
+        };
+
+        let antes = HoldemOpening::Blinds {
+            ante: 0.5,
+            big_blind_ante: true,
+            ante_first: false,
+        };
+        let state = State::start_hand(&config(antes), setup()).unwrap();
+        let posted: Vec<f64> = state.players_state.iter().map(|ps| ps.ante_chips).collect();
+        assert_eq!(posted, vec![0.0, 0.0, 0.5]);
+
+        let ante_only = HoldemOpening::AnteOnly { ante: 1.0 };
+        let state = State::start_hand(&config(ante_only), setup()).unwrap();
+        assert!(state.players_state.iter().all(|ps| ps.bet_chips == 0.0));
+        assert_eq!(state.pot, 3.0);
+
+        let bomb_pot = HoldemOpening::BombPot {
+            ante: 2.0,
+            double_board: false,
+        };
+        let state = State::start_hand(&config(bomb_pot), setup()).unwrap();
+        assert_eq!(state.stage, Stage::Flop);
+        assert_eq!(state.pot, 6.0);
+    }
+
+    #[test]
+    fn short_stacks_post_what_they_have() {
+        let config = HoldemConfig {
+            small_blind: 5.0,
+            big_blind: 10.0,
+            opening: HoldemOpening::Blinds {
+                ante: 0.0,
+                big_blind_ante: false,
+                ante_first: false,
+            },
+            rules: TableRules::default(),
+        };
+        let setup = |stacks: Vec<f64>| HandSetup {
+            n_players: stacks.len() as u64,
+            button: 0,
+            stacks,
+            deck: shuffled_deck(4),
+            seed: 4,
+        };
+
+        // The big blind is all-in for 4, the small blind still has a call to make
+        let state = State::start_hand(&config, setup(vec![300.0, 80.0, 4.0])).unwrap();
+        let bets: Vec<f64> = state.players_state.iter().map(|ps| ps.bet_chips).collect();
+        let stakes: Vec<f64> = state.players_state.iter().map(|ps| ps.stake).collect();
+        assert_eq!(bets, vec![0.0, 5.0, 4.0]);
+        assert_eq!(stakes, vec![300.0, 75.0, 0.0]);
+        assert_eq!(state.pot, 9.0);
+        assert!(state.check_invariants().is_empty());
+
+        // Both blinds all-in: nobody acts and the board is run out
+        let state = State::start_hand(&config, setup(vec![3.0, 8.0])).unwrap();
+        assert!(state.final_state);
+        assert_eq!(state.current_actor(), None);
+        let result = state.result().unwrap();
+        assert!(result.iter().sum::<f64>().abs() < 1e-9);
+
+        assert!(State::start_hand(&config, setup(vec![100.0, 0.0])).is_err());
+    }
+}
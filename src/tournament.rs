@@ -0,0 +1,878 @@
+// tournament.rs - Multi-table tournaments: seating, balancing, table breaking and payouts
+use crate::events::{PoolSubscriber, Subscriber};
+use crate::game_logic::now;
+use crate::game_server::{prize_pool, GameConfig, GamePlayer, GameServer, PlayerAction};
+use crate::hand_archive::HandArchive;
+use crate::messages::ClientError;
+use crate::player_stats::PlayerStatsStore;
+use crate::seeds::hand_seed;
+use crate::tournament_clock::TournamentClock;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use tracing::{error, info};
+
+/// Where a player finished a tournament
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finish {
+    pub player_id: String,
+    pub name: String,
+    /// 1 for the winner. Players busted in the same hand, or in the same round of
+    /// hand-for-hand play, share the best of their places.
+    pub place: usize,
+    /// What the places shared by the player pay, split evenly between them
+    pub prize: f64,
+    /// Bounties paid to the player at a knockout tournament, the winners' own included
+    pub bounties: f64,
+}
+
+/// A tournament played over as many tables of `GameConfig.max_players` as it takes, every
+/// table playing the blind structure of the config on the one clock of the tournament, which
+/// starts with it. Players register until the tournament starts, or with
+/// `GameConfig.late_registration_levels` until the end of the break after that level, and
+/// the players who busted can enter again until then. Between hands, the
+/// players who busted are out, tables are broken as soon as the players left fit at one
+/// fewer, and players move from the fullest tables to the shortest so that no table has two
+/// more players than another. On the money bubble the tables play hand for hand: none deals
+/// its next hand before every table is done with the current one. A satellite is over once
+/// the players left are down to its seats.
+pub struct Tournament {
+    tournament_id: String,
+    /// Settings of every table, `blind_levels` included
+    config: GameConfig,
+    /// Seeds of the tables, which deal from `hand_seed(seed, table number)`
+    seed: u64,
+    /// Blind levels and breaks of every table, see `Tournament::follow_clock`
+    clock: TournamentClock,
+    /// Tables in play, by id
+    tables: BTreeMap<String, GameServer>,
+    /// Tables opened so far, broken ones included
+    opened: u64,
+    /// Player id -> id of the table the player sits at
+    seated: HashMap<String, String>,
+    /// Players out, in the order they busted, and the winner last
+    finishes: Vec<Finish>,
+    /// Players who busted while registration was open, by hand, in the order they busted.
+    /// They finish behind every player still in once it closes.
+    late_busts: Vec<Vec<GamePlayer>>,
+    entries: u32,
+    started: bool,
+    /// Followers of every table, see `Tournament::subscribe`
+    subscribers: Vec<PoolSubscriber>,
+    player_stats: Arc<Mutex<PlayerStatsStore>>,
+    hand_archive: Arc<Mutex<HandArchive>>,
+}
+
+impl Tournament {
+    /// Tables are named after `tournament_id`, e.g. `sunday-1`, `sunday-2`. Fails without
+    /// `config.blind_levels`, which make the tables play their stacks down.
+    pub fn new(tournament_id: &str, config: GameConfig) -> Result<Self, String> {
+        let clock = TournamentClock::new(config.blind_levels.clone())
+            .ok_or("A tournament needs blind levels")?;
+        let player_stats = match config.stats_file {
+            Some(ref path) => PlayerStatsStore::load(path.into()).unwrap_or_else(|e| {
+                error!("Failed to load player stats from {}: {}", path, e);
+                PlayerStatsStore::default()
+            }),
+            None => PlayerStatsStore::default(),
+        };
+        let hand_archive = match config.hand_history_dir {
+            Some(ref dir) => HandArchive::open(dir.into()).unwrap_or_else(|e| {
+                error!("Failed to open the hand histories in {}: {}", dir, e);
+                HandArchive::default()
+            }),
+            None => HandArchive::default(),
+        };
+        let seed = config.session_seed.unwrap_or_else(rand::random);
+        info!("Tournament {}, seed {}", tournament_id, seed);
+
+        Ok(Self {
+            tournament_id: tournament_id.to_owned(),
+            config,
+            seed,
+            clock,
+            tables: BTreeMap::new(),
+            opened: 0,
+            seated: HashMap::new(),
+            finishes: Vec::new(),
+            late_busts: Vec::new(),
+            entries: 0,
+            started: false,
+            subscribers: Vec::new(),
+            player_stats: Arc::new(Mutex::new(player_stats)),
+            hand_archive: Arc::new(Mutex::new(hand_archive)),
+        })
+    }
+
+    /// Follow everything that happens at the tables of the tournament, from now on
+    pub fn subscribe(&mut self, subscriber: PoolSubscriber) {
+        for (table_id, table) in self.tables.iter_mut() {
+            table.subscribe(for_table(table_id, &subscriber));
+        }
+        self.subscribers.push(subscriber);
+    }
+
+    /// Buy in with the default stack, at the table with the fewest players, while
+    /// registration is open. A player who busted enters again with a new stack.
+    pub fn register(
+        &mut self,
+        player_id: &str,
+        name: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if !self.registration_open() {
+            return Err(ClientError::RegistrationClosed.into());
+        }
+        if self.players().any(|p| p.name == name || p.id == player_id) {
+            return Err(ClientError::NameTaken(name.to_string()).into());
+        }
+
+        let mut player = GamePlayer::new(
+            player_id.to_string(),
+            name.to_string(),
+            self.config.default_stack_size,
+        );
+        player.entries = 1;
+        player.bounty = self.config.bounty;
+        let table_id = match self.shortest_table(None) {
+            Some(table_id) if self.open_seat(&table_id).is_some() => table_id,
+            _ => self.open_table(),
+        };
+        self.seat(player, &table_id)?;
+        self.entries += 1;
+        for table in self.tables.values_mut() {
+            table.set_entries(self.entries);
+        }
+        info!(
+            "Player {} registered for tournament {}",
+            name, self.tournament_id
+        );
+        Ok(())
+    }
+
+    /// Start the clock, balance the tables and deal their first hands
+    pub async fn start(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.started {
+            return Ok(());
+        }
+        if self.seated.len() < 2 {
+            return Err(ClientError::NotEnoughPlayers.into());
+        }
+        self.started = true;
+        self.clock.start(now());
+        info!(
+            "Tournament {} started with {} players at {} tables",
+            self.tournament_id,
+            self.seated.len(),
+            self.tables.len()
+        );
+        self.advance().await;
+        Ok(())
+    }
+
+    /// Play an action at the player's table, then move on the tables whose hand is over
+    pub async fn handle_action(
+        &mut self,
+        player_id: &str,
+        action: PlayerAction,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let table = self
+            .seated
+            .get(player_id)
+            .and_then(|table_id| self.tables.get_mut(table_id))
+            .ok_or(ClientError::NotSeated)?;
+        table.follow_clock(&self.clock);
+        table.handle_action(player_id, action).await?;
+        self.advance().await;
+        Ok(())
+    }
+
+    /// Run the clocks and timers of every table and deal the tables that can. Called
+    /// periodically, like `GameServer::tick`.
+    pub async fn tick(&mut self) {
+        for table in self.tables.values_mut() {
+            table.follow_clock(&self.clock);
+            table.tick().await;
+        }
+        self.advance().await;
+    }
+
+    /// Players out so far, in the order they busted, and the winner last once it is over
+    pub fn finishes(&self) -> &[Finish] {
+        &self.finishes
+    }
+
+    /// Players still in
+    pub fn remaining(&self) -> usize {
+        self.seated.len()
+    }
+
+    /// Buy-ins paid, and what the prize pool pays each place
+    pub fn prize_pool(&self) -> (f64, Vec<f64>) {
+        prize_pool(&self.config, self.entries)
+    }
+
+    /// One player from the money once registration has closed: the tables wait for each
+    /// other between hands
+    pub fn hand_for_hand(&self) -> bool {
+        self.started
+            && !self.finished()
+            && !self.registration_open()
+            && self.remaining() == self.paid_places() + 1
+    }
+
+    /// Whether players can register: until the tournament starts, and with
+    /// `GameConfig.late_registration_levels` until the end of the break after that level
+    pub fn registration_open(&self) -> bool {
+        match self.config.late_registration_levels {
+            _ if !self.started => true,
+            Some(levels) => self.clock.status(now()).level < levels,
+            None => false,
+        }
+    }
+
+    /// Every player has finished, the winners included
+    pub fn finished(&self) -> bool {
+        self.started && self.finishes.len() == self.entries as usize
+    }
+
+    fn players(&self) -> impl Iterator<Item = &GamePlayer> {
+        self.seated.iter().filter_map(|(player_id, table_id)| {
+            self.tables
+                .get(table_id)
+                .and_then(|table| table.player(player_id))
+        })
+    }
+
+    fn paid_places(&self) -> usize {
+        self.prize_pool().1.len()
+    }
+
+    /// Players who win the tournament: the seats of a satellite, or the last player left
+    fn winners(&self) -> usize {
+        match self.config.satellite_seats {
+            Some(seats) if seats > 0 => seats as usize,
+            _ => 1,
+        }
+    }
+
+    /// Settle the tables whose hand is over, then break, balance and deal them
+    async fn advance(&mut self) {
+        if !self.started || self.finished() {
+            return;
+        }
+        self.follow_clock();
+        self.knock_out();
+        if self.finished() {
+            return;
+        }
+        self.break_tables();
+        self.balance();
+
+        // On the bubble every table waits for the others
+        let idle: Vec<String> = self
+            .tables
+            .iter()
+            .filter(|(_, table)| !table.game_running())
+            .map(|(table_id, _)| table_id.clone())
+            .collect();
+        if self.hand_for_hand() && idle.len() < self.tables.len() {
+            return;
+        }
+        for table_id in idle {
+            let Some(table) = self.tables.get_mut(&table_id) else {
+                continue;
+            };
+            if table.seated_players().len() < 2 {
+                continue;
+            }
+            // A table that cannot deal, e.g. on a break, is dealt again on a later call
+            if let Err(e) = table.start_game().await {
+                info!("Table {} could not deal: {}", table_id, e);
+            }
+        }
+    }
+
+    /// Take out the players who lost their stack, who share the places they bust on. The
+    /// places of those who bust while registration is open wait until it closes.
+    fn knock_out(&mut self) {
+        let mut busted = Vec::new();
+        for (player_id, table_id) in &self.seated {
+            let Some(table) = self.tables.get(table_id) else {
+                continue;
+            };
+            if table.game_running() {
+                continue;
+            }
+            if table
+                .player(player_id)
+                .is_some_and(|p| p.seat.is_none() && p.chips <= 0.0)
+            {
+                busted.push((player_id.clone(), table_id.clone()));
+            }
+        }
+        busted.sort();
+
+        let mut out = Vec::new();
+        for (player_id, table_id) in &busted {
+            self.seated.remove(player_id);
+            let Some(table) = self.tables.get_mut(table_id) else {
+                continue;
+            };
+            match table.transfer_out(player_id) {
+                Ok(player) => out.push(player),
+                Err(e) => error!("Failed to take out player {}: {}", player_id, e),
+            }
+        }
+        if self.registration_open() {
+            if !out.is_empty() {
+                self.late_busts.push(out);
+            }
+            return;
+        }
+
+        // Whoever busted during late registration finishes behind the players still in, the
+        // last of them first
+        let late_busts = std::mem::take(&mut self.late_busts);
+        let mut best_place = self.seated.len() + out.len() + 1;
+        let mut places = Vec::new();
+        for players in late_busts.iter().rev() {
+            places.push(best_place);
+            best_place += players.len();
+        }
+        for (players, place) in late_busts.into_iter().zip(places.into_iter().rev()) {
+            self.finish(players, place);
+        }
+        let best_place = self.seated.len() + 1;
+        self.finish(out, best_place);
+
+        // The winner, or everybody left once a satellite is down to its seats, who also
+        // collect their own bounty
+        if self.seated.len() <= self.winners() {
+            let mut winners: Vec<GamePlayer> = self.players().cloned().collect();
+            winners.sort_by(|a, b| a.id.cmp(&b.id));
+            for winner in &mut winners {
+                winner.bounties_won += std::mem::take(&mut winner.bounty);
+            }
+            self.finish(winners, 1);
+        }
+    }
+
+    /// Record the places of players out together, who share them and what they pay
+    fn finish(&mut self, players: Vec<GamePlayer>, best_place: usize) {
+        let (_, payouts) = self.prize_pool();
+        let prize = (best_place..best_place + players.len())
+            .map(|place| payouts.get(place - 1).copied().unwrap_or(0.0))
+            .sum::<f64>()
+            / players.len().max(1) as f64;
+        for player in players {
+            info!(
+                "Player {} finished tournament {} in place {}, winning {}",
+                player.name, self.tournament_id, best_place, prize
+            );
+            self.finishes.push(Finish {
+                player_id: player.id,
+                name: player.name,
+                place: best_place,
+                prize,
+                bounties: player.bounties_won,
+            });
+        }
+    }
+
+    /// Break the table between hands with the fewest players while the players left fit at
+    /// one table fewer, and seat its players at the shortest tables
+    fn break_tables(&mut self) {
+        let size = self.table_size();
+        while self.tables.len() > self.seated.len().div_ceil(size).max(1) {
+            let broken = self
+                .tables
+                .iter()
+                .filter(|(_, table)| !table.game_running())
+                .min_by_key(|(_, table)| table.seated_players().len())
+                .map(|(table_id, _)| table_id.clone());
+            let Some(broken) = broken else {
+                return;
+            };
+            let Some(mut table) = self.tables.remove(&broken) else {
+                return;
+            };
+            info!("Tournament {} broke table {}", self.tournament_id, broken);
+            for player_id in sorted(table.seated_players()) {
+                match table.transfer_out(&player_id) {
+                    Ok(player) => self.reseat(player),
+                    Err(e) => error!("Failed to move player {}: {}", player_id, e),
+                }
+            }
+        }
+    }
+
+    /// Move players from the fullest table between hands to the shortest, until no table
+    /// has two more players than another
+    fn balance(&mut self) {
+        loop {
+            let fullest = self
+                .tables
+                .iter()
+                .filter(|(_, table)| !table.game_running())
+                .max_by_key(|(_, table)| table.seated_players().len())
+                .map(|(table_id, table)| (table_id.clone(), table.seated_players().len()));
+            let Some((from, players)) = fullest else {
+                return;
+            };
+            let Some(to) = self.shortest_table(Some(&from)) else {
+                return;
+            };
+            if players < self.seated_at(&to) + 2 {
+                return;
+            }
+
+            // The player in the highest seat moves
+            let table = self.tables.get_mut(&from).expect("fullest table");
+            let moving = table
+                .seated_players()
+                .into_iter()
+                .max_by_key(|id| table.player(id).and_then(|p| p.seat));
+            let Some(player_id) = moving else {
+                return;
+            };
+            match table.transfer_out(&player_id) {
+                Ok(player) => {
+                    info!(
+                        "Tournament {} moves {} from {} to {}",
+                        self.tournament_id, player.name, from, to
+                    );
+                    self.seated.remove(&player_id);
+                    if let Err(e) = self.seat(player, &to) {
+                        error!("Failed to move player {}: {}", player_id, e);
+                    }
+                }
+                Err(e) => {
+                    error!("Failed to move player {}: {}", player_id, e);
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Seat a player taken from a broken table at the shortest table
+    fn reseat(&mut self, player: GamePlayer) {
+        self.seated.remove(&player.id);
+        let player_id = player.id.clone();
+        let result = match self.shortest_table(None) {
+            Some(table_id) => self.seat(player, &table_id),
+            None => Err(ClientError::NotSeated),
+        };
+        if let Err(e) = result {
+            error!("Failed to move player {}: {}", player_id, e);
+        }
+    }
+
+    fn seat(&mut self, player: GamePlayer, table_id: &str) -> Result<(), ClientError> {
+        let seat = self
+            .open_seat(table_id)
+            .ok_or(ClientError::SeatOccupied(0))?;
+        let player_id = player.id.clone();
+        let table = self
+            .tables
+            .get_mut(table_id)
+            .ok_or(ClientError::NotSeated)?;
+        table.transfer_in(player, seat)?;
+        self.seated.insert(player_id, table_id.to_owned());
+        Ok(())
+    }
+
+    /// The table with the fewest players, other than `except`, the first of them by id
+    fn shortest_table(&self, except: Option<&str>) -> Option<String> {
+        self.tables
+            .keys()
+            .filter(|table_id| Some(table_id.as_str()) != except)
+            .min_by_key(|table_id| self.seated_at(table_id))
+            .cloned()
+    }
+
+    fn seated_at(&self, table_id: &str) -> usize {
+        self.tables
+            .get(table_id)
+            .map_or(0, |table| table.seated_players().len())
+    }
+
+    /// Lowest free seat of the table
+    fn open_seat(&self, table_id: &str) -> Option<u8> {
+        let table = self.tables.get(table_id)?;
+        let taken: Vec<u8> = table
+            .seated_players()
+            .iter()
+            .filter_map(|id| table.player(id)?.seat)
+            .collect();
+        (1..=self.table_size() as u8).find(|seat| !taken.contains(seat))
+    }
+
+    fn table_size(&self) -> usize {
+        self.config.max_players.max(2) as usize
+    }
+
+    /// Have every table play the levels of the tournament clock
+    fn follow_clock(&mut self) {
+        for table in self.tables.values_mut() {
+            table.follow_clock(&self.clock);
+        }
+    }
+
+    fn open_table(&mut self) -> String {
+        self.opened += 1;
+        let table_id = format!("{}-{}", self.tournament_id, self.opened);
+        let config = GameConfig {
+            table_id: Some(table_id.clone()),
+            session_seed: Some(hand_seed(self.seed, self.opened)),
+            ..self.config.clone()
+        };
+        let mut table =
+            GameServer::with_stores(config, self.player_stats.clone(), self.hand_archive.clone());
+        for subscriber in &self.subscribers {
+            table.subscribe(for_table(&table_id, subscriber));
+        }
+        table.set_entries(self.entries);
+        table.follow_clock(&self.clock);
+        info!(
+            "Tournament {} opened table {}",
+            self.tournament_id, table_id
+        );
+        self.tables.insert(table_id.clone(), table);
+        table_id
+    }
+}
+
+fn sorted(mut ids: Vec<String>) -> Vec<String> {
+    ids.sort();
+    ids
+}
+
+/// Follow a single table of the tournament
+fn for_table(table_id: &str, subscriber: &PoolSubscriber) -> Subscriber {
+    let table_id = table_id.to_owned();
+    let subscriber = subscriber.clone();
+    Arc::new(move |event| subscriber(&table_id, event))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::TableEvent;
+    use crate::game_logic::set_simulated_time;
+    use crate::tournament_clock::BlindLevel;
+    use crate::websocket_server::{OnMoveMessage, TournamentClockMessage};
+
+    /// Tournament of tables of `max_players`, and the last move asked at each table
+    fn tournament(
+        max_players: u8,
+        payouts: Vec<f64>,
+    ) -> (Tournament, Arc<Mutex<HashMap<String, OnMoveMessage>>>) {
+        tournament_with(GameConfig {
+            max_players,
+            payouts,
+            ..Default::default()
+        })
+    }
+
+    /// A single level of 5/10 unless `config` has blind levels
+    fn tournament_with(
+        config: GameConfig,
+    ) -> (Tournament, Arc<Mutex<HashMap<String, OnMoveMessage>>>) {
+        let blind_levels = match config.blind_levels.is_empty() {
+            true => vec![BlindLevel::checked(5.0, 10.0, 600.0, 0.0, 0.0).unwrap()],
+            false => config.blind_levels.clone(),
+        };
+        let mut tournament = Tournament::new(
+            "sunday",
+            GameConfig {
+                session_seed: Some(7),
+                stats_file: None,
+                hand_history_dir: None,
+                blind_levels,
+                buy_in: 10.0,
+                ..config
+            },
+        )
+        .unwrap();
+        let on_move = Arc::new(Mutex::new(HashMap::new()));
+        let follower = on_move.clone();
+        tournament.subscribe(Arc::new(move |table_id: &str, event: &TableEvent| {
+            if let TableEvent::OnMove(message) = event {
+                follower
+                    .lock()
+                    .unwrap()
+                    .insert(table_id.to_owned(), message.clone());
+            }
+        }));
+        (tournament, on_move)
+    }
+
+    /// Play the player to act at `table_id` all in, or call them
+    async fn shove(
+        tournament: &mut Tournament,
+        on_move: &Arc<Mutex<HashMap<String, OnMoveMessage>>>,
+        table_id: &str,
+    ) {
+        let message = on_move.lock().unwrap()[table_id].clone();
+        let action = if message.can_raise {
+            PlayerAction::Raise(message.max_raise_to_total_bet)
+        } else {
+            PlayerAction::Call
+        };
+        tournament
+            .handle_action(&message.address, action)
+            .await
+            .unwrap();
+    }
+
+    fn table_ids(tournament: &Tournament) -> Vec<String> {
+        tournament.tables.keys().cloned().collect()
+    }
+
+    fn running(tournament: &Tournament) -> Vec<String> {
+        tournament
+            .tables
+            .iter()
+            .filter(|(_, table)| table.game_running())
+            .map(|(table_id, _)| table_id.clone())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn tables_wait_for_each_other_on_the_bubble() {
+        let (mut tournament, on_move) = tournament(3, vec![0.5, 0.3, 0.2]);
+        for i in 1..=4 {
+            tournament
+                .register(&format!("p{}", i), &format!("Player {}", i))
+                .unwrap();
+        }
+        assert_eq!(table_ids(&tournament), vec!["sunday-1", "sunday-2"]);
+        assert_eq!(tournament.tables["sunday-1"].seated_players().len(), 3);
+
+        // Starting balances the tables two and two, and four players are one from the money
+        tournament.start().await.unwrap();
+        for table_id in ["sunday-1", "sunday-2"] {
+            assert_eq!(tournament.tables[table_id].seated_players().len(), 2);
+        }
+        assert!(tournament.hand_for_hand());
+        assert!(tournament.register("p5", "Player 5").is_err());
+
+        // The first table done waits for the other before dealing again
+        let folder = on_move.lock().unwrap()["sunday-1"].address.clone();
+        tournament
+            .handle_action(&folder, PlayerAction::Fold)
+            .await
+            .unwrap();
+        assert_eq!(running(&tournament), vec!["sunday-2"]);
+        let folder = on_move.lock().unwrap()["sunday-2"].address.clone();
+        tournament
+            .handle_action(&folder, PlayerAction::Fold)
+            .await
+            .unwrap();
+        assert_eq!(running(&tournament), vec!["sunday-1", "sunday-2"]);
+
+        for _ in 0..1000 {
+            let Some(table_id) = running(&tournament).into_iter().next() else {
+                break;
+            };
+            shove(&mut tournament, &on_move, &table_id).await;
+        }
+        assert!(tournament.finished());
+        let finishes = tournament.finishes();
+        assert_eq!(finishes.len(), 4);
+        assert_eq!(finishes.last().unwrap().place, 1);
+        let (pool, _) = tournament.prize_pool();
+        let paid: f64 = finishes.iter().map(|f| f.prize).sum();
+        assert!((paid - pool).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn tables_break_as_players_bust() {
+        let (mut tournament, on_move) = tournament(3, vec![0.6, 0.4]);
+        for i in 1..=5 {
+            tournament
+                .register(&format!("p{}", i), &format!("Player {}", i))
+                .unwrap();
+        }
+        assert_eq!(table_ids(&tournament).len(), 2);
+        tournament.start().await.unwrap();
+
+        for _ in 0..1000 {
+            let running = running(&tournament);
+            let Some(table_id) = running.first() else {
+                break;
+            };
+            // Between hands the players left sit at as few tables as they fit, balanced
+            if running.len() == tournament.tables.len() {
+                let sizes: Vec<usize> = tournament
+                    .tables
+                    .values()
+                    .map(|table| table.seated_players().len())
+                    .collect();
+                assert!(sizes.len() <= tournament.remaining().div_ceil(3));
+                assert!(sizes.iter().max().unwrap() - sizes.iter().min().unwrap() < 2);
+            }
+            shove(&mut tournament, &on_move, table_id).await;
+        }
+        assert!(tournament.finished());
+        assert_eq!(table_ids(&tournament).len(), 1);
+
+        let finishes = tournament.finishes();
+        assert_eq!(finishes.len(), 5);
+        let winner = finishes.last().unwrap();
+        assert_eq!(winner.place, 1);
+        assert!((winner.prize - 30.0).abs() < 1e-9);
+        assert!(
+            (tournament
+                .players()
+                .find(|p| p.id == winner.player_id)
+                .unwrap()
+                .chips
+                - 5000.0)
+                .abs()
+                < 1e-9
+        );
+        let paid: f64 = finishes.iter().map(|f| f.prize).sum();
+        assert!((paid - 50.0).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn satellites_end_with_their_seats_and_pay_every_bounty() {
+        let (mut tournament, on_move) = tournament_with(GameConfig {
+            max_players: 3,
+            satellite_seats: Some(2),
+            bounty: 20.0,
+            ..Default::default()
+        });
+        for i in 1..=5 {
+            tournament
+                .register(&format!("p{}", i), &format!("Player {}", i))
+                .unwrap();
+        }
+        tournament.start().await.unwrap();
+        for _ in 0..1000 {
+            let Some(table_id) = running(&tournament).into_iter().next() else {
+                break;
+            };
+            shove(&mut tournament, &on_move, &table_id).await;
+        }
+
+        // The last two players left each win a seat, which is half the prize pool
+        assert!(tournament.finished());
+        assert_eq!(tournament.remaining(), 2);
+        let finishes = tournament.finishes();
+        assert_eq!(finishes.len(), 5);
+        let seats: Vec<&Finish> = finishes.iter().filter(|f| f.place == 1).collect();
+        assert_eq!(seats.len(), 2);
+        for seat in seats {
+            assert!((seat.prize - 25.0).abs() < 1e-9);
+        }
+
+        // Half of every bounty was paid on the knockout and the other half with the seats
+        let bounties: f64 = finishes.iter().map(|f| f.bounties).sum();
+        assert!((bounties - 100.0).abs() < 1e-9);
+    }
+
+    /// Levels of 5/10 and 10/20, with late registration during the first
+    fn late_registration(max_players: u8, payouts: Vec<f64>) -> GameConfig {
+        GameConfig {
+            max_players,
+            payouts,
+            blind_levels: vec![
+                BlindLevel::checked(5.0, 10.0, 600.0, 0.0, 0.0).unwrap(),
+                BlindLevel::checked(10.0, 20.0, 600.0, 0.0, 0.0).unwrap(),
+            ],
+            late_registration_levels: Some(1),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn late_registrations_play_on_the_tournament_clock() {
+        set_simulated_time(Some(1000.0));
+        let (mut tournament, _) = tournament_with(late_registration(2, Vec::new()));
+        let clocks = Arc::new(Mutex::new(HashMap::new()));
+        let follower = clocks.clone();
+        tournament.subscribe(Arc::new(move |table_id: &str, event: &TableEvent| {
+            if let TableEvent::TournamentClock(message) = event {
+                follower
+                    .lock()
+                    .unwrap()
+                    .insert(table_id.to_owned(), message.clone());
+            }
+        }));
+        tournament.register("p1", "Player 1").unwrap();
+        tournament.register("p2", "Player 2").unwrap();
+        tournament.start().await.unwrap();
+
+        // Halfway through the first level two more players open a second table
+        set_simulated_time(Some(1300.0));
+        tournament.register("p3", "Player 3").unwrap();
+        tournament.register("p4", "Player 4").unwrap();
+        tournament.tick().await;
+        assert_eq!(table_ids(&tournament), vec!["sunday-1", "sunday-2"]);
+        assert_eq!(running(&tournament).len(), 2);
+
+        // Both tables move to the second level together, and registration closes
+        set_simulated_time(Some(1700.0));
+        tournament.tick().await;
+        let clocks: HashMap<String, TournamentClockMessage> = clocks.lock().unwrap().clone();
+        assert_eq!(clocks.len(), 2);
+        for clock in clocks.values() {
+            assert_eq!((clock.level.level, clock.level.big_blind), (2, 20.0));
+            assert!(!clock.registration_open);
+            assert_eq!(clock.entries, 4);
+        }
+        assert!(tournament.register("p5", "Player 5").is_err());
+        set_simulated_time(None);
+    }
+
+    #[tokio::test]
+    async fn players_busted_in_late_registration_enter_again_and_finish_last() {
+        set_simulated_time(Some(1000.0));
+        let (mut tournament, on_move) = tournament_with(late_registration(3, vec![0.6, 0.4]));
+        for i in 1..=3 {
+            tournament
+                .register(&format!("p{}", i), &format!("Player {}", i))
+                .unwrap();
+        }
+        tournament.start().await.unwrap();
+        for _ in 0..1000 {
+            if !tournament.late_busts.is_empty() {
+                break;
+            }
+            let Some(table_id) = running(&tournament).into_iter().next() else {
+                break;
+            };
+            shove(&mut tournament, &on_move, &table_id).await;
+        }
+
+        // Their places wait for registration to close, and they can buy in again meanwhile
+        let busted: Vec<GamePlayer> = tournament.late_busts.concat();
+        assert!(!busted.is_empty());
+        assert!(tournament.finishes().is_empty());
+        for player in &busted {
+            tournament.register(&player.id, &player.name).unwrap();
+        }
+        let entries = 3 + busted.len();
+
+        set_simulated_time(Some(1700.0));
+        tournament.tick().await;
+        for _ in 0..1000 {
+            let Some(table_id) = running(&tournament).into_iter().next() else {
+                break;
+            };
+            shove(&mut tournament, &on_move, &table_id).await;
+        }
+        assert!(tournament.finished());
+        let finishes = tournament.finishes();
+        assert_eq!(finishes.len(), entries);
+        for (finish, player) in finishes.iter().zip(&busted) {
+            assert_eq!(finish.player_id, player.id);
+            assert!(finish.place > 3);
+            assert_eq!(finish.prize, 0.0);
+        }
+        let paid: f64 = finishes.iter().map(|f| f.prize).sum();
+        assert!((paid - 10.0 * entries as f64).abs() < 1e-9);
+        set_simulated_time(None);
+    }
+}
@@ -0,0 +1,293 @@
+// tournament_clock.rs - Blind levels and breaks of a tournament table, on the wall clock
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+/// One level of the blind structure, optionally followed by a break
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BlindLevel {
+    #[pyo3(get, set)]
+    pub small_blind: f64,
+    #[pyo3(get, set)]
+    pub big_blind: f64,
+    #[pyo3(get, set)]
+    pub ante: f64,
+    /// Seconds the level lasts. The last level of a structure never ends.
+    #[pyo3(get, set)]
+    pub duration: f64,
+    /// Seconds of break after the level, no break when 0
+    #[pyo3(get, set)]
+    pub break_after: f64,
+}
+
+impl BlindLevel {
+    /// A level with finite and positive blinds and duration, and a finite ante and break that
+    /// are not negative
+    pub fn checked(
+        small_blind: f64,
+        big_blind: f64,
+        duration: f64,
+        ante: f64,
+        break_after: f64,
+    ) -> Result<BlindLevel, String> {
+        let positive = |value: f64| value.is_finite() && value > 0.0;
+        let not_negative = |value: f64| value.is_finite() && value >= 0.0;
+        if !positive(small_blind) || !positive(big_blind) || big_blind < small_blind {
+            return Err(
+                "Blinds must be positive, the big blind at least the small blind".to_owned(),
+            );
+        }
+        if !positive(duration) || !not_negative(ante) || !not_negative(break_after) {
+            return Err(
+                "Levels must last a positive time, antes and breaks cannot be negative".to_owned(),
+            );
+        }
+        Ok(BlindLevel {
+            small_blind,
+            big_blind,
+            ante,
+            duration,
+            break_after,
+        })
+    }
+
+    /// See `BlindLevel.parse_schedule`
+    pub fn parse(schedule: &str) -> Result<Vec<BlindLevel>, String> {
+        schedule
+            .split(',')
+            .map(|level| level.trim())
+            .filter(|level| !level.is_empty())
+            .map(|level| {
+                let invalid = || format!("Invalid blind level '{}'", level);
+                let (blinds, times) = level.split_once(':').ok_or_else(invalid)?;
+                let blinds = blinds
+                    .split('/')
+                    .map(|amount| amount.trim().parse::<f64>().map_err(|_| invalid()))
+                    .collect::<Result<Vec<f64>, String>>()?;
+                let (minutes, break_minutes) = times.split_once('+').unwrap_or((times, "0"));
+                let minutes = minutes.trim().parse::<f64>().map_err(|_| invalid())?;
+                let break_minutes = break_minutes.trim().parse::<f64>().map_err(|_| invalid())?;
+                match blinds[..] {
+                    [small_blind, big_blind] | [small_blind, big_blind, _] => BlindLevel::checked(
+                        small_blind,
+                        big_blind,
+                        minutes * 60.0,
+                        blinds.get(2).copied().unwrap_or(0.0),
+                        break_minutes * 60.0,
+                    ),
+                    _ => Err(invalid()),
+                }
+            })
+            .collect()
+    }
+}
+
+#[pymethods]
+impl BlindLevel {
+    #[new]
+    #[pyo3(signature = (small_blind, big_blind, duration, ante=0.0, break_after=0.0))]
+    pub fn new(
+        small_blind: f64,
+        big_blind: f64,
+        duration: f64,
+        ante: f64,
+        break_after: f64,
+    ) -> PyResult<BlindLevel> {
+        BlindLevel::checked(small_blind, big_blind, duration, ante, break_after)
+            .map_err(PyValueError::new_err)
+    }
+
+    /// Blind structure written as `sb/bb[/ante]:minutes[+break minutes]` levels separated by
+    /// commas, e.g. `5/10:10,10/20:10+5,15/30/5:10`
+    #[staticmethod]
+    pub fn parse_schedule(schedule: &str) -> PyResult<Vec<BlindLevel>> {
+        BlindLevel::parse(schedule).map_err(PyValueError::new_err)
+    }
+
+    pub fn __str__(&self) -> PyResult<String> {
+        Ok(format!("{:#?}", self))
+    }
+}
+
+/// Where the clock stands in the blind structure
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClockStatus {
+    /// Index of the level being played, or of the level before the break
+    pub level: usize,
+    pub on_break: bool,
+    pub paused: bool,
+    /// Seconds left in the level or the break, `None` on the last level
+    pub remaining: Option<f64>,
+}
+
+/// Runs a blind structure from the first hand on. Time spent paused does not count.
+#[derive(Debug, Clone)]
+pub struct TournamentClock {
+    levels: Vec<BlindLevel>,
+    started: Option<f64>,
+    paused_at: Option<f64>,
+    /// Seconds spent paused so far, not counting the current pause
+    paused_for: f64,
+}
+
+impl TournamentClock {
+    /// `None` for an empty structure
+    pub fn new(levels: Vec<BlindLevel>) -> Option<TournamentClock> {
+        (!levels.is_empty()).then_some(TournamentClock {
+            levels,
+            started: None,
+            paused_at: None,
+            paused_for: 0.0,
+        })
+    }
+
+    /// Start the first level at `now`, unless the clock is already running
+    pub fn start(&mut self, now: f64) {
+        self.started.get_or_insert(now);
+    }
+
+    pub fn started(&self) -> bool {
+        self.started.is_some()
+    }
+
+    pub fn paused(&self) -> bool {
+        self.paused_at.is_some()
+    }
+
+    pub fn pause(&mut self, now: f64) {
+        self.paused_at.get_or_insert(now);
+    }
+
+    pub fn resume(&mut self, now: f64) {
+        if let Some(paused_at) = self.paused_at.take() {
+            self.paused_for += now - paused_at;
+        }
+    }
+
+    pub fn levels(&self) -> &[BlindLevel] {
+        &self.levels
+    }
+
+    /// Seconds the clock has run at `now`
+    fn elapsed(&self, now: f64) -> f64 {
+        match self.started {
+            Some(started) => (self.paused_at.unwrap_or(now) - started - self.paused_for).max(0.0),
+            None => 0.0,
+        }
+    }
+
+    pub fn status(&self, now: f64) -> ClockStatus {
+        let mut elapsed = self.elapsed(now);
+        let last = self.levels.len() - 1;
+        for (level, blinds) in self.levels.iter().enumerate() {
+            if level == last {
+                break;
+            }
+            if elapsed < blinds.duration {
+                return ClockStatus {
+                    level,
+                    on_break: false,
+                    paused: self.paused(),
+                    remaining: Some(blinds.duration - elapsed),
+                };
+            }
+            elapsed -= blinds.duration;
+            if elapsed < blinds.break_after {
+                return ClockStatus {
+                    level,
+                    on_break: true,
+                    paused: self.paused(),
+                    remaining: Some(blinds.break_after - elapsed),
+                };
+            }
+            elapsed -= blinds.break_after;
+        }
+        ClockStatus {
+            level: last,
+            on_break: false,
+            paused: self.paused(),
+            remaining: None,
+        }
+    }
+
+    /// Blinds of the level being played at `now`
+    pub fn level(&self, now: f64) -> BlindLevel {
+        self.levels[self.status(now).level]
+    }
+
+    /// Seconds until the next break starts, `None` during a break or when none is left
+    pub fn next_break_in(&self, now: f64) -> Option<f64> {
+        let status = self.status(now);
+        if status.on_break {
+            return None;
+        }
+        let mut seconds = status.remaining?;
+        for (level, blinds) in self.levels.iter().enumerate().skip(status.level) {
+            if level == self.levels.len() - 1 {
+                return None;
+            }
+            if level > status.level {
+                seconds += blinds.duration;
+            }
+            if blinds.break_after > 0.0 {
+                return Some(seconds);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn structure() -> Vec<BlindLevel> {
+        BlindLevel::parse("5/10:10, 10/20:10+5, 15/30/5:10, 25/50/5:10").unwrap()
+    }
+
+    #[test]
+    fn levels_and_breaks_follow_the_schedule() {
+        let levels = structure();
+        assert_eq!(levels[1].break_after, 300.0);
+        assert_eq!(levels[2].ante, 5.0);
+
+        let mut clock = TournamentClock::new(levels).unwrap();
+        // Nothing runs before the first hand
+        assert_eq!(clock.status(1000.0).remaining, Some(600.0));
+        clock.start(1000.0);
+        assert_eq!(clock.next_break_in(1000.0), Some(1200.0));
+
+        let status = |clock: &TournamentClock, t: f64| {
+            let status = clock.status(1000.0 + t);
+            (status.level, status.on_break, status.remaining)
+        };
+        assert_eq!(status(&clock, 700.0), (1, false, Some(500.0)));
+        assert_eq!(status(&clock, 1300.0), (1, true, Some(200.0)));
+        assert_eq!(clock.next_break_in(2300.0), None);
+        assert_eq!(status(&clock, 1500.0), (2, false, Some(600.0)));
+        assert_eq!(clock.level(2500.0).big_blind, 30.0);
+        assert_eq!(status(&clock, 2100.0), (3, false, None));
+        assert_eq!(clock.next_break_in(3100.0), None);
+
+        // Paused time does not count
+        clock.pause(1100.0);
+        assert_eq!(status(&clock, 5000.0), (0, false, Some(500.0)));
+        clock.resume(1400.0);
+        assert_eq!(status(&clock, 700.0), (0, false, Some(200.0)));
+    }
+
+    #[test]
+    fn bad_schedules_are_rejected() {
+        assert!(BlindLevel::parse("5/10").is_err());
+        assert!(BlindLevel::parse("5:10").is_err());
+        assert!(BlindLevel::parse("10/5:10").is_err());
+        assert!(BlindLevel::parse("5/10:0").is_err());
+        assert!(BlindLevel::parse("5/10:x+5").is_err());
+        assert!(BlindLevel::parse("5/inf:10").is_err());
+        assert!(BlindLevel::parse("5/10:NaN").is_err());
+        assert!(BlindLevel::parse("5/10/inf:10").is_err());
+        assert!(BlindLevel::parse("5/10:10+inf").is_err());
+        assert!(BlindLevel::parse("").unwrap().is_empty());
+        assert!(TournamentClock::new(vec![]).is_none());
+    }
+}
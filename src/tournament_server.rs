@@ -0,0 +1,370 @@
+// tournament_server.rs - Serving the tables of a multi-table tournament on one WebSocket port
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+use tokio::sync::RwLock;
+use tracing::{error, info, warn};
+use tungstenite::Message;
+use uuid::Uuid;
+
+use crate::events::TableEvent;
+use crate::game_server::{GameConfig, PlayerAction};
+use crate::messages::{ClientError, Locale};
+use crate::runtime::{self, accept_async, TcpListener, TcpStream};
+use crate::tournament::Tournament;
+use crate::websocket_server::{
+    ClientId, ClientSender, ErrorMessage, PlayerActionMessage, RegisterPlayerMessage,
+    WebSocketMessage, TICK,
+};
+
+/// Sent to a player once they finished the tournament, the winners included
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TournamentFinishMessage {
+    pub place: usize,
+    pub prize: f64,
+    pub bounties: f64,
+}
+
+/// A tournament served over WebSocket. Clients register for it on any connection and are
+/// sent the messages of the table they sit at, following them when they move tables.
+#[derive(Clone)]
+pub struct TournamentServer {
+    tournament: Arc<RwLock<Tournament>>,
+    clients: Arc<RwLock<HashMap<ClientId, ClientSender>>>,
+    /// Finishes already sent to their players, see `Tournament::finishes`
+    finishes_sent: Arc<AtomicUsize>,
+    locale: Locale,
+}
+
+impl TournamentServer {
+    /// See `Tournament::new`
+    pub fn new(tournament_id: &str, config: GameConfig) -> Result<Self, String> {
+        let locale = config.locale;
+        Ok(Self {
+            tournament: Arc::new(RwLock::new(Tournament::new(tournament_id, config)?)),
+            clients: Arc::new(RwLock::new(HashMap::new())),
+            finishes_sent: Arc::new(AtomicUsize::new(0)),
+            locale,
+        })
+    }
+
+    pub async fn start(&self, addr: SocketAddr) -> Result<(), Box<dyn std::error::Error>> {
+        let listener = TcpListener::bind(addr).await?;
+        info!("Tournament server listening on: {}", addr);
+        self.serve(listener).await
+    }
+
+    /// Accept connections on a bound listener
+    pub async fn serve(&self, listener: TcpListener) -> Result<(), Box<dyn std::error::Error>> {
+        let events = self.follow_tables().await;
+        runtime::spawn(self.clone().forward_events(events));
+
+        // Follow the clock and the action timers of every table, and deal the tables that can
+        let server = self.clone();
+        runtime::spawn(async move {
+            loop {
+                runtime::sleep(TICK).await;
+                server.tournament.write().await.tick().await;
+                server.send_finishes().await;
+            }
+        });
+
+        while let Ok((stream, peer_addr)) = listener.accept().await {
+            let server = self.clone();
+            runtime::spawn(async move {
+                if let Err(e) = server.handle_connection(stream, peer_addr).await {
+                    error!("Error handling connection from {}: {}", peer_addr, e);
+                }
+            });
+        }
+
+        Ok(())
+    }
+
+    /// The events of every table, in order, with the id of their table
+    async fn follow_tables(&self) -> UnboundedReceiver<(String, TableEvent)> {
+        let (event_sender, events) = unbounded_channel();
+        self.tournament.write().await.subscribe(Arc::new(
+            move |table_id: &str, event: &TableEvent| {
+                let _ = event_sender.send((table_id.to_owned(), event.clone()));
+            },
+        ));
+        events
+    }
+
+    /// Send the clients what happens at their tables, for as long as the server runs. A
+    /// player sits at the table whose game state last listed them, and busted players keep
+    /// following their last table.
+    async fn forward_events(self, mut events: UnboundedReceiver<(String, TableEvent)>) {
+        let mut tables: HashMap<String, String> = HashMap::new();
+        while let Some((table_id, event)) = events.recv().await {
+            self.deliver(&mut tables, &table_id, event).await;
+        }
+    }
+
+    /// Send the clients concerned the message of `event` at `table_id`. `tables` is the table
+    /// of each player, as of the previous events.
+    async fn deliver(
+        &self,
+        tables: &mut HashMap<String, String>,
+        table_id: &str,
+        event: TableEvent,
+    ) {
+        if let TableEvent::GameState { ref message, .. } = event {
+            for player in message.players.values() {
+                tables.insert(player.address.clone(), table_id.to_owned());
+            }
+        }
+        let (recipients, message) = match event {
+            TableEvent::Error { player_id, message } => (
+                vec![player_id],
+                WebSocketMessage {
+                    message_type: "error".to_string(),
+                    data: serde_json::to_value(message).unwrap_or_default(),
+                },
+            ),
+            event => {
+                let Some(message) = event.broadcast() else {
+                    return;
+                };
+                let at_table = tables
+                    .iter()
+                    .filter(|(_, table)| table.as_str() == table_id)
+                    .map(|(player_id, _)| player_id.clone())
+                    .collect();
+                (at_table, message)
+            }
+        };
+        if let Ok(json) = serde_json::to_string(&message) {
+            let clients = self.clients.read().await;
+            for client_id in recipients {
+                if let Some(sender) = clients.get(&client_id) {
+                    let _ = sender.send(Message::Text(json.clone()));
+                }
+            }
+        }
+    }
+
+    /// Tell the players who finished since the last call where they finished
+    async fn send_finishes(&self) {
+        let tournament = self.tournament.read().await;
+        let finishes = tournament.finishes();
+        let sent = self.finishes_sent.swap(finishes.len(), Ordering::SeqCst);
+        let clients = self.clients.read().await;
+        for finish in finishes.iter().skip(sent) {
+            let message = WebSocketMessage {
+                message_type: "tournamentFinish".to_string(),
+                data: serde_json::to_value(TournamentFinishMessage {
+                    place: finish.place,
+                    prize: finish.prize,
+                    bounties: finish.bounties,
+                })
+                .unwrap_or_default(),
+            };
+            if let (Some(sender), Ok(json)) = (
+                clients.get(&finish.player_id),
+                serde_json::to_string(&message),
+            ) {
+                let _ = sender.send(Message::Text(json));
+            }
+        }
+    }
+
+    async fn handle_connection(
+        self,
+        stream: TcpStream,
+        peer_addr: SocketAddr,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let client_id = Uuid::new_v4().to_string();
+        info!(
+            "New WebSocket connection from {} with ID {}",
+            peer_addr, client_id
+        );
+
+        let ws_stream = accept_async(stream).await?;
+        let (mut ws_sender, mut ws_receiver) = ws_stream.split();
+        let (tx, mut rx) = unbounded_channel();
+        self.clients.write().await.insert(client_id.clone(), tx);
+        let outgoing_task = runtime::spawn(async move {
+            while let Some(message) = rx.recv().await {
+                if ws_sender.send(message).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        while let Some(msg) = ws_receiver.next().await {
+            match msg {
+                Ok(Message::Text(text)) => {
+                    if let Err(e) = self.handle_message(&text, &client_id).await {
+                        error!("Error handling message from {}: {}", client_id, e);
+                    }
+                }
+                Ok(Message::Close(_)) => break,
+                Err(e) => {
+                    error!("WebSocket error for client {}: {}", client_id, e);
+                    break;
+                }
+                _ => {}
+            }
+        }
+
+        // The player stays in, and plays on as their action timer runs out
+        self.clients.write().await.remove(&client_id);
+        info!("Client {} disconnected", client_id);
+        outgoing_task.abort();
+        Ok(())
+    }
+
+    /// Play a client's request and send them the error if it is rejected
+    async fn handle_message(
+        &self,
+        text: &str,
+        client_id: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let message: WebSocketMessage = serde_json::from_str(text)?;
+        info!(
+            "Received message from {}: {}",
+            client_id, message.message_type
+        );
+
+        // Rejected requests are reported back to the client, anything else is only logged
+        let client_error = match self.dispatch_message(message, client_id).await {
+            Ok(()) => None,
+            Err(e) => Some(e.downcast::<ClientError>()?),
+        };
+        self.send_finishes().await;
+        let Some(client_error) = client_error else {
+            return Ok(());
+        };
+        let error = WebSocketMessage {
+            message_type: "error".to_string(),
+            data: serde_json::to_value(ErrorMessage {
+                code: client_error.code().to_string(),
+                message: client_error.text(self.locale),
+            })
+            .unwrap_or_default(),
+        };
+        if let Some(sender) = self.clients.read().await.get(client_id) {
+            let _ = sender.send(Message::Text(serde_json::to_string(&error)?));
+        }
+        Err(client_error)
+    }
+
+    async fn dispatch_message(
+        &self,
+        message: WebSocketMessage,
+        client_id: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut tournament = self.tournament.write().await;
+        let action = match message.message_type.as_str() {
+            "registerPlayer" => {
+                let register_msg: RegisterPlayerMessage = serde_json::from_value(message.data)?;
+                return tournament.register(client_id, &register_msg.name);
+            }
+            "startGame" => return tournament.start().await,
+            "fold" => PlayerAction::Fold,
+            "check" => PlayerAction::Check,
+            "call" => PlayerAction::Call,
+            "raise" | "bet" => {
+                let action_msg: PlayerActionMessage = serde_json::from_value(message.data)?;
+                let amount = action_msg.amount.unwrap_or(0.0);
+                match message.message_type.as_str() {
+                    "raise" => PlayerAction::Raise(amount),
+                    _ => PlayerAction::Bet(amount),
+                }
+            }
+            _ => {
+                warn!("Unknown message type: {}", message.message_type);
+                return Ok(());
+            }
+        };
+        tournament.handle_action(client_id, action).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tournament_clock::BlindLevel;
+    use serde_json::json;
+
+    /// The messages a client was sent since the last call
+    fn received(client: &mut UnboundedReceiver<Message>) -> Vec<WebSocketMessage> {
+        let mut messages = Vec::new();
+        while let Ok(Message::Text(text)) = client.try_recv() {
+            messages.push(serde_json::from_str(&text).unwrap());
+        }
+        messages
+    }
+
+    #[tokio::test]
+    async fn players_follow_their_own_table() {
+        let server = TournamentServer::new(
+            "sunday",
+            GameConfig {
+                max_players: 2,
+                session_seed: Some(7),
+                stats_file: None,
+                hand_history_dir: None,
+                blind_levels: vec![BlindLevel::checked(5.0, 10.0, 600.0, 0.0, 0.0).unwrap()],
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let mut events = server.follow_tables().await;
+        let mut clients = HashMap::new();
+        for i in 1..=4 {
+            let (sender, client) = unbounded_channel();
+            let client_id = format!("p{}", i);
+            server
+                .clients
+                .write()
+                .await
+                .insert(client_id.clone(), sender);
+            let register =
+                json!({ "messageType": "registerPlayer", "data": { "name": client_id } });
+            server
+                .handle_message(&register.to_string(), &client_id)
+                .await
+                .unwrap();
+            clients.insert(client_id, client);
+        }
+        let start = json!({ "messageType": "startGame", "data": {} }).to_string();
+        server.handle_message(&start, "p1").await.unwrap();
+
+        let mut tables = HashMap::new();
+        while let Ok((table_id, event)) = events.try_recv() {
+            server.deliver(&mut tables, &table_id, event).await;
+        }
+        // Every client is sent the state of the table they sit at, and the two tables differ
+        let mut table_ids = std::collections::HashSet::new();
+        for (client_id, client) in clients.iter_mut() {
+            let states: Vec<WebSocketMessage> = received(client)
+                .into_iter()
+                .filter(|m| m.message_type == "gameState")
+                .collect();
+            let state = &states.last().unwrap().data;
+            let players = state["players"].as_object().unwrap();
+            assert_eq!(players.len(), 2);
+            assert!(players.values().any(|p| p["address"] == client_id.as_str()));
+            table_ids.insert(state["tableId"].as_str().unwrap().to_owned());
+        }
+        assert_eq!(table_ids.len(), 2);
+
+        // A rejected request is answered with the error: there is no late registration
+        let register = json!({ "messageType": "registerPlayer", "data": { "name": "p5" } });
+        assert!(server
+            .handle_message(&register.to_string(), "p1")
+            .await
+            .is_err());
+        let errors = received(clients.get_mut("p1").unwrap());
+        assert_eq!(errors.last().unwrap().message_type, "error");
+        assert_eq!(errors.last().unwrap().data["code"], "registrationClosed");
+    }
+}
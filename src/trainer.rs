@@ -0,0 +1,614 @@
+// trainer.rs - Grade the hero's decisions against the EV of every option versus the villain's
+// range
+//
+// The EV of an option assumes the hand is checked down once the hero checks or calls, and that
+// the villain answers a bet or raise by defending the minimum share of its range that keeps the
+// bet from profiting with any two cards, with its strongest hands, and never re-raises.
+use crate::cards::combos_for_range;
+use crate::evaluator::Evaluator;
+use crate::explain::explain_action;
+use crate::range::{hand_class, preflop_rank, HandRange};
+use crate::seeds::hand_seed;
+use crate::solver_strategy::SolverStrategy;
+use crate::spots::SpotSpec;
+use crate::state::action::{Action, ActionEnum};
+use crate::state::card::Card;
+use crate::state::{State, StateStatus};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+
+/// Raises graded besides the hero's, as fractions of the pot after a call
+const POT_FRACTIONS: [f64; 2] = [0.5, 1.0];
+
+/// An option of the hero and its EV in chips, counted from the hero's stack before acting
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OptionEv {
+    #[pyo3(get)]
+    pub action: Action,
+    #[pyo3(get)]
+    pub ev: f64,
+}
+
+#[pymethods]
+impl OptionEv {
+    pub fn __str__(&self) -> PyResult<String> {
+        Ok(format!("{:#?}", self))
+    }
+}
+
+/// Verdict of `grade_action`
+#[pyclass]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActionGrade {
+    /// The action graded, as the engine applies it
+    #[pyo3(get)]
+    pub action: Action,
+    #[pyo3(get)]
+    pub ev: f64,
+    /// The option with the highest EV
+    #[pyo3(get)]
+    pub best: Action,
+    #[pyo3(get)]
+    pub best_ev: f64,
+    /// EV given up against the best option, never negative
+    #[pyo3(get)]
+    pub ev_loss: f64,
+    /// 100 for the best option, 0 for the worst and linear in between
+    #[pyo3(get)]
+    pub score: f64,
+    /// Equity of the hero's hand against the villain's range at showdown
+    #[pyo3(get)]
+    pub equity: f64,
+    /// Every option graded, best first
+    #[pyo3(get)]
+    pub options: Vec<OptionEv>,
+}
+
+#[pymethods]
+impl ActionGrade {
+    pub fn __str__(&self) -> PyResult<String> {
+        Ok(format!("{:#?}", self))
+    }
+}
+
+/// Equity of `hand` against `villain` over `samples` runouts of `board`, ties counting half. A
+/// complete board is evaluated once.
+fn runout_equity(
+    evaluator: &dyn Evaluator,
+    hand: (Card, Card),
+    villain: (Card, Card),
+    board: &[Card],
+    samples: usize,
+    rng: &mut StdRng,
+) -> f64 {
+    let samples = if board.len() >= 5 { 1 } else { samples.max(1) };
+    let deck = Card::collect();
+    let hole_cards = [hand.0, hand.1, villain.0, villain.1];
+    let mut runout = board.to_vec();
+    let mut score = 0.0;
+    for _ in 0..samples {
+        runout.truncate(board.len());
+        while runout.len() < 5 {
+            let card = deck[rng.gen_range(0..deck.len())];
+            if !runout.contains(&card) && !hole_cards.contains(&card) {
+                runout.push(card);
+            }
+        }
+        score += match evaluator
+            .rank_hand(hand, &runout)
+            .cmp(&evaluator.rank_hand(villain, &runout))
+        {
+            std::cmp::Ordering::Less => 1.0,
+            std::cmp::Ordering::Equal => 0.5,
+            std::cmp::Ordering::Greater => 0.0,
+        };
+    }
+    score / samples as f64
+}
+
+/// Share of a range defending against a bet that leaves `to_call` to call into `pot`, so that
+/// the bettor risking it to win the rest gains nothing with any two cards
+fn defended_share(pot: f64, to_call: f64) -> f64 {
+    if pot <= 0.0 {
+        return 1.0;
+    }
+    ((pot - to_call) / pot).clamp(0.0, 1.0)
+}
+
+/// The part of each combo's weight defending `share` of the range, its strongest hands first:
+/// the best made hands on a board, the best preflop classes before it
+fn defence(
+    evaluator: &dyn Evaluator,
+    board: &[Card],
+    combos: &[((Card, Card), f64)],
+    share: f64,
+) -> Vec<f64> {
+    let mut order: Vec<usize> = (0..combos.len()).collect();
+    if board.len() >= 3 {
+        let ranks: Vec<_> = combos
+            .iter()
+            .map(|(combo, _)| evaluator.rank_hand(*combo, board))
+            .collect();
+        order.sort_by(|&a, &b| ranks[a].cmp(&ranks[b]));
+    } else {
+        order.sort_by_key(|&i| preflop_rank(hand_class(combos[i].0)));
+    }
+
+    let total: f64 = combos.iter().map(|(_, weight)| weight).sum();
+    let mut left = share * total;
+    let mut parts = vec![0.0; combos.len()];
+    for i in order {
+        if left <= 0.0 {
+            break;
+        }
+        let weight = combos[i].1;
+        parts[i] = (left / weight).min(1.0);
+        left -= weight;
+    }
+    parts
+}
+
+/// The hero to act in `state` and the only villain left with it
+fn heads_up(state: &State) -> Result<(usize, usize), String> {
+    if !matches!(state.status, StateStatus::Ok) || state.final_state {
+        return Err("The hand is over".to_owned());
+    }
+    if state.legal_actions.is_empty() {
+        return Err(format!(
+            "Player {} is all-in and cannot act",
+            state.current_player
+        ));
+    }
+    let hero = state.current_player as usize;
+    let villains: Vec<usize> = state
+        .players_state
+        .iter()
+        .filter(|ps| ps.active && ps.player as usize != hero)
+        .map(|ps| ps.player as usize)
+        .collect();
+    match villains[..] {
+        [villain] => Ok((hero, villain)),
+        _ => Err("Decisions are graded against a single villain left in the hand".to_owned()),
+    }
+}
+
+/// The hero's options: folding when facing a bet, checking or calling, the hero's own raise and
+/// a minimum raise, half pot, pot and all-in, as the engine applies them
+fn options(state: &State, action: Action, hero: usize) -> Vec<Action> {
+    let ps = &state.players_state[hero];
+    let max_bet = state
+        .players_state
+        .iter()
+        .map(|ps| ps.bet_chips)
+        .fold(0.0, f64::max);
+    let to_call = max_bet - ps.bet_chips;
+
+    let mut candidates = vec![action];
+    if to_call > 0.0 {
+        candidates.push(Action::new(ActionEnum::Fold, 0.0));
+    }
+    candidates.push(Action::new(ActionEnum::CheckCall, 0.0));
+    if state.legal_actions.contains(&ActionEnum::BetRaise) {
+        let min_raise = state.min_bet * state.rules.min_raise_ratio.max(1.0);
+        candidates.push(Action::new(
+            ActionEnum::BetRaise,
+            min_raise.max(max_bet + state.bb),
+        ));
+        for fraction in POT_FRACTIONS {
+            let total = max_bet + fraction * (state.pot + to_call);
+            candidates.push(Action::new(ActionEnum::BetRaise, total));
+        }
+        candidates.push(Action::new(ActionEnum::BetRaise, ps.bet_chips + ps.stake));
+    }
+
+    let mut options: Vec<Action> = Vec::new();
+    for candidate in candidates {
+        let mut applied = explain_action(state, candidate)
+            .substitute
+            .unwrap_or(candidate);
+        // A bet of nothing more is a check or a call
+        if applied.action == ActionEnum::BetRaise && applied.amount <= max_bet {
+            applied = Action::new(ActionEnum::CheckCall, 0.0);
+        }
+        if !options.contains(&applied) {
+            options.push(applied);
+        }
+    }
+    options
+}
+
+/// EV of `action` for the hero against the villain's `combos`, each with its weight and the
+/// hero's equity against it
+fn option_ev(
+    state: &State,
+    (hero, villain): (usize, usize),
+    action: Action,
+    combos: &[((Card, Card), f64)],
+    equities: &[f64],
+) -> f64 {
+    let h = &state.players_state[hero];
+    let v = &state.players_state[villain];
+    let total: f64 = combos.iter().map(|(_, weight)| weight).sum();
+    let equity = |parts: &[f64]| -> (f64, f64) {
+        let weight: f64 = combos.iter().zip(parts).map(|((_, w), p)| w * p).sum();
+        let won: f64 = combos
+            .iter()
+            .zip(parts)
+            .zip(equities)
+            .map(|(((_, w), p), eq)| w * p * eq)
+            .sum();
+        (
+            weight / total,
+            if weight > 0.0 { won / weight } else { 0.0 },
+        )
+    };
+
+    match action.action {
+        ActionEnum::Fold => 0.0,
+        ActionEnum::CheckCall => {
+            let call = (v.bet_chips - h.bet_chips).clamp(0.0, h.stake);
+            // What the villain bet beyond the hero's stack goes back to it
+            let contested = state.pot - v.bet_chips + v.bet_chips.min(h.bet_chips + call) + call;
+            let (_, eq) = equity(&vec![1.0; combos.len()]);
+            eq * contested - call
+        }
+        ActionEnum::BetRaise => {
+            // Nothing the villain cannot match is at stake
+            let total_bet = action.amount.min(v.bet_chips + v.stake);
+            let added = (total_bet - h.bet_chips).max(0.0);
+            let to_call = (total_bet - v.bet_chips).max(0.0);
+            let share = defended_share(state.pot + added, to_call);
+            let parts = defence(
+                state.rules.variant.evaluator(),
+                &state.public_cards,
+                combos,
+                share,
+            );
+            let (called, eq) = equity(&parts);
+            (1.0 - called) * state.pot + called * (eq * (state.pot + added + to_call) - added)
+        }
+    }
+}
+
+/// Grade the hero's `action` in `state` against every option, the villain's hand drawn from
+/// `villain_range` without the cards the hero sees. Equities take `samples` runouts per villain
+/// combo, combo `i` dealt from `hand_seed(seed, i)`.
+pub fn grade(
+    state: &State,
+    villain_range: &HandRange,
+    action: Action,
+    samples: usize,
+    seed: u64,
+) -> Result<ActionGrade, String> {
+    let seats = heads_up(state)?;
+    let hand = state.players_state[seats.0].hand;
+    let board = &state.public_cards;
+    let mut dead = board.to_vec();
+    dead.extend([hand.0, hand.1]);
+    let combos = combos_for_range(villain_range, dead);
+    if combos.is_empty() {
+        return Err("No hand of the villain's range can be dealt".to_owned());
+    }
+
+    let evaluator = state.rules.variant.evaluator();
+    let equities: Vec<f64> = combos
+        .par_iter()
+        .enumerate()
+        .map(|(i, (combo, _))| {
+            let mut rng = StdRng::seed_from_u64(hand_seed(seed, i as u64));
+            runout_equity(evaluator, hand, *combo, board, samples, &mut rng)
+        })
+        .collect();
+    let total: f64 = combos.iter().map(|(_, weight)| weight).sum();
+    let equity = combos
+        .iter()
+        .zip(&equities)
+        .map(|((_, weight), eq)| weight * eq)
+        .sum::<f64>()
+        / total;
+
+    let mut options: Vec<OptionEv> = options(state, action, seats.0)
+        .into_iter()
+        .map(|action| OptionEv {
+            action,
+            ev: option_ev(state, seats, action, &combos, &equities),
+        })
+        .collect();
+    let graded = options[0];
+    options.sort_by(|a, b| b.ev.total_cmp(&a.ev));
+    let best = options[0];
+    let worst = options[options.len() - 1].ev;
+    let score = if best.ev > worst {
+        100.0 * (graded.ev - worst) / (best.ev - worst)
+    } else {
+        100.0
+    };
+
+    Ok(ActionGrade {
+        action: graded.action,
+        ev: graded.ev,
+        best: best.action,
+        best_ev: best.ev,
+        ev_loss: best.ev - graded.ev,
+        score,
+        equity,
+        options,
+    })
+}
+
+/// Grade `action`, the decision of the player to act in `state`, against the EV of every
+/// option versus `villain_range`, the range of the only other player left in the hand
+#[pyfunction]
+#[pyo3(signature = (state, villain_range, action, samples=200, seed=0))]
+pub fn grade_action(
+    py: Python,
+    state: &State,
+    villain_range: &HandRange,
+    action: Action,
+    samples: usize,
+    seed: u64,
+) -> PyResult<ActionGrade> {
+    py.allow_threads(|| grade(state, villain_range, action, samples, seed))
+        .map_err(PyValueError::new_err)
+}
+
+/// Training session over the spots of a `SpotSpec`. Each spot is played to the end: the hero's
+/// decisions are graded and applied, and the villain, dealt from its range, plays its solver
+/// strategy where it has one, and elsewhere checks and answers bets the way the grading assumes.
+#[pyclass]
+#[derive(Debug, Clone)]
+pub struct Trainer {
+    #[pyo3(get)]
+    pub spec: SpotSpec,
+    /// Range the villain's hand is graded against, the spec's or any hand
+    #[pyo3(get)]
+    pub villain_range: HandRange,
+    /// Strategy the villain plays on the nodes it solves
+    #[pyo3(get)]
+    pub villain_strategy: Option<SolverStrategy>,
+    /// Runouts per villain combo
+    #[pyo3(get)]
+    pub samples: usize,
+    #[pyo3(get)]
+    pub seed: u64,
+    /// Spots dealt, spot `i` from `hand_seed(seed, i)`
+    #[pyo3(get)]
+    pub spots_dealt: u64,
+    /// The spot being played, None before the first
+    #[pyo3(get)]
+    pub state: Option<State>,
+    /// Every decision graded so far
+    #[pyo3(get)]
+    pub grades: Vec<ActionGrade>,
+}
+
+impl Trainer {
+    /// Act for the villain until the hero is to act or the hand is over
+    fn play_villain(&self, mut state: State) -> State {
+        let (hero, villain) = (self.spec.hero, self.spec.villain);
+        while !state.final_state
+            && state.current_player == villain
+            && matches!(state.status, StateStatus::Ok)
+        {
+            let v = &state.players_state[villain as usize];
+            let solved = self.villain_strategy.as_ref();
+            if let Some(action) = solved.and_then(|strategy| strategy.sample(&state, v.hand)) {
+                state = state.apply_action(action);
+                continue;
+            }
+            let to_call = state.players_state[hero as usize].bet_chips - v.bet_chips;
+            let defends = to_call <= 0.0 || {
+                let combos = combos_for_range(&self.villain_range, state.public_cards.to_vec());
+                let share = defended_share(state.pot, to_call.min(v.stake));
+                let parts = defence(
+                    state.rules.variant.evaluator(),
+                    &state.public_cards,
+                    &combos,
+                    share,
+                );
+                combos
+                    .iter()
+                    .position(|(combo, _)| *combo == v.hand || *combo == (v.hand.1, v.hand.0))
+                    .is_some_and(|i| parts[i] >= 0.5)
+            };
+            let action = if defends {
+                ActionEnum::CheckCall
+            } else {
+                ActionEnum::Fold
+            };
+            state = state.apply_action(Action::new(action, 0.0));
+        }
+        state
+    }
+}
+
+#[pymethods]
+impl Trainer {
+    #[new]
+    #[pyo3(signature = (spec, samples=200, seed=0, villain_strategy=None))]
+    pub fn new(
+        spec: SpotSpec,
+        samples: usize,
+        seed: u64,
+        villain_strategy: Option<SolverStrategy>,
+    ) -> Trainer {
+        let villain_range = spec.villain_range.clone().unwrap_or(HandRange {
+            weights: vec![1.0; 169],
+        });
+        Trainer {
+            spec,
+            villain_range,
+            villain_strategy,
+            samples,
+            seed,
+            spots_dealt: 0,
+            state: None,
+            grades: Vec::new(),
+        }
+    }
+
+    /// Deal the next spot and play the villain up to the hero's first decision
+    pub fn next_spot(&mut self) -> PyResult<State> {
+        let state = self
+            .spec
+            .generate(hand_seed(self.seed, self.spots_dealt))
+            .map_err(PyValueError::new_err)?;
+        self.spots_dealt += 1;
+        let state = self.play_villain(state);
+        self.state = Some(state.clone());
+        Ok(state)
+    }
+
+    /// Whether the hero is to act in the current spot
+    pub fn awaiting_hero(&self) -> bool {
+        self.state.as_ref().is_some_and(|state| {
+            !state.final_state
+                && state.current_player == self.spec.hero
+                && !state.legal_actions.is_empty()
+        })
+    }
+
+    /// Grade the hero's `action`, then apply it and the villain's answer
+    pub fn act(&mut self, action: Action) -> PyResult<ActionGrade> {
+        if !self.awaiting_hero() {
+            return Err(PyValueError::new_err(
+                "The hero is not to act, deal a spot with next_spot",
+            ));
+        }
+        let state = self.state.take().unwrap();
+        let seed = hand_seed(self.seed ^ state.seed, self.grades.len() as u64);
+        let grade = match grade(&state, &self.villain_range, action, self.samples, seed) {
+            Ok(grade) => grade,
+            Err(msg) => {
+                self.state = Some(state);
+                return Err(PyValueError::new_err(msg));
+            }
+        };
+        self.state = Some(self.play_villain(state.apply_action(grade.action)));
+        self.grades.push(grade.clone());
+        Ok(grade)
+    }
+
+    /// Average score of the decisions graded so far, None before the first
+    #[getter]
+    pub fn score(&self) -> Option<f64> {
+        if self.grades.is_empty() {
+            return None;
+        }
+        Some(self.grades.iter().map(|grade| grade.score).sum::<f64>() / self.grades.len() as f64)
+    }
+
+    /// Chips given up against the best options so far
+    #[getter]
+    pub fn ev_loss(&self) -> f64 {
+        self.grades.iter().map(|grade| grade.ev_loss).sum()
+    }
+
+    pub fn __str__(&self) -> PyResult<String> {
+        Ok(format!("{:#?}", self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spots::PotType;
+    use crate::state::stage::Stage;
+
+    fn cards(names: &[&str]) -> Vec<Card> {
+        names
+            .iter()
+            .map(|name| Card::from_string(name.to_string()).unwrap())
+            .collect()
+    }
+
+    /// A heads-up hand where player 1 holds `first` and player 0 `second`, checked to the river
+    /// of `board` with player 1 to act
+    fn river(first: [&str; 2], second: [&str; 2], board: [&str; 5]) -> State {
+        let mut deck = cards(&[first[0], first[1], second[0], second[1]]);
+        deck.extend(cards(&board));
+        for card in Card::collect() {
+            if !deck.contains(&card) {
+                deck.push(card);
+            }
+        }
+        let mut state = State::from_deck(2, 0, 0.5, 1.0, 100.0, deck, false, 0, 0.0).unwrap();
+        while state.stage != Stage::River {
+            state = state.apply_action(Action::new(ActionEnum::CheckCall, 0.0));
+        }
+        assert_eq!(state.public_cards.to_vec(), cards(&board));
+        state
+    }
+
+    #[test]
+    fn the_nuts_bet_and_air_folds() {
+        let board = ["SA", "SK", "D7", "C2", "H9"];
+        let villain_range = HandRange::from_string("KQs, KJs, QJs, 77, 22").unwrap();
+        let check = Action::new(ActionEnum::CheckCall, 0.0);
+        let pot_bet = Action::new(ActionEnum::BetRaise, 2.0);
+
+        // Top set beats the whole range: betting beats checking
+        let state = river(["HA", "DA"], ["C3", "C4"], board);
+        assert_eq!(state.current_player, 1);
+        let nuts = grade(&state, &villain_range, check, 1, 0).unwrap();
+        assert_eq!(nuts.equity, 1.0);
+        assert_eq!(nuts.ev, 2.0);
+        assert_eq!(nuts.best.action, ActionEnum::BetRaise);
+        assert!(nuts.score < 100.0 && nuts.ev_loss > 0.0);
+        let bet = grade(&state, &villain_range, pot_bet, 1, 0).unwrap();
+        assert!(bet.ev > nuts.ev);
+
+        // Six high facing a pot bet loses to everything: calling or raising costs the call
+        let state = river(["C3", "C5"], ["D6", "H4"], board).apply_action(pot_bet);
+        assert_eq!(state.current_player, 0);
+        let air = grade(&state, &villain_range, check, 1, 0).unwrap();
+        assert_eq!(air.equity, 0.0);
+        assert_eq!(air.ev, -2.0);
+        assert_eq!(air.best, Action::new(ActionEnum::Fold, 0.0));
+        assert_eq!(air.score, 0.0);
+        assert_eq!(air.options.len(), 6);
+        assert!(air.options.windows(2).all(|w| w[0].ev >= w[1].ev));
+
+        // Nothing is left to grade once the hand is over
+        let over = state.apply_action(Action::new(ActionEnum::Fold, 0.0));
+        assert!(grade(&over, &villain_range, check, 1, 0).is_err());
+    }
+
+    #[test]
+    fn trainer_plays_spots_to_the_end() {
+        let spec = SpotSpec::checked(
+            6,
+            2,
+            0,
+            PotType::SingleRaised,
+            40.0,
+            None,
+            Some(HandRange::from_string("AA, KK, QQ, AKs, 98s, 76s").unwrap()),
+            None,
+            2.5,
+            3.0,
+        )
+        .unwrap();
+        let mut trainer = Trainer::new(spec, 20, 5, None);
+        for _ in 0..3 {
+            let spot = trainer.next_spot().unwrap();
+            assert_eq!(spot.stage, Stage::Flop);
+            while trainer.awaiting_hero() {
+                let grade = trainer
+                    .act(Action::new(ActionEnum::CheckCall, 0.0))
+                    .unwrap();
+                assert!((0.0..=100.0).contains(&grade.score));
+                assert!((0.0..=1.0).contains(&grade.equity));
+            }
+            assert!(trainer.state.as_ref().unwrap().final_state);
+        }
+        assert!(trainer.act(Action::new(ActionEnum::Fold, 0.0)).is_err());
+        assert_eq!(trainer.spots_dealt, 3);
+        assert_eq!(trainer.grades.len(), 9);
+        assert!(trainer.score().is_some());
+    }
+}
@@ -0,0 +1,299 @@
+// trajectories.rs - Parquet recording of batched simulation steps
+use crate::datasets::{observation, observation_array, record_batch, writer_properties};
+use crate::node_lock::NodeLock;
+use crate::parallel::apply_and_record;
+use crate::state::action::{Action, ActionEnum};
+use crate::state::{State, StateStatus};
+use arrow::array::{
+    ArrayRef, BooleanArray, FixedSizeListArray, Float64Array, ListArray, StringArray, UInt32Array,
+    UInt64Array, UInt8Array,
+};
+use arrow::datatypes::{DataType, Field, Float64Type};
+use arrow::record_batch::RecordBatch;
+use parquet::arrow::ArrowWriter;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::fs::File;
+use std::sync::Arc;
+
+/// One decision of one environment, amounts in big blinds
+struct Step {
+    env: u32,
+    seed: u64,
+    hand_id: String,
+    /// Empty when the state has no table or session id
+    table_id: String,
+    session_id: String,
+    n_players: u8,
+    button: u8,
+    player: u8,
+    stage: u8,
+    observation: Vec<f32>,
+    /// Fold, CheckCall, BetRaise
+    legal_actions: [bool; 3],
+    action: u8,
+    amount: f64,
+    /// The action asked for, when the engine replaced it
+    requested_action: u8,
+    requested_amount: f64,
+    /// 0 for a genuine decision, the `Coercion` otherwise
+    coercion: u8,
+    /// Reward of the acting player, non-zero only when the step ends the hand
+    reward: f64,
+    done: bool,
+    /// Rewards of every player when the step ends the hand, empty otherwise
+    rewards: Vec<f64>,
+}
+
+impl Step {
+    /// `None` when `state` does not expect an action (hand over or engine error)
+    fn record(env: usize, state: &State, next: &State) -> Option<Step> {
+        if state.final_state || !matches!(state.status, StateStatus::Ok) {
+            return None;
+        }
+        let record = next.action_list.get(state.action_list.len())?;
+        let bb = state.bb;
+
+        Some(Step {
+            env: env as u32,
+            seed: state.seed,
+            hand_id: state.hand_id.clone(),
+            table_id: state.table_id.clone().unwrap_or_default(),
+            session_id: state.session_id.clone().unwrap_or_default(),
+            n_players: state.players_state.len() as u8,
+            button: state.button as u8,
+            player: state.current_player as u8,
+            stage: state.stage as u8,
+            observation: observation(state, state.current_player),
+            legal_actions: [
+                ActionEnum::Fold,
+                ActionEnum::CheckCall,
+                ActionEnum::BetRaise,
+            ]
+            .map(|a| state.legal_actions.contains(&a)),
+            action: record.action.action as u8,
+            amount: record.action.amount / bb,
+            requested_action: record.requested.action as u8,
+            requested_amount: record.requested.amount / bb,
+            coercion: record.coercion.map_or(0, |c| c as u8),
+            reward: if next.final_state {
+                next.players_state[state.current_player as usize].reward / bb
+            } else {
+                0.0
+            },
+            done: next.final_state,
+            rewards: if next.final_state {
+                next.players_state.iter().map(|ps| ps.reward / bb).collect()
+            } else {
+                Vec::new()
+            },
+        })
+    }
+}
+
+fn steps_batch(steps: &[Step]) -> Result<RecordBatch, String> {
+    let observations = observation_array(steps.iter().flat_map(|s| s.observation.iter().copied()))?;
+    let legal_actions = FixedSizeListArray::try_new(
+        Arc::new(Field::new("item", DataType::Boolean, false)),
+        3,
+        Arc::new(BooleanArray::from(
+            steps
+                .iter()
+                .flat_map(|s| s.legal_actions)
+                .collect::<Vec<bool>>(),
+        )),
+        None,
+    )
+    .map_err(|e| e.to_string())?;
+    let rewards = ListArray::from_iter_primitive::<Float64Type, _, _>(
+        steps
+            .iter()
+            .map(|s| Some(s.rewards.iter().map(|&r| Some(r)).collect::<Vec<_>>())),
+    );
+
+    let u8_column = |f: fn(&Step) -> u8| -> ArrayRef {
+        Arc::new(UInt8Array::from_iter_values(steps.iter().map(f)))
+    };
+    let f64_column = |f: fn(&Step) -> f64| -> ArrayRef {
+        Arc::new(Float64Array::from_iter_values(steps.iter().map(f)))
+    };
+    let string_column = |f: fn(&Step) -> &str| -> ArrayRef {
+        Arc::new(StringArray::from_iter_values(steps.iter().map(f)))
+    };
+
+    record_batch(vec![
+        (
+            "env",
+            Arc::new(UInt32Array::from_iter_values(steps.iter().map(|s| s.env))),
+        ),
+        (
+            "seed",
+            Arc::new(UInt64Array::from_iter_values(steps.iter().map(|s| s.seed))),
+        ),
+        ("hand_id", string_column(|s| &s.hand_id)),
+        ("table_id", string_column(|s| &s.table_id)),
+        ("session_id", string_column(|s| &s.session_id)),
+        ("n_players", u8_column(|s| s.n_players)),
+        ("button", u8_column(|s| s.button)),
+        ("player", u8_column(|s| s.player)),
+        ("stage", u8_column(|s| s.stage)),
+        ("observation", Arc::new(observations)),
+        ("legal_actions", Arc::new(legal_actions)),
+        ("action", u8_column(|s| s.action)),
+        ("amount", f64_column(|s| s.amount)),
+        ("requested_action", u8_column(|s| s.requested_action)),
+        ("requested_amount", f64_column(|s| s.requested_amount)),
+        ("coercion", u8_column(|s| s.coercion)),
+        ("reward", f64_column(|s| s.reward)),
+        (
+            "done",
+            Arc::new(BooleanArray::from(
+                steps.iter().map(|s| s.done).collect::<Vec<bool>>(),
+            )),
+        ),
+        ("rewards", Arc::new(rewards)),
+    ])
+}
+
+/// Records every decision of a batched simulation straight to a Parquet file. Rows are
+/// buffered in Rust and written one row group at a time, so nothing goes through Python.
+#[pyclass]
+pub struct TrajectoryRecorder {
+    writer: Option<ArrowWriter<File>>,
+    steps: Vec<Step>,
+    row_group_size: usize,
+
+    /// Rows recorded so far, written or still buffered
+    #[pyo3(get)]
+    pub rows: usize,
+}
+
+#[pymethods]
+impl TrajectoryRecorder {
+    #[new]
+    #[pyo3(signature = (path, row_group_size=65536))]
+    pub fn new(path: &str, row_group_size: usize) -> PyResult<TrajectoryRecorder> {
+        let file = File::create(path)
+            .map_err(|e| PyValueError::new_err(format!("Cannot create {}: {}", path, e)))?;
+        let schema = steps_batch(&[]).map_err(PyValueError::new_err)?.schema();
+        let writer = ArrowWriter::try_new(file, schema, Some(writer_properties()))
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+
+        Ok(TrajectoryRecorder {
+            writer: Some(writer),
+            steps: Vec::with_capacity(row_group_size),
+            row_group_size: row_group_size.max(1),
+            rows: 0,
+        })
+    }
+
+    /// Apply `actions` to `states` in parallel like `parallel_apply_action`, recording one
+    /// row per state that was waiting for an action
+    #[pyo3(signature = (states, actions, locks=None))]
+    pub fn step(
+        &mut self,
+        states: Vec<State>,
+        actions: Vec<Action>,
+        locks: Option<Vec<NodeLock>>,
+    ) -> PyResult<Vec<State>> {
+        if self.writer.is_none() {
+            return Err(PyValueError::new_err("The recorder is closed"));
+        }
+
+        let locks = locks.unwrap_or_default();
+        let (next_states, steps) = apply_and_record(&states, actions, &locks, Step::record);
+
+        for step in steps {
+            self.steps.push(step);
+            self.rows += 1;
+            if self.steps.len() >= self.row_group_size {
+                self.flush()?;
+            }
+        }
+        Ok(next_states)
+    }
+
+    /// Write the buffered rows
+    pub fn flush(&mut self) -> PyResult<()> {
+        let writer = match self.writer.as_mut() {
+            Some(writer) => writer,
+            None => return Err(PyValueError::new_err("The recorder is closed")),
+        };
+        if self.steps.is_empty() {
+            return Ok(());
+        }
+
+        let batch = steps_batch(&self.steps).map_err(PyValueError::new_err)?;
+        writer
+            .write(&batch)
+            .and_then(|_| writer.flush())
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        self.steps.clear();
+        Ok(())
+    }
+
+    /// Write the remaining rows and the file footer. The file is unreadable until closed.
+    pub fn close(&mut self) -> PyResult<()> {
+        if self.writer.is_none() {
+            return Ok(());
+        }
+        self.flush()?;
+        if let Some(writer) = self.writer.take() {
+            writer
+                .close()
+                .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    pub fn __enter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+
+    pub fn __exit__(
+        &mut self,
+        _exc_type: &PyAny,
+        _exc_value: &PyAny,
+        _traceback: &PyAny,
+    ) -> PyResult<()> {
+        self.close()
+    }
+}
+
+impl Drop for TrajectoryRecorder {
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+
+    #[test]
+    fn records_one_row_per_decision() {
+        let path = std::env::temp_dir().join(format!(
+            "pokers_trajectories_{}.parquet",
+            std::process::id()
+        ));
+        let mut recorder = TrajectoryRecorder::new(path.to_str().unwrap(), 2).unwrap();
+
+        let mut states: Vec<State> = (0..3)
+            .map(|seed| State::from_seed(2, 0, 0.5, 1.0, 100.0, seed, false, 0.0).unwrap())
+            .collect();
+        // Fold everything: one decision per table, the second batch finds the hands over
+        for _ in 0..2 {
+            let actions = vec![Action::new(ActionEnum::Fold, 0.0); states.len()];
+            states = recorder.step(states, actions, None).unwrap();
+        }
+        assert_eq!(recorder.rows, 3);
+        recorder.close().unwrap();
+
+        let reader = SerializedFileReader::new(File::open(&path).unwrap()).unwrap();
+        assert_eq!(reader.metadata().file_metadata().num_rows(), 3);
+        // Row groups of 2 rows: flushed once full, then the last row on close
+        assert_eq!(reader.metadata().num_row_groups(), 2);
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -1,9 +1,25 @@
 // visualization.rs
+use crate::chip_format::ChipFormat;
+use crate::messages::{self, display_width, Label, Locale};
+use crate::range::{hand_class_name, HandRange, GRID_RANKS, N_HAND_CLASSES};
 use crate::state::State;
+use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
 
+/// Pad `text` with spaces to `width` terminal columns
+fn pad(text: &str, width: usize, left: bool) -> String {
+    let padding = " ".repeat(width.saturating_sub(display_width(text)));
+    if left {
+        format!("{padding}{text}")
+    } else {
+        format!("{text}{padding}")
+    }
+}
+
+/// The states of a hand one under the other, the amounts written with `chips`
 #[pyfunction]
-pub fn visualize_trace(trace: Vec<State>) -> String {
+#[pyo3(signature = (trace, locale=Locale::En, chips=None))]
+pub fn visualize_trace(trace: Vec<State>, locale: Locale, chips: Option<ChipFormat>) -> String {
     let players = trace[0]
         .players_state
         .iter()
@@ -25,16 +41,25 @@ pub fn visualize_trace(trace: Vec<State>) -> String {
 
     let vis = trace
         .iter()
-        .map(|state| visualize_state(state))
-        .fold(format!("{players}    pot    public\n{hands}"), |s1, s2| {
-            format!("{s1}\n{s2}")
-        });
+        .map(|state| visualize_state(state, locale, chips.clone()))
+        .fold(
+            format!(
+                "{players}{}    {}\n{hands}",
+                pad(Label::Pot.text(locale), 7, true),
+                Label::Board.text(locale)
+            ),
+            |s1, s2| format!("{s1}\n{s2}"),
+        );
 
     vis
 }
 
+/// The action that led to `state`, the bets and stacks, the pot and the board, the amounts
+/// written with `chips`
 #[pyfunction]
-pub fn visualize_state(state: &State) -> String {
+#[pyo3(signature = (state, locale=Locale::En, chips=None))]
+pub fn visualize_state(state: &State, locale: Locale, chips: Option<ChipFormat>) -> String {
+    let chips = chips.unwrap_or_default();
     let action = match &state.from_action {
         None => "".to_owned(),
         Some(action_record) => {
@@ -42,12 +67,13 @@ pub fn visualize_state(state: &State) -> String {
             let pad = std::iter::repeat(" ")
                 .take(action_offset as usize)
                 .collect::<String>();
+            let action = messages::action(action_record.action.action, locale);
             if action_record.action.amount == 0.0 {
-                format!("{pad}↓ {:?}\n", action_record.action.action)
+                format!("{pad}↓ {action}\n")
             } else {
                 format!(
-                    "{pad}↓ {:?}({})\n",
-                    action_record.action.action, action_record.action.amount
+                    "{pad}↓ {action}({})\n",
+                    chips.format(action_record.action.amount)
                 )
             }
         }
@@ -60,11 +86,11 @@ pub fn visualize_state(state: &State) -> String {
             format!(
                 "{0:>4}/{1:<3}",
                 if ps.active {
-                    ps.bet_chips.to_string()
+                    chips.format(ps.bet_chips)
                 } else {
-                    format!("x{}", ps.bet_chips)
+                    format!("x{}", chips.format(ps.bet_chips))
                 },
-                ps.bet_chips + ps.stake,
+                chips.format(ps.bet_chips + ps.stake),
             )
         })
         .fold("".to_owned(), |s1, s2| format!("{s1}  {s2}"));
@@ -74,7 +100,89 @@ pub fn visualize_state(state: &State) -> String {
         .iter()
         .fold("".to_owned(), |c1, c2| format!("{0} {1}", c1, c2));
     format!(
-        "{action}{0:<9?}:{players_bets}  {1:>4}    |{public_cards}|",
-        state.stage, state.pot
+        "{action}{0}:{players_bets}  {1:>4}    |{public_cards}|",
+        pad(messages::stage(state.stage, locale), 9, false),
+        chips.format(state.chips_in_pot())
     )
 }
+
+#[derive(FromPyObject)]
+pub enum RangeOrWeights {
+    Range(HandRange),
+    Weights(Vec<f64>),
+}
+
+/// Render a 13x13 preflop grid (pairs on the diagonal, suited above, offsuit below) with
+/// the weight of every hand class, as plain text or as an HTML table
+#[pyfunction]
+#[pyo3(signature = (range, html=false))]
+pub fn render_range_grid(range: RangeOrWeights, html: bool) -> PyResult<String> {
+    let range = match range {
+        RangeOrWeights::Range(range) => range,
+        RangeOrWeights::Weights(weights) => HandRange::from_weights(weights)?,
+    };
+    if range.weights.len() != N_HAND_CLASSES {
+        return Err(PyValueError::new_err("A range needs 169 weights"));
+    }
+
+    if html {
+        Ok(range_grid_html(&range))
+    } else {
+        Ok(range_grid_text(&range))
+    }
+}
+
+fn range_grid_text(range: &HandRange) -> String {
+    let header = GRID_RANKS
+        .iter()
+        .fold("  ".to_owned(), |s, r| format!("{s} {r:>4}"));
+
+    (0..13).fold(header, |grid, row| {
+        let cells = (0..13)
+            .map(|col| match range.weight(row, col) {
+                w if w <= 0.0 => "   .".to_owned(),
+                w => format!("{:>4}", (w * 100.0).round()),
+            })
+            .fold(format!(" {}", GRID_RANKS[row]), |s, c| format!("{s} {c}"));
+        format!("{grid}\n{cells}")
+    })
+}
+
+pub(crate) fn range_grid_html(range: &HandRange) -> String {
+    let rows = (0..13)
+        .map(|row| {
+            let cells = (0..13)
+                .map(|col| {
+                    let weight = range.weight(row, col).clamp(0.0, 1.0);
+                    format!(
+                        "<td title=\"{0}: {1:.3}\" style=\"background-color: rgba(46, 160, 67, {1:.3}); text-align: center; padding: 4px;\">{0}</td>",
+                        hand_class_name(row, col),
+                        weight
+                    )
+                })
+                .collect::<String>();
+            format!("<tr>{cells}</tr>")
+        })
+        .collect::<String>();
+
+    format!("<table style=\"border-collapse: collapse; font-family: monospace;\">{rows}</table>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn range_grid_text_layout() {
+        let range = HandRange::from_string("AA, AKs:0.5, KQo").unwrap();
+        let grid = range_grid_text(&range);
+        let lines: Vec<&str> = grid.lines().collect();
+        assert_eq!(lines.len(), 14);
+        assert_eq!(
+            lines[0],
+            "      A    K    Q    J    T    9    8    7    6    5    4    3    2"
+        );
+        assert!(lines[1].starts_with(" A  100   50    ."));
+        assert!(lines[3].starts_with(" Q    .  100    ."));
+    }
+}
@@ -0,0 +1,478 @@
+// webhooks.rs - HTTP notifications of finished hands and players coming and going
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{error, warn};
+
+use crate::events::TableEvent;
+use crate::game_logic::now;
+use crate::messages::{Label, Locale};
+use crate::protocol::CardInfo;
+use crate::state::schema::SCHEMA_VERSION;
+use crate::websocket_server::WinningInfo;
+
+/// Seconds a webhook endpoint has to answer
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+/// Attempts at delivering an event before it is dropped
+const WEBHOOK_ATTEMPTS: u32 = 3;
+
+/// What happened at the table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebhookEvent {
+    /// A hand was settled, with the result of every player dealt in
+    HandComplete,
+    /// A player took a seat at the table
+    PlayerJoined,
+    /// A seated player's connection closed
+    PlayerLeft,
+    /// A hand with a pot of at least `GameConfig.big_pot_alert` big blinds was settled
+    BigPot,
+}
+
+impl WebhookEvent {
+    pub const ALL: [WebhookEvent; 4] = [
+        WebhookEvent::HandComplete,
+        WebhookEvent::PlayerJoined,
+        WebhookEvent::PlayerLeft,
+        WebhookEvent::BigPot,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            WebhookEvent::HandComplete => "handComplete",
+            WebhookEvent::PlayerJoined => "playerJoined",
+            WebhookEvent::PlayerLeft => "playerLeft",
+            WebhookEvent::BigPot => "bigPot",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<WebhookEvent> {
+        WebhookEvent::ALL
+            .into_iter()
+            .find(|event| event.name() == name)
+    }
+}
+
+/// Body of the requests sent to a webhook
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WebhookFormat {
+    /// `{"event", "schemaVersion", "tableId", "sessionId", "timestamp", "data"}`
+    #[default]
+    Json,
+    /// A Slack incoming webhook message, `{"text"}`
+    Slack,
+    /// A Discord webhook message, `{"content"}`
+    Discord,
+}
+
+/// Endpoint the server POSTs table events to
+#[pyclass]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Webhook {
+    #[pyo3(get, set)]
+    pub url: String,
+    /// Names of the events sent to the endpoint, every event when empty
+    #[pyo3(get)]
+    pub events: Vec<String>,
+    #[pyo3(get, set)]
+    pub format: WebhookFormat,
+}
+
+impl Webhook {
+    pub fn checked(
+        url: String,
+        events: Vec<String>,
+        format: WebhookFormat,
+    ) -> Result<Webhook, String> {
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return Err(format!("Webhook URL '{}' is not an HTTP(S) URL", url));
+        }
+        if let Some(unknown) = events
+            .iter()
+            .find(|event| WebhookEvent::from_name(event).is_none())
+        {
+            return Err(format!(
+                "Unknown webhook event '{}', expected one of {}",
+                unknown,
+                WebhookEvent::ALL.map(WebhookEvent::name).join(", ")
+            ));
+        }
+        Ok(Webhook {
+            url,
+            events,
+            format,
+        })
+    }
+
+    /// See `Webhook.parse_list`
+    pub fn parse(list: &str) -> Result<Vec<Webhook>, String> {
+        list.split(',')
+            .map(|webhook| webhook.trim())
+            .filter(|webhook| !webhook.is_empty())
+            .map(|webhook| {
+                let (format, url) = match webhook.split_once('=') {
+                    Some(("slack", url)) => (WebhookFormat::Slack, url),
+                    Some(("discord", url)) => (WebhookFormat::Discord, url),
+                    Some(("json", url)) => (WebhookFormat::Json, url),
+                    _ => (WebhookFormat::Json, webhook),
+                };
+                Webhook::checked(url.to_string(), Vec::new(), format)
+            })
+            .collect()
+    }
+
+    fn wants(&self, event: WebhookEvent) -> bool {
+        self.events.is_empty() || self.events.iter().any(|name| name == event.name())
+    }
+
+    fn body(&self, event: WebhookEvent, payload: &Value, text: &str) -> Value {
+        match self.format {
+            WebhookFormat::Json => {
+                let mut body = payload.clone();
+                body["event"] = event.name().into();
+                body
+            }
+            WebhookFormat::Slack => json!({ "text": text }),
+            WebhookFormat::Discord => json!({ "content": text }),
+        }
+    }
+}
+
+#[pymethods]
+impl Webhook {
+    #[new]
+    #[pyo3(signature = (url, events=Vec::new(), format=WebhookFormat::Json))]
+    pub fn new(url: String, events: Vec<String>, format: WebhookFormat) -> PyResult<Webhook> {
+        Webhook::checked(url, events, format).map_err(PyValueError::new_err)
+    }
+
+    #[setter]
+    pub fn set_events(&mut self, events: Vec<String>) -> PyResult<()> {
+        *self = Webhook::checked(self.url.clone(), events, self.format)
+            .map_err(PyValueError::new_err)?;
+        Ok(())
+    }
+
+    /// Webhooks separated by commas, each a URL optionally prefixed with `slack=`, `discord=`
+    /// or `json=` for its format, e.g. `slack=https://hooks.slack.com/services/...`. Every
+    /// event is sent to them.
+    #[staticmethod]
+    pub fn parse_list(list: &str) -> PyResult<Vec<Webhook>> {
+        Webhook::parse(list).map_err(PyValueError::new_err)
+    }
+
+    pub fn __str__(&self) -> PyResult<String> {
+        Ok(format!("{:#?}", self))
+    }
+}
+
+/// Result of a player dealt into a finished hand
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerResult {
+    pub seat: u8,
+    pub player_name: String,
+    /// Chips won, negative when lost
+    pub net: f64,
+    /// Stack after the hand
+    pub chips: f64,
+}
+
+/// Data of the `handComplete` and `bigPot` events
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HandResult {
+    pub hand_id: String,
+    pub pot: f64,
+    /// The pot in big blinds of the hand
+    pub big_blinds: f64,
+    pub community_cards: Vec<CardInfo>,
+    pub winnings: Vec<WinningInfo>,
+    /// Every player dealt in, in the order of `State.players_state`
+    pub players: Vec<PlayerResult>,
+}
+
+/// One request of the delivery queue
+#[derive(Debug)]
+struct Delivery {
+    url: String,
+    body: Value,
+}
+
+/// Sends the table events to the configured webhooks, one at a time and in order, from a
+/// thread of its own so a slow endpoint never holds up the table
+#[derive(Debug, Clone, Default)]
+pub struct Webhooks {
+    webhooks: Vec<Webhook>,
+    /// See `GameConfig.big_pot_alert`
+    big_pot_alert: Option<f64>,
+    /// Language of the hand descriptions
+    locale: Locale,
+    table_id: String,
+    session_id: String,
+    sender: Option<mpsc::UnboundedSender<Delivery>>,
+}
+
+impl Webhooks {
+    pub fn new(
+        webhooks: Vec<Webhook>,
+        big_pot_alert: Option<f64>,
+        locale: Locale,
+        table_id: &str,
+        session_id: &str,
+    ) -> Self {
+        let sender = (!webhooks.is_empty()).then(|| {
+            let (sender, receiver) = mpsc::unbounded_channel();
+            std::thread::spawn(move || deliver(receiver));
+            sender
+        });
+        Self {
+            webhooks,
+            big_pot_alert,
+            locale,
+            table_id: table_id.to_string(),
+            session_id: session_id.to_string(),
+            sender,
+        }
+    }
+
+    /// Send the webhook events of a table event
+    pub fn on_event(&self, event: &TableEvent) {
+        match event {
+            TableEvent::PlayerJoined { seat, name, chips } => {
+                let text = format!(
+                    "{} took seat {} at table {} with {} chips",
+                    name, seat, self.table_id, chips
+                );
+                let player = PlayerResult {
+                    seat: *seat,
+                    player_name: name.clone(),
+                    net: 0.0,
+                    chips: *chips,
+                };
+                self.send(WebhookEvent::PlayerJoined, player, &text);
+            }
+            TableEvent::PlayerLeft {
+                seat,
+                name,
+                chips,
+                session_net,
+            } => {
+                let text = format!("{} left table {}", name, self.table_id);
+                let player = PlayerResult {
+                    seat: *seat,
+                    player_name: name.clone(),
+                    net: *session_net,
+                    chips: *chips,
+                };
+                self.send(WebhookEvent::PlayerLeft, player, &text);
+            }
+            TableEvent::HandComplete(hand) => {
+                let state = &hand.state;
+                let players: Vec<PlayerResult> = hand
+                    .players
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, player)| !player.name.is_empty())
+                    .map(|(index, player)| PlayerResult {
+                        seat: player.seat,
+                        player_name: player.name.clone(),
+                        net: hand.net(index),
+                        chips: player.chips,
+                    })
+                    .collect();
+                let uncontested = Label::Uncontested.text(self.locale);
+                let won = hand
+                    .winnings
+                    .iter()
+                    .map(|w| match w.hand_description.as_str() {
+                        description if description == uncontested => {
+                            format!("{} won {}", w.player_name, w.amount_won)
+                        }
+                        description => format!(
+                            "{} won {} with {}",
+                            w.player_name, w.amount_won, description
+                        ),
+                    })
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                let pot = state.chips_in_pot();
+                let big_blinds = pot / state.bb;
+                let result = HandResult {
+                    hand_id: state.hand_id.clone(),
+                    pot,
+                    big_blinds,
+                    community_cards: hand.community_cards.clone(),
+                    winnings: hand.winnings.clone(),
+                    players,
+                };
+
+                let text = format!("Hand {} at table {}: {}", state.hand_id, self.table_id, won);
+                self.send(WebhookEvent::HandComplete, &result, &text);
+                if matches!(self.big_pot_alert, Some(alert) if big_blinds >= alert) {
+                    let text = format!(
+                        "Big pot of {} ({:.0} big blinds) at table {}: {}",
+                        pot, big_blinds, self.table_id, won
+                    );
+                    self.send(WebhookEvent::BigPot, &result, &text);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Queue `event` for every webhook that wants it. `data` is sent to JSON webhooks and
+    /// `text` to chat webhooks.
+    pub fn send(&self, event: WebhookEvent, data: impl Serialize, text: &str) {
+        let Some(ref sender) = self.sender else {
+            return;
+        };
+        let payload = json!({
+            "schemaVersion": SCHEMA_VERSION,
+            "tableId": self.table_id,
+            "sessionId": self.session_id,
+            "timestamp": now(),
+            "data": data,
+        });
+        for webhook in self.webhooks.iter().filter(|webhook| webhook.wants(event)) {
+            let delivery = Delivery {
+                url: webhook.url.clone(),
+                body: webhook.body(event, &payload, text),
+            };
+            // Only fails once the delivery thread is gone
+            if sender.send(delivery).is_err() {
+                error!("Webhook delivery stopped, dropping {} event", event.name());
+            }
+        }
+    }
+}
+
+/// Deliver the queued requests until every `Webhooks` is dropped
+fn deliver(mut receiver: mpsc::UnboundedReceiver<Delivery>) {
+    let runtime = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            error!("Failed to start webhook delivery: {}", e);
+            return;
+        }
+    };
+    let client = reqwest::Client::builder()
+        .timeout(WEBHOOK_TIMEOUT)
+        .build()
+        .unwrap_or_default();
+    runtime.block_on(async move {
+        while let Some(delivery) = receiver.recv().await {
+            for attempt in 1..=WEBHOOK_ATTEMPTS {
+                let result = client
+                    .post(&delivery.url)
+                    .json(&delivery.body)
+                    .send()
+                    .await
+                    .and_then(|response| response.error_for_status());
+                match result {
+                    Ok(_) => break,
+                    // Client errors will not go away by retrying
+                    Err(e) if e.status().is_some_and(|status| status.is_client_error()) => {
+                        warn!("Webhook {} rejected the event: {}", delivery.url, e);
+                        break;
+                    }
+                    Err(e) if attempt == WEBHOOK_ATTEMPTS => {
+                        warn!("Webhook {} failed, dropping the event: {}", delivery.url, e);
+                    }
+                    Err(_) => tokio::time::sleep(Duration::from_secs(attempt as u64)).await,
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    /// Body of the next request to `listener`, answered with `status`
+    fn receive(listener: &TcpListener, status: &str) -> Value {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line.trim().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    length = value.trim().parse().unwrap();
+                }
+            }
+        }
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).unwrap();
+        write!(
+            reader.get_mut(),
+            "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            status
+        )
+        .unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[test]
+    fn events_are_posted_in_order() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let webhooks = Webhooks::new(
+            vec![
+                Webhook::checked(url.clone(), Vec::new(), WebhookFormat::Json).unwrap(),
+                Webhook::checked(url, vec!["bigPot".to_string()], WebhookFormat::Slack).unwrap(),
+            ],
+            None,
+            Locale::En,
+            "main",
+            "session",
+        );
+
+        webhooks.send(WebhookEvent::PlayerJoined, json!({"seat": 2}), "joined");
+        webhooks.send(WebhookEvent::BigPot, json!({"pot": 400.0}), "big pot");
+
+        let joined = receive(&listener, "204 No Content");
+        assert_eq!(joined["event"], "playerJoined");
+        assert_eq!(joined["tableId"], "main");
+        assert_eq!(joined["schemaVersion"], SCHEMA_VERSION);
+        assert_eq!(joined["data"]["seat"], 2);
+        // A server error is retried
+        assert_eq!(
+            receive(&listener, "503 Service Unavailable")["event"],
+            "bigPot"
+        );
+        assert_eq!(receive(&listener, "200 OK")["data"]["pot"], 400.0);
+        assert_eq!(receive(&listener, "200 OK"), json!({"text": "big pot"}));
+    }
+
+    #[test]
+    fn webhooks_parse() {
+        let webhooks =
+            Webhook::parse("https://example.com/a, discord=https://discord.com/api/webhooks/1")
+                .unwrap();
+        assert_eq!(webhooks[0].format, WebhookFormat::Json);
+        assert_eq!(webhooks[1].format, WebhookFormat::Discord);
+        assert_eq!(webhooks[1].url, "https://discord.com/api/webhooks/1");
+        assert!(Webhook::parse("ftp://example.com").is_err());
+        assert!(Webhook::checked(
+            "https://example.com".to_string(),
+            vec!["handEnded".to_string()],
+            WebhookFormat::Json
+        )
+        .is_err());
+    }
+}
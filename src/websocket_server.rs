@@ -3,12 +3,28 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::sync::Arc;
-use tokio::sync::{broadcast, RwLock};
-use tokio_tungstenite::{accept_async, tungstenite::Message};
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, watch, RwLock};
 use tracing::{error, info, warn};
+use tungstenite::Message;
 use uuid::Uuid;
 
+use crate::chip_format::ChipStack;
+use crate::events::TableEvent;
 use crate::game_server::{GameConfig, GameServer, PlayerAction};
+use crate::messages::ClientError;
+use crate::player_stats::AllInResults;
+use crate::protocol::CardInfo;
+use crate::runtime::{self, accept_async, AsyncReadExt, AsyncWriteExt, TcpListener, TcpStream};
+
+/// How long a download stays available, see `WebSocketServer::add_download`
+const DOWNLOAD_TTL: Duration = Duration::from_secs(600);
+/// Path of the downloads, served over plain HTTP on the WebSocket port
+const DOWNLOAD_PREFIX: &[u8] = b"GET /downloads/";
+/// Path of the admin API, served like the downloads when `GameConfig.admin_token` is set
+const ADMIN_PREFIX: &[u8] = b"GET /admin/";
+/// How often the server moves the tournament clock and the action timers on
+pub(crate) const TICK: Duration = Duration::from_millis(500);
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -21,6 +37,18 @@ pub struct WebSocketMessage {
 #[serde(rename_all = "camelCase")]
 pub struct RegisterPlayerMessage {
     pub name: String,
+    /// Token of a `registered` message, to take a seat back from a new connection
+    #[serde(default)]
+    pub reconnect_token: Option<String>,
+}
+
+/// Sent to a player once they registered. The token proves who they are when they come
+/// back on a new connection.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RegisteredMessage {
+    pub name: String,
+    pub reconnect_token: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -36,17 +64,205 @@ pub struct PlayerActionMessage {
     pub amount: Option<f64>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HandStrengthHintsMessage {
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StraddleMessage {
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InsuranceMessage {
+    pub premium: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerStatsRequestMessage {
+    pub name: Option<String>,
+}
+
+/// What a client can handle, declared with a `capabilities` message when it connects
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClientCapabilities {
+    /// Sent `gameStateDelta` messages instead of the full game state, see
+    /// `GameStateMessage::delta`
+    #[serde(default)]
+    pub low_bandwidth: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GameStateMessage {
     pub game_started: bool,
+    /// `None` until the first hand is dealt
+    pub hand_id: Option<String>,
+    pub table_id: String,
+    pub session_id: String,
+    pub players: HashMap<String, PlayerInfo>,
+    pub community_cards: Vec<CardInfo>,
+    /// Only sent for double-board bomb pots
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub second_board: Vec<CardInfo>,
+    pub pot: f64,
+    /// `pot` written with `GameConfig.chip_format`, only sent when it is set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pot_text: Option<String>,
+    pub table: TableLayoutInfo,
+    /// Only sent at tournament tables
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tournament_clock: Option<TournamentClockMessage>,
+}
+
+impl GameStateMessage {
+    /// What changed since `previous`: the seats whose player changed, without the cards of
+    /// those whose cards did not, and the seats emptied
+    pub fn delta(&self, previous: &GameStateMessage) -> GameStateDeltaMessage {
+        let players = self
+            .players
+            .iter()
+            .filter_map(|(seat, player)| match previous.players.get(seat) {
+                Some(before) if before == player => None,
+                Some(before) if before.cards == player.cards => Some((
+                    seat.clone(),
+                    PlayerInfo {
+                        cards: None,
+                        ..player.clone()
+                    },
+                )),
+                _ => Some((seat.clone(), player.clone())),
+            })
+            .collect();
+        let mut left_seats: Vec<String> = previous
+            .players
+            .keys()
+            .filter(|seat| !self.players.contains_key(*seat))
+            .cloned()
+            .collect();
+        left_seats.sort();
+
+        GameStateDeltaMessage {
+            game_started: self.game_started,
+            hand_id: self.hand_id.clone(),
+            players,
+            left_seats,
+            community_cards: self.community_cards.clone(),
+            second_board: self.second_board.clone(),
+            pot: self.pot,
+            pot_text: self.pot_text.clone(),
+            table: (self.table != previous.table).then(|| self.table.clone()),
+            tournament_clock: self.tournament_clock.clone(),
+        }
+    }
+}
+
+/// Game state sent to low-bandwidth clients after the first full one. The table and session
+/// ids never change and are left out.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GameStateDeltaMessage {
+    pub game_started: bool,
+    pub hand_id: Option<String>,
+    /// Only the seats whose player changed
     pub players: HashMap<String, PlayerInfo>,
+    /// Seats emptied since the last state
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub left_seats: Vec<String>,
     pub community_cards: Vec<CardInfo>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub second_board: Vec<CardInfo>,
     pub pot: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pot_text: Option<String>,
+    /// Only sent when it changed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub table: Option<TableLayoutInfo>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tournament_clock: Option<TournamentClockMessage>,
+}
+
+/// Where the seats are around the table, so clients can lay out any table size
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TableLayoutInfo {
+    pub max_seats: u8,
+    /// Every seat number clockwise, the way the button and the action move
+    pub seat_order: Vec<u8>,
+    /// Button of the hand being played, `None` between hands
+    pub dealer_seat: Option<u8>,
+    /// Seats that posted the blinds of the hand, `None` between hands and in hands without
+    /// blinds like bomb pots
+    pub small_blind_seat: Option<u8>,
+    pub big_blind_seat: Option<u8>,
+}
+
+/// Where a tournament table stands in its blind structure
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TournamentClockMessage {
+    /// The level being played, or the level before the break
+    pub level: BlindLevelInfo,
+    /// `None` on the last level
+    pub next_level: Option<BlindLevelInfo>,
+    pub on_break: bool,
+    pub paused: bool,
+    /// The clock starts with the first hand
+    pub started: bool,
+    /// Seconds left in the level or the break, `None` on the last level
+    pub seconds_remaining: Option<f64>,
+    /// Seconds until the next break, `None` during a break or when no break is left
+    pub next_break_in: Option<f64>,
+    /// New players can take a seat, see `GameConfig.late_registration_levels`
+    pub registration_open: bool,
+    /// Buy-ins of the tournament, re-entries included
+    pub entries: u32,
+    /// `entries` times `GameConfig.buy_in`
+    pub prize_pool: f64,
+    /// What the prize pool pays each place, from the winner down
+    pub payouts: Vec<f64>,
+}
+
+/// A player busted at a knockout tournament table and the winners of the pot collected
+/// their bounty
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KnockoutMessage {
+    pub seat: u8,
+    pub name: String,
+    /// The bounty on the busted player's head, split between the winners
+    pub bounty: f64,
+    pub winners: Vec<BountyInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BountyInfo {
+    pub name: String,
+    /// Half of the winner's share, paid to them
+    pub won: f64,
+    /// The winner's own bounty, grown by the other half
+    pub bounty: f64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+pub struct BlindLevelInfo {
+    /// Counted from 1
+    pub level: usize,
+    pub small_blind: f64,
+    pub big_blind: f64,
+    pub ante: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct PlayerInfo {
     pub name: String,
     pub address: String,
@@ -56,14 +272,26 @@ pub struct PlayerInfo {
     pub on_move: bool,
     pub folded: bool,
     pub session_net_win_loss: f64,
-    pub cards: Vec<CardInfo>,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct CardInfo {
-    pub suit: u8,
-    pub rank: u8,
+    /// Always sent in `gameState`, left out of `gameStateDelta` when unchanged
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cards: Option<Vec<CardInfo>>,
+    /// Joined mid-session and still has to post or wait for the big blind
+    pub owes_blind: bool,
+    /// Asked to straddle the next hand
+    pub straddle: bool,
+    /// Played by the server, see `GameServer::seat_bot`
+    pub bot: bool,
+    /// `chips` and `bet` written with `GameConfig.chip_format`, only sent when it is set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chips_text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bet_text: Option<String>,
+    /// Bounty on the player's head and the bounties paid to them, only sent at knockout
+    /// tables, see `GameConfig.bounty`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bounty: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bounties_won: Option<f64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -82,23 +310,103 @@ pub struct OnMoveMessage {
     pub can_check: bool,
     pub call_amount: f64,
     pub min_bet_to_total_value: f64,
+    /// Whether the player can bet or raise, the bounds are 0 otherwise
+    pub can_raise: bool,
     pub min_raise_to_total_bet: f64,
+    /// Capped at what the deepest opponent still in can call
+    pub max_raise_to_total_bet: f64,
     pub pot_size: f64,
 }
 
+/// A betting round closed: the bets are swept into the pot, sent once per street
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActionClosedMessage {
+    pub hand_id: String,
+    pub stage: String,
+    /// Chips each seat put in on the street, by seat number. Uncalled bets are included and
+    /// go back to their owner when the hand is settled.
+    pub bets: HashMap<String, f64>,
+    pub street_total: f64,
+    pub pot: f64,
+}
+
+/// A pot and the seats still able to win it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PotInfo {
+    pub amount: f64,
+    pub eligible_seats: Vec<u8>,
+    /// `amount` written with `GameConfig.chip_format`, only sent when it is set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub amount_text: Option<String>,
+}
+
+/// The pot as it stands after an action, sent after every action of the hand
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PotUpdateMessage {
-    pub main_pot: f64,
-    pub side_pots: Vec<f64>,
+    pub hand_id: String,
+    /// Chips swept in that every player still in can win
+    pub main_pot: PotInfo,
+    /// Pots the short all-in players cannot win, in the order they were made
+    pub side_pots: Vec<PotInfo>,
+    /// Chips in front of each seat on the current street, by seat number
     pub player_bets: HashMap<String, f64>,
+    /// Pots and bets together
+    pub total: f64,
+    /// `total` written with `GameConfig.chip_format`, only sent when it is set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_text: Option<String>,
+    /// The chips each bet of `player_bets` is stacked with, only sent with a
+    /// `GameConfig.chip_format`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bet_stacks: Option<HashMap<String, Vec<ChipStack>>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct HandWinningsMessage {
+    pub hand_id: String,
+    pub table_id: String,
+    pub session_id: String,
     pub community_cards: Vec<CardInfo>,
     pub winnings: Vec<WinningInfo>,
+    /// Empty unless the board was run out after an all-in
+    pub all_in_equities: Vec<AllInEquityInfo>,
+    /// Taken from the winners, already left out of `winnings`
+    pub rake: f64,
+    pub jackpot_drop: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bad_beat: Option<BadBeatInfo>,
+}
+
+/// A losing hand that hit the bad beat jackpot
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BadBeatInfo {
+    pub jackpot: String,
+    /// Chips dropped into the jackpot so far
+    pub pool: f64,
+    pub losing_seat: u8,
+    pub losing_hand: String,
+    pub winning_seat: u8,
+    pub winning_hand: String,
+}
+
+/// Equities of the seats still in when `stage` was dealt during an all-in runout
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AllInEquityInfo {
+    pub stage: String,
+    pub equities: Vec<SeatEquity>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SeatEquity {
+    pub seat: u8,
+    pub equity: f64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -110,16 +418,147 @@ pub struct WinningInfo {
     pub pot_description: String,
     pub hand_description: String,
     pub hole_cards: Vec<CardInfo>,
+    /// The five cards of the hand shown down, as indices into the hole cards followed by the
+    /// community cards. Empty when everybody else folded.
+    #[serde(default)]
+    pub winning_cards: Vec<u8>,
+    /// Set when the player split a pot with hands of the same rank
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub split: Option<SplitInfo>,
+    /// `amount_won` written with `GameConfig.chip_format`, and the chips pushed to the
+    /// winner, only sent when it is set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub amount_won_text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub won_stacks: Option<Vec<ChipStack>>,
+}
+
+/// A pot split between the players with the best hand
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SplitInfo {
+    /// Every player who split the pot
+    pub seats: Vec<u8>,
+    /// Nobody beat the board. Otherwise the players made the same five-card hand with their
+    /// hole cards.
+    pub board_plays: bool,
+    /// e.g. "Both play the board" or "Split with A-K kickers"
+    pub description: String,
+}
+
+/// A rejected request. `code` does not depend on the server locale.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ErrorMessage {
+    pub code: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HandStrengthMessage {
+    pub seat: u8,
+    pub stage: String,
+    pub hand_category: String,
+    pub outs: u32,
+    pub equity: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerStatsMessage {
+    pub name: String,
+    pub hands_played: u64,
+    pub net_winnings: f64,
+    pub biggest_pot: f64,
+    pub last_hand_id: Option<String>,
+    pub all_in: AllInResults,
+    pub luck_adjusted_winnings: f64,
+    pub session_id: Option<String>,
+    pub session_all_in: AllInResults,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InsuranceOfferMessage {
+    pub seat: u8,
+    pub stage: String,
+    pub equity: f64,
+    pub scoop_probability: f64,
+    pub outs: u32,
+    pub payout_ratio: f64,
+    pub max_premium: f64,
+}
+
+/// Where to fetch the ZIP file of hand histories asked for with `exportHands`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HandsExportMessage {
+    /// HTTP path on the host and port of the WebSocket server
+    pub path: String,
+    pub hands: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InsuranceResultMessage {
+    pub seat: u8,
+    pub premium: f64,
+    pub payout: f64,
 }
 
 pub type ClientId = String;
 pub type ClientSender = tokio::sync::mpsc::UnboundedSender<Message>;
+/// Files to download by token, with when they were added
+type Downloads = Arc<RwLock<HashMap<String, (Instant, Vec<u8>)>>>;
+
+/// A message for every client. The game state is encoded by each connection, as
+/// low-bandwidth clients only get what changed since their last one.
+#[derive(Debug, Clone)]
+pub(crate) enum Broadcast {
+    Text(String),
+    GameState {
+        state: Arc<GameStateMessage>,
+        /// The full `gameState` message
+        json: String,
+    },
+}
+
+/// A client connected without a socket, see `WebSocketServer::local_client`
+pub(crate) struct LocalClient {
+    pub(crate) capabilities: watch::Sender<ClientCapabilities>,
+    direct: tokio::sync::mpsc::UnboundedReceiver<Message>,
+    broadcasts: broadcast::Receiver<Broadcast>,
+    last_state: Option<Arc<GameStateMessage>>,
+}
+
+impl LocalClient {
+    /// The messages sent since last asked, encoded as for a WebSocket
+    pub(crate) fn drain(&mut self) -> Vec<String> {
+        let mut sent = Vec::new();
+        while let Ok(message) = self.direct.try_recv() {
+            if let Message::Text(text) = message {
+                sent.push(text);
+            }
+        }
+        while let Ok(broadcast) = self.broadcasts.try_recv() {
+            let low_bandwidth = self.capabilities.borrow().low_bandwidth;
+            sent.push(broadcast_text(
+                broadcast,
+                low_bandwidth,
+                &mut self.last_state,
+            ));
+        }
+        sent
+    }
+}
 
 #[derive(Clone)]
 pub struct WebSocketServer {
     clients: Arc<RwLock<HashMap<ClientId, ClientSender>>>,
     game_server: Arc<RwLock<GameServer>>,
-    broadcast_sender: broadcast::Sender<String>,
+    broadcast_sender: broadcast::Sender<Broadcast>,
+    downloads: Downloads,
 }
 
 impl WebSocketServer {
@@ -131,6 +570,7 @@ impl WebSocketServer {
             clients: Arc::new(RwLock::new(HashMap::new())),
             game_server: Arc::new(RwLock::new(GameServer::new(None))),
             broadcast_sender,
+            downloads: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 
@@ -141,22 +581,50 @@ impl WebSocketServer {
             clients: Arc::new(RwLock::new(HashMap::new())),
             game_server: Arc::new(RwLock::new(GameServer::new(Some(config)))),
             broadcast_sender,
+            downloads: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 
     pub async fn start(&self, addr: SocketAddr) -> Result<(), Box<dyn std::error::Error>> {
-        let listener = tokio::net::TcpListener::bind(addr).await?;
+        let listener = TcpListener::bind(addr).await?;
         info!("WebSocket server listening on: {}", addr);
+        self.serve(listener).await
+    }
+
+    /// Accept connections on a bound listener, e.g. one bound to port 0 by `serve`
+    pub async fn serve(&self, listener: TcpListener) -> Result<(), Box<dyn std::error::Error>> {
+        let events = self.follow_table().await;
+        runtime::spawn(self.clone().forward_events(events));
+
+        // Follow the tournament clock and force the action of players who run out of time or
+        // stay disconnected
+        let game_server = self.game_server.clone();
+        runtime::spawn(async move {
+            loop {
+                runtime::sleep(TICK).await;
+                // Most ticks have nothing to do, and then leave the table to the players
+                if game_server.read().await.tick_due() {
+                    game_server.write().await.tick().await;
+                }
+            }
+        });
 
         while let Ok((stream, peer_addr)) = listener.accept().await {
             let clients = self.clients.clone();
             let game_server = self.game_server.clone();
             let broadcast_sender = self.broadcast_sender.clone();
+            let downloads = self.downloads.clone();
 
-            tokio::spawn(async move {
-                if let Err(e) =
-                    handle_connection(stream, peer_addr, clients, game_server, broadcast_sender)
-                        .await
+            runtime::spawn(async move {
+                if let Err(e) = handle_connection(
+                    stream,
+                    peer_addr,
+                    clients,
+                    game_server,
+                    broadcast_sender,
+                    downloads,
+                )
+                .await
                 {
                     error!("Error handling connection from {}: {}", peer_addr, e);
                 }
@@ -166,16 +634,239 @@ impl WebSocketServer {
         Ok(())
     }
 
+    /// The events of the table, in order, for the clients to follow
+    pub(crate) async fn follow_table(&self) -> tokio::sync::mpsc::UnboundedReceiver<TableEvent> {
+        let (event_sender, events) = tokio::sync::mpsc::unbounded_channel();
+        let mut game_server = self.game_server.write().await;
+        game_server.attach(Arc::downgrade(&self.game_server));
+        game_server.subscribe(Arc::new(move |event| {
+            let _ = event_sender.send(event.clone());
+        }));
+        events
+    }
+
+    /// Send the clients what happens at the table, for as long as the server runs
+    async fn forward_events(self, mut events: tokio::sync::mpsc::UnboundedReceiver<TableEvent>) {
+        while let Some(event) = events.recv().await {
+            self.deliver(event).await;
+        }
+    }
+
+    /// Send the clients concerned the messages of `event`
+    pub(crate) async fn deliver(&self, event: TableEvent) {
+        match event {
+            TableEvent::GameState { message, .. } => self.broadcast_game_state(message).await,
+            TableEvent::OnMove(message) => self.broadcast_on_move(message).await,
+            TableEvent::ActionClosed(message) => self.broadcast_action_closed(message).await,
+            TableEvent::PotUpdate(message) => self.broadcast_pot_update(message).await,
+            TableEvent::TournamentClock(message) => self.broadcast_tournament_clock(message).await,
+            TableEvent::Knockout(message) => self.broadcast_knockout(message).await,
+            TableEvent::HandWinnings(message) => self.broadcast_winnings(message).await,
+            TableEvent::HandStrength { player_id, message } => {
+                self.send_hand_strength(&player_id, message).await
+            }
+            TableEvent::InsuranceOffer { player_id, message } => {
+                self.send_insurance_offer(&player_id, message).await
+            }
+            TableEvent::InsuranceResult { player_id, message } => {
+                self.send_insurance_result(&player_id, message).await
+            }
+            TableEvent::PlayerStats { player_id, message } => {
+                self.send_player_stats(&player_id, message).await
+            }
+            TableEvent::Registered { player_id, message } => {
+                self.send_registered(&player_id, message).await
+            }
+            TableEvent::HandsExport {
+                player_id,
+                zip,
+                hands,
+            } => {
+                let path = self.add_download(zip.to_vec()).await;
+                self.send_hands_export(&player_id, HandsExportMessage { path, hands })
+                    .await;
+            }
+            TableEvent::Error { player_id, message } => self.send_error(&player_id, message).await,
+            TableEvent::PlayerJoined { .. }
+            | TableEvent::PlayerLeft { .. }
+            | TableEvent::HandComplete(_) => {}
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn game_server(&self) -> Arc<RwLock<GameServer>> {
+        self.game_server.clone()
+    }
+
+    /// Connect a client without a socket, e.g. for a replay
+    pub(crate) async fn local_client(&self, client_id: &str) -> LocalClient {
+        let (sender, direct) = tokio::sync::mpsc::unbounded_channel();
+        self.clients
+            .write()
+            .await
+            .insert(client_id.to_owned(), sender);
+        LocalClient {
+            capabilities: watch::channel(ClientCapabilities::default()).0,
+            direct,
+            broadcasts: self.broadcast_sender.subscribe(),
+            last_state: None,
+        }
+    }
+
+    /// Stop sending to a client connected by `local_client`
+    pub(crate) async fn forget_client(&self, client_id: &str) {
+        self.clients.write().await.remove(client_id);
+    }
+
     pub async fn broadcast_message(&self, message: &str) {
-        if let Err(e) = self.broadcast_sender.send(message.to_string()) {
+        if let Err(e) = self
+            .broadcast_sender
+            .send(Broadcast::Text(message.to_string()))
+        {
             warn!("Failed to broadcast message: {}", e);
         }
     }
 
+    /// Send a message to a single client only
+    pub async fn send_to_client(&self, client_id: &str, message: &str) {
+        let clients = self.clients.read().await;
+        match clients.get(client_id) {
+            Some(sender) => {
+                if let Err(e) = sender.send(Message::Text(message.to_string())) {
+                    warn!("Failed to send message to {}: {}", client_id, e);
+                }
+            }
+            None => warn!("Unknown client {}", client_id),
+        }
+    }
+
+    pub async fn send_hand_strength(&self, client_id: &str, hand_strength: HandStrengthMessage) {
+        let message = WebSocketMessage {
+            message_type: "handStrength".to_string(),
+            data: serde_json::to_value(hand_strength).unwrap_or_default(),
+        };
+
+        if let Ok(json) = serde_json::to_string(&message) {
+            self.send_to_client(client_id, &json).await;
+        }
+    }
+
+    pub async fn send_insurance_offer(&self, client_id: &str, offer: InsuranceOfferMessage) {
+        let message = WebSocketMessage {
+            message_type: "insuranceOffer".to_string(),
+            data: serde_json::to_value(offer).unwrap_or_default(),
+        };
+
+        if let Ok(json) = serde_json::to_string(&message) {
+            self.send_to_client(client_id, &json).await;
+        }
+    }
+
+    /// Serve `file` over HTTP for `DOWNLOAD_TTL` and return its path. The file is dropped as
+    /// soon as it expires, downloaded or not.
+    pub async fn add_download(&self, file: Vec<u8>) -> String {
+        let token = Uuid::new_v4().simple().to_string();
+        self.downloads
+            .write()
+            .await
+            .insert(token.clone(), (Instant::now(), file));
+
+        let downloads = self.downloads.clone();
+        let expired = token.clone();
+        runtime::spawn(async move {
+            runtime::sleep(DOWNLOAD_TTL).await;
+            downloads.write().await.remove(&expired);
+        });
+        format!("/downloads/{}.zip", token)
+    }
+
+    pub async fn send_hands_export(&self, client_id: &str, export: HandsExportMessage) {
+        let message = WebSocketMessage {
+            message_type: "handsExport".to_string(),
+            data: serde_json::to_value(export).unwrap_or_default(),
+        };
+
+        if let Ok(json) = serde_json::to_string(&message) {
+            self.send_to_client(client_id, &json).await;
+        }
+    }
+
+    pub async fn send_insurance_result(&self, client_id: &str, result: InsuranceResultMessage) {
+        let message = WebSocketMessage {
+            message_type: "insuranceResult".to_string(),
+            data: serde_json::to_value(result).unwrap_or_default(),
+        };
+
+        if let Ok(json) = serde_json::to_string(&message) {
+            self.send_to_client(client_id, &json).await;
+        }
+    }
+
+    pub async fn send_registered(&self, client_id: &str, registered: RegisteredMessage) {
+        let message = WebSocketMessage {
+            message_type: "registered".to_string(),
+            data: serde_json::to_value(registered).unwrap_or_default(),
+        };
+
+        if let Ok(json) = serde_json::to_string(&message) {
+            self.send_to_client(client_id, &json).await;
+        }
+    }
+
+    pub async fn send_player_stats(&self, client_id: &str, stats: PlayerStatsMessage) {
+        let message = WebSocketMessage {
+            message_type: "playerStats".to_string(),
+            data: serde_json::to_value(stats).unwrap_or_default(),
+        };
+
+        if let Ok(json) = serde_json::to_string(&message) {
+            self.send_to_client(client_id, &json).await;
+        }
+    }
+
+    pub async fn send_error(&self, client_id: &str, error: ErrorMessage) {
+        let message = WebSocketMessage {
+            message_type: "error".to_string(),
+            data: serde_json::to_value(error).unwrap_or_default(),
+        };
+
+        if let Ok(json) = serde_json::to_string(&message) {
+            self.send_to_client(client_id, &json).await;
+        }
+    }
+
     pub async fn broadcast_game_state(&self, state: GameStateMessage) {
         let message = WebSocketMessage {
             message_type: "gameState".to_string(),
-            data: serde_json::to_value(state).unwrap_or_default(),
+            data: serde_json::to_value(&state).unwrap_or_default(),
+        };
+
+        if let Ok(json) = serde_json::to_string(&message) {
+            let broadcast = Broadcast::GameState {
+                state: Arc::new(state),
+                json,
+            };
+            if let Err(e) = self.broadcast_sender.send(broadcast) {
+                warn!("Failed to broadcast message: {}", e);
+            }
+        }
+    }
+
+    pub async fn broadcast_tournament_clock(&self, clock: TournamentClockMessage) {
+        let message = WebSocketMessage {
+            message_type: "tournamentClock".to_string(),
+            data: serde_json::to_value(clock).unwrap_or_default(),
+        };
+
+        if let Ok(json) = serde_json::to_string(&message) {
+            self.broadcast_message(&json).await;
+        }
+    }
+
+    pub async fn broadcast_knockout(&self, knockout: KnockoutMessage) {
+        let message = WebSocketMessage {
+            message_type: "knockout".to_string(),
+            data: serde_json::to_value(knockout).unwrap_or_default(),
         };
 
         if let Ok(json) = serde_json::to_string(&message) {
@@ -194,7 +885,17 @@ impl WebSocketServer {
         }
     }
 
-    #[allow(dead_code)]
+    pub async fn broadcast_action_closed(&self, action_closed: ActionClosedMessage) {
+        let message = WebSocketMessage {
+            message_type: "actionClosed".to_string(),
+            data: serde_json::to_value(action_closed).unwrap_or_default(),
+        };
+
+        if let Ok(json) = serde_json::to_string(&message) {
+            self.broadcast_message(&json).await;
+        }
+    }
+
     pub async fn broadcast_pot_update(&self, pot_update: PotUpdateMessage) {
         let message = WebSocketMessage {
             message_type: "potUpdate".to_string(),
@@ -218,13 +919,256 @@ impl WebSocketServer {
     }
 }
 
+/// A plain HTTP request on the WebSocket port
+enum HttpRequest {
+    /// `GET /downloads/<token>.zip`
+    Download(String),
+    /// `GET /admin/<path>`
+    Admin(String),
+}
+
+/// The plain HTTP request a connection opens with, if any. Only peeks at the request line, so
+/// a WebSocket handshake is left for `accept_async`.
+async fn http_request(stream: &TcpStream) -> Option<HttpRequest> {
+    let mut buf = [0u8; 256];
+    // The request line comes in the first packet, give a slow client a second at most
+    for _ in 0..100 {
+        let n = stream.peek(&mut buf).await.ok()?;
+        let head = &buf[..n];
+        let prefix = [DOWNLOAD_PREFIX, ADMIN_PREFIX]
+            .into_iter()
+            .find(|prefix| head.starts_with(prefix) || prefix.starts_with(head));
+        let prefix = match prefix {
+            Some(prefix) if n > 0 => prefix,
+            _ => return None,
+        };
+        if let Some(end) = head.iter().position(|&b| b == b'\n') {
+            let line = std::str::from_utf8(&head[prefix.len()..end]).ok()?;
+            let path = line.split(' ').next()?;
+            return match prefix == DOWNLOAD_PREFIX {
+                true => path
+                    .strip_suffix(".zip")
+                    .map(|token| HttpRequest::Download(token.to_string())),
+                false => Some(HttpRequest::Admin(path.to_string())),
+            };
+        }
+        if n == buf.len() {
+            return None;
+        }
+        runtime::sleep(Duration::from_millis(10)).await;
+    }
+    None
+}
+
+/// Read a request up to the blank line ending its headers
+async fn read_head(stream: &mut TcpStream) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut head = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !head.ends_with(b"\r\n\r\n") && head.len() < 16 * 1024 {
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        head.extend_from_slice(&buf[..n]);
+    }
+    Ok(head)
+}
+
+/// Answer a download request with the file, or 404 once it has expired
+async fn serve_download(
+    mut stream: TcpStream,
+    token: &str,
+    downloads: &Downloads,
+) -> Result<(), Box<dyn std::error::Error>> {
+    read_head(&mut stream).await?;
+    let file = downloads
+        .read()
+        .await
+        .get(token)
+        .filter(|(added, _)| added.elapsed() < DOWNLOAD_TTL)
+        .map(|(_, file)| file.clone());
+    match file {
+        Some(file) => {
+            let header = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/zip\r\nContent-Length: {}\r\n\
+                 Content-Disposition: attachment; filename=\"hands.zip\"\r\nConnection: close\r\n\r\n",
+                file.len()
+            );
+            stream.write_all(header.as_bytes()).await?;
+            stream.write_all(&file).await?;
+        }
+        None => {
+            stream
+                .write_all(
+                    b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                )
+                .await?;
+        }
+    }
+    runtime::shutdown(&mut stream).await?;
+    Ok(())
+}
+
+/// Answer to an admin API request: a JSON body, or the ZIP file of a hand export
+pub(crate) struct AdminResponse {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: Vec<u8>,
+}
+
+impl AdminResponse {
+    fn json(status: u16, body: impl Serialize) -> Self {
+        AdminResponse {
+            status,
+            content_type: "application/json",
+            body: serde_json::to_vec(&body).unwrap_or_default(),
+        }
+    }
+
+    fn error(status: u16, error: &str) -> Self {
+        Self::json(status, serde_json::json!({ "error": error }))
+    }
+}
+
+/// Answer to the admin API request for `path`, the part after `/admin/`.
+/// `authorization` is the value of the request's `Authorization` header.
+pub(crate) async fn admin_response(
+    game_server: &RwLock<GameServer>,
+    path: &str,
+    authorization: Option<&str>,
+) -> AdminResponse {
+    let allowed = {
+        let game = game_server.read().await;
+        match game.admin_token() {
+            // Without a token there is no admin API
+            None => {
+                return AdminResponse {
+                    status: 404,
+                    content_type: "application/json",
+                    body: Vec::new(),
+                }
+            }
+            Some(token) => authorization.and_then(|a| a.strip_prefix("Bearer ")) == Some(token),
+        }
+    };
+    if !allowed {
+        return AdminResponse::error(401, "unauthorized");
+    }
+
+    let segments: Vec<&str> = path
+        .split('?')
+        .next()
+        .unwrap_or_default()
+        .split('/')
+        .collect();
+    let (name, resource) = match segments[..] {
+        ["players", name, resource @ ("stats" | "hands")] => (name, resource),
+        _ => return AdminResponse::error(404, "not found"),
+    };
+    let Some(name) = percent_decode(name) else {
+        return AdminResponse::error(400, "invalid name");
+    };
+    if resource == "stats" {
+        let read = game_server.read().await.player_stats_message(&name);
+        return AdminResponse::json(200, read.await);
+    }
+    // The same archive as the download offered for `exportHands`
+    let export = game_server.read().await.export_hands_zip(&name);
+    match export.await {
+        Ok((_, 0)) => AdminResponse::error(404, "no hand histories"),
+        Ok((zip, _)) => AdminResponse {
+            status: 200,
+            content_type: "application/zip",
+            body: zip,
+        },
+        Err(e) => {
+            error!("Failed to export the hands of {}: {}", name, e);
+            AdminResponse::error(500, "export failed")
+        }
+    }
+}
+
+/// `text` with its `%XX` escapes decoded, `None` when they do not make UTF-8
+fn percent_decode(text: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut rest = text.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        match byte {
+            b'%' => {
+                let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+                bytes.push(u8::from_str_radix(hex, 16).ok()?);
+                rest = &tail[2..];
+            }
+            _ => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Answer an admin API request, see `admin_response`
+async fn serve_admin(
+    mut stream: TcpStream,
+    path: &str,
+    game_server: &RwLock<GameServer>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let head = read_head(&mut stream).await?;
+    let head = String::from_utf8_lossy(&head);
+    let authorization = head.lines().skip(1).find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        name.eq_ignore_ascii_case("authorization")
+            .then(|| value.trim())
+    });
+    let response = admin_response(game_server, path, authorization).await;
+    let reason = match response.status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        500 => "Internal Server Error",
+        _ => "Not Found",
+    };
+    let disposition = match response.content_type {
+        "application/zip" => "Content-Disposition: attachment; filename=\"hands.zip\"\r\n",
+        _ => "",
+    };
+    let header = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n{}\
+         Connection: close\r\n\r\n",
+        response.status,
+        reason,
+        response.content_type,
+        response.body.len(),
+        disposition
+    );
+    stream.write_all(header.as_bytes()).await?;
+    stream.write_all(&response.body).await?;
+    runtime::shutdown(&mut stream).await?;
+    Ok(())
+}
+
 async fn handle_connection(
-    stream: tokio::net::TcpStream,
+    stream: TcpStream,
     peer_addr: SocketAddr,
     clients: Arc<RwLock<HashMap<ClientId, ClientSender>>>,
     game_server: Arc<RwLock<GameServer>>,
-    broadcast_sender: broadcast::Sender<String>,
+    broadcast_sender: broadcast::Sender<Broadcast>,
+    downloads: Downloads,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    // Plain HTTP downloads and the admin API share the port with the WebSocket clients
+    match http_request(&stream).await {
+        Some(HttpRequest::Download(token)) => {
+            info!("Download request from {}", peer_addr);
+            return serve_download(stream, &token, &downloads).await;
+        }
+        Some(HttpRequest::Admin(path)) => {
+            info!("Admin request from {}", peer_addr);
+            return serve_admin(stream, &path, &game_server).await;
+        }
+        None => {}
+    }
+
     let client_id = Uuid::new_v4().to_string();
     info!(
         "New WebSocket connection from {} with ID {}",
@@ -243,17 +1187,25 @@ async fn handle_connection(
     }
 
     let mut broadcast_receiver = broadcast_sender.subscribe();
+    let (capabilities_sender, capabilities) = watch::channel(ClientCapabilities::default());
+    let recorder = game_server.read().await.session_recorder(&client_id);
+    let outgoing_recorder = recorder.clone();
 
     // Spawn task to handle outgoing messages
     let client_id_clone = client_id.clone();
     let clients_clone = clients.clone();
-    let outgoing_task = tokio::spawn(async move {
+    let outgoing_task = runtime::spawn(async move {
+        // Last game state sent to a low-bandwidth client, the base of the next delta
+        let mut last_state: Option<Arc<GameStateMessage>> = None;
         loop {
             tokio::select! {
                 // Handle direct messages to this client
                 msg = rx.recv() => {
                     match msg {
                         Some(message) => {
+                            if let (Some(recorder), Message::Text(text)) = (&outgoing_recorder, &message) {
+                                recorder.sent(text);
+                            }
                             if ws_sender.send(message).await.is_err() {
                                 break;
                             }
@@ -263,13 +1215,18 @@ async fn handle_connection(
                 }
                 // Handle broadcast messages
                 broadcast_msg = broadcast_receiver.recv() => {
-                    match broadcast_msg {
-                        Ok(msg) => {
-                            if ws_sender.send(Message::Text(msg)).await.is_err() {
-                                break;
-                            }
+                    let text = match broadcast_msg {
+                        Ok(broadcast) => {
+                            let low_bandwidth = capabilities.borrow().low_bandwidth;
+                            broadcast_text(broadcast, low_bandwidth, &mut last_state)
                         }
                         Err(_) => break,
+                    };
+                    if let Some(ref recorder) = outgoing_recorder {
+                        recorder.sent(&text);
+                    }
+                    if ws_sender.send(Message::Text(text)).await.is_err() {
+                        break;
                     }
                 }
             }
@@ -285,7 +1242,12 @@ async fn handle_connection(
     while let Some(msg) = ws_receiver.next().await {
         match msg {
             Ok(Message::Text(text)) => {
-                if let Err(e) = handle_message(&text, &client_id, &game_server).await {
+                if let Some(ref recorder) = recorder {
+                    recorder.received(&text);
+                }
+                if let Err(e) =
+                    handle_message(&text, &client_id, &game_server, &capabilities_sender).await
+                {
                     error!("Error handling message from {}: {}", client_id, e);
                 }
             }
@@ -301,6 +1263,10 @@ async fn handle_connection(
         }
     }
 
+    if let Some(ref recorder) = recorder {
+        recorder.closed();
+    }
+
     // Notify game server about player disconnection
     {
         let mut game = game_server.write().await;
@@ -311,10 +1277,39 @@ async fn handle_connection(
     Ok(())
 }
 
-async fn handle_message(
+/// The text of `broadcast` for a client. Low-bandwidth clients get the game state as the
+/// delta from `last_state`, the last one they were sent.
+pub(crate) fn broadcast_text(
+    broadcast: Broadcast,
+    low_bandwidth: bool,
+    last_state: &mut Option<Arc<GameStateMessage>>,
+) -> String {
+    match broadcast {
+        Broadcast::Text(text) => text,
+        Broadcast::GameState { json, .. } if !low_bandwidth => {
+            *last_state = None;
+            json
+        }
+        Broadcast::GameState { state, json } => match last_state.replace(state.clone()) {
+            Some(previous) => game_state_delta_json(&state, &previous),
+            None => json,
+        },
+    }
+}
+
+fn game_state_delta_json(state: &GameStateMessage, previous: &GameStateMessage) -> String {
+    let message = WebSocketMessage {
+        message_type: "gameStateDelta".to_string(),
+        data: serde_json::to_value(state.delta(previous)).unwrap_or_default(),
+    };
+    serde_json::to_string(&message).unwrap_or_default()
+}
+
+pub(crate) async fn handle_message(
     text: &str,
     client_id: &str,
     game_server: &Arc<RwLock<GameServer>>,
+    capabilities: &watch::Sender<ClientCapabilities>,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let message: WebSocketMessage = serde_json::from_str(text)?;
 
@@ -323,17 +1318,66 @@ async fn handle_message(
         client_id, message.message_type
     );
 
+    // The capabilities only concern this connection, the table is not involved
+    if message.message_type == "capabilities" {
+        let declared: ClientCapabilities = serde_json::from_value(message.data)?;
+        capabilities.send_replace(declared);
+        return Ok(());
+    }
+
     let mut game = game_server.write().await;
+    // Rejected requests are reported back to the client, anything else is only logged
+    let client_error = match dispatch_message(message, client_id, &mut game).await {
+        Ok(()) => return Ok(()),
+        Err(e) => e.downcast::<ClientError>()?,
+    };
+    game.send_error(client_id, &client_error);
+    Err(client_error)
+}
 
+async fn dispatch_message(
+    message: WebSocketMessage,
+    client_id: &str,
+    game: &mut GameServer,
+) -> Result<(), Box<dyn std::error::Error>> {
     match message.message_type.as_str() {
         "registerPlayer" => {
             let register_msg: RegisterPlayerMessage = serde_json::from_value(message.data)?;
-            game.register_player(&register_msg.name, client_id).await?;
+            game.register_player(
+                &register_msg.name,
+                client_id,
+                register_msg.reconnect_token.as_deref(),
+            )
+            .await?;
         }
         "takeSeat" => {
             let seat_msg: TakeSeatMessage = serde_json::from_value(message.data)?;
             game.seat_player(client_id, seat_msg.seat).await?;
         }
+        "handStrengthHints" => {
+            let hints_msg: HandStrengthHintsMessage = serde_json::from_value(message.data)?;
+            game.set_hand_strength_hints(client_id, hints_msg.enabled)
+                .await?;
+        }
+        "straddle" => {
+            let straddle_msg: StraddleMessage = serde_json::from_value(message.data)?;
+            game.set_straddle(client_id, straddle_msg.enabled).await?;
+        }
+        "insurance" => {
+            let insurance_msg: InsuranceMessage = serde_json::from_value(message.data)?;
+            game.resolve_insurance(client_id, insurance_msg.premium)
+                .await?;
+        }
+        "exportHands" => {
+            game.export_hands(client_id).await?;
+        }
+        "playerStats" => {
+            // The request data is optional: no data asks for the player's own stats
+            let stats_msg: PlayerStatsRequestMessage = serde_json::from_value(message.data)
+                .unwrap_or(PlayerStatsRequestMessage { name: None });
+            game.send_player_stats(client_id, stats_msg.name.as_deref())
+                .await?;
+        }
         "startGame" => {
             game.start_game().await?;
         }
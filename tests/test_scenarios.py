@@ -0,0 +1,83 @@
+import pokers as pkrs
+import json
+import glob
+
+try:
+    import tomllib  # Python 3.11+
+except ImportError:
+    tomllib = None
+
+EPSILON = 1e-9
+
+
+def check(name, step, state, expect):
+    ctx = f"scenario '{name}', {step}"
+    if "current_player" in expect:
+        assert state.current_player == expect["current_player"], ctx
+    if "stage" in expect:
+        assert state.stage == pkrs.Stage.__dict__[expect["stage"]], ctx
+    if "pot" in expect:
+        assert abs(state.pot - expect["pot"]) < EPSILON, ctx
+    if "min_bet" in expect:
+        assert abs(state.min_bet - expect["min_bet"]) < EPSILON, ctx
+    if "legal_actions" in expect:
+        expected = [pkrs.ActionEnum.__dict__[a] for a in expect["legal_actions"]]
+        assert state.legal_actions == expected, ctx
+    if "bet_chips" in expect:
+        assert [ps.bet_chips for ps in state.players_state] == expect["bet_chips"], ctx
+    if "final_state" in expect:
+        assert state.final_state == expect["final_state"], ctx
+    if "rewards" in expect:
+        rewards = [ps.reward for ps in state.players_state]
+        assert len(rewards) == len(expect["rewards"]), ctx
+        for reward, expected in zip(rewards, expect["rewards"]):
+            assert abs(reward - expected) < EPSILON, ctx
+    if "winners" in expect:
+        winners = [ps.player for ps in state.players_state if ps.reward > 0.0]
+        assert winners == expect["winners"], ctx
+
+
+def load(path):
+    if path.endswith(".toml"):
+        with open(path, "rb") as f:
+            return tomllib.load(f)
+    with open(path) as f:
+        return json.load(f)
+
+
+def test_golden_scenarios():
+    files = sorted(glob.glob("tests/test_files/scenarios/*.json"))
+    assert files
+    # TOML scenarios need tomllib, from Python 3.11 on
+    if tomllib is not None:
+        files += sorted(glob.glob("tests/test_files/scenarios/*.toml"))
+
+    for path in files:
+        scenario = load(path)
+
+        name = scenario["name"]
+        deck = [pkrs.Card.from_string(c) for c in scenario["deck"]]
+        state = pkrs.State.from_deck(
+            n_players=scenario["n_players"],
+            button=scenario["button"],
+            sb=scenario["sb"],
+            bb=scenario["bb"],
+            stake=scenario["stake"],
+            deck=deck,
+        )
+        if "stacks" in scenario:
+            state = state.with_stacks(scenario["stacks"])
+        # The rules left out keep their defaults
+        rules = dict(scenario.get("rules", {}))
+        if "variant" in rules:
+            rules["variant"] = pkrs.GameVariant.__dict__[rules["variant"]]
+        state = state.with_rules(pkrs.TableRules(**rules))
+        if scenario.get("ante", 0.0) > 0.0:
+            state = state.post_antes(scenario["ante"])
+        check(name, "initial state", state, scenario.get("initial", {}))
+
+        for i, step in enumerate(scenario["steps"]):
+            assert state.current_player == step["player"], f"scenario '{name}', step {i}"
+            action = pkrs.Action(pkrs.ActionEnum.__dict__[step["action"]], step.get("amount", 0.0))
+            state = state.apply_action(action)
+            check(name, f"step {i}", state, step.get("expect", {}))